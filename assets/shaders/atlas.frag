@@ -0,0 +1,41 @@
+#version 450
+
+layout(location = 0) in vec2 fragUV;
+layout(location = 1) in vec4 fragColor;
+layout(location = 2) flat in float fragDistanceRange;
+
+layout(binding = 2) uniform sampler2D texSampler;
+
+layout(location = 0) out vec4 outColor;
+
+float median(vec3 v) {
+    return max(min(v.r, v.g), min(max(v.r, v.g), v.b));
+}
+
+// The sampler filters with the nearest texel, distances have to be interpolated
+vec3 sampleBilinear(vec2 uv) {
+    ivec2 size = textureSize(texSampler, 0);
+    vec2 p = uv * vec2(size) - 0.5;
+    ivec2 i = ivec2(floor(p));
+    vec2 f = p - vec2(i);
+    ivec2 hi = size - 1;
+    vec3 a = texelFetch(texSampler, clamp(i, ivec2(0), hi), 0).rgb;
+    vec3 b = texelFetch(texSampler, clamp(i + ivec2(1, 0), ivec2(0), hi), 0).rgb;
+    vec3 c = texelFetch(texSampler, clamp(i + ivec2(0, 1), ivec2(0), hi), 0).rgb;
+    vec3 d = texelFetch(texSampler, clamp(i + ivec2(1, 1), ivec2(0), hi), 0).rgb;
+    return mix(mix(a, b, f.x), mix(c, d, f.x), f.y);
+}
+
+void main() {
+    if (fragDistanceRange > 0.0) {
+        // Signed distance field, see glyph_sdf.rs
+        float distance = median(sampleBilinear(fragUV)) - 0.5;
+        float coverage = clamp(distance * fragDistanceRange + 0.5, 0.0, 1.0);
+        outColor = vec4(fragColor.rgb, fragColor.a * coverage);
+    } else {
+        outColor = texture(texSampler, fragUV) * fragColor;
+    }
+    if (outColor.a == 0.0) {
+        discard;
+    }
+}
@@ -0,0 +1,38 @@
+#version 450
+
+// Sprites sampled from a region of a shared atlas texture, see VkContext::render_sprite_uv
+// and VkContext::render_sprite_sdf
+
+layout(location = 0) in vec2 inPos; // Quad corners in [-1, 1]
+
+layout(binding = 0) uniform UniformBufferObject {
+    uvec2 window;
+} ubo;
+
+layout(push_constant) uniform PushConstants {
+    vec2 offset; // Top-left corner in pixels
+    vec2 size;
+    float z;
+    float r, g, b, a;
+    uint rotation_id; // Multiples of 90 degrees
+    float u0, v0, u1, v1;
+    float distance_range; // Pixels spanned by a distance field texture, 0 for plain textures
+} pc;
+
+layout(location = 0) out vec2 fragUV;
+layout(location = 1) out vec4 fragColor;
+layout(location = 2) flat out float fragDistanceRange;
+
+void main() {
+    vec2 t = inPos * 0.5 + 0.5;
+    vec2 pos = pc.offset + t * pc.size;
+    gl_Position = vec4(pos / vec2(ubo.window) * 2.0 - 1.0, pc.z, 1.0);
+
+    vec2 local = inPos;
+    for (uint i = 0; i < pc.rotation_id % 4; i++) {
+        local = vec2(-local.y, local.x);
+    }
+    fragUV = mix(vec2(pc.u0, pc.v0), vec2(pc.u1, pc.v1), local * 0.5 + 0.5);
+    fragColor = vec4(pc.r, pc.g, pc.b, pc.a);
+    fragDistanceRange = pc.distance_range;
+}
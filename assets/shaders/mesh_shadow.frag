@@ -0,0 +1,60 @@
+#version 450
+
+#define MAX_CASCADES 4
+
+layout(location = 0) in vec3 fragWorldPos;
+layout(location = 1) in vec3 fragNormal;
+layout(location = 2) in float fragViewDepth;
+
+// Must match ShadowUniforms in src/shadow.rs
+layout(binding = 1) uniform ShadowData {
+    mat4 light_matrices[MAX_CASCADES];
+    vec4 cascade_splits;
+    vec4 light_dir; // xyz: direction, w: 0 directional / 1 spot
+    vec4 params;    // x: pcf radius, y: texel size, z: cascade count
+} shadow;
+
+layout(binding = 2) uniform sampler2DArrayShadow shadowMap;
+
+layout(location = 0) out vec4 outColor;
+
+int select_cascade() {
+    int count = int(shadow.params.z);
+    for (int i = 0; i < count - 1; i++) {
+        if (fragViewDepth < shadow.cascade_splits[i]) {
+            return i;
+        }
+    }
+    return count - 1;
+}
+
+float shadow_factor(int layer) {
+    vec4 pos = shadow.light_matrices[layer] * vec4(fragWorldPos, 1.0);
+    pos.xyz /= pos.w;
+    vec2 uv = pos.xy * 0.5 + 0.5;
+    if (pos.z > 1.0) {
+        return 1.0;
+    }
+
+    // Percentage Closer Filtering
+    int radius = int(shadow.params.x);
+    float texel = shadow.params.y;
+    float lit = 0.0;
+    for (int y = -radius; y <= radius; y++) {
+        for (int x = -radius; x <= radius; x++) {
+            lit += texture(shadowMap, vec4(uv + vec2(x, y) * texel, layer, pos.z));
+        }
+    }
+    float taps = float((2 * radius + 1) * (2 * radius + 1));
+    return lit / taps;
+}
+
+void main() {
+    int layer = shadow.light_dir.w == 0.0 ? select_cascade() : 0;
+    vec3 n = normalize(fragNormal);
+    vec3 l = -normalize(shadow.light_dir.xyz);
+    float diffuse = max(dot(n, l), 0.0);
+    float ambient = 0.1;
+    vec3 albedo = vec3(0.8);
+    outColor = vec4(albedo * (ambient + diffuse * shadow_factor(layer)), 1.0);
+}
@@ -0,0 +1,23 @@
+#version 450
+
+layout(location = 0) in vec3 inPos;
+layout(location = 1) in vec3 inNormal;
+
+layout(binding = 0) uniform UniformBufferObject {
+    mat4 model;
+    mat4 view;
+    mat4 proj;
+} ubo;
+
+layout(location = 0) out vec3 fragWorldPos;
+layout(location = 1) out vec3 fragNormal;
+layout(location = 2) out float fragViewDepth;
+
+void main() {
+    vec4 world_pos = ubo.model * vec4(inPos, 1.0);
+    vec4 view_pos = ubo.view * world_pos;
+    fragWorldPos = world_pos.xyz;
+    fragNormal = mat3(ubo.model) * inNormal;
+    fragViewDepth = -view_pos.z;
+    gl_Position = ubo.proj * view_pos;
+}
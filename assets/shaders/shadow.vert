@@ -0,0 +1,14 @@
+#version 450
+
+// Depth-only pass used to render the shadow casters into a layer of the shadow map.
+
+layout(location = 0) in vec3 inPos;
+
+layout(push_constant) uniform PushConstants {
+    mat4 light_matrix;
+    mat4 model;
+} pc;
+
+void main() {
+    gl_Position = pc.light_matrix * pc.model * vec4(inPos, 1.0);
+}
@@ -1,6 +1,5 @@
 use std::ffi::OsStr;
 use std::fmt::Debug;
-use std::os::unix::fs::PermissionsExt;
 use std::path::Path;
 use std::process::Command;
 
@@ -8,25 +7,14 @@ fn main() {
     println!("cargo:rustc-link-search=.");
 
     println!("cargo:rerun-if-changed=build.rs");
-    println!("cargo:rerun-if-changed=compile.sh");
+    println!("cargo:rerun-if-changed=compile_shaders.sh");
     println!("cargo:rerun-if-changed=assets/shaders/");
 
-    // Download stb_image
-    download_if_not_present("stb_image.h", "https://github.com/nothings/stb/raw/master/stb_image.h");
-
-    if !Path::new("glslc").exists() {
-        Command::new("/usr/bin/wget")
-            .arg("https://github.com/AlexViaColl/icarus-deps/raw/main/glslc.tar.gz")
-            .status()
-            .unwrap();
-
-        Command::new("/usr/bin/tar").arg("-xzvf").arg("glslc.tar.gz").status().unwrap();
-        std::fs::set_permissions("glslc", std::fs::Permissions::from_mode(0o770)).unwrap();
+    // Shaders are compiled with a locally installed glslc or glslangValidator, see compile_shaders.sh
+    if !Command::new("/bin/sh").arg("compile_shaders.sh").status().unwrap().success() {
+        println!("cargo:warning=compile_shaders.sh failed, the shaders under assets/shaders are not compiled");
     }
 
-    Command::new("/bin/sh").arg("compile_stb.sh").status().unwrap();
-    Command::new("/bin/sh").arg("compile_shaders.sh").status().unwrap();
-
     // Download flappy bird assets
     let path = "assets/textures/flappy";
     let url = "https://github.com/samuelcust/flappy-bird-assets/raw/master/sprites";
@@ -2,5 +2,17 @@
 
 set -e
 
+# Prefer a glslc next to the script, then the ones installed with the Vulkan SDK or glslang
+if [ -x ./glslc ]; then
+    COMPILE() { ./glslc "$1" -o "$2"; }
+elif command -v glslc > /dev/null; then
+    COMPILE() { glslc "$1" -o "$2"; }
+elif command -v glslangValidator > /dev/null; then
+    COMPILE() { glslangValidator -V --quiet "$1" -o "$2"; }
+else
+    echo "Can't compile shaders: install glslc or glslangValidator" >&2
+    exit 1
+fi
+
 echo "Compiling Shaders..."
-for FILE in $(find ./assets/shaders -name '*.vert' -o -name '*.frag'); do ./glslc $FILE -o $FILE.spv; done
+for FILE in $(find ./assets/shaders -name '*.vert' -o -name '*.frag' -o -name '*.comp'); do COMPILE $FILE $FILE.spv; done
@@ -0,0 +1,508 @@
+use crate::vk_util::{load_image_rgba, NineSlice, SliceMode};
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+// Texture Atlas:
+// Many small images are packed into a few big textures (pages) so that a single texture can be
+// shared by all the sprites of a game. Packing uses the skyline bottom-left heuristic.
+//
+// Sprite Sheet format (.atlas), one entry per line, '#' starts a comment:
+//   page <width> <height> [image path relative to the sheet]
+//   sprite <name> <page index> <x> <y> <w> <h>
+// Names can not contain whitespace. Sheets can be generated at load time with `AtlasBuilder` or
+// offline (written with `SpriteSheet::to_string`) and loaded back with `SpriteSheet::load`.
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PackedRect {
+    pub x: u32,
+    pub y: u32,
+    pub w: u32,
+    pub h: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct SkylineNode {
+    x: u32,
+    y: u32,
+    w: u32,
+}
+
+pub struct SkylinePacker {
+    pub width: u32,
+    pub height: u32,
+    skyline: Vec<SkylineNode>,
+}
+
+impl SkylinePacker {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            skyline: vec![SkylineNode {
+                x: 0,
+                y: 0,
+                w: width,
+            }],
+        }
+    }
+
+    /// Finds room for a `w` x `h` rectangle, picking the position with the lowest top edge.
+    pub fn pack(&mut self, w: u32, h: u32) -> Option<PackedRect> {
+        if w == 0 || h == 0 {
+            return Some(PackedRect::default());
+        }
+        let mut best: Option<(usize, u32)> = None;
+        let mut best_key = (u32::MAX, u32::MAX);
+        for i in 0..self.skyline.len() {
+            if let Some(y) = self.fit(i, w, h) {
+                let key = (y + h, self.skyline[i].w);
+                if key < best_key {
+                    best_key = key;
+                    best = Some((i, y));
+                }
+            }
+        }
+        let (i, y) = best?;
+        let rect = PackedRect {
+            x: self.skyline[i].x,
+            y,
+            w,
+            h,
+        };
+        self.add_level(i, rect);
+        Some(rect)
+    }
+
+    fn fit(&self, i: usize, w: u32, h: u32) -> Option<u32> {
+        let x = self.skyline[i].x;
+        if x + w > self.width {
+            return None;
+        }
+        let mut y = 0;
+        let mut remaining = w as i64;
+        let mut j = i;
+        while remaining > 0 {
+            y = y.max(self.skyline[j].y);
+            if y + h > self.height {
+                return None;
+            }
+            remaining -= self.skyline[j].w as i64;
+            j += 1;
+        }
+        Some(y)
+    }
+
+    fn add_level(&mut self, i: usize, rect: PackedRect) {
+        self.skyline.insert(
+            i,
+            SkylineNode {
+                x: rect.x,
+                y: rect.y + rect.h,
+                w: rect.w,
+            },
+        );
+
+        // Shrink or remove the nodes now covered by the new one
+        let end = rect.x + rect.w;
+        while i + 1 < self.skyline.len() && self.skyline[i + 1].x < end {
+            let next = &mut self.skyline[i + 1];
+            let shrink = end - next.x;
+            if next.w <= shrink {
+                self.skyline.remove(i + 1);
+            } else {
+                next.x += shrink;
+                next.w -= shrink;
+                break;
+            }
+        }
+
+        // Merge neighbors at the same height
+        let mut j = 0;
+        while j + 1 < self.skyline.len() {
+            if self.skyline[j].y == self.skyline[j + 1].y {
+                self.skyline[j].w += self.skyline[j + 1].w;
+                self.skyline.remove(j + 1);
+            } else {
+                j += 1;
+            }
+        }
+    }
+}
+
+/// Packs the rectangles with the given sizes into as many `page_width` x `page_height` pages as
+/// needed. Returns the page index and position of every rectangle, in the same order as `sizes`.
+/// `padding` empty pixels are kept around every rectangle to avoid bleeding when filtering.
+pub fn pack_rects(
+    sizes: &[(u32, u32)],
+    page_width: u32,
+    page_height: u32,
+    padding: u32,
+) -> Result<Vec<(usize, PackedRect)>, String> {
+    // Taller rectangles first gives a flatter skyline
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by_key(|&i| (std::cmp::Reverse(sizes[i].1), std::cmp::Reverse(sizes[i].0)));
+
+    let mut pages: Vec<SkylinePacker> = vec![];
+    let mut result = vec![(0, PackedRect::default()); sizes.len()];
+    for i in order {
+        let (w, h) = sizes[i];
+        let padded = |size: u32| padding.checked_mul(2).and_then(|p| size.checked_add(p));
+        let (pw, ph) = match (padded(w), padded(h)) {
+            (Some(pw), Some(ph)) if pw <= page_width && ph <= page_height => (pw, ph),
+            _ => return Err(format!("Rect {}x{} does not fit in a {}x{} page", w, h, page_width, page_height)),
+        };
+        let mut placed = None;
+        for (page_idx, page) in pages.iter_mut().enumerate() {
+            if let Some(r) = page.pack(pw, ph) {
+                placed = Some((page_idx, r));
+                break;
+            }
+        }
+        let (page_idx, r) = match placed {
+            Some(placed) => placed,
+            None => {
+                let mut page = SkylinePacker::new(page_width, page_height);
+                let r = page.pack(pw, ph).unwrap();
+                pages.push(page);
+                (pages.len() - 1, r)
+            }
+        };
+        result[i] = (
+            page_idx,
+            PackedRect {
+                x: r.x + padding,
+                y: r.y + padding,
+                w,
+                h,
+            },
+        );
+    }
+    Ok(result)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SheetPage {
+    pub width: u32,
+    pub height: u32,
+    pub image: Option<String>, // None when the pixels were generated at load time
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpriteRegion {
+    pub name: String,
+    pub page: usize,
+    pub rect: PackedRect,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SpriteSheet {
+    pub pages: Vec<SheetPage>,
+    pub sprites: Vec<SpriteRegion>,
+    pub material_base: u32, // Texture index of the first page once uploaded (see VkContext::load_sprite_sheet)
+    by_name: HashMap<String, usize>,
+}
+
+impl SpriteSheet {
+    pub fn add_page(&mut self, width: u32, height: u32, image: Option<String>) -> usize {
+        self.pages.push(SheetPage {
+            width,
+            height,
+            image,
+        });
+        self.pages.len() - 1
+    }
+
+    pub fn add_sprite<S: Into<String>>(&mut self, name: S, page: usize, rect: PackedRect) -> Result<(), String> {
+        let name = name.into();
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err(format!("Invalid sprite name '{}'", name));
+        }
+        if page >= self.pages.len() {
+            return Err(format!("Sprite '{}' references unknown page {}", name, page));
+        }
+        let p = &self.pages[page];
+        let inside = |start: u32, size: u32, max: u32| start.checked_add(size).is_some_and(|end| end <= max);
+        if !inside(rect.x, rect.w, p.width) || !inside(rect.y, rect.h, p.height) {
+            return Err(format!("Sprite '{}' is out of bounds of page {}", name, page));
+        }
+        if self.by_name.contains_key(&name) {
+            return Err(format!("Duplicated sprite '{}'", name));
+        }
+        self.by_name.insert(name.clone(), self.sprites.len());
+        self.sprites.push(SpriteRegion {
+            name,
+            page,
+            rect,
+        });
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&SpriteRegion> {
+        self.by_name.get(name).map(|&i| &self.sprites[i])
+    }
+
+    fn region(&self, name: &str) -> &SpriteRegion {
+        self.get(name).unwrap_or_else(|| panic!("Unknown sprite '{}'", name))
+    }
+
+    /// Texture index to use as material when rendering the sprite.
+    pub fn material(&self, name: &str) -> u32 {
+        self.material_base + self.region(name).page as u32
+    }
+
+    /// Normalized texture coordinates of the sprite: [u0, v0, u1, v1].
+    pub fn uv(&self, name: &str) -> [f32; 4] {
+        let region = self.region(name);
+        let page = &self.pages[region.page];
+        let (w, h) = (page.width as f32, page.height as f32);
+        let r = region.rect;
+        [r.x as f32 / w, r.y as f32 / h, (r.x + r.w) as f32 / w, (r.y + r.h) as f32 / h]
+    }
+
+    /// Nine-slice description of the sprite, `insets` are the left, top, right and bottom borders in
+    /// pixels (see `vk_util::push_nine_slice`).
+    pub fn nine_slice(&self, name: &str, insets: [f32; 4], mode: SliceMode) -> NineSlice {
+        NineSlice {
+            uv: self.uv(name),
+            size: self.size(name),
+            insets,
+            mode,
+        }
+    }
+
+    /// Size of the sprite in pixels.
+    pub fn size(&self, name: &str) -> (f32, f32) {
+        let r = self.region(name).rect;
+        (r.w as f32, r.h as f32)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut sheet = Self::default();
+        for (line_idx, line) in text.lines().enumerate() {
+            let err = |msg: String| format!("line {}: {}", line_idx + 1, msg);
+            let line = line.split('#').next().unwrap().trim();
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            let num = |i: usize| -> Result<u32, String> {
+                let token = tokens.get(i).ok_or_else(|| err(format!("Missing field {}", i)))?;
+                token.parse::<u32>().map_err(|_| err(format!("Invalid number '{}'", token)))
+            };
+            match tokens.first() {
+                None => {}
+                Some(&"page") => {
+                    if tokens.len() > 4 {
+                        return Err(err(String::from("Too many fields")));
+                    }
+                    sheet.add_page(num(1)?, num(2)?, tokens.get(3).map(|s| s.to_string()));
+                }
+                Some(&"sprite") => {
+                    if tokens.len() != 7 {
+                        return Err(err(format!("Expected 7 fields, found {}", tokens.len())));
+                    }
+                    let rect = PackedRect {
+                        x: num(3)?,
+                        y: num(4)?,
+                        w: num(5)?,
+                        h: num(6)?,
+                    };
+                    sheet.add_sprite(tokens[1], num(2)? as usize, rect).map_err(err)?;
+                }
+                Some(other) => return Err(err(format!("Unknown entry '{}'", other))),
+            }
+        }
+        Ok(sheet)
+    }
+
+    /// Parses a sheet file and loads the images of its pages (relative to the sheet file).
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<(Self, Vec<AtlasPage>), String> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let sheet = Self::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let dir = path.parent().unwrap_or_else(|| Path::new("."));
+        let mut pages = vec![];
+        for (i, page) in sheet.pages.iter().enumerate() {
+            let image = page.image.as_ref().ok_or_else(|| format!("Page {} has no image", i))?;
+            let (pixels, width, height) = load_image_rgba(dir.join(image).to_string_lossy())?;
+            if (width as u32, height as u32) != (page.width, page.height) {
+                return Err(format!("Page {} is {}x{}, expected {}x{}", i, width, height, page.width, page.height));
+            }
+            pages.push(AtlasPage {
+                width: page.width,
+                height: page.height,
+                pixels,
+            });
+        }
+        Ok((sheet, pages))
+    }
+}
+
+impl fmt::Display for SpriteSheet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for page in &self.pages {
+            write!(f, "page {} {}", page.width, page.height)?;
+            if let Some(image) = &page.image {
+                write!(f, " {}", image)?;
+            }
+            writeln!(f)?;
+        }
+        for s in &self.sprites {
+            writeln!(f, "sprite {} {} {} {} {} {}", s.name, s.page, s.rect.x, s.rect.y, s.rect.w, s.rect.h)?;
+        }
+        Ok(())
+    }
+}
+
+/// RGBA8 pixels of an atlas page.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AtlasPage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+struct AtlasImage {
+    name: String,
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+pub struct AtlasBuilder {
+    pub page_width: u32,
+    pub page_height: u32,
+    pub padding: u32,
+    images: Vec<AtlasImage>,
+}
+
+impl AtlasBuilder {
+    pub fn new(page_width: u32, page_height: u32) -> Self {
+        Self {
+            page_width,
+            page_height,
+            padding: 1,
+            images: vec![],
+        }
+    }
+
+    pub fn add<S: Into<String>>(&mut self, name: S, pixels: Vec<u8>, width: u32, height: u32) {
+        assert_eq!(Some(pixels.len()), (width as usize).checked_mul(height as usize).and_then(|n| n.checked_mul(4)));
+        self.images.push(AtlasImage {
+            name: name.into(),
+            width,
+            height,
+            pixels,
+        });
+    }
+
+    pub fn add_file<S: Into<String>, P: AsRef<str>>(&mut self, name: S, path: P) -> Result<(), String> {
+        let (pixels, width, height) = load_image_rgba(path)?;
+        self.add(name, pixels, width as u32, height as u32);
+        Ok(())
+    }
+
+    pub fn build(self) -> Result<(SpriteSheet, Vec<AtlasPage>), String> {
+        let sizes: Vec<(u32, u32)> = self.images.iter().map(|img| (img.width, img.height)).collect();
+        let placements = pack_rects(&sizes, self.page_width, self.page_height, self.padding)?;
+        let page_count = placements.iter().map(|(page, _)| page + 1).max().unwrap_or(0);
+
+        let page_size = (self.page_width as usize)
+            .checked_mul(self.page_height as usize)
+            .and_then(|n| n.checked_mul(4))
+            .ok_or_else(|| format!("Pages of {}x{} are too large", self.page_width, self.page_height))?;
+        let mut sheet = SpriteSheet::default();
+        let mut pages = vec![];
+        for _ in 0..page_count {
+            sheet.add_page(self.page_width, self.page_height, None);
+            pages.push(AtlasPage {
+                width: self.page_width,
+                height: self.page_height,
+                pixels: vec![0; page_size],
+            });
+        }
+
+        for (img, (page_idx, rect)) in self.images.iter().zip(placements) {
+            let page = &mut pages[page_idx];
+            let row_size = img.width as usize * 4;
+            for row in 0..img.height as usize {
+                let src = row * row_size;
+                let dst = ((rect.y as usize + row) * page.width as usize + rect.x as usize) * 4;
+                page.pixels[dst..dst + row_size].copy_from_slice(&img.pixels[src..src + row_size]);
+            }
+            sheet.add_sprite(img.name.clone(), page_idx, rect)?;
+        }
+        Ok((sheet, pages))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn overlaps(a: &PackedRect, b: &PackedRect) -> bool {
+        a.x < b.x + b.w && b.x < a.x + a.w && a.y < b.y + b.h && b.y < a.y + a.h
+    }
+
+    #[test]
+    fn skyline_packing() {
+        let mut packer = SkylinePacker::new(64, 64);
+        let mut rects = vec![];
+        for (w, h) in [(32, 32), (32, 16), (16, 16), (16, 48), (48, 8)] {
+            rects.push(packer.pack(w, h).unwrap());
+        }
+        for (i, a) in rects.iter().enumerate() {
+            assert!(a.x + a.w <= 64 && a.y + a.h <= 64);
+            for b in &rects[i + 1..] {
+                assert!(!overlaps(a, b), "{:?} {:?}", a, b);
+            }
+        }
+        assert_eq!(packer.pack(64, 64), None);
+    }
+
+    #[test]
+    fn pack_rects_multiple_pages() {
+        let sizes = vec![(30, 30); 6];
+        let placements = pack_rects(&sizes, 64, 64, 1).unwrap();
+        // Only 4 padded 32x32 rects fit per page
+        assert_eq!(placements.iter().filter(|(page, _)| *page == 0).count(), 4);
+        assert_eq!(placements.iter().filter(|(page, _)| *page == 1).count(), 2);
+        assert!(pack_rects(&[(65, 1)], 64, 64, 0).is_err());
+    }
+
+    #[test]
+    fn builder_and_sheet_roundtrip() {
+        let mut builder = AtlasBuilder::new(16, 16);
+        builder.add("red", [255, 0, 0, 255].repeat(4 * 2), 4, 2);
+        builder.add("green", [0, 255, 0, 255].repeat(3 * 3), 3, 3);
+        let (sheet, pages) = builder.build().unwrap();
+        assert_eq!(pages.len(), 1);
+
+        let red = sheet.get("red").unwrap().rect;
+        let idx = ((red.y * 16 + red.x) * 4) as usize;
+        assert_eq!(&pages[0].pixels[idx..idx + 4], &[255, 0, 0, 255]);
+        assert_eq!(sheet.size("green"), (3.0, 3.0));
+
+        let uv = sheet.uv("red");
+        assert_eq!(
+            uv,
+            [red.x as f32 / 16.0, red.y as f32 / 16.0, (red.x + 4) as f32 / 16.0, (red.y + 2) as f32 / 16.0]
+        );
+
+        let parsed = SpriteSheet::parse(&sheet.to_string()).unwrap();
+        assert_eq!(parsed, sheet);
+    }
+
+    #[test]
+    fn parse_errors() {
+        let text = "page 32 32 page0.png\nsprite a 0 0 0 8 8 # comment\n\nsprite b 1 0 0 8 8\n";
+        assert_eq!(SpriteSheet::parse(text).unwrap_err(), "line 4: Sprite 'b' references unknown page 1");
+        assert!(SpriteSheet::parse("sprite a 0 0 0 8").is_err());
+        assert!(SpriteSheet::parse("page 32 x").unwrap_err().contains("Invalid number 'x'"));
+        assert!(SpriteSheet::parse("page 8 8\nsprite a 0 4 4 8 8").unwrap_err().contains("out of bounds"));
+        let overflow = SpriteSheet::parse("page 16 16\nsprite a 0 4294967295 0 1 1");
+        assert!(overflow.unwrap_err().contains("out of bounds"));
+        assert!(pack_rects(&[(u32::MAX, 1)], 64, 64, 1).is_err());
+        assert!(pack_rects(&[(1, 1)], 64, 64, u32::MAX).is_err());
+    }
+}
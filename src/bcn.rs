@@ -0,0 +1,233 @@
+// BCn block compression:
+// CPU decoders for the BC1 to BC5 (DXT1 to DXT5, RGTC) texture formats, for devices that can't sample them. Every
+// 4x4 block of pixels is stored in 8 or 16 bytes: BC1 holds two RGB565 endpoints and 2-bit indices into the colors
+// interpolated between them, BC2 adds 4-bit alpha, BC3 adds alpha as a BC4 block, BC4 holds one channel with two 8-bit
+// endpoints and 3-bit indices, and BC5 holds two BC4 channels.
+// Decoded pixels are RGBA8 laid out as the GPU samples them: BC4 to (r, 0, 0, 1), BC5 to (r, g, 0, 1). Signed BC4
+// and BC5 decode to signed bytes (two's complement, 1.0 is 127) for an SNORM texture.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BcFormat {
+    /// Opaque BC1, the 3-color mode has black instead of transparent
+    Bc1Rgb,
+    Bc1Rgba,
+    Bc2,
+    Bc3,
+    Bc4,
+    Bc4Signed,
+    Bc5,
+    Bc5Signed,
+}
+
+impl BcFormat {
+    /// Size of a block of 4x4 pixels in bytes
+    pub fn block_size(self) -> usize {
+        match self {
+            BcFormat::Bc1Rgb | BcFormat::Bc1Rgba | BcFormat::Bc4 | BcFormat::Bc4Signed => 8,
+            BcFormat::Bc2 | BcFormat::Bc3 | BcFormat::Bc5 | BcFormat::Bc5Signed => 16,
+        }
+    }
+
+    /// Size of an image of the given size in bytes
+    pub fn data_size(self, width: usize, height: usize) -> usize {
+        width.div_ceil(4) * height.div_ceil(4) * self.block_size()
+    }
+}
+
+fn rgb565(c: u16) -> [u8; 3] {
+    let (r, g, b) = ((c >> 11) as u8, (c >> 5 & 63) as u8, (c & 31) as u8);
+    [r << 3 | r >> 2, g << 2 | g >> 4, b << 3 | b >> 2]
+}
+
+/// BC1 color block. `four_colors` forces the 4-color mode, as BC2 and BC3 always use it.
+fn decode_color(block: &[u8], four_colors: bool, transparent: bool) -> [[u8; 4]; 16] {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let (e0, e1) = (rgb565(c0), rgb565(c1));
+    let mix = |a: u32, b: u32, d: u32| -> [u8; 4] {
+        let c = |i: usize| ((a * e0[i] as u32 + b * e1[i] as u32 + d / 2) / d) as u8;
+        [c(0), c(1), c(2), 255]
+    };
+    let palette = if c0 > c1 || four_colors {
+        [mix(1, 0, 1), mix(0, 1, 1), mix(2, 1, 3), mix(1, 2, 3)]
+    } else {
+        [
+            mix(1, 0, 1),
+            mix(0, 1, 1),
+            mix(1, 1, 2),
+            [
+                0,
+                0,
+                0,
+                if transparent {
+                    0
+                } else {
+                    255
+                },
+            ],
+        ]
+    };
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+    std::array::from_fn(|i| palette[(indices >> (2 * i) & 3) as usize])
+}
+
+/// BC4 block (also the alpha of BC3), signed values are returned as their two's complement byte.
+fn decode_channel(block: &[u8], signed: bool) -> [u8; 16] {
+    let (e0, e1) = if signed {
+        // -128 is clamped to -127, so that both -1.0 and 1.0 are exact
+        ((block[0] as i8).max(-127) as i32, (block[1] as i8).max(-127) as i32)
+    } else {
+        (block[0] as i32, block[1] as i32)
+    };
+    let (min, max) = if signed {
+        (-127, 127)
+    } else {
+        (0, 255)
+    };
+    let mix = |a: i32, b: i32, d: i32| ((a * e0 + b * e1) as f32 / d as f32).round() as i32;
+    let palette: [i32; 8] = if e0 > e1 {
+        std::array::from_fn(|i| match i {
+            0 => e0,
+            1 => e1,
+            _ => mix(8 - i as i32, i as i32 - 1, 7),
+        })
+    } else {
+        std::array::from_fn(|i| match i {
+            0 => e0,
+            1 => e1,
+            6 => min,
+            7 => max,
+            _ => mix(6 - i as i32, i as i32 - 1, 5),
+        })
+    };
+    let indices = block[2..8].iter().rev().fold(0u64, |bits, &b| bits << 8 | b as u64);
+    std::array::from_fn(|i| palette[(indices >> (3 * i) & 7) as usize] as u8)
+}
+
+/// Decodes BCn compressed data of the given size to RGBA8 pixels.
+pub fn decode_bc(format: BcFormat, data: &[u8], width: usize, height: usize) -> Result<Vec<u8>, String> {
+    let expected = format.data_size(width, height);
+    if data.len() != expected {
+        return Err(format!(
+            "{:?} data of {}x{} should be {} bytes, got {}",
+            format,
+            width,
+            height,
+            expected,
+            data.len()
+        ));
+    }
+    let mut pixels = vec![0; width * height * 4];
+    let blocks_per_row = width.div_ceil(4);
+    for (b, block) in data.chunks_exact(format.block_size()).enumerate() {
+        let (bx, by) = (b % blocks_per_row * 4, b / blocks_per_row * 4);
+        let texels: [[u8; 4]; 16] = match format {
+            BcFormat::Bc1Rgb => decode_color(block, false, false),
+            BcFormat::Bc1Rgba => decode_color(block, false, true),
+            BcFormat::Bc2 => {
+                let mut texels = decode_color(&block[8..], true, false);
+                for (i, texel) in texels.iter_mut().enumerate() {
+                    texel[3] = (block[i / 2] >> (i % 2 * 4) & 15) * 17;
+                }
+                texels
+            }
+            BcFormat::Bc3 => {
+                let mut texels = decode_color(&block[8..], true, false);
+                let alpha = decode_channel(block, false);
+                texels.iter_mut().zip(alpha).for_each(|(texel, a)| texel[3] = a);
+                texels
+            }
+            BcFormat::Bc4 | BcFormat::Bc4Signed => {
+                let signed = format == BcFormat::Bc4Signed;
+                let one = if signed {
+                    127
+                } else {
+                    255
+                };
+                decode_channel(block, signed).map(|r| [r, 0, 0, one])
+            }
+            BcFormat::Bc5 | BcFormat::Bc5Signed => {
+                let signed = format == BcFormat::Bc5Signed;
+                let one = if signed {
+                    127
+                } else {
+                    255
+                };
+                let (r, g) = (decode_channel(block, signed), decode_channel(&block[8..], signed));
+                std::array::from_fn(|i| [r[i], g[i], 0, one])
+            }
+        };
+        // Blocks on the right and bottom edges may cover pixels outside of the image
+        for (i, texel) in texels.iter().enumerate() {
+            let (x, y) = (bx + i % 4, by + i / 4);
+            if x < width && y < height {
+                pixels[(y * width + x) * 4..][..4].copy_from_slice(texel);
+            }
+        }
+    }
+    Ok(pixels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Indices packed from the first pixel in the lowest bits
+    fn pack(indices: &[u64], bits: usize) -> u64 {
+        indices.iter().enumerate().fold(0, |packed, (i, &index)| packed | index << (bits * i))
+    }
+
+    #[test]
+    fn bc1() {
+        // Red and blue endpoints, 4-color mode: red, blue, 2/3 red, 1/3 red
+        let indices = pack(&[0, 1, 2, 3, 3, 2, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0], 2) as u32;
+        let block = [&0xF800u16.to_le_bytes()[..], &0x001Fu16.to_le_bytes(), &indices.to_le_bytes()].concat();
+        let pixels = decode_bc(BcFormat::Bc1Rgba, &block, 4, 4).unwrap();
+        let texel = |i: usize| &pixels[i * 4..i * 4 + 4];
+        assert_eq!(
+            [texel(0), texel(1), texel(2), texel(3)],
+            [[255, 0, 0, 255], [0, 0, 255, 255], [170, 0, 85, 255], [85, 0, 170, 255]]
+        );
+
+        // Swapped endpoints, 3-color mode: the midpoint and transparent black, opaque black in BC1 RGB. BC2 always
+        // interpolates 4 colors.
+        let block = [&0x001Fu16.to_le_bytes()[..], &0xF800u16.to_le_bytes(), &indices.to_le_bytes()].concat();
+        let pixels = decode_bc(BcFormat::Bc1Rgba, &block, 4, 4).unwrap();
+        assert_eq!((&pixels[8..12], &pixels[12..16]), (&[128, 0, 128, 255][..], &[0, 0, 0, 0][..]));
+        let pixels = decode_bc(BcFormat::Bc1Rgb, &block, 4, 4).unwrap();
+        assert_eq!(&pixels[12..16], [0, 0, 0, 255]);
+        let bc2 = [&[0x10, 0x32, 0x54, 0x76, 0x98, 0xBA, 0xDC, 0xFE][..], &block].concat();
+        let pixels = decode_bc(BcFormat::Bc2, &bc2, 4, 4).unwrap();
+        assert_eq!(&pixels[12..16], [170, 0, 85, 51]);
+        assert_eq!(pixels.chunks(4).map(|p| p[3] / 17).collect::<Vec<_>>(), (0..16).collect::<Vec<u8>>());
+
+        // Partial blocks of a 5x2 image
+        let pixels = decode_bc(BcFormat::Bc1Rgb, &[block.clone(), block].concat(), 5, 2).unwrap();
+        assert_eq!((pixels.len(), &pixels[16..20]), (40, &[0, 0, 255, 255][..]));
+        assert!(decode_bc(BcFormat::Bc1Rgb, &[0; 8], 5, 2).unwrap_err().contains("16 bytes"));
+    }
+
+    #[test]
+    fn bc4() {
+        // 8-value mode from 200 to 60
+        let indices = pack(&[0, 1, 2, 3, 4, 5, 6, 7, 0, 0, 0, 0, 0, 0, 0, 0], 3);
+        let block = [&[200, 60][..], &indices.to_le_bytes()[..6]].concat();
+        let pixels = decode_bc(BcFormat::Bc4, &block, 4, 4).unwrap();
+        let reds: Vec<u8> = pixels.chunks(4).take(8).map(|p| p[0]).collect();
+        assert_eq!(reds, [200, 60, 180, 160, 140, 120, 100, 80]);
+        assert_eq!(&pixels[..4], [200, 0, 0, 255]);
+
+        // 6-value mode from 10 to 110 with the extremes
+        let block = [&[10, 110][..], &indices.to_le_bytes()[..6]].concat();
+        let pixels = decode_bc(BcFormat::Bc3, &[block.clone(), vec![0; 8]].concat(), 4, 4).unwrap();
+        let alphas: Vec<u8> = pixels.chunks(4).take(8).map(|p| p[3]).collect();
+        assert_eq!(alphas, [10, 110, 30, 50, 70, 90, 0, 255]);
+
+        // Signed, -128 behaves as -127
+        let block = [&[0x80, 127][..], &indices.to_le_bytes()[..6]].concat();
+        let pixels = decode_bc(BcFormat::Bc5Signed, &[block.clone(), block].concat(), 4, 4).unwrap();
+        let reds: Vec<i8> = pixels.chunks(4).take(8).map(|p| p[0] as i8).collect();
+        assert_eq!(reds, [-127, 127, -76, -25, 25, 76, -127, 127]);
+        assert_eq!(&pixels[4..8], [127, 127, 0, 127]);
+    }
+}
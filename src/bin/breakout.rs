@@ -1,6 +1,7 @@
 use icarus::color;
 use icarus::input::{InputState, KeyId};
 use icarus::math::{Rect, Vec2};
+use icarus::particles::{Curve, EmitterConfig, EmitterShape, ParticleSystem};
 use icarus::platform::{Config, Platform};
 use icarus::vk_util::{self, RenderCommand, VkContext};
 
@@ -31,6 +32,20 @@ impl Block {
     }
 }
 
+fn brick_emitter() -> EmitterConfig {
+    EmitterConfig {
+        rate: 0.0,
+        shape: EmitterShape::Rect(Vec2::new(BLOCK_SIZE, BLOCK_SIZE)),
+        lifetime: (0.4, 0.8),
+        speed: (50.0, 250.0),
+        gravity: Vec2::new(0.0, 1500.0),
+        color: Curve::linear(color::WHITE, color::BLACK),
+        size: Curve::linear(10.0, 2.0),
+        z: 0.1,
+        ..EmitterConfig::default()
+    }
+}
+
 #[derive(Default)]
 struct Game {
     paused: bool,
@@ -39,6 +54,7 @@ struct Game {
     ball: Vec2,
     ball_vel: Vec2,
     blocks: Vec<Block>,
+    particles: ParticleSystem,
 }
 impl Game {
     fn init() -> Self {
@@ -73,6 +89,7 @@ impl Game {
         if self.paused {
             return;
         }
+        self.particles.update(dt);
         if self.game_over {
             return;
         }
@@ -119,6 +136,7 @@ impl Game {
             let block_rect = Rect::offset_extent(block.pos, (BLOCK_SIZE - BLOCK_PADDING, BLOCK_SIZE - BLOCK_PADDING));
             if block.alive && ball_rect.collides(&block_rect) {
                 block.alive = false;
+                self.particles.burst(&brick_emitter(), block_rect.center(), 30);
                 if self.ball_vel.y < 0.0 {
                     self.ball_vel.y = -self.ball_vel.y;
                 }
@@ -155,6 +173,8 @@ impl Game {
             }
         }
 
+        self.particles.render(cmd);
+
         let alive_count = self.blocks.iter().filter(|x| x.alive).count();
         let text = if alive_count == 0 {
             "Victory".to_string()
@@ -1,3 +1,4 @@
+use icarus::atlas::{AtlasBuilder, SpriteSheet};
 use icarus::input::{InputState, KeyId};
 use icarus::math::{Rect, Vec2};
 use icarus::platform::{Config, Platform};
@@ -20,23 +21,19 @@ fn main() {
     let mut input = InputState::default();
     let mut game = Game::init();
     let mut vk_ctx = VkContext::init(&platform);
-    vk_ctx.set_shader("sprite");
+    vk_ctx.set_shader("atlas");
 
     vk_ctx.vertex_buffer.destroy();
     let vertices: [(f32, f32); 4] = [(-1.0, -1.0), (-1.0, 1.0), (1.0, 1.0), (1.0, -1.0)];
     vk_ctx.create_vertex_buffer(&vertices);
 
-    vk_ctx.load_texture_image("assets/textures/flappy/background-day.png");
-    vk_ctx.load_texture_image("assets/textures/flappy/base.png");
-    vk_ctx.load_texture_image("assets/textures/flappy/pipe-green.png");
-    vk_ctx.load_texture_image("assets/textures/flappy/bluebird-downflap.png");
-    vk_ctx.load_texture_image("assets/textures/flappy/bluebird-midflap.png");
-    vk_ctx.load_texture_image("assets/textures/flappy/bluebird-upflap.png");
-    vk_ctx.load_texture_image("assets/textures/flappy/gameover.png");
-
-    for i in 0..10 {
-        vk_ctx.load_texture_image(format!("assets/textures/flappy/{}.png", i));
+    // Pack all the sprites into a single texture
+    let mut atlas = AtlasBuilder::new(1024, 1024);
+    for name in [BACKGROUND, BASE, PIPE, BIRD_DOWN, BIRD_MID, BIRD_UP, GAME_OVER].iter().chain(DIGITS.iter()) {
+        atlas.add_file(*name, format!("assets/textures/flappy/{}.png", name)).unwrap();
     }
+    let (mut sheet, pages) = atlas.build().expect("Failed to pack the sprites");
+    vk_ctx.load_sprite_sheet(&mut sheet, &pages);
 
     vk_ctx.update_descriptor_sets((platform.window_width, platform.window_height));
 
@@ -52,7 +49,7 @@ fn main() {
         let seconds_elapsed = prev_frame_time.elapsed().as_secs_f32();
         prev_frame_time = Instant::now();
         game.update(&input, seconds_elapsed);
-        game.render(&mut vk_ctx);
+        game.render(&mut vk_ctx, &sheet);
     }
 
     vk_ctx.cleanup(&platform);
@@ -67,6 +64,7 @@ struct Game {
     cmd: Vec<RenderCommand>,
     materials: Vec<u32>,
     rotations: Vec<u32>,
+    uvs: Vec<[f32; 4]>,
 
     bird_idx: usize,
     sprites: Vec<Sprite>,
@@ -86,20 +84,30 @@ const PIPE_WIDTH: f32 = 52.0;
 const PIPE_HEIGHT: f32 = 320.0;
 const GAP_HEIGHT: f32 = HEIGHT - BASE_HEIGHT - PIPE_HEIGHT - (PIPE_HEIGHT / 2.0);
 
+// Sprite names, also the file names of the textures
+const BACKGROUND: &str = "background-day";
+const BASE: &str = "base";
+const PIPE: &str = "pipe-green";
+const BIRD_DOWN: &str = "bluebird-downflap";
+const BIRD_MID: &str = "bluebird-midflap";
+const BIRD_UP: &str = "bluebird-upflap";
+const GAME_OVER: &str = "gameover";
+const DIGITS: [&str; 10] = ["0", "1", "2", "3", "4", "5", "6", "7", "8", "9"];
+
 impl Game {
     fn init() -> Self {
         let mut sprites = vec![];
         for i in 0..6 {
             // Background
             sprites.push(Sprite::new(
-                Material::Background,
+                BACKGROUND,
                 ((i as f32) * BG_WIDTH, HEIGHT / 2.0),
                 (BG_WIDTH, /*BG_HEIGHT*/ HEIGHT),
                 0.9,
             ));
             // Base
             sprites.push(Sprite::new(
-                Material::Base,
+                BASE,
                 ((i as f32) * BASE_WIDTH, HEIGHT - (BASE_HEIGHT / 2.0)),
                 (BASE_WIDTH, BASE_HEIGHT),
                 0.8,
@@ -107,13 +115,13 @@ impl Game {
         }
         // Pipes
         sprites.push(Sprite::new(
-            Material::Pipe,
+            PIPE,
             (WIDTH / 2.0 + BG_WIDTH, HEIGHT - BASE_HEIGHT - (PIPE_HEIGHT / 2.0)),
             (PIPE_WIDTH, PIPE_HEIGHT),
             0.2,
         ));
         sprites.push(Sprite::with_rot(
-            Material::Pipe,
+            PIPE,
             (WIDTH / 2.0 + BG_WIDTH, 0.0 /*PIPE_HEIGHT / 2.0*/),
             (PIPE_WIDTH, PIPE_HEIGHT),
             0.2,
@@ -122,7 +130,7 @@ impl Game {
         // Bird
         let bird_idx = sprites.len();
         let bird_pos = (WIDTH / 2.0, HEIGHT / 2.0);
-        sprites.push(Sprite::new(Material::BirdMid, bird_pos, (BIRD_WIDTH, BIRD_HEIGHT), 0.1));
+        sprites.push(Sprite::new(BIRD_MID, bird_pos, (BIRD_WIDTH, BIRD_HEIGHT), 0.1));
 
         Self {
             running: true,
@@ -181,11 +189,11 @@ impl Game {
 
         // Bird Animation
         if self.timer < 0.33 {
-            self.sprites[self.bird_idx].material = Material::BirdDown;
+            self.sprites[self.bird_idx].name = BIRD_DOWN;
         } else if self.timer < 0.66 {
-            self.sprites[self.bird_idx].material = Material::BirdMid;
+            self.sprites[self.bird_idx].name = BIRD_MID;
         } else {
-            self.sprites[self.bird_idx].material = Material::BirdUp;
+            self.sprites[self.bird_idx].name = BIRD_UP;
         }
 
         // Update base
@@ -226,59 +234,60 @@ impl Game {
         }
     }
 
-    fn render(&mut self, vk_ctx: &mut VkContext) {
+    fn render(&mut self, vk_ctx: &mut VkContext, sheet: &SpriteSheet) {
         self.cmd.clear();
         self.materials.clear();
         self.rotations.clear();
+        self.uvs.clear();
 
         for i in 0..self.sprites.len() {
-            self.render_sprite(self.sprites[i]);
+            self.render_sprite(sheet, self.sprites[i]);
         }
 
         if !self.dead {
             // render score
-            self.render_number(self.score);
+            self.render_number(sheet, self.score);
         } else {
-            self.render_sprite(Sprite::new(
-                Material::GameOver,
-                (WIDTH / 2.0, HEIGHT / 2.0 - 100.0),
-                (192.0 * 2.0, 42.0 * 2.0),
-                0.0,
-            ));
+            self.render_sprite(
+                sheet,
+                Sprite::new(GAME_OVER, (WIDTH / 2.0, HEIGHT / 2.0 - 100.0), (192.0 * 2.0, 42.0 * 2.0), 0.0),
+            );
         }
 
-        vk_ctx.render(&self.cmd, None, &self.materials, &self.rotations);
+        vk_ctx.render_sprite_uv(&self.cmd, None, &self.materials, &self.rotations, &self.uvs);
     }
 
-    fn render_sprite(&mut self, sprite: Sprite) {
+    fn render_sprite(&mut self, sheet: &SpriteSheet, sprite: Sprite) {
         vk_util::push_rect(&mut self.cmd, Rect::center_extent(sprite.pos, sprite.size), sprite.depth);
-        self.materials.push(sprite.material as u32);
+        self.materials.push(sheet.material(sprite.name));
         self.rotations.push(sprite.rotation);
+        self.uvs.push(sheet.uv(sprite.name));
 
         if self.debug {
             vk_util::push_rect_outline(&mut self.cmd, Rect::center_extent(sprite.pos, sprite.size), sprite.depth / 2.0);
             for _ in 0..4 {
-                self.materials.push(Material::Default as u32);
+                self.materials.push(0);
                 self.rotations.push(sprite.rotation);
+                self.uvs.push(vk_util::FULL_UV);
             }
         }
     }
 
-    fn render_number(&mut self, mut number: u32) {
+    fn render_number(&mut self, sheet: &SpriteSheet, mut number: u32) {
         let mut start_x = WIDTH / 2.0 - 200.0;
         if number == 0 {
-            self.render_digit(number, start_x);
+            self.render_digit(sheet, number, start_x);
         } else {
             while number > 0 {
                 let digit = number % 10;
                 number /= 10;
-                self.render_digit(digit, start_x);
+                self.render_digit(sheet, digit, start_x);
                 start_x -= 24.0 * 1.5;
             }
         }
     }
 
-    fn render_digit(&mut self, digit: u32, x: f32) {
+    fn render_digit(&mut self, sheet: &SpriteSheet, digit: u32, x: f32) {
         assert!(digit <= 9);
         let w = if digit == 1 {
             16.0
@@ -286,18 +295,13 @@ impl Game {
             24.0
         };
         let h = 36.0;
-        self.render_sprite(Sprite::new(
-            (Material::Num0 as u32 + digit).into(),
-            (x, HEIGHT / 6.0),
-            (w * 1.5, h * 1.5),
-            0.0,
-        ));
+        self.render_sprite(sheet, Sprite::new(DIGITS[digit as usize], (x, HEIGHT / 6.0), (w * 1.5, h * 1.5), 0.0));
     }
 }
 
 #[derive(Default, Copy, Clone)]
 struct Sprite {
-    material: Material,
+    name: &'static str,
     pos: Vec2,  // center of the sprite
     size: Vec2, // from left to right, not half!
     depth: f32,
@@ -305,12 +309,12 @@ struct Sprite {
 }
 
 impl Sprite {
-    fn new<V: Into<Vec2>>(material: Material, pos: V, size: V, depth: f32) -> Self {
-        Self::with_rot(material, pos, size, depth, 0)
+    fn new<V: Into<Vec2>>(name: &'static str, pos: V, size: V, depth: f32) -> Self {
+        Self::with_rot(name, pos, size, depth, 0)
     }
-    fn with_rot<V: Into<Vec2>>(material: Material, pos: V, size: V, depth: f32, rotation: u32) -> Self {
+    fn with_rot<V: Into<Vec2>>(name: &'static str, pos: V, size: V, depth: f32, rotation: u32) -> Self {
         Self {
-            material,
+            name,
             pos: pos.into(),
             size: size.into(),
             depth,
@@ -318,60 +322,3 @@ impl Sprite {
         }
     }
 }
-
-#[repr(u32)]
-#[derive(Copy, Clone)]
-enum Material {
-    Default = 0,
-
-    Background = 1,
-    Base = 2,
-    Pipe = 3,
-    BirdDown = 4,
-    BirdMid = 5,
-    BirdUp = 6,
-    GameOver = 7,
-
-    Num0 = 8,
-    Num1,
-    Num2,
-    Num3,
-    Num4,
-    Num5,
-    Num6,
-    Num7,
-    Num8,
-    Num9,
-}
-impl Default for Material {
-    fn default() -> Self {
-        Self::Default
-    }
-}
-impl From<u32> for Material {
-    fn from(x: u32) -> Self {
-        match x {
-            x if x == Self::Default as u32 => Self::Default,
-
-            x if x == Self::Background as u32 => Self::Background,
-            x if x == Self::Base as u32 => Self::Base,
-            x if x == Self::Pipe as u32 => Self::Pipe,
-            x if x == Self::BirdDown as u32 => Self::BirdDown,
-            x if x == Self::BirdMid as u32 => Self::BirdMid,
-            x if x == Self::BirdUp as u32 => Self::BirdUp,
-            x if x == Self::GameOver as u32 => Self::GameOver,
-
-            x if x == Self::Num0 as u32 => Self::Num0,
-            x if x == Self::Num1 as u32 => Self::Num1,
-            x if x == Self::Num2 as u32 => Self::Num2,
-            x if x == Self::Num3 as u32 => Self::Num3,
-            x if x == Self::Num4 as u32 => Self::Num4,
-            x if x == Self::Num5 as u32 => Self::Num5,
-            x if x == Self::Num6 as u32 => Self::Num6,
-            x if x == Self::Num7 as u32 => Self::Num7,
-            x if x == Self::Num8 as u32 => Self::Num8,
-            x if x == Self::Num9 as u32 => Self::Num9,
-            n => panic!("Unknown Material {}", n),
-        }
-    }
-}
@@ -2,6 +2,7 @@ use icarus::color;
 use icarus::color::Color;
 use icarus::input::{InputState, KeyId};
 use icarus::math::{Rect, Vec2};
+use icarus::particles::{Curve, EmitterConfig, ParticleSystem};
 use icarus::platform::{Config, Platform};
 use icarus::rand::Rand;
 use icarus::vk_util::{self, RenderCommand, VkContext};
@@ -45,10 +46,30 @@ struct Enemy {
     material: u32,
     dead: bool,
 }
-#[derive(Default)]
-struct Splat {
-    pos: Vec2,
-    duration: f32,
+fn splat_emitter() -> EmitterConfig {
+    EmitterConfig {
+        rate: 0.0,
+        lifetime: (0.2, 0.2),
+        speed: (0.0, 0.0),
+        size: Curve::constant(ENEMY_WIDTH),
+        aspect: ENEMY_HEIGHT / ENEMY_WIDTH,
+        material: 9,
+        z: 0.9,
+        ..EmitterConfig::default()
+    }
+}
+
+fn debris_emitter() -> EmitterConfig {
+    EmitterConfig {
+        rate: 0.0,
+        lifetime: (0.3, 0.6),
+        speed: (100.0, 300.0),
+        gravity: Vec2::new(0.0, 600.0),
+        color: Curve::linear(color::WHITE, BG_COLOR),
+        size: Curve::linear(6.0, 2.0),
+        z: 0.9,
+        ..EmitterConfig::default()
+    }
 }
 
 #[derive(Default)]
@@ -67,7 +88,9 @@ struct Game {
     enemies: Vec<Enemy>,
     enemies_moving_left: bool,
     enemies_offset: Vec2,
-    splats: Vec<Splat>,
+    particles: ParticleSystem,
+    // Kept apart from the debris as they move with the formation
+    splats: ParticleSystem,
     bunkers: Vec<Vec2>,
     seconds_timer: Timer,
     rand: Rand,
@@ -155,6 +178,7 @@ impl Game {
         });
         if self.seconds_timer.elapsed >= self.seconds_timer.duration {
             self.seconds_timer.elapsed -= self.seconds_timer.duration;
+            let previous_offset = self.enemies_offset;
             if self.enemies_moving_left {
                 self.enemies_offset.x -= 50.0;
                 if self.enemies_offset.x < -250.0 {
@@ -171,14 +195,19 @@ impl Game {
                 }
             }
 
+            let shift = self.enemies_offset - previous_offset;
+            for splat in self.splats.emitters.iter_mut().flat_map(|e| e.particles.iter_mut()) {
+                splat.pos = splat.pos + shift;
+            }
+
             let idx = self.rand.next_usize() % self.enemies.iter().filter(|e| !e.dead).count();
             self.bullets.push(Bullet {
                 pos: self.enemies.iter().filter(|e| !e.dead).nth(idx).unwrap().pos + self.enemies_offset,
                 vel: Vec2::new(0.0, BULLET_SPEED),
             });
         }
-        self.splats.iter_mut().for_each(|s| s.duration -= dt);
-        self.splats.retain(|s| s.duration > 0.0);
+        self.particles.update(dt);
+        self.splats.update(dt);
 
         self.player = self.player + Vec2::new(self.player_vel, 0.0) * dt;
         self.player.x = self.player.x.clamp(PLAYER_WIDTH / 2.0, WIDTH - PLAYER_WIDTH / 2.0);
@@ -205,10 +234,9 @@ impl Game {
                 if enemy_rect.collides(&bullet_rect) {
                     enemy.dead = true;
                     bullet.pos.y = -10.0;
-                    self.splats.push(Splat {
-                        pos: enemy.pos,
-                        duration: 0.2,
-                    });
+                    let pos = enemy.pos + self.enemies_offset;
+                    self.splats.burst(&splat_emitter(), pos, 1);
+                    self.particles.burst(&debris_emitter(), pos, 24);
                 }
             }
         }
@@ -317,14 +345,8 @@ impl Game {
                 }
             }
 
-            for splat in &self.splats {
-                vk_util::push_rect(
-                    cmd,
-                    Rect::center_extent(splat.pos + self.enemies_offset, (ENEMY_WIDTH, ENEMY_HEIGHT)),
-                    0.9,
-                );
-                materials.push(9);
-            }
+            self.splats.render_with_materials(cmd, materials);
+            self.particles.render_with_materials(cmd, materials);
         }
     }
 }
@@ -344,16 +366,16 @@ fn main() {
     let vertices: [(f32, f32); 4] = [(-1.0, -1.0), (-1.0, 1.0), (1.0, 1.0), (1.0, -1.0)];
     vk_ctx.create_vertex_buffer(&vertices);
 
-    vk_ctx.load_texture_image("assets/textures/invaders/player.png"); // 1
-    vk_ctx.load_texture_image("assets/textures/invaders/invader_01_1.png"); // 2
-    vk_ctx.load_texture_image("assets/textures/invaders/invader_01_2.png"); // 3
-    vk_ctx.load_texture_image("assets/textures/invaders/invader_02_1.png"); // 4
-    vk_ctx.load_texture_image("assets/textures/invaders/invader_02_2.png"); // 5
-    vk_ctx.load_texture_image("assets/textures/invaders/invader_03_1.png"); // 6
-    vk_ctx.load_texture_image("assets/textures/invaders/invader_03_2.png"); // 7
-    vk_ctx.load_texture_image("assets/textures/invaders/bunker.png"); // 8
-    vk_ctx.load_texture_image("assets/textures/invaders/splat.png"); // 9
-    vk_ctx.load_texture_image("assets/textures/invaders/ship.png"); // 10
+    vk_ctx.load_texture_image("assets/textures/invaders/player.png").unwrap(); // 1
+    vk_ctx.load_texture_image("assets/textures/invaders/invader_01_1.png").unwrap(); // 2
+    vk_ctx.load_texture_image("assets/textures/invaders/invader_01_2.png").unwrap(); // 3
+    vk_ctx.load_texture_image("assets/textures/invaders/invader_02_1.png").unwrap(); // 4
+    vk_ctx.load_texture_image("assets/textures/invaders/invader_02_2.png").unwrap(); // 5
+    vk_ctx.load_texture_image("assets/textures/invaders/invader_03_1.png").unwrap(); // 6
+    vk_ctx.load_texture_image("assets/textures/invaders/invader_03_2.png").unwrap(); // 7
+    vk_ctx.load_texture_image("assets/textures/invaders/bunker.png").unwrap(); // 8
+    vk_ctx.load_texture_image("assets/textures/invaders/splat.png").unwrap(); // 9
+    vk_ctx.load_texture_image("assets/textures/invaders/ship.png").unwrap(); // 10
     vk_ctx.update_descriptor_sets((WIDTH, HEIGHT));
 
     let start_time = Instant::now();
@@ -1,11 +1,14 @@
 use icarus::color;
 use icarus::color::*;
-use icarus::glyph::{Glyph, GLYPH_PIXEL_SIZE};
+use icarus::glyph::{Glyph, GLYPH_HEIGHT, GLYPH_PIXEL_SIZE};
+use icarus::glyph_sdf::{SdfAtlas, SdfOptions};
 use icarus::input::{ButtonId, InputState, KeyId};
 use icarus::math::{Rect, Vec2};
 use icarus::platform::{Config, Platform};
 use icarus::rand::Rand;
-use icarus::vk_util::{self, RenderCommand, VkContext};
+use icarus::text_layout::{Align, LayoutOptions, TextLayout};
+use icarus::tilemap::Grid;
+use icarus::vk_util::{self, NineSlice, RenderCommand, SliceMode, VkContext, FULL_UV};
 
 use std::time::Instant;
 
@@ -24,9 +27,21 @@ const TILE_ACTIVATED_COLOR: Color = color!(rgb(0.5, 0.5, 0.5));
 // Layers from top to bottom
 const TEXT_Z: f32 = 0.0;
 //const OUTLINE_Z: f32 = 0.1;
+const PANEL_Z: f32 = 0.5;
 const TILE_FOREGROUND_Z: f32 = 0.8;
 const TILE_BACKGROUND_Z: f32 = 0.9;
 
+// Beveled panel texture used as nine-slice for the menu buttons and the titles
+const PANEL_MATERIAL: u32 = 1;
+const PANEL_SIZE: usize = 16;
+const PANEL_BEVEL: usize = 3;
+const PANEL_SLICE: NineSlice = NineSlice {
+    uv: FULL_UV,
+    size: (PANEL_SIZE as f32, PANEL_SIZE as f32),
+    insets: [4.0, 4.0, 4.0, 4.0],
+    mode: SliceMode::Stretch,
+};
+
 pub struct Game {
     pub running: bool,
     pub seconds_elapsed: f32,
@@ -41,6 +56,9 @@ pub struct Game {
     pub state: GameState,
     pub tiles: [Tile; MAX_TILE_COUNT], // actual size: tile_count,
     pub render_commands: Vec<RenderCommand>,
+    pub materials: Vec<u32>,
+    pub uvs: Vec<[f32; 4]>,
+    pub distance_ranges: Vec<f32>,
     pub rand: Rand,
 }
 #[derive(PartialEq, Copy, Clone)]
@@ -89,6 +107,17 @@ fn main() {
     let mut input = InputState::default();
     let mut game = Game::init(0);
     let mut vk_ctx = VkContext::init(&platform);
+    vk_ctx.set_shader("atlas");
+
+    vk_ctx.vertex_buffer.destroy();
+    let vertices: [(f32, f32); 4] = [(-1.0, -1.0), (-1.0, 1.0), (1.0, 1.0), (1.0, -1.0)];
+    vk_ctx.create_vertex_buffer(&vertices);
+
+    let panel = vk_ctx.create_texture_image(&panel_pixels(), PANEL_SIZE, PANEL_SIZE);
+    vk_ctx.texture_images.push(panel); // PANEL_MATERIAL
+    let mut text_atlas = SdfAtlas::from_bitmap_glyphs(&SdfOptions::default()).expect("Failed to build the text atlas");
+    vk_ctx.load_sdf_atlas(&mut text_atlas);
+    vk_ctx.update_descriptor_sets((platform.window_width, platform.window_height));
 
     // Main loop
     let start_time = Instant::now();
@@ -99,9 +128,17 @@ fn main() {
         let seconds_elapsed = prev_frame_time.elapsed().as_secs_f32();
         prev_frame_time = Instant::now();
         game.update(&input, seconds_elapsed);
-        game.render();
-
-        vk_ctx.render_simple(&game.render_commands, None);
+        game.render(&text_atlas);
+
+        let rotations = vec![0; game.render_commands.len()];
+        vk_ctx.render_sprite_sdf(
+            &game.render_commands,
+            None,
+            &game.materials,
+            &rotations,
+            &game.uvs,
+            &game.distance_ranges,
+        );
     }
 
     vk_ctx.cleanup(&platform);
@@ -146,6 +183,9 @@ impl Game {
             mines_left: mine_count,
             tiles,
             render_commands: vec![],
+            materials: vec![],
+            uvs: vec![],
+            distance_ranges: vec![],
             rand,
         }
     }
@@ -221,8 +261,11 @@ impl Game {
     }
 
     // Render the current state of the game.
-    fn render(&mut self) {
+    fn render(&mut self, text_atlas: &SdfAtlas) {
         self.render_commands.clear();
+        self.materials.clear();
+        self.uvs.clear();
+        self.distance_ranges.clear();
 
         //push_rect(&mut self.render_commands, Rect::offset_extent((0.0, 25.0), (WINDOW_WIDTH, 2.0)), TILE_FOREGROUND_Z);
         //push_rect(&mut self.render_commands, Rect::offset_extent((0.0, 175.0), (WINDOW_WIDTH, 2.0)), TILE_FOREGROUND_Z);
@@ -234,16 +277,7 @@ impl Game {
 
         match self.state {
             GameState::Menu(option) => {
-                vk_util::push_str_centered_color(
-                    &mut self.render_commands,
-                    "Level",
-                    TITLE_Y + 25.0,
-                    TEXT_Z,
-                    TITLE_PIXEL_SIZE,
-                    TITLE_COLOR,
-                    true,
-                    Rect::offset_extent((0.0, 0.0), (WINDOW_WIDTH, WINDOW_HEIGHT)),
-                );
+                push_title(self, text_atlas, "Level", TITLE_Y + 25.0, TITLE_PIXEL_SIZE);
                 render_menu(self, option)
             }
             GameState::Playing => {
@@ -267,36 +301,91 @@ impl Game {
             }
             GameState::Win => {
                 render_board(self);
-                vk_util::push_str_centered_color(
-                    &mut self.render_commands,
-                    "Victory!",
-                    TITLE_Y,
-                    TEXT_Z,
-                    TITLE_PIXEL_SIZE,
-                    TITLE_COLOR,
-                    true,
-                    Rect::offset_extent((0.0, 0.0), (WINDOW_WIDTH, WINDOW_HEIGHT)),
-                );
+                render_title_panel(self, text_atlas, "Victory!", TITLE_Y, TITLE_PIXEL_SIZE);
+                push_title(self, text_atlas, "Victory!", TITLE_Y, TITLE_PIXEL_SIZE);
             }
             GameState::GameOver => {
                 render_board(self);
-                vk_util::push_str_centered_color(
-                    &mut self.render_commands,
-                    "Game Over!",
-                    TITLE_Y,
-                    TEXT_Z,
-                    TITLE_PIXEL_SIZE,
-                    TITLE_COLOR,
-                    true,
-                    Rect::offset_extent((0.0, 0.0), (WINDOW_WIDTH, WINDOW_HEIGHT)),
-                );
+                render_title_panel(self, text_atlas, "Game Over!", TITLE_Y, TITLE_PIXEL_SIZE);
+                push_title(self, text_atlas, "Game Over!", TITLE_Y, TITLE_PIXEL_SIZE);
             }
         }
+        pad_materials(self);
+    }
+}
+
+// Untextured rects use the default white texture
+fn pad_materials(game: &mut Game) {
+    game.materials.resize(game.render_commands.len(), 0);
+    game.uvs.resize(game.render_commands.len(), FULL_UV);
+    game.distance_ranges.resize(game.render_commands.len(), 0.0);
+}
+
+// Titles are drawn out of the distance field atlas, one quad per character whatever their size
+fn title_layout(text_atlas: &SdfAtlas, text: &str, pixel_size: f32) -> TextLayout {
+    let options = LayoutOptions {
+        size: pixel_size * GLYPH_HEIGHT as f32, // One em of the atlas is the height of a glyph
+        max_width: Some(WINDOW_WIDTH),
+        align: Align::Center,
+        ..Default::default()
+    };
+    TextLayout::new(text_atlas, text, &options)
+}
+
+fn push_title(game: &mut Game, text_atlas: &SdfAtlas, text: &str, y: f32, pixel_size: f32) {
+    pad_materials(game);
+    vk_util::push_text_sdf(
+        &mut game.render_commands,
+        &mut game.materials,
+        &mut game.uvs,
+        &mut game.distance_ranges,
+        text_atlas,
+        &title_layout(text_atlas, text, pixel_size),
+        (0.0, y),
+        TEXT_Z,
+        TITLE_COLOR,
+    );
+}
+
+fn push_panel<R: Into<Rect>>(game: &mut Game, r: R, color: Color) {
+    pad_materials(game);
+    let count = vk_util::push_nine_slice(&mut game.render_commands, &mut game.uvs, r, PANEL_Z, color, &PANEL_SLICE);
+    game.materials.resize(game.materials.len() + count, PANEL_MATERIAL);
+}
+
+fn render_title_panel(game: &mut Game, text_atlas: &SdfAtlas, text: &str, y: f32, pixel_size: f32) {
+    let bounds = title_layout(text_atlas, text, pixel_size).bounds();
+    let padding = 20.0;
+    push_panel(
+        game,
+        Rect::offset_extent(
+            (bounds.offset.x - padding, y + bounds.offset.y - padding),
+            (bounds.extent.x + 2.0 * padding, bounds.extent.y + 2.0 * padding),
+        ),
+        LIGHT_GREY,
+    );
+}
+
+fn panel_pixels() -> Vec<u8> {
+    let mut pixels = vec![];
+    for y in 0..PANEL_SIZE {
+        for x in 0..PANEL_SIZE {
+            let in_border =
+                x < PANEL_BEVEL || y < PANEL_BEVEL || x >= PANEL_SIZE - PANEL_BEVEL || y >= PANEL_SIZE - PANEL_BEVEL;
+            let value = if !in_border {
+                200
+            } else if x + y < PANEL_SIZE - 1 && (x < PANEL_BEVEL || y < PANEL_BEVEL) {
+                255 // Highlight on the top-left
+            } else {
+                96 // Shadow on the bottom-right
+            };
+            pixels.extend_from_slice(&[value, value, value, 255]);
+        }
     }
+    pixels
 }
 
 fn render_menu(game: &mut Game, option: usize) {
-    let cmd = &mut game.render_commands;
     //push_str_centered_color(cmd, "DIFFICULY", 100.0, TEXT_Z, GLYPH_PIXEL_SIZE * 1.3, WHITE, true);
     let x = WINDOW_WIDTH / 2.0;
     let mut y = 300.0;
@@ -305,10 +394,9 @@ fn render_menu(game: &mut Game, option: usize) {
     let padding = 25.0;
     let texts = ["Beginner", "Intermediate", "Expert"];
     for (i, text) in texts.iter().enumerate() {
-        vk_util::push_rect_color(
-            cmd,
+        push_panel(
+            game,
             Rect::center_extent((x, y + 40.0), (w, h)),
-            TILE_BACKGROUND_Z,
             if option == i {
                 DARK_GREY
             } else {
@@ -316,7 +404,7 @@ fn render_menu(game: &mut Game, option: usize) {
             },
         );
         vk_util::push_str_centered_color(
-            cmd,
+            &mut game.render_commands,
             text,
             y,
             TEXT_Z,
@@ -334,16 +422,12 @@ fn render_menu(game: &mut Game, option: usize) {
 }
 
 fn render_board(game: &mut Game) {
+    let grid = board_grid(game);
     let cmd = &mut game.render_commands;
-    let center_x = WINDOW_WIDTH / 2.0;
-    let offset = 200.0;
-    let center_y = offset + (WINDOW_HEIGHT - offset) / 2.0;
-
-    let start_x = center_x - TILE_SIZE * (game.tiles_x as f32 / 2.0).floor();
-    let start_y = center_y - TILE_SIZE * (game.tiles_y as f32 / 2.0).floor();
     for row in 0..game.tiles_y {
         for col in 0..game.tiles_x {
-            let idx = row * game.tiles_x + col;
+            let idx = grid.idx(col, row);
+            let center = grid.tile_center(col, row);
             let color = match game.tiles[idx] {
                 Tile::Clear => TILE_CLEAR_COLOR,
                 Tile::Mine => TILE_CLEAR_COLOR,
@@ -354,26 +438,17 @@ fn render_board(game: &mut Game) {
             };
             vk_util::push_rect_color(
                 cmd,
-                Rect::center_extent(
-                    (start_x + (col as f32) * TILE_SIZE, start_y + (row as f32) * TILE_SIZE),
-                    (TILE_SIZE - 2.0, TILE_SIZE - 2.0),
-                ),
+                Rect::center_extent(center, (TILE_SIZE - 2.0, TILE_SIZE - 2.0)),
                 TILE_BACKGROUND_Z,
                 color,
             );
             match game.tiles[idx] {
                 Tile::MineShown | Tile::MineExploded => {
-                    let offset = (
-                        start_x + (col as f32) * TILE_SIZE - TILE_SIZE / 4.0,
-                        start_y + (row as f32) * TILE_SIZE - 18.0,
-                    );
+                    let offset = (center.x - TILE_SIZE / 4.0, center.y - 18.0);
                     vk_util::push_glyph_color(cmd, &MINE_GLYPH, offset, TILE_FOREGROUND_Z, 6.0, BLACK, false);
                 }
                 Tile::Flagged(_) => {
-                    let offset = (
-                        start_x + (col as f32) * TILE_SIZE - TILE_SIZE / 4.0,
-                        start_y + (row as f32) * TILE_SIZE - 24.0,
-                    );
+                    let offset = (center.x - TILE_SIZE / 4.0, center.y - 24.0);
                     vk_util::push_glyph_color(cmd, &FLAG_GLYPH, offset, TILE_FOREGROUND_Z, 6.0, BLACK, false);
                 }
                 Tile::Neighbors(0) => {}
@@ -389,10 +464,7 @@ fn render_board(game: &mut Game) {
                         8 => GREY,
                         _ => WHITE,
                     };
-                    let offset = (
-                        start_x + (col as f32) * TILE_SIZE - TILE_SIZE / 4.0,
-                        start_y + (row as f32) * TILE_SIZE - 18.0,
-                    );
+                    let offset = (center.x - TILE_SIZE / 4.0, center.y - 18.0);
                     vk_util::push_str_color(cmd, &format!("{}", count), offset, TILE_FOREGROUND_Z, 5.0, color, false);
                 }
                 _ => {}
@@ -407,7 +479,7 @@ fn activate_tile(game: &mut Game, idx: usize) {
     match game.tiles[idx] {
         Tile::Clear => {
             // println!("Activating tile at ({}, {})", col, row);
-            let neighbors = get_neighbors(game, row as isize, col as isize);
+            let neighbors = get_neighbors(game, row, col);
             let count = neighbors.iter().filter(|t| matches!(t.1, Tile::Mine | Tile::Flagged(true))).count();
             game.tiles[idx] = Tile::Neighbors(count);
             if count == 0 {
@@ -433,39 +505,31 @@ fn activate_tile(game: &mut Game, idx: usize) {
     }
 }
 
-fn get_tile_from_pos(game: &Game, x: i32, y: i32) -> Option<usize> {
+// Geometry of the board, centered horizontally below the header.
+fn board_grid(game: &Game) -> Grid {
     let center_x = WINDOW_WIDTH / 2.0;
     let offset = 200.0;
     let center_y = offset + (WINDOW_HEIGHT - offset) / 2.0;
 
-    let start_x = center_x - TILE_SIZE * (game.tiles_x as f32 / 2.0).floor();
-    let start_y = center_y - TILE_SIZE * (game.tiles_y as f32 / 2.0).floor();
-    for row in 0..game.tiles_y {
-        for col in 0..game.tiles_x {
-            let idx = row * game.tiles_x + col;
-            let rect = Rect::center_extent(
-                (start_x + (col as f32) * TILE_SIZE, start_y + (row as f32) * TILE_SIZE),
-                (TILE_SIZE - 2.0, TILE_SIZE - 2.0),
-            );
-            if rect.is_inside((x as f32, y as f32)) {
-                return Some(idx);
-            }
-        }
-    }
-    None
+    let start_x = center_x - TILE_SIZE * (game.tiles_x as f32 / 2.0).floor() - TILE_SIZE / 2.0;
+    let start_y = center_y - TILE_SIZE * (game.tiles_y as f32 / 2.0).floor() - TILE_SIZE / 2.0;
+    Grid::new(game.tiles_x, game.tiles_y, (TILE_SIZE, TILE_SIZE), (start_x, start_y))
 }
 
-fn get_neighbors(game: &Game, row: isize, col: isize) -> Vec<(usize, Tile)> {
-    let mut neighbors = vec![];
-    for j in row - 1..=row + 1 {
-        for i in col - 1..=col + 1 {
-            if j >= 0 && j < (game.tiles_y as isize) && i >= 0 && i < (game.tiles_x as isize) {
-                let idx = (j as usize) * game.tiles_x + (i as usize);
-                neighbors.push((idx, game.tiles[idx]));
-            }
-        }
-    }
-    neighbors
+fn get_tile_from_pos(game: &Game, x: i32, y: i32) -> Option<usize> {
+    let grid = board_grid(game);
+    grid.to_grid((x as f32, y as f32)).map(|(col, row)| grid.idx(col, row))
+}
+
+fn get_neighbors(game: &Game, row: usize, col: usize) -> Vec<(usize, Tile)> {
+    let grid = board_grid(game);
+    grid.neighbors8(col, row)
+        .into_iter()
+        .map(|(c, r)| {
+            let idx = grid.idx(c, r);
+            (idx, game.tiles[idx])
+        })
+        .collect()
 }
 
 #[rustfmt::skip]
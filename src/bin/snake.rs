@@ -34,11 +34,11 @@ fn main() {
     let vertices: [(f32, f32); 4] = [(-1.0, -1.0), (-1.0, 1.0), (1.0, 1.0), (1.0, -1.0)];
     vk_ctx.create_vertex_buffer(&vertices);
 
-    vk_ctx.load_texture_image("assets/textures/snake/snake_head.png");
-    vk_ctx.load_texture_image("assets/textures/snake/snake_body_0.png");
-    vk_ctx.load_texture_image("assets/textures/snake/snake_body_1.png");
-    vk_ctx.load_texture_image("assets/textures/snake/snake_tail.png");
-    vk_ctx.load_texture_image("assets/textures/snake/coin.png");
+    vk_ctx.load_texture_image("assets/textures/snake/snake_head.png").unwrap();
+    vk_ctx.load_texture_image("assets/textures/snake/snake_body_0.png").unwrap();
+    vk_ctx.load_texture_image("assets/textures/snake/snake_body_1.png").unwrap();
+    vk_ctx.load_texture_image("assets/textures/snake/snake_tail.png").unwrap();
+    vk_ctx.load_texture_image("assets/textures/snake/coin.png").unwrap();
     let global_state = (platform.window_width, platform.window_height);
     vk_ctx.update_descriptor_sets(global_state);
 
@@ -0,0 +1,43 @@
+use icarus::spirv_dis::{self, DisassembleOptions};
+
+const USAGE: &str = "Usage: spirv_dis [--raw-id] [--no-header] [--no-indent] <file.spv>";
+
+fn main() {
+    let mut options = DisassembleOptions::default();
+    let mut path = None;
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--raw-id" => options.friendly_names = false,
+            "--no-header" => options.header = false,
+            "--no-indent" => options.indent = false,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ if path.is_none() && !arg.starts_with("--") => path = Some(arg),
+            _ => {
+                eprintln!("{}", USAGE);
+                std::process::exit(2);
+            }
+        }
+    }
+    let Some(path) = path else {
+        eprintln!("{}", USAGE);
+        std::process::exit(2);
+    };
+
+    let bytes = match std::fs::read(&path) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            eprintln!("Failed to read '{}': {}", path, e);
+            std::process::exit(1);
+        }
+    };
+    match spirv_dis::disassemble_bytes(&bytes, &options) {
+        Ok(text) => print!("{}", text),
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            std::process::exit(1);
+        }
+    }
+}
@@ -1,9 +1,10 @@
 use icarus::color;
 use icarus::color::Color;
 use icarus::input::{InputState, KeyId};
-use icarus::math::Rect;
+use icarus::math::{Rect, Vec2};
 use icarus::platform::{Config, Platform};
 use icarus::rand::Rand;
+use icarus::tilemap::Grid;
 use icarus::vk_util::{self, RenderCommand, VkContext};
 
 use std::time::Instant;
@@ -16,6 +17,18 @@ const HEIGHT: f32 = 900.0;
 const TILES_X: isize = 10;
 const TILES_Y: isize = 20;
 const TILE_SIZE: f32 = 30.0;
+const BOARD: Grid = Grid {
+    width: TILES_X as usize,
+    height: TILES_Y as usize,
+    tile_size: Vec2 {
+        x: TILE_SIZE,
+        y: TILE_SIZE,
+    },
+    origin: Vec2 {
+        x: WIDTH / 2.0 - TILE_SIZE * (TILES_X / 2) as f32,
+        y: HEIGHT / 2.0 - TILE_SIZE * (TILES_Y / 2) as f32,
+    },
+};
 const TILE_BG_COLOR: Color = color!(rgb8(15, 15, 15));
 const TEXT_COLOR: Color = color!(hex(0xE5E4E2)); //color::srgb_to_linear(0xE5E4E2).into();
 const BG_COLOR: Color = color!(hex(0x28282B));
@@ -268,18 +281,13 @@ impl Game {
         }
     }
     fn render(&self, cmd: &mut Vec<RenderCommand>) {
-        let start_x = WIDTH / 2.0 - TILE_SIZE * (TILES_X / 2) as f32;
-        let start_y = HEIGHT / 2.0 - TILE_SIZE * (TILES_Y / 2) as f32;
-        for row in 0..TILES_Y {
-            for col in 0..TILES_X {
-                let idx = pos_to_idx(col, row);
-                let tile = self.tiles[idx];
+        let (start_x, start_y) = (BOARD.origin.x, BOARD.origin.y);
+        for row in 0..BOARD.height {
+            for col in 0..BOARD.width {
+                let tile = self.tiles[BOARD.idx(col, row)];
                 vk_util::push_rect_color(
                     cmd,
-                    Rect::offset_extent(
-                        (start_x + col as f32 * TILE_SIZE, start_y + row as f32 * TILE_SIZE),
-                        (TILE_SIZE - 1.0, TILE_SIZE - 1.0),
-                    ),
+                    Rect::offset_extent(BOARD.to_world(col, row), (TILE_SIZE - 1.0, TILE_SIZE - 1.0)),
                     0.2,
                     tile.color,
                 );
@@ -552,7 +560,7 @@ impl Game {
 }
 
 fn pos_to_idx(x: isize, y: isize) -> usize {
-    (y * TILES_X + x) as usize
+    BOARD.idx(x as usize, y as usize)
 }
 
 fn main() {
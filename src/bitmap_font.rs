@@ -0,0 +1,612 @@
+use crate::glyph::{bitmap_char, GLYPHS, GLYPH_HEIGHT, GLYPH_WIDTH, REPLACEMENT_GLYPH};
+use crate::text_layout::LayoutFont;
+use crate::vk_util::load_image_rgba;
+
+use std::collections::HashMap;
+use std::path::Path;
+
+// Bitmap (pixel) fonts:
+// Glyphs are 1-bit images placed relative to the pen position on the baseline, all the metrics are in font pixels.
+// Fonts can be loaded from:
+//   - BDF (Glyph Bitmap Distribution Format) text files, encodings are taken as Unicode code points.
+//   - PSF (PC Screen Font) version 1 and 2 console fonts, with their Unicode table if they have one. The format
+//     has no baseline, the baseline is the bottom of the cells.
+//   - Images with a grid of fixed size cells, one per character of `GridOptions::chars` in reading order.
+// `BitmapFont::builtin` is the 5x7 font of `glyph::GLYPHS`.
+
+const PSF1_MAGIC: [u8; 2] = [0x36, 0x04];
+const PSF1_MODE_512: u8 = 0x01;
+const PSF1_MODE_HAS_TABLE: u8 = 0x02 | 0x04;
+const PSF1_SEPARATOR: u16 = 0xFFFF;
+const PSF1_START_SEQUENCE: u16 = 0xFFFE;
+const PSF2_MAGIC: [u8; 4] = [0x72, 0xB5, 0x4A, 0x86];
+const PSF2_HAS_UNICODE_TABLE: u32 = 0x01;
+const PSF2_SEPARATOR: u8 = 0xFF;
+const PSF2_START_SEQUENCE: u8 = 0xFE;
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct BitmapGlyph {
+    pub width: u32,
+    pub height: u32,
+    /// Offset from the pen position on the baseline to the top-left corner, y down
+    pub left: i32,
+    pub top: i32,
+    pub advance: u32,
+    /// 1 for the set pixels, 0 for the others, rows from top to bottom
+    pub pixels: Vec<u8>,
+}
+
+impl BitmapGlyph {
+    pub fn get(&self, x: u32, y: u32) -> bool {
+        self.pixels[(y * self.width + x) as usize] != 0
+    }
+
+    /// Outline of a box filling the cell, for the characters a font doesn't have.
+    fn replacement(width: u32, ascent: u32, descent: u32) -> Self {
+        let height = ascent + descent;
+        let (width, advance) = (width.saturating_sub(1).max(1), width.max(1));
+        let pixels = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x == 0 || y == 0 || x + 1 == width || y + 1 == height) as u8))
+            .collect();
+        Self {
+            width,
+            height,
+            left: 0,
+            top: -(ascent as i32),
+            advance,
+            pixels,
+        }
+    }
+}
+
+/// Layout of a font image, see `BitmapFont::from_grid`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GridOptions {
+    pub cell_width: u32,
+    pub cell_height: u32,
+    /// Empty pixels around the glyph on every side of a cell
+    pub padding: u32,
+    /// Characters of the cells in reading order, the remaining cells are ignored
+    pub chars: String,
+    /// Rows of the glyphs above the baseline, by default all of them
+    pub ascent: Option<u32>,
+    /// By default the glyph width plus one pixel of spacing
+    pub advance: Option<u32>,
+}
+
+impl Default for GridOptions {
+    fn default() -> Self {
+        Self {
+            cell_width: 8,
+            cell_height: 8,
+            padding: 0,
+            chars: (' '..='~').collect(),
+            ascent: None,
+            advance: None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct BitmapFont {
+    pub name: String,
+    /// Pixels above and below the baseline
+    pub ascent: u32,
+    pub descent: u32,
+    pub line_gap: u32,
+    pub glyphs: HashMap<char, BitmapGlyph>,
+    /// Drawn for the characters missing in `glyphs`
+    pub replacement: BitmapGlyph,
+}
+
+/// BDF bounding box: width, height and offset from the pen position to the bottom-left corner, y up
+type BoundingBox = (u32, u32, i32, i32);
+
+fn parse_number<T: std::str::FromStr>(token: Option<&str>, line: usize) -> Result<T, String> {
+    let token = token.ok_or_else(|| format!("line {}: Missing number", line))?;
+    token.parse().map_err(|_| format!("line {}: Invalid number '{}'", line, token))
+}
+
+impl BitmapFont {
+    /// Loads a BDF, PSF or PSF2 font, recognized by their contents.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let font = if bytes.starts_with(&PSF1_MAGIC) || bytes.starts_with(&PSF2_MAGIC) {
+            Self::parse_psf(&bytes)
+        } else if bytes.starts_with(b"STARTFONT") {
+            Self::parse_bdf(&String::from_utf8_lossy(&bytes))
+        } else {
+            Err(String::from("Unknown font format"))
+        };
+        font.map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Loads a font from an image with a grid of glyphs, pixels brighter than half are set.
+    pub fn load_grid<P: AsRef<str>>(path: P, options: &GridOptions) -> Result<Self, String> {
+        let (rgba, width, height) = load_image_rgba(path)?;
+        let pixels: Vec<u8> = rgba
+            .chunks_exact(4)
+            .map(|p| (p[0] as u32 + p[1] as u32 + p[2] as u32 >= 3 * 128 && p[3] >= 128) as u8)
+            .collect();
+        Self::from_grid(&pixels, width as u32, height as u32, options)
+    }
+
+    /// Font from the pixels of a grid image (non-zero pixels are set), see `GridOptions`.
+    pub fn from_grid(pixels: &[u8], width: u32, height: u32, options: &GridOptions) -> Result<Self, String> {
+        let (glyph_width, glyph_height) = (
+            options.cell_width.saturating_sub(2 * options.padding),
+            options.cell_height.saturating_sub(2 * options.padding),
+        );
+        if glyph_width == 0 || glyph_height == 0 {
+            return Err(format!(
+                "Invalid {}x{} cells with padding {}",
+                options.cell_width, options.cell_height, options.padding
+            ));
+        }
+        if pixels.len() != (width * height) as usize {
+            return Err(format!("Expected {} pixels, found {}", width * height, pixels.len()));
+        }
+        let columns = width / options.cell_width;
+        let cells = columns * (height / options.cell_height);
+        let count = options.chars.chars().count() as u32;
+        if count > cells {
+            return Err(format!("{} characters for {} cells", count, cells));
+        }
+        let ascent = options.ascent.unwrap_or(glyph_height).min(glyph_height);
+        let advance = options.advance.unwrap_or(glyph_width + 1);
+
+        let mut font = Self {
+            ascent,
+            descent: glyph_height - ascent,
+            replacement: BitmapGlyph::replacement(advance, ascent, glyph_height - ascent),
+            ..Default::default()
+        };
+        for (i, c) in options.chars.chars().enumerate() {
+            let x0 = (i as u32 % columns) * options.cell_width + options.padding;
+            let y0 = (i as u32 / columns) * options.cell_height + options.padding;
+            let glyph_pixels = (y0..y0 + glyph_height)
+                .flat_map(|y| (x0..x0 + glyph_width).map(move |x| (pixels[(y * width + x) as usize] != 0) as u8))
+                .collect();
+            font.glyphs.insert(
+                c,
+                BitmapGlyph {
+                    width: glyph_width,
+                    height: glyph_height,
+                    left: 0,
+                    top: -(ascent as i32),
+                    advance,
+                    pixels: glyph_pixels,
+                },
+            );
+        }
+        Ok(font)
+    }
+
+    pub fn parse_bdf(text: &str) -> Result<Self, String> {
+        let mut font = Self::default();
+        let (mut ascent, mut descent) = (None, None);
+        let mut bounding_box = (0, 0, 0, 0);
+        let mut default_char = None;
+        // Character being parsed: encoding, advance and bounding box, then its bitmap with the rows read so far
+        let mut glyph: Option<(i64, u32, BoundingBox)> = None;
+        let mut bitmap: Option<(BitmapGlyph, u32)> = None;
+
+        for (line_idx, line) in text.lines().enumerate() {
+            let line_number = line_idx + 1;
+            if let Some((ref mut current, ref mut row)) = bitmap {
+                if line.trim() == "ENDCHAR" {
+                    if *row != current.height {
+                        return Err(format!("line {}: Missing bitmap rows", line_number));
+                    }
+                    let (encoding, ..) = glyph.take().unwrap();
+                    let (current, _) = bitmap.take().unwrap();
+                    if let Some(c) = u32::try_from(encoding).ok().and_then(char::from_u32) {
+                        font.glyphs.insert(c, current);
+                    }
+                    continue;
+                }
+                if *row >= current.height {
+                    return Err(format!("line {}: Too many bitmap rows", line_number));
+                }
+                let digits = line.trim();
+                let bits = (0..current.width).map(|x| {
+                    let digit = digits.as_bytes().get(x as usize / 4).and_then(|&d| (d as char).to_digit(16));
+                    digit.map(|d| ((d >> (3 - x % 4)) & 1) as u8)
+                });
+                let bits: Option<Vec<u8>> = bits.collect();
+                let bits = bits.ok_or_else(|| format!("line {}: Invalid bitmap row '{}'", line_number, digits))?;
+                current.pixels.extend(bits);
+                *row += 1;
+                continue;
+            }
+
+            let mut tokens = line.split_whitespace();
+            let keyword = tokens.next().unwrap_or("");
+            let mut number = || parse_number::<i64>(tokens.next(), line_number);
+            match keyword {
+                "FONT" => font.name = line["FONT".len()..].trim().to_string(),
+                "FONTBOUNDINGBOX" => bounding_box = (number()?, number()?, number()?, number()?),
+                "FONT_ASCENT" => ascent = Some(number()?),
+                "FONT_DESCENT" => descent = Some(number()?),
+                "DEFAULT_CHAR" => default_char = Some(number()?),
+                "STARTCHAR" => glyph = Some((-1, bounding_box.0 as u32, (0, 0, 0, 0))),
+                "ENCODING" | "DWIDTH" | "BBX" => {
+                    let Some((encoding, advance, bbx)) = glyph.as_mut() else {
+                        return Err(format!("line {}: {} outside of a character", line_number, keyword));
+                    };
+                    match keyword {
+                        "ENCODING" => *encoding = number()?,
+                        "DWIDTH" => *advance = number()? as u32,
+                        _ => *bbx = (number()? as u32, number()? as u32, number()? as i32, number()? as i32),
+                    }
+                }
+                "BITMAP" => {
+                    let Some((_, advance, (width, height, x, y))) = glyph else {
+                        return Err(format!("line {}: BITMAP outside of a character", line_number));
+                    };
+                    // The bounding box offset is from the pen to the bottom-left corner, y up
+                    let current = BitmapGlyph {
+                        width,
+                        height,
+                        left: x,
+                        top: -(y + height as i32),
+                        advance,
+                        pixels: Vec::with_capacity((width * height) as usize),
+                    };
+                    bitmap = Some((current, 0));
+                }
+                _ => {}
+            }
+        }
+        if bitmap.is_some() {
+            return Err(String::from("Missing ENDCHAR"));
+        }
+        if font.glyphs.is_empty() {
+            return Err(String::from("No characters"));
+        }
+
+        let (width, height, _, y) = bounding_box;
+        font.ascent = ascent.unwrap_or(height + y).max(0) as u32;
+        font.descent = descent.unwrap_or(-y).max(0) as u32;
+        let default_char = default_char.and_then(|c| u32::try_from(c).ok()).and_then(char::from_u32);
+        font.replacement = match default_char.and_then(|c| font.glyphs.get(&c)) {
+            Some(glyph) => glyph.clone(),
+            None => BitmapGlyph::replacement(width as u32, font.ascent, font.descent),
+        };
+        Ok(font)
+    }
+
+    pub fn parse_psf(bytes: &[u8]) -> Result<Self, String> {
+        let u32_at = |offset: usize| -> Result<u32, String> {
+            let b = bytes.get(offset..offset + 4).ok_or("Truncated PSF2 header")?;
+            Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        };
+        let (glyph_count, glyph_size, width, height, glyphs_offset, has_table) = if bytes.starts_with(&PSF1_MAGIC) {
+            let (mode, height) = match bytes.get(2..4) {
+                Some(header) => (header[0], header[1] as u32),
+                None => return Err(String::from("Truncated PSF header")),
+            };
+            let count = if mode & PSF1_MODE_512 != 0 {
+                512
+            } else {
+                256
+            };
+            (count, height, 8, height, 4, mode & PSF1_MODE_HAS_TABLE != 0)
+        } else if bytes.starts_with(&PSF2_MAGIC) {
+            let header_size = u32_at(8)? as usize;
+            let flags = u32_at(12)?;
+            (u32_at(16)?, u32_at(20)?, u32_at(28)?, u32_at(24)?, header_size, flags & PSF2_HAS_UNICODE_TABLE != 0)
+        } else {
+            return Err(String::from("Not a PSF font"));
+        };
+        // The header fields are untrusted, sizes are computed in usize and checked for overflow
+        let (glyph_count, glyph_size) = (glyph_count as usize, glyph_size as usize);
+        let row_size = width.div_ceil(8) as usize;
+        let bitmap_size = row_size.checked_mul(height as usize);
+        if width == 0 || height == 0 || bitmap_size.is_none_or(|size| glyph_size < size) {
+            return Err(format!("Invalid {}x{} glyphs of {} bytes", width, height, glyph_size));
+        }
+        let table_offset = glyph_count
+            .checked_mul(glyph_size)
+            .and_then(|size| size.checked_add(glyphs_offset))
+            .filter(|&offset| offset <= bytes.len())
+            .ok_or("Truncated glyphs")?;
+
+        // Characters of every glyph, the glyph index is the character without a table
+        let mut chars: Vec<Vec<char>> = vec![];
+        if has_table {
+            let mut table = &bytes[table_offset..];
+            for _ in 0..glyph_count {
+                let mut glyph_chars = vec![];
+                if bytes.starts_with(&PSF1_MAGIC) {
+                    let mut in_sequence = false;
+                    loop {
+                        let Some((value, rest)) = table.split_first_chunk::<2>() else {
+                            return Err(String::from("Truncated Unicode table"));
+                        };
+                        table = rest;
+                        match u16::from_le_bytes(*value) {
+                            PSF1_SEPARATOR => break,
+                            PSF1_START_SEQUENCE => in_sequence = true,
+                            // Sequences (a character with combining marks) can't be looked up by character
+                            _ if in_sequence => {}
+                            value => glyph_chars.extend(char::from_u32(value as u32)),
+                        }
+                    }
+                } else {
+                    let end = table.iter().position(|&b| b == PSF2_SEPARATOR).ok_or("Truncated Unicode table")?;
+                    let entry = &table[..end];
+                    let single = entry.split(|&b| b == PSF2_START_SEQUENCE).next().unwrap_or(&[]);
+                    glyph_chars
+                        .extend(String::from_utf8_lossy(single).chars().filter(|&c| c != char::REPLACEMENT_CHARACTER));
+                    table = &table[end + 1..];
+                }
+                chars.push(glyph_chars);
+            }
+        } else {
+            chars = (0..glyph_count).map(|i| char::from_u32(i as u32).into_iter().collect()).collect();
+        }
+
+        let mut font = Self {
+            ascent: height,
+            descent: 0,
+            replacement: BitmapGlyph::replacement(width, height, 0),
+            ..Default::default()
+        };
+        for (i, glyph_chars) in chars.into_iter().enumerate() {
+            let data = &bytes[glyphs_offset + i * glyph_size..];
+            let pixels = (0..height as usize)
+                .flat_map(|y| (0..width as usize).map(move |x| (data[y * row_size + x / 8] >> (7 - x % 8)) & 1))
+                .collect();
+            let glyph = BitmapGlyph {
+                width,
+                height,
+                left: 0,
+                top: -(height as i32),
+                advance: width,
+                pixels,
+            };
+            for c in glyph_chars {
+                font.glyphs.entry(c).or_insert_with(|| glyph.clone());
+            }
+        }
+        if let Some(glyph) = font.glyphs.get(&char::REPLACEMENT_CHARACTER) {
+            font.replacement = glyph.clone();
+        }
+        Ok(font)
+    }
+
+    /// The 5x7 font of `glyph::GLYPHS`, with accented Latin-1 letters and typographic punctuation drawn like their
+    /// closest ASCII character (see `glyph::bitmap_char`).
+    pub fn builtin() -> Self {
+        let glyph = |pixels: &[u8]| BitmapGlyph {
+            width: GLYPH_WIDTH as u32,
+            height: GLYPH_HEIGHT as u32,
+            left: 0,
+            top: -(GLYPH_HEIGHT as i32),
+            advance: GLYPH_WIDTH as u32 + 1,
+            pixels: pixels.to_vec(),
+        };
+        let chars = (' '..='~').chain('\u{A0}'..='\u{FF}').chain('\u{2000}'..='\u{2212}');
+        let glyphs = chars
+            .filter_map(|c| bitmap_char(c).map(|ascii| (c, glyph(&GLYPHS[ascii as usize - ' ' as usize]))))
+            .collect();
+        Self {
+            name: String::from("builtin"),
+            ascent: GLYPH_HEIGHT as u32,
+            descent: 0,
+            line_gap: 2,
+            glyphs,
+            replacement: glyph(&REPLACEMENT_GLYPH),
+        }
+    }
+
+    /// Glyph of `c`, the replacement glyph if the font has none.
+    pub fn glyph(&self, c: char) -> &BitmapGlyph {
+        self.glyphs.get(&c).unwrap_or(&self.replacement)
+    }
+
+    pub fn line_height(&self) -> u32 {
+        self.ascent + self.descent + self.line_gap
+    }
+}
+
+// One em is the height of a line without the gap, text sizes are then multiples of the font pixels
+impl LayoutFont for BitmapFont {
+    fn advance(&self, c: char) -> f32 {
+        if crate::unicode::is_combining_mark(c) && !self.glyphs.contains_key(&c) {
+            return 0.0;
+        }
+        self.glyph(c).advance as f32 / (self.ascent + self.descent) as f32
+    }
+
+    fn kerning(&self, _left: char, _right: char) -> f32 {
+        0.0
+    }
+
+    fn line_metrics(&self) -> (f32, f32, f32) {
+        let em = (self.ascent + self.descent) as f32;
+        (self.ascent as f32 / em, -(self.descent as f32) / em, self.line_gap as f32 / em)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Rows of a glyph as '#' and '.'
+    fn rows(glyph: &BitmapGlyph) -> Vec<String> {
+        (0..glyph.height)
+            .map(|y| {
+                (0..glyph.width)
+                    .map(|x| {
+                        if glyph.get(x, y) {
+                            '#'
+                        } else {
+                            '.'
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    const BDF: &str = "STARTFONT 2.1
+FONT -Test-Fixed-Medium-R-Normal--6-60-75-75-C-40-ISO10646-1
+SIZE 6 75 75
+FONTBOUNDINGBOX 4 6 0 -1
+STARTPROPERTIES 3
+FONT_ASCENT 5
+FONT_DESCENT 1
+DEFAULT_CHAR 63
+ENDPROPERTIES
+CHARS 3
+STARTCHAR A
+ENCODING 65
+SWIDTH 666 0
+DWIDTH 4 0
+BBX 3 5 0 0
+BITMAP
+40
+A0
+E0
+A0
+A0
+ENDCHAR
+STARTCHAR question
+ENCODING 63
+DWIDTH 4 0
+BBX 3 4 0 1
+BITMAP
+E0
+20
+00
+40
+ENDCHAR
+STARTCHAR g
+ENCODING 103
+DWIDTH 5 0
+BBX 4 4 0 -1
+BITMAP
+70
+90
+70
+E0
+ENDCHAR
+ENDFONT
+";
+
+    #[test]
+    fn bdf() {
+        let font = BitmapFont::parse_bdf(BDF).unwrap();
+        assert_eq!(font.name, "-Test-Fixed-Medium-R-Normal--6-60-75-75-C-40-ISO10646-1");
+        assert_eq!((font.ascent, font.descent, font.glyphs.len()), (5, 1, 3));
+        let a = font.glyph('A');
+        assert_eq!(rows(a), [".#.", "#.#", "###", "#.#", "#.#"]);
+        assert_eq!((a.left, a.top, a.advance), (0, -5, 4));
+        let g = font.glyph('g');
+        assert_eq!(rows(g), [".###", "#..#", ".###", "###."]);
+        assert_eq!((g.top, g.advance), (-3, 5));
+        // DEFAULT_CHAR is drawn for the missing characters
+        assert_eq!(font.glyph('Z'), font.glyph('?'));
+        assert_eq!(font.glyph('?').top, -5);
+
+        assert!(BitmapFont::parse_bdf(&BDF.replace("A0\nENDCHAR", "ENDCHAR"))
+            .unwrap_err()
+            .contains("Missing bitmap rows"));
+        assert!(BitmapFont::parse_bdf(&BDF.replace("70\n90", "G0\n90")).unwrap_err().contains("Invalid bitmap row"));
+        assert!(BitmapFont::parse_bdf("STARTFONT 2.1\nENDFONT\n").is_err());
+    }
+
+    /// 10x2 glyphs: the glyph index on the first row, its bits reversed on the second.
+    fn psf_glyphs(count: usize) -> Vec<u8> {
+        (0..count).flat_map(|i| [i as u8, 0x00, (i as u8).reverse_bits(), 0xC0]).collect()
+    }
+
+    #[test]
+    fn psf() {
+        // Version 1 is 8 pixels wide, without a table the glyph index is the character
+        let psf1 = [PSF1_MAGIC.to_vec(), vec![0, 1], (0..=255u8).collect()].concat();
+        let font = BitmapFont::parse_psf(&psf1).unwrap();
+        assert_eq!(font.glyphs.len(), 256);
+        assert_eq!(rows(font.glyph('A')), [".#.....#"]);
+        assert_eq!((font.ascent, font.descent, font.glyph('A').top), (1, 0, -1));
+
+        // Version 2 with a Unicode table: glyph 1 is 'x' and 'é', glyph 2 is '中' and 'é' as a sequence
+        let header = [
+            PSF2_MAGIC.to_vec(),
+            [0, 32, PSF2_HAS_UNICODE_TABLE, 3, 4, 2, 10].iter().flat_map(|v: &u32| v.to_le_bytes()).collect(),
+        ]
+        .concat();
+        let table = [
+            b"a\xFF".to_vec(),
+            ["x".as_bytes(), "é".as_bytes(), b"\xFF"].concat(),
+            ["中".as_bytes(), b"\xFE", "e\u{301}".as_bytes(), b"\xFF"].concat(),
+        ]
+        .concat();
+        let psf2 = [header, psf_glyphs(3), table].concat();
+        let font = BitmapFont::parse_psf(&psf2).unwrap();
+        assert_eq!(font.glyphs.len(), 4);
+        assert_eq!(rows(font.glyph('x')), [".......#..", "#.......##"]);
+        assert_eq!(font.glyph('x'), font.glyph('é'));
+        assert_eq!(rows(font.glyph('a')), ["..........", "........##"]);
+        assert_eq!(font.glyph('中').advance, 10);
+        assert_eq!(rows(font.glyph('b')), ["#########", "#########"]);
+
+        assert!(BitmapFont::parse_psf(&psf2[..40]).unwrap_err().contains("Truncated glyphs"));
+        assert!(BitmapFont::parse_psf(&psf2[..psf2.len() - 1]).unwrap_err().contains("Unicode table"));
+
+        // Header fields whose products overflow 32 bits
+        let header_only =
+            |fields: [u32; 7]| [PSF2_MAGIC.to_vec(), fields.iter().flat_map(|v| v.to_le_bytes()).collect()].concat();
+        let error = BitmapFont::parse_psf(&header_only([0, 32, 0, u32::MAX, 0x10000, 2, 10])).unwrap_err();
+        assert!(error.contains("Truncated glyphs"));
+        let error = BitmapFont::parse_psf(&header_only([0, 32, 0, 1, 4, 0x8000_0000, 10])).unwrap_err();
+        assert!(error.contains("Invalid"));
+    }
+
+    #[test]
+    fn grid() {
+        // 2x1 cells of 4x4 pixels with 1 pixel of padding: a dot and a bar
+        #[rustfmt::skip]
+        let pixels = [
+            0, 0, 0, 0,  0, 0, 0, 0,
+            0, 1, 0, 0,  0, 1, 1, 0,
+            0, 0, 0, 0,  0, 1, 1, 0,
+            0, 0, 0, 0,  0, 0, 0, 0,
+        ];
+        let options = GridOptions {
+            cell_width: 4,
+            cell_height: 4,
+            padding: 1,
+            chars: String::from(".|"),
+            ascent: Some(1),
+            ..Default::default()
+        };
+        let font = BitmapFont::from_grid(&pixels, 8, 4, &options).unwrap();
+        assert_eq!(rows(font.glyph('.')), ["#.", ".."]);
+        assert_eq!(rows(font.glyph('|')), ["##", "##"]);
+        assert_eq!((font.ascent, font.descent, font.glyph('|').top, font.glyph('|').advance), (1, 1, -1, 3));
+        assert_eq!(rows(font.glyph('x')), ["##", "##"]);
+
+        let too_many = GridOptions {
+            chars: String::from(".|-"),
+            ..options.clone()
+        };
+        assert!(BitmapFont::from_grid(&pixels, 8, 4, &too_many).is_err());
+        assert!(BitmapFont::from_grid(&pixels[1..], 8, 4, &options).is_err());
+    }
+
+    #[test]
+    fn builtin() {
+        let font = BitmapFont::builtin();
+        let a = font.glyph('a');
+        assert_eq!(a.pixels, GLYPHS['a' as usize - ' ' as usize]);
+        assert_eq!((a.width, a.height, a.top, a.advance), (5, 7, -7, 6));
+        assert_eq!(font.glyph('é'), font.glyph('e'));
+        assert_eq!(font.glyph('中').pixels, REPLACEMENT_GLYPH);
+        assert_eq!(font.line_height(), 9);
+        assert_eq!(font.line_metrics(), (1.0, 0.0, 2.0 / 7.0));
+    }
+}
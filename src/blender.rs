@@ -1,3 +1,4 @@
+use crate::image::RgbaImage;
 use crate::parsing::*;
 
 use std::io::Cursor;
@@ -33,6 +34,8 @@ impl std::fmt::Debug for DnaField {
 pub struct Blend {
     pub blocks: Vec<BlendBlock>,
     pub dna: Dna,
+    /// Preview stored by Blender when saving, `thumbnail.save("thumb.png")` writes it out
+    pub thumbnail: Option<RgbaImage>,
 }
 #[derive(Debug, Default)]
 pub struct BlendBlock {
@@ -204,6 +207,7 @@ pub fn parse_blend(bytes: &[u8]) -> std::io::Result<Blend> {
     let mut edges = vec![];
     let mut loops = vec![];
     let mut polys = vec![];
+    let mut thumbnail = None;
     for block in &blend.blocks {
         match block.tag.as_str() {
             "REND" => {
@@ -280,16 +284,17 @@ pub fn parse_blend(bytes: &[u8]) -> std::io::Result<Blend> {
             "TEST" => {
                 // Thumbnail
                 let mut r = Cursor::new(&block.data);
-                let _width = read_u32_le(&mut r)?;
-                let _height = read_u32_le(&mut r)?;
-                let _pixels = &block.data[8..];
-                //println!("Thumbnail: {}x{}, pixels: {}\n", width, height, pixels.len());
-
-                // Write as .ppm
-                //let pixels = data[8..].chunks(4).map(|c| &c[..3]).flatten().map(|b| *b).collect::<Vec<_>>();
-                //let mut file = fs::File::create("/home/alex/blendthumb.ppm")?;
-                //file.write_all(format!("P6\n{} {}\n255\n", width, height).as_bytes())?;
-                //file.write_all(&pixels)?;
+                let width = read_u32_le(&mut r)?;
+                let height = read_u32_le(&mut r)?;
+                let invalid = |message| std::io::Error::new(std::io::ErrorKind::InvalidData, message);
+                let end = (width as usize)
+                    .checked_mul(height as usize)
+                    .and_then(|count| count.checked_mul(4))
+                    .and_then(|size| size.checked_add(8))
+                    .ok_or_else(|| invalid("Invalid thumbnail size"))?;
+                let pixels = block.data.get(8..end).ok_or_else(|| invalid("Truncated thumbnail"))?;
+                // RGBA rows from bottom to top
+                thumbnail = Some(RgbaImage::from_pixels(pixels.to_vec(), width, height).flip_vertical());
             }
             "USER" => {
                 // UserDef
@@ -372,6 +377,7 @@ pub fn parse_blend(bytes: &[u8]) -> std::io::Result<Blend> {
         }
     }
     //println!("{} {:#?}", data_structs_set.len(), data_structs_set);
+    blend.thumbnail = thumbnail;
 
     Ok(blend)
 }
@@ -406,7 +412,9 @@ mod tests {
             println!("{:?}", path);
             let bytes = fs::read(path).unwrap();
             assert!(bytes.len() != 0);
-            let _ = parse_blend(&bytes)?;
+            let blend = parse_blend(&bytes)?;
+            let thumbnail = blend.thumbnail.expect("Missing thumbnail");
+            assert_eq!((thumbnail.width, thumbnail.height), (128, 128));
         }
         Ok(())
     }
@@ -0,0 +1,265 @@
+use crate::parsing::{get_bytes, get_u16_le, get_u32_le};
+
+// BMP images:
+// Decodes uncompressed 8-bit (palette), 24-bit and 32-bit Windows bitmaps (BI_RGB or BI_BITFIELDS, bottom-up or
+// top-down) to RGBA8. Encodes opaque images as 24-bit and the others as 32-bit with an alpha mask (BITMAPV4HEADER),
+// which most tools read.
+
+const FILE_HEADER_SIZE: usize = 14;
+const INFO_HEADER_SIZE: usize = 40;
+const V4_HEADER_SIZE: usize = 108;
+const BI_RGB: u32 = 0;
+const BI_BITFIELDS: u32 = 3;
+const BI_ALPHABITFIELDS: u32 = 6;
+/// 'sRGB' color space of the V4 header
+const LCS_SRGB: u32 = 0x73524742;
+
+/// Value of the bits of `mask` in `pixel` scaled to 8 bits, `default` for an empty mask.
+fn extract(pixel: u32, mask: u32, default: u8) -> u8 {
+    if mask == 0 {
+        return default;
+    }
+    let bits = mask.count_ones();
+    // In 64 bits, as masks may be up to 32 bits wide
+    let value = ((pixel & mask) >> mask.trailing_zeros()) as u64;
+    (value * 255 / ((1u64 << bits) - 1)) as u8
+}
+
+/// Decodes a BMP file, returns (RGBA8 pixels, width, height).
+pub fn decode_bmp(bytes: &[u8]) -> Result<(Vec<u8>, usize, usize), String> {
+    if !bytes.starts_with(b"BM") || bytes.len() < FILE_HEADER_SIZE + 4 {
+        return Err(String::from("Not a BMP file"));
+    }
+    let data_offset = get_u32_le(bytes, 10)? as usize;
+    let header_size = get_u32_le(bytes, FILE_HEADER_SIZE)? as usize;
+    let header = get_bytes(bytes, FILE_HEADER_SIZE, header_size).map_err(|_| "Truncated BMP header")?;
+    let (width, height, bits_per_pixel, compression) = match header_size {
+        // OS/2 BITMAPCOREHEADER
+        12 => (get_u16_le(header, 4)? as i32, get_u16_le(header, 6)? as i16 as i32, get_u16_le(header, 10)?, BI_RGB),
+        40 | 52 | 56 | 108 | 124 => (
+            get_u32_le(header, 4)? as i32,
+            get_u32_le(header, 8)? as i32,
+            get_u16_le(header, 14)?,
+            get_u32_le(header, 16)?,
+        ),
+        _ => return Err(format!("Unsupported BMP header size {}", header_size)),
+    };
+    if width <= 0 || height == 0 || height == i32::MIN {
+        return Err(format!("Invalid size {}x{}", width, height));
+    }
+    // Negative heights are stored top-down
+    let (width, height, top_down) = (width as usize, height.unsigned_abs() as usize, height < 0);
+
+    let masks = match (compression, bits_per_pixel) {
+        (BI_RGB, 24) => [0xFF0000, 0xFF00, 0xFF, 0],
+        // The fourth byte is unused in BI_RGB, but some tools store alpha there (see below)
+        (BI_RGB, 32) => [0xFF0000, 0xFF00, 0xFF, 0xFF000000],
+        (BI_RGB, 8) => [0; 4],
+        (BI_BITFIELDS | BI_ALPHABITFIELDS, 16 | 32) => {
+            // The masks follow a BITMAPINFOHEADER, or are part of the larger headers
+            let masks = get_bytes(bytes, FILE_HEADER_SIZE + 40, 16).map_err(|_| "Missing BMP bit masks")?;
+            let alpha = if header_size > 52 || compression == BI_ALPHABITFIELDS {
+                get_u32_le(masks, 12)?
+            } else {
+                0
+            };
+            [get_u32_le(masks, 0)?, get_u32_le(masks, 4)?, get_u32_le(masks, 8)?, alpha]
+        }
+        _ => return Err(format!("Unsupported BMP format: {} bits, compression {}", bits_per_pixel, compression)),
+    };
+    let palette: Vec<[u8; 4]> = if bits_per_pixel == 8 {
+        let colors = match get_u32_le(header, 32)? {
+            0 => 256,
+            n => n.min(256) as usize,
+        };
+        let entry_size = if header_size == 12 {
+            3
+        } else {
+            4
+        };
+        let start = FILE_HEADER_SIZE + header_size;
+        let table = get_bytes(bytes, start, colors * entry_size).map_err(|_| "Truncated BMP palette")?;
+        table.chunks_exact(entry_size).map(|c| [c[2], c[1], c[0], 255]).collect()
+    } else {
+        vec![]
+    };
+
+    let bytes_per_pixel = bits_per_pixel as usize / 8;
+    let row_size = (width * bits_per_pixel as usize).div_ceil(32) * 4;
+    let data = row_size
+        .checked_mul(height)
+        .and_then(|size| get_bytes(bytes, data_offset, size).ok())
+        .ok_or("Truncated BMP pixel data")?;
+    let mut pixels = vec![0; width * height * 4];
+    for y in 0..height {
+        let row = &data[y * row_size..];
+        let out_y = if top_down {
+            y
+        } else {
+            height - 1 - y
+        };
+        for x in 0..width {
+            let p = &row[x * bytes_per_pixel..(x + 1) * bytes_per_pixel];
+            let rgba = if bits_per_pixel == 8 {
+                *palette.get(p[0] as usize).ok_or_else(|| format!("Invalid palette index {}", p[0]))?
+            } else {
+                let value = p.iter().rev().fold(0, |value, &b| value << 8 | b as u32);
+                [
+                    extract(value, masks[0], 0),
+                    extract(value, masks[1], 0),
+                    extract(value, masks[2], 0),
+                    extract(value, masks[3], 255),
+                ]
+            };
+            let i = (out_y * width + x) * 4;
+            pixels[i..i + 4].copy_from_slice(&rgba);
+        }
+    }
+    // 32-bit BI_RGB files written without alpha have only zeros in the fourth byte
+    if compression == BI_RGB && bits_per_pixel == 32 && pixels.chunks_exact(4).all(|p| p[3] == 0) {
+        pixels.chunks_exact_mut(4).for_each(|p| p[3] = 255);
+    }
+    Ok((pixels, width, height))
+}
+
+/// Encodes RGBA8 pixels as a bottom-up BMP file, 24-bit if every pixel is opaque, 32-bit with alpha otherwise.
+pub fn encode_bmp(pixels: &[u8], width: usize, height: usize) -> Vec<u8> {
+    assert_eq!(pixels.len(), width * height * 4);
+    let opaque = pixels.chunks_exact(4).all(|p| p[3] == 255);
+    let (bits_per_pixel, header_size) = if opaque {
+        (24, INFO_HEADER_SIZE)
+    } else {
+        (32, V4_HEADER_SIZE)
+    };
+    let row_size = (width * bits_per_pixel).div_ceil(32) * 4;
+    let data_offset = FILE_HEADER_SIZE + header_size;
+
+    let mut out = Vec::with_capacity(data_offset + row_size * height);
+    out.extend(b"BM");
+    out.extend(((data_offset + row_size * height) as u32).to_le_bytes());
+    out.extend([0; 4]);
+    out.extend((data_offset as u32).to_le_bytes());
+
+    out.extend((header_size as u32).to_le_bytes());
+    out.extend((width as i32).to_le_bytes());
+    out.extend((height as i32).to_le_bytes());
+    out.extend(1u16.to_le_bytes());
+    out.extend((bits_per_pixel as u16).to_le_bytes());
+    out.extend(
+        (if opaque {
+            BI_RGB
+        } else {
+            BI_BITFIELDS
+        })
+        .to_le_bytes(),
+    );
+    out.extend(((row_size * height) as u32).to_le_bytes());
+    // 2835 pixels per meter is 72 DPI
+    out.extend(2835u32.to_le_bytes());
+    out.extend(2835u32.to_le_bytes());
+    out.extend([0; 8]);
+    if !opaque {
+        for mask in [0x00FF0000u32, 0x0000FF00, 0x000000FF, 0xFF000000, LCS_SRGB] {
+            out.extend(mask.to_le_bytes());
+        }
+        // Endpoints and gamma, unused with sRGB
+        out.extend([0; 48]);
+    }
+
+    for row in pixels.chunks_exact(width * 4).rev() {
+        let start = out.len();
+        for p in row.chunks_exact(4) {
+            out.extend([p[2], p[1], p[0]]);
+            if !opaque {
+                out.push(p[3]);
+            }
+        }
+        out.resize(start + row_size, 0);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 5x3 pixels, all opaque but the 8th
+    fn sample(alpha: u8) -> Vec<u8> {
+        let mut pixels: Vec<u8> = (0..15u8).flat_map(|i| [i * 10, 255 - i, i * 3, 255]).collect();
+        pixels[7 * 4 + 3] = alpha;
+        pixels
+    }
+
+    #[test]
+    fn round_trip() {
+        // 5x3: rows of 15 bytes padded to 16 in 24-bit files
+        let opaque = sample(255);
+        let file = encode_bmp(&opaque, 5, 3);
+        assert_eq!((file.len(), get_u16_le(&file, 28).unwrap()), (14 + 40 + 16 * 3, 24));
+        assert_eq!(decode_bmp(&file).unwrap(), (opaque, 5, 3));
+
+        let translucent = sample(100);
+        let file = encode_bmp(&translucent, 5, 3);
+        assert_eq!(
+            (get_u32_le(&file, 14).unwrap(), get_u16_le(&file, 28).unwrap(), get_u32_le(&file, 30).unwrap()),
+            (108, 32, BI_BITFIELDS)
+        );
+        assert_eq!(decode_bmp(&file).unwrap(), (translucent, 5, 3));
+    }
+
+    /// BITMAPINFOHEADER file with the given fields and data right after the header and `extra` bytes, which are the
+    /// palette of 8-bit files.
+    fn bmp(width: i32, height: i32, bits_per_pixel: u16, compression: u32, extra: &[u8], data: &[u8]) -> Vec<u8> {
+        let offset = (14 + 40 + extra.len()) as u32;
+        let mut out = b"BM".to_vec();
+        out.extend((offset + data.len() as u32).to_le_bytes());
+        out.extend([0; 4]);
+        out.extend(offset.to_le_bytes());
+        out.extend(40u32.to_le_bytes());
+        out.extend(width.to_le_bytes());
+        out.extend(height.to_le_bytes());
+        out.extend(1u16.to_le_bytes());
+        out.extend(bits_per_pixel.to_le_bytes());
+        out.extend(compression.to_le_bytes());
+        out.extend([0; 12]);
+        let colors = if bits_per_pixel == 8 {
+            extra.len() / 4
+        } else {
+            0
+        };
+        out.extend((colors as u32).to_le_bytes());
+        out.extend([0; 4]);
+        out.extend(extra);
+        out.extend(data);
+        out
+    }
+
+    #[test]
+    fn formats() {
+        // Top-down 8-bit palette with 2 colors, rows padded to 4 bytes
+        let palette = [0, 0, 255, 0, 255, 0, 0, 0];
+        let file = bmp(2, -2, 8, BI_RGB, &palette, &[0, 1, 0, 0, 1, 1, 0, 0]);
+        let (pixels, ..) = decode_bmp(&file).unwrap();
+        assert_eq!(pixels, [255, 0, 0, 255, 0, 0, 255, 255, 0, 0, 255, 255, 0, 0, 255, 255]);
+        let invalid = bmp(1, 1, 8, BI_RGB, &palette[..4], &[1, 0, 0, 0]);
+        assert!(decode_bmp(&invalid).unwrap_err().contains("palette index"));
+
+        // 16-bit 5-6-5 bit fields
+        let masks: Vec<u8> = [0xF800u32, 0x07E0, 0x001F].iter().flat_map(|m| m.to_le_bytes()).collect();
+        let file = bmp(2, 1, 16, BI_BITFIELDS, &masks, &[0x00, 0xF8, 0x1F, 0x00]);
+        assert_eq!(decode_bmp(&file).unwrap().0, [255, 0, 0, 255, 0, 0, 255, 255]);
+
+        // 32-bit bit fields of 30 and 2 bits
+        let masks: Vec<u8> = [0xFFFFFFFCu32, 0, 3].iter().flat_map(|m| m.to_le_bytes()).collect();
+        let file = bmp(1, 1, 32, BI_BITFIELDS, &masks, &[0xFE, 0xFF, 0xFF, 0x7F]);
+        assert_eq!(decode_bmp(&file).unwrap().0, [127, 0, 170, 255]);
+
+        // 32-bit BI_RGB without alpha is opaque
+        let file = bmp(1, 1, 32, BI_RGB, &[], &[1, 2, 3, 0]);
+        assert_eq!(decode_bmp(&file).unwrap().0, [3, 2, 1, 255]);
+
+        assert!(decode_bmp(&file[..file.len() - 1]).unwrap_err().contains("Truncated"));
+        assert!(decode_bmp(&bmp(1, 1, 4, 2, &[], &[0; 4])).unwrap_err().contains("Unsupported"));
+        assert!(decode_bmp(b"GIF89a").is_err());
+    }
+}
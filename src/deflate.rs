@@ -0,0 +1,736 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+// DEFLATE (RFC 1951) compression and the zlib (RFC 1950) container, as used by PNG.
+// Blocks are made of literals and back references (length, distance) into the last 32 KiB of output, Huffman coded
+// with either the fixed codes of the format or dynamic codes sent in the block header. The encoder finds matches
+// with hash chains and writes each block as stored, fixed or dynamic, whichever is the smallest.
+
+const LENGTH_BASE: [u16; 29] =
+    [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145,
+    8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] =
+    [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+/// Order of the code length code lengths in a dynamic block header
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+const END_OF_BLOCK: u16 = 256;
+const WINDOW_SIZE: usize = 32768;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const MAX_STORED: usize = 65535;
+/// Tokens per block, the codes of each block adapt to its contents
+const BLOCK_TOKENS: usize = 1 << 14;
+
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                0xEDB88320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// CRC-32 of `data` (the checksum of PNG chunks).
+pub fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0, |crc, &b| CRC_TABLE[((crc ^ b as u32) & 0xFF) as usize] ^ (crc >> 8))
+}
+
+/// Adler-32 of `data` (the checksum of zlib streams).
+pub fn adler32(data: &[u8]) -> u32 {
+    // 5552 is the largest n such that 255 n (n + 1) / 2 + (n + 1) (65521 - 1) fits in 32 bits
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    b << 16 | a
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    bits: u64,
+    count: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            pos: 0,
+            bits: 0,
+            count: 0,
+        }
+    }
+
+    fn refill(&mut self) {
+        while self.count <= 56 && self.pos < self.data.len() {
+            self.bits |= (self.data[self.pos] as u64) << self.count;
+            self.pos += 1;
+            self.count += 8;
+        }
+    }
+
+    fn bits(&mut self, n: u32) -> Result<u32, String> {
+        if self.count < n {
+            self.refill();
+            if self.count < n {
+                return Err(String::from("Unexpected end of data"));
+            }
+        }
+        let value = (self.bits & ((1 << n) - 1)) as u32;
+        self.bits >>= n;
+        self.count -= n;
+        Ok(value)
+    }
+
+    fn align(&mut self) {
+        let n = self.count % 8;
+        self.bits >>= n;
+        self.count -= n;
+    }
+
+    /// Bytes consumed so far, once aligned.
+    fn position(&self) -> usize {
+        self.pos - self.count as usize / 8
+    }
+}
+
+/// Canonical Huffman codes from their lengths, in the order of the symbols.
+fn canonical_codes(lengths: &[u8]) -> Result<Vec<u16>, String> {
+    let mut counts = [0u16; 16];
+    for &len in lengths {
+        counts[len as usize] += 1;
+    }
+    counts[0] = 0;
+    let mut next = [0u32; 16];
+    let mut code = 0;
+    for len in 1..16 {
+        code = (code + counts[len - 1] as u32) << 1;
+        if code + counts[len] as u32 > 1 << len {
+            return Err(String::from("Over-subscribed Huffman code"));
+        }
+        next[len] = code;
+    }
+    Ok(lengths
+        .iter()
+        .map(|&len| {
+            let code = next[len as usize];
+            next[len as usize] += 1;
+            // Huffman codes are sent from their most significant bit
+            (code as u16).reverse_bits() >> (16 - len.max(1))
+        })
+        .collect())
+}
+
+/// Lookup table indexed by the next `bits` input bits: (symbol, code length), a length of 0 for invalid codes.
+struct Decoder {
+    table: Vec<(u16, u8)>,
+    bits: u32,
+}
+
+impl Decoder {
+    fn new(lengths: &[u8]) -> Result<Self, String> {
+        let bits = lengths.iter().copied().max().unwrap_or(0).max(1) as u32;
+        let mut table = vec![(0, 0); 1 << bits];
+        for (symbol, (&len, code)) in lengths.iter().zip(canonical_codes(lengths)?).enumerate() {
+            if len > 0 {
+                for entry in (code as usize..table.len()).step_by(1 << len) {
+                    table[entry] = (symbol as u16, len);
+                }
+            }
+        }
+        Ok(Self {
+            table,
+            bits,
+        })
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, String> {
+        if reader.count < self.bits {
+            reader.refill();
+        }
+        let (symbol, len) = self.table[(reader.bits & ((1 << self.bits) - 1)) as usize];
+        if len == 0 {
+            return Err(String::from("Invalid Huffman code"));
+        }
+        if reader.count < len as u32 {
+            return Err(String::from("Unexpected end of data"));
+        }
+        reader.bits >>= len;
+        reader.count -= len as u32;
+        Ok(symbol)
+    }
+}
+
+fn fixed_lengths() -> (Vec<u8>, Vec<u8>) {
+    let literals = (0..288).map(|s| match s {
+        0..=143 => 8,
+        144..=255 => 9,
+        256..=279 => 7,
+        _ => 8,
+    });
+    (literals.collect(), vec![5; 30])
+}
+
+fn read_dynamic_lengths(reader: &mut BitReader) -> Result<(Vec<u8>, Vec<u8>), String> {
+    let literal_count = reader.bits(5)? as usize + 257;
+    let distance_count = reader.bits(5)? as usize + 1;
+    let code_length_count = reader.bits(4)? as usize + 4;
+    let mut code_lengths = [0; 19];
+    for &i in &CODE_LENGTH_ORDER[..code_length_count] {
+        code_lengths[i] = reader.bits(3)? as u8;
+    }
+    let decoder = Decoder::new(&code_lengths)?;
+    let mut lengths = Vec::with_capacity(literal_count + distance_count);
+    while lengths.len() < literal_count + distance_count {
+        let (value, repeat) = match decoder.decode(reader)? {
+            16 => {
+                let previous = *lengths.last().ok_or("Repeated code length without a previous one")?;
+                (previous, 3 + reader.bits(2)?)
+            }
+            17 => (0, 3 + reader.bits(3)?),
+            18 => (0, 11 + reader.bits(7)?),
+            len => (len as u8, 1),
+        };
+        lengths.extend(std::iter::repeat_n(value, repeat as usize));
+    }
+    if lengths.len() > literal_count + distance_count {
+        return Err(String::from("Code lengths overflow"));
+    }
+    if lengths[END_OF_BLOCK as usize] == 0 {
+        return Err(String::from("Missing end of block code"));
+    }
+    let distances = lengths.split_off(literal_count);
+    Ok((lengths, distances))
+}
+
+/// Decompresses raw DEFLATE data, returns the data and the number of bytes read.
+fn inflate_stream(data: &[u8]) -> Result<(Vec<u8>, usize), String> {
+    let mut reader = BitReader::new(data);
+    let mut out = vec![];
+    loop {
+        let last = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => {
+                reader.align();
+                let len = reader.bits(16)?;
+                if reader.bits(16)? != !len & 0xFFFF {
+                    return Err(String::from("Invalid stored block length"));
+                }
+                for _ in 0..len {
+                    out.push(reader.bits(8)? as u8);
+                }
+            }
+            block_type @ (1 | 2) => {
+                let (literals, distances) = if block_type == 1 {
+                    fixed_lengths()
+                } else {
+                    read_dynamic_lengths(&mut reader)?
+                };
+                let (literals, distances) = (Decoder::new(&literals)?, Decoder::new(&distances)?);
+                loop {
+                    let symbol = literals.decode(&mut reader)?;
+                    if symbol < END_OF_BLOCK {
+                        out.push(symbol as u8);
+                        continue;
+                    }
+                    if symbol == END_OF_BLOCK {
+                        break;
+                    }
+                    let i = (symbol - 257) as usize;
+                    if i >= LENGTH_BASE.len() {
+                        return Err(format!("Invalid length symbol {}", symbol));
+                    }
+                    let len = LENGTH_BASE[i] as usize + reader.bits(LENGTH_EXTRA[i] as u32)? as usize;
+                    let i = distances.decode(&mut reader)? as usize;
+                    if i >= DISTANCE_BASE.len() {
+                        return Err(format!("Invalid distance symbol {}", i));
+                    }
+                    let distance = DISTANCE_BASE[i] as usize + reader.bits(DISTANCE_EXTRA[i] as u32)? as usize;
+                    if distance > out.len() {
+                        return Err(format!("Distance {} beyond the start of the data", distance));
+                    }
+                    // Matches can overlap the bytes they produce
+                    let start = out.len() - distance;
+                    for j in 0..len {
+                        out.push(out[start + j]);
+                    }
+                }
+            }
+            _ => return Err(String::from("Invalid block type")),
+        }
+        if last {
+            break;
+        }
+    }
+    reader.align();
+    Ok((out, reader.position()))
+}
+
+/// Decompresses raw DEFLATE data.
+pub fn inflate(data: &[u8]) -> Result<Vec<u8>, String> {
+    inflate_stream(data).map(|(out, _)| out)
+}
+
+/// Decompresses a zlib stream and checks its checksum.
+pub fn zlib_decompress(data: &[u8]) -> Result<Vec<u8>, String> {
+    let (cmf, flags) = match data {
+        [cmf, flags, ..] => (*cmf, *flags),
+        _ => return Err(String::from("Missing zlib header")),
+    };
+    if cmf & 0x0F != 8 || cmf >> 4 > 7 || !(cmf as u16 * 256 + flags as u16).is_multiple_of(31) {
+        return Err(String::from("Invalid zlib header"));
+    }
+    if flags & 0x20 != 0 {
+        return Err(String::from("Preset dictionaries are not supported"));
+    }
+    let (out, len) = inflate_stream(&data[2..])?;
+    let checksum = data.get(2 + len..2 + len + 4).ok_or("Missing zlib checksum")?;
+    if u32::from_be_bytes([checksum[0], checksum[1], checksum[2], checksum[3]]) != adler32(&out) {
+        return Err(String::from("zlib checksum mismatch"));
+    }
+    Ok(out)
+}
+
+struct BitWriter {
+    out: Vec<u8>,
+    bits: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, n: u32) {
+        self.bits |= (value as u64) << self.count;
+        self.count += n;
+        while self.count >= 8 {
+            self.out.push(self.bits as u8);
+            self.bits >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    fn align(&mut self) {
+        if self.count > 0 {
+            self.write(0, 8 - self.count);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Token {
+    Literal(u8),
+    Match(u16, u16),
+}
+
+fn length_symbol(len: usize) -> (usize, u32) {
+    let i = LENGTH_BASE.partition_point(|&base| base as usize <= len) - 1;
+    (i, (len - LENGTH_BASE[i] as usize) as u32)
+}
+
+fn distance_symbol(distance: usize) -> (usize, u32) {
+    let i = DISTANCE_BASE.partition_point(|&base| base as usize <= distance) - 1;
+    (i, (distance - DISTANCE_BASE[i] as usize) as u32)
+}
+
+/// Greedy LZ77 parsing, following at most `max_chain` previous positions with the same 3 bytes.
+fn find_matches(data: &[u8], max_chain: usize) -> Vec<Token> {
+    const HASH_SIZE: usize = 1 << 15;
+    const NONE: usize = usize::MAX;
+    let hash = |i: usize| ((data[i] as usize) << 10 ^ (data[i + 1] as usize) << 5 ^ data[i + 2] as usize) % HASH_SIZE;
+    let mut head = vec![NONE; HASH_SIZE];
+    let mut previous = vec![NONE; WINDOW_SIZE];
+    let insert = |i: usize, head: &mut [usize], previous: &mut [usize]| {
+        if i + MIN_MATCH <= data.len() {
+            let h = hash(i);
+            previous[i % WINDOW_SIZE] = head[h];
+            head[h] = i;
+        }
+    };
+
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < data.len() {
+        let (mut best_len, mut best_distance) = (0, 0);
+        if i + MIN_MATCH <= data.len() {
+            let max_len = MAX_MATCH.min(data.len() - i);
+            let mut candidate = head[hash(i)];
+            let mut chain = max_chain;
+            while candidate != NONE && i - candidate <= WINDOW_SIZE && chain > 0 {
+                let len = data[candidate..].iter().zip(&data[i..i + max_len]).take_while(|(a, b)| a == b).count();
+                if len > best_len {
+                    (best_len, best_distance) = (len, i - candidate);
+                    if len == max_len {
+                        break;
+                    }
+                }
+                // Entries of positions that left the window have been overwritten by more recent ones
+                let next = previous[candidate % WINDOW_SIZE];
+                if next == NONE || next >= candidate {
+                    break;
+                }
+                candidate = next;
+                chain -= 1;
+            }
+        }
+        if best_len >= MIN_MATCH {
+            tokens.push(Token::Match(best_len as u16, best_distance as u16));
+            for j in i..i + best_len {
+                insert(j, &mut head, &mut previous);
+            }
+            i += best_len;
+        } else {
+            tokens.push(Token::Literal(data[i]));
+            insert(i, &mut head, &mut previous);
+            i += 1;
+        }
+    }
+    tokens
+}
+
+/// Huffman code lengths of at most `limit` bits for the symbol frequencies. Frequencies are flattened until the
+/// longest code fits.
+fn code_lengths(frequencies: &[u32], limit: u8) -> Vec<u8> {
+    let mut frequencies = frequencies.to_vec();
+    loop {
+        let mut lengths = vec![0u8; frequencies.len()];
+        let used: Vec<usize> = (0..frequencies.len()).filter(|&s| frequencies[s] > 0).collect();
+        if used.len() == 1 {
+            lengths[used[0]] = 1;
+        }
+        if used.len() > 1 {
+            // Nodes are the symbols then the internal nodes, each with the index of its parent
+            let mut parents = vec![usize::MAX; used.len()];
+            let mut heap: BinaryHeap<_> =
+                used.iter().enumerate().map(|(node, &s)| Reverse((frequencies[s], node))).collect();
+            while let (Some(Reverse((a, first))), Some(Reverse((b, second)))) = (heap.pop(), heap.pop()) {
+                let node = parents.len();
+                parents.push(usize::MAX);
+                (parents[first], parents[second]) = (node, node);
+                heap.push(Reverse((a + b, node)));
+            }
+            for (node, &s) in used.iter().enumerate() {
+                let (mut depth, mut parent) = (0, parents[node]);
+                while parent != usize::MAX {
+                    depth += 1;
+                    parent = parents[parent];
+                }
+                lengths[s] = depth;
+            }
+        }
+        if lengths.iter().all(|&len| len <= limit) {
+            return lengths;
+        }
+        for frequency in frequencies.iter_mut().filter(|f| **f > 0) {
+            *frequency = (*frequency >> 1).max(1);
+        }
+    }
+}
+
+/// Run-length encoded code lengths of a dynamic block header: (code length symbol, extra bits value).
+fn encode_lengths(lengths: &[u8]) -> Vec<(u8, u32)> {
+    let mut symbols = vec![];
+    let mut i = 0;
+    while i < lengths.len() {
+        let len = lengths[i];
+        let run = lengths[i..].iter().take_while(|&&l| l == len).count();
+        if len == 0 && run >= 11 {
+            let run = run.min(138);
+            symbols.push((18, run as u32 - 11));
+            i += run;
+        } else if len == 0 && run >= 3 {
+            symbols.push((17, run as u32 - 3));
+            i += run;
+        } else if len != 0 && run >= 4 {
+            let run = (run - 1).min(6);
+            symbols.push((len, 0));
+            symbols.push((16, run as u32 - 3));
+            i += run + 1;
+        } else {
+            symbols.push((len, 0));
+            i += 1;
+        }
+    }
+    symbols
+}
+
+/// Literal/length and distance symbol frequencies of a block, with the end of block symbol.
+fn frequencies(tokens: &[Token]) -> (Vec<u32>, Vec<u32>) {
+    let (mut literals, mut distances) = (vec![0; 286], vec![0; 30]);
+    for &token in tokens {
+        match token {
+            Token::Literal(b) => literals[b as usize] += 1,
+            Token::Match(len, distance) => {
+                literals[257 + length_symbol(len as usize).0] += 1;
+                distances[distance_symbol(distance as usize).0] += 1;
+            }
+        }
+    }
+    literals[END_OF_BLOCK as usize] += 1;
+    (literals, distances)
+}
+
+/// Size in bits of the tokens with the codes of these lengths.
+fn coded_size(literals: &[u32], distances: &[u32], literal_lengths: &[u8], distance_lengths: &[u8]) -> usize {
+    let literal_bits: usize = literals
+        .iter()
+        .enumerate()
+        .map(|(s, &f)| {
+            f as usize * (literal_lengths[s] as usize + s.checked_sub(257).map_or(0, |i| LENGTH_EXTRA[i] as usize))
+        })
+        .sum();
+    let distance_bits: usize = distances
+        .iter()
+        .enumerate()
+        .map(|(s, &f)| f as usize * (distance_lengths[s] + DISTANCE_EXTRA[s]) as usize)
+        .sum();
+    literal_bits + distance_bits
+}
+
+fn write_tokens(writer: &mut BitWriter, tokens: &[Token], literal_lengths: &[u8], distance_lengths: &[u8]) {
+    let literal_codes = canonical_codes(literal_lengths).unwrap();
+    let distance_codes = canonical_codes(distance_lengths).unwrap();
+    let literal = |writer: &mut BitWriter, s: usize| writer.write(literal_codes[s] as u32, literal_lengths[s] as u32);
+    for &token in tokens {
+        match token {
+            Token::Literal(b) => literal(writer, b as usize),
+            Token::Match(len, distance) => {
+                let (i, extra) = length_symbol(len as usize);
+                literal(writer, 257 + i);
+                writer.write(extra, LENGTH_EXTRA[i] as u32);
+                let (i, extra) = distance_symbol(distance as usize);
+                writer.write(distance_codes[i] as u32, distance_lengths[i] as u32);
+                writer.write(extra, DISTANCE_EXTRA[i] as u32);
+            }
+        }
+    }
+    literal(writer, END_OF_BLOCK as usize);
+}
+
+/// Writes `data` as stored blocks of at most `MAX_STORED` bytes.
+fn write_stored(writer: &mut BitWriter, data: &[u8], last: bool) {
+    let chunks: Vec<&[u8]> = if data.is_empty() {
+        vec![data]
+    } else {
+        data.chunks(MAX_STORED).collect()
+    };
+    for (i, chunk) in chunks.iter().enumerate() {
+        writer.write((last && i + 1 == chunks.len()) as u32, 1);
+        writer.write(0, 2);
+        writer.align();
+        writer.write(chunk.len() as u32, 16);
+        writer.write(!chunk.len() as u32 & 0xFFFF, 16);
+        writer.out.extend_from_slice(chunk);
+    }
+}
+
+/// Writes the tokens of `data` as one or more blocks of the smallest type.
+fn write_block(writer: &mut BitWriter, tokens: &[Token], data: &[u8], last: bool) {
+    let (literals, distances) = frequencies(tokens);
+
+    let (fixed_literal_lengths, fixed_distance_lengths) = fixed_lengths();
+    let fixed_size = 3 + coded_size(&literals, &distances, &fixed_literal_lengths, &fixed_distance_lengths);
+
+    let literal_lengths = code_lengths(&literals, 15);
+    let mut distance_lengths = code_lengths(&distances, 15);
+    if distance_lengths.iter().all(|&len| len == 0) {
+        // Some decoders reject a block without any distance code
+        distance_lengths[0] = 1;
+    }
+    let literal_count = 257 + literal_lengths[257..].iter().rposition(|&len| len > 0).map_or(0, |i| i + 1);
+    let distance_count = 1 + distance_lengths[1..].iter().rposition(|&len| len > 0).map_or(0, |i| i + 1);
+    let header = encode_lengths(&[&literal_lengths[..literal_count], &distance_lengths[..distance_count]].concat());
+    let mut header_frequencies = vec![0; 19];
+    for &(symbol, _) in &header {
+        header_frequencies[symbol as usize] += 1;
+    }
+    let header_lengths = code_lengths(&header_frequencies, 7);
+    let header_count = 4.max(CODE_LENGTH_ORDER.iter().rposition(|&s| header_lengths[s] > 0).map_or(0, |i| i + 1));
+    let header_size: usize = header
+        .iter()
+        .map(|&(s, _)| header_lengths[s as usize] as usize + [2, 3, 7].get((s as usize).wrapping_sub(16)).unwrap_or(&0))
+        .sum();
+    let dynamic_size = 3
+        + 14
+        + 3 * header_count
+        + header_size
+        + coded_size(&literals, &distances, &literal_lengths, &distance_lengths);
+
+    let stored_size = data.len().div_ceil(MAX_STORED).max(1) * (3 + 7 + 32) + 8 * data.len();
+
+    if stored_size <= fixed_size.min(dynamic_size) {
+        write_stored(writer, data, last);
+    } else if fixed_size <= dynamic_size {
+        writer.write(last as u32, 1);
+        writer.write(1, 2);
+        write_tokens(writer, tokens, &fixed_literal_lengths, &fixed_distance_lengths);
+    } else {
+        writer.write(last as u32, 1);
+        writer.write(2, 2);
+        writer.write(literal_count as u32 - 257, 5);
+        writer.write(distance_count as u32 - 1, 5);
+        writer.write(header_count as u32 - 4, 4);
+        for &s in &CODE_LENGTH_ORDER[..header_count] {
+            writer.write(header_lengths[s] as u32, 3);
+        }
+        let header_codes = canonical_codes(&header_lengths).unwrap();
+        for (s, extra) in header {
+            writer.write(header_codes[s as usize] as u32, header_lengths[s as usize] as u32);
+            match s {
+                16 => writer.write(extra, 2),
+                17 => writer.write(extra, 3),
+                18 => writer.write(extra, 7),
+                _ => {}
+            }
+        }
+        write_tokens(writer, tokens, &literal_lengths, &distance_lengths);
+    }
+}
+
+/// Compresses `data` as raw DEFLATE data, `level` goes from 0 (stored, fastest) to 9 (smallest).
+pub fn deflate(data: &[u8], level: u8) -> Vec<u8> {
+    let mut writer = BitWriter {
+        out: vec![],
+        bits: 0,
+        count: 0,
+    };
+    if level == 0 || data.is_empty() {
+        write_stored(&mut writer, data, true);
+        return writer.out;
+    }
+    let max_chain = [0, 4, 8, 16, 32, 64, 128, 256, 1024, 4096][level.min(9) as usize];
+    let tokens = find_matches(data, max_chain);
+    let blocks: Vec<&[Token]> = tokens.chunks(BLOCK_TOKENS).collect();
+    let mut start = 0;
+    for (i, block) in blocks.iter().enumerate() {
+        let len: usize = block
+            .iter()
+            .map(|token| match token {
+                Token::Literal(_) => 1,
+                Token::Match(len, _) => *len as usize,
+            })
+            .sum();
+        write_block(&mut writer, block, &data[start..start + len], i + 1 == blocks.len());
+        start += len;
+    }
+    writer.align();
+    writer.out
+}
+
+/// Compresses `data` as a zlib stream, see `deflate`.
+pub fn zlib_compress(data: &[u8], level: u8) -> Vec<u8> {
+    // 32 KiB window, deflate, and the level hint in the check bits
+    let cmf = 0x78u16;
+    let hint = match level {
+        0..=1 => 0,
+        2..=5 => 1,
+        6 => 2,
+        _ => 3,
+    };
+    let flags = hint << 6;
+    let flags = flags + (31 - (cmf * 256 + flags) % 31) % 31;
+    let mut out = vec![cmf as u8, flags as u8];
+    out.extend(deflate(data, level));
+    out.extend(adler32(data).to_be_bytes());
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksums() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF43926);
+        assert_eq!(crc32(b"IEND"), 0xAE426082);
+        assert_eq!(adler32(b""), 1);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E60398);
+        assert_eq!(adler32(&vec![0xFF; 100000]), 0x149A302C);
+    }
+
+    #[test]
+    fn decompress() {
+        // zlib.compress(b"hello hello hello hello\n"), a fixed block with a back reference
+        let data =
+            [0x78, 0x9C, 0xCB, 0x48, 0xCD, 0xC9, 0xC9, 0x57, 0xC8, 0x40, 0x27, 0xB9, 0x00, 0x70, 0xBE, 0x08, 0xBB];
+        assert_eq!(zlib_decompress(&data).unwrap(), b"hello hello hello hello\n");
+        // Two stored blocks
+        let stored = [0x00, 0x02, 0x00, 0xFD, 0xFF, b'a', b'b', 0x01, 0x01, 0x00, 0xFE, 0xFF, b'a'];
+        assert_eq!(inflate(&stored).unwrap(), b"aba");
+
+        let mut corrupted = data;
+        corrupted[16] ^= 1;
+        assert!(zlib_decompress(&corrupted).unwrap_err().contains("checksum"));
+        assert!(zlib_decompress(&data[..10]).is_err());
+        assert!(zlib_decompress(&[0x78, 0x9D]).unwrap_err().contains("header"));
+        // Invalid block type 3
+        assert!(inflate(&[0x07]).unwrap_err().contains("block type"));
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut random = 12345u32;
+        let noise: Vec<u8> = (0..70000)
+            .map(|_| {
+                random = random.wrapping_mul(1103515245).wrapping_add(12345);
+                (random >> 16) as u8
+            })
+            .collect();
+        let text = "The quick brown fox jumps over the lazy dog. ".repeat(2000).into_bytes();
+        let mixed: Vec<u8> = text
+            .iter()
+            .zip(&noise)
+            .map(|(&t, &n)| {
+                if n < 16 {
+                    n
+                } else {
+                    t
+                }
+            })
+            .collect();
+        for data in [vec![], vec![42], vec![0; 100000], noise, text, mixed] {
+            for level in [0, 1, 6, 9] {
+                let compressed = zlib_compress(&data, level);
+                assert_eq!(zlib_decompress(&compressed).unwrap(), data, "level {}", level);
+            }
+        }
+        // Repetitive data is compressed with back references, random data is stored
+        assert!(zlib_compress(&[0; 100000], 6).len() < 200);
+        assert!(deflate(&"abc".repeat(1000).into_bytes(), 6).len() < 50);
+    }
+
+    #[test]
+    fn limited_code_lengths() {
+        // Fibonacci frequencies give a code 20 bits deep without a limit
+        let mut frequencies = vec![1, 1];
+        for i in 2..22 {
+            frequencies.push(frequencies[i - 1] + frequencies[i - 2]);
+        }
+        let lengths = code_lengths(&frequencies, 15);
+        assert!(lengths.iter().all(|&len| (1..=15).contains(&len)));
+        let kraft: f64 = lengths.iter().map(|&len| 0.5f64.powi(len as i32)).sum();
+        assert!(kraft <= 1.0);
+        assert_eq!(code_lengths(&[0, 5, 0], 15), [0, 1, 0]);
+        assert_eq!(code_lengths(&[3, 1, 1], 15), [1, 2, 2]);
+    }
+}
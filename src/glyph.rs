@@ -2,7 +2,8 @@
 #![allow(dead_code)]
 #![allow(unused_variables)]
 
-use crate::stb_image::*;
+use crate::bitmap_font::{BitmapFont, GridOptions};
+use crate::parsing::*;
 
 // https://developer.apple.com/fonts/TrueType-Reference-Manual/RM01/Chap1.html
 // Bezier Quadratic Curve: from point p0 to p2 with p1 an off-curve point.
@@ -48,6 +49,7 @@ const TAG_glyf: Tag = Tag::new(b"glyf"); // glyph data
 const TAG_head: Tag = Tag::new(b"head"); // font header
 const TAG_hhea: Tag = Tag::new(b"hhea"); // horizontal header
 const TAG_hmtx: Tag = Tag::new(b"hmtx"); // horizontal metrics
+const TAG_kern: Tag = Tag::new(b"kern"); // kerning
 const TAG_loca: Tag = Tag::new(b"loca"); // index to location
 const TAG_maxp: Tag = Tag::new(b"maxp"); // maximum profile
 const TAG_name: Tag = Tag::new(b"name"); // naming
@@ -75,24 +77,285 @@ impl std::fmt::Debug for Tag {
 }
 
 fn calc_table_checksum(table: &[u32]) -> u32 {
-    todo!()
+    table.iter().fold(0u32, |sum, word| sum.wrapping_add(*word))
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::parsing::*;
+/// Checksum of a table, padded with zeros to a multiple of 4 bytes.
+fn table_checksum(bytes: &[u8]) -> u32 {
+    let words: Vec<u32> = bytes
+        .chunks(4)
+        .map(|chunk| chunk.iter().enumerate().fold(0, |word, (i, b)| word | (*b as u32) << (24 - 8 * i)))
+        .collect();
+    calc_table_checksum(&words)
+}
 
-    #[test]
-    fn tag_new() {
-        assert_eq!(Tag::new(b"cmap"), Tag(0x636d6170));
+fn invalid<T>(message: impl Into<String>) -> std::io::Result<T> {
+    Err(std::io::Error::new(std::io::ErrorKind::InvalidData, message.into()))
+}
+
+// Name ids of the `name` table
+pub const NAME_COPYRIGHT: u16 = 0;
+pub const NAME_FAMILY: u16 = 1;
+pub const NAME_SUBFAMILY: u16 = 2;
+pub const NAME_UNIQUE_ID: u16 = 3;
+pub const NAME_FULL_NAME: u16 = 4;
+pub const NAME_VERSION: u16 = 5;
+pub const NAME_POSTSCRIPT_NAME: u16 = 6;
+
+// Flags of simple glyphs
+const ON_CURVE_POINT: u8 = 0x01;
+const X_SHORT_VECTOR: u8 = 0x02;
+const Y_SHORT_VECTOR: u8 = 0x04;
+const REPEAT_FLAG: u8 = 0x08;
+const X_IS_SAME_OR_POSITIVE_X_SHORT_VECTOR: u8 = 0x10;
+const Y_IS_SAME_OR_POSITIVE_Y_SHORT_VECTOR: u8 = 0x20;
+
+// Flags of composite glyph components
+const ARG_1_AND_2_ARE_WORDS: u16 = 0x0001;
+const ARGS_ARE_XY_VALUES: u16 = 0x0002;
+const WE_HAVE_A_SCALE: u16 = 0x0008;
+const MORE_COMPONENTS: u16 = 0x0020;
+const WE_HAVE_AN_X_AND_Y_SCALE: u16 = 0x0040;
+const WE_HAVE_A_TWO_BY_TWO: u16 = 0x0080;
+const SCALED_COMPONENT_OFFSET: u16 = 0x0800;
+
+/// Composite glyphs referencing composite glyphs deeper than this are rejected (they are probably cyclic).
+const MAX_COMPONENT_DEPTH: usize = 8;
+/// Outlines made of more glyphs and points than this are rejected, composites can reference a glyph many times at
+/// every level.
+const MAX_OUTLINE_SIZE: usize = 1 << 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct HMetrics {
+    pub advance_width: u16,
+    pub left_side_bearing: i16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OutlinePoint {
+    pub x: f32,
+    pub y: f32,
+    pub on_curve: bool,
+}
+
+/// Glyph outline in font units, y up. Contours are closed and made of quadratic Bezier curves where two consecutive
+/// off-curve points imply an on-curve point halfway between them.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Outline {
+    pub contours: Vec<Vec<OutlinePoint>>,
+    pub xmin: i16,
+    pub ymin: i16,
+    pub xmax: i16,
+    pub ymax: i16,
+}
+
+/// Piece of a contour, in the units of the outline.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Segment {
+    Line((f32, f32), (f32, f32)),
+    /// Quadratic Bezier curve from the first point to the last one, the middle point is off-curve
+    Quad((f32, f32), (f32, f32), (f32, f32)),
+}
+
+impl Segment {
+    pub fn start(&self) -> (f32, f32) {
+        match *self {
+            Segment::Line(p0, _) | Segment::Quad(p0, _, _) => p0,
+        }
     }
 
-    #[test]
-    #[ignore]
-    fn parse_ttf() -> std::io::Result<()> {
-        let bytes = std::fs::read("/usr/share/fonts/TTF/knewave.ttf").unwrap();
-        let mut reader = std::io::Cursor::new(bytes);
+    pub fn end(&self) -> (f32, f32) {
+        match *self {
+            Segment::Line(_, p1) | Segment::Quad(_, _, p1) => p1,
+        }
+    }
+}
+
+impl Outline {
+    fn points(&self) -> impl Iterator<Item = &OutlinePoint> {
+        self.contours.iter().flatten()
+    }
+
+    /// Lines and curves of every contour, with the implied on-curve points made explicit.
+    /// Every contour ends where it starts.
+    pub fn segments(&self) -> Vec<Vec<Segment>> {
+        let mut contours = vec![];
+        for contour in &self.contours {
+            if contour.is_empty() {
+                continue;
+            }
+            // Start on an on-curve point, or between the first two off-curve points if there are none
+            let n = contour.len();
+            let point = |i: usize| (contour[i % n].x, contour[i % n].y);
+            let first_on = contour.iter().position(|p| p.on_curve);
+            let (start, mut current) = match first_on {
+                Some(i) => (i, point(i)),
+                None => {
+                    let (a, b) = (point(0), point(1));
+                    (0, ((a.0 + b.0) / 2.0, (a.1 + b.1) / 2.0))
+                }
+            };
+            let origin = current;
+            let mut segments = vec![];
+            let mut control = None;
+            let steps = if first_on.is_some() {
+                n
+            } else {
+                n + 1
+            };
+            for i in 1..=steps {
+                let p = point(start + i);
+                match (control, contour[(start + i) % n].on_curve) {
+                    (None, true) => {
+                        segments.push(Segment::Line(current, p));
+                        current = p;
+                    }
+                    (None, false) => control = Some(p),
+                    (Some(c), true) => {
+                        segments.push(Segment::Quad(current, c, p));
+                        current = p;
+                        control = None;
+                    }
+                    (Some(c), false) => {
+                        // Two off-curve points imply an on-curve point halfway between them
+                        let mid = ((c.0 + p.0) / 2.0, (c.1 + p.1) / 2.0);
+                        segments.push(Segment::Quad(current, c, mid));
+                        current = mid;
+                        control = Some(p);
+                    }
+                }
+            }
+            match control {
+                Some(c) => segments.push(Segment::Quad(current, c, origin)),
+                None if current != origin => segments.push(Segment::Line(current, origin)),
+                None => {}
+            }
+            contours.push(segments);
+        }
+        contours
+    }
+
+    /// Outline of the pixels of a bitmap that are not 0, rows from top to bottom.
+    /// Pixels are 1 unit wide and y goes up from the bottom of the last row.
+    pub fn from_bitmap(pixels: &[u8], width: usize, height: usize) -> Self {
+        let filled = |x: isize, y: isize| {
+            x >= 0
+                && y >= 0
+                && (x as usize) < width
+                && (y as usize) < height
+                && pixels[y as usize * width + x as usize] != 0
+        };
+        // Borders between filled and empty pixels, clockwise around the filled ones (filled on the right)
+        let mut edges: Vec<((i32, i32), (i32, i32))> = vec![];
+        for row in 0..height as isize {
+            for col in 0..width as isize {
+                if !filled(col, row) {
+                    continue;
+                }
+                let (x0, x1) = (col as i32, col as i32 + 1);
+                let (y0, y1) = ((height as isize - row - 1) as i32, (height as isize - row) as i32);
+                if !filled(col - 1, row) {
+                    edges.push(((x0, y0), (x0, y1)));
+                }
+                if !filled(col, row - 1) {
+                    edges.push(((x0, y1), (x1, y1)));
+                }
+                if !filled(col + 1, row) {
+                    edges.push(((x1, y1), (x1, y0)));
+                }
+                if !filled(col, row + 1) {
+                    edges.push(((x1, y0), (x0, y0)));
+                }
+            }
+        }
+
+        // Chain the borders into contours, keeping only the corners
+        let mut outline = Outline::default();
+        let mut used = vec![false; edges.len()];
+        while let Some(first) = used.iter().position(|used| !used) {
+            let mut points = vec![];
+            let mut edge = first;
+            loop {
+                used[edge] = true;
+                points.push(edges[edge].0);
+                let end = edges[edge].1;
+                match (0..edges.len()).find(|&i| !used[i] && edges[i].0 == end) {
+                    Some(next) => edge = next,
+                    None => break,
+                }
+            }
+            let n = points.len();
+            let contour = (0..n)
+                .filter(|&i| {
+                    let (prev, p, next) = (points[(i + n - 1) % n], points[i], points[(i + 1) % n]);
+                    (p.0 - prev.0, p.1 - prev.1) != (next.0 - p.0, next.1 - p.1)
+                })
+                .map(|i| OutlinePoint {
+                    x: points[i].0 as f32,
+                    y: points[i].1 as f32,
+                    on_curve: true,
+                })
+                .collect();
+            outline.contours.push(contour);
+        }
+
+        let bounds = outline.points().fold(None, |bounds, p| match bounds {
+            None => Some((p.x, p.y, p.x, p.y)),
+            Some((xmin, ymin, xmax, ymax)) => Some((xmin.min(p.x), ymin.min(p.y), xmax.max(p.x), ymax.max(p.y))),
+        });
+        if let Some((xmin, ymin, xmax, ymax)) = bounds {
+            (outline.xmin, outline.ymin, outline.xmax, outline.ymax) =
+                (xmin as i16, ymin as i16, xmax as i16, ymax as i16);
+        }
+        outline
+    }
+}
+
+#[derive(Debug, Clone)]
+enum CharMap {
+    /// Segment mapping to delta values: (start, end, delta, range offset) and the glyph id array.
+    /// Range offsets are converted to indices into the glyph id array.
+    Format4(Vec<(u16, u16, u16, Option<usize>)>, Vec<u16>),
+    /// Segmented coverage: (start, end, start glyph)
+    Format12(Vec<(u32, u32, u32)>),
+}
+
+/// TrueType font (glyphs with quadratic outlines), loaded from the bytes of a `.ttf` file.
+#[derive(Debug, Clone)]
+pub struct Font {
+    data: Vec<u8>,
+    glyf: std::ops::Range<usize>,
+    loca: Vec<u32>,
+    cmap: CharMap,
+    h_metrics: Vec<HMetrics>,
+    /// Kerning pairs sorted by (left glyph << 16 | right glyph)
+    kerning: Vec<(u32, i16)>,
+    names: Vec<(u16, String)>,
+    pub units_per_em: u16,
+    pub num_glyphs: u16,
+    pub ascent: i16,
+    pub descent: i16,
+    pub line_gap: i16,
+    /// Bounding box of all glyphs: xmin, ymin, xmax, ymax
+    pub bounds: [i16; 4],
+    pub underline_position: i16,
+    pub underline_thickness: i16,
+    pub is_fixed_pitch: bool,
+}
+
+impl Font {
+    pub fn load<P: AsRef<std::path::Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        Self::from_bytes(bytes)
+    }
+
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self, String> {
+        Self::parse(bytes).map_err(|e| format!("Invalid TrueType font: {}", e))
+    }
+
+    fn parse(data: Vec<u8>) -> std::io::Result<Self> {
+        let mut reader = std::io::Cursor::new(&data[..]);
         let offset_subtable = OffsetSubtable {
             scaler_type: read_u32_be(&mut reader)?,
             num_tables: read_u16_be(&mut reader)?,
@@ -100,8 +363,11 @@ mod tests {
             entry_selector: read_u16_be(&mut reader)?,
             range_shift: read_u16_be(&mut reader)?,
         };
-        println!("{:#?}", offset_subtable);
-        println!();
+        if offset_subtable.scaler_type != 0x00010000 && offset_subtable.scaler_type != Tag::new(b"true").0 {
+            return invalid(format!("Unsupported scaler type 0x{:08x}", offset_subtable.scaler_type));
+        }
+
+        let mut tables = vec![];
         for _ in 0..offset_subtable.num_tables {
             let table_dir = TableDirectory {
                 tag: Tag(read_u32_be(&mut reader)?),
@@ -109,127 +375,849 @@ mod tests {
                 offset: read_u32_be(&mut reader)?,
                 length: read_u32_be(&mut reader)?,
             };
-            println!("{:#?}", table_dir);
-
-            let table_bytes =
-                &reader.get_ref()[(table_dir.offset as usize)..(table_dir.offset + table_dir.length) as usize];
-            let mut reader = std::io::Cursor::new(table_bytes);
-            match table_dir.tag {
-                TAG_head => {
-                    let version = read_u32_be(&mut reader)?;
-                    let font_revision = read_u32_be(&mut reader)?;
-                    let checksum_adjustment = read_u32_be(&mut reader)?;
-                    let magic_number = read_u32_be(&mut reader)?;
-                    let flags = read_u16_be(&mut reader)?;
-                    let units_per_em = read_u16_be(&mut reader)?;
-                    let created = read_u64_be(&mut reader)?;
-                    let modified = read_u64_be(&mut reader)?;
-                    let xmin = read_i16_be(&mut reader)?;
-                    let ymin = read_i16_be(&mut reader)?;
-                    let xmax = read_i16_be(&mut reader)?;
-                    let ymax = read_i16_be(&mut reader)?;
-                    let mac_style = read_u16_be(&mut reader)?;
-                    let lowest_rec_ppem = read_u16_be(&mut reader)?;
-                    let font_direction_hint = read_i16_be(&mut reader)?;
-                    let index_to_loc_format = read_i16_be(&mut reader)?;
-                    let glyph_data_format = read_i16_be(&mut reader)?;
-                    println!("version: 0x{:08x}, revision: {}, checksum: {}, magic: 0x{:08x}, flags: 0b{:032b}, units per em: {}, created: {}, modified: {}",
-                        version, font_revision, checksum_adjustment, magic_number, flags, units_per_em, created, modified);
-                    println!(
-                        "xmin: {}, ymin: {}, xmax: {}, ymax: {}, mac style: {}",
-                        xmin, ymin, xmax, ymax, mac_style
-                    );
-                    println!(
-                        "lowest rec ppem: {}, direction hint: {}, index to loc format: {}, glyph data format: {}",
-                        lowest_rec_ppem, font_direction_hint, index_to_loc_format, glyph_data_format
-                    );
-                    //break;
+            let range = table_dir.offset as usize..table_dir.offset as usize + table_dir.length as usize;
+            let Some(bytes) = data.get(range.clone()) else {
+                return invalid(format!("Table {:?} is out of bounds", table_dir.tag));
+            };
+            // The checksum of head is calculated with checksum_adjustment set to 0
+            let checksum = match table_dir.tag {
+                TAG_head if bytes.len() >= 12 => {
+                    let adjustment = u32::from_be_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]);
+                    table_checksum(bytes).wrapping_sub(adjustment)
                 }
-                TAG_maxp => {
-                    let version = read_u32_be(&mut reader)?;
-                    let num_glyphs = read_u16_be(&mut reader)?;
-                    println!("version: 0x{:08x}, num glyphs: {}", version, num_glyphs);
+                _ => table_checksum(bytes),
+            };
+            if checksum != table_dir.checksum {
+                return invalid(format!(
+                    "Checksum of {:?} is 0x{:08x} instead of 0x{:08x}",
+                    table_dir.tag, checksum, table_dir.checksum
+                ));
+            }
+            tables.push((table_dir.tag, range));
+        }
+        let table = |tag: Tag| match tables.iter().find(|(t, _)| *t == tag) {
+            Some((_, range)) => Ok(range.clone()),
+            None => invalid(format!("Missing table {:?}", tag)),
+        };
+
+        let mut reader = std::io::Cursor::new(&data[table(TAG_head)?]);
+        reader.set_position(12);
+        let magic_number = read_u32_be(&mut reader)?;
+        if magic_number != 0x5F0F3CF5 {
+            return invalid(format!("Invalid head magic number 0x{:08x}", magic_number));
+        }
+        let flags = read_u16_be(&mut reader)?;
+        let units_per_em = read_u16_be(&mut reader)?;
+        if !(16..=16384).contains(&units_per_em) {
+            return invalid(format!("unitsPerEm should be from 16 to 16384, not {}", units_per_em));
+        }
+        let created = read_u64_be(&mut reader)?;
+        let modified = read_u64_be(&mut reader)?;
+        let bounds = [
+            read_i16_be(&mut reader)?,
+            read_i16_be(&mut reader)?,
+            read_i16_be(&mut reader)?,
+            read_i16_be(&mut reader)?,
+        ];
+        let mac_style = read_u16_be(&mut reader)?;
+        let lowest_rec_ppem = read_u16_be(&mut reader)?;
+        let font_direction_hint = read_i16_be(&mut reader)?;
+        let index_to_loc_format = read_i16_be(&mut reader)?;
+
+        let mut reader = std::io::Cursor::new(&data[table(TAG_maxp)?]);
+        let version = read_u32_be(&mut reader)?;
+        let num_glyphs = read_u16_be(&mut reader)?;
+
+        let mut reader = std::io::Cursor::new(&data[table(TAG_hhea)?]);
+        let version = read_u32_be(&mut reader)?;
+        let ascent = read_i16_be(&mut reader)?;
+        let descent = read_i16_be(&mut reader)?;
+        let line_gap = read_i16_be(&mut reader)?;
+        reader.set_position(34);
+        let num_of_long_hor_metrics = read_u16_be(&mut reader)?;
+
+        let mut reader = std::io::Cursor::new(&data[table(TAG_hmtx)?]);
+        let mut h_metrics = vec![];
+        for _ in 0..num_of_long_hor_metrics {
+            h_metrics.push(HMetrics {
+                advance_width: read_u16_be(&mut reader)?,
+                left_side_bearing: read_i16_be(&mut reader)?,
+            });
+        }
+        // The remaining glyphs only have a left side bearing and the advance of the last metric
+        let advance_width = h_metrics.last().map_or(0, |m| m.advance_width);
+        for _ in num_of_long_hor_metrics..num_glyphs {
+            h_metrics.push(HMetrics {
+                advance_width,
+                left_side_bearing: read_i16_be(&mut reader)?,
+            });
+        }
+
+        let mut reader = std::io::Cursor::new(&data[table(TAG_loca)?]);
+        let mut loca = vec![];
+        for _ in 0..=num_glyphs {
+            loca.push(match index_to_loc_format {
+                0 => read_u16_be(&mut reader)? as u32 * 2,
+                1 => read_u32_be(&mut reader)?,
+                _ => return invalid(format!("Unknown index to loc format {}", index_to_loc_format)),
+            });
+        }
+        let glyf = table(TAG_glyf)?;
+        if loca.windows(2).any(|w| w[0] > w[1]) || *loca.last().unwrap() as usize > glyf.len() {
+            return invalid("Glyph locations are out of order or out of bounds");
+        }
+
+        let cmap = parse_cmap(&data[table(TAG_cmap)?])?;
+        let kerning = match table(TAG_kern) {
+            Ok(range) => parse_kern(&data[range])?,
+            Err(_) => vec![],
+        };
+        let names = match table(TAG_name) {
+            Ok(range) => parse_names(&data[range])?,
+            Err(_) => vec![],
+        };
+
+        let (mut underline_position, mut underline_thickness, mut is_fixed_pitch) = (0, 0, false);
+        if let Ok(range) = table(TAG_post) {
+            let mut reader = std::io::Cursor::new(&data[range]);
+            reader.set_position(8);
+            underline_position = read_i16_be(&mut reader)?;
+            underline_thickness = read_i16_be(&mut reader)?;
+            is_fixed_pitch = read_u32_be(&mut reader)? != 0;
+        }
+
+        Ok(Self {
+            data,
+            glyf,
+            loca,
+            cmap,
+            h_metrics,
+            kerning,
+            names,
+            units_per_em,
+            num_glyphs,
+            ascent,
+            descent,
+            line_gap,
+            bounds,
+            underline_position,
+            underline_thickness,
+            is_fixed_pitch,
+        })
+    }
+
+    /// Glyph of a character, None if the font doesn't have one (glyph 0 is the missing glyph).
+    pub fn glyph_index(&self, c: char) -> Option<u16> {
+        let c = c as u32;
+        let glyph = match &self.cmap {
+            CharMap::Format4(segments, glyph_ids) => {
+                let c = u16::try_from(c).ok()?;
+                let i = segments.partition_point(|(_, end, _, _)| *end < c);
+                let &(start, _, delta, range_index) = segments.get(i).filter(|(start, ..)| *start <= c)?;
+                match range_index {
+                    None => c.wrapping_add(delta),
+                    Some(index) => match *glyph_ids.get(index + (c - start) as usize)? {
+                        0 => 0,
+                        glyph => glyph.wrapping_add(delta),
+                    },
                 }
-                TAG_cmap => {
-                    let version = read_u16_be(&mut reader)?;
-                    let num_subtables = read_u16_be(&mut reader)?;
-                    //println!("Version: {}", version);
-                    //println!("Num Subtables: {}", num_subtables);
-
-                    for _ in 0..num_subtables {
-                        let platform_id = read_u16_be(&mut reader)?;
-                        let platform_specific_id = read_u16_be(&mut reader)?;
-                        let offset = read_u32_be(&mut reader)?;
-                        //println!(
-                        //    "platform id: {}, platform specific id: {}, offset: 0x{:08x}",
-                        //    platform_id, platform_specific_id, offset
-                        //);
+            }
+            CharMap::Format12(groups) => {
+                let i = groups.partition_point(|(_, end, _)| *end < c);
+                let &(start, _, start_glyph) = groups.get(i).filter(|(start, ..)| *start <= c)?;
+                u16::try_from(start_glyph.checked_add(c - start)?).ok()?
+            }
+        };
+        (glyph != 0 && glyph < self.num_glyphs).then_some(glyph)
+    }
+
+    pub fn h_metrics(&self, glyph: u16) -> HMetrics {
+        self.h_metrics.get(glyph as usize).copied().unwrap_or_default()
+    }
+
+    /// Horizontal adjustment of the advance of `left` when followed by `right`, in font units.
+    pub fn kerning(&self, left: u16, right: u16) -> i16 {
+        let key = (left as u32) << 16 | right as u32;
+        match self.kerning.binary_search_by_key(&key, |(pair, _)| *pair) {
+            Ok(i) => self.kerning[i].1,
+            Err(_) => 0,
+        }
+    }
+
+    /// Outline of a glyph, empty for glyphs without contours like the space.
+    pub fn outline(&self, glyph: u16) -> Result<Outline, String> {
+        let mut budget = MAX_OUTLINE_SIZE;
+        self.parse_outline(glyph, 0, &mut budget).map_err(|e| format!("Invalid glyph {}: {}", glyph, e))
+    }
+
+    /// `budget` is the number of glyphs and points the outline can still have.
+    fn parse_outline(&self, glyph: u16, depth: usize, budget: &mut usize) -> std::io::Result<Outline> {
+        spend(budget, 1)?;
+        if glyph >= self.num_glyphs {
+            return invalid(format!("Glyph out of range (the font has {} glyphs)", self.num_glyphs));
+        }
+        let start = self.glyf.start + self.loca[glyph as usize] as usize;
+        let end = self.glyf.start + self.loca[glyph as usize + 1] as usize;
+        if start == end {
+            return Ok(Outline::default());
+        }
+        let mut reader = std::io::Cursor::new(&self.data[start..end]);
+        let num_contours = read_i16_be(&mut reader)?;
+        let mut outline = Outline {
+            contours: vec![],
+            xmin: read_i16_be(&mut reader)?,
+            ymin: read_i16_be(&mut reader)?,
+            xmax: read_i16_be(&mut reader)?,
+            ymax: read_i16_be(&mut reader)?,
+        };
+
+        if num_contours >= 0 {
+            // Simple Glyph
+            let mut end_pts_of_contours = vec![];
+            for _ in 0..num_contours {
+                end_pts_of_contours.push(read_u16_be(&mut reader)? as usize);
+            }
+            let instruction_len = read_u16_be(&mut reader)?;
+            reader.set_position(reader.position() + instruction_len as u64);
+            let num_points = end_pts_of_contours.last().map_or(0, |end| end + 1);
+            spend(budget, num_points)?;
+            if end_pts_of_contours.windows(2).any(|w| w[0] >= w[1]) {
+                return invalid("Contour end points are out of order");
+            }
+
+            let mut flags = Vec::with_capacity(num_points);
+            while flags.len() < num_points {
+                let flag = read_u8(&mut reader)?;
+                let repeat = if flag & REPEAT_FLAG != 0 {
+                    read_u8(&mut reader)? as usize
+                } else {
+                    0
+                };
+                flags.extend(std::iter::repeat_n(flag, repeat + 1));
+            }
+            flags.truncate(num_points);
+
+            // Coordinates are deltas from the previous point
+            let mut read_coordinates = |short: u8, same_or_positive: u8| -> std::io::Result<Vec<i16>> {
+                let mut value = 0i16;
+                let mut coordinates = Vec::with_capacity(num_points);
+                for flag in &flags {
+                    if flag & short != 0 {
+                        let delta = read_u8(&mut reader)? as i16;
+                        value = value.wrapping_add(if flag & same_or_positive != 0 {
+                            delta
+                        } else {
+                            -delta
+                        });
+                    } else if flag & same_or_positive == 0 {
+                        value = value.wrapping_add(read_i16_be(&mut reader)?);
                     }
+                    coordinates.push(value);
+                }
+                Ok(coordinates)
+            };
+            let xs = read_coordinates(X_SHORT_VECTOR, X_IS_SAME_OR_POSITIVE_X_SHORT_VECTOR)?;
+            let ys = read_coordinates(Y_SHORT_VECTOR, Y_IS_SAME_OR_POSITIVE_Y_SHORT_VECTOR)?;
+
+            let mut start = 0;
+            for end in end_pts_of_contours {
+                outline.contours.push(
+                    (start..=end)
+                        .map(|i| OutlinePoint {
+                            x: xs[i] as f32,
+                            y: ys[i] as f32,
+                            on_curve: flags[i] & ON_CURVE_POINT != 0,
+                        })
+                        .collect(),
+                );
+                start = end + 1;
+            }
+        } else {
+            // Composite Glyph: components are other glyphs transformed by a 2x2 matrix and an offset
+            if depth >= MAX_COMPONENT_DEPTH {
+                return invalid("Too many nested components");
+            }
+            loop {
+                let flags = read_u16_be(&mut reader)?;
+                let component = read_u16_be(&mut reader)?;
+                let (arg1, arg2) = match (flags & ARG_1_AND_2_ARE_WORDS != 0, flags & ARGS_ARE_XY_VALUES != 0) {
+                    (true, true) => (read_i16_be(&mut reader)? as i32, read_i16_be(&mut reader)? as i32),
+                    (true, false) => (read_u16_be(&mut reader)? as i32, read_u16_be(&mut reader)? as i32),
+                    (false, true) => (read_u8(&mut reader)? as i8 as i32, read_u8(&mut reader)? as i8 as i32),
+                    (false, false) => (read_u8(&mut reader)? as i32, read_u8(&mut reader)? as i32),
+                };
+                // F2Dot14
+                let mut read_scale = || -> std::io::Result<f32> { Ok(read_i16_be(&mut reader)? as f32 / 16384.0) };
+                let [a, b, c, d] = if flags & WE_HAVE_A_SCALE != 0 {
+                    let scale = read_scale()?;
+                    [scale, 0.0, 0.0, scale]
+                } else if flags & WE_HAVE_AN_X_AND_Y_SCALE != 0 {
+                    [read_scale()?, 0.0, 0.0, read_scale()?]
+                } else if flags & WE_HAVE_A_TWO_BY_TWO != 0 {
+                    [read_scale()?, read_scale()?, read_scale()?, read_scale()?]
+                } else {
+                    [1.0, 0.0, 0.0, 1.0]
+                };
 
-                    //break;
+                let mut component = self.parse_outline(component, depth + 1, budget)?;
+                for point in component.contours.iter_mut().flatten() {
+                    (point.x, point.y) = (a * point.x + c * point.y, b * point.x + d * point.y);
                 }
-                TAG_glyf => {
-                    for glyph_idx in 0..211 {
-                        println!("GLYPH {}", glyph_idx);
-                        let num_contours = read_i16_be(&mut reader)?;
-                        let xmin = read_i16_be(&mut reader)?;
-                        let ymin = read_i16_be(&mut reader)?;
-                        let xmax = read_i16_be(&mut reader)?;
-                        let ymax = read_i16_be(&mut reader)?;
-                        println!(
-                            "num contours: {}, xmin: {}, ymin: {}, xmax: {}, ymax: {}",
-                            num_contours, xmin, ymin, xmax, ymax
-                        );
-                        if num_contours >= 0 {
-                            // Simple Glyph
-                            for _ in 0..num_contours {
-                                let end_pt_of_contour = read_u16_be(&mut reader)?;
-                                println!("end pt of contour: {}", end_pt_of_contour);
-                            }
-                            let instruction_len = read_u16_be(&mut reader)?;
-                            println!("instruction len: {}", instruction_len);
-                            for _ in 0..instruction_len {
-                                let instruction = read_u8(&mut reader)?;
-                            }
-                            let flag = read_u8(&mut reader)?;
-                            println!("0b{:08b}", flag);
-                            if flag & 0x01 != 0 {
-                                println!("ON_CURVE_POINT");
-                            }
-                            if flag & 0x02 != 0 {
-                                println!("X_SHORT_VECTOR");
-                                // x coordinate is 1 byte long
-                            }
-                            if flag & 0x04 != 0 {
-                                println!("Y_SHORT_VECTOR");
-                                // y coordinate is 1 byte long
-                            }
-                            if flag & 0x08 != 0 {
-                                println!("REPEAT_FLAG");
-                            }
-                            if flag & 0x10 != 0 {
-                                println!("X_IS_SAME_OR_POSITIVE_X_SHORT_VECTOR");
-                            }
-                            if flag & 0x20 != 0 {
-                                println!("Y_IS_SAME_OR_POSITIVE_Y_SHORT_VECTOR");
-                            }
-                            if flag & 0x40 != 0 {
-                                println!("OVERLAP_SIMPLE");
-                            }
-                            panic!("TODO: flags...");
-                        } else {
-                            // Compound Glyph
-                            panic!("Compount Glyphs not supported yet!");
-                        }
+                let (dx, dy) = if flags & ARGS_ARE_XY_VALUES != 0 {
+                    let (dx, dy) = (arg1 as f32, arg2 as f32);
+                    if flags & SCALED_COMPONENT_OFFSET != 0 {
+                        (a * dx + c * dy, b * dx + d * dy)
+                    } else {
+                        (dx, dy)
                     }
-                    //break;
+                } else {
+                    // Point matching: the point arg2 of the component lands on the point arg1 of the glyph so far
+                    let (Some(parent), Some(child)) =
+                        (outline.points().nth(arg1 as usize), component.points().nth(arg2 as usize))
+                    else {
+                        return invalid("Matched point out of range");
+                    };
+                    (parent.x - child.x, parent.y - child.y)
+                };
+                for point in component.contours.iter_mut().flatten() {
+                    point.x += dx;
+                    point.y += dy;
+                }
+                outline.contours.extend(component.contours);
+
+                if flags & MORE_COMPONENTS == 0 {
+                    break;
                 }
-                _ => {}
             }
         }
+        Ok(outline)
+    }
+
+    /// Entry of the `name` table, see the NAME_* constants.
+    pub fn name(&self, id: u16) -> Option<&str> {
+        self.names.iter().find(|(name_id, _)| *name_id == id).map(|(_, name)| name.as_str())
+    }
+
+    pub fn family_name(&self) -> Option<&str> {
+        self.name(NAME_FAMILY)
+    }
+
+    pub fn subfamily_name(&self) -> Option<&str> {
+        self.name(NAME_SUBFAMILY)
+    }
+
+    pub fn full_name(&self) -> Option<&str> {
+        self.name(NAME_FULL_NAME)
+    }
+
+    pub fn postscript_name(&self) -> Option<&str> {
+        self.name(NAME_POSTSCRIPT_NAME)
+    }
+}
+
+/// Takes `amount` glyphs or points from the budget of an outline.
+fn spend(budget: &mut usize, amount: usize) -> std::io::Result<()> {
+    match budget.checked_sub(amount) {
+        Some(rest) => {
+            *budget = rest;
+            Ok(())
+        }
+        None => invalid("Too many components and points"),
+    }
+}
+
+fn parse_cmap(bytes: &[u8]) -> std::io::Result<CharMap> {
+    let mut reader = std::io::Cursor::new(bytes);
+    let version = read_u16_be(&mut reader)?;
+    let num_subtables = read_u16_be(&mut reader)?;
+
+    // Prefer full Unicode (format 12) subtables over BMP (format 4) ones
+    let mut best = None;
+    for _ in 0..num_subtables {
+        let platform_id = read_u16_be(&mut reader)?;
+        let platform_specific_id = read_u16_be(&mut reader)?;
+        let offset = read_u32_be(&mut reader)? as usize;
+        let unicode = matches!((platform_id, platform_specific_id), (0, _) | (3, 1) | (3, 10));
+        let Some(format) = bytes.get(offset..offset + 2).map(|b| u16::from_be_bytes([b[0], b[1]])) else {
+            return invalid("cmap subtable out of bounds");
+        };
+        let rank = match format {
+            12 => 2,
+            4 => 1,
+            _ => continue,
+        };
+        if unicode && best.is_none_or(|(best_rank, _)| rank > best_rank) {
+            best = Some((rank, offset));
+        }
+    }
+    let Some((_, offset)) = best else {
+        return invalid("No Unicode cmap subtable of format 4 or 12");
+    };
+
+    let mut reader = std::io::Cursor::new(&bytes[offset..]);
+    match read_u16_be(&mut reader)? {
+        4 => {
+            let length = read_u16_be(&mut reader)? as usize;
+            let language = read_u16_be(&mut reader)?;
+            let seg_count = read_u16_be(&mut reader)? as usize / 2;
+            reader.set_position(14);
+            let mut read_array =
+                |len: usize| -> std::io::Result<Vec<u16>> { (0..len).map(|_| read_u16_be(&mut reader)).collect() };
+            let end_codes = read_array(seg_count)?;
+            let reserved_pad = read_array(1)?;
+            let start_codes = read_array(seg_count)?;
+            let id_deltas = read_array(seg_count)?;
+            let id_range_offsets = read_array(seg_count)?;
+            // The glyph id array goes up to the end of the subtable
+            let glyph_ids = read_array(length.saturating_sub(16 + 8 * seg_count) / 2)?;
+
+            let segments = (0..seg_count)
+                .map(|i| {
+                    // The range offset is relative to its own position in id_range_offsets
+                    let range_index = match id_range_offsets[i] {
+                        0 => None,
+                        offset => Some((offset as usize / 2 + i).wrapping_sub(seg_count)),
+                    };
+                    (start_codes[i], end_codes[i], id_deltas[i], range_index)
+                })
+                .collect();
+            Ok(CharMap::Format4(segments, glyph_ids))
+        }
+        _ => {
+            let reserved = read_u16_be(&mut reader)?;
+            let length = read_u32_be(&mut reader)?;
+            let language = read_u32_be(&mut reader)?;
+            let num_groups = read_u32_be(&mut reader)?;
+            let mut groups = vec![];
+            for _ in 0..num_groups {
+                groups.push((read_u32_be(&mut reader)?, read_u32_be(&mut reader)?, read_u32_be(&mut reader)?));
+            }
+            Ok(CharMap::Format12(groups))
+        }
+    }
+}
+
+/// Horizontal kerning pairs of the format 0 subtables of a `kern` table, in the Microsoft (version 0)
+/// or Apple (version 1) layout. Values of pairs found in several subtables are added up.
+fn parse_kern(bytes: &[u8]) -> std::io::Result<Vec<(u32, i16)>> {
+    let mut reader = std::io::Cursor::new(bytes);
+    let apple = read_u16_be(&mut reader)? == 1;
+    let num_tables = if apple {
+        reader.set_position(4);
+        read_u32_be(&mut reader)?
+    } else {
+        read_u16_be(&mut reader)? as u32
+    };
+
+    let mut pairs: Vec<(u32, i16)> = vec![];
+    for _ in 0..num_tables {
+        let start = reader.position();
+        let (length, format, usable) = if apple {
+            let length = read_u32_be(&mut reader)? as u64;
+            let coverage = read_u16_be(&mut reader)?;
+            let tuple_index = read_u16_be(&mut reader)?;
+            // Not vertical, cross-stream or variation
+            (length, coverage & 0xFF, coverage & 0xE000 == 0)
+        } else {
+            let version = read_u16_be(&mut reader)?;
+            let length = read_u16_be(&mut reader)? as u64;
+            let coverage = read_u16_be(&mut reader)?;
+            // Horizontal, not minimum or cross-stream
+            (length, coverage >> 8, coverage & 0x7 == 0x1)
+        };
+        if format == 0 && usable {
+            let num_pairs = read_u16_be(&mut reader)?;
+            reader.set_position(reader.position() + 6);
+            for _ in 0..num_pairs {
+                let key = read_u32_be(&mut reader)?;
+                let value = read_i16_be(&mut reader)?;
+                pairs.push((key, value));
+            }
+        }
+        reader.set_position(start + length);
+    }
+    pairs.sort_by_key(|(key, _)| *key);
+    pairs.dedup_by(|(key, value), (first_key, first_value)| {
+        let duplicated = key == first_key;
+        if duplicated {
+            *first_value = first_value.saturating_add(*value);
+        }
+        duplicated
+    });
+    Ok(pairs)
+}
+
+/// Names of the `name` table, English ones first.
+fn parse_names(bytes: &[u8]) -> std::io::Result<Vec<(u16, String)>> {
+    let mut reader = std::io::Cursor::new(bytes);
+    let format = read_u16_be(&mut reader)?;
+    let count = read_u16_be(&mut reader)?;
+    let string_offset = read_u16_be(&mut reader)? as usize;
+
+    let mut names = vec![];
+    for _ in 0..count {
+        let platform_id = read_u16_be(&mut reader)?;
+        let platform_specific_id = read_u16_be(&mut reader)?;
+        let language_id = read_u16_be(&mut reader)?;
+        let name_id = read_u16_be(&mut reader)?;
+        let length = read_u16_be(&mut reader)? as usize;
+        let offset = read_u16_be(&mut reader)? as usize;
+        let Some(string) = bytes.get(string_offset + offset..string_offset + offset + length) else {
+            return invalid("Name out of bounds");
+        };
+        let (priority, name) = match (platform_id, platform_specific_id, language_id) {
+            // Windows, Unicode BMP or full repertoire, English (United States)
+            (3, 1 | 10, 0x409) => (0, utf16_be(string)),
+            (0, _, _) | (3, 1 | 10, _) => (1, utf16_be(string)),
+            // Macintosh, Roman, English: the ASCII range is the same as Latin-1
+            (1, 0, 0) => (2, string.iter().map(|&b| b as char).collect()),
+            _ => continue,
+        };
+        names.push((priority, name_id, name));
+    }
+    names.sort_by_key(|(priority, ..)| *priority);
+    Ok(names.into_iter().map(|(_, id, name)| (id, name)).collect())
+}
+
+fn utf16_be(bytes: &[u8]) -> String {
+    let units: Vec<u16> = bytes.chunks_exact(2).map(|b| u16::from_be_bytes([b[0], b[1]])).collect();
+    String::from_utf16_lossy(&units)
+}
+
+/// Fonts searched in order for the glyphs of the characters, the fallback fonts complete the first one with the
+/// scripts and symbols it lacks.
+#[derive(Debug, Clone)]
+pub struct FontSet<'a> {
+    pub fonts: Vec<&'a Font>,
+}
+
+impl<'a> FontSet<'a> {
+    pub fn new(fonts: Vec<&'a Font>) -> Self {
+        assert!(!fonts.is_empty(), "A font set needs at least one font");
+        Self {
+            fonts,
+        }
+    }
+
+    /// Index in `fonts` of the first font with a glyph for `c`, and the glyph.
+    pub fn find(&self, c: char) -> Option<(usize, u16)> {
+        self.fonts.iter().enumerate().find_map(|(i, font)| font.glyph_index(c).map(|glyph| (i, glyph)))
+    }
+
+    /// Like `find` but falls back to the replacement character U+FFFD, then to the missing glyph of the first font.
+    pub fn lookup(&self, c: char) -> (usize, u16) {
+        self.find(c).or_else(|| self.find(char::REPLACEMENT_CHARACTER)).unwrap_or((0, 0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tag_new() {
+        assert_eq!(Tag::new(b"cmap"), Tag(0x636d6170));
+    }
+
+    fn be16(values: &[i32]) -> Vec<u8> {
+        values.iter().flat_map(|v| (*v as u16).to_be_bytes()).collect()
+    }
+
+    fn be32(values: &[u32]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_be_bytes()).collect()
+    }
+
+    /// Font with 4 glyphs: .notdef, a square, 4 points with repeated flags and a composite of both.
+    fn test_font(cmap_format: u16) -> Vec<u8> {
+        let square =
+            [be16(&[1, 0, 0, 500, 500, 3, 0]), vec![0x31, 0x21, 0x11, 0x21], be16(&[500, -500]), be16(&[500])].concat();
+        let repeated = [be16(&[1, 10, 10, 100, 200, 3, 0]), vec![0x3F, 2, 0x36], vec![10, 10, 10, 70, 10, 10, 10, 170]];
+        let repeated = repeated.concat();
+        let composite =
+            [be16(&[-1, -5, -50, 350, 205]), be16(&[0x2B, 1, 100, -50, 0x2000]), be16(&[0x02, 2]), vec![-5i8 as u8, 5]]
+                .concat();
+        test_font_with_glyphs(cmap_format, [vec![], square, repeated, composite])
+    }
+
+    /// `test_font` with other glyphs, the font still has 4 glyphs.
+    fn test_font_with_glyphs(cmap_format: u16, glyphs: [Vec<u8>; 4]) -> Vec<u8> {
+        let head = [
+            be32(&[0x00010000, 0x00010000, 0, 0x5F0F3CF5]),
+            be16(&[0, 1000]),
+            vec![0; 16],
+            be16(&[-5, -50, 500, 500, 0, 8, 2, 0, 0]),
+        ]
+        .concat();
+        let maxp = [be32(&[0x00005000]), be16(&[4])].concat();
+        let hhea = [be32(&[0x00010000]), be16(&[800, -200, 90]), vec![0; 24], be16(&[2])].concat();
+        let hmtx = be16(&[500, 0, 600, 50, 10, 20]);
+        let kern = be16(&[0, 1, 0, 26, 0x0001, 2, 12, 1, 0, 1, 2, -40, 2, 1, 25]);
+
+        let mut glyf = vec![];
+        let mut loca = vec![0];
+        for glyph in glyphs {
+            glyf.extend(&glyph);
+            glyf.resize(glyf.len().next_multiple_of(2), 0);
+            loca.push(glyf.len() as i32 / 2);
+        }
+        let loca = be16(&loca);
+
+        let subtable = match cmap_format {
+            4 => {
+                [be16(&[4, 44, 0, 6, 4, 1, 2]), be16(&[65, 67, 0xFFFF, 0, 65, 66, 0xFFFF, 1 - 65, 0, 1, 0, 4, 0, 2, 3])]
+                    .concat()
+            }
+            _ => [be16(&[12, 0]), be32(&[40, 0, 2, 65, 67, 1, 0x1F600, 0x1F600, 3])].concat(),
+        };
+        let cmap = [
+            be16(&[
+                0,
+                1,
+                3,
+                if cmap_format == 4 {
+                    1
+                } else {
+                    10
+                },
+            ]),
+            be32(&[12]),
+            subtable,
+        ]
+        .concat();
+
+        let strings = [vec![b'M', b'a', b'c'], be16(&[b'T' as i32, b'e' as i32, b's' as i32, b't' as i32])].concat();
+        let name = [
+            be16(&[0, 3, 6 + 3 * 12]),
+            be16(&[1, 0, 0, 1, 3, 0]),
+            be16(&[3, 1, 0x409, 1, 8, 3]),
+            be16(&[1, 0, 0, 2, 3, 0]),
+            strings,
+        ]
+        .concat();
+        let post = [be32(&[0x00030000, 0]), be16(&[-100, 50]), be32(&[1, 0, 0, 0, 0])].concat();
+
+        let tables = [
+            (b"cmap", cmap),
+            (b"glyf", glyf),
+            (b"head", head),
+            (b"hhea", hhea),
+            (b"hmtx", hmtx),
+            (b"kern", kern),
+            (b"loca", loca),
+            (b"maxp", maxp),
+            (b"name", name),
+            (b"post", post),
+        ];
+        let mut font = [be32(&[0x00010000]), be16(&[tables.len() as i32, 128, 3, 16])].concat();
+        let mut data = vec![];
+        for (tag, table) in &tables {
+            let offset = 12 + 16 * tables.len() + data.len();
+            font.extend(be32(&[Tag::new(tag).0, table_checksum(table), offset as u32, table.len() as u32]));
+            data.extend(table);
+            data.resize(data.len().next_multiple_of(4), 0);
+        }
+        font.extend(data);
+        font
+    }
+
+    #[test]
+    fn font() {
+        let point = |x, y, on_curve| OutlinePoint {
+            x,
+            y,
+            on_curve,
+        };
+        for format in [4, 12] {
+            let font = Font::from_bytes(test_font(format)).unwrap();
+            assert_eq!((font.units_per_em, font.num_glyphs), (1000, 4));
+            assert_eq!((font.ascent, font.descent, font.line_gap), (800, -200, 90));
+            assert_eq!(font.bounds, [-5, -50, 500, 500]);
+            assert_eq!((font.underline_position, font.underline_thickness, font.is_fixed_pitch), (-100, 50, true));
+            assert_eq!(font.family_name(), Some("Test"));
+            assert_eq!(font.subfamily_name(), Some("Mac"));
+            assert_eq!(font.full_name(), None);
 
-        //assert_eq!(bytes.len(), 0);
-        Ok(())
+            assert_eq!(font.glyph_index('A'), Some(1));
+            assert_eq!(font.glyph_index('B'), Some(2));
+            assert_eq!(font.glyph_index('C'), Some(3));
+            assert_eq!(font.glyph_index('D'), None);
+            assert_eq!(
+                font.glyph_index('\u{1F600}'),
+                if format == 12 {
+                    Some(3)
+                } else {
+                    None
+                }
+            );
+
+            assert_eq!(
+                font.h_metrics(1),
+                HMetrics {
+                    advance_width: 600,
+                    left_side_bearing: 50
+                }
+            );
+            assert_eq!(
+                font.h_metrics(3),
+                HMetrics {
+                    advance_width: 600,
+                    left_side_bearing: 20
+                }
+            );
+
+            assert_eq!((font.kerning(1, 2), font.kerning(2, 1), font.kerning(1, 1)), (-40, 25, 0));
+
+            assert_eq!(font.outline(0).unwrap(), Outline::default());
+            let square = font.outline(1).unwrap();
+            assert_eq!((square.xmin, square.ymin, square.xmax, square.ymax), (0, 0, 500, 500));
+            let square_points = vec![
+                point(0.0, 0.0, true),
+                point(500.0, 0.0, true),
+                point(500.0, 500.0, true),
+                point(0.0, 500.0, true),
+            ];
+            assert_eq!(square.contours, vec![square_points]);
+            let repeated_points = vec![
+                point(10.0, 10.0, true),
+                point(20.0, 20.0, true),
+                point(30.0, 30.0, true),
+                point(100.0, 200.0, false),
+            ];
+            assert_eq!(font.outline(2).unwrap().contours, vec![repeated_points]);
+
+            let composite = font.outline(3).unwrap();
+            assert_eq!(composite.contours.len(), 2);
+            assert_eq!(composite.contours[0][2], point(350.0, 200.0, true));
+            assert_eq!(composite.contours[1][3], point(95.0, 205.0, false));
+            assert!(font.outline(4).is_err());
+        }
+
+        let mut corrupted = test_font(4);
+        let last = corrupted.len() - 1;
+        corrupted[last] ^= 1;
+        assert!(Font::from_bytes(corrupted).unwrap_err().contains("Checksum of post"));
+
+        // unitsPerEm out of range in head (the third table)
+        for units_per_em in [0u16, 8, 20000] {
+            let mut font = test_font(4);
+            patch_table(&mut font, 2, 18, &units_per_em.to_be_bytes());
+            assert!(Font::from_bytes(font).unwrap_err().contains("unitsPerEm"));
+        }
+
+        // Glyph ids of a format 12 group past u32::MAX
+        let mut font = test_font(12);
+        patch_table(&mut font, 0, 36, &0xFFFF_FFFFu32.to_be_bytes());
+        let font = Font::from_bytes(font).unwrap();
+        assert_eq!((font.glyph_index('A'), font.glyph_index('C')), (None, None));
+    }
+
+    /// Overwrites bytes of the `table`-th table of a font from `test_font` and updates its checksum.
+    fn patch_table(font: &mut [u8], table: usize, offset: usize, bytes: &[u8]) {
+        let record = 12 + 16 * table;
+        let field = |i: usize| u32::from_be_bytes(font[record + i..record + i + 4].try_into().unwrap()) as usize;
+        let (start, length) = (field(8), field(12));
+        font[start + offset..][..bytes.len()].copy_from_slice(bytes);
+        let checksum = table_checksum(&font[start..start + length]);
+        font[record + 4..record + 8].copy_from_slice(&checksum.to_be_bytes());
+    }
+
+    #[test]
+    fn composite_fan_out() {
+        // Glyph 2 is 200 copies of the square and glyph 3 is 200 copies of glyph 2
+        let square =
+            [be16(&[1, 0, 0, 500, 500, 3, 0]), vec![0x31, 0x21, 0x11, 0x21], be16(&[500, -500]), be16(&[500])].concat();
+        let copies = |glyph: i32| {
+            let mut components = be16(&[-1, 0, 0, 500, 500]);
+            for i in 0..200 {
+                let more = if i < 199 {
+                    MORE_COMPONENTS
+                } else {
+                    0
+                };
+                components.extend([be16(&[(ARGS_ARE_XY_VALUES | more) as i32, glyph]), vec![0, 0]].concat());
+            }
+            components
+        };
+        let font = Font::from_bytes(test_font_with_glyphs(4, [vec![], square, copies(1), copies(2)])).unwrap();
+        assert_eq!(font.outline(2).unwrap().contours.len(), 200);
+        assert!(font.outline(3).unwrap_err().contains("Too many components and points"));
+    }
+
+    #[test]
+    fn font_set() {
+        let (bmp, full) = (Font::from_bytes(test_font(4)).unwrap(), Font::from_bytes(test_font(12)).unwrap());
+        let fonts = FontSet::new(vec![&bmp, &full]);
+        assert_eq!(fonts.find('A'), Some((0, 1)));
+        assert_eq!(fonts.find('\u{1F600}'), Some((1, 3)));
+        assert_eq!(fonts.find('D'), None);
+        assert_eq!(fonts.lookup('D'), (0, 0));
+    }
+
+    #[test]
+    fn bitmap_glyphs() {
+        assert_eq!(ACCENTED_LETTERS.chars().count(), BASE_LETTERS.chars().count());
+        assert_eq!(bitmap_char('a'), Some('a'));
+        assert_eq!(bitmap_char('É'), Some('E'));
+        assert_eq!(bitmap_char('ÿ'), Some('y'));
+        assert_eq!(bitmap_char('\u{2014}'), Some('-'));
+        assert_eq!(bitmap_char('\u{201C}'), Some('"'));
+        assert_eq!(bitmap_char('中'), None);
+        assert_eq!(bitmap_glyph('é'), &GLYPHS['e' as usize - ' ' as usize]);
+        assert_eq!(bitmap_glyph('\u{301}'), &REPLACEMENT_GLYPH);
+    }
+
+    #[test]
+    fn from_bitmap() {
+        #[rustfmt::skip]
+        let ring = [
+            1, 1, 1, 0,
+            1, 0, 1, 0,
+            1, 1, 1, 1,
+        ];
+        let outline = Outline::from_bitmap(&ring, 4, 3);
+        let corners: Vec<usize> = outline.contours.iter().map(|contour| contour.len()).collect();
+        assert_eq!(corners, [6, 4]);
+        assert_eq!((outline.xmin, outline.ymin, outline.xmax, outline.ymax), (0, 0, 4, 3));
+        let segments = outline.segments();
+        assert!(segments.iter().flatten().all(|segment| matches!(segment, Segment::Line(..))));
+        assert_eq!(segments[0].first().unwrap().start(), segments[0].last().unwrap().end());
+
+        let bitmap = crate::glyph_raster::rasterize(&outline, 1.0, (0.0, 3.0));
+        assert_eq!((bitmap.width, bitmap.height, bitmap.left, bitmap.top), (4, 3, 0, 0));
+        assert_eq!(bitmap.pixels.iter().map(|&p| (p == 255) as u8).collect::<Vec<_>>(), ring);
+    }
+
+    #[test]
+    #[ignore]
+    fn parse_ttf() {
+        let path = ["/usr/share/fonts/TTF/DejaVuSans.ttf", "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf"]
+            .into_iter()
+            .find(|path| std::path::Path::new(path).exists())
+            .unwrap();
+        let font = Font::load(path).unwrap();
+        assert_eq!(font.family_name(), Some("DejaVu Sans"));
+        assert_eq!(font.subfamily_name(), Some("Book"));
+        assert_eq!(font.units_per_em, 2048);
+        assert!(font.ascent > 0 && font.descent < 0);
+
+        let a = font.glyph_index('A').unwrap();
+        let outline = font.outline(a).unwrap();
+        assert_eq!(outline.contours.len(), 2);
+        assert!(font.h_metrics(a).advance_width > 0);
+        assert!(font.glyph_index('中').is_none());
+        let v = font.glyph_index('V').unwrap();
+        assert!(font.kerning(a, v) < 0);
+
+        // Composite glyphs are made of their components
+        let e_acute = font.outline(font.glyph_index('é').unwrap()).unwrap();
+        let e = font.outline(font.glyph_index('e').unwrap()).unwrap();
+        assert!(e_acute.contours.len() > e.contours.len());
+        for c in ' '..='~' {
+            let glyph = font.glyph_index(c).unwrap();
+            font.outline(glyph).unwrap();
+        }
+        let space = font.outline(font.glyph_index(' ').unwrap()).unwrap();
+        assert!(space.contours.is_empty());
     }
 }
 
@@ -335,34 +1323,56 @@ pub const GLYPHS: [Glyph; 95] = [
     [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 1, 1, 0, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0], // ~
 ];
 
+/// Drawn for the characters that have no glyph in `GLYPHS`
+#[rustfmt::skip]
+pub const REPLACEMENT_GLYPH: Glyph = [
+    1, 1, 1, 1, 1,
+    1, 0, 0, 0, 1,
+    1, 0, 0, 0, 1,
+    1, 0, 0, 0, 1,
+    1, 0, 0, 0, 1,
+    1, 0, 0, 0, 1,
+    1, 1, 1, 1, 1,
+];
+
+// Latin-1 letters drawn with the glyph of their base letter
+const ACCENTED_LETTERS: &str = "ÀÁÂÃÄÅÇÈÉÊËÌÍÎÏÑÒÓÔÕÖØÙÚÛÜÝàáâãäåçèéêëìíîïñòóôõöøùúûüýÿ";
+const BASE_LETTERS: &str = "AAAAAACEEEEIIIINOOOOOOUUUUYaaaaaaceeeeiiiinoooooouuuuyy";
+
+/// Character of `GLYPHS` used to draw `c`: printable ASCII as is, accented Latin-1 letters without their accent
+/// and typographic punctuation as the closest ASCII one. None for everything else.
+pub fn bitmap_char(c: char) -> Option<char> {
+    match c {
+        ' '..='~' => Some(c),
+        '\u{A0}' | '\u{2000}'..='\u{200A}' => Some(' '),
+        '\u{2010}'..='\u{2015}' | '\u{2212}' => Some('-'),
+        '\u{2018}'..='\u{201B}' | '\u{2032}' => Some('\''),
+        '\u{201C}'..='\u{201F}' | '\u{2033}' => Some('"'),
+        '\u{AB}' | '\u{2039}' => Some('<'),
+        '\u{BB}' | '\u{203A}' => Some('>'),
+        '\u{D7}' => Some('x'),
+        _ => ACCENTED_LETTERS.chars().position(|letter| letter == c).and_then(|i| BASE_LETTERS.chars().nth(i)),
+    }
+}
+
+/// Glyph of the pixel font for `c`, `REPLACEMENT_GLYPH` if there is none (see `bitmap_char`).
+pub fn bitmap_glyph(c: char) -> &'static Glyph {
+    match bitmap_char(c) {
+        Some(c) => &GLYPHS[c as usize - ' ' as usize],
+        None => &REPLACEMENT_GLYPH,
+    }
+}
+
+/// Prints `GLYPHS` out of a font image with 7x9 cells (1 pixel of padding) in rows of 18 characters from ' '.
 #[allow(dead_code)]
 pub fn generate_glyphs<P: AsRef<str>>(path: P) {
-    unsafe {
-        let mut width = 0;
-        let mut height = 0;
-        let mut channels = 0;
-        let mut path = path.as_ref().to_string();
-        path.push(0 as char);
-        let pixels = stbi_load(path.as_ptr() as *const i8, &mut width, &mut height, &mut channels, 1);
-
-        // 7x9 quads with 1 pixel of padding
-        let mut glyphs = vec![];
-        for row in 0..6 {
-            for col in 0..18 {
-                let quad = (7 * col, 9 * row, 7, 9);
-                let mut glyph = vec![];
-                for y in quad.1 + 1..quad.1 + 9 - 1 {
-                    for x in quad.0 + 1..quad.0 + 7 - 1 {
-                        glyph.push(if *pixels.offset((y * width + x) as isize) == 0 {
-                            0
-                        } else {
-                            1
-                        });
-                    }
-                }
-                glyphs.push(glyph);
-            }
-        }
-        println!("{:?}", glyphs);
-    }
+    let options = GridOptions {
+        cell_width: 7,
+        cell_height: 9,
+        padding: 1,
+        ..Default::default()
+    };
+    let font = BitmapFont::load_grid(path, &options).unwrap();
+    let glyphs: Vec<&Vec<u8>> = (' '..='~').map(|c| &font.glyph(c).pixels).collect();
+    println!("{:?}", glyphs);
 }
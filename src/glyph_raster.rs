@@ -0,0 +1,298 @@
+#[cfg(test)]
+use crate::glyph::OutlinePoint;
+use crate::glyph::{Font, Outline, Segment};
+
+// Scanline rasterizer for glyph outlines.
+// Contours are flattened into lines, then each pixel row is sampled by SUBSAMPLES horizontal scanlines. On each
+// scanline the crossings with the lines are sorted and filled with the non-zero winding rule, spans are accumulated
+// with exact horizontal coverage. Only f32 arithmetic in a fixed order is used so the output is deterministic.
+
+/// Scanlines per pixel row
+const SUBSAMPLES: usize = 16;
+/// Maximum distance in pixels between a quadratic curve and the lines approximating it
+const FLATNESS: f32 = 0.1;
+const MAX_CURVE_SEGMENTS: usize = 64;
+
+/// 8-bit coverage bitmap, rows from top to bottom.
+/// `left` and `top` are the offsets from the pen position on the baseline to the top-left corner, y down.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Bitmap {
+    pub width: u32,
+    pub height: u32,
+    pub left: i32,
+    pub top: i32,
+    pub pixels: Vec<u8>,
+}
+
+impl Bitmap {
+    pub fn get(&self, x: u32, y: u32) -> u8 {
+        self.pixels[(y * self.width + x) as usize]
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Line {
+    x0: f32,
+    y0: f32,
+    x1: f32,
+    y1: f32,
+}
+
+/// Lines approximating the closed contours, in pixels with y down.
+fn flatten(outline: &Outline, scale: f32, offset: (f32, f32)) -> Vec<Line> {
+    let transform = |p: (f32, f32)| (p.0 * scale + offset.0, -p.1 * scale + offset.1);
+    let mut lines = vec![];
+    for segment in outline.segments().into_iter().flatten() {
+        match segment {
+            Segment::Line(p0, p1) => push_line(&mut lines, transform(p0), transform(p1)),
+            Segment::Quad(p0, p1, p2) => push_quad(&mut lines, transform(p0), transform(p1), transform(p2)),
+        }
+    }
+    lines
+}
+
+fn push_line(lines: &mut Vec<Line>, p0: (f32, f32), p1: (f32, f32)) {
+    // Horizontal lines never cross a scanline
+    if p0.1 != p1.1 {
+        lines.push(Line {
+            x0: p0.0,
+            y0: p0.1,
+            x1: p1.0,
+            y1: p1.1,
+        });
+    }
+}
+
+fn push_quad(lines: &mut Vec<Line>, p0: (f32, f32), p1: (f32, f32), p2: (f32, f32)) {
+    // With n uniform steps the distance to the curve is at most |p0 - 2p1 + p2| / (4n²)
+    let (ddx, ddy) = (p0.0 - 2.0 * p1.0 + p2.0, p0.1 - 2.0 * p1.1 + p2.1);
+    let deviation = (ddx * ddx + ddy * ddy).sqrt() / 4.0;
+    let n = ((deviation / FLATNESS).sqrt().ceil() as usize).clamp(1, MAX_CURVE_SEGMENTS);
+    let mut previous = p0;
+    for i in 1..=n {
+        let t = i as f32 / n as f32;
+        let u = 1.0 - t;
+        let p = if i == n {
+            p2
+        } else {
+            (u * u * p0.0 + 2.0 * u * t * p1.0 + t * t * p2.0, u * u * p0.1 + 2.0 * u * t * p1.1 + t * t * p2.1)
+        };
+        push_line(lines, previous, p);
+        previous = p;
+    }
+}
+
+/// Rasterizes an outline in font units scaled by `scale` (pixel size / units per em).
+/// `offset` is the sub-pixel position of the pen, usually in [0, 1).
+pub fn rasterize(outline: &Outline, scale: f32, offset: (f32, f32)) -> Bitmap {
+    let lines = flatten(outline, scale, offset);
+    if lines.is_empty() {
+        return Bitmap::default();
+    }
+    let (mut xmin, mut ymin, mut xmax, mut ymax) = (f32::MAX, f32::MAX, f32::MIN, f32::MIN);
+    for line in &lines {
+        xmin = xmin.min(line.x0).min(line.x1);
+        xmax = xmax.max(line.x0).max(line.x1);
+        ymin = ymin.min(line.y0).min(line.y1);
+        ymax = ymax.max(line.y0).max(line.y1);
+    }
+    let (left, top) = (xmin.floor() as i32, ymin.floor() as i32);
+    let width = (xmax.ceil() as i32 - left).max(1) as usize;
+    let height = (ymax.ceil() as i32 - top).max(1) as usize;
+
+    let mut pixels = vec![0; width * height];
+    let mut coverage = vec![0.0f32; width];
+    let mut crossings: Vec<(f32, i32)> = vec![];
+    for row in 0..height {
+        let row_top = (top + row as i32) as f32;
+        let active: Vec<&Line> =
+            lines.iter().filter(|l| l.y0.max(l.y1) > row_top && l.y0.min(l.y1) < row_top + 1.0).collect();
+        coverage.fill(0.0);
+        for sample in 0..SUBSAMPLES {
+            let y = row_top + (sample as f32 + 0.5) / SUBSAMPLES as f32;
+            crossings.clear();
+            for line in &active {
+                // Half-open so that a scanline through a vertex counts it once
+                let (y0, y1, winding) = if line.y0 < line.y1 {
+                    (line.y0, line.y1, 1)
+                } else {
+                    (line.y1, line.y0, -1)
+                };
+                if y >= y0 && y < y1 {
+                    let x = line.x0 + (y - line.y0) * (line.x1 - line.x0) / (line.y1 - line.y0);
+                    crossings.push((x - left as f32, winding));
+                }
+            }
+            crossings.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+
+            let mut winding = 0;
+            let mut span_start = 0.0;
+            for &(x, direction) in &crossings {
+                let previous = winding;
+                winding += direction;
+                if previous == 0 && winding != 0 {
+                    span_start = x;
+                } else if previous != 0 && winding == 0 {
+                    add_span(&mut coverage, span_start, x);
+                }
+            }
+        }
+        for (pixel, coverage) in pixels[row * width..(row + 1) * width].iter_mut().zip(&coverage) {
+            *pixel = (coverage / SUBSAMPLES as f32 * 255.0).round().clamp(0.0, 255.0) as u8;
+        }
+    }
+
+    Bitmap {
+        width: width as u32,
+        height: height as u32,
+        left,
+        top,
+        pixels,
+    }
+}
+
+/// Adds the coverage of the span [x0, x1) to the pixels of a scanline.
+fn add_span(coverage: &mut [f32], x0: f32, x1: f32) {
+    let x0 = x0.clamp(0.0, coverage.len() as f32);
+    let x1 = x1.clamp(0.0, coverage.len() as f32);
+    if x1 <= x0 {
+        return;
+    }
+    let (first, last) = (x0 as usize, x1 as usize);
+    if first == last {
+        coverage[first] += x1 - x0;
+        return;
+    }
+    coverage[first] += (first + 1) as f32 - x0;
+    for pixel in &mut coverage[first + 1..last] {
+        *pixel += 1.0;
+    }
+    if last < coverage.len() {
+        coverage[last] += x1 - last as f32;
+    }
+}
+
+/// Rasterizes a glyph of `font` at `pixel_size` pixels per em.
+pub fn rasterize_glyph(font: &Font, glyph: u16, pixel_size: f32, offset: (f32, f32)) -> Result<Bitmap, String> {
+    let outline = font.outline(glyph)?;
+    Ok(rasterize(&outline, pixel_size / font.units_per_em as f32, offset))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x0: f32, y0: f32, x1: f32, y1: f32, clockwise: bool) -> Vec<OutlinePoint> {
+        let mut points = vec![(x0, y0), (x0, y1), (x1, y1), (x1, y0)];
+        if !clockwise {
+            points.reverse();
+        }
+        points
+            .into_iter()
+            .map(|(x, y)| OutlinePoint {
+                x,
+                y,
+                on_curve: true,
+            })
+            .collect()
+    }
+
+    fn outline(contours: Vec<Vec<OutlinePoint>>) -> Outline {
+        Outline {
+            contours,
+            ..Default::default()
+        }
+    }
+
+    /// One character per pixel, from ' ' (empty) to '@' (full).
+    fn ascii(bitmap: &Bitmap) -> String {
+        const LEVELS: &[u8] = b" .:-=+*#%@";
+        let mut s = String::new();
+        for y in 0..bitmap.height {
+            for x in 0..bitmap.width {
+                let level = (bitmap.get(x, y) as usize * (LEVELS.len() - 1) + 127) / 255;
+                s.push(LEVELS[level] as char);
+            }
+            s.push('\n');
+        }
+        s
+    }
+
+    #[test]
+    fn squares() {
+        let square = outline(vec![rect(0.0, 0.0, 500.0, 500.0, true)]);
+        let bitmap = rasterize(&square, 0.01, (0.0, 0.0));
+        assert_eq!((bitmap.width, bitmap.height, bitmap.left, bitmap.top), (5, 5, 0, -5));
+        assert!(bitmap.pixels.iter().all(|&p| p == 255));
+
+        // Sub-pixel positioning
+        let bitmap = rasterize(&square, 0.01, (0.5, 0.25));
+        assert_eq!((bitmap.width, bitmap.height, bitmap.left, bitmap.top), (6, 6, 0, -5));
+        assert_eq!(bitmap.pixels[..6], [96, 191, 191, 191, 191, 96]);
+        assert_eq!(bitmap.pixels[6..12], [128, 255, 255, 255, 255, 128]);
+
+        // Non-zero winding: overlapping contours in the same direction stay filled, opposite ones make holes
+        let overlap = outline(vec![rect(0.0, 0.0, 4.0, 4.0, true), rect(2.0, 0.0, 6.0, 4.0, true)]);
+        assert_eq!(ascii(&rasterize(&overlap, 1.0, (0.0, 0.0))), "@@@@@@\n".repeat(4));
+        let hole = outline(vec![rect(0.0, 0.0, 6.0, 6.0, true), rect(2.0, 2.0, 4.0, 4.0, false)]);
+        let expected = "@@@@@@\n@@@@@@\n@@  @@\n@@  @@\n@@@@@@\n@@@@@@\n";
+        assert_eq!(ascii(&rasterize(&hole, 1.0, (0.0, 0.0))), expected);
+
+        assert_eq!(rasterize(&Outline::default(), 1.0, (0.0, 0.0)), Bitmap::default());
+    }
+
+    #[test]
+    fn curves() {
+        // Circle of radius 5 made of off-curve points only, the on-curve points are implied
+        let r = 5.0;
+        let k = r * std::f32::consts::FRAC_PI_8.tan();
+        let circle: Vec<OutlinePoint> = (0..8)
+            .map(|i| {
+                let (x, y) = [(r, k), (k, r), (-k, r), (-r, k), (-r, -k), (-k, -r), (k, -r), (r, -k)][i];
+                OutlinePoint {
+                    x,
+                    y,
+                    on_curve: false,
+                }
+            })
+            .collect();
+        let bitmap = rasterize(&outline(vec![circle.clone()]), 1.0, (0.0, 0.0));
+        let expected = concat!(
+            "  :*%%*:  \n",
+            " =@@@@@@= \n",
+            ":@@@@@@@@:\n",
+            "*@@@@@@@@*\n",
+            "%@@@@@@@@%\n",
+            "%@@@@@@@@%\n",
+            "*@@@@@@@@*\n",
+            ":@@@@@@@@:\n",
+            " =@@@@@@= \n",
+            "  :*%%*:  \n",
+        );
+        assert_eq!(ascii(&bitmap), expected);
+        assert_eq!((bitmap.left, bitmap.top), (-5, -5));
+        // Symmetric
+        for y in 0..bitmap.height {
+            for x in 0..bitmap.width {
+                let mirrored = bitmap.get(bitmap.width - 1 - x, bitmap.height - 1 - y);
+                assert!(bitmap.get(x, y).abs_diff(mirrored) <= 1);
+            }
+        }
+        // Deterministic
+        assert_eq!(rasterize(&outline(vec![circle]), 1.0, (0.0, 0.0)), bitmap);
+    }
+
+    #[test]
+    #[ignore]
+    fn glyph() {
+        let path = ["/usr/share/fonts/TTF/DejaVuSans.ttf", "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf"]
+            .into_iter()
+            .find(|path| std::path::Path::new(path).exists())
+            .unwrap();
+        let font = Font::load(path).unwrap();
+        let glyph = font.glyph_index('g').unwrap();
+        let bitmap = rasterize_glyph(&font, glyph, 16.0, (0.0, 0.0)).unwrap();
+        println!("{}", ascii(&bitmap));
+        assert!(bitmap.top < 0 && bitmap.top + bitmap.height as i32 > 0);
+    }
+}
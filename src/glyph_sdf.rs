@@ -0,0 +1,673 @@
+use crate::atlas::{pack_rects, AtlasPage};
+use crate::bitmap_font::{BitmapFont, BitmapGlyph};
+use crate::glyph::{Font, FontSet, Outline, Segment};
+
+use std::collections::HashMap;
+
+// Signed distance field glyph atlases.
+// Every texel stores the distance from its centre to the glyph outline, 0.5 on the outline, more inside and less
+// outside, so one atlas can be rendered sharp at any scale by thresholding the bilinear interpolated distance.
+// Single-channel fields round sharp corners when magnified. Multi-channel fields (MSDF) split the contours into
+// edges at the corners and colour them so that every corner is formed by two edges sharing only one channel. Each
+// channel stores the pseudo-distance to the nearest edge of its colour, and the median of the three channels
+// rebuilds the sharp corners. The alpha channel of multi-channel fields stores the true distance.
+//
+// Rendering (see assets/shaders/atlas.frag): alpha = clamp((median - 0.5) * screen range + 0.5, 0, 1), where the
+// screen range is the distance range of the atlas scaled to the rendered size.
+
+/// Maximum distance in pixels between a quadratic curve and the lines approximating it
+const FLATNESS: f32 = 0.05;
+const MAX_CURVE_SEGMENTS: usize = 64;
+/// Edges meeting at a larger angle (in radians) form a corner
+const CORNER_ANGLE: f32 = 3.0;
+
+// Channels of an edge colour
+const RED: u8 = 1;
+const GREEN: u8 = 2;
+const BLUE: u8 = 4;
+const CYAN: u8 = GREEN | BLUE;
+const MAGENTA: u8 = RED | BLUE;
+const YELLOW: u8 = RED | GREEN;
+const WHITE: u8 = RED | GREEN | BLUE;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SdfMode {
+    Single,
+    Multi,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SdfOptions {
+    pub mode: SdfMode,
+    /// Atlas pixels per em
+    pub pixel_size: f32,
+    /// Distance in atlas pixels between the values 0 and 1 of the field
+    pub range: f32,
+    pub page_width: u32,
+    pub page_height: u32,
+    pub padding: u32,
+}
+
+impl Default for SdfOptions {
+    fn default() -> Self {
+        Self {
+            mode: SdfMode::Multi,
+            pixel_size: 32.0,
+            range: 4.0,
+            page_width: 512,
+            page_height: 512,
+            padding: 1,
+        }
+    }
+}
+
+/// Placement of a glyph in the atlas. Sizes are in ems, multiply by the text size to get pixels.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct SdfGlyph {
+    pub page: usize,
+    /// Normalized texture coordinates: [u0, v0, u1, v1]
+    pub uv: [f32; 4],
+    pub size: (f32, f32),
+    /// Offset from the pen position on the baseline to the top-left corner, y down
+    pub bearing: (f32, f32),
+    pub advance: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SdfAtlas {
+    pub glyphs: HashMap<char, SdfGlyph>,
+    /// Kerning pairs of the font in ems (see `text_layout::LayoutFont`)
+    pub kerning: HashMap<(char, char), f32>,
+    pub pages: Vec<AtlasPage>,
+    pub mode: SdfMode,
+    pub pixel_size: f32,
+    pub range: f32,
+    /// Line metrics in ems
+    pub ascent: f32,
+    pub descent: f32,
+    pub line_gap: f32,
+    pub material_base: u32, // Texture index of the first page once uploaded (see VkContext::load_sdf_atlas)
+}
+
+/// Distance field of one glyph, RGBA8 pixels.
+struct Field {
+    width: u32,
+    height: u32,
+    left: i32,
+    top: i32,
+    pixels: Vec<u8>,
+}
+
+/// Contour section between two corners, flattened into a polyline.
+struct Edge {
+    color: u8,
+    points: Vec<(f32, f32)>,
+}
+
+/// Distance from a point to an edge.
+#[derive(Debug, Clone, Copy)]
+struct EdgeDistance {
+    /// Unsigned distance to the edge
+    distance: f32,
+    /// Cosine of the angle between the edge and the direction to the point, 0 unless the point is past an end
+    dot: f32,
+    /// Signed distance to the closest piece of the edge, extended past the ends of the edge. Positive inside.
+    pseudo: f32,
+}
+
+impl EdgeDistance {
+    const FAR: Self = Self {
+        distance: f32::INFINITY,
+        dot: 1.0,
+        pseudo: f32::NEG_INFINITY,
+    };
+
+    fn closer_than(&self, other: &Self) -> bool {
+        (self.distance - other.distance).abs() <= 1e-4 && self.dot < other.dot || self.distance < other.distance - 1e-4
+    }
+}
+
+fn sub(a: (f32, f32), b: (f32, f32)) -> (f32, f32) {
+    (a.0 - b.0, a.1 - b.1)
+}
+
+fn dot(a: (f32, f32), b: (f32, f32)) -> f32 {
+    a.0 * b.0 + a.1 * b.1
+}
+
+fn cross(a: (f32, f32), b: (f32, f32)) -> f32 {
+    a.0 * b.1 - a.1 * b.0
+}
+
+fn normalize(v: (f32, f32)) -> (f32, f32) {
+    let len = dot(v, v).sqrt();
+    if len == 0.0 {
+        (0.0, 0.0)
+    } else {
+        (v.0 / len, v.1 / len)
+    }
+}
+
+/// Directions at the start and at the end of a segment.
+fn tangents(segment: &Segment) -> ((f32, f32), (f32, f32)) {
+    match *segment {
+        Segment::Line(p0, p1) => (sub(p1, p0), sub(p1, p0)),
+        Segment::Quad(p0, c, p1) => {
+            let start = if c == p0 {
+                sub(p1, p0)
+            } else {
+                sub(c, p0)
+            };
+            let end = if c == p1 {
+                sub(p1, p0)
+            } else {
+                sub(p1, c)
+            };
+            (start, end)
+        }
+    }
+}
+
+fn is_corner(a: (f32, f32), b: (f32, f32)) -> bool {
+    let (a, b) = (normalize(a), normalize(b));
+    dot(a, b) <= 0.0 || cross(a, b).abs() > CORNER_ANGLE.sin()
+}
+
+fn flatten_segment(points: &mut Vec<(f32, f32)>, segment: &Segment) {
+    match *segment {
+        Segment::Line(_, p1) => points.push(p1),
+        Segment::Quad(p0, c, p1) => {
+            // Same subdivision as the rasterizer, see glyph_raster::push_quad
+            let (ddx, ddy) = (p0.0 - 2.0 * c.0 + p1.0, p0.1 - 2.0 * c.1 + p1.1);
+            let deviation = (ddx * ddx + ddy * ddy).sqrt() / 4.0;
+            let n = ((deviation / FLATNESS).sqrt().ceil() as usize).clamp(1, MAX_CURVE_SEGMENTS);
+            for i in 1..n {
+                let t = i as f32 / n as f32;
+                let u = 1.0 - t;
+                points.push((
+                    u * u * p0.0 + 2.0 * u * t * c.0 + t * t * p1.0,
+                    u * u * p0.1 + 2.0 * u * t * c.1 + t * t * p1.1,
+                ));
+            }
+            points.push(p1);
+        }
+    }
+}
+
+/// Splits the contours into coloured edges at their corners.
+fn edges(contours: &[Vec<Segment>]) -> Vec<Edge> {
+    let mut edges = vec![];
+    for contour in contours {
+        let n = contour.len();
+        let corners: Vec<usize> =
+            (0..n).filter(|&i| is_corner(tangents(&contour[(i + n - 1) % n]).1, tangents(&contour[i]).0)).collect();
+
+        // Smooth contours (and teardrops) are a single edge with all the channels
+        let starts = if corners.len() < 2 {
+            vec![corners.first().copied().unwrap_or(0)]
+        } else {
+            corners
+        };
+        let count = starts.len();
+        let colors = [CYAN, MAGENTA, YELLOW];
+        for (k, &start) in starts.iter().enumerate() {
+            let end = if k + 1 < count {
+                starts[k + 1]
+            } else {
+                starts[0] + n
+            };
+            let color = if count == 1 {
+                WHITE
+            } else if k + 1 == count && colors[k % 3] == CYAN {
+                // The last edge also meets the first one, use the colour neither of them has
+                colors[(k + 1) % 3]
+            } else {
+                colors[k % 3]
+            };
+            let mut points = vec![contour[start].start()];
+            for i in start..end {
+                flatten_segment(&mut points, &contour[i % n]);
+            }
+            edges.push(Edge {
+                color,
+                points,
+            });
+        }
+    }
+    edges
+}
+
+fn edge_distance(edge: &Edge, p: (f32, f32)) -> EdgeDistance {
+    let pieces = edge.points.len() - 1;
+    let mut best = EdgeDistance::FAR;
+    for i in 0..pieces {
+        let (a, b) = (edge.points[i], edge.points[i + 1]);
+        let ab = sub(b, a);
+        let len2 = dot(ab, ab);
+        if len2 == 0.0 {
+            continue;
+        }
+        let t = dot(sub(p, a), ab) / len2;
+        let closest = if t <= 0.0 {
+            a
+        } else if t >= 1.0 {
+            b
+        } else {
+            (a.0 + ab.0 * t, a.1 + ab.1 * t)
+        };
+        let to_p = sub(p, closest);
+        let distance = dot(to_p, to_p).sqrt();
+        let dir = normalize(ab);
+        let side = cross(dir, sub(p, a));
+        // Past the ends of the edge the distance to the extended line is used, away from them the signed
+        // distance to the piece
+        let past_end = (i == 0 && t < 0.0) || (i + 1 == pieces && t > 1.0);
+        let pseudo = if past_end {
+            side
+        } else if side < 0.0 {
+            -distance
+        } else {
+            distance
+        };
+        let candidate = EdgeDistance {
+            distance,
+            dot: if (0.0..=1.0).contains(&t) {
+                0.0
+            } else {
+                dot(dir, normalize(to_p)).abs()
+            },
+            pseudo,
+        };
+        if candidate.closer_than(&best) {
+            best = candidate;
+        }
+    }
+    best
+}
+
+/// Non-zero winding number of the edges around `p`.
+fn winding(edges: &[Edge], p: (f32, f32)) -> i32 {
+    let mut winding = 0;
+    for edge in edges {
+        for piece in edge.points.windows(2) {
+            let (a, b) = (piece[0], piece[1]);
+            if (a.1 <= p.1) != (b.1 <= p.1) {
+                let x = a.0 + (p.1 - a.1) / (b.1 - a.1) * (b.0 - a.0);
+                if x > p.0 {
+                    winding += if b.1 > a.1 {
+                        1
+                    } else {
+                        -1
+                    };
+                }
+            }
+        }
+    }
+    winding
+}
+
+fn median(a: f32, b: f32, c: f32) -> f32 {
+    a.max(b).min(a.min(b).max(c))
+}
+
+fn encode(distance: f32, range: f32) -> u8 {
+    ((0.5 + distance / range).clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+/// Distance field of an outline in font units scaled by `scale`, with room for `range` around the glyph.
+fn generate(outline: &Outline, scale: f32, mode: SdfMode, range: f32) -> Field {
+    let contours: Vec<Vec<Segment>> = outline
+        .segments()
+        .into_iter()
+        .filter(|contour| !contour.is_empty())
+        .map(|contour| {
+            let transform = |p: (f32, f32)| (p.0 * scale, -p.1 * scale);
+            contour
+                .into_iter()
+                .map(|segment| match segment {
+                    Segment::Line(p0, p1) => Segment::Line(transform(p0), transform(p1)),
+                    Segment::Quad(p0, c, p1) => Segment::Quad(transform(p0), transform(c), transform(p1)),
+                })
+                .collect()
+        })
+        .collect();
+    if contours.is_empty() {
+        return Field {
+            width: 0,
+            height: 0,
+            left: 0,
+            top: 0,
+            pixels: vec![],
+        };
+    }
+
+    let mut edges = edges(&contours);
+    let (mut xmin, mut ymin, mut xmax, mut ymax) = (f32::MAX, f32::MAX, f32::MIN, f32::MIN);
+    for p in edges.iter().flat_map(|edge| &edge.points) {
+        (xmin, ymin, xmax, ymax) = (xmin.min(p.0), ymin.min(p.1), xmax.max(p.0), ymax.max(p.1));
+    }
+    let pad = range.ceil() as i32;
+    let (left, top) = (xmin.floor() as i32 - pad, ymin.floor() as i32 - pad);
+    let width = (xmax.ceil() as i32 + pad - left) as u32;
+    let height = (ymax.ceil() as i32 + pad - top) as u32;
+    for edge in &mut edges {
+        for p in &mut edge.points {
+            *p = (p.0 - left as f32, p.1 - top as f32);
+        }
+    }
+
+    let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+    for y in 0..height {
+        for x in 0..width {
+            let p = (x as f32 + 0.5, y as f32 + 0.5);
+            let distances: Vec<EdgeDistance> = edges.iter().map(|edge| edge_distance(edge, p)).collect();
+            let nearest = distances.iter().map(|d| d.distance).fold(f32::INFINITY, f32::min);
+            let inside = winding(&edges, p) != 0;
+            let signed = if inside {
+                nearest
+            } else {
+                -nearest
+            };
+            match mode {
+                SdfMode::Single => pixels.extend_from_slice(&[encode(signed, range); 4]),
+                SdfMode::Multi => {
+                    let mut channels = [signed; 3];
+                    for (channel, mask) in [RED, GREEN, BLUE].into_iter().enumerate() {
+                        let mut best = EdgeDistance::FAR;
+                        for (edge, distance) in edges.iter().zip(&distances) {
+                            if edge.color & mask != 0 && distance.closer_than(&best) {
+                                best = *distance;
+                            }
+                        }
+                        if best.distance.is_finite() {
+                            channels[channel] = best.pseudo;
+                        }
+                    }
+                    // Where the channels disagree with the winding the median would show artifacts
+                    if (median(channels[0], channels[1], channels[2]) > 0.0) != inside {
+                        channels = [signed; 3];
+                    }
+                    pixels.extend(channels.iter().map(|&d| encode(d, range)));
+                    pixels.push(encode(signed, range));
+                }
+            }
+        }
+    }
+    Field {
+        width,
+        height,
+        left,
+        top,
+        pixels,
+    }
+}
+
+impl SdfAtlas {
+    /// Distance fields of the characters of `chars` found in `font`, see `from_font_set`.
+    pub fn from_font<I: IntoIterator<Item = char>>(
+        font: &Font,
+        chars: I,
+        options: &SdfOptions,
+    ) -> Result<Self, String> {
+        Self::from_font_set(&FontSet::new(vec![font]), chars, options)
+    }
+
+    /// Distance fields of the characters of `chars` found in any font of the set, plus the replacement character
+    /// drawn for the others (see `FontSet::lookup`). Line metrics are the ones of the first font.
+    pub fn from_font_set<I: IntoIterator<Item = char>>(
+        fonts: &FontSet,
+        chars: I,
+        options: &SdfOptions,
+    ) -> Result<Self, String> {
+        let mut glyphs = vec![];
+        let mut indices: Vec<(char, usize, u16)> = vec![];
+        let replacement = fonts.lookup(char::REPLACEMENT_CHARACTER);
+        let found = chars.into_iter().filter_map(|c| fonts.find(c).map(|(font, glyph)| (c, font, glyph)));
+        for (c, font_index, glyph) in found.chain([(char::REPLACEMENT_CHARACTER, replacement.0, replacement.1)]) {
+            if indices.iter().any(|&(other, ..)| other == c) {
+                continue;
+            }
+            let font = fonts.fonts[font_index];
+            let units_per_em = font.units_per_em as f32;
+            let field = generate(&font.outline(glyph)?, options.pixel_size / units_per_em, options.mode, options.range);
+            glyphs.push((c, field, font.h_metrics(glyph).advance_width as f32 / units_per_em));
+            indices.push((c, font_index, glyph));
+        }
+        let font = fonts.fonts[0];
+        let units_per_em = font.units_per_em as f32;
+        let metrics = (
+            font.ascent as f32 / units_per_em,
+            font.descent as f32 / units_per_em,
+            font.line_gap as f32 / units_per_em,
+        );
+        let mut atlas = Self::build(glyphs, metrics, options)?;
+        for &(left, left_font, left_glyph) in &indices {
+            for &(right, right_font, right_glyph) in &indices {
+                let font = fonts.fonts[left_font];
+                let kerning = font.kerning(left_glyph, right_glyph);
+                if left_font == right_font && kerning != 0 {
+                    atlas.kerning.insert((left, right), kerning as f32 / font.units_per_em as f32);
+                }
+            }
+        }
+        Ok(atlas)
+    }
+
+    /// Distance fields of the built-in pixel font (see `glyph::GLYPHS`), one em is the height of a glyph.
+    /// Accented Latin-1 letters and typographic punctuation share the glyphs of ASCII ones (see `glyph::bitmap_char`).
+    pub fn from_bitmap_glyphs(options: &SdfOptions) -> Result<Self, String> {
+        Self::from_bitmap_font(&BitmapFont::builtin(), options)
+    }
+
+    /// Atlas of the glyphs of a pixel font, one em is the ascent plus the descent. Characters sharing the same
+    /// glyph share the same field, and the replacement glyph of the font is U+FFFD.
+    pub fn from_bitmap_font(font: &BitmapFont, options: &SdfOptions) -> Result<Self, String> {
+        let em = (font.ascent + font.descent).max(1) as f32;
+        let mut chars: Vec<char> = font.glyphs.keys().copied().collect();
+        chars.sort_unstable();
+        let mut unique: Vec<(char, &BitmapGlyph)> = vec![];
+        let mut aliases = vec![];
+        let all = chars.iter().map(|&c| (c, &font.glyphs[&c]));
+        for (c, glyph) in std::iter::once((char::REPLACEMENT_CHARACTER, &font.replacement)).chain(all) {
+            match unique.iter().find(|(_, other)| *other == glyph) {
+                Some(&(first, _)) => aliases.push((c, first)),
+                None => unique.push((c, glyph)),
+            }
+        }
+        let glyphs = unique
+            .iter()
+            .map(|&(c, glyph)| {
+                let mut outline = Outline::from_bitmap(&glyph.pixels, glyph.width as usize, glyph.height as usize);
+                // From the bottom-left corner of the bitmap to the pen position on the baseline, y up
+                let (dx, dy) = (glyph.left, -(glyph.top + glyph.height as i32));
+                for point in outline.contours.iter_mut().flatten() {
+                    point.x += dx as f32;
+                    point.y += dy as f32;
+                }
+                outline.xmin += dx as i16;
+                outline.xmax += dx as i16;
+                outline.ymin += dy as i16;
+                outline.ymax += dy as i16;
+                let field = generate(&outline, options.pixel_size / em, options.mode, options.range);
+                (c, field, glyph.advance as f32 / em)
+            })
+            .collect();
+        let metrics = (font.ascent as f32 / em, -(font.descent as f32) / em, font.line_gap as f32 / em);
+        let mut atlas = Self::build(glyphs, metrics, options)?;
+        for (c, first) in aliases {
+            let glyph = atlas.glyphs[&first];
+            atlas.glyphs.insert(c, glyph);
+        }
+        Ok(atlas)
+    }
+
+    fn build(glyphs: Vec<(char, Field, f32)>, metrics: (f32, f32, f32), options: &SdfOptions) -> Result<Self, String> {
+        let sizes: Vec<(u32, u32)> = glyphs.iter().map(|(_, field, _)| (field.width, field.height)).collect();
+        let rects = pack_rects(&sizes, options.page_width, options.page_height, options.padding)?;
+        let (page_width, page_height) = (options.page_width as f32, options.page_height as f32);
+        let mut atlas = Self {
+            glyphs: HashMap::new(),
+            kerning: HashMap::new(),
+            pages: vec![],
+            mode: options.mode,
+            pixel_size: options.pixel_size,
+            range: options.range,
+            ascent: metrics.0,
+            descent: metrics.1,
+            line_gap: metrics.2,
+            material_base: 0,
+        };
+        for ((c, field, advance), (page, rect)) in glyphs.into_iter().zip(rects) {
+            let mut glyph = SdfGlyph {
+                advance,
+                ..Default::default()
+            };
+            if field.width > 0 && field.height > 0 {
+                while atlas.pages.len() <= page {
+                    atlas.pages.push(AtlasPage {
+                        width: options.page_width,
+                        height: options.page_height,
+                        pixels: vec![0; (options.page_width * options.page_height * 4) as usize],
+                    });
+                }
+                let pixels = &mut atlas.pages[page].pixels;
+                let row_len = (field.width * 4) as usize;
+                for y in 0..field.height {
+                    let dst = (((rect.y + y) * options.page_width + rect.x) * 4) as usize;
+                    let src = (y * field.width * 4) as usize;
+                    pixels[dst..dst + row_len].copy_from_slice(&field.pixels[src..src + row_len]);
+                }
+                glyph = SdfGlyph {
+                    page,
+                    uv: [
+                        rect.x as f32 / page_width,
+                        rect.y as f32 / page_height,
+                        (rect.x + rect.w) as f32 / page_width,
+                        (rect.y + rect.h) as f32 / page_height,
+                    ],
+                    size: (field.width as f32 / options.pixel_size, field.height as f32 / options.pixel_size),
+                    bearing: (field.left as f32 / options.pixel_size, field.top as f32 / options.pixel_size),
+                    advance,
+                };
+            }
+            atlas.glyphs.insert(c, glyph);
+        }
+        Ok(atlas)
+    }
+
+    /// Glyph of `c`, or of the replacement character U+FFFD if the atlas has none.
+    pub fn glyph(&self, c: char) -> Option<&SdfGlyph> {
+        self.glyphs.get(&c).or_else(|| self.glyphs.get(&char::REPLACEMENT_CHARACTER))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::glyph::OutlinePoint;
+
+    fn square(size: f32) -> Outline {
+        let points = [(0.0, 0.0), (0.0, size), (size, size), (size, 0.0)];
+        Outline {
+            contours: vec![points
+                .into_iter()
+                .map(|(x, y)| OutlinePoint {
+                    x,
+                    y,
+                    on_curve: true,
+                })
+                .collect()],
+            ..Default::default()
+        }
+    }
+
+    fn texel(field: &Field, x: u32, y: u32) -> &[u8] {
+        let i = ((y * field.width + x) * 4) as usize;
+        &field.pixels[i..i + 4]
+    }
+
+    #[test]
+    fn single() {
+        let field = generate(&square(10.0), 1.0, SdfMode::Single, 4.0);
+        assert_eq!((field.width, field.height, field.left, field.top), (18, 18, -4, -14));
+        // Inside the centre is further than the range from the edges, outside the corners too
+        assert_eq!(texel(&field, 9, 9), &[255; 4]);
+        assert_eq!(texel(&field, 0, 0), &[0; 4]);
+        // Half a pixel from the edge
+        assert_eq!(texel(&field, 4, 9)[0], encode(0.5, 4.0));
+        assert_eq!(texel(&field, 3, 9)[0], encode(-0.5, 4.0));
+        for y in 0..field.height {
+            for x in 0..field.width {
+                let inside = (4..14).contains(&x) && (4..14).contains(&y);
+                assert_eq!(texel(&field, x, y)[0] > 128, inside, "{} {}", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn multi() {
+        let contours: Vec<Vec<Segment>> = square(10.0).segments();
+        let colors: Vec<u8> = edges(&contours).iter().map(|edge| edge.color).collect();
+        assert_eq!(colors, [CYAN, MAGENTA, YELLOW, MAGENTA]);
+
+        let field = generate(&square(10.0), 1.0, SdfMode::Multi, 4.0);
+        for y in 0..field.height {
+            for x in 0..field.width {
+                let t = texel(&field, x, y);
+                let inside = (4..14).contains(&x) && (4..14).contains(&y);
+                assert_eq!(median(t[0] as f32, t[1] as f32, t[2] as f32) > 128.0, inside, "{} {}", x, y);
+                assert_eq!(t[3] > 128, inside);
+            }
+        }
+        // A curved contour is a single white edge
+        let mut circle = square(10.0);
+        for p in &mut circle.contours[0] {
+            p.on_curve = false;
+        }
+        let contours = circle.segments();
+        let edges = edges(&contours);
+        assert_eq!(edges.len(), 1);
+        assert_eq!(edges[0].color, WHITE);
+    }
+
+    #[test]
+    fn bitmap_glyphs() {
+        let options = SdfOptions {
+            pixel_size: 14.0,
+            page_width: 256,
+            page_height: 256,
+            ..Default::default()
+        };
+        let atlas = SdfAtlas::from_bitmap_glyphs(&options).unwrap();
+        assert_eq!(atlas.pages.len(), 1);
+        assert_eq!(atlas.glyph(' ').unwrap().size, (0.0, 0.0));
+        for glyph in atlas.glyphs.values() {
+            assert!(glyph.uv.iter().all(|uv| (0.0..=1.0).contains(uv)));
+            assert_eq!(glyph.advance, 6.0 / 7.0);
+        }
+        // '!' is 1 pixel wide in the middle column, 7 high, plus the range on every side
+        let mark = atlas.glyph('!').unwrap();
+        assert_eq!(mark.size, ((2.0 + 8.0) / 14.0, (14.0 + 8.0) / 14.0));
+        assert_eq!(mark.bearing, ((4.0 - 4.0) / 14.0, (-14.0 - 4.0) / 14.0));
+        // Missing characters use the replacement glyph, accented letters the glyph of their base letter
+        assert_eq!(atlas.glyph('\u{e9}'), atlas.glyph('e'));
+        assert_eq!(atlas.glyph('\u{2014}'), atlas.glyph('-'));
+        assert_eq!(atlas.glyph('中'), atlas.glyph(char::REPLACEMENT_CHARACTER));
+        assert_ne!(atlas.glyph('中'), atlas.glyph('?'));
+    }
+
+    #[test]
+    #[ignore]
+    fn ttf() {
+        let font = Font::load("/usr/share/fonts/TTF/DejaVuSans.ttf")
+            .or_else(|_| Font::load("/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf"))
+            .unwrap();
+        let atlas = SdfAtlas::from_font(&font, ' '..='~', &SdfOptions::default()).unwrap();
+        assert_eq!(atlas.glyphs.len(), 96);
+        let o = atlas.glyph('o').unwrap();
+        assert!(o.size.0 > 0.5 && o.size.1 > 0.5);
+        assert!(o.bearing.1 < 0.0);
+        assert!(atlas.ascent > 0.0 && atlas.descent < 0.0);
+        assert!(atlas.kerning[&('A', 'V')] < 0.0);
+    }
+}
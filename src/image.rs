@@ -0,0 +1,643 @@
+use crate::bmp::{decode_bmp, encode_bmp};
+use crate::png::{decode_png, encode_png};
+use crate::pnm::{decode_pnm, encode_pgm, encode_ppm, is_pnm};
+use crate::qoi::{decode_qoi, encode_qoi};
+use crate::tga::{decode_tga, encode_tga, is_tga};
+
+// CPU images:
+// `RgbaImage` holds tightly packed RGBA8 pixels, rows from top to bottom, and knows whether its colors are sRGB
+// encoded (textures, sprites) or linear (distance fields, normal maps, masks). Resampling and mipmaps filter sRGB
+// images in linear light, and every filter works on premultiplied alpha so that transparent pixels don't bleed
+// their color into the opaque ones.
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSpace {
+    #[default]
+    Srgb,
+    Linear,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    Nearest,
+    /// Average of the covered pixels, the filter of mipmaps
+    Box,
+    Bilinear,
+    /// Sharpest, with some ringing around hard edges
+    Lanczos3,
+}
+
+impl Filter {
+    /// Radius of the filter in source pixels when magnifying
+    fn support(self) -> f32 {
+        match self {
+            Filter::Nearest | Filter::Box => 0.5,
+            Filter::Bilinear => 1.0,
+            Filter::Lanczos3 => 3.0,
+        }
+    }
+
+    fn weight(self, x: f32) -> f32 {
+        let x = x.abs();
+        match self {
+            Filter::Nearest | Filter::Box => (x <= 0.5) as u32 as f32,
+            Filter::Bilinear => (1.0 - x).max(0.0),
+            Filter::Lanczos3 if x < 1e-6 => 1.0,
+            Filter::Lanczos3 if x < 3.0 => {
+                let pi_x = std::f32::consts::PI * x;
+                3.0 * pi_x.sin() * (pi_x / 3.0).sin() / (pi_x * pi_x)
+            }
+            Filter::Lanczos3 => 0.0,
+        }
+    }
+}
+
+/// Memory layouts of pixels, see `RgbaImage::from_format` and `RgbaImage::to_format`. R8 and Rg8 keep the first
+/// channels as they are (GPU formats), Gray8 and GrayAlpha8 store the luminance. 16-bit and float formats are
+/// little endian and hold the same encoded values scaled to 0..1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelFormat {
+    R8,
+    Rg8,
+    Gray8,
+    GrayAlpha8,
+    Rgb8,
+    Rgba8,
+    Bgra8,
+    Rgba16,
+    Rgba32F,
+}
+
+impl PixelFormat {
+    pub fn bytes_per_pixel(self) -> usize {
+        match self {
+            PixelFormat::R8 | PixelFormat::Gray8 => 1,
+            PixelFormat::Rg8 | PixelFormat::GrayAlpha8 => 2,
+            PixelFormat::Rgb8 => 3,
+            PixelFormat::Rgba8 | PixelFormat::Bgra8 => 4,
+            PixelFormat::Rgba16 => 8,
+            PixelFormat::Rgba32F => 16,
+        }
+    }
+}
+
+/// Image file formats read by `load_image` and written by `RgbaImage::encode`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    Bmp,
+    Tga,
+    Qoi,
+    /// Color only, alpha is dropped when encoding
+    Ppm,
+    /// Luminance only, alpha is dropped when encoding
+    Pgm,
+}
+
+impl ImageFormat {
+    /// Format of a file from its first bytes. TGA files have no magic number and are detected last, from a
+    /// plausible header.
+    pub fn detect(bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(ImageFormat::Png)
+        } else if bytes.starts_with(b"BM") {
+            Some(ImageFormat::Bmp)
+        } else if bytes.starts_with(b"qoif") {
+            Some(ImageFormat::Qoi)
+        } else if is_pnm(bytes) {
+            Some(if matches!(bytes[1], b'2' | b'5') {
+                ImageFormat::Pgm
+            } else {
+                ImageFormat::Ppm
+            })
+        } else if is_tga(bytes) {
+            Some(ImageFormat::Tga)
+        } else {
+            None
+        }
+    }
+
+    /// Format of a file name from its extension, in any case
+    pub fn from_path<P: AsRef<std::path::Path>>(path: P) -> Option<Self> {
+        let extension = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "png" => Some(ImageFormat::Png),
+            "bmp" => Some(ImageFormat::Bmp),
+            "tga" => Some(ImageFormat::Tga),
+            "qoi" => Some(ImageFormat::Qoi),
+            "ppm" => Some(ImageFormat::Ppm),
+            "pgm" => Some(ImageFormat::Pgm),
+            _ => None,
+        }
+    }
+}
+
+/// Decodes a PNG, BMP, TGA, QOI, PPM or PGM file, detecting its format from its content.
+pub fn load_image(bytes: &[u8]) -> Result<RgbaImage, String> {
+    let (pixels, width, height) = match ImageFormat::detect(bytes).ok_or("Unknown image format")? {
+        ImageFormat::Png => decode_png(bytes)?,
+        ImageFormat::Bmp => decode_bmp(bytes)?,
+        ImageFormat::Tga => decode_tga(bytes)?,
+        ImageFormat::Qoi => decode_qoi(bytes)?,
+        ImageFormat::Ppm | ImageFormat::Pgm => decode_pnm(bytes)?,
+    };
+    Ok(RgbaImage::from_pixels(pixels, width as u32, height as u32))
+}
+
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+fn to_u8(c: f32) -> u8 {
+    (c * 255.0).round().clamp(0.0, 255.0) as u8
+}
+
+/// Rec. 709 luminance of encoded values
+fn luminance(p: &[u8]) -> u8 {
+    ((2126 * p[0] as u32 + 7152 * p[1] as u32 + 722 * p[2] as u32 + 5000) / 10000) as u8
+}
+
+/// Source pixels contributing to each destination pixel along one axis, with their normalized weights.
+fn contributions(src: u32, dst: u32, filter: Filter) -> Vec<Vec<(usize, f32)>> {
+    let scale = dst as f32 / src as f32;
+    // Minifying widens the filter to cover all the source pixels
+    let filter_scale = scale.min(1.0);
+    let support = filter.support() / filter_scale;
+    (0..dst)
+        .map(|i| {
+            let center = (i as f32 + 0.5) / scale;
+            let first = (center - support).floor() as i64;
+            let last = (center + support).ceil() as i64;
+            let mut weights: Vec<(usize, f32)> = vec![];
+            for j in first..=last {
+                let w = filter.weight((j as f32 + 0.5 - center) * filter_scale);
+                if w == 0.0 {
+                    continue;
+                }
+                // Edge pixels are repeated outside of the image
+                let j = j.clamp(0, src as i64 - 1) as usize;
+                match weights.last_mut() {
+                    Some((last, total)) if *last == j => *total += w,
+                    _ => weights.push((j, w)),
+                }
+            }
+            if weights.is_empty() {
+                weights.push(((center as usize).min(src as usize - 1), 1.0));
+            }
+            let total: f32 = weights.iter().map(|(_, w)| w).sum();
+            weights.iter_mut().for_each(|(_, w)| *w /= total);
+            weights
+        })
+        .collect()
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RgbaImage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+    pub color_space: ColorSpace,
+}
+
+impl RgbaImage {
+    /// Transparent black image
+    pub fn new(width: u32, height: u32) -> Self {
+        Self::from_pixels(vec![0; (width * height * 4) as usize], width, height)
+    }
+
+    pub fn from_pixels(pixels: Vec<u8>, width: u32, height: u32) -> Self {
+        assert_eq!(pixels.len(), (width * height * 4) as usize);
+        Self {
+            width,
+            height,
+            pixels,
+            color_space: ColorSpace::Srgb,
+        }
+    }
+
+    pub fn with_color_space(mut self, color_space: ColorSpace) -> Self {
+        self.color_space = color_space;
+        self
+    }
+
+    /// Loads an image file in any format of `load_image`.
+    pub fn load<P: AsRef<std::path::Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        load_image(&bytes).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Saves the image in the format of the extension of `path`.
+    pub fn save<P: AsRef<std::path::Path>>(&self, path: P) -> Result<(), String> {
+        let path = path.as_ref();
+        let format = ImageFormat::from_path(path).ok_or_else(|| format!("Unknown image format: {}", path.display()))?;
+        std::fs::write(path, self.encode(format)).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    pub fn encode(&self, format: ImageFormat) -> Vec<u8> {
+        let (pixels, width, height) = (&self.pixels, self.width as usize, self.height as usize);
+        match format {
+            ImageFormat::Png => encode_png(pixels, width, height),
+            ImageFormat::Bmp => encode_bmp(pixels, width, height),
+            ImageFormat::Tga => encode_tga(pixels, width, height),
+            ImageFormat::Qoi => encode_qoi(pixels, width, height),
+            ImageFormat::Ppm => encode_ppm(pixels, width, height),
+            ImageFormat::Pgm => encode_pgm(pixels, width, height),
+        }
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        ((y * self.width + x) * 4) as usize
+    }
+
+    pub fn get(&self, x: u32, y: u32) -> [u8; 4] {
+        let i = self.index(x, y);
+        [self.pixels[i], self.pixels[i + 1], self.pixels[i + 2], self.pixels[i + 3]]
+    }
+
+    pub fn set(&mut self, x: u32, y: u32, pixel: [u8; 4]) {
+        let i = self.index(x, y);
+        self.pixels[i..i + 4].copy_from_slice(&pixel);
+    }
+
+    /// Converts pixels of `format` to RGBA8.
+    pub fn from_format(format: PixelFormat, data: &[u8], width: u32, height: u32) -> Result<Self, String> {
+        let count = (width * height) as usize;
+        if data.len() != count * format.bytes_per_pixel() {
+            return Err(format!(
+                "Expected {} bytes for {}x{} {:?} pixels, found {}",
+                count * format.bytes_per_pixel(),
+                width,
+                height,
+                format,
+                data.len()
+            ));
+        }
+        let pixels = data
+            .chunks_exact(format.bytes_per_pixel())
+            .flat_map(|p| match format {
+                PixelFormat::R8 => [p[0], 0, 0, 255],
+                PixelFormat::Rg8 => [p[0], p[1], 0, 255],
+                PixelFormat::Gray8 => [p[0], p[0], p[0], 255],
+                PixelFormat::GrayAlpha8 => [p[0], p[0], p[0], p[1]],
+                PixelFormat::Rgb8 => [p[0], p[1], p[2], 255],
+                PixelFormat::Rgba8 => [p[0], p[1], p[2], p[3]],
+                PixelFormat::Bgra8 => [p[2], p[1], p[0], p[3]],
+                PixelFormat::Rgba16 => {
+                    std::array::from_fn(|c| to_u8(u16::from_le_bytes([p[2 * c], p[2 * c + 1]]) as f32 / 65535.0))
+                }
+                PixelFormat::Rgba32F => std::array::from_fn(|c| {
+                    to_u8(f32::from_le_bytes([p[4 * c], p[4 * c + 1], p[4 * c + 2], p[4 * c + 3]]))
+                }),
+            })
+            .collect();
+        Ok(Self::from_pixels(pixels, width, height))
+    }
+
+    /// Pixels in `format`, see `PixelFormat`.
+    pub fn to_format(&self, format: PixelFormat) -> Vec<u8> {
+        let mut out = Vec::with_capacity((self.width * self.height) as usize * format.bytes_per_pixel());
+        for p in self.pixels.chunks_exact(4) {
+            match format {
+                PixelFormat::R8 => out.push(p[0]),
+                PixelFormat::Rg8 => out.extend_from_slice(&p[..2]),
+                PixelFormat::Gray8 => out.push(luminance(p)),
+                PixelFormat::GrayAlpha8 => out.extend([luminance(p), p[3]]),
+                PixelFormat::Rgb8 => out.extend_from_slice(&p[..3]),
+                PixelFormat::Rgba8 => out.extend_from_slice(p),
+                PixelFormat::Bgra8 => out.extend([p[2], p[1], p[0], p[3]]),
+                PixelFormat::Rgba16 => out.extend(p.iter().flat_map(|&c| (c as u16 * 257).to_le_bytes())),
+                PixelFormat::Rgba32F => out.extend(p.iter().flat_map(|&c| (c as f32 / 255.0).to_le_bytes())),
+            }
+        }
+        out
+    }
+
+    /// Premultiplied linear values of the pixels
+    fn to_linear(&self) -> Vec<[f32; 4]> {
+        let table: Vec<f32> = (0..256)
+            .map(|c| match self.color_space {
+                ColorSpace::Srgb => srgb_to_linear(c as f32 / 255.0),
+                ColorSpace::Linear => c as f32 / 255.0,
+            })
+            .collect();
+        self.pixels
+            .chunks_exact(4)
+            .map(|p| {
+                let alpha = p[3] as f32 / 255.0;
+                [table[p[0] as usize] * alpha, table[p[1] as usize] * alpha, table[p[2] as usize] * alpha, alpha]
+            })
+            .collect()
+    }
+
+    fn from_linear(values: &[[f32; 4]], width: u32, height: u32, color_space: ColorSpace) -> Self {
+        let pixels = values
+            .iter()
+            .flat_map(|&[r, g, b, a]| {
+                let a = a.clamp(0.0, 1.0);
+                let encode = |c: f32| {
+                    let c = if a > 0.0 {
+                        (c / a).clamp(0.0, 1.0)
+                    } else {
+                        0.0
+                    };
+                    to_u8(match color_space {
+                        ColorSpace::Srgb => linear_to_srgb(c),
+                        ColorSpace::Linear => c,
+                    })
+                };
+                [encode(r), encode(g), encode(b), to_u8(a)]
+            })
+            .collect();
+        Self::from_pixels(pixels, width, height).with_color_space(color_space)
+    }
+
+    /// Resamples the image to `width` x `height` pixels.
+    pub fn resize(&self, width: u32, height: u32, filter: Filter) -> Self {
+        assert!(width > 0 && height > 0, "Invalid size {}x{}", width, height);
+        if filter == Filter::Nearest {
+            let mut out = Self::new(width, height).with_color_space(self.color_space);
+            for y in 0..height {
+                for x in 0..width {
+                    let src_x = ((x as u64 * 2 + 1) * self.width as u64 / (2 * width as u64)) as u32;
+                    let src_y = ((y as u64 * 2 + 1) * self.height as u64 / (2 * height as u64)) as u32;
+                    out.set(x, y, self.get(src_x, src_y));
+                }
+            }
+            return out;
+        }
+        let src = self.to_linear();
+        let (src_width, dst_width) = (self.width as usize, width as usize);
+        // Horizontal pass, then vertical pass
+        let columns = contributions(self.width, width, filter);
+        let mut horizontal = vec![[0.0; 4]; dst_width * self.height as usize];
+        for y in 0..self.height as usize {
+            for (x, weights) in columns.iter().enumerate() {
+                let out = &mut horizontal[y * dst_width + x];
+                for &(i, w) in weights {
+                    let p = src[y * src_width + i];
+                    (0..4).for_each(|c| out[c] += p[c] * w);
+                }
+            }
+        }
+        let rows = contributions(self.height, height, filter);
+        let mut values = vec![[0.0; 4]; dst_width * height as usize];
+        for (y, weights) in rows.iter().enumerate() {
+            for &(i, w) in weights {
+                for x in 0..dst_width {
+                    let p = horizontal[i * dst_width + x];
+                    let out = &mut values[y * dst_width + x];
+                    (0..4).for_each(|c| out[c] += p[c] * w);
+                }
+            }
+        }
+        Self::from_linear(&values, width, height, self.color_space)
+    }
+
+    /// Mip chain from this image (level 0) down to 1x1, each level half the size of the previous one (rounded
+    /// down) and filtered from it.
+    pub fn mipmaps(&self) -> Vec<Self> {
+        let mut levels = vec![self.clone()];
+        while let Some(level) = levels.last().filter(|level| level.width > 1 || level.height > 1) {
+            let next = level.resize((level.width / 2).max(1), (level.height / 2).max(1), Filter::Box);
+            levels.push(next);
+        }
+        levels
+    }
+
+    fn map_pixels(&self, width: u32, height: u32, source: impl Fn(u32, u32) -> (u32, u32)) -> Self {
+        let mut out = Self::new(width, height).with_color_space(self.color_space);
+        for y in 0..height {
+            for x in 0..width {
+                let (src_x, src_y) = source(x, y);
+                out.set(x, y, self.get(src_x, src_y));
+            }
+        }
+        out
+    }
+
+    pub fn flip_horizontal(&self) -> Self {
+        self.map_pixels(self.width, self.height, |x, y| (self.width - 1 - x, y))
+    }
+
+    pub fn flip_vertical(&self) -> Self {
+        self.map_pixels(self.width, self.height, |x, y| (x, self.height - 1 - y))
+    }
+
+    /// Rotates by 90 degrees clockwise.
+    pub fn rotate_90(&self) -> Self {
+        self.map_pixels(self.height, self.width, |x, y| (y, self.height - 1 - x))
+    }
+
+    pub fn rotate_180(&self) -> Self {
+        self.map_pixels(self.width, self.height, |x, y| (self.width - 1 - x, self.height - 1 - y))
+    }
+
+    /// Rotates by 90 degrees counterclockwise.
+    pub fn rotate_270(&self) -> Self {
+        self.map_pixels(self.height, self.width, |x, y| (self.width - 1 - y, x))
+    }
+
+    /// Copy of the `width` x `height` pixels at (`x`, `y`), clipped to the image.
+    pub fn sub_image(&self, x: u32, y: u32, width: u32, height: u32) -> Self {
+        let width = width.min(self.width.saturating_sub(x));
+        let height = height.min(self.height.saturating_sub(y));
+        self.map_pixels(width, height, |dx, dy| (x + dx, y + dy))
+    }
+
+    /// Overlapping pixels of `src` placed at (`x`, `y`) and of the image, (source pixel, destination pixel).
+    fn overlap(&self, src: &Self, x: i32, y: i32) -> impl Iterator<Item = (usize, usize)> {
+        let (width, height) = (self.width as i64, self.height as i64);
+        let (src_width, src_height) = (src.width as i64, src.height as i64);
+        let (x, y) = (x as i64, y as i64);
+        let (x0, x1) = (x.max(0), (x + src_width).min(width));
+        let (y0, y1) = (y.max(0), (y + src_height).min(height));
+        (y0..y1).flat_map(move |dy| {
+            (x0..x1).map(move |dx| (((dy - y) * src_width + dx - x) as usize * 4, (dy * width + dx) as usize * 4))
+        })
+    }
+
+    /// Replaces the pixels under `src` placed at (`x`, `y`), the parts outside of the image are ignored.
+    pub fn copy_from(&mut self, src: &Self, x: i32, y: i32) {
+        let pairs: Vec<_> = self.overlap(src, x, y).collect();
+        for (s, d) in pairs {
+            self.pixels[d..d + 4].copy_from_slice(&src.pixels[s..s + 4]);
+        }
+    }
+
+    /// Draws `src` at (`x`, `y`) over the image with alpha blending (straight alpha, in the encoded values).
+    pub fn blit(&mut self, src: &Self, x: i32, y: i32) {
+        let pairs: Vec<_> = self.overlap(src, x, y).collect();
+        for (s, d) in pairs {
+            let src_alpha = src.pixels[s + 3] as u32;
+            let dst_alpha = self.pixels[d + 3] as u32 * (255 - src_alpha) / 255;
+            let alpha = src_alpha + dst_alpha;
+            if alpha == 0 {
+                continue;
+            }
+            for c in 0..3 {
+                let sum = src.pixels[s + c] as u32 * src_alpha + self.pixels[d + c] as u32 * dst_alpha;
+                self.pixels[d + c] = ((sum + alpha / 2) / alpha) as u8;
+            }
+            self.pixels[d + 3] = alpha as u8;
+        }
+    }
+
+    /// Multiplies the colors by their alpha (for premultiplied alpha blending).
+    pub fn premultiply_alpha(&mut self) {
+        for p in self.pixels.chunks_exact_mut(4) {
+            for c in 0..3 {
+                p[c] = ((p[c] as u32 * p[3] as u32 + 127) / 255) as u8;
+            }
+        }
+    }
+
+    /// Reverses `premultiply_alpha`, some precision is lost for translucent pixels.
+    pub fn unpremultiply_alpha(&mut self) {
+        for p in self.pixels.chunks_exact_mut(4).filter(|p| p[3] > 0) {
+            for c in 0..3 {
+                p[c] = ((p[c] as u32 * 255 + p[3] as u32 / 2) / p[3] as u32).min(255) as u8;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 3x2 image with a different color per pixel
+    fn sample() -> RgbaImage {
+        let pixels = (0..6).flat_map(|i| [i * 10, i * 10 + 1, i * 10 + 2, 255]).collect();
+        RgbaImage::from_pixels(pixels, 3, 2)
+    }
+
+    fn reds(image: &RgbaImage) -> Vec<u8> {
+        image.pixels.chunks_exact(4).map(|p| p[0]).collect()
+    }
+
+    #[test]
+    fn transforms() {
+        let image = sample();
+        assert_eq!(reds(&image.flip_horizontal()), [20, 10, 0, 50, 40, 30]);
+        assert_eq!(reds(&image.flip_vertical()), [30, 40, 50, 0, 10, 20]);
+        let rotated = image.rotate_90();
+        assert_eq!((rotated.width, rotated.height), (2, 3));
+        assert_eq!(reds(&rotated), [30, 0, 40, 10, 50, 20]);
+        assert_eq!(reds(&image.rotate_270()), [20, 50, 10, 40, 0, 30]);
+        assert_eq!(image.rotate_90().rotate_90(), image.rotate_180());
+        assert_eq!(image.rotate_90().rotate_270(), image);
+        assert_eq!(reds(&image.sub_image(1, 1, 5, 5)), [40, 50]);
+    }
+
+    #[test]
+    fn copy_and_blit() {
+        let mut image = RgbaImage::new(3, 2);
+        image.copy_from(&sample(), 1, -1);
+        assert_eq!(reds(&image), [0, 30, 40, 0, 0, 0]);
+
+        let mut background = RgbaImage::from_pixels([200, 100, 0, 255].repeat(2), 2, 1);
+        let overlay = RgbaImage::from_pixels(vec![0, 0, 200, 255, 0, 0, 200, 0], 2, 1);
+        background.blit(&overlay, 0, 0);
+        assert_eq!(background.pixels, [0, 0, 200, 255, 200, 100, 0, 255]);
+        let mut transparent = RgbaImage::new(1, 1);
+        transparent.blit(&RgbaImage::from_pixels(vec![100, 50, 0, 128], 1, 1), 0, 0);
+        assert_eq!(transparent.pixels, [100, 50, 0, 128]);
+    }
+
+    #[test]
+    fn premultiply() {
+        let mut image = RgbaImage::from_pixels(vec![255, 128, 0, 128, 10, 20, 30, 0], 2, 1);
+        image.premultiply_alpha();
+        assert_eq!(image.pixels, [128, 64, 0, 128, 0, 0, 0, 0]);
+        image.unpremultiply_alpha();
+        assert_eq!(image.pixels, [255, 128, 0, 128, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn formats() {
+        let image = sample();
+        for format in [PixelFormat::Rgba8, PixelFormat::Bgra8, PixelFormat::Rgba16, PixelFormat::Rgba32F] {
+            let data = image.to_format(format);
+            assert_eq!(data.len(), 6 * format.bytes_per_pixel());
+            assert_eq!(RgbaImage::from_format(format, &data, 3, 2).unwrap(), image, "{:?}", format);
+        }
+        assert_eq!(image.to_format(PixelFormat::Bgra8)[..4], [2, 1, 0, 255]);
+        assert_eq!(image.to_format(PixelFormat::Rgba16)[..2], [0x00, 0x00]);
+        assert_eq!(image.to_format(PixelFormat::Rg8)[2..4], [10, 11]);
+        assert_eq!(image.to_format(PixelFormat::Gray8)[1], 11);
+        let gray = RgbaImage::from_format(PixelFormat::GrayAlpha8, &[7, 9], 1, 1).unwrap();
+        assert_eq!(gray.pixels, [7, 7, 7, 9]);
+        assert_eq!(RgbaImage::from_format(PixelFormat::R8, &[7], 1, 1).unwrap().pixels, [7, 0, 0, 255]);
+        assert!(RgbaImage::from_format(PixelFormat::Rgb8, &[0; 17], 3, 2).is_err());
+    }
+
+    #[test]
+    fn resize() {
+        // Black and white columns average to the sRGB encoding of half the light, not to 128
+        let stripes = RgbaImage::from_pixels([0, 0, 0, 255, 255, 255, 255, 255].repeat(4), 4, 2);
+        let half = stripes.resize(2, 1, Filter::Box);
+        assert_eq!(half.pixels, [188, 188, 188, 255].repeat(2));
+        let linear = stripes.clone().with_color_space(ColorSpace::Linear).resize(1, 1, Filter::Box);
+        assert_eq!(linear.pixels, [128, 128, 128, 255]);
+
+        // Transparent pixels don't bleed their color
+        let edge = RgbaImage::from_pixels(vec![255, 0, 0, 255, 0, 255, 0, 0], 2, 1);
+        assert_eq!(edge.resize(1, 1, Filter::Box).pixels, [255, 0, 0, 128]);
+
+        // Upscaling a constant image keeps it constant, nearest repeats the pixels
+        let constant = RgbaImage::from_pixels([40, 80, 120, 255].repeat(4), 2, 2);
+        for filter in [Filter::Box, Filter::Bilinear, Filter::Lanczos3] {
+            assert_eq!(constant.resize(5, 3, filter).pixels, [40, 80, 120, 255].repeat(15), "{:?}", filter);
+        }
+        assert_eq!(reds(&sample().resize(6, 1, Filter::Nearest)), [30, 30, 40, 40, 50, 50]);
+        // Bilinear magnification interpolates between the pixel centers
+        let ramp =
+            RgbaImage::from_pixels(vec![0, 0, 0, 255, 0, 0, 0, 255, 255, 255, 255, 255, 255, 255, 255, 255], 4, 1)
+                .with_color_space(ColorSpace::Linear);
+        assert_eq!(reds(&ramp.resize(8, 1, Filter::Bilinear)), [0, 0, 0, 64, 191, 255, 255, 255]);
+    }
+
+    #[test]
+    fn mipmaps() {
+        let image = RgbaImage::from_pixels([255, 0, 0, 255, 0, 0, 255, 255].repeat(20), 5, 8);
+        let levels = image.mipmaps();
+        let sizes: Vec<(u32, u32)> = levels.iter().map(|level| (level.width, level.height)).collect();
+        assert_eq!(sizes, [(5, 8), (2, 4), (1, 2), (1, 1)]);
+        assert_eq!(levels[0], image);
+        assert!(levels.iter().all(|level| level.color_space == ColorSpace::Srgb));
+        assert_eq!(RgbaImage::new(1, 1).mipmaps().len(), 1);
+    }
+
+    #[test]
+    fn file_formats() {
+        let mut image = sample();
+        for format in [ImageFormat::Png, ImageFormat::Bmp, ImageFormat::Tga, ImageFormat::Qoi, ImageFormat::Ppm] {
+            let file = image.encode(format);
+            assert_eq!(ImageFormat::detect(&file), Some(format));
+            assert_eq!(load_image(&file).unwrap(), image, "{:?}", format);
+        }
+        let gray = load_image(&image.encode(ImageFormat::Pgm)).unwrap();
+        assert_eq!(gray.get(2, 1), [51, 51, 51, 255]);
+
+        image.set(1, 0, [1, 2, 3, 4]);
+        for format in [ImageFormat::Png, ImageFormat::Bmp, ImageFormat::Tga, ImageFormat::Qoi] {
+            assert_eq!(load_image(&image.encode(format)).unwrap(), image, "{:?}", format);
+        }
+        assert!(load_image(b"GIF89a").unwrap_err().contains("Unknown"));
+        assert_eq!(ImageFormat::from_path("a/b.Tga"), Some(ImageFormat::Tga));
+        assert_eq!(ImageFormat::from_path("a.gif"), None);
+    }
+}
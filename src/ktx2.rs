@@ -0,0 +1,302 @@
+use crate::deflate::zlib_decompress;
+use crate::parsing::{get_bytes, get_u32_le, get_u64_le};
+use crate::vk_sys::*;
+
+// KTX2 textures:
+// Reads the Khronos texture container (https://registry.khronos.org/KTX/specs/2.0/ktxspec.v2.html), which stores the
+// mip levels, array layers and cubemap faces of a texture in a Vulkan format, compressed (BCn, ETC2, ASTC) or not, as
+// the GPU expects them. Levels may be supercompressed with zlib; BasisLZ and Zstandard are not supported, nor are 3D
+// textures. The data format descriptor is ignored as `format` describes the data.
+
+const IDENTIFIER: [u8; 12] = [0xAB, b'K', b'T', b'X', b' ', b'2', b'0', 0xBB, b'\r', b'\n', 0x1A, b'\n'];
+const HEADER_SIZE: usize = 80;
+const LEVEL_INDEX_ENTRY_SIZE: usize = 24;
+const SUPERCOMPRESSION_NONE: u32 = 0;
+const SUPERCOMPRESSION_ZLIB: u32 = 3;
+
+#[derive(Debug, Default)]
+pub struct Ktx2 {
+    pub format: VkFormat,
+    pub width: u32,
+    pub height: u32,
+    /// Array layers, 1 for textures that aren't arrays
+    pub layers: u32,
+    /// 6 for cubemaps, 1 otherwise
+    pub faces: u32,
+    /// Whether the texture is an array, even of a single layer
+    pub is_array: bool,
+    /// Data of every mip level from the full size down, each holding the images of every layer, and of every face
+    /// within each layer
+    pub levels: Vec<Vec<u8>>,
+    /// Key/value metadata, like "KTXorientation" or "KTXwriter"
+    pub key_values: Vec<(String, Vec<u8>)>,
+}
+
+/// Region of `bytes` or an error naming it if it is out of bounds
+fn section<'a>(bytes: &'a [u8], offset: usize, length: usize, name: &str) -> Result<&'a [u8], String> {
+    get_bytes(bytes, offset, length).map_err(|_| format!("Truncated KTX2 {}", name))
+}
+
+/// Texel block of a format as (width, height, size in bytes), 1x1 for uncompressed formats. None for the combined
+/// depth/stencil formats, which are stored as separate planes.
+pub fn texel_block(format: VkFormat) -> Option<(u32, u32, usize)> {
+    // Values of the VkFormat variants, which are grouped by size
+    let size = match format as u32 {
+        1 | 9..=15 | 127 => 1,
+        2..=8 | 16..=22 | 70..=76 | 124 => 2,
+        23..=36 => 3,
+        37..=69 | 77..=83 | 98..=100 | 122 | 123 | 125 | 126 => 4,
+        84..=90 => 6,
+        91..=97 | 101..=103 | 110..=112 => 8,
+        104..=106 => 12,
+        107..=109 | 113..=115 => 16,
+        116..=118 => 24,
+        119..=121 => 32,
+        // BC1, BC4, ETC2 RGB and RGB A1, EAC R11
+        131..=134 | 139 | 140 | 147..=150 | 153 | 154 => return Some((4, 4, 8)),
+        // BC2, BC3, BC5 to BC7, ETC2 RGBA, EAC RG11
+        135..=138 | 141..=146 | 151 | 152 | 155 | 156 => return Some((4, 4, 16)),
+        // ASTC, UNORM and SRGB of each block size
+        157..=184 => {
+            const ASTC: [(u32, u32); 14] = [
+                (4, 4),
+                (5, 4),
+                (5, 5),
+                (6, 5),
+                (6, 6),
+                (8, 5),
+                (8, 6),
+                (8, 8),
+                (10, 5),
+                (10, 6),
+                (10, 8),
+                (10, 10),
+                (12, 10),
+                (12, 12),
+            ];
+            let (width, height) = ASTC[(format as u32 - 157) as usize / 2];
+            return Some((width, height, 16));
+        }
+        _ => return None,
+    };
+    Some((1, 1, size))
+}
+
+impl Ktx2 {
+    pub fn parse(bytes: &[u8]) -> Result<Self, String> {
+        if !bytes.starts_with(&IDENTIFIER) || bytes.len() < HEADER_SIZE {
+            return Err(String::from("Not a KTX2 file"));
+        }
+        let field = |i: usize| get_u32_le(bytes, 12 + i * 4);
+        let (vk_format, width, height, depth) = (field(0)?, field(2)?, field(3)?, field(4)?);
+        let (layers, faces, level_count, supercompression) = (field(5)?, field(6)?, field(7)?, field(8)?);
+        // The enum has no variants between the ASTC formats and the extension formats
+        if vk_format == 0 || vk_format > VK_FORMAT_ASTC_12x12_SRGB_BLOCK as u32 {
+            return Err(format!("Unsupported KTX2 format {}", vk_format));
+        }
+        // SAFETY: VkFormat is a repr(C) enum with variants for every value from 0 to VK_FORMAT_ASTC_12x12_SRGB_BLOCK
+        let format = unsafe { std::mem::transmute::<u32, VkFormat>(vk_format) };
+        let (block_width, block_height, block_size) =
+            texel_block(format).ok_or_else(|| format!("Unsupported KTX2 format {:?}", format))?;
+        if width == 0 || depth > 0 {
+            return Err(String::from("Only 1D and 2D KTX2 textures are supported"));
+        }
+        if faces != 1 && faces != 6 {
+            return Err(format!("Invalid KTX2 face count {}", faces));
+        }
+        if faces == 6 && width != height {
+            return Err(format!("KTX2 cubemap faces of {}x{} aren't square", width, height));
+        }
+        if supercompression != SUPERCOMPRESSION_NONE && supercompression != SUPERCOMPRESSION_ZLIB {
+            return Err(format!("Unsupported KTX2 supercompression scheme {}", supercompression));
+        }
+        // A level count of 0 asks the loader to generate the mipmaps, the file has the full size level only
+        let level_count = level_count.max(1) as usize;
+        let max_levels = 32 - width.max(height).leading_zeros() as usize;
+        if level_count > max_levels {
+            return Err(format!("Too many KTX2 levels: {} for {}x{}", level_count, width, height));
+        }
+
+        let index = section(bytes, HEADER_SIZE, level_count * LEVEL_INDEX_ENTRY_SIZE, "level index")?;
+        let mut levels = vec![];
+        let images = layers.max(1) as u64 * faces as u64;
+        for (level, entry) in index.chunks_exact(LEVEL_INDEX_ENTRY_SIZE).enumerate() {
+            let entry_field = |offset: usize| get_u64_le(entry, offset).map(|value| value as usize);
+            let (offset, length, uncompressed_length) = (entry_field(0)?, entry_field(8)?, entry_field(16)?);
+            let data = section(bytes, offset, length, "level")?;
+            let data = match supercompression {
+                SUPERCOMPRESSION_ZLIB => {
+                    let data = zlib_decompress(data).map_err(|e| format!("Invalid KTX2 level: {}", e))?;
+                    if data.len() != uncompressed_length {
+                        return Err(format!("KTX2 level of {} bytes, expected {}", data.len(), uncompressed_length));
+                    }
+                    data
+                }
+                _ => data.to_vec(),
+            };
+            // The device copies whole images from the level, it must hold exactly every block of them
+            let (level_width, level_height) = ((width >> level).max(1), (height.max(1) >> level).max(1));
+            let blocks = level_width.div_ceil(block_width) as u64 * level_height.div_ceil(block_height) as u64;
+            let expected = blocks.checked_mul(block_size as u64 * images);
+            if expected != Some(data.len() as u64) {
+                return Err(format!(
+                    "KTX2 level {} of {} bytes should hold {} {}x{} images of {:?}",
+                    level,
+                    data.len(),
+                    images,
+                    level_width,
+                    level_height,
+                    format
+                ));
+            }
+            levels.push(data);
+        }
+
+        let mut key_values = vec![];
+        let mut kvd = section(bytes, field(11)? as usize, field(12)? as usize, "key/value data")?;
+        while kvd.len() >= 4 {
+            let length = get_u32_le(kvd, 0)? as usize;
+            let entry = section(kvd, 4, length, "key/value data")?;
+            let nul = entry.iter().position(|&b| b == 0).ok_or("KTX2 key without terminator")?;
+            let key = String::from_utf8_lossy(&entry[..nul]).into_owned();
+            key_values.push((key, entry[nul + 1..].to_vec()));
+            // Entries are padded to 4 bytes
+            kvd = &kvd[(4 + length).next_multiple_of(4).min(kvd.len())..];
+        }
+
+        Ok(Self {
+            format,
+            width,
+            height: height.max(1),
+            layers: layers.max(1),
+            faces,
+            is_array: layers > 0,
+            levels,
+            key_values,
+        })
+    }
+
+    pub fn load<P: AsRef<std::path::Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        Self::parse(&bytes).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn is_cubemap(&self) -> bool {
+        self.faces == 6
+    }
+
+    /// Size of a mip level in pixels
+    pub fn level_size(&self, level: usize) -> (u32, u32) {
+        ((self.width >> level).max(1), (self.height >> level).max(1))
+    }
+
+    /// Data of one face of one layer of a mip level
+    pub fn image(&self, level: usize, layer: u32, face: u32) -> &[u8] {
+        let data = &self.levels[level];
+        let size = data.len() / (self.layers * self.faces) as usize;
+        let i = (layer * self.faces + face) as usize;
+        &data[i * size..(i + 1) * size]
+    }
+
+    pub fn key_value(&self, key: &str) -> Option<&[u8]> {
+        self.key_values.iter().find(|(k, _)| k == key).map(|(_, value)| &value[..])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deflate::zlib_compress;
+
+    /// KTX2 file with the given header fields, key/value data and levels from the full size down, which are
+    /// compressed if the fields ask for zlib supercompression.
+    fn ktx2(fields: [u32; 9], key_values: &[(&str, &[u8])], levels: &[Vec<u8>]) -> Vec<u8> {
+        let mut kvd = vec![];
+        for (key, value) in key_values {
+            let entry = [key.as_bytes(), &[0], value].concat();
+            kvd.extend((entry.len() as u32).to_le_bytes());
+            kvd.extend(entry);
+            kvd.resize(kvd.len().next_multiple_of(4), 0);
+        }
+        let kvd_offset = HEADER_SIZE + levels.len() * LEVEL_INDEX_ENTRY_SIZE;
+        let mut out = IDENTIFIER.to_vec();
+        fields.iter().for_each(|field| out.extend(field.to_le_bytes()));
+        for value in [0, 0, kvd_offset as u32, kvd.len() as u32] {
+            out.extend(value.to_le_bytes());
+        }
+        out.extend([0; 16]);
+        let supercompressed = fields[8] == SUPERCOMPRESSION_ZLIB;
+        let stored: Vec<Vec<u8>> = levels
+            .iter()
+            .map(|level| {
+                if supercompressed {
+                    zlib_compress(level, 6)
+                } else {
+                    level.clone()
+                }
+            })
+            .collect();
+        // Levels are stored from the smallest
+        let mut offset = kvd_offset + kvd.len();
+        let mut offsets = vec![0; levels.len()];
+        for (i, level) in stored.iter().enumerate().rev() {
+            offsets[i] = offset;
+            offset += level.len();
+        }
+        for ((level, data), offset) in levels.iter().zip(&stored).zip(offsets) {
+            for value in [offset, data.len(), level.len()] {
+                out.extend((value as u64).to_le_bytes());
+            }
+        }
+        out.extend(kvd);
+        stored.iter().rev().for_each(|data| out.extend(data));
+        out
+    }
+
+    #[test]
+    fn parse() {
+        // BC1 cubemap array of 2 layers with 2 levels: 12 images of 8x8 then 4x4
+        let level0: Vec<u8> = (0..12 * 32).map(|i| i as u8).collect();
+        let level1: Vec<u8> = (0..12 * 8).map(|i| 100 + i as u8).collect();
+        let fields = [VK_FORMAT_BC1_RGBA_SRGB_BLOCK as u32, 1, 8, 8, 0, 2, 6, 2, 0];
+        let file =
+            ktx2(fields, &[("KTXorientation", b"rd\0"), ("KTXwriter", b"test")], &[level0.clone(), level1.clone()]);
+        let ktx = Ktx2::parse(&file).unwrap();
+        assert_eq!((ktx.format, ktx.width, ktx.height), (VK_FORMAT_BC1_RGBA_SRGB_BLOCK, 8, 8));
+        assert_eq!((ktx.layers, ktx.faces, ktx.is_array, ktx.is_cubemap()), (2, 6, true, true));
+        assert_eq!((ktx.levels.len(), ktx.level_size(1), ktx.level_size(5)), (2, (4, 4), (1, 1)));
+        assert_eq!(ktx.image(0, 1, 2), &level0[256..288]);
+        assert_eq!(ktx.image(1, 1, 5), &level1[88..]);
+        assert_eq!(ktx.key_value("KTXorientation"), Some(&b"rd\0"[..]));
+        assert_eq!(ktx.key_value("KTXwriter"), Some(&b"test"[..]));
+
+        // zlib supercompressed 1D texture, the level count of 0 means only the full size level
+        let pixels: Vec<u8> = [1, 2, 3, 4].repeat(12);
+        let file = ktx2([VK_FORMAT_R8G8B8A8_UNORM as u32, 1, 12, 0, 0, 0, 1, 0, 3], &[], &[pixels.clone()]);
+        let ktx = Ktx2::parse(&file).unwrap();
+        assert_eq!((ktx.width, ktx.height, ktx.layers, ktx.is_array), (12, 1, 1, false));
+        assert_eq!(ktx.levels, [pixels]);
+    }
+
+    #[test]
+    fn errors() {
+        let format = VK_FORMAT_R8_UNORM as u32;
+        let error = |fields: [u32; 9], levels: &[Vec<u8>]| Ktx2::parse(&ktx2(fields, &[], levels)).unwrap_err();
+        assert!(error([0, 1, 1, 1, 0, 0, 1, 1, 0], &[vec![0]]).contains("format 0"));
+        assert!(error([format, 1, 1, 1, 4, 0, 1, 1, 0], &[vec![0]]).contains("2D"));
+        assert!(error([format, 1, 1, 1, 0, 0, 2, 1, 0], &[vec![0]]).contains("face count"));
+        assert!(error([format, 1, 1, 1, 0, 0, 1, 1, 2], &[vec![0]]).contains("supercompression"));
+        assert!(error([format, 1, 2, 2, 0, 0, 1, 3, 0], &[vec![0; 4], vec![0], vec![0]]).contains("Too many"));
+        assert!(error([format, 1, 1, 1, 0, 3, 1, 1, 0], &[vec![0; 4]]).contains("3 1x1 images"));
+        assert!(error([format, 1, 4, 4, 0, 0, 1, 2, 0], &[vec![0; 16], vec![0; 2]]).contains("level 1 of 2 bytes"));
+        let bc1 = VK_FORMAT_BC1_RGB_UNORM_BLOCK as u32;
+        assert!(error([bc1, 1, 5, 4, 0, 0, 1, 1, 0], &[vec![0; 8]]).contains("of 8 bytes"));
+        assert!(error([format, 1, 4, 2, 0, 0, 6, 1, 0], &[vec![0; 48]]).contains("square"));
+
+        let file = ktx2([format, 1, 2, 1, 0, 0, 1, 1, 0], &[], &[vec![0; 2]]);
+        assert!(Ktx2::parse(&file[..file.len() - 1]).unwrap_err().contains("Truncated KTX2 level"));
+        assert!(Ktx2::parse(&file[..90]).unwrap_err().contains("Truncated KTX2 level index"));
+        assert!(Ktx2::parse(b"\xABKTX 11\xBB\r\n\x1A\n").is_err());
+    }
+}
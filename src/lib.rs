@@ -1,21 +1,45 @@
 pub mod alsa;
+pub mod atlas;
+pub mod bcn;
+pub mod bitmap_font;
 pub mod blender;
+pub mod bmp;
 pub mod color;
+pub mod deflate;
 pub mod egl_sys;
 pub mod gl_sys;
 pub mod glx_sys;
 pub mod glyph;
+pub mod glyph_raster;
+pub mod glyph_sdf;
+pub mod image;
 pub mod input;
+pub mod ktx2;
 pub mod macros;
 pub mod math;
 pub mod parsing;
+pub mod particles;
 pub mod platform;
+pub mod png;
+pub mod pnm;
 pub mod pulseaudio;
+pub mod qoi;
 pub mod rand;
 pub mod shaderc;
+pub mod shadow;
 pub mod spirv;
-pub mod stb_image;
+pub mod spirv_as;
+pub mod spirv_dis;
+pub mod spirv_glsl;
+pub mod spirv_grammar;
+pub mod spirv_interp;
+pub mod spirv_opt;
+pub mod spirv_val;
 pub mod string_util;
+pub mod text_layout;
+pub mod tga;
+pub mod tilemap;
+pub mod unicode;
 pub mod vk_sys;
 pub mod vk_util;
 pub mod wavefront_loader;
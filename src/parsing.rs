@@ -70,3 +70,19 @@ pub fn read_f32_le<R: std::io::Read>(mut r: R) -> std::io::Result<f32> {
     r.read_exact(&mut b)?;
     Ok(f32::from_le_bytes(b))
 }
+
+/// `length` bytes of `bytes` from `offset`, or an error if they are out of bounds
+pub fn get_bytes(bytes: &[u8], offset: usize, length: usize) -> Result<&[u8], String> {
+    offset.checked_add(length).and_then(|end| bytes.get(offset..end)).ok_or_else(|| {
+        format!("Unexpected end of data: {} bytes at offset {} of {}", length, offset, bytes.len())
+    })
+}
+pub fn get_u16_le(bytes: &[u8], offset: usize) -> Result<u16, String> {
+    Ok(u16::from_le_bytes(get_bytes(bytes, offset, 2)?.try_into().unwrap()))
+}
+pub fn get_u32_le(bytes: &[u8], offset: usize) -> Result<u32, String> {
+    Ok(u32::from_le_bytes(get_bytes(bytes, offset, 4)?.try_into().unwrap()))
+}
+pub fn get_u64_le(bytes: &[u8], offset: usize) -> Result<u64, String> {
+    Ok(u64::from_le_bytes(get_bytes(bytes, offset, 8)?.try_into().unwrap()))
+}
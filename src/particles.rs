@@ -0,0 +1,694 @@
+use crate::check;
+use crate::color::{self, Color};
+use crate::cstr;
+use crate::math::{Rect, Vec2};
+use crate::rand::Rand;
+use crate::vk_sys::*;
+use crate::vk_util::{self, *};
+
+use core::ffi::c_void;
+use std::f32::consts::PI;
+use std::mem;
+use std::ptr;
+
+// Particle System:
+// An `Emitter` spawns particles continuously (`rate` per second) and/or in bursts from a shape
+// around its position. Every particle lives for a random lifetime, moves with its velocity and
+// the emitter's gravity, and gets its color and size from curves sampled with the normalized age.
+//
+// The simulation runs on the CPU by default and the particles are pushed as regular
+// `RenderCommand::Rect`s, so they render through whatever pipeline the game already uses
+// (`material` selects the texture when using the "sprite" shader).
+//
+// `GpuParticles` is the optional compute path: particles are spawned on the CPU into a storage
+// buffer and integrated by assets/shaders/particles.comp, which also writes the resulting rects.
+
+pub trait Lerp: Copy {
+    fn lerp(a: Self, b: Self, t: f32) -> Self;
+}
+impl Lerp for f32 {
+    fn lerp(a: Self, b: Self, t: f32) -> Self {
+        a + (b - a) * t
+    }
+}
+impl Lerp for Vec2 {
+    fn lerp(a: Self, b: Self, t: f32) -> Self {
+        a + (b - a) * t
+    }
+}
+impl Lerp for Color {
+    fn lerp(a: Self, b: Self, t: f32) -> Self {
+        let c = |a: u8, b: u8| f32::lerp(a as f32, b as f32, t).round().clamp(0.0, 255.0) as u8;
+        Color::new(c(a.r, b.r), c(a.g, b.g), c(a.b, b.b), c(a.a, b.a))
+    }
+}
+
+/// Piecewise linear curve over the normalized lifetime of a particle ([0, 1]).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Curve<T> {
+    keys: Vec<(f32, T)>,
+}
+impl<T: Lerp> Curve<T> {
+    pub fn new(keys: &[(f32, T)]) -> Self {
+        assert!(!keys.is_empty());
+        assert!(keys.windows(2).all(|w| w[0].0 <= w[1].0), "Curve keys must be sorted");
+        Self {
+            keys: keys.to_vec(),
+        }
+    }
+
+    pub fn constant(value: T) -> Self {
+        Self::new(&[(0.0, value)])
+    }
+
+    pub fn linear(start: T, end: T) -> Self {
+        Self::new(&[(0.0, start), (1.0, end)])
+    }
+
+    pub fn sample(&self, t: f32) -> T {
+        let first = self.keys[0];
+        if t <= first.0 {
+            return first.1;
+        }
+        for w in self.keys.windows(2) {
+            let ((t0, v0), (t1, v1)) = (w[0], w[1]);
+            if t <= t1 {
+                let s = if t1 > t0 {
+                    (t - t0) / (t1 - t0)
+                } else {
+                    1.0
+                };
+                return T::lerp(v0, v1, s);
+            }
+        }
+        self.keys[self.keys.len() - 1].1
+    }
+
+    pub fn first(&self) -> T {
+        self.keys[0].1
+    }
+
+    pub fn last(&self) -> T {
+        self.keys[self.keys.len() - 1].1
+    }
+}
+
+/// Area around the emitter position where new particles appear.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EmitterShape {
+    Point,
+    Circle(f32), // radius
+    Rect(Vec2),  // extent, centered on the emitter
+}
+
+#[derive(Debug, Clone)]
+pub struct EmitterConfig {
+    pub rate: f32, // Particles per second, 0 for burst-only emitters
+    pub max_particles: usize,
+    pub shape: EmitterShape,
+    pub lifetime: (f32, f32), // min, max (seconds)
+    pub speed: (f32, f32),    // min, max (units per second)
+    pub direction: f32,       // radians, 0 points to +x, PI/2 to +y (down in screen space)
+    pub spread: f32,          // radians around direction, 2*PI emits in every direction
+    pub gravity: Vec2,
+    pub drag: f32, // Fraction of the velocity lost per second
+    pub color: Curve<Color>,
+    pub size: Curve<f32>, // Width, the height is `size * aspect`
+    pub aspect: f32,
+    pub material: u32, // Texture index for the "sprite" shader, 0 is plain white
+    pub z: f32,
+}
+impl Default for EmitterConfig {
+    fn default() -> Self {
+        Self {
+            rate: 10.0,
+            max_particles: 1000,
+            shape: EmitterShape::Point,
+            lifetime: (1.0, 1.0),
+            speed: (50.0, 100.0),
+            direction: 0.0,
+            spread: 2.0 * PI,
+            gravity: Vec2::default(),
+            drag: 0.0,
+            color: Curve::constant(color::WHITE),
+            size: Curve::constant(4.0),
+            aspect: 1.0,
+            material: 0,
+            z: 0.5,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Particle {
+    pub pos: Vec2,
+    pub vel: Vec2,
+    pub age: f32,
+    pub lifetime: f32,
+}
+impl Particle {
+    pub fn t(&self) -> f32 {
+        (self.age / self.lifetime).clamp(0.0, 1.0)
+    }
+}
+
+pub struct Emitter {
+    pub config: EmitterConfig,
+    pub pos: Vec2,
+    pub emitting: bool,
+    pub particles: Vec<Particle>,
+    spawn_accumulator: f32,
+    rand: Rand,
+}
+impl Emitter {
+    pub fn new<P: Into<Vec2>>(config: EmitterConfig, pos: P, seed: usize) -> Self {
+        Self {
+            emitting: config.rate > 0.0,
+            config,
+            pos: pos.into(),
+            particles: vec![],
+            spawn_accumulator: 0.0,
+            rand: Rand::new(seed),
+        }
+    }
+
+    pub fn burst(&mut self, count: usize) {
+        for _ in 0..count {
+            self.spawn();
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        !self.emitting && self.particles.is_empty()
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        for p in &mut self.particles {
+            p.age += dt;
+            p.vel = p.vel + self.config.gravity * dt;
+            p.vel = p.vel * (1.0 - self.config.drag * dt).max(0.0);
+            p.pos = p.pos + p.vel * dt;
+        }
+        self.particles.retain(|p| p.age < p.lifetime);
+
+        if self.emitting {
+            self.spawn_accumulator += self.config.rate * dt;
+            while self.spawn_accumulator >= 1.0 {
+                self.spawn_accumulator -= 1.0;
+                self.spawn();
+            }
+        }
+    }
+
+    pub fn render(&self, cmd: &mut Vec<RenderCommand>) {
+        for p in &self.particles {
+            let t = p.t();
+            let size = self.config.size.sample(t);
+            vk_util::push_rect_color(
+                cmd,
+                Rect::center_extent(p.pos, (size, size * self.config.aspect)),
+                self.config.z,
+                self.config.color.sample(t),
+            );
+        }
+    }
+
+    /// Same as `render` but also pushes the emitter material for every particle.
+    pub fn render_with_materials(&self, cmd: &mut Vec<RenderCommand>, materials: &mut Vec<u32>) {
+        let count = cmd.len();
+        self.render(cmd);
+        materials.resize(materials.len() + cmd.len() - count, self.config.material);
+    }
+
+    fn spawn(&mut self) {
+        if self.particles.len() >= self.config.max_particles {
+            return;
+        }
+        let particle = spawn_particle(&self.config, self.pos, &mut self.rand);
+        self.particles.push(particle);
+    }
+}
+
+fn next_unit(rand: &mut Rand) -> f32 {
+    (rand.next_u32() >> 8) as f32 / (1 << 24) as f32
+}
+
+fn next_range(rand: &mut Rand, (min, max): (f32, f32)) -> f32 {
+    min + (max - min) * next_unit(rand)
+}
+
+fn spawn_particle(config: &EmitterConfig, origin: Vec2, rand: &mut Rand) -> Particle {
+    let offset = match config.shape {
+        EmitterShape::Point => Vec2::default(),
+        EmitterShape::Circle(radius) => {
+            // sqrt for a uniform distribution over the area
+            let r = radius * next_unit(rand).sqrt();
+            let a = 2.0 * PI * next_unit(rand);
+            Vec2::new(r * a.cos(), r * a.sin())
+        }
+        EmitterShape::Rect(extent) => Vec2::new((next_unit(rand) - 0.5) * extent.x, (next_unit(rand) - 0.5) * extent.y),
+    };
+    let angle = config.direction + (next_unit(rand) - 0.5) * config.spread;
+    let speed = next_range(rand, config.speed);
+    Particle {
+        pos: origin + offset,
+        vel: Vec2::new(angle.cos(), angle.sin()) * speed,
+        age: 0.0,
+        lifetime: next_range(rand, config.lifetime).max(f32::EPSILON),
+    }
+}
+
+/// Collection of emitters, one-shot effects (bursts) are removed once all their particles die.
+#[derive(Default)]
+pub struct ParticleSystem {
+    pub emitters: Vec<Emitter>,
+    seed: usize,
+}
+impl ParticleSystem {
+    pub fn add(&mut self, config: EmitterConfig, pos: Vec2) -> &mut Emitter {
+        self.seed += 1;
+        self.emitters.push(Emitter::new(config, pos, self.seed * 7919));
+        self.emitters.last_mut().unwrap()
+    }
+
+    pub fn burst<P: Into<Vec2>>(&mut self, config: &EmitterConfig, pos: P, count: usize) {
+        let emitter = self.add(
+            EmitterConfig {
+                rate: 0.0,
+                ..config.clone()
+            },
+            pos.into(),
+        );
+        emitter.burst(count);
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        self.emitters.iter_mut().for_each(|e| e.update(dt));
+        self.emitters.retain(|e| !e.is_finished());
+    }
+
+    pub fn clear(&mut self) {
+        self.emitters.clear();
+    }
+
+    pub fn particle_count(&self) -> usize {
+        self.emitters.iter().map(|e| e.particles.len()).sum()
+    }
+
+    pub fn render(&self, cmd: &mut Vec<RenderCommand>) {
+        self.emitters.iter().for_each(|e| e.render(cmd));
+    }
+
+    pub fn render_with_materials(&self, cmd: &mut Vec<RenderCommand>, materials: &mut Vec<u32>) {
+        self.emitters.iter().for_each(|e| e.render_with_materials(cmd, materials));
+    }
+}
+
+// GPU simulation
+
+// Must match the Particle struct in assets/shaders/particles.comp (std430)
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+struct GpuParticle {
+    color_start: [f32; 4],
+    color_end: [f32; 4],
+    pos: [f32; 2],
+    vel: [f32; 2],
+    age: f32,
+    lifetime: f32,
+    size_start: f32,
+    size_end: f32,
+}
+
+// Must match the Instance struct in assets/shaders/particles.comp, same layout as RenderCommand::Rect
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+struct GpuInstance {
+    rect: [f32; 4],
+    z: f32,
+    color: [f32; 4],
+}
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+struct GpuPushConstants {
+    gravity: [f32; 2],
+    dt: f32,
+    drag: f32,
+    z: f32,
+    count: u32,
+    aspect: f32,
+}
+
+const GPU_WORKGROUP_SIZE: u32 = 64;
+
+/// Compute shader simulation for large particle counts. Color and size only support a linear
+/// interpolation between the first and last key of the emitter curves.
+pub struct GpuParticles {
+    pub config: EmitterConfig,
+    pub max_particles: usize,
+    device: VkDevice,
+    particles: Buffer,
+    instances: Buffer,
+    expires: Vec<f32>, // CPU copy of when every slot becomes free again
+    time: f32,
+    next_slot: usize,
+    rand: Rand,
+    descriptor_set_layout: VkDescriptorSetLayout,
+    descriptor_pool: VkDescriptorPool,
+    descriptor_set: VkDescriptorSet,
+    pipeline_layout: VkPipelineLayout,
+    pipeline: VkPipeline,
+}
+impl GpuParticles {
+    pub fn new(vk_ctx: &VkContext, config: EmitterConfig) -> Self {
+        let max_particles = config.max_particles;
+        let host_visible = (VK_MEMORY_PROPERTY_HOST_VISIBLE_BIT | VK_MEMORY_PROPERTY_HOST_COHERENT_BIT).into();
+        let particles = vk_ctx.create_buffer(
+            max_particles * mem::size_of::<GpuParticle>(),
+            VK_BUFFER_USAGE_STORAGE_BUFFER_BIT.into(),
+            host_visible,
+        );
+        let instances = vk_ctx.create_buffer(
+            max_particles * mem::size_of::<GpuInstance>(),
+            VK_BUFFER_USAGE_STORAGE_BUFFER_BIT.into(),
+            host_visible,
+        );
+        let dead = vec![GpuParticle::default(); max_particles];
+        vk_map_memory_copy(vk_ctx.device, particles.memory, dead.as_ptr(), mem::size_of_val(&dead[..]));
+
+        let device = vk_ctx.device;
+        unsafe {
+            let bindings = [
+                layout_binding(0, VK_DESCRIPTOR_TYPE_STORAGE_BUFFER, VK_SHADER_STAGE_COMPUTE_BIT),
+                layout_binding(1, VK_DESCRIPTOR_TYPE_STORAGE_BUFFER, VK_SHADER_STAGE_COMPUTE_BIT),
+            ];
+            let mut descriptor_set_layout = VkDescriptorSetLayout::default();
+            check!(vkCreateDescriptorSetLayout(
+                device,
+                &VkDescriptorSetLayoutCreateInfo {
+                    bindingCount: bindings.len() as u32,
+                    pBindings: bindings.as_ptr(),
+                    ..VkDescriptorSetLayoutCreateInfo::default()
+                },
+                ptr::null(),
+                &mut descriptor_set_layout
+            ));
+
+            let mut descriptor_pool = VkDescriptorPool::default();
+            check!(vkCreateDescriptorPool(
+                device,
+                &VkDescriptorPoolCreateInfo {
+                    maxSets: 1,
+                    poolSizeCount: 1,
+                    pPoolSizes: &VkDescriptorPoolSize::new(VK_DESCRIPTOR_TYPE_STORAGE_BUFFER, 2),
+                    ..VkDescriptorPoolCreateInfo::default()
+                },
+                ptr::null(),
+                &mut descriptor_pool
+            ));
+
+            let mut descriptor_set = VkDescriptorSet::default();
+            check!(vkAllocateDescriptorSets(
+                device,
+                &VkDescriptorSetAllocateInfo {
+                    descriptorPool: descriptor_pool,
+                    descriptorSetCount: 1,
+                    pSetLayouts: &descriptor_set_layout,
+                    ..VkDescriptorSetAllocateInfo::default()
+                },
+                &mut descriptor_set
+            ));
+
+            let writes = [particles.buffer, instances.buffer].map(|buffer| VkDescriptorBufferInfo {
+                buffer,
+                offset: 0,
+                range: VK_WHOLE_SIZE,
+            });
+            let writes = [
+                VkWriteDescriptorSet {
+                    dstSet: descriptor_set,
+                    dstBinding: 0,
+                    descriptorCount: 1,
+                    descriptorType: VK_DESCRIPTOR_TYPE_STORAGE_BUFFER,
+                    pBufferInfo: &writes[0],
+                    ..VkWriteDescriptorSet::default()
+                },
+                VkWriteDescriptorSet {
+                    dstSet: descriptor_set,
+                    dstBinding: 1,
+                    descriptorCount: 1,
+                    descriptorType: VK_DESCRIPTOR_TYPE_STORAGE_BUFFER,
+                    pBufferInfo: &writes[1],
+                    ..VkWriteDescriptorSet::default()
+                },
+            ];
+            vkUpdateDescriptorSets(device, writes.len() as u32, writes.as_ptr(), 0, ptr::null());
+
+            let pipeline_layout = vk_create_pipeline_layout(
+                device,
+                &[descriptor_set_layout],
+                &[VkPushConstantRange {
+                    stageFlags: VK_SHADER_STAGE_COMPUTE_BIT.into(),
+                    offset: 0,
+                    size: mem::size_of::<GpuPushConstants>() as u32,
+                }],
+            );
+
+            let shader_module = vk_create_shader_module(device, "assets/shaders/particles.comp.spv");
+            let mut pipeline = VkPipeline::default();
+            check!(vkCreateComputePipelines(
+                device,
+                VkPipelineCache::default(),
+                1,
+                &VkComputePipelineCreateInfo {
+                    stage: VkPipelineShaderStageCreateInfo {
+                        stage: VK_SHADER_STAGE_COMPUTE_BIT.into(),
+                        module: shader_module,
+                        pName: cstr!("main"),
+                        ..VkPipelineShaderStageCreateInfo::default()
+                    },
+                    layout: pipeline_layout,
+                    basePipelineIndex: -1,
+                    ..VkComputePipelineCreateInfo::default()
+                },
+                ptr::null(),
+                &mut pipeline
+            ));
+            vkDestroyShaderModule(device, shader_module, ptr::null());
+
+            Self {
+                config,
+                max_particles,
+                device,
+                particles,
+                instances,
+                expires: vec![0.0; max_particles],
+                time: 0.0,
+                next_slot: 0,
+                rand: Rand::new(max_particles),
+                descriptor_set_layout,
+                descriptor_pool,
+                descriptor_set,
+                pipeline_layout,
+                pipeline,
+            }
+        }
+    }
+
+    /// Spawns up to `count` particles into free slots of the particle buffer.
+    pub fn burst<P: Into<Vec2>>(&mut self, pos: P, count: usize) {
+        let pos = pos.into();
+        let color_start = self.config.color.first().as_f32();
+        let color_end = self.config.color.last().as_f32();
+        let (size_start, size_end) = (self.config.size.first(), self.config.size.last());
+        unsafe {
+            let mut mapped = ptr::null_mut();
+            check!(vkMapMemory(self.device, self.particles.memory, 0, VK_WHOLE_SIZE, 0, &mut mapped));
+            let gpu_particles = std::slice::from_raw_parts_mut(mapped as *mut GpuParticle, self.max_particles);
+            let mut spawned = 0;
+            for _ in 0..self.max_particles {
+                if spawned == count {
+                    break;
+                }
+                let slot = self.next_slot;
+                self.next_slot = (self.next_slot + 1) % self.max_particles;
+                if self.expires[slot] > self.time {
+                    continue;
+                }
+                let p = spawn_particle(&self.config, pos, &mut self.rand);
+                self.expires[slot] = self.time + p.lifetime;
+                gpu_particles[slot] = GpuParticle {
+                    color_start,
+                    color_end,
+                    pos: [p.pos.x, p.pos.y],
+                    vel: [p.vel.x, p.vel.y],
+                    age: 0.0,
+                    lifetime: p.lifetime,
+                    size_start,
+                    size_end,
+                };
+                spawned += 1;
+            }
+            vkUnmapMemory(self.device, self.particles.memory);
+        }
+    }
+
+    /// Runs one simulation step on the GPU and waits for it to finish.
+    pub fn simulate(&mut self, vk_ctx: &VkContext, dt: f32) {
+        self.time += dt;
+        let push_constants = GpuPushConstants {
+            gravity: [self.config.gravity.x, self.config.gravity.y],
+            dt,
+            drag: self.config.drag,
+            z: self.config.z,
+            count: self.max_particles as u32,
+            aspect: self.config.aspect,
+        };
+        unsafe {
+            let cmd = vk_allocate_command_buffers(self.device, vk_ctx.command_pool, 1)[0];
+            vk_begin_command_buffer(cmd);
+            vkCmdBindPipeline(cmd, VK_PIPELINE_BIND_POINT_COMPUTE, self.pipeline);
+            vkCmdBindDescriptorSets(
+                cmd,
+                VK_PIPELINE_BIND_POINT_COMPUTE,
+                self.pipeline_layout,
+                0,
+                1,
+                &self.descriptor_set,
+                0,
+                ptr::null(),
+            );
+            vkCmdPushConstants(
+                cmd,
+                self.pipeline_layout,
+                VK_SHADER_STAGE_COMPUTE_BIT.into(),
+                0,
+                mem::size_of::<GpuPushConstants>() as u32,
+                &push_constants as *const _ as *const c_void,
+            );
+            let group_count = (self.max_particles as u32).div_ceil(GPU_WORKGROUP_SIZE);
+            vkCmdDispatch(cmd, group_count, 1, 1);
+            vk_end_command_buffer(cmd);
+
+            let fence = vk_create_fence(self.device, 0);
+            let s = VkSemaphore::default();
+            vk_queue_submit(vk_ctx.graphics_queue, cmd, s, s, fence);
+            vk_wait_for_fences(self.device, fence);
+            vk_destroy_fence(self.device, fence);
+            vkFreeCommandBuffers(self.device, vk_ctx.command_pool, 1, &cmd);
+        }
+    }
+
+    /// Pushes the rects produced by the last simulation step, dead particles are skipped.
+    pub fn render(&self, cmd: &mut Vec<RenderCommand>) {
+        unsafe {
+            let mut mapped = ptr::null_mut();
+            check!(vkMapMemory(self.device, self.instances.memory, 0, VK_WHOLE_SIZE, 0, &mut mapped));
+            let instances = std::slice::from_raw_parts(mapped as *const GpuInstance, self.max_particles);
+            for (instance, expires) in instances.iter().zip(&self.expires) {
+                if *expires > self.time && instance.rect[2] > 0.0 {
+                    let [x, y, w, h] = instance.rect;
+                    cmd.push(RenderCommand::Rect(x, y, w, h, instance.z, instance.color));
+                }
+            }
+            vkUnmapMemory(self.device, self.instances.memory);
+        }
+    }
+
+    pub fn destroy(&mut self) {
+        unsafe {
+            vkDestroyPipeline(self.device, self.pipeline, ptr::null());
+            vkDestroyPipelineLayout(self.device, self.pipeline_layout, ptr::null());
+            vkDestroyDescriptorPool(self.device, self.descriptor_pool, ptr::null());
+            vkDestroyDescriptorSetLayout(self.device, self.descriptor_set_layout, ptr::null());
+        }
+        self.instances.destroy();
+        self.particles.destroy();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn curve_sample() {
+        let curve = Curve::new(&[(0.0, 0.0), (0.5, 10.0), (1.0, 0.0)]);
+        assert_eq!(curve.sample(-1.0), 0.0);
+        assert_eq!(curve.sample(0.25), 5.0);
+        assert_eq!(curve.sample(0.5), 10.0);
+        assert_eq!(curve.sample(0.75), 5.0);
+        assert_eq!(curve.sample(2.0), 0.0);
+
+        let colors = Curve::linear(Color::new(0, 0, 0, 255), Color::new(255, 255, 255, 0));
+        assert_eq!(colors.sample(0.5), Color::new(128, 128, 128, 128));
+    }
+
+    #[test]
+    fn emitter_rate_and_lifetime() {
+        let mut emitter = Emitter::new(
+            EmitterConfig {
+                rate: 10.0,
+                lifetime: (0.5, 0.5),
+                ..EmitterConfig::default()
+            },
+            (0.0, 0.0),
+            0,
+        );
+        emitter.update(0.25);
+        assert_eq!(emitter.particles.len(), 2);
+        emitter.update(0.25);
+        assert_eq!(emitter.particles.len(), 5);
+        // The first particles reach their lifetime
+        emitter.update(0.375);
+        assert_eq!(emitter.particles.len(), 6);
+        emitter.emitting = false;
+        emitter.update(1.0);
+        assert!(emitter.is_finished());
+    }
+
+    #[test]
+    fn burst_shape_and_gravity() {
+        let config = EmitterConfig {
+            rate: 0.0,
+            shape: EmitterShape::Circle(10.0),
+            speed: (0.0, 0.0),
+            gravity: Vec2::new(0.0, 100.0),
+            lifetime: (1.0, 2.0),
+            ..EmitterConfig::default()
+        };
+        let mut system = ParticleSystem::default();
+        system.burst(&config, (100.0, 100.0), 50);
+        assert_eq!(system.particle_count(), 50);
+        for p in &system.emitters[0].particles {
+            assert!((p.pos - Vec2::new(100.0, 100.0)).len() <= 10.0);
+            assert!((1.0..=2.0).contains(&p.lifetime));
+        }
+        let before: Vec<f32> = system.emitters[0].particles.iter().map(|p| p.pos.y).collect();
+        system.update(0.5);
+        for (p, y) in system.emitters[0].particles.iter().zip(before) {
+            assert!(p.pos.y > y);
+        }
+        system.update(2.0);
+        assert!(system.emitters.is_empty());
+    }
+
+    #[test]
+    fn render_with_materials() {
+        let mut system = ParticleSystem::default();
+        let config = EmitterConfig {
+            size: Curve::constant(40.0),
+            aspect: 0.8,
+            material: 3,
+            ..EmitterConfig::default()
+        };
+        system.burst(&config, (0.0, 0.0), 4);
+        let mut cmd = vec![];
+        let mut materials = vec![1];
+        system.render_with_materials(&mut cmd, &mut materials);
+        assert_eq!(cmd.len(), 4);
+        assert_eq!(materials, vec![1, 3, 3, 3, 3]);
+        assert!(matches!(cmd[0], RenderCommand::Rect(_, _, w, h, ..) if (w, h) == (40.0, 32.0)));
+    }
+}
@@ -0,0 +1,469 @@
+use crate::deflate::{crc32, zlib_compress, zlib_decompress};
+
+// PNG images:
+// Decodes every standard PNG (grayscale, RGB, palette, with or without alpha, 1 to 16 bits per sample, Adam7
+// interlaced or not, with tRNS transparency) to RGBA8 and encodes RGBA8 pixels as 8-bit RGB or RGBA.
+// Gamma, color profiles and the other ancillary chunks are ignored, 16-bit samples keep their most significant byte.
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+const COLOR_GRAY: u8 = 0;
+const COLOR_RGB: u8 = 2;
+const COLOR_PALETTE: u8 = 3;
+const COLOR_GRAY_ALPHA: u8 = 4;
+const COLOR_RGBA: u8 = 6;
+/// Adam7 passes: (x0, y0, dx, dy)
+const ADAM7: [(usize, usize, usize, usize); 7] =
+    [(0, 0, 8, 8), (4, 0, 8, 8), (0, 4, 4, 8), (2, 0, 4, 4), (0, 2, 2, 4), (1, 0, 2, 2), (0, 1, 1, 2)];
+/// Largest accepted width or height, as most decoders do
+const MAX_SIZE: u32 = 1 << 24;
+/// Largest accepted number of pixels
+const MAX_PIXELS: usize = 400_000_000;
+
+struct Header {
+    width: usize,
+    height: usize,
+    depth: u8,
+    color_type: u8,
+    interlaced: bool,
+}
+
+impl Header {
+    fn channels(&self) -> usize {
+        match self.color_type {
+            COLOR_GRAY | COLOR_PALETTE => 1,
+            COLOR_GRAY_ALPHA => 2,
+            COLOR_RGB => 3,
+            _ => 4,
+        }
+    }
+
+    /// Bytes per complete pixel for the filters, at least 1
+    fn filter_stride(&self) -> usize {
+        (self.channels() * self.depth as usize).div_ceil(8)
+    }
+
+    fn row_size(&self, width: usize) -> usize {
+        (width * self.channels() * self.depth as usize).div_ceil(8)
+    }
+}
+
+fn be_u32(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = ((p - a as i16).abs(), (p - b as i16).abs(), (p - c as i16).abs());
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// Reverses the filter of `row` in place, `previous` is the unfiltered row above (zeros for the first one).
+fn unfilter(filter: u8, row: &mut [u8], previous: &[u8], stride: usize) -> Result<(), String> {
+    for i in 0..row.len() {
+        let left = if i >= stride {
+            row[i - stride]
+        } else {
+            0
+        };
+        let up = previous[i];
+        let up_left = if i >= stride {
+            previous[i - stride]
+        } else {
+            0
+        };
+        row[i] = row[i].wrapping_add(match filter {
+            0 => 0,
+            1 => left,
+            2 => up,
+            3 => ((left as u16 + up as u16) / 2) as u8,
+            4 => paeth(left, up, up_left),
+            _ => return Err(format!("Invalid filter type {}", filter)),
+        });
+    }
+    Ok(())
+}
+
+/// Decodes a PNG file, returns (RGBA8 pixels, width, height).
+pub fn decode_png(bytes: &[u8]) -> Result<(Vec<u8>, usize, usize), String> {
+    if !bytes.starts_with(&SIGNATURE) {
+        return Err(String::from("Not a PNG file"));
+    }
+    let mut header = None;
+    let mut palette: Vec<[u8; 4]> = vec![];
+    let mut transparency = None;
+    let mut data = vec![];
+    let mut pos = SIGNATURE.len();
+    loop {
+        let chunk_header = bytes.get(pos..pos + 8).ok_or("Missing IEND chunk")?;
+        let len = be_u32(chunk_header) as usize;
+        let chunk_type = &bytes[pos + 4..pos + 8];
+        let body = bytes.get(pos + 8..pos + 8 + len).ok_or("Truncated chunk")?;
+        let crc = bytes.get(pos + 8 + len..pos + 12 + len).ok_or("Truncated chunk")?;
+        if be_u32(crc) != crc32(&bytes[pos + 4..pos + 8 + len]) {
+            return Err(format!("Invalid checksum of {} chunk", String::from_utf8_lossy(chunk_type)));
+        }
+        pos += 12 + len;
+        match chunk_type {
+            b"IHDR" => {
+                if body.len() != 13 {
+                    return Err(String::from("Invalid IHDR chunk"));
+                }
+                let (width, height) = (be_u32(body), be_u32(&body[4..]));
+                let (depth, color_type) = (body[8], body[9]);
+                let valid_depth = match color_type {
+                    COLOR_GRAY => [1, 2, 4, 8, 16].contains(&depth),
+                    COLOR_PALETTE => [1, 2, 4, 8].contains(&depth),
+                    COLOR_RGB | COLOR_GRAY_ALPHA | COLOR_RGBA => [8, 16].contains(&depth),
+                    _ => return Err(format!("Invalid color type {}", color_type)),
+                };
+                if !valid_depth {
+                    return Err(format!("Invalid bit depth {} for color type {}", depth, color_type));
+                }
+                let pixel_count = width as usize * height as usize;
+                if width == 0 || height == 0 || width > MAX_SIZE || height > MAX_SIZE || pixel_count > MAX_PIXELS {
+                    return Err(format!("Invalid size {}x{}", width, height));
+                }
+                if body[10] != 0 || body[11] != 0 || body[12] > 1 {
+                    return Err(String::from("Unknown compression, filter or interlace method"));
+                }
+                header = Some(Header {
+                    width: width as usize,
+                    height: height as usize,
+                    depth,
+                    color_type,
+                    interlaced: body[12] == 1,
+                });
+            }
+            b"PLTE" => {
+                if body.len() % 3 != 0 || body.len() > 256 * 3 {
+                    return Err(String::from("Invalid PLTE chunk"));
+                }
+                palette = body.chunks_exact(3).map(|c| [c[0], c[1], c[2], 255]).collect();
+            }
+            b"tRNS" => transparency = Some(body.to_vec()),
+            b"IDAT" => data.extend_from_slice(body),
+            b"IEND" => break,
+            // Unknown critical chunks (uppercase first letter) change how the image is decoded
+            _ if chunk_type[0].is_ascii_uppercase() => {
+                return Err(format!("Unknown critical chunk {}", String::from_utf8_lossy(chunk_type)));
+            }
+            _ => {}
+        }
+    }
+    let header = header.ok_or("Missing IHDR chunk")?;
+    if header.color_type == COLOR_PALETTE {
+        if palette.is_empty() {
+            return Err(String::from("Missing PLTE chunk"));
+        }
+        for (entry, &alpha) in palette.iter_mut().zip(transparency.iter().flatten()) {
+            entry[3] = alpha;
+        }
+    }
+    // Grayscale and RGB images have one fully transparent color, with samples of the image bit depth
+    let transparent: Option<Vec<u16>> = match (header.color_type, &transparency) {
+        (COLOR_GRAY | COLOR_RGB, Some(t)) => {
+            Some(t.chunks_exact(2).map(|s| u16::from_be_bytes([s[0], s[1]])).collect())
+        }
+        _ => None,
+    };
+
+    let data = zlib_decompress(&data)?;
+    let passes: &[_] = if header.interlaced {
+        &ADAM7
+    } else {
+        &[(0, 0, 1, 1)]
+    };
+    let (channels, depth, stride) = (header.channels(), header.depth as usize, header.filter_stride());
+    let max_sample = (1u32 << depth.min(8)) - 1;
+    let pass_size = |&(x0, y0, dx, dy): &(usize, usize, usize, usize)| {
+        ((header.width + dx - 1 - x0) / dx, (header.height + dy - 1 - y0) / dy)
+    };
+    // Every scanline of a non-empty pass starts with its filter type
+    let expected: usize =
+        passes.iter().map(pass_size).filter(|&(w, h)| w > 0 && h > 0).map(|(w, h)| h * (1 + header.row_size(w))).sum();
+    if data.len() != expected {
+        return Err(format!("Expected {} bytes of image data, got {}", expected, data.len()));
+    }
+    let mut pixels = vec![0; header.width * header.height * 4];
+    let mut pos = 0;
+    for pass in passes {
+        let (x0, y0, dx, dy) = *pass;
+        let (pass_width, pass_height) = pass_size(pass);
+        if pass_width == 0 || pass_height == 0 {
+            continue;
+        }
+        let row_size = header.row_size(pass_width);
+        let mut previous = vec![0; row_size];
+        for y in 0..pass_height {
+            let filter = *data.get(pos).ok_or("Not enough image data")?;
+            let mut row = data.get(pos + 1..pos + 1 + row_size).ok_or("Not enough image data")?.to_vec();
+            pos += 1 + row_size;
+            unfilter(filter, &mut row, &previous, stride)?;
+
+            let sample = |i: usize| -> u16 {
+                match depth {
+                    16 => u16::from_be_bytes([row[2 * i], row[2 * i + 1]]),
+                    8 => row[i] as u16,
+                    _ => ((row[i * depth / 8] >> (8 - depth - i * depth % 8)) as u32 & max_sample) as u16,
+                }
+            };
+            // Samples to 8 bits
+            let scale = |s: u16| -> u8 {
+                match depth {
+                    16 => (s >> 8) as u8,
+                    _ => (s as u32 * 255 / max_sample) as u8,
+                }
+            };
+            for x in 0..pass_width {
+                let samples: Vec<u16> = (0..channels).map(|c| sample(x * channels + c)).collect();
+                let rgba = match header.color_type {
+                    COLOR_PALETTE => *palette
+                        .get(samples[0] as usize)
+                        .ok_or_else(|| format!("Invalid palette index {}", samples[0]))?,
+                    COLOR_GRAY => {
+                        let gray = scale(samples[0]);
+                        let alpha = if transparent.as_deref() == Some(&samples[..]) {
+                            0
+                        } else {
+                            255
+                        };
+                        [gray, gray, gray, alpha]
+                    }
+                    COLOR_GRAY_ALPHA => {
+                        let gray = scale(samples[0]);
+                        [gray, gray, gray, scale(samples[1])]
+                    }
+                    COLOR_RGB => {
+                        let alpha = if transparent.as_deref() == Some(&samples[..]) {
+                            0
+                        } else {
+                            255
+                        };
+                        [scale(samples[0]), scale(samples[1]), scale(samples[2]), alpha]
+                    }
+                    _ => [scale(samples[0]), scale(samples[1]), scale(samples[2]), scale(samples[3])],
+                };
+                let i = ((y0 + y * dy) * header.width + x0 + x * dx) * 4;
+                pixels[i..i + 4].copy_from_slice(&rgba);
+            }
+            previous = row;
+        }
+    }
+    Ok((pixels, header.width, header.height))
+}
+
+fn write_chunk(out: &mut Vec<u8>, chunk_type: &[u8; 4], body: &[u8]) {
+    out.extend((body.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend_from_slice(chunk_type);
+    out.extend_from_slice(body);
+    let crc = crc32(&out[start..]);
+    out.extend(crc.to_be_bytes());
+}
+
+/// Encodes RGBA8 pixels as a PNG file, without the alpha channel if every pixel is opaque. Each row is filtered
+/// with the filter giving the smallest sum of absolute differences.
+pub fn encode_png(pixels: &[u8], width: usize, height: usize) -> Vec<u8> {
+    assert_eq!(pixels.len(), width * height * 4);
+    let opaque = pixels.chunks_exact(4).all(|p| p[3] == 255);
+    let channels = if opaque {
+        3
+    } else {
+        4
+    };
+    let row_size = width * channels;
+
+    let mut data = Vec::with_capacity((row_size + 1) * height);
+    let mut previous = vec![0; row_size];
+    for row in pixels.chunks_exact(width * 4) {
+        let row: Vec<u8> = row.chunks_exact(4).flat_map(|p| &p[..channels]).copied().collect();
+        let filtered = (0..5).map(|filter| {
+            let mut filtered = vec![filter];
+            for i in 0..row_size {
+                let left = if i >= channels {
+                    row[i - channels]
+                } else {
+                    0
+                };
+                let up_left = if i >= channels {
+                    previous[i - channels]
+                } else {
+                    0
+                };
+                let predicted = match filter {
+                    0 => 0,
+                    1 => left,
+                    2 => previous[i],
+                    3 => ((left as u16 + previous[i] as u16) / 2) as u8,
+                    _ => paeth(left, previous[i], up_left),
+                };
+                filtered.push(row[i].wrapping_sub(predicted));
+            }
+            filtered
+        });
+        let cost = |filtered: &Vec<u8>| filtered[1..].iter().map(|&b| (b as i8).unsigned_abs() as u32).sum::<u32>();
+        data.extend(filtered.min_by_key(cost).unwrap());
+        previous = row;
+    }
+
+    let mut header = vec![];
+    header.extend((width as u32).to_be_bytes());
+    header.extend((height as u32).to_be_bytes());
+    header.extend([
+        8,
+        if opaque {
+            COLOR_RGB
+        } else {
+            COLOR_RGBA
+        },
+        0,
+        0,
+        0,
+    ]);
+
+    let mut out = SIGNATURE.to_vec();
+    write_chunk(&mut out, b"IHDR", &header);
+    write_chunk(&mut out, b"IDAT", &zlib_compress(&data, 6));
+    write_chunk(&mut out, b"IEND", &[]);
+    out
+}
+
+/// Loads a PNG file as RGBA8 pixels, returns (pixels, width, height).
+pub fn load_png<P: AsRef<std::path::Path>>(path: P) -> Result<(Vec<u8>, usize, usize), String> {
+    let path = path.as_ref();
+    let bytes = std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    decode_png(&bytes).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+pub fn save_png<P: AsRef<std::path::Path>>(path: P, pixels: &[u8], width: usize, height: usize) -> Result<(), String> {
+    let path = path.as_ref();
+    std::fs::write(path, encode_png(pixels, width, height))
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// PNG file with the given header fields and chunks, `rows` are the unfiltered scanlines of every pass.
+    fn png(
+        width: u32,
+        height: u32,
+        depth: u8,
+        color_type: u8,
+        interlaced: bool,
+        chunks: &[(&[u8; 4], &[u8])],
+        rows: &[Vec<u8>],
+    ) -> Vec<u8> {
+        let mut header = vec![];
+        header.extend(width.to_be_bytes());
+        header.extend(height.to_be_bytes());
+        header.extend([depth, color_type, 0, 0, interlaced as u8]);
+        let data: Vec<u8> = rows.iter().flat_map(|row| [&[0], &row[..]].concat()).collect();
+        let mut out = SIGNATURE.to_vec();
+        write_chunk(&mut out, b"IHDR", &header);
+        for (chunk_type, body) in chunks {
+            write_chunk(&mut out, chunk_type, body);
+        }
+        write_chunk(&mut out, b"IDAT", &zlib_compress(&data, 6));
+        write_chunk(&mut out, b"IEND", &[]);
+        out
+    }
+
+    fn opaque_gray(values: &[u8]) -> Vec<u8> {
+        values.iter().flat_map(|&v| [v, v, v, 255]).collect()
+    }
+
+    #[test]
+    fn round_trip() {
+        let (width, height) = (37, 23);
+        let mut pixels: Vec<u8> = (0..width * height)
+            .flat_map(|i| [(i % 256) as u8, (i * 7 % 256) as u8, (i / width * 11) as u8, 255])
+            .collect();
+        let opaque = encode_png(&pixels, width, height);
+        assert_eq!(opaque[8 + 8 + 9], COLOR_RGB);
+        assert_eq!(decode_png(&opaque).unwrap(), (pixels.clone(), width, height));
+
+        pixels[3] = 128;
+        let translucent = encode_png(&pixels, width, height);
+        assert_eq!(translucent[8 + 8 + 9], COLOR_RGBA);
+        assert_eq!(decode_png(&translucent).unwrap(), (pixels, width, height));
+    }
+
+    #[test]
+    fn color_types() {
+        // 1-bit grayscale, rows are padded to whole bytes
+        let gray = png(10, 2, 1, COLOR_GRAY, false, &[], &[vec![0b1010_0000, 0b1100_0000], vec![0xFF, 0x00]]);
+        let expected: Vec<u8> =
+            [1, 0, 1, 0, 0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 0, 0].iter().map(|&v| v * 255).collect();
+        assert_eq!(decode_png(&gray).unwrap(), (opaque_gray(&expected), 10, 2));
+
+        // 2-bit palette with transparency for the first two entries only
+        let palette: &[u8] = &[255, 0, 0, 0, 255, 0, 0, 0, 255];
+        let indices =
+            png(3, 1, 2, COLOR_PALETTE, false, &[(b"PLTE", palette), (b"tRNS", &[0, 128])], &[vec![0b0001_1000]]);
+        assert_eq!(decode_png(&indices).unwrap().0, [255, 0, 0, 0, 0, 255, 0, 128, 0, 0, 255, 255]);
+        let out_of_range = png(1, 1, 2, COLOR_PALETTE, false, &[(b"PLTE", palette)], &[vec![0b1100_0000]]);
+        assert!(decode_png(&out_of_range).unwrap_err().contains("palette index"));
+
+        // 16-bit RGB with a transparent color, compared on all 16 bits
+        let rows = [vec![0x12, 0x34, 0x56, 0x78, 0x9A, 0xBC, 0x12, 0x34, 0x56, 0x78, 0x9A, 0xBD]];
+        let trns: &[u8] = &[0x12, 0x34, 0x56, 0x78, 0x9A, 0xBC];
+        let rgb = png(2, 1, 16, COLOR_RGB, false, &[(b"tRNS", trns)], &rows);
+        assert_eq!(decode_png(&rgb).unwrap().0, [0x12, 0x56, 0x9A, 0, 0x12, 0x56, 0x9A, 255]);
+
+        // 4-bit grayscale scales to 8 bits, 8-bit gray with alpha
+        let gray4 = png(2, 1, 4, COLOR_GRAY, false, &[], &[vec![0x0F]]);
+        assert_eq!(decode_png(&gray4).unwrap().0, opaque_gray(&[0, 255]));
+        let gray_alpha = png(1, 1, 8, COLOR_GRAY_ALPHA, false, &[], &[vec![100, 50]]);
+        assert_eq!(decode_png(&gray_alpha).unwrap().0, [100, 100, 100, 50]);
+    }
+
+    #[test]
+    fn interlaced() {
+        // 8-bit grayscale where every pixel is x + 10 y, with the scanlines of the 7 passes
+        let (width, height) = (5, 3);
+        let mut rows = vec![];
+        for &(x0, y0, dx, dy) in &ADAM7 {
+            for y in (y0..height).step_by(dy) {
+                let row: Vec<u8> = (x0..width).step_by(dx).map(|x| (x + 10 * y) as u8).collect();
+                if !row.is_empty() {
+                    rows.push(row);
+                }
+            }
+        }
+        let image = png(width as u32, height as u32, 8, COLOR_GRAY, true, &[], &rows);
+        let expected: Vec<u8> = (0..height).flat_map(|y| (0..width).map(move |x| (x + 10 * y) as u8)).collect();
+        assert_eq!(decode_png(&image).unwrap(), (opaque_gray(&expected), width, height));
+    }
+
+    #[test]
+    fn errors() {
+        let image = encode_png(&[1, 2, 3, 4], 1, 1);
+        assert!(decode_png(&image[1..]).unwrap_err().contains("Not a PNG"));
+        assert!(decode_png(&image[..image.len() - 12]).unwrap_err().contains("IEND"));
+        let mut corrupted = image.clone();
+        corrupted[20] ^= 1;
+        assert!(decode_png(&corrupted).unwrap_err().contains("checksum"));
+        let bad_depth = png(1, 1, 4, COLOR_RGB, false, &[], &[vec![0]]);
+        assert!(decode_png(&bad_depth).unwrap_err().contains("bit depth"));
+        let critical = png(1, 1, 8, COLOR_GRAY, false, &[(b"ABCD", &[])], &[vec![0]]);
+        assert!(decode_png(&critical).unwrap_err().contains("critical"));
+        let missing = png(2, 2, 8, COLOR_GRAY, false, &[], &[vec![0, 0]]);
+        assert!(decode_png(&missing).unwrap_err().contains("image data"));
+        let extra = png(1, 1, 8, COLOR_GRAY, false, &[], &[vec![0, 0]]);
+        assert!(decode_png(&extra).unwrap_err().contains("image data"));
+
+        // The size is checked before anything is allocated for the pixels
+        let huge = png(MAX_SIZE, MAX_SIZE, 8, COLOR_RGBA, false, &[], &[vec![0; 4]]);
+        assert!(decode_png(&huge).unwrap_err().contains("Invalid size"));
+        let large = png(20000, 20000, 8, COLOR_RGBA, false, &[], &[vec![0; 4]]);
+        assert!(decode_png(&large).unwrap_err().contains("image data"));
+    }
+}
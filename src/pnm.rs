@@ -0,0 +1,145 @@
+// PPM and PGM images:
+// Decodes the Netpbm color (P3 ASCII, P6 binary) and grayscale (P2 ASCII, P5 binary) formats with any maximum value
+// up to 65535 to RGBA8, and encodes RGBA8 pixels as binary 8-bit PPM or PGM. Neither format stores alpha.
+
+/// Whether `bytes` starts with the magic number of a PPM or PGM file.
+pub fn is_pnm(bytes: &[u8]) -> bool {
+    bytes.len() > 2
+        && bytes[0] == b'P'
+        && matches!(bytes[1], b'2' | b'3' | b'5' | b'6')
+        && bytes[2].is_ascii_whitespace()
+}
+
+/// Reads the next whitespace separated ASCII number, skipping `#` comments up to the end of their line.
+fn number(bytes: &[u8], offset: &mut usize) -> Result<u32, String> {
+    loop {
+        match bytes.get(*offset) {
+            Some(b'#') => {
+                while bytes.get(*offset).is_some_and(|&c| c != b'\n' && c != b'\r') {
+                    *offset += 1;
+                }
+            }
+            Some(c) if c.is_ascii_whitespace() => *offset += 1,
+            _ => break,
+        }
+    }
+    let start = *offset;
+    while bytes.get(*offset).is_some_and(u8::is_ascii_digit) {
+        *offset += 1;
+    }
+    std::str::from_utf8(&bytes[start..*offset])
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| format!("Expected a number at byte {}", start))
+}
+
+/// Decodes a PPM or PGM file, returns (RGBA8 pixels, width, height).
+pub fn decode_pnm(bytes: &[u8]) -> Result<(Vec<u8>, usize, usize), String> {
+    if !is_pnm(bytes) {
+        return Err(String::from("Not a PPM or PGM file"));
+    }
+    let (ascii, channels) = match bytes[1] {
+        b'2' => (true, 1),
+        b'3' => (true, 3),
+        b'5' => (false, 1),
+        _ => (false, 3),
+    };
+    let mut offset = 2;
+    let width = number(bytes, &mut offset)? as usize;
+    let height = number(bytes, &mut offset)? as usize;
+    let max = number(bytes, &mut offset)?;
+    if width == 0 || height == 0 {
+        return Err(format!("Invalid size {}x{}", width, height));
+    }
+    if max == 0 || max > 65535 {
+        return Err(format!("Invalid maximum value {}", max));
+    }
+    let count = width.checked_mul(height * channels).ok_or("PPM or PGM image too large")?;
+    let samples: Vec<u32> = if ascii {
+        (0..count).map(|_| number(bytes, &mut offset)).collect::<Result<_, _>>()?
+    } else {
+        // A single whitespace character separates the header from the binary samples
+        offset += 1;
+        let size = if max > 255 {
+            2
+        } else {
+            1
+        };
+        let data =
+            bytes.get(offset..offset.saturating_add(count.saturating_mul(size))).ok_or("Truncated PPM or PGM data")?;
+        data.chunks_exact(size).map(|s| s.iter().fold(0, |v, &b| v << 8 | b as u32)).collect()
+    };
+    if let Some(s) = samples.iter().find(|&&s| s > max) {
+        return Err(format!("Sample {} above the maximum value {}", s, max));
+    }
+    let scale = |s: u32| ((s * 255 + max / 2) / max) as u8;
+    let pixels = samples
+        .chunks_exact(channels)
+        .flat_map(|p| match p {
+            [v] => [scale(*v), scale(*v), scale(*v), 255],
+            _ => [scale(p[0]), scale(p[1]), scale(p[2]), 255],
+        })
+        .collect();
+    Ok((pixels, width, height))
+}
+
+fn header(magic: &str, width: usize, height: usize) -> Vec<u8> {
+    format!("{}\n{} {}\n255\n", magic, width, height).into_bytes()
+}
+
+/// Encodes the colors of RGBA8 pixels as a binary PPM file (P6), dropping alpha.
+pub fn encode_ppm(pixels: &[u8], width: usize, height: usize) -> Vec<u8> {
+    assert_eq!(pixels.len(), width * height * 4);
+    let mut out = header("P6", width, height);
+    out.extend(pixels.chunks_exact(4).flat_map(|p| [p[0], p[1], p[2]]));
+    out
+}
+
+/// Encodes the Rec. 709 luminance of RGBA8 pixels as a binary PGM file (P5), dropping alpha.
+pub fn encode_pgm(pixels: &[u8], width: usize, height: usize) -> Vec<u8> {
+    assert_eq!(pixels.len(), width * height * 4);
+    let mut out = header("P5", width, height);
+    out.extend(
+        pixels
+            .chunks_exact(4)
+            .map(|p| ((2126 * p[0] as u32 + 7152 * p[1] as u32 + 722 * p[2] as u32 + 5000) / 10000) as u8),
+    );
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let pixels: Vec<u8> = (0..12u8).flat_map(|i| [i * 20, 255 - i, i, 255]).collect();
+        let file = encode_ppm(&pixels, 4, 3);
+        assert!(file.starts_with(b"P6\n4 3\n255\n"));
+        assert_eq!(decode_pnm(&file).unwrap(), (pixels, 4, 3));
+
+        let gray: Vec<u8> = (0..6u8).flat_map(|i| [i * 40, i * 40, i * 40, 255]).collect();
+        let file = encode_pgm(&gray, 3, 2);
+        assert_eq!(file.len(), 11 + 6);
+        assert_eq!(decode_pnm(&file).unwrap(), (gray, 3, 2));
+    }
+
+    #[test]
+    fn decode() {
+        // ASCII with comments, maximum value 15
+        let file = b"P3 # comment\n2 1 # size\n15\n15 0 0\n# last pixel\n0 5 15\n";
+        assert_eq!(decode_pnm(file).unwrap(), (vec![255, 0, 0, 255, 0, 85, 255, 255], 2, 1));
+        let file = b"P2\n2 2\n1\n0 1\n1 0";
+        assert_eq!(decode_pnm(file).unwrap().0.chunks(4).map(|p| p[1]).collect::<Vec<_>>(), [0, 255, 255, 0]);
+
+        // 16-bit big endian samples
+        let file = [b"P5 2 1 65535\n".as_slice(), &[0xFF, 0xFF, 0x80, 0x00]].concat();
+        assert_eq!(decode_pnm(&file).unwrap().0, [255, 255, 255, 255, 128, 128, 128, 255]);
+
+        assert!(decode_pnm(&file[..file.len() - 1]).unwrap_err().contains("Truncated"));
+        assert!(decode_pnm(b"P2 1 1 3 4").unwrap_err().contains("above the maximum"));
+        assert!(decode_pnm(b"P2 1 1 3").unwrap_err().contains("Expected a number"));
+        assert!(decode_pnm(b"P6 1 1 70000\n").unwrap_err().contains("maximum value"));
+        assert!(!is_pnm(b"P4 1 1\n") && !is_pnm(b"PK\x03\x04"));
+    }
+}
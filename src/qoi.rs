@@ -0,0 +1,204 @@
+// QOI images:
+// The "Quite OK Image" format (https://qoiformat.org/qoi-specification.pdf) compresses RGB(A) pixels in one pass
+// with runs, an index of recently seen colors and small differences to the previous pixel. Decodes to RGBA8 and
+// encodes RGBA8 pixels with 3 channels if every pixel is opaque, 4 otherwise.
+
+const MAGIC: &[u8; 4] = b"qoif";
+const HEADER_SIZE: usize = 14;
+const END_MARKER: [u8; 8] = [0, 0, 0, 0, 0, 0, 0, 1];
+const OP_INDEX: u8 = 0x00;
+const OP_DIFF: u8 = 0x40;
+const OP_LUMA: u8 = 0x80;
+const OP_RUN: u8 = 0xC0;
+const OP_RGB: u8 = 0xFE;
+const OP_RGBA: u8 = 0xFF;
+const MASK_2: u8 = 0xC0;
+/// Largest accepted number of pixels, as the reference implementation does
+const MAX_PIXELS: usize = 400_000_000;
+
+fn hash(p: [u8; 4]) -> usize {
+    (p[0] as usize * 3 + p[1] as usize * 5 + p[2] as usize * 7 + p[3] as usize * 11) % 64
+}
+
+/// Decodes a QOI file, returns (RGBA8 pixels, width, height).
+pub fn decode_qoi(bytes: &[u8]) -> Result<(Vec<u8>, usize, usize), String> {
+    if !bytes.starts_with(MAGIC) || bytes.len() < HEADER_SIZE {
+        return Err(String::from("Not a QOI file"));
+    }
+    let width = u32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]) as usize;
+    let height = u32::from_be_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]) as usize;
+    let (channels, color_space) = (bytes[12], bytes[13]);
+    if width == 0 || height == 0 || width.saturating_mul(height) > MAX_PIXELS {
+        return Err(format!("Invalid size {}x{}", width, height));
+    }
+    if !matches!(channels, 3 | 4) || color_space > 1 {
+        return Err(format!("Invalid QOI header: {} channels, color space {}", channels, color_space));
+    }
+
+    let mut pixels = Vec::with_capacity(width * height * 4);
+    let mut index = [[0u8; 4]; 64];
+    let mut previous = [0, 0, 0, 255];
+    let mut data = bytes[HEADER_SIZE..].iter().copied();
+    let mut next = || data.next().ok_or("Truncated QOI data");
+    while pixels.len() < width * height * 4 {
+        let op = next()?;
+        let mut run = 1;
+        let p = match op {
+            OP_RGB => [next()?, next()?, next()?, previous[3]],
+            OP_RGBA => [next()?, next()?, next()?, next()?],
+            _ => match op & MASK_2 {
+                OP_INDEX => index[op as usize],
+                OP_DIFF => {
+                    let d = |shift: u8| ((op >> shift) & 3).wrapping_sub(2);
+                    let [r, g, b, a] = previous;
+                    [r.wrapping_add(d(4)), g.wrapping_add(d(2)), b.wrapping_add(d(0)), a]
+                }
+                OP_LUMA => {
+                    let dg = (op & 0x3F).wrapping_sub(32);
+                    let second = next()?;
+                    let dr = dg.wrapping_add(second >> 4).wrapping_sub(8);
+                    let db = dg.wrapping_add(second & 0x0F).wrapping_sub(8);
+                    let [r, g, b, a] = previous;
+                    [r.wrapping_add(dr), g.wrapping_add(dg), b.wrapping_add(db), a]
+                }
+                _ => {
+                    run = (op & 0x3F) as usize + 1;
+                    previous
+                }
+            },
+        };
+        index[hash(p)] = p;
+        previous = p;
+        for _ in 0..run.min(width * height - pixels.len() / 4) {
+            pixels.extend(p);
+        }
+    }
+    Ok((pixels, width, height))
+}
+
+/// Encodes RGBA8 pixels as a QOI file.
+pub fn encode_qoi(pixels: &[u8], width: usize, height: usize) -> Vec<u8> {
+    assert_eq!(pixels.len(), width * height * 4);
+    let opaque = pixels.chunks_exact(4).all(|p| p[3] == 255);
+    let mut out = Vec::with_capacity(HEADER_SIZE + pixels.len() / 2);
+    out.extend(MAGIC);
+    out.extend((width as u32).to_be_bytes());
+    out.extend((height as u32).to_be_bytes());
+    // sRGB with linear alpha
+    out.extend([
+        if opaque {
+            3
+        } else {
+            4
+        },
+        0,
+    ]);
+
+    let mut index = [[0u8; 4]; 64];
+    let mut previous = [0, 0, 0, 255];
+    let mut run = 0;
+    for (i, p) in pixels.chunks_exact(4).enumerate() {
+        let p = [p[0], p[1], p[2], p[3]];
+        if p == previous {
+            run += 1;
+            if run == 62 || i == width * height - 1 {
+                out.push(OP_RUN | (run - 1));
+                run = 0;
+            }
+            continue;
+        }
+        if run > 0 {
+            out.push(OP_RUN | (run - 1));
+            run = 0;
+        }
+        let h = hash(p);
+        if index[h] == p {
+            out.push(OP_INDEX | h as u8);
+        } else if p[3] != previous[3] {
+            out.extend([OP_RGBA, p[0], p[1], p[2], p[3]]);
+        } else {
+            let dr = p[0].wrapping_sub(previous[0]) as i8;
+            let dg = p[1].wrapping_sub(previous[1]) as i8;
+            let db = p[2].wrapping_sub(previous[2]) as i8;
+            let (dr_dg, db_dg) = (dr.wrapping_sub(dg), db.wrapping_sub(dg));
+            if (-2..2).contains(&dr) && (-2..2).contains(&dg) && (-2..2).contains(&db) {
+                out.push(OP_DIFF | ((dr + 2) as u8) << 4 | ((dg + 2) as u8) << 2 | (db + 2) as u8);
+            } else if (-32..32).contains(&dg) && (-8..8).contains(&dr_dg) && (-8..8).contains(&db_dg) {
+                out.extend([OP_LUMA | (dg + 32) as u8, ((dr_dg + 8) as u8) << 4 | (db_dg + 8) as u8]);
+            } else {
+                out.extend([OP_RGB, p[0], p[1], p[2]]);
+            }
+        }
+        index[h] = p;
+        previous = p;
+    }
+    out.extend(END_MARKER);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        // Runs longer than 62 pixels, small and large differences, repeated colors and alpha changes
+        let mut pixels: Vec<u8> = vec![];
+        for i in 0..400u32 {
+            let p = match i {
+                0..100 => [0, 0, 0, 255],
+                100..200 => [(i * 3) as u8, 100 + i as u8 % 5, (i / 2) as u8, 255],
+                200..300 => [(i * i) as u8, (i * 7) as u8, (i * 13) as u8, (i * 5) as u8],
+                _ => [
+                    (i % 3 * 80) as u8,
+                    10,
+                    20,
+                    if i % 3 == 0 {
+                        255
+                    } else {
+                        128
+                    },
+                ],
+            };
+            pixels.extend(p);
+        }
+        let file = encode_qoi(&pixels, 20, 20);
+        assert_eq!(file[12], 4);
+        assert!(file.ends_with(&END_MARKER) && file.len() < pixels.len());
+        assert_eq!(decode_qoi(&file).unwrap(), (pixels, 20, 20));
+
+        let opaque = vec![255; 7 * 9 * 4];
+        let file = encode_qoi(&opaque, 7, 9);
+        // A difference of -1 to the initial black, then a single run
+        assert_eq!((file[12], file.len()), (3, HEADER_SIZE + 2 + 8));
+        assert_eq!(decode_qoi(&file).unwrap(), (opaque, 7, 9));
+    }
+
+    #[test]
+    fn decode() {
+        let header = |width: u32, height: u32, channels: u8| {
+            [MAGIC.as_slice(), &width.to_be_bytes(), &height.to_be_bytes(), &[channels, 0]].concat()
+        };
+        // RGB, DIFF (+1, -2, 0), LUMA (dg = 4, dr = 4 - 3, db = 4 + 2), RUN of 2, INDEX of the first pixel
+        let ops = [
+            OP_RGB,
+            10,
+            20,
+            30,
+            OP_DIFF | 3 << 4 | 2,
+            OP_LUMA | 36,
+            5 << 4 | 10,
+            OP_RUN | 1,
+            hash([10, 20, 30, 255]) as u8,
+        ];
+        let file = [header(6, 1, 3), ops.to_vec(), END_MARKER.to_vec()].concat();
+        let (pixels, ..) = decode_qoi(&file).unwrap();
+        let expected = [[10, 20, 30, 255], [11, 18, 30, 255], [12, 22, 36, 255], [12, 22, 36, 255], [12, 22, 36, 255]];
+        assert_eq!(pixels, [expected.concat(), vec![10, 20, 30, 255]].concat());
+
+        assert!(decode_qoi(&file[..HEADER_SIZE + 5]).unwrap_err().contains("Truncated"));
+        assert!(decode_qoi(&header(0, 1, 4)).unwrap_err().contains("Invalid size"));
+        assert!(decode_qoi(&header(1, 1, 2)).unwrap_err().contains("channels"));
+        assert!(decode_qoi(b"qoi").is_err());
+    }
+}
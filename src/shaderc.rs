@@ -2,33 +2,43 @@
 #![allow(non_snake_case)]
 
 use crate::opaque;
-
-// TODO: Incomplete!!!
+use std::cell::RefCell;
+use std::ffi::{c_void, CStr, CString};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::sync::OnceLock;
 
 // glsl: https://github.com/KhronosGroup/glslang
-/// GLSL Text -> SPIR-V Binary
-// C++ api??? -> ShCompile
-//#[link(name = "glslang")]
-//extern "C" {
-//    pub fn glslang_initialize_process() -> i32;
-//    pub fn glslang_finalize_process();
-//
-//    pub fn glslang_shader_create(input: *const glslang_input_t) -> *mut glslang_shader_t;
-//    pub fn glslang_shader_delete(shader: *mut glslang_shader_t);
-//    pub fn glslang_shader_parse(shader: *mut glslang_shader_t, input: *const glslang_input_t) -> i32;
-//
-//    pub fn glslang_program_create() -> *mut glslang_program_t;
-//    pub fn glslang_program_delete(program: *mut glslang_program_t);
-//    pub fn glslang_program_add_shader(program: *mut glslang_program_t, shader: *mut glslang_shader_t);
-//    pub fn glslang_program_link(program: *mut glslang_program_t, messages: i32) -> i32;
-//    pub fn glslang_program_SPIRV_generate(program: *mut glslang_program_t, stage: glslang_stage_t);
-//    pub fn glslang_program_SPIRV_get_size(program: *mut glslang_program_t) -> usize;
-//    pub fn glslang_program_SPIRV_get(program: *mut glslang_program_t, spirv: *mut u32);
-//    pub fn glslang_program_SPIRV_get_ptr(program: *mut glslang_program_t) -> *mut u32;
-//    pub fn glslang_program_SPIRV_get_messages(program: *mut glslang_program_t) -> *const i8;
-//    pub fn glslang_program_get_info_log(program: *mut glslang_program_t) -> *const i8;
-//    pub fn glslang_program_get_info_debug_log(program: *mut glslang_program_t) -> *const i8;
-//}
+// GLSL Text -> SPIR-V Binary
+#[link(name = "glslang")]
+#[link(name = "glslang-default-resource-limits")]
+extern "C" {
+    pub fn glslang_initialize_process() -> i32;
+    pub fn glslang_finalize_process();
+    pub fn glslang_default_resource() -> *const glslang_resource_t;
+
+    pub fn glslang_shader_create(input: *const glslang_input_t) -> *mut glslang_shader_t;
+    pub fn glslang_shader_delete(shader: *mut glslang_shader_t);
+    pub fn glslang_shader_set_preamble(shader: *mut glslang_shader_t, s: *const i8);
+    pub fn glslang_shader_preprocess(shader: *mut glslang_shader_t, input: *const glslang_input_t) -> i32;
+    pub fn glslang_shader_parse(shader: *mut glslang_shader_t, input: *const glslang_input_t) -> i32;
+    pub fn glslang_shader_get_preprocessed_code(shader: *mut glslang_shader_t) -> *const i8;
+    pub fn glslang_shader_get_info_log(shader: *mut glslang_shader_t) -> *const i8;
+    pub fn glslang_shader_get_info_debug_log(shader: *mut glslang_shader_t) -> *const i8;
+
+    pub fn glslang_program_create() -> glslang_program_t;
+    pub fn glslang_program_delete(program: glslang_program_t);
+    pub fn glslang_program_add_shader(program: glslang_program_t, shader: *mut glslang_shader_t);
+    pub fn glslang_program_link(program: glslang_program_t, messages: i32) -> i32;
+    pub fn glslang_program_SPIRV_generate(program: glslang_program_t, stage: glslang_stage_t);
+    pub fn glslang_program_SPIRV_get_size(program: glslang_program_t) -> usize;
+    pub fn glslang_program_SPIRV_get(program: glslang_program_t, spirv: *mut u32);
+    pub fn glslang_program_SPIRV_get_ptr(program: glslang_program_t) -> *mut u32;
+    pub fn glslang_program_SPIRV_get_messages(program: glslang_program_t) -> *const i8;
+    pub fn glslang_program_get_info_log(program: glslang_program_t) -> *const i8;
+    pub fn glslang_program_get_info_debug_log(program: glslang_program_t) -> *const i8;
+}
 
 #[repr(C)]
 #[derive(Debug)]
@@ -66,8 +76,40 @@ pub struct glslang_input_t {
     pub default_profile: glslang_profile_t,
     pub force_default_version_and_profile: i32,
     pub forward_compatible: i32,
-    pub messages: glslang_messages_t,
+    /// Bits of `glslang_messages_t`
+    pub messages: i32,
     pub resource: *const glslang_resource_t,
+    pub callbacks: glsl_include_callbacks_t,
+    pub callbacks_ctx: *mut c_void,
+}
+
+#[repr(C)]
+#[derive(Debug)]
+pub struct glsl_include_result_t {
+    /// Resolved name of the header, empty when the include failed (`header_data` then holds the error)
+    pub header_name: *const i8,
+    pub header_data: *const i8,
+    pub header_length: usize,
+}
+
+pub type glsl_include_system_func = Option<
+    unsafe extern "C" fn(
+        ctx: *mut c_void,
+        header_name: *const i8,
+        includer_name: *const i8,
+        include_depth: usize,
+    ) -> *mut glsl_include_result_t,
+>;
+pub type glsl_include_local_func = glsl_include_system_func;
+pub type glsl_free_include_result_func =
+    Option<unsafe extern "C" fn(ctx: *mut c_void, result: *mut glsl_include_result_t) -> i32>;
+
+#[repr(C)]
+#[derive(Debug, Default)]
+pub struct glsl_include_callbacks_t {
+    pub include_system: glsl_include_system_func,
+    pub include_local: glsl_include_local_func,
+    pub free_include_result: glsl_free_include_result_func,
 }
 
 #[repr(C)]
@@ -81,7 +123,7 @@ pub enum glslang_source_t {
 pub use glslang_source_t::*;
 
 #[repr(C)]
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum glslang_stage_t {
     GLSLANG_STAGE_VERTEX,
     GLSLANG_STAGE_TESSCONTROL,
@@ -112,7 +154,7 @@ pub enum glslang_client_t {
 pub use glslang_client_t::*;
 
 #[repr(C)]
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum glslang_target_client_version_t {
     GLSLANG_TARGET_VULKAN_1_0 = (1 << 22),
     GLSLANG_TARGET_VULKAN_1_1 = (1 << 22) | (1 << 12),
@@ -133,7 +175,7 @@ pub enum glslang_target_language_t {
 pub use glslang_target_language_t::*;
 
 #[repr(C)]
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum glslang_target_language_version_t {
     GLSLANG_TARGET_SPV_1_0 = (1 << 16),
     GLSLANG_TARGET_SPV_1_1 = (1 << 16) | (1 << 8),
@@ -309,94 +351,474 @@ pub struct glslang_limits_t {
     pub general_constant_matrix_vector_indexing: bool,
 }
 
-/// SPIR-V Assembly Text -> SPIR-V Binary
-//#[link(name = "SPIRV-Tools-shared")]
-//extern "C" {
-//    pub fn spvSoftwareVersionString() -> *const i8;
-//    pub fn spvSoftwareVersionDetailsString() -> *const i8;
-//    pub fn spvTargetEnvDescription(env: spv_target_env) -> *const i8;
-//    pub fn spvParseTargetEnv(s: *const i8, env: *mut spv_target_env) -> bool;
-//    pub fn spvParseVulkanEnv(vulkan_ver: u32, spirv_ver: u32, env: *mut spv_target_env) -> bool;
-//    pub fn spvContextCreate(env: spv_target_env) -> spv_context;
-//    pub fn spvContextDestroy(context: spv_context);
-//    pub fn spvValidatorOptionsCreate() -> spv_validator_options;
-//    pub fn spvValidatorOptionsDestroy(options: spv_validator_options);
-//    pub fn spvValidatorOptionsSetUniversalLimit(
-//        options: spv_validator_options,
-//        limit_type: spv_validator_limit,
-//        limit: u32,
-//    );
-//    pub fn spvValidatorOptionsSetRelaxStoreStruct(options: spv_validator_options, val: bool);
-//    pub fn spvValidatorOptionsSetRelaxLogicalPointer(options: spv_validator_options, val: bool);
-//    // pub fn spvValidatorOptionsSetBeforeHlslLegalization
-//    // pub fn spvValidatorOptionsSetRelaxBlockLayout
-//    // pub fn spvValidatorOptionsSetUniformBufferStandardLayout
-//    // pub fn spvValidatorOptionsSetScalarBlockLayout
-//    // pub fn spvValidatorOptionsSetWorkgroupScalarBlockLayout
-//    // pub fn spvValidatorOptionsSetSkipBlockLayout
-//    // pub fn spvValidatorOptionsSetAllowLocalSizeId
-//
-//    pub fn spvOptimizerOptionsCreate() -> spv_optimizer_options;
-//    pub fn spvOptimizerOptionsDestroy(options: spv_optimizer_options);
-//    // pub fn spvOptimizerOptionsSetRunValidator
-//    // pub fn spvOptimizerOptionsSetValidatorOptions
-//    // pub fn spvOptimizerOptionsSetMaxIdBound
-//    // pub fn spvOptimizerOptionsSetPreserveBindings
-//    // pub fn spvOptimizerOptionsSetPreserveSpecConstants
-//
-//    pub fn spvReducerOptionsCreate() -> spv_reducer_options;
-//    pub fn spvReducerOptionsDestroy(options: spv_reducer_options);
-//    // pub fn spvReducerOptionsSetStepLimit
-//    // pub fn spvReducerOptionsSetFailOnValidationError
-//    // pub fn spvReducerOptionsSetTargetFunction
-//
-//    pub fn spvFuzzerOptionsCreate() -> spv_fuzzer_options;
-//    pub fn spvFuzzererOptionsDestroy(options: spv_fuzzer_options);
-//    // pub fn spvFuzzerOptionsEnableReplayValidation
-//    // pub fn spvFuzzerOptionsSetRandomSeed
-//    // pub fn spvFuzzerOptionsSetReplayRange
-//    // pub fn spvFuzzerOptionsSetShrinkerStepLimit
-//    // pub fn spvFuzzerOptionsEnableFuzzerPassValidation
-//    // pub fn spvFuzzerOptionsEnableAllPasses
-//
-//    pub fn spvTextToBinary(
-//        context: spv_const_context,
-//        text: *const i8,
-//        length: usize,
-//        binary: *mut spv_binary,
-//        diagnostic: *mut spv_diagnostic,
-//    ) -> spv_result_t;
-//    pub fn spvTextToBinaryWithOptions(
-//        context: spv_const_context,
-//        text: *const i8,
-//        length: usize,
-//        options: u32,
-//        binary: *mut spv_binary,
-//        diagnostic: *mut spv_diagnostic,
-//    ) -> spv_result_t;
-//    pub fn spvTextDestroy(text: spv_text);
-//    pub fn spvBinaryToText(
-//        context: spv_const_context,
-//        binary: *const u32,
-//        word_count: usize,
-//        options: u32,
-//        text: *mut spv_text,
-//        diagnostic: *mut spv_diagnostic,
-//    ) -> spv_result_t;
-//    pub fn spvBinaryDestroy(binary: spv_binary);
-//
-//    // pub fn spvBinaryDestroy
-//    // pub fn spvValidateWithOptions
-//    // pub fn spvValidateBinary
-//    // pub fn spvDiagnosticCreate
-//    // pub fn spvDiagnosticDestroy
-//    // pub fn spvDiagnosticPrint
-//    // pub fn spvOpcodeString
-//    // pub fn spvBinaryParse
-//}
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShaderStage {
+    Vertex,
+    TessControl,
+    TessEvaluation,
+    Geometry,
+    Fragment,
+    Compute,
+}
+
+impl ShaderStage {
+    /// Stage of a shader from the extension of its file, as used by glslc (`.vert`, `.frag`, `.comp`...).
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        Some(match path.as_ref().extension()?.to_str()? {
+            "vert" => Self::Vertex,
+            "tesc" => Self::TessControl,
+            "tese" => Self::TessEvaluation,
+            "geom" => Self::Geometry,
+            "frag" => Self::Fragment,
+            "comp" => Self::Compute,
+            _ => return None,
+        })
+    }
+
+    fn glslang(self) -> glslang_stage_t {
+        match self {
+            Self::Vertex => GLSLANG_STAGE_VERTEX,
+            Self::TessControl => GLSLANG_STAGE_TESSCONTROL,
+            Self::TessEvaluation => GLSLANG_STAGE_TESSEVALUATION,
+            Self::Geometry => GLSLANG_STAGE_GEOMETRY,
+            Self::Fragment => GLSLANG_STAGE_FRAGMENT,
+            Self::Compute => GLSLANG_STAGE_COMPUTE,
+        }
+    }
+}
+
+/// Resolves `#include "name"` and `#include <name>`: gets the name of the header and of the file including it,
+/// returns the resolved name of the header (used in diagnostics and nested includes) and its contents.
+pub type IncludeCallback<'a> = &'a dyn Fn(&str, &str) -> Result<(String, String), String>;
+
+#[derive(Clone)]
+pub struct CompileOptions<'a> {
+    /// Name of the source in diagnostics and the includer name of its includes
+    pub file_name: String,
+    /// Macros defined before the first line, like `-D NAME=VALUE`
+    pub defines: Vec<(String, String)>,
+    pub include: Option<IncludeCallback<'a>>,
+    pub client_version: glslang_target_client_version_t,
+    pub target_language_version: glslang_target_language_version_t,
+    pub warnings_as_errors: bool,
+}
+
+impl Default for CompileOptions<'_> {
+    fn default() -> Self {
+        Self {
+            file_name: "shader".to_string(),
+            defines: vec![],
+            include: None,
+            client_version: GLSLANG_TARGET_VULKAN_1_0,
+            target_language_version: GLSLANG_TARGET_SPV_1_0,
+            warnings_as_errors: false,
+        }
+    }
+}
+
+/// Include callback reading headers relative to the file including them, then relative to `dirs`.
+pub fn include_from_dirs(dirs: Vec<PathBuf>) -> impl Fn(&str, &str) -> Result<(String, String), String> {
+    move |name, includer| {
+        let relative = Path::new(includer).parent().map(|dir| dir.join(name));
+        let candidates = relative.into_iter().chain(dirs.iter().map(|dir| dir.join(name)));
+        for path in candidates {
+            if let Ok(contents) = std::fs::read_to_string(&path) {
+                return Ok((path.to_string_lossy().into_owned(), contents));
+            }
+        }
+        Err(format!("Can't find '{}' included by '{}'", name, includer))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub file: Option<String>,
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}:", file)?;
+            if let Some(line) = self.line {
+                write!(f, "{}:", line)?;
+            }
+            if let Some(column) = self.column {
+                write!(f, "{}:", column)?;
+            }
+            write!(f, " ")?;
+        }
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{}: {}", severity, self.message)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Diagnostics(pub Vec<Diagnostic>);
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, diagnostic) in self.0.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", diagnostic)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Diagnostics {}
+
+impl Diagnostics {
+    fn error(message: impl Into<String>) -> Self {
+        Self(vec![Diagnostic {
+            severity: Severity::Error,
+            file: None,
+            line: None,
+            column: None,
+            message: message.into(),
+        }])
+    }
+
+    /// Parses an info log of glslang, made of lines like `ERROR: 0:12: 'foo' : undeclared identifier`.
+    /// The main source is called "0" by glslang, it's replaced by `file_name`.
+    pub fn parse(log: &str, file_name: &str) -> Self {
+        let mut diagnostics = vec![];
+        for line in log.lines() {
+            let (severity, rest) = if let Some(rest) = line.strip_prefix("ERROR: ") {
+                (Severity::Error, rest)
+            } else if let Some(rest) = line.strip_prefix("WARNING: ") {
+                (Severity::Warning, rest)
+            } else {
+                // Continuation of the previous message
+                match diagnostics.last_mut() {
+                    Some(Diagnostic {
+                        message,
+                        ..
+                    }) if !line.trim().is_empty() => {
+                        *message += "\n";
+                        *message += line;
+                    }
+                    _ => {}
+                }
+                continue;
+            };
+            // Summaries like "1 compilation errors.  No code generated."
+            if rest.ends_with("No code generated.") || rest.ends_with("compilation warnings.") {
+                continue;
+            }
+
+            // "file:line: message" or "file:line:column: message", file names can contain ':'
+            let (location, message) = rest.split_once(": ").unwrap_or(("", rest));
+            let mut numbers = location.rsplitn(3, ':');
+            let last = numbers.next().and_then(|n| n.parse::<u32>().ok());
+            let (file, line, column) = match (last, numbers.next(), numbers.next()) {
+                (Some(column), Some(line), Some(file)) if line.parse::<u32>().is_ok() => {
+                    (file.to_string(), line.parse().ok(), Some(column))
+                }
+                (Some(line), Some(file), rest) => {
+                    (rest.map_or(file.to_string(), |rest| format!("{}:{}", rest, file)), Some(line), None)
+                }
+                _ => {
+                    diagnostics.push(Diagnostic {
+                        severity,
+                        file: None,
+                        line: None,
+                        column: None,
+                        message: rest.trim().to_string(),
+                    });
+                    continue;
+                }
+            };
+            let diagnostic = Diagnostic {
+                severity,
+                file: Some(if file == "0" {
+                    file_name.to_string()
+                } else {
+                    file
+                }),
+                line,
+                column,
+                message: message.trim().to_string(),
+            };
+            diagnostics.push(diagnostic);
+        }
+        Self(diagnostics)
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.0.iter().any(|d| d.severity == Severity::Error)
+    }
+}
+
+/// Headers returned to glslang, freed when it's done with them.
 #[repr(C)]
-#[derive(Debug)]
+struct IncludeResult {
+    result: glsl_include_result_t,
+    _name: CString,
+    _data: CString,
+}
+
+struct IncludeContext<'a> {
+    callback: IncludeCallback<'a>,
+}
+
+unsafe extern "C" fn include(
+    ctx: *mut c_void,
+    header_name: *const i8,
+    includer_name: *const i8,
+    _include_depth: usize,
+) -> *mut glsl_include_result_t {
+    let context = &*(ctx as *const IncludeContext);
+    let header_name = CStr::from_ptr(header_name).to_string_lossy();
+    let includer_name = CStr::from_ptr(includer_name).to_string_lossy();
+    // An empty name tells glslang that the include failed, with the error as data
+    let (name, data) = (context.callback)(&header_name, &includer_name).unwrap_or_else(|e| (String::new(), e));
+    let name = CString::new(name).unwrap_or_default();
+    let data = CString::new(data).unwrap_or_default();
+    let result = Box::new(IncludeResult {
+        result: glsl_include_result_t {
+            header_name: name.as_ptr(),
+            header_data: data.as_ptr(),
+            header_length: data.as_bytes().len(),
+        },
+        _name: name,
+        _data: data,
+    });
+    Box::into_raw(result) as *mut glsl_include_result_t
+}
+
+unsafe extern "C" fn free_include(_ctx: *mut c_void, result: *mut glsl_include_result_t) -> i32 {
+    // `result` is the first field of a boxed IncludeResult
+    drop(Box::from_raw(result as *mut IncludeResult));
+    0
+}
+
+static GLSLANG: OnceLock<bool> = OnceLock::new();
+
+/// Compiles GLSL to SPIR-V for Vulkan with the glslang library.
+/// Warnings don't fail the compilation unless `warnings_as_errors` is set.
+pub fn compile_glsl(source: &str, stage: ShaderStage, options: &CompileOptions) -> Result<Vec<u32>, Diagnostics> {
+    // The process is initialized once and never finalized, glslang can compile on several threads after that
+    if !*GLSLANG.get_or_init(|| unsafe { glslang_initialize_process() } != 0) {
+        return Err(Diagnostics::error("Failed to initialize glslang"));
+    }
+    let code = CString::new(source).map_err(|_| Diagnostics::error("Source contains a NUL character"))?;
+    let mut preamble = String::new();
+    if options.include.is_some() {
+        preamble += "#extension GL_GOOGLE_include_directive : require\n";
+    }
+    for (name, value) in &options.defines {
+        preamble += &format!("#define {} {}\n", name, value);
+    }
+    let preamble = CString::new(preamble).map_err(|_| Diagnostics::error("Define contains a NUL character"))?;
+
+    let context = options.include.map(|callback| IncludeContext {
+        callback,
+    });
+    let callbacks = match &context {
+        Some(_) => glsl_include_callbacks_t {
+            include_system: Some(include),
+            include_local: Some(include),
+            free_include_result: Some(free_include),
+        },
+        None => glsl_include_callbacks_t::default(),
+    };
+    let messages = GLSLANG_MSG_SPV_RULES_BIT as i32 | GLSLANG_MSG_VULKAN_RULES_BIT as i32;
+    let input = glslang_input_t {
+        language: GLSLANG_SOURCE_GLSL,
+        stage: stage.glslang(),
+        client: GLSLANG_CLIENT_VULKAN,
+        client_version: options.client_version,
+        target_language: GLSLANG_TARGET_SPV,
+        target_language_version: options.target_language_version,
+        code: code.as_ptr(),
+        default_version: 100,
+        default_profile: GLSLANG_NO_PROFILE,
+        force_default_version_and_profile: 0,
+        forward_compatible: 0,
+        messages,
+        resource: unsafe { glslang_default_resource() },
+        callbacks,
+        callbacks_ctx: context.as_ref().map_or(ptr::null_mut(), |c| c as *const IncludeContext as *mut c_void),
+    };
+
+    unsafe {
+        let shader = glslang_shader_create(&input);
+        if shader.is_null() {
+            return Err(Diagnostics::error("Failed to create the shader"));
+        }
+        glslang_shader_set_preamble(shader, preamble.as_ptr());
+        let shader_log = |shader| CStr::from_ptr(glslang_shader_get_info_log(shader)).to_string_lossy().into_owned();
+        if glslang_shader_preprocess(shader, &input) == 0 || glslang_shader_parse(shader, &input) == 0 {
+            let diagnostics = Diagnostics::parse(&shader_log(shader), &options.file_name);
+            glslang_shader_delete(shader);
+            return Err(diagnostics);
+        }
+        let mut diagnostics = Diagnostics::parse(&shader_log(shader), &options.file_name);
+
+        let program = glslang_program_create();
+        glslang_program_add_shader(program, shader);
+        let linked = glslang_program_link(program, messages) != 0;
+        let program_log = CStr::from_ptr(glslang_program_get_info_log(program)).to_string_lossy().into_owned();
+        diagnostics.0.extend(Diagnostics::parse(&program_log, &options.file_name).0);
+        let spirv = if linked {
+            glslang_program_SPIRV_generate(program, stage.glslang());
+            let messages = glslang_program_SPIRV_get_messages(program);
+            if !messages.is_null() {
+                let messages = CStr::from_ptr(messages).to_string_lossy().into_owned();
+                diagnostics.0.extend(Diagnostics::parse(&messages, &options.file_name).0);
+            }
+            let mut spirv = vec![0; glslang_program_SPIRV_get_size(program)];
+            glslang_program_SPIRV_get(program, spirv.as_mut_ptr());
+            Some(spirv)
+        } else {
+            None
+        };
+        glslang_program_delete(program);
+        glslang_shader_delete(shader);
+
+        match spirv {
+            Some(_) if diagnostics.has_errors() => Err(diagnostics),
+            Some(_) if options.warnings_as_errors && !diagnostics.0.is_empty() => Err(diagnostics),
+            Some(spirv) => Ok(spirv),
+            None if diagnostics.has_errors() => Err(diagnostics),
+            None => Err(Diagnostics::error("Failed to link the shader")),
+        }
+    }
+}
+
+// SPIRV-Tools: https://github.com/KhronosGroup/SPIRV-Tools
+// SPIR-V Assembly Text <-> SPIR-V Binary, validation and optimization
+#[link(name = "SPIRV-Tools-shared")]
+extern "C" {
+    pub fn spvSoftwareVersionString() -> *const i8;
+    pub fn spvSoftwareVersionDetailsString() -> *const i8;
+    pub fn spvTargetEnvDescription(env: spv_target_env) -> *const i8;
+    pub fn spvParseTargetEnv(s: *const i8, env: *mut spv_target_env) -> bool;
+    pub fn spvParseVulkanEnv(vulkan_ver: u32, spirv_ver: u32, env: *mut spv_target_env) -> bool;
+    pub fn spvContextCreate(env: spv_target_env) -> spv_context;
+    pub fn spvContextDestroy(context: spv_context);
+    pub fn spvValidatorOptionsCreate() -> spv_validator_options;
+    pub fn spvValidatorOptionsDestroy(options: spv_validator_options);
+    pub fn spvValidatorOptionsSetUniversalLimit(
+        options: spv_validator_options,
+        limit_type: spv_validator_limit,
+        limit: u32,
+    );
+    pub fn spvValidatorOptionsSetRelaxStoreStruct(options: spv_validator_options, val: bool);
+    pub fn spvValidatorOptionsSetRelaxLogicalPointer(options: spv_validator_options, val: bool);
+    pub fn spvValidatorOptionsSetBeforeHlslLegalization(options: spv_validator_options, val: bool);
+    pub fn spvValidatorOptionsSetRelaxBlockLayout(options: spv_validator_options, val: bool);
+    pub fn spvValidatorOptionsSetUniformBufferStandardLayout(options: spv_validator_options, val: bool);
+    pub fn spvValidatorOptionsSetScalarBlockLayout(options: spv_validator_options, val: bool);
+    pub fn spvValidatorOptionsSetWorkgroupScalarBlockLayout(options: spv_validator_options, val: bool);
+    pub fn spvValidatorOptionsSetSkipBlockLayout(options: spv_validator_options, val: bool);
+    pub fn spvValidatorOptionsSetAllowLocalSizeId(options: spv_validator_options, val: bool);
+
+    pub fn spvOptimizerOptionsCreate() -> spv_optimizer_options;
+    pub fn spvOptimizerOptionsDestroy(options: spv_optimizer_options);
+    pub fn spvOptimizerOptionsSetRunValidator(options: spv_optimizer_options, val: bool);
+    pub fn spvOptimizerOptionsSetValidatorOptions(options: spv_optimizer_options, val: spv_validator_options);
+    pub fn spvOptimizerOptionsSetMaxIdBound(options: spv_optimizer_options, val: u32);
+    pub fn spvOptimizerOptionsSetPreserveBindings(options: spv_optimizer_options, val: bool);
+    pub fn spvOptimizerOptionsSetPreserveSpecConstants(options: spv_optimizer_options, val: bool);
+
+    pub fn spvOptimizerCreate(env: spv_target_env) -> spv_optimizer;
+    pub fn spvOptimizerDestroy(optimizer: spv_optimizer);
+    pub fn spvOptimizerSetMessageConsumer(optimizer: spv_optimizer, consumer: spv_message_consumer);
+    pub fn spvOptimizerRegisterLegalizationPasses(optimizer: spv_optimizer);
+    pub fn spvOptimizerRegisterPerformancePasses(optimizer: spv_optimizer);
+    pub fn spvOptimizerRegisterSizePasses(optimizer: spv_optimizer);
+    pub fn spvOptimizerRegisterPassFromFlag(optimizer: spv_optimizer, flag: *const i8) -> bool;
+    pub fn spvOptimizerRun(
+        optimizer: spv_optimizer,
+        binary: *const u32,
+        word_count: usize,
+        optimized_binary: *mut spv_binary,
+        options: spv_optimizer_options,
+    ) -> spv_result_t;
+
+    //pub fn spvReducerOptionsCreate() -> spv_reducer_options;
+    //pub fn spvReducerOptionsDestroy(options: spv_reducer_options);
+    // pub fn spvReducerOptionsSetStepLimit
+    // pub fn spvReducerOptionsSetFailOnValidationError
+    // pub fn spvReducerOptionsSetTargetFunction
+
+    //pub fn spvFuzzerOptionsCreate() -> spv_fuzzer_options;
+    //pub fn spvFuzzererOptionsDestroy(options: spv_fuzzer_options);
+    // pub fn spvFuzzerOptionsEnableReplayValidation
+    // pub fn spvFuzzerOptionsSetRandomSeed
+    // pub fn spvFuzzerOptionsSetReplayRange
+    // pub fn spvFuzzerOptionsSetShrinkerStepLimit
+    // pub fn spvFuzzerOptionsEnableFuzzerPassValidation
+    // pub fn spvFuzzerOptionsEnableAllPasses
+
+    pub fn spvTextToBinary(
+        context: spv_const_context,
+        text: *const i8,
+        length: usize,
+        binary: *mut spv_binary,
+        diagnostic: *mut spv_diagnostic,
+    ) -> spv_result_t;
+    pub fn spvTextToBinaryWithOptions(
+        context: spv_const_context,
+        text: *const i8,
+        length: usize,
+        options: u32,
+        binary: *mut spv_binary,
+        diagnostic: *mut spv_diagnostic,
+    ) -> spv_result_t;
+    pub fn spvTextDestroy(text: spv_text);
+    pub fn spvBinaryToText(
+        context: spv_const_context,
+        binary: *const u32,
+        word_count: usize,
+        options: u32,
+        text: *mut spv_text,
+        diagnostic: *mut spv_diagnostic,
+    ) -> spv_result_t;
+    pub fn spvBinaryDestroy(binary: spv_binary);
+
+    pub fn spvValidate(
+        context: spv_const_context,
+        binary: spv_const_binary,
+        diagnostic: *mut spv_diagnostic,
+    ) -> spv_result_t;
+    pub fn spvValidateWithOptions(
+        context: spv_const_context,
+        options: spv_validator_options,
+        binary: spv_const_binary,
+        diagnostic: *mut spv_diagnostic,
+    ) -> spv_result_t;
+    pub fn spvValidateBinary(
+        context: spv_const_context,
+        words: *const u32,
+        num_words: usize,
+        diagnostic: *mut spv_diagnostic,
+    ) -> spv_result_t;
+    pub fn spvDiagnosticCreate(position: *const spv_position_t, message: *const i8) -> spv_diagnostic;
+    pub fn spvDiagnosticDestroy(diagnostic: spv_diagnostic);
+    pub fn spvDiagnosticPrint(diagnostic: *const spv_diagnostic_t) -> spv_result_t;
+    pub fn spvOpcodeString(opcode: u32) -> *const i8;
+    // pub fn spvBinaryParse
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum spv_target_env {
     SPV_ENV_UNIVERSAL_1_0, // SPIR-V 1.0 latest revision, no other restrictions.
     SPV_ENV_VULKAN_1_0,    // Vulkan 1.0 latest revision.
@@ -439,7 +861,7 @@ pub enum spv_target_env {
 pub use spv_target_env::*;
 
 #[repr(C)]
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum spv_validator_limit {
     spv_validator_limit_max_struct_members,
     spv_validator_limit_max_struct_depth,
@@ -454,7 +876,7 @@ pub enum spv_validator_limit {
 pub use spv_validator_limit::*;
 
 #[repr(C)]
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum spv_result_t {
     SPV_SUCCESS = 0,
     SPV_UNSUPPORTED = 1,
@@ -541,6 +963,7 @@ opaque!(spv_validator_options, spv_validator_options_t);
 opaque!(spv_optimizer_options, spv_optimizer_options_t);
 opaque!(spv_reducer_options, spv_reducer_options_t);
 opaque!(spv_fuzzer_options, spv_fuzzer_options_t);
+opaque!(spv_optimizer, spv_optimizer_t);
 
 #[repr(C)]
 #[derive(Debug)]
@@ -550,6 +973,14 @@ pub struct spv_binary_t {
 }
 pub type spv_binary = *mut spv_binary_t;
 
+#[repr(C)]
+#[derive(Debug)]
+pub struct spv_const_binary_t {
+    pub code: *const u32,
+    pub word_count: usize,
+}
+pub type spv_const_binary = *const spv_const_binary_t;
+
 #[repr(C)]
 #[derive(Debug)]
 pub struct spv_text_t {
@@ -575,88 +1006,581 @@ pub struct spv_position_t {
     pub index: usize,
 }
 
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum spv_message_level_t {
+    SPV_MSG_FATAL,          // Unrecoverable error due to environment.
+    SPV_MSG_INTERNAL_ERROR, // Unrecoverable error due to SPIRV-Tools internals.
+    SPV_MSG_ERROR,          // Normal error due to user input.
+    SPV_MSG_WARNING,        // Warning information.
+    SPV_MSG_INFO,           // General information.
+    SPV_MSG_DEBUG,          // Debug information.
+}
+pub use spv_message_level_t::*;
+
+pub type spv_message_consumer = Option<
+    unsafe extern "C" fn(
+        level: spv_message_level_t,
+        source: *const i8,
+        position: *const spv_position_t,
+        message: *const i8,
+    ),
+>;
+
+// spv_text_to_binary_options_t
+pub const SPV_TEXT_TO_BINARY_OPTION_NONE: u32 = 0x1;
+pub const SPV_TEXT_TO_BINARY_OPTION_PRESERVE_NUMERIC_IDS: u32 = 0x2;
+
+// spv_binary_to_text_options_t
+pub const SPV_BINARY_TO_TEXT_OPTION_NONE: u32 = 0x1;
+pub const SPV_BINARY_TO_TEXT_OPTION_PRINT: u32 = 0x2;
+pub const SPV_BINARY_TO_TEXT_OPTION_COLOR: u32 = 0x4;
+pub const SPV_BINARY_TO_TEXT_OPTION_INDENT: u32 = 0x8;
+pub const SPV_BINARY_TO_TEXT_OPTION_SHOW_BYTE_OFFSET: u32 = 0x10;
+pub const SPV_BINARY_TO_TEXT_OPTION_NO_HEADER: u32 = 0x20;
+pub const SPV_BINARY_TO_TEXT_OPTION_FRIENDLY_NAMES: u32 = 0x40;
+pub const SPV_BINARY_TO_TEXT_OPTION_COMMENT: u32 = 0x80;
+
+/// Message of SPIRV-Tools. Positions of text sources are 1-based lines and columns, positions of binaries are word
+/// offsets of instructions, like `spirv_val::ValidationError::offset`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolsDiagnostic {
+    pub severity: Severity,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub offset: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for ToolsDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.severity {
+            Severity::Error => write!(f, "error: ")?,
+            Severity::Warning => write!(f, "warning: ")?,
+        }
+        if let (Some(line), Some(column)) = (self.line, self.column) {
+            write!(f, "{}:{}: ", line, column)?;
+        }
+        if let Some(offset) = self.offset {
+            write!(f, "word {}: ", offset)?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+impl ToolsDiagnostic {
+    /// Takes ownership of `diagnostic` and destroys it.
+    unsafe fn from_raw(diagnostic: spv_diagnostic) -> Option<Self> {
+        if diagnostic.is_null() {
+            return None;
+        }
+        let result = Self::from_position(
+            Severity::Error,
+            &(*diagnostic).position,
+            (*diagnostic).is_text_source,
+            (*diagnostic).error,
+        );
+        spvDiagnosticDestroy(diagnostic);
+        Some(result)
+    }
+
+    unsafe fn from_position(
+        severity: Severity,
+        position: &spv_position_t,
+        is_text_source: bool,
+        message: *const i8,
+    ) -> Self {
+        let message = if message.is_null() {
+            String::new()
+        } else {
+            CStr::from_ptr(message).to_string_lossy().trim_end().to_string()
+        };
+        Self {
+            severity,
+            line: is_text_source.then_some(position.line + 1),
+            column: is_text_source.then_some(position.column + 1),
+            offset: (!is_text_source).then_some(position.index),
+            message,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolsError {
+    pub result: spv_result_t,
+    pub diagnostics: Vec<ToolsDiagnostic>,
+}
+
+impl fmt::Display for ToolsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.diagnostics.is_empty() {
+            return write!(f, "SPIRV-Tools failed with {:?}", self.result);
+        }
+        for (i, diagnostic) in self.diagnostics.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", diagnostic)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ToolsError {}
+
+impl ToolsError {
+    unsafe fn new(result: spv_result_t, diagnostic: spv_diagnostic) -> Self {
+        Self {
+            result,
+            diagnostics: ToolsDiagnostic::from_raw(diagnostic).into_iter().collect(),
+        }
+    }
+}
+
+/// Context of SPIRV-Tools for a target environment.
+struct Context(spv_context);
+
+impl Context {
+    fn new(env: spv_target_env) -> Result<Self, ToolsError> {
+        let context = unsafe { spvContextCreate(env) };
+        if context.0.is_null() {
+            return Err(ToolsError {
+                result: spv_result_t::SPV_ERROR_INVALID_TABLE,
+                diagnostics: vec![],
+            });
+        }
+        Ok(Self(context))
+    }
+}
+
+impl Drop for Context {
+    fn drop(&mut self) {
+        unsafe { spvContextDestroy(self.0) };
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ValidatorOptions {
+    pub limits: Vec<(spv_validator_limit, u32)>,
+    pub relax_store_struct: bool,
+    pub relax_logical_pointer: bool,
+    pub before_hlsl_legalization: bool,
+    pub relax_block_layout: bool,
+    pub uniform_buffer_standard_layout: bool,
+    pub scalar_block_layout: bool,
+    pub workgroup_scalar_block_layout: bool,
+    pub skip_block_layout: bool,
+    pub allow_local_size_id: bool,
+}
+
+impl ValidatorOptions {
+    /// The caller destroys the options with `spvValidatorOptionsDestroy`.
+    unsafe fn create(&self) -> spv_validator_options {
+        let options = spvValidatorOptionsCreate();
+        for &(limit, value) in &self.limits {
+            spvValidatorOptionsSetUniversalLimit(options, limit, value);
+        }
+        spvValidatorOptionsSetRelaxStoreStruct(options, self.relax_store_struct);
+        spvValidatorOptionsSetRelaxLogicalPointer(options, self.relax_logical_pointer);
+        spvValidatorOptionsSetBeforeHlslLegalization(options, self.before_hlsl_legalization);
+        spvValidatorOptionsSetRelaxBlockLayout(options, self.relax_block_layout);
+        spvValidatorOptionsSetUniformBufferStandardLayout(options, self.uniform_buffer_standard_layout);
+        spvValidatorOptionsSetScalarBlockLayout(options, self.scalar_block_layout);
+        spvValidatorOptionsSetWorkgroupScalarBlockLayout(options, self.workgroup_scalar_block_layout);
+        spvValidatorOptionsSetSkipBlockLayout(options, self.skip_block_layout);
+        spvValidatorOptionsSetAllowLocalSizeId(options, self.allow_local_size_id);
+        options
+    }
+}
+
+/// Validates a module with the validator of SPIRV-Tools, the counterpart of `spirv_val::validate`.
+pub fn validate_spirv(words: &[u32], env: spv_target_env, options: &ValidatorOptions) -> Result<(), ToolsError> {
+    let context = Context::new(env)?;
+    let binary = spv_const_binary_t {
+        code: words.as_ptr(),
+        word_count: words.len(),
+    };
+    unsafe {
+        let options = options.create();
+        let mut diagnostic = ptr::null_mut();
+        let result = spvValidateWithOptions(context.0 .0, options, &binary, &mut diagnostic);
+        spvValidatorOptionsDestroy(options);
+        match result {
+            spv_result_t::SPV_SUCCESS => Ok(()),
+            result => Err(ToolsError::new(result, diagnostic)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OptimizerPass {
+    /// Passes legalizing the output of HLSL front-ends
+    Legalization,
+    /// Passes of `spirv-opt -O`
+    Performance,
+    /// Passes of `spirv-opt -Os`
+    Size,
+    /// A pass by its `spirv-opt` flag, like `--eliminate-dead-code-aggressive`
+    Flag(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct OptimizerOptions {
+    pub passes: Vec<OptimizerPass>,
+    /// Validates the module before optimizing it
+    pub run_validator: bool,
+    pub validator: ValidatorOptions,
+    pub max_id_bound: Option<u32>,
+    pub preserve_bindings: bool,
+    pub preserve_spec_constants: bool,
+}
+
+impl Default for OptimizerOptions {
+    fn default() -> Self {
+        Self {
+            passes: vec![OptimizerPass::Performance],
+            run_validator: true,
+            validator: ValidatorOptions::default(),
+            max_id_bound: None,
+            preserve_bindings: false,
+            preserve_spec_constants: false,
+        }
+    }
+}
+
+thread_local! {
+    /// The message consumer of the optimizer has no user data, messages are collected for the running thread.
+    static OPTIMIZER_MESSAGES: RefCell<Vec<ToolsDiagnostic>> = const { RefCell::new(vec![]) };
+}
+
+unsafe extern "C" fn consume_message(
+    level: spv_message_level_t,
+    _source: *const i8,
+    position: *const spv_position_t,
+    message: *const i8,
+) {
+    let severity = match level {
+        SPV_MSG_FATAL | SPV_MSG_INTERNAL_ERROR | SPV_MSG_ERROR => Severity::Error,
+        SPV_MSG_WARNING => Severity::Warning,
+        SPV_MSG_INFO | SPV_MSG_DEBUG => return,
+    };
+    let position = if position.is_null() {
+        spv_position_t {
+            line: 0,
+            column: 0,
+            index: 0,
+        }
+    } else {
+        std::ptr::read(position)
+    };
+    let diagnostic = ToolsDiagnostic::from_position(severity, &position, false, message);
+    OPTIMIZER_MESSAGES.with(|messages| messages.borrow_mut().push(diagnostic));
+}
+
+/// Optimizes a module with the optimizer of SPIRV-Tools, passes run in order.
+pub fn optimize_spirv(words: &[u32], env: spv_target_env, options: &OptimizerOptions) -> Result<Vec<u32>, ToolsError> {
+    let error = |result, diagnostics| ToolsError {
+        result,
+        diagnostics,
+    };
+    unsafe {
+        let optimizer = spvOptimizerCreate(env);
+        if optimizer.0.is_null() {
+            return Err(error(spv_result_t::SPV_ERROR_OUT_OF_MEMORY, vec![]));
+        }
+        spvOptimizerSetMessageConsumer(optimizer, Some(consume_message));
+        for pass in &options.passes {
+            match pass {
+                OptimizerPass::Legalization => spvOptimizerRegisterLegalizationPasses(optimizer),
+                OptimizerPass::Performance => spvOptimizerRegisterPerformancePasses(optimizer),
+                OptimizerPass::Size => spvOptimizerRegisterSizePasses(optimizer),
+                OptimizerPass::Flag(flag) => {
+                    let registered = CString::new(flag.as_str())
+                        .map(|flag| spvOptimizerRegisterPassFromFlag(optimizer, flag.as_ptr()))
+                        .unwrap_or(false);
+                    if !registered {
+                        spvOptimizerDestroy(optimizer);
+                        let diagnostic = ToolsDiagnostic {
+                            severity: Severity::Error,
+                            line: None,
+                            column: None,
+                            offset: None,
+                            message: format!("Unknown optimizer pass {}", flag),
+                        };
+                        return Err(error(spv_result_t::SPV_ERROR_INVALID_VALUE, vec![diagnostic]));
+                    }
+                }
+            }
+        }
+
+        let validator_options = options.validator.create();
+        let optimizer_options = spvOptimizerOptionsCreate();
+        spvOptimizerOptionsSetRunValidator(optimizer_options, options.run_validator);
+        spvOptimizerOptionsSetValidatorOptions(optimizer_options, validator_options);
+        if let Some(bound) = options.max_id_bound {
+            spvOptimizerOptionsSetMaxIdBound(optimizer_options, bound);
+        }
+        spvOptimizerOptionsSetPreserveBindings(optimizer_options, options.preserve_bindings);
+        spvOptimizerOptionsSetPreserveSpecConstants(optimizer_options, options.preserve_spec_constants);
+
+        OPTIMIZER_MESSAGES.with(|messages| messages.borrow_mut().clear());
+        let mut binary = ptr::null_mut();
+        let result = spvOptimizerRun(optimizer, words.as_ptr(), words.len(), &mut binary, optimizer_options);
+        let diagnostics = OPTIMIZER_MESSAGES.with(|messages| messages.take());
+
+        spvOptimizerOptionsDestroy(optimizer_options);
+        spvValidatorOptionsDestroy(validator_options);
+        spvOptimizerDestroy(optimizer);
+        match result {
+            spv_result_t::SPV_SUCCESS if !binary.is_null() => Ok(take_binary(binary)),
+            result => Err(error(result, diagnostics)),
+        }
+    }
+}
+
+/// Copies the words of `binary` and destroys it.
+unsafe fn take_binary(binary: spv_binary) -> Vec<u32> {
+    let words = std::slice::from_raw_parts((*binary).code, (*binary).word_count).to_vec();
+    spvBinaryDestroy(binary);
+    words
+}
+
+/// Assembles SPIR-V text with SPIRV-Tools, the counterpart of `spirv_as::assemble_words`.
+/// Ids are renumbered in order of appearance unless `preserve_numeric_ids` is set.
+pub fn assemble_spirv(text: &str, env: spv_target_env, preserve_numeric_ids: bool) -> Result<Vec<u32>, ToolsError> {
+    let context = Context::new(env)?;
+    let options = match preserve_numeric_ids {
+        true => SPV_TEXT_TO_BINARY_OPTION_PRESERVE_NUMERIC_IDS,
+        false => SPV_TEXT_TO_BINARY_OPTION_NONE,
+    };
+    unsafe {
+        let mut binary = ptr::null_mut();
+        let mut diagnostic = ptr::null_mut();
+        let result = spvTextToBinaryWithOptions(
+            context.0 .0,
+            text.as_ptr() as *const i8,
+            text.len(),
+            options,
+            &mut binary,
+            &mut diagnostic,
+        );
+        match result {
+            spv_result_t::SPV_SUCCESS => Ok(take_binary(binary)),
+            result => Err(ToolsError::new(result, diagnostic)),
+        }
+    }
+}
+
+/// Disassembles a module with SPIRV-Tools, the counterpart of `spirv_dis::disassemble`.
+pub fn disassemble_spirv(
+    words: &[u32],
+    env: spv_target_env,
+    options: &crate::spirv_dis::DisassembleOptions,
+) -> Result<String, ToolsError> {
+    let context = Context::new(env)?;
+    let mut flags = SPV_BINARY_TO_TEXT_OPTION_NONE;
+    if options.friendly_names {
+        flags |= SPV_BINARY_TO_TEXT_OPTION_FRIENDLY_NAMES;
+    }
+    if options.indent {
+        flags |= SPV_BINARY_TO_TEXT_OPTION_INDENT;
+    }
+    if !options.header {
+        flags |= SPV_BINARY_TO_TEXT_OPTION_NO_HEADER;
+    }
+    unsafe {
+        let mut text = ptr::null_mut();
+        let mut diagnostic = ptr::null_mut();
+        let result = spvBinaryToText(context.0 .0, words.as_ptr(), words.len(), flags, &mut text, &mut diagnostic);
+        match result {
+            spv_result_t::SPV_SUCCESS => {
+                let bytes = std::slice::from_raw_parts((*text).str as *const u8, (*text).length);
+                let string = String::from_utf8_lossy(bytes).into_owned();
+                spvTextDestroy(text);
+                Ok(string)
+            }
+            result => Err(ToolsError::new(result, diagnostic)),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    //use super::*;
-    //use std::ffi::{CStr, CString};
-    //use std::fs;
-    //use std::ptr;
+    use super::*;
+
+    #[test]
+    fn diagnostics() {
+        let log = "WARNING: 0:3: '#extension' : extension not supported: GL_foo\n\
+                   ERROR: 0:12: 'color' : undeclared identifier\n\
+                   ERROR: common/light.glsl:4:9: '' : syntax error, unexpected SEMICOLON\n\
+                   ERROR: C:/shaders/a.glsl:7: 'x' : redefinition\n\
+                   ERROR: Linking fragment stage: Missing entry point: Each stage requires one entry point\n\
+                   \n\
+                   ERROR: 3 compilation errors.  No code generated.\n";
+        let diagnostics = Diagnostics::parse(log, "atlas.frag");
+        assert_eq!(diagnostics.0.len(), 5);
+        assert!(diagnostics.has_errors());
+        assert_eq!(
+            diagnostics.0[0],
+            Diagnostic {
+                severity: Severity::Warning,
+                file: Some("atlas.frag".to_string()),
+                line: Some(3),
+                column: None,
+                message: "'#extension' : extension not supported: GL_foo".to_string(),
+            }
+        );
+        assert_eq!(
+            diagnostics.to_string(),
+            "atlas.frag:3: warning: '#extension' : extension not supported: GL_foo\n\
+             atlas.frag:12: error: 'color' : undeclared identifier\n\
+             common/light.glsl:4:9: error: '' : syntax error, unexpected SEMICOLON\n\
+             C:/shaders/a.glsl:7: error: 'x' : redefinition\n\
+             error: Linking fragment stage: Missing entry point: Each stage requires one entry point"
+        );
+
+        assert_eq!(ShaderStage::from_path("assets/shaders/particles.comp"), Some(ShaderStage::Compute));
+        assert_eq!(ShaderStage::from_path("assets/shaders/particles.comp.spv"), None);
+    }
 
     #[test]
+    #[ignore] // Needs glslang
     fn glslang() {
-        //unsafe {
-        //    assert_eq!(glslang_initialize_process(), 1);
-        //    let code = fs::read_to_string("assets/shaders/shader.vert").unwrap();
-        //    let input = glslang_input_t {
-        //        language: GLSLANG_SOURCE_GLSL,
-        //        stage: GLSLANG_STAGE_VERTEX,
-        //        client: GLSLANG_CLIENT_VULKAN,
-        //        client_version: GLSLANG_TARGET_VULKAN_1_0,
-        //        target_language: GLSLANG_TARGET_SPV,
-        //        target_language_version: GLSLANG_TARGET_SPV_1_3,
-        //        code: code.as_ptr() as *const i8,
-        //        default_version: 450,
-        //        default_profile: GLSLANG_NO_PROFILE,
-        //        force_default_version_and_profile: 0,
-        //        forward_compatible: 0,
-        //        messages: GLSLANG_MSG_DEFAULT_BIT,
-        //        resource: &glslang_resource_t::default(),
-        //    };
-        //    let shader = glslang_shader_create(&input);
-        //    assert!(!shader.is_null());
-
-        //    let res = glslang_shader_parse(shader, &input);
-        //    println!("glslang_shader_parse(): {} {:?}", res, *shader);
-
-        //    //setEntryPoint((*shader).shader, b"main\0".as_ptr() as *const i8);
-        //    //setSourceEntryPoint((*shader).shader, b"main\0".as_ptr() as *const i8);
-
-        //    let program = glslang_program_create();
-        //    glslang_program_add_shader(program, shader);
-        //    let res = glslang_program_link(program, 0);
-        //    println!("glslang_program_link(): {}", res);
-        //    let msg = glslang_program_get_info_log(program);
-        //    let msg = CStr::from_ptr(msg);
-        //    println!("glslang_program_get_info_log(): {}", msg.to_str().unwrap());
-        //    glslang_program_delete(program);
-
-        //    glslang_shader_delete(shader);
-        //}
+        let path = "assets/shaders/atlas.frag";
+        let source = std::fs::read_to_string(path).unwrap();
+        let options = CompileOptions {
+            file_name: path.to_string(),
+            ..Default::default()
+        };
+        let spirv = compile_glsl(&source, ShaderStage::Fragment, &options).unwrap();
+        crate::spirv_val::validate(&spirv).unwrap();
+
+        // Defines and includes
+        let source = "#version 450\n#include \"color.glsl\"\nlayout(location = 0) out vec4 o;\nvoid main() { o = COLOR * SCALE; }\n";
+        let include = |name: &str, includer: &str| {
+            assert_eq!((name, includer), ("color.glsl", "main.frag"));
+            Ok(("color.glsl".to_string(), "#define COLOR vec4(1.0)\n".to_string()))
+        };
+        let options = CompileOptions {
+            file_name: "main.frag".to_string(),
+            defines: vec![("SCALE".to_string(), "0.5".to_string())],
+            include: Some(&include),
+            ..Default::default()
+        };
+        let spirv = compile_glsl(source, ShaderStage::Fragment, &options).unwrap();
+        crate::spirv_val::validate(&spirv).unwrap();
+
+        let source = "#version 450\nvoid main() {\n    undeclared = 1;\n}\n";
+        let diagnostics = compile_glsl(source, ShaderStage::Compute, &options).unwrap_err();
+        assert_eq!(diagnostics.0[0].file.as_deref(), Some("main.frag"));
+        assert_eq!(diagnostics.0[0].line, Some(3));
     }
 
+    const FILL_COMP: &str = r#"
+               OpCapability Shader
+               OpMemoryModel Logical GLSL450
+               OpEntryPoint GLCompute %main "main" %id
+               OpExecutionMode %main LocalSize 64 1 1
+               OpDecorate %id BuiltIn GlobalInvocationId
+               OpDecorate %_runtimearr_uint ArrayStride 4
+               OpMemberDecorate %Buffer 0 Offset 0
+               OpDecorate %Buffer Block
+               OpDecorate %buffer DescriptorSet 0
+               OpDecorate %buffer Binding 0
+       %void = OpTypeVoid
+         %fn = OpTypeFunction %void
+       %uint = OpTypeInt 32 0
+        %int = OpTypeInt 32 1
+     %v3uint = OpTypeVector %uint 3
+%_ptr_Input_v3uint = OpTypePointer Input %v3uint
+%_runtimearr_uint = OpTypeRuntimeArray %uint
+     %Buffer = OpTypeStruct %_runtimearr_uint
+%_ptr_StorageBuffer_Buffer = OpTypePointer StorageBuffer %Buffer
+%_ptr_StorageBuffer_uint = OpTypePointer StorageBuffer %uint
+      %int_0 = OpConstant %int 0
+     %uint_2 = OpConstant %uint 2
+     %uint_3 = OpConstant %uint 3
+         %id = OpVariable %_ptr_Input_v3uint Input
+     %buffer = OpVariable %_ptr_StorageBuffer_Buffer StorageBuffer
+       %main = OpFunction %void None %fn
+      %entry = OpLabel
+     %id_vec = OpLoad %v3uint %id
+      %index = OpCompositeExtract %uint %id_vec 0
+      %value = OpIMul %uint %uint_2 %uint_3
+    %pointer = OpAccessChain %_ptr_StorageBuffer_uint %buffer %int_0 %index
+               OpStore %pointer %value
+               OpReturn
+               OpFunctionEnd
+"#;
+
     #[test]
+    fn tools_diagnostics() {
+        let position = spv_position_t {
+            line: 2,
+            column: 7,
+            index: 0,
+        };
+        let message = CString::new("Expected operand for OpStore\n").unwrap();
+        let text = unsafe { ToolsDiagnostic::from_position(Severity::Error, &position, true, message.as_ptr()) };
+        assert_eq!(text.to_string(), "error: 3:8: Expected operand for OpStore");
+
+        let position = spv_position_t {
+            line: 0,
+            column: 0,
+            index: 23,
+        };
+        let binary = unsafe { ToolsDiagnostic::from_position(Severity::Warning, &position, false, ptr::null()) };
+        assert_eq!(binary.offset, Some(23));
+        let error = ToolsError {
+            result: spv_result_t::SPV_ERROR_INVALID_ID,
+            diagnostics: vec![text, binary],
+        };
+        assert_eq!(error.to_string(), "error: 3:8: Expected operand for OpStore\nwarning: word 23: ");
+        let error = ToolsError {
+            result: spv_result_t::SPV_ERROR_INVALID_BINARY,
+            diagnostics: vec![],
+        };
+        assert_eq!(error.to_string(), "SPIRV-Tools failed with SPV_ERROR_INVALID_BINARY");
+    }
+
+    #[test]
+    #[ignore] // Needs SPIRV-Tools
     fn tools() {
-        //unsafe {
-        //    let version = CStr::from_ptr(spvSoftwareVersionString());
-        //    println!("spvSoftwareVersionString(): {}", version.to_str().unwrap());
-        //    let details = CStr::from_ptr(spvSoftwareVersionDetailsString());
-        //    println!("spvSoftwareVersionDetailsString(): {}", details.to_str().unwrap());
-        //    let target = CStr::from_ptr(spvTargetEnvDescription(spv_target_env::SPV_ENV_VULKAN_1_3));
-        //    println!("spvTargetEnvDescription(): {}", target.to_str().unwrap());
-        //    let mut env = SPV_ENV_MAX;
-        //    let env_str = CString::new("vulkan1.3").unwrap();
-        //    assert!(spvParseTargetEnv(env_str.as_ptr(), &mut env));
-        //    println!("spvParseTargetEnv(\"vulkan1.3\"): {:?}", env);
-
-        //    let context = spvContextCreate(env);
-        //    assert!(!context.0.is_null());
-        //    println!("spvContextCreate(): {:?}", context);
-
-        //    let options = spvValidatorOptionsCreate();
-        //    assert!(!options.0.is_null());
-        //    println!("spvValidatorOptionsCreate(): {:?}", options);
-
-        //    let bytes = std::fs::read("assets/shaders/shader.vert.spv").unwrap();
-        //    let word_count = bytes.len() / 4;
-        //    let binary = bytes.as_ptr() as *const u32;
-        //    let mut text = ptr::null_mut();
-        //    let result = spvBinaryToText(context.0, binary, word_count, 0, &mut text, ptr::null_mut());
-        //    let text = CStr::from_ptr((*text).str);
-        //    println!("svpBinaryToText(): {:?}, {}", result, text.to_str().unwrap());
-
-        //    spvValidatorOptionsDestroy(options);
-        //    spvContextDestroy(context);
-        //}
+        let words = crate::spirv_as::assemble_words(FILL_COMP).unwrap();
+        crate::spirv_val::validate(&words).unwrap();
+        validate_spirv(&words, SPV_ENV_VULKAN_1_0, &ValidatorOptions::default()).unwrap();
+
+        // Both assemblers agree on everything but the generator
+        let options = crate::spirv_dis::DisassembleOptions {
+            friendly_names: false,
+            indent: false,
+            header: false,
+        };
+        let text = disassemble_spirv(&words, SPV_ENV_VULKAN_1_0, &options).unwrap();
+        assert_eq!(crate::spirv_as::assemble_words(&text).unwrap()[3..], words[3..]);
+        let assembled = assemble_spirv(&text, SPV_ENV_VULKAN_1_0, true).unwrap();
+        assert_eq!(assembled[3..], words[3..]);
+
+        let optimized = optimize_spirv(&words, SPV_ENV_VULKAN_1_0, &OptimizerOptions::default()).unwrap();
+        crate::spirv_val::validate(&optimized).unwrap();
+        assert!(optimized.len() <= words.len());
+        let options = OptimizerOptions {
+            passes: vec![OptimizerPass::Flag("--not-a-pass".to_string())],
+            ..Default::default()
+        };
+        let error = optimize_spirv(&words, SPV_ENV_VULKAN_1_0, &options).unwrap_err();
+        assert_eq!(error.result, spv_result_t::SPV_ERROR_INVALID_VALUE);
+
+        // Both validators reject an OpFunctionEnd running past the end at the same offset
+        let mut truncated = words.clone();
+        *truncated.last_mut().unwrap() += 1 << 16;
+        let ours = crate::spirv_val::validate(&truncated).unwrap_err();
+        let theirs = validate_spirv(&truncated, SPV_ENV_VULKAN_1_0, &ValidatorOptions::default()).unwrap_err();
+        assert_eq!(theirs.diagnostics[0].offset, Some(ours.offset));
+
+        let error = assemble_spirv("%1 = OpTypeVoid\nOpBogus\n", SPV_ENV_VULKAN_1_0, false).unwrap_err();
+        assert_eq!(error.result, spv_result_t::SPV_ERROR_INVALID_TEXT);
+        assert_eq!(error.diagnostics[0].line, Some(2));
     }
 
     #[test]
@@ -0,0 +1,637 @@
+use crate::check;
+use crate::cstr;
+use crate::math::{Mat4, Vec3, Vec4};
+use crate::vk_sys::*;
+use crate::vk_util::{vk_allocate_memory_for_image, vk_create_framebuffer};
+
+use std::ptr;
+
+// Shadow Mapping:
+// 1. Render the shadow casters with a depth-only pipeline from the point of view of every light,
+//    one layer of a depth array image per directional cascade / spot light.
+// 2. In the lit shader transform the world position with the same light matrix and compare its
+//    depth against the stored one through a comparison sampler (sampler2DArrayShadow).
+// 3. PCF: average several comparisons around the texel to soften the shadow edges.
+//
+// All the light matrices produced here map depth to [0, 1] (near = 0, far = 1) which is what
+// VK_COMPARE_OP_LESS_OR_EQUAL in the depth pass and the comparison sampler expect.
+//
+// Cascaded Shadow Maps (directional lights):
+// The view frustum is split along the view direction into `cascade_count` slices, each one gets
+// its own orthographic projection that tightly fits a bounding sphere of the slice corners.
+
+pub const MAX_CASCADES: usize = 4;
+pub const SHADOW_MAP_FORMAT: VkFormat = VK_FORMAT_D32_SFLOAT;
+
+#[derive(Debug, Clone, Copy)]
+pub struct DirectionalLight {
+    pub direction: Vec3, // Direction the light travels to
+    pub color: Vec3,
+    pub intensity: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SpotLight {
+    pub position: Vec3,
+    pub direction: Vec3,
+    pub color: Vec3,
+    pub intensity: f32,
+    pub outer_angle: f32, // Full cone angle in radians
+    pub range: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ShadowSettings {
+    pub map_size: u32,
+    pub cascade_count: usize,
+    pub split_lambda: f32, // 0: uniform splits, 1: logarithmic splits
+    pub pcf_radius: u32,   // 0: single comparison, 1: 3x3 kernel, 2: 5x5 kernel...
+    pub depth_bias_constant: f32,
+    pub depth_bias_slope: f32,
+}
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        Self {
+            map_size: 2048,
+            cascade_count: MAX_CASCADES,
+            split_lambda: 0.95,
+            pcf_radius: 1,
+            depth_bias_constant: 1.25,
+            depth_bias_slope: 1.75,
+        }
+    }
+}
+
+/// View frustum of the camera the cascades are built for.
+/// `view` transforms world coordinates into camera coordinates (see `Mat4::look_at`).
+#[derive(Debug, Clone, Copy)]
+pub struct CameraFrustum {
+    pub view: Mat4,
+    pub fovy: f32,
+    pub aspect: f32,
+    pub near: f32,
+    pub far: f32,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Cascade {
+    pub split_near: f32,
+    pub split_far: f32,
+    pub view_proj: Mat4,
+}
+
+// Must match the ShadowData uniform block in assets/shaders/mesh_shadow.frag
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ShadowUniforms {
+    pub light_matrices: [Mat4; MAX_CASCADES], // column-major (transposed) for GLSL
+    pub cascade_splits: [f32; MAX_CASCADES],  // far distance of each cascade in view space
+    pub light_dir: [f32; 4],                  // xyz: direction, w: 0 directional / 1 spot
+    pub params: [f32; 4],                     // x: pcf radius, y: texel size, z: cascade count, w: unused
+}
+
+impl ShadowUniforms {
+    pub fn directional(light: &DirectionalLight, cascades: &[Cascade], settings: &ShadowSettings) -> Self {
+        assert!(cascades.len() <= MAX_CASCADES);
+        let mut uniforms = Self::default();
+        for (i, cascade) in cascades.iter().enumerate() {
+            uniforms.light_matrices[i] = cascade.view_proj.transpose();
+            uniforms.cascade_splits[i] = cascade.split_far;
+        }
+        let dir = light.direction.normalize();
+        uniforms.light_dir = [dir.x, dir.y, dir.z, 0.0];
+        uniforms.params = [settings.pcf_radius as f32, 1.0 / settings.map_size as f32, cascades.len() as f32, 0.0];
+        uniforms
+    }
+
+    pub fn spot(light: &SpotLight, settings: &ShadowSettings) -> Self {
+        let mut uniforms = Self::default();
+        uniforms.light_matrices[0] = spot_light_matrix(light).transpose();
+        uniforms.cascade_splits[0] = light.range;
+        let dir = light.direction.normalize();
+        uniforms.light_dir = [dir.x, dir.y, dir.z, 1.0];
+        uniforms.params = [settings.pcf_radius as f32, 1.0 / settings.map_size as f32, 1.0, 0.0];
+        uniforms
+    }
+}
+
+/// Remaps the [-1, 1] (near = +1) depth produced by `Mat4::ortho` to [0, 1] (near = 0).
+#[rustfmt::skip]
+const ORTHO_DEPTH_FIXUP: Mat4 = Mat4::new([
+    1.0, 0.0,  0.0, 0.0,
+    0.0, 1.0,  0.0, 0.0,
+    0.0, 0.0, -0.5, 0.5,
+    0.0, 0.0,  0.0, 1.0,
+]);
+
+/// Remaps the reversed [1, 0] depth produced by `Mat4::perspective` to [0, 1] (z' = w - z).
+#[rustfmt::skip]
+const PERSPECTIVE_DEPTH_FIXUP: Mat4 = Mat4::new([
+    1.0, 0.0,  0.0, 0.0,
+    0.0, 1.0,  0.0, 0.0,
+    0.0, 0.0, -1.0, 1.0,
+    0.0, 0.0,  0.0, 1.0,
+]);
+
+fn light_up(dir: Vec3) -> Vec3 {
+    if dir.y.abs() > 0.99 {
+        Vec3::new(0.0, 0.0, 1.0)
+    } else {
+        Vec3::new(0.0, 1.0, 0.0)
+    }
+}
+
+pub fn spot_light_matrix(light: &SpotLight) -> Mat4 {
+    let dir = light.direction.normalize();
+    let view = Mat4::look_at(light.position, light.position + dir, light_up(dir));
+    let near = (light.range * 0.01).max(0.05);
+    let proj = Mat4::perspective(light.outer_angle, 1.0, near, light.range);
+    PERSPECTIVE_DEPTH_FIXUP * proj * view
+}
+
+/// Distances (in view space) where each cascade ends, using the "practical split scheme":
+/// a blend between logarithmic and uniform splits controlled by `lambda`.
+pub fn cascade_splits(near: f32, far: f32, count: usize, lambda: f32) -> Vec<f32> {
+    assert!(0.0 < near && near < far);
+    assert!((1..=MAX_CASCADES).contains(&count));
+    (1..=count)
+        .map(|i| {
+            let p = i as f32 / count as f32;
+            let log = near * (far / near).powf(p);
+            let uniform = near + (far - near) * p;
+            lambda * log + (1.0 - lambda) * uniform
+        })
+        .collect()
+}
+
+/// World space corners of the slice of the camera frustum between the distances `d0` and `d1`.
+pub fn frustum_slice_corners(frustum: &CameraFrustum, d0: f32, d1: f32) -> [Vec3; 8] {
+    let inv_view = frustum.view.inverse();
+    let tan_half_fovy = (frustum.fovy * 0.5).tan();
+    let mut corners = [Vec3::default(); 8];
+    for (i, d) in [d0, d1].iter().enumerate() {
+        let h = d * tan_half_fovy;
+        let w = h * frustum.aspect;
+        for (j, (x, y)) in [(-w, -h), (w, -h), (w, h), (-w, h)].iter().enumerate() {
+            let p = inv_view * Vec4::new(*x, *y, -d, 1.0);
+            corners[i * 4 + j] = Vec3::new(p.x, p.y, p.z);
+        }
+    }
+    corners
+}
+
+pub fn directional_light_cascades(
+    light: &DirectionalLight,
+    frustum: &CameraFrustum,
+    settings: &ShadowSettings,
+) -> Vec<Cascade> {
+    let dir = light.direction.normalize();
+    let splits = cascade_splits(frustum.near, frustum.far, settings.cascade_count, settings.split_lambda);
+
+    let mut cascades = vec![];
+    let mut split_near = frustum.near;
+    for split_far in splits {
+        let corners = frustum_slice_corners(frustum, split_near, split_far);
+        let mut center = Vec3::default();
+        for c in &corners {
+            center += *c;
+        }
+        center = center * (1.0 / corners.len() as f32);
+
+        // Bounding sphere so that the projection does not change size when the camera rotates.
+        let radius = corners.iter().map(|c| (*c - center).len()).fold(0.0, f32::max);
+        let radius = (radius * 16.0).ceil() / 16.0;
+
+        let eye = center - dir * radius;
+        let view = Mat4::look_at(eye, center, light_up(dir));
+        let proj = Mat4::ortho(-radius, radius, -radius, radius, 0.0, -2.0 * radius);
+        let mut view_proj = ORTHO_DEPTH_FIXUP * proj * view;
+
+        // Snap the projection to whole texels to avoid shimmering edges when the camera moves.
+        let origin = view_proj * Vec4::new(0.0, 0.0, 0.0, 1.0);
+        let half_size = settings.map_size as f32 * 0.5;
+        let (x, y) = (origin.x * half_size, origin.y * half_size);
+        view_proj.0[3] += (x.round() - x) / half_size;
+        view_proj.0[7] += (y.round() - y) / half_size;
+
+        cascades.push(Cascade {
+            split_near,
+            split_far,
+            view_proj,
+        });
+        split_near = split_far;
+    }
+    cascades
+}
+
+/// Depth array image with one layer per cascade (or spot light) plus everything needed to render
+/// into it and to sample it with depth comparison.
+#[derive(Default)]
+pub struct ShadowMap {
+    pub device: VkDevice,
+    pub size: u32,
+    pub layers: u32,
+    pub image: VkImage,
+    pub memory: VkDeviceMemory,
+    pub view: VkImageView,             // VK_IMAGE_VIEW_TYPE_2D_ARRAY, for sampling
+    pub layer_views: Vec<VkImageView>, // One per layer, for rendering
+    pub framebuffers: Vec<VkFramebuffer>,
+    pub render_pass: VkRenderPass,
+    pub sampler: VkSampler,
+}
+
+impl ShadowMap {
+    pub fn new(physical_device: VkPhysicalDevice, device: VkDevice, size: u32, layers: u32) -> Self {
+        unsafe {
+            let mut image = VkImage::default();
+            check!(vkCreateImage(
+                device,
+                &VkImageCreateInfo {
+                    imageType: VK_IMAGE_TYPE_2D,
+                    format: SHADOW_MAP_FORMAT,
+                    extent: VkExtent3D {
+                        width: size,
+                        height: size,
+                        depth: 1,
+                    },
+                    mipLevels: 1,
+                    arrayLayers: layers,
+                    samples: VK_SAMPLE_COUNT_1_BIT.into(),
+                    tiling: VK_IMAGE_TILING_OPTIMAL,
+                    usage: (VK_IMAGE_USAGE_DEPTH_STENCIL_ATTACHMENT_BIT | VK_IMAGE_USAGE_SAMPLED_BIT).into(),
+                    sharingMode: VK_SHARING_MODE_EXCLUSIVE,
+                    initialLayout: VK_IMAGE_LAYOUT_UNDEFINED,
+                    ..VkImageCreateInfo::default()
+                },
+                ptr::null(),
+                &mut image,
+            ));
+            let memory =
+                vk_allocate_memory_for_image(physical_device, device, image, VK_MEMORY_PROPERTY_DEVICE_LOCAL_BIT);
+            check!(vkBindImageMemory(device, image, memory, 0));
+
+            let view = create_layer_view(device, image, VK_IMAGE_VIEW_TYPE_2D_ARRAY, 0, layers);
+            let layer_views =
+                (0..layers).map(|i| create_layer_view(device, image, VK_IMAGE_VIEW_TYPE_2D, i, 1)).collect::<Vec<_>>();
+
+            let render_pass = vk_create_shadow_render_pass(device);
+            let framebuffers =
+                layer_views.iter().map(|v| vk_create_framebuffer(device, render_pass, &[*v], size, size)).collect();
+
+            Self {
+                device,
+                size,
+                layers,
+                image,
+                memory,
+                view,
+                layer_views,
+                framebuffers,
+                render_pass,
+                sampler: vk_create_shadow_sampler(device),
+            }
+        }
+    }
+
+    /// Starts the depth-only render pass for the given layer, setting viewport, scissor and the
+    /// depth bias used to fight shadow acne.
+    pub fn begin(&self, cmd: VkCommandBuffer, layer: usize, settings: &ShadowSettings) {
+        unsafe {
+            vkCmdBeginRenderPass(
+                cmd,
+                &VkRenderPassBeginInfo {
+                    renderPass: self.render_pass,
+                    framebuffer: self.framebuffers[layer],
+                    renderArea: VkRect2D::new(0, 0, self.size, self.size),
+                    clearValueCount: 1,
+                    pClearValues: &VkClearDepthStencilValue::new(1.0, 0),
+                    ..VkRenderPassBeginInfo::default()
+                },
+                VK_SUBPASS_CONTENTS_INLINE,
+            );
+            let size = self.size as f32;
+            vkCmdSetViewport(cmd, 0, 1, &VkViewport::new(0.0, 0.0, size, size, 0.0, 1.0));
+            vkCmdSetScissor(cmd, 0, 1, &VkRect2D::new(0, 0, self.size, self.size));
+            vkCmdSetDepthBias(cmd, settings.depth_bias_constant, 0.0, settings.depth_bias_slope);
+        }
+    }
+
+    pub fn end(&self, cmd: VkCommandBuffer) {
+        unsafe { vkCmdEndRenderPass(cmd) };
+    }
+
+    pub fn descriptor_image_info(&self) -> VkDescriptorImageInfo {
+        VkDescriptorImageInfo {
+            sampler: self.sampler,
+            imageView: self.view,
+            imageLayout: VK_IMAGE_LAYOUT_DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+        }
+    }
+
+    pub fn destroy(&mut self) {
+        if self.device != VkDevice::default() {
+            unsafe {
+                vkDestroySampler(self.device, self.sampler, ptr::null());
+                self.framebuffers.iter().for_each(|fb| vkDestroyFramebuffer(self.device, *fb, ptr::null()));
+                vkDestroyRenderPass(self.device, self.render_pass, ptr::null());
+                self.layer_views.iter().for_each(|v| vkDestroyImageView(self.device, *v, ptr::null()));
+                vkDestroyImageView(self.device, self.view, ptr::null());
+                vkFreeMemory(self.device, self.memory, ptr::null());
+                vkDestroyImage(self.device, self.image, ptr::null());
+            }
+        }
+    }
+}
+
+fn create_layer_view(
+    device: VkDevice,
+    image: VkImage,
+    view_type: VkImageViewType,
+    base_layer: u32,
+    layer_count: u32,
+) -> VkImageView {
+    unsafe {
+        let mut view = VkImageView::default();
+        check!(vkCreateImageView(
+            device,
+            &VkImageViewCreateInfo {
+                image,
+                viewType: view_type,
+                format: SHADOW_MAP_FORMAT,
+                subresourceRange: VkImageSubresourceRange {
+                    aspectMask: VK_IMAGE_ASPECT_DEPTH_BIT.into(),
+                    levelCount: 1,
+                    baseArrayLayer: base_layer,
+                    layerCount: layer_count,
+                    ..VkImageSubresourceRange::default()
+                },
+                ..VkImageViewCreateInfo::default()
+            },
+            ptr::null(),
+            &mut view
+        ));
+        view
+    }
+}
+
+/// Depth-only render pass that leaves the attachment ready to be sampled by the lit shader.
+pub fn vk_create_shadow_render_pass(device: VkDevice) -> VkRenderPass {
+    unsafe {
+        let mut render_pass = VkRenderPass::default();
+        check!(vkCreateRenderPass(
+            device,
+            &VkRenderPassCreateInfo {
+                attachmentCount: 1,
+                pAttachments: &VkAttachmentDescription {
+                    flags: 0.into(),
+                    format: SHADOW_MAP_FORMAT,
+                    samples: VK_SAMPLE_COUNT_1_BIT.into(),
+                    loadOp: VK_ATTACHMENT_LOAD_OP_CLEAR,
+                    storeOp: VK_ATTACHMENT_STORE_OP_STORE,
+                    stencilLoadOp: VK_ATTACHMENT_LOAD_OP_DONT_CARE,
+                    stencilStoreOp: VK_ATTACHMENT_STORE_OP_DONT_CARE,
+                    initialLayout: VK_IMAGE_LAYOUT_UNDEFINED,
+                    finalLayout: VK_IMAGE_LAYOUT_DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+                },
+                subpassCount: 1,
+                pSubpasses: &VkSubpassDescription {
+                    pipelineBindPoint: VK_PIPELINE_BIND_POINT_GRAPHICS,
+                    colorAttachmentCount: 0,
+                    pDepthStencilAttachment: &VkAttachmentReference {
+                        attachment: 0,
+                        layout: VK_IMAGE_LAYOUT_DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+                    },
+                    ..VkSubpassDescription::default()
+                },
+                dependencyCount: 2,
+                pDependencies: [
+                    VkSubpassDependency {
+                        srcSubpass: VK_SUBPASS_EXTERNAL,
+                        dstSubpass: 0,
+                        srcStageMask: VK_PIPELINE_STAGE_FRAGMENT_SHADER_BIT.into(),
+                        dstStageMask: VK_PIPELINE_STAGE_EARLY_FRAGMENT_TESTS_BIT.into(),
+                        srcAccessMask: VK_ACCESS_SHADER_READ_BIT.into(),
+                        dstAccessMask: VK_ACCESS_DEPTH_STENCIL_ATTACHMENT_WRITE_BIT.into(),
+                        dependencyFlags: VK_DEPENDENCY_BY_REGION_BIT.into(),
+                    },
+                    VkSubpassDependency {
+                        srcSubpass: 0,
+                        dstSubpass: VK_SUBPASS_EXTERNAL,
+                        srcStageMask: VK_PIPELINE_STAGE_LATE_FRAGMENT_TESTS_BIT.into(),
+                        dstStageMask: VK_PIPELINE_STAGE_FRAGMENT_SHADER_BIT.into(),
+                        srcAccessMask: VK_ACCESS_DEPTH_STENCIL_ATTACHMENT_WRITE_BIT.into(),
+                        dstAccessMask: VK_ACCESS_SHADER_READ_BIT.into(),
+                        dependencyFlags: VK_DEPENDENCY_BY_REGION_BIT.into(),
+                    },
+                ]
+                .as_ptr(),
+                ..VkRenderPassCreateInfo::default()
+            },
+            ptr::null(),
+            &mut render_pass
+        ));
+        render_pass
+    }
+}
+
+/// Comparison sampler for `sampler2DArrayShadow`, linear filtering gives 2x2 hardware PCF for free.
+/// Everything outside of the shadow map is lit (border depth 1.0).
+pub fn vk_create_shadow_sampler(device: VkDevice) -> VkSampler {
+    unsafe {
+        let mut sampler = VkSampler::default();
+        check!(vkCreateSampler(
+            device,
+            &VkSamplerCreateInfo {
+                magFilter: VK_FILTER_LINEAR,
+                minFilter: VK_FILTER_LINEAR,
+                mipmapMode: VK_SAMPLER_MIPMAP_MODE_NEAREST,
+                addressModeU: VK_SAMPLER_ADDRESS_MODE_CLAMP_TO_BORDER,
+                addressModeV: VK_SAMPLER_ADDRESS_MODE_CLAMP_TO_BORDER,
+                addressModeW: VK_SAMPLER_ADDRESS_MODE_CLAMP_TO_BORDER,
+                compareEnable: VK_TRUE,
+                compareOp: VK_COMPARE_OP_LESS_OR_EQUAL,
+                maxLod: 1.0,
+                borderColor: VK_BORDER_COLOR_FLOAT_OPAQUE_WHITE,
+                ..VkSamplerCreateInfo::default()
+            },
+            ptr::null(),
+            &mut sampler
+        ));
+        sampler
+    }
+}
+
+/// Depth-only pipeline for the shadow casters. Only the vertex positions (location 0, vec3) are
+/// consumed, the light matrix and model matrix are expected as push constants.
+pub fn vk_create_shadow_pipeline(
+    device: VkDevice,
+    layout: VkPipelineLayout,
+    render_pass: VkRenderPass,
+    vertex_shader: VkShaderModule,
+    vertex_stride: u32,
+) -> VkPipeline {
+    unsafe {
+        let mut pipeline = VkPipeline::default();
+        check!(vkCreateGraphicsPipelines(
+            device,
+            VkPipelineCache::default(),
+            1,
+            &VkGraphicsPipelineCreateInfo {
+                stageCount: 1,
+                pStages: &VkPipelineShaderStageCreateInfo {
+                    stage: VK_SHADER_STAGE_VERTEX_BIT.into(),
+                    module: vertex_shader,
+                    pName: cstr!("main"),
+                    ..VkPipelineShaderStageCreateInfo::default()
+                },
+                pVertexInputState: &VkPipelineVertexInputStateCreateInfo {
+                    vertexBindingDescriptionCount: 1,
+                    pVertexBindingDescriptions: &VkVertexInputBindingDescription {
+                        binding: 0,
+                        stride: vertex_stride,
+                        inputRate: VK_VERTEX_INPUT_RATE_VERTEX,
+                    },
+                    vertexAttributeDescriptionCount: 1,
+                    pVertexAttributeDescriptions: &VkVertexInputAttributeDescription {
+                        binding: 0,
+                        location: 0,
+                        format: VK_FORMAT_R32G32B32_SFLOAT,
+                        offset: 0,
+                    },
+                    ..VkPipelineVertexInputStateCreateInfo::default()
+                },
+                pInputAssemblyState: &VkPipelineInputAssemblyStateCreateInfo {
+                    topology: VK_PRIMITIVE_TOPOLOGY_TRIANGLE_LIST,
+                    ..VkPipelineInputAssemblyStateCreateInfo::default()
+                },
+                pViewportState: &VkPipelineViewportStateCreateInfo {
+                    viewportCount: 1,
+                    scissorCount: 1,
+                    ..VkPipelineViewportStateCreateInfo::default()
+                },
+                pRasterizationState: &VkPipelineRasterizationStateCreateInfo {
+                    polygonMode: VK_POLYGON_MODE_FILL,
+                    // No culling so that single sided geometry also casts shadows
+                    cullMode: VK_CULL_MODE_NONE.into(),
+                    frontFace: VK_FRONT_FACE_COUNTER_CLOCKWISE,
+                    depthClampEnable: VK_FALSE,
+                    depthBiasEnable: VK_TRUE,
+                    lineWidth: 1.0,
+                    ..VkPipelineRasterizationStateCreateInfo::default()
+                },
+                pMultisampleState: &VkPipelineMultisampleStateCreateInfo {
+                    rasterizationSamples: VK_SAMPLE_COUNT_1_BIT.into(),
+                    ..VkPipelineMultisampleStateCreateInfo::default()
+                },
+                pDepthStencilState: &VkPipelineDepthStencilStateCreateInfo {
+                    depthTestEnable: VK_TRUE,
+                    depthWriteEnable: VK_TRUE,
+                    depthCompareOp: VK_COMPARE_OP_LESS_OR_EQUAL,
+                    ..VkPipelineDepthStencilStateCreateInfo::default()
+                },
+                pColorBlendState: &VkPipelineColorBlendStateCreateInfo {
+                    attachmentCount: 0,
+                    ..VkPipelineColorBlendStateCreateInfo::default()
+                },
+                pDynamicState: &VkPipelineDynamicStateCreateInfo {
+                    dynamicStateCount: 3,
+                    pDynamicStates: [VK_DYNAMIC_STATE_VIEWPORT, VK_DYNAMIC_STATE_SCISSOR, VK_DYNAMIC_STATE_DEPTH_BIAS]
+                        .as_ptr(),
+                    ..VkPipelineDynamicStateCreateInfo::default()
+                },
+                layout,
+                renderPass: render_pass,
+                subpass: 0,
+                basePipelineIndex: -1,
+                ..VkGraphicsPipelineCreateInfo::default()
+            },
+            ptr::null(),
+            &mut pipeline,
+        ));
+        pipeline
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn project(m: &Mat4, p: Vec3) -> Vec3 {
+        let v = *m * Vec4::new(p.x, p.y, p.z, 1.0);
+        Vec3::new(v.x / v.w, v.y / v.w, v.z / v.w)
+    }
+
+    fn camera() -> CameraFrustum {
+        CameraFrustum {
+            view: Mat4::look_at((0.0, 2.0, 5.0), (0.0, 0.0, 0.0), (0.0, 1.0, 0.0)),
+            fovy: std::f32::consts::FRAC_PI_3,
+            aspect: 16.0 / 9.0,
+            near: 0.1,
+            far: 100.0,
+        }
+    }
+
+    #[test]
+    fn cascade_splits_are_increasing_and_end_at_far() {
+        let splits = cascade_splits(0.1, 100.0, 4, 0.95);
+        assert_eq!(splits.len(), 4);
+        assert!(splits.windows(2).all(|w| w[0] < w[1]));
+        assert!((splits[3] - 100.0).abs() < 1e-3);
+
+        let uniform = cascade_splits(1.0, 5.0, 4, 0.0);
+        assert_eq!(uniform, vec![2.0, 3.0, 4.0, 5.0]);
+    }
+
+    #[test]
+    fn cascades_contain_their_frustum_slice() {
+        let light = DirectionalLight {
+            direction: Vec3::new(-0.3, -1.0, -0.2),
+            color: Vec3::new(1.0, 1.0, 1.0),
+            intensity: 1.0,
+        };
+        let frustum = camera();
+        let cascades = directional_light_cascades(&light, &frustum, &ShadowSettings::default());
+        assert_eq!(cascades.len(), MAX_CASCADES);
+        for cascade in &cascades {
+            for corner in frustum_slice_corners(&frustum, cascade.split_near, cascade.split_far) {
+                let p = project(&cascade.view_proj, corner);
+                assert!(p.x.abs() <= 1.01 && p.y.abs() <= 1.01, "{:?}", p);
+                assert!((-0.01..=1.01).contains(&p.z), "{:?}", p);
+            }
+        }
+    }
+
+    #[test]
+    fn directional_depth_increases_along_the_light() {
+        let light = DirectionalLight {
+            direction: Vec3::new(0.0, -1.0, 0.0),
+            color: Vec3::new(1.0, 1.0, 1.0),
+            intensity: 1.0,
+        };
+        let cascades = directional_light_cascades(&light, &camera(), &ShadowSettings::default());
+        let m = &cascades[0].view_proj;
+        let top = project(m, Vec3::new(0.0, 1.0, 3.0));
+        let bottom = project(m, Vec3::new(0.0, 0.0, 3.0));
+        assert!(top.z < bottom.z);
+    }
+
+    #[test]
+    fn spot_light_depth_range() {
+        let light = SpotLight {
+            position: Vec3::new(0.0, 5.0, 0.0),
+            direction: Vec3::new(0.0, -1.0, 0.0),
+            color: Vec3::new(1.0, 1.0, 1.0),
+            intensity: 1.0,
+            outer_angle: std::f32::consts::FRAC_PI_2,
+            range: 10.0,
+        };
+        let m = spot_light_matrix(&light);
+        let near = project(&m, Vec3::new(0.0, 5.0 - 0.1, 0.0));
+        let mid = project(&m, Vec3::new(0.0, 0.0, 0.0));
+        let far = project(&m, Vec3::new(0.0, 5.0 - 10.0, 0.0));
+        assert!(near.z.abs() < 1e-3, "{:?}", near);
+        assert!(mid.z > 0.0 && mid.z < 1.0);
+        assert!((far.z - 1.0).abs() < 1e-3, "{:?}", far);
+        assert!(mid.x.abs() < 1e-5 && mid.y.abs() < 1e-5);
+    }
+}
@@ -1,8 +1,10 @@
 #![allow(non_camel_case_types)]
 
 // TODO: Bitflag enums
-use crate::parsing::read_u32_le;
+use crate::spirv_grammar::EnumKind;
+use crate::spirv_val::{self, ValidationError, ValidationErrorKind};
 
+use std::collections::HashMap;
 use std::fmt;
 
 macro_rules! def_enum {
@@ -16,11 +18,29 @@ macro_rules! def_enum {
         pub enum $enum_name {
             $($variant = $value,)*
         }
-        impl From<u32> for $enum_name {
-            fn from(flag: u32) -> Self {
-                match flag {
-                    $($value => $enum_name::$variant,)*
-                    n => panic!("Invalid flag: {}", n),
+        impl TryFrom<u32> for $enum_name {
+            type Error = u32;
+
+            /// Fails with the value if it isn't one of the variants.
+            fn try_from(value: u32) -> Result<Self, u32> {
+                match value {
+                    $($value => Ok($enum_name::$variant),)*
+                    n => Err(n),
+                }
+            }
+        }
+        impl $enum_name {
+            /// Name of a value as written in the SPIR-V specification.
+            pub fn name(value: u32) -> Option<&'static str> {
+                match value {
+                    $($value => Some(stringify!($variant)),)*
+                    _ => None,
+                }
+            }
+            pub fn from_name(name: &str) -> Option<u32> {
+                match name {
+                    $(stringify!($variant) => Some($value),)*
+                    _ => None,
                 }
             }
         }
@@ -183,86 +203,552 @@ impl ShaderModule {
     }
 }
 
-impl fmt::Debug for ShaderModule {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        writeln!(f, "ShaderModule {{")?;
-        writeln!(f, "    magic: 0x{:08x}", self.magic)?;
-        writeln!(f, "    version: 0x{:08x}", self.version)?;
-        writeln!(f, "    generator: 0x{:08x}", self.generator)?;
-        writeln!(f, "    bound: {}", self.bound)?;
-        writeln!(f, "    instructions: {:#?}", self.instructions)?;
-        writeln!(f, "}}")
+// Reflection:
+// Describes the resources a module expects from the pipeline: descriptor bindings, push constants,
+// specialization constants and entry points. Block layouts come from the explicit Offset,
+// ArrayStride and MatrixStride decorations, which are mandatory for Vulkan shaders.
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DescriptorType {
+    Sampler,
+    CombinedImageSampler,
+    SampledImage,
+    StorageImage,
+    UniformTexelBuffer,
+    StorageTexelBuffer,
+    UniformBuffer,
+    StorageBuffer,
+    InputAttachment,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct BlockMember {
+    pub name: String,
+    pub offset: u32,
+    pub size: u32,                 // 0 for a runtime array
+    pub array_stride: u32,         // 0 when the member is not an array
+    pub members: Vec<BlockMember>, // Only for structs
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct BlockLayout {
+    pub name: String,
+    pub size: u32, // Without the trailing runtime array, if any
+    pub members: Vec<BlockMember>,
+}
+
+impl BlockLayout {
+    /// Offset and size of the bytes actually covered by the members, as needed by push constant ranges.
+    pub fn range(&self) -> (u32, u32) {
+        let start = self.members.iter().map(|m| m.offset).min().unwrap_or(0);
+        (start, self.size - start)
     }
 }
 
-impl TryFrom<&[u8]> for ShaderModule {
-    type Error = String;
+#[derive(Debug, Clone, PartialEq)]
+pub struct DescriptorBinding {
+    pub set: u32,
+    pub binding: u32,
+    pub descriptor_type: DescriptorType,
+    pub count: u32, // 0 for runtime sized arrays
+    pub name: String,
+    pub block: Option<BlockLayout>, // Layout of uniform and storage buffers
+}
 
-    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
-        if bytes.len() % 4 != 0 {
-            return Err("Length should be an even multiple of 4.".to_string());
-        }
-        let mut r = std::io::Cursor::new(bytes);
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpecConstant {
+    pub id: u32,
+    pub name: String,
+    pub default: Vec<u32>, // Literal words, booleans are 0 or 1
+}
 
-        // Magic number 0x07230203
-        let magic = read_u32_le(&mut r).map_err(|_| "IO Error")?;
-        //println!("Magic Number: 0x{:08x}", magic);
-        if magic != 0x07230203 {
-            return Err("Magic number should be 0x07230203.".to_string());
-        }
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntryPoint {
+    pub name: String,
+    pub execution_model: ExecutionModel,
+    pub function: u32,
+}
 
-        // Version number
-        let version = read_u32_le(&mut r).map_err(|_| "IO Error")?;
-        //println!("Version: 0x{:08x}", version);
-        if !(0x0001_0000..=0x0001_0600).contains(&version) {
-            // 1.0 <= version <= 1.6
-            return Err(format!("Unexpected version number 0x{:08x}.", version));
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Reflection {
+    pub entry_points: Vec<EntryPoint>,
+    pub descriptor_bindings: Vec<DescriptorBinding>, // Sorted by set and binding
+    pub push_constants: Option<BlockLayout>,
+    pub spec_constants: Vec<SpecConstant>,
+}
+
+type Decorations<'a> = Vec<(Decoration, &'a [u32])>;
+
+struct ReflectionContext<'a> {
+    defs: HashMap<u32, &'a Instruction>,
+    names: HashMap<u32, &'a str>,
+    member_names: HashMap<(u32, u32), &'a str>,
+    decorations: HashMap<u32, Decorations<'a>>,
+    member_decorations: HashMap<(u32, u32), Decorations<'a>>,
+}
+
+impl<'a> ReflectionContext<'a> {
+    fn new(module: &'a ShaderModule) -> Self {
+        let mut ctx = Self {
+            defs: HashMap::new(),
+            names: HashMap::new(),
+            member_names: HashMap::new(),
+            decorations: HashMap::new(),
+            member_decorations: HashMap::new(),
+        };
+        for inst in &module.instructions {
+            match inst {
+                Instruction::OpName {
+                    target,
+                    name,
+                    ..
+                } => {
+                    ctx.names.insert(*target, name);
+                }
+                Instruction::OpMemberName {
+                    ttype,
+                    member,
+                    name,
+                    ..
+                } => {
+                    ctx.member_names.insert((*ttype, *member), name);
+                }
+                Instruction::OpDecorate {
+                    target,
+                    decoration,
+                    extra,
+                    ..
+                } => ctx.decorations.entry(*target).or_default().push((*decoration, extra)),
+                Instruction::OpMemberDecorate {
+                    structure_type,
+                    member,
+                    decoration,
+                    extra,
+                    ..
+                } => ctx.member_decorations.entry((*structure_type, *member)).or_default().push((*decoration, extra)),
+                Instruction::OpTypeBool {
+                    result,
+                    ..
+                }
+                | Instruction::OpTypeInt {
+                    result,
+                    ..
+                }
+                | Instruction::OpTypeFloat {
+                    result,
+                    ..
+                }
+                | Instruction::OpTypeVector {
+                    result,
+                    ..
+                }
+                | Instruction::OpTypeMatrix {
+                    result,
+                    ..
+                }
+                | Instruction::OpTypeImage {
+                    result,
+                    ..
+                }
+                | Instruction::OpTypeSampler {
+                    result,
+                    ..
+                }
+                | Instruction::OpTypeSampledImage {
+                    result,
+                    ..
+                }
+                | Instruction::OpTypeArray {
+                    result,
+                    ..
+                }
+                | Instruction::OpTypeRuntimeArray {
+                    result,
+                    ..
+                }
+                | Instruction::OpTypeStruct {
+                    result,
+                    ..
+                }
+                | Instruction::OpTypePointer {
+                    result,
+                    ..
+                }
+                | Instruction::OpConstant {
+                    result,
+                    ..
+                }
+                | Instruction::OpSpecConstant {
+                    result,
+                    ..
+                } => {
+                    ctx.defs.insert(*result, inst);
+                }
+                _ => {}
+            }
         }
+        ctx
+    }
+
+    fn def(&self, id: u32) -> Result<&'a Instruction, String> {
+        self.defs.get(&id).copied().ok_or_else(|| format!("Unknown type or constant %{}", id))
+    }
 
-        let generator = read_u32_le(&mut r).map_err(|_| "IO Error")?;
-        //println!("Generator Magic: 0x{:08x}", generator);
-        // assert_eq!(generator_magic, 0x000d000a);
+    fn name(&self, id: u32) -> String {
+        self.names.get(&id).map(|s| s.to_string()).unwrap_or_default()
+    }
 
-        let bound = read_u32_le(&mut r).map_err(|_| "IO Error")?;
-        //println!("Bound: {}", bound); // All "ids" in the module should be smaller than bound
-        //assert_eq!(bound, 0);
+    fn decoration(&self, id: u32, decoration: Decoration) -> Option<&'a [u32]> {
+        self.decorations.get(&id)?.iter().find(|(d, _)| *d == decoration).map(|(_, extra)| *extra)
+    }
 
-        let reserved = read_u32_le(&mut r).map_err(|_| "IO Error")?;
-        if reserved != 0 {
-            return Err("Reserved should be 0.".to_string());
+    fn member_decoration(&self, ttype: u32, member: u32, decoration: Decoration) -> Option<u32> {
+        let decorations = self.member_decorations.get(&(ttype, member))?;
+        decorations.iter().find(|(d, _)| *d == decoration).and_then(|(_, extra)| extra.first().copied())
+    }
+
+    fn constant_u32(&self, id: u32) -> Result<u32, String> {
+        match self.def(id)? {
+            Instruction::OpConstant {
+                value,
+                ..
+            }
+            | Instruction::OpSpecConstant {
+                value,
+                ..
+            } => value.first().copied().ok_or_else(|| format!("Empty constant %{}", id)),
+            _ => Err(format!("%{} is not a constant", id)),
+        }
+    }
+
+    /// Strips the arrays around a type, returning the element type and the total element count
+    /// (0 if any of the arrays is runtime sized).
+    fn array_elements(&self, mut ty: u32) -> Result<(u32, u32), String> {
+        let mut count: u32 = 1;
+        loop {
+            match self.def(ty)? {
+                Instruction::OpTypeArray {
+                    element_type,
+                    length,
+                    ..
+                } => {
+                    count = count
+                        .checked_mul(self.constant_u32(*length)?)
+                        .ok_or_else(|| format!("Element count of %{} overflows", ty))?;
+                    ty = *element_type;
+                }
+                Instruction::OpTypeRuntimeArray {
+                    element_type,
+                    ..
+                } => {
+                    count = 0;
+                    ty = *element_type;
+                }
+                _ => return Ok((ty, count)),
+            }
         }
+    }
 
-        let instructions_size = bytes.len() - 20;
-        let instruction_count = instructions_size / 4;
-        let mut instructions = Vec::with_capacity(instruction_count);
-        while r.position() < (bytes.len() as u64) {
-            let mut inst_words = [0; 32];
+    /// Size in bytes of a type inside an explicitly laid out block.
+    fn size_of(&self, ty: u32, matrix_stride: Option<u32>) -> Result<u32, String> {
+        let mul = |a: u32, b: u32| a.checked_mul(b).ok_or_else(|| format!("Size of %{} overflows", ty));
+        Ok(match self.def(ty)? {
+            Instruction::OpTypeBool {
+                ..
+            } => 4,
+            Instruction::OpTypeInt {
+                width,
+                ..
+            }
+            | Instruction::OpTypeFloat {
+                width,
+                ..
+            } => width / 8,
+            Instruction::OpTypeVector {
+                component_type,
+                component_count,
+                ..
+            } => mul(self.size_of(*component_type, None)?, *component_count)?,
+            Instruction::OpTypeMatrix {
+                column_type,
+                column_count,
+                ..
+            } => match matrix_stride {
+                Some(stride) => mul(stride, *column_count)?,
+                None => mul(self.size_of(*column_type, None)?, *column_count)?,
+            },
+            Instruction::OpTypeArray {
+                element_type,
+                length,
+                ..
+            } => {
+                let count = self.constant_u32(*length)?;
+                match self.decoration(ty, Decoration::ArrayStride) {
+                    Some(stride) => mul(stride[0], count)?,
+                    None => mul(self.size_of(*element_type, matrix_stride)?, count)?,
+                }
+            }
+            Instruction::OpTypeRuntimeArray {
+                ..
+            } => 0,
+            Instruction::OpTypeStruct {
+                ..
+            } => self.block(ty)?.size,
+            _ => return Err(format!("%{} has no size", ty)),
+        })
+    }
+
+    fn block(&self, ty: u32) -> Result<BlockLayout, String> {
+        let member_types = match self.def(ty)? {
+            Instruction::OpTypeStruct {
+                member_types,
+                ..
+            } => member_types,
+            _ => return Err(format!("%{} is not a struct", ty)),
+        };
+        let mut block = BlockLayout {
+            name: self.name(ty),
+            ..Default::default()
+        };
+        for (i, &member_ty) in member_types.iter().enumerate() {
+            let i = i as u32;
+            let offset = self
+                .member_decoration(ty, i, Decoration::Offset)
+                .ok_or_else(|| format!("Member {} of %{} has no Offset", i, ty))?;
+            let matrix_stride = self.member_decoration(ty, i, Decoration::MatrixStride);
+            let size = self.size_of(member_ty, matrix_stride)?;
+            let array_stride = match self.def(member_ty)? {
+                Instruction::OpTypeArray {
+                    ..
+                }
+                | Instruction::OpTypeRuntimeArray {
+                    ..
+                } => self.decoration(member_ty, Decoration::ArrayStride).map_or(0, |s| s[0]),
+                _ => 0,
+            };
+            let (element_ty, _) = self.array_elements(member_ty)?;
+            let members = match self.def(element_ty)? {
+                Instruction::OpTypeStruct {
+                    ..
+                } => self.block(element_ty)?.members,
+                _ => vec![],
+            };
+            let end = offset.checked_add(size).ok_or_else(|| format!("Member {} of %{} overflows", i, ty))?;
+            block.size = block.size.max(end);
+            block.members.push(BlockMember {
+                name: self.member_names.get(&(ty, i)).map(|s| s.to_string()).unwrap_or_default(),
+                offset,
+                size,
+                array_stride,
+                members,
+            });
+        }
+        Ok(block)
+    }
 
-            inst_words[0] = read_u32_le(&mut r).map_err(|_| "IO Error")?;
-            let word_count = ((inst_words[0] >> 16) & 0xffff) as usize;
+    fn descriptor_type(&self, ty: u32, storage_class: StorageClass) -> Result<DescriptorType, String> {
+        Ok(match (storage_class, self.def(ty)?) {
+            (StorageClass::StorageBuffer, _) => DescriptorType::StorageBuffer,
+            (StorageClass::Uniform, _) if self.decoration(ty, Decoration::BufferBlock).is_some() => {
+                DescriptorType::StorageBuffer
+            }
+            (StorageClass::Uniform, _) => DescriptorType::UniformBuffer,
+            (
+                _,
+                Instruction::OpTypeSampler {
+                    ..
+                },
+            ) => DescriptorType::Sampler,
+            (
+                _,
+                Instruction::OpTypeSampledImage {
+                    image_type,
+                    ..
+                },
+            ) => match self.def(*image_type)? {
+                Instruction::OpTypeImage {
+                    dim: Dim::Buffer,
+                    ..
+                } => DescriptorType::UniformTexelBuffer,
+                _ => DescriptorType::CombinedImageSampler,
+            },
+            (
+                _,
+                Instruction::OpTypeImage {
+                    dim,
+                    sampled,
+                    ..
+                },
+            ) => match (dim, sampled) {
+                (Dim::SubpassData, _) => DescriptorType::InputAttachment,
+                (Dim::Buffer, 2) => DescriptorType::StorageTexelBuffer,
+                (Dim::Buffer, _) => DescriptorType::UniformTexelBuffer,
+                (_, 2) => DescriptorType::StorageImage,
+                _ => DescriptorType::SampledImage,
+            },
+            _ => return Err(format!("%{} is not a descriptor type", ty)),
+        })
+    }
+}
 
-            let mut remaining = word_count - 1;
-            let mut i = 1;
-            while remaining > 0 {
-                inst_words[i] = read_u32_le(&mut r).map_err(|_| "IO Error")?;
-                remaining -= 1;
-                i += 1;
+impl ShaderModule {
+    pub fn reflect(&self) -> Result<Reflection, String> {
+        let ctx = ReflectionContext::new(self);
+        let mut reflection = Reflection::default();
+        for inst in &self.instructions {
+            match inst {
+                Instruction::OpEntryPoint {
+                    execution_model,
+                    entry_point,
+                    name,
+                    ..
+                } => reflection.entry_points.push(EntryPoint {
+                    name: name.clone(),
+                    execution_model: *execution_model,
+                    function: *entry_point,
+                }),
+                Instruction::OpVariable {
+                    result_type,
+                    result,
+                    storage_class,
+                    ..
+                } => {
+                    let pointee = match ctx.def(*result_type)? {
+                        Instruction::OpTypePointer {
+                            ttype,
+                            ..
+                        } => *ttype,
+                        _ => return Err(format!("Variable %{} is not a pointer", result)),
+                    };
+                    match storage_class {
+                        StorageClass::PushConstant => {
+                            if reflection.push_constants.is_some() {
+                                return Err(String::from("Only one push constant block is allowed"));
+                            }
+                            let mut block = ctx.block(pointee)?;
+                            if block.name.is_empty() {
+                                block.name = ctx.name(*result);
+                            }
+                            reflection.push_constants = Some(block);
+                        }
+                        StorageClass::UniformConstant | StorageClass::Uniform | StorageClass::StorageBuffer => {
+                            let set = ctx.decoration(*result, Decoration::DescriptorSet);
+                            let binding = ctx.decoration(*result, Decoration::Binding);
+                            let (set, binding) = match (set, binding) {
+                                (Some(set), Some(binding)) => (set[0], binding[0]),
+                                _ => return Err(format!("Variable %{} has no DescriptorSet/Binding", result)),
+                            };
+                            let (element_ty, count) = ctx.array_elements(pointee)?;
+                            let descriptor_type = ctx.descriptor_type(element_ty, *storage_class)?;
+                            let block = match descriptor_type {
+                                DescriptorType::UniformBuffer | DescriptorType::StorageBuffer => {
+                                    Some(ctx.block(element_ty)?)
+                                }
+                                _ => None,
+                            };
+                            reflection.descriptor_bindings.push(DescriptorBinding {
+                                set,
+                                binding,
+                                descriptor_type,
+                                count,
+                                name: ctx.name(*result),
+                                block,
+                            });
+                        }
+                        _ => {}
+                    }
+                }
+                Instruction::OpSpecConstantTrue {
+                    result,
+                    ..
+                }
+                | Instruction::OpSpecConstantFalse {
+                    result,
+                    ..
+                }
+                | Instruction::OpSpecConstant {
+                    result,
+                    ..
+                } => {
+                    // Constants without SpecId are only used to build other spec constants
+                    if let Some(id) = ctx.decoration(*result, Decoration::SpecId) {
+                        let default = match inst {
+                            Instruction::OpSpecConstantTrue {
+                                ..
+                            } => vec![1],
+                            Instruction::OpSpecConstant {
+                                value,
+                                ..
+                            } => value.clone(),
+                            _ => vec![0],
+                        };
+                        reflection.spec_constants.push(SpecConstant {
+                            id: id[0],
+                            name: ctx.name(*result),
+                            default,
+                        });
+                    }
+                }
+                _ => {}
             }
-            let inst_words = &inst_words[..word_count];
+        }
+        reflection.descriptor_bindings.sort_by_key(|b| (b.set, b.binding));
+        Ok(reflection)
+    }
+}
 
-            instructions.push(Instruction::try_from(inst_words).map_err(|_| "Failed to parse Instruction")?);
+impl fmt::Debug for ShaderModule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "ShaderModule {{")?;
+        writeln!(f, "    magic: 0x{:08x}", self.magic)?;
+        writeln!(f, "    version: 0x{:08x}", self.version)?;
+        writeln!(f, "    generator: 0x{:08x}", self.generator)?;
+        writeln!(f, "    bound: {}", self.bound)?;
+        writeln!(f, "    instructions: {:#?}", self.instructions)?;
+        writeln!(f, "}}")
+    }
+}
+
+impl TryFrom<&[u8]> for ShaderModule {
+    type Error = ValidationError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        // Checks the header, word counts and operand layout, so decoding the instructions can't run out of words
+        let words = spirv_val::words_from_bytes(bytes)?;
+        let split = spirv_val::split_module(&words)?;
+        let mut instructions = Vec::with_capacity(split.len());
+        for (offset, inst_words) in split {
+            instructions.push(Instruction::try_from(inst_words).map_err(|kind| ValidationError {
+                offset,
+                kind,
+            })?);
         }
 
         Ok(Self {
-            magic,
-            version,
-            generator,
-            bound,
+            magic: words[0],
+            version: words[1],
+            generator: words[2],
+            bound: words[3],
             instructions,
         })
     }
 }
 
+impl ShaderModule {
+    /// Encodes the module back to words. Parsing a module and encoding it gives back the same words.
+    pub fn to_words(&self) -> Result<Vec<u32>, String> {
+        let mut words = vec![self.magic, self.version, self.generator, self.bound, 0];
+        for inst in &self.instructions {
+            inst.encode(&mut words)?;
+        }
+        Ok(words)
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, String> {
+        Ok(self.to_words()?.iter().flat_map(|w| w.to_le_bytes()).collect())
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Instruction {
     OpNop {
@@ -500,17 +986,35 @@ pub enum Instruction {
         result: u32,
     }, // 46
 
-    OpSpecConstantTrue,      // 48
-    OpSpecConstantFalse,     // 49
-    OpSpecConstant,          // 50
-    OpSpecConstantComposite, // 51
-    OpSpecConstantOp,        // 52
+    OpSpecConstantTrue {
+        opcode: u32,
+        result_type: u32,
+        result: u32,
+    }, // 48
+    OpSpecConstantFalse {
+        opcode: u32,
+        result_type: u32,
+        result: u32,
+    }, // 49
+    OpSpecConstant {
+        opcode: u32,
+        result_type: u32,
+        result: u32,
+        value: Vec<u32>,
+    }, // 50
+    OpSpecConstantComposite {
+        opcode: u32,
+        result_type: u32,
+        result: u32,
+        constituents: Vec<u32>,
+    }, // 51
+    OpSpecConstantOp, // 52
 
     OpFunction {
         opcode: u32,
         result_type: u32,
         result: u32,
-        function_control: FunctionControl,
+        function_control: u32, // Bit mask of FunctionControl
         function_type: u32,
     }, // 54
     OpFunctionParameter, // 55
@@ -532,13 +1036,13 @@ pub enum Instruction {
         result_type: u32,
         result: u32,
         pointer: u32,
-        memory_operands: Option<MemoryOperands>,
+        memory_operands: Vec<u32>,
     }, // 61
     OpStore {
         opcode: u32,
         pointer: u32,
         object: u32,
-        memory_operands: Option<MemoryOperands>,
+        memory_operands: Vec<u32>,
     }, // 62
     OpCopyMemory,        // 63
     OpCopyMemorySized,   // 64
@@ -566,6 +1070,7 @@ pub enum Instruction {
         structure_type: u32,
         member: u32,
         decoration: Decoration,
+        extra: Vec<u32>,
     }, // 72
     OpDecorationGroup,     // 73
     OpGroupDecorate,       // 74
@@ -605,7 +1110,7 @@ pub enum Instruction {
         result: u32,
         sampled_image: u32,
         coordinate: u32,
-        image_operands: Option<ImageOperands>,
+        image_operands: Vec<u32>,
     }, // 87
     OpImageSampleExplicitLod, // 88
     OpImageSampleDrefImplicitLod, // 89
@@ -948,10 +1453,16 @@ pub enum Instruction {
     OpPtrEqual,    // 401
     OpPtrNotEqual, // 402
     OpPtrDiff,     // 403
+
+    // Any instruction that is not parsed into one of the above yet
+    OpUnknown {
+        opcode: u32,
+        operands: Vec<u32>,
+    },
 }
 
 impl TryFrom<&[u32]> for Instruction {
-    type Error = &'static str;
+    type Error = ValidationErrorKind;
 
     fn try_from(inst_words: &[u32]) -> Result<Self, Self::Error> {
         let word_count = ((inst_words[0] >> 16) & 0xffff) as usize;
@@ -967,14 +1478,7 @@ impl TryFrom<&[u32]> for Instruction {
             }),
             2 => Ok(Instruction::OpSourceContinued {
                 opcode,
-                continued_source: String::from_utf8(
-                    inst_words[1..word_count]
-                        .iter()
-                        .flat_map(|val| val.to_le_bytes())
-                        .take_while(|x| *x != 0)
-                        .collect::<Vec<_>>(),
-                )
-                .unwrap(),
+                continued_source: decode_string(&inst_words[1..word_count])?,
             }),
             3 => {
                 let file = if word_count >= 4 {
@@ -982,10 +1486,14 @@ impl TryFrom<&[u32]> for Instruction {
                 } else {
                     None
                 };
-                let source = None; // TODO: Parse source if available
+                let source = if word_count >= 5 {
+                    Some(decode_string(&inst_words[4..word_count])?)
+                } else {
+                    None
+                };
                 Ok(Instruction::OpSource {
                     opcode,
-                    source_language: SourceLanguage::from(inst_words[1]),
+                    source_language: decode_enum(EnumKind::SourceLanguage, inst_words[1])?,
                     version: inst_words[2],
                     file,
                     source,
@@ -993,80 +1501,38 @@ impl TryFrom<&[u32]> for Instruction {
             }
             4 => Ok(Instruction::OpSourceExtension {
                 opcode,
-                extension: String::from_utf8(
-                    inst_words[1..word_count]
-                        .iter()
-                        .flat_map(|val| val.to_le_bytes())
-                        .take_while(|x| *x != 0)
-                        .collect::<Vec<_>>(),
-                )
-                .unwrap(),
+                extension: decode_string(&inst_words[1..word_count])?,
             }),
             5 => Ok(Instruction::OpName {
                 opcode,
                 target: inst_words[1],
-                name: String::from_utf8(
-                    inst_words[2..word_count]
-                        .iter()
-                        .flat_map(|val| val.to_le_bytes())
-                        .take_while(|x| *x != 0)
-                        .collect::<Vec<_>>(),
-                )
-                .unwrap(),
+                name: decode_string(&inst_words[2..word_count])?,
             }),
             6 => Ok(Instruction::OpMemberName {
                 opcode,
                 ttype: inst_words[1],
                 member: inst_words[2],
-                name: String::from_utf8(
-                    inst_words[3..word_count]
-                        .iter()
-                        .flat_map(|val| val.to_le_bytes())
-                        .take_while(|x| *x != 0)
-                        .collect::<Vec<_>>(),
-                )
-                .unwrap(),
+                name: decode_string(&inst_words[3..word_count])?,
             }),
             7 => Ok(Instruction::OpString {
                 opcode,
                 result: inst_words[1],
-                string: String::from_utf8(
-                    inst_words[2..word_count]
-                        .iter()
-                        .flat_map(|val| val.to_le_bytes())
-                        .take_while(|x| *x != 0)
-                        .collect::<Vec<_>>(),
-                )
-                .unwrap(),
+                string: decode_string(&inst_words[2..word_count])?,
             }),
             8 => Ok(Instruction::OpLine {
                 opcode,
                 file: inst_words[1],
                 line: inst_words[2],
-                column: inst_words[2],
+                column: inst_words[3],
             }),
             10 => Ok(Instruction::OpExtension {
                 opcode,
-                name: String::from_utf8(
-                    inst_words[1..word_count]
-                        .iter()
-                        .flat_map(|val| val.to_le_bytes())
-                        .take_while(|x| *x != 0)
-                        .collect::<Vec<_>>(),
-                )
-                .unwrap(),
+                name: decode_string(&inst_words[1..word_count])?,
             }),
             11 => Ok(Instruction::OpExtInstImport {
                 opcode,
                 result: inst_words[1],
-                name: String::from_utf8(
-                    inst_words[2..word_count]
-                        .iter()
-                        .flat_map(|val| val.to_le_bytes())
-                        .take_while(|x| *x != 0)
-                        .collect::<Vec<_>>(),
-                )
-                .unwrap(),
+                name: decode_string(&inst_words[2..word_count])?,
             }),
             12 => Ok(Instruction::OpExtInst {
                 opcode,
@@ -1079,35 +1545,30 @@ impl TryFrom<&[u32]> for Instruction {
 
             14 => Ok(Instruction::OpMemoryModel {
                 opcode,
-                addressing_model: AddressingModel::from(inst_words[1]),
-                memory_model: MemoryModel::from(inst_words[2]),
+                addressing_model: decode_enum(EnumKind::AddressingModel, inst_words[1])?,
+                memory_model: decode_enum(EnumKind::MemoryModel, inst_words[2])?,
             }),
             15 => {
-                // TODO: handle case where name is not "main"
+                let name = decode_string(&inst_words[3..word_count])?;
+                // The name is nul terminated and padded to a whole number of words
+                let interface_start = 3 + name.len() / 4 + 1;
                 Ok(Instruction::OpEntryPoint {
                     opcode,
-                    execution_model: ExecutionModel::from(inst_words[1]),
+                    execution_model: decode_enum(EnumKind::ExecutionModel, inst_words[1])?,
                     entry_point: inst_words[2],
-                    name: String::from_utf8(
-                        inst_words[3..word_count]
-                            .iter()
-                            .flat_map(|val| val.to_le_bytes())
-                            .take_while(|x| *x != 0)
-                            .collect::<Vec<_>>(),
-                    )
-                    .unwrap(),
-                    interface: inst_words[5..].to_vec(),
+                    name,
+                    interface: inst_words[interface_start..].to_vec(),
                 })
             }
             16 => Ok(Instruction::OpExecutionMode {
                 opcode,
                 entry_point: inst_words[1],
-                mode: ExecutionMode::from(inst_words[2]),
+                mode: decode_enum(EnumKind::ExecutionMode, inst_words[2])?,
                 literals: inst_words[3..].to_vec(),
             }),
             17 => Ok(Instruction::OpCapability {
                 opcode,
-                capability: Capability::from(inst_words[1]),
+                capability: decode_enum(EnumKind::Capability, inst_words[1])?,
             }),
             19 => Ok(Instruction::OpTypeVoid {
                 opcode,
@@ -1144,14 +1605,14 @@ impl TryFrom<&[u32]> for Instruction {
                 opcode,
                 result: inst_words[1],
                 sampled_type: inst_words[2],
-                dim: Dim::from(inst_words[3]),
+                dim: decode_enum(EnumKind::Dim, inst_words[3])?,
                 depth: inst_words[4],
                 arrayed: inst_words[5],
                 ms: inst_words[6],
                 sampled: inst_words[7],
-                image_format: ImageFormat::from(inst_words[8]),
+                image_format: decode_enum(EnumKind::ImageFormat, inst_words[8])?,
                 access_qualifier: if word_count >= 10 {
-                    Some(AccessQualifier::from(inst_words[9]))
+                    Some(decode_enum(EnumKind::AccessQualifier, inst_words[9])?)
                 } else {
                     None
                 },
@@ -1184,19 +1645,12 @@ impl TryFrom<&[u32]> for Instruction {
             31 => Ok(Instruction::OpTypeOpaque {
                 opcode,
                 result: inst_words[1],
-                name: String::from_utf8(
-                    inst_words[2..word_count]
-                        .iter()
-                        .flat_map(|val| val.to_le_bytes())
-                        .take_while(|x| *x != 0)
-                        .collect::<Vec<_>>(),
-                )
-                .unwrap(),
+                name: decode_string(&inst_words[2..word_count])?,
             }),
             32 => Ok(Instruction::OpTypePointer {
                 opcode,
                 result: inst_words[1],
-                storage_class: StorageClass::from(inst_words[2]),
+                storage_class: decode_enum(EnumKind::StorageClass, inst_words[2])?,
                 ttype: inst_words[3],
             }),
             33 => Ok(Instruction::OpTypeFunction {
@@ -1205,6 +1659,42 @@ impl TryFrom<&[u32]> for Instruction {
                 return_type: inst_words[2],
                 parameter_types: inst_words[3..].to_vec(),
             }),
+            34 => Ok(Instruction::OpTypeEvent {
+                opcode,
+                result: inst_words[1],
+            }),
+            35 => Ok(Instruction::OpTypeDeviceEvent {
+                opcode,
+                result: inst_words[1],
+            }),
+            36 => Ok(Instruction::OpTypeReserveId {
+                opcode,
+                result: inst_words[1],
+            }),
+            37 => Ok(Instruction::OpTypeQueue {
+                opcode,
+                result: inst_words[1],
+            }),
+            38 => Ok(Instruction::OpTypePipe {
+                opcode,
+                result: inst_words[1],
+                qualifier: decode_enum(EnumKind::AccessQualifier, inst_words[2])?,
+            }),
+            39 => Ok(Instruction::OpTypeForwardPointer {
+                opcode,
+                pointer_type: inst_words[1],
+                storage_class: decode_enum(EnumKind::StorageClass, inst_words[2])?,
+            }),
+            41 => Ok(Instruction::OpConstantTrue {
+                opcode,
+                result_type: inst_words[1],
+                result: inst_words[2],
+            }),
+            42 => Ok(Instruction::OpConstantFalse {
+                opcode,
+                result_type: inst_words[1],
+                result: inst_words[2],
+            }),
             43 => Ok(Instruction::OpConstant {
                 opcode,
                 result_type: inst_words[1],
@@ -1217,11 +1707,46 @@ impl TryFrom<&[u32]> for Instruction {
                 result: inst_words[2],
                 constituents: inst_words[3..].to_vec(),
             }),
+            45 => Ok(Instruction::OpConstantSampler {
+                opcode,
+                result_type: inst_words[1],
+                result: inst_words[2],
+                sampler_addressing_mode: decode_enum(EnumKind::SamplerAddressingMode, inst_words[3])?,
+                param: inst_words[4],
+                sampler_filter_mode: decode_enum(EnumKind::SamplerFilterMode, inst_words[5])?,
+            }),
+            46 => Ok(Instruction::OpConstantNull {
+                opcode,
+                result_type: inst_words[1],
+                result: inst_words[2],
+            }),
+            48 => Ok(Instruction::OpSpecConstantTrue {
+                opcode,
+                result_type: inst_words[1],
+                result: inst_words[2],
+            }),
+            49 => Ok(Instruction::OpSpecConstantFalse {
+                opcode,
+                result_type: inst_words[1],
+                result: inst_words[2],
+            }),
+            50 => Ok(Instruction::OpSpecConstant {
+                opcode,
+                result_type: inst_words[1],
+                result: inst_words[2],
+                value: inst_words[3..].to_vec(),
+            }),
+            51 => Ok(Instruction::OpSpecConstantComposite {
+                opcode,
+                result_type: inst_words[1],
+                result: inst_words[2],
+                constituents: inst_words[3..].to_vec(),
+            }),
             54 => Ok(Instruction::OpFunction {
                 opcode,
                 result_type: inst_words[1],
                 result: inst_words[2],
-                function_control: FunctionControl::from(inst_words[3]),
+                function_control: inst_words[3],
                 function_type: inst_words[4],
             }),
             56 => Ok(Instruction::OpFunctionEnd {
@@ -1237,7 +1762,7 @@ impl TryFrom<&[u32]> for Instruction {
                     opcode,
                     result_type: inst_words[1],
                     result: inst_words[2],
-                    storage_class: StorageClass::from(inst_words[3]),
+                    storage_class: decode_enum(EnumKind::StorageClass, inst_words[3])?,
                     initializer,
                 })
             }
@@ -1246,21 +1771,13 @@ impl TryFrom<&[u32]> for Instruction {
                 result_type: inst_words[1],
                 result: inst_words[2],
                 pointer: inst_words[3],
-                memory_operands: if word_count >= 5 {
-                    Some(MemoryOperands::from(inst_words[4]))
-                } else {
-                    None
-                },
+                memory_operands: inst_words[4..].to_vec(),
             }),
             62 => Ok(Instruction::OpStore {
                 opcode,
                 pointer: inst_words[1],
                 object: inst_words[2],
-                memory_operands: if word_count >= 4 {
-                    Some(MemoryOperands::from(inst_words[3]))
-                } else {
-                    None
-                },
+                memory_operands: inst_words[3..].to_vec(),
             }),
             65 => Ok(Instruction::OpAccessChain {
                 opcode,
@@ -1272,14 +1789,15 @@ impl TryFrom<&[u32]> for Instruction {
             71 => Ok(Instruction::OpDecorate {
                 opcode,
                 target: inst_words[1],
-                decoration: Decoration::from(inst_words[2]),
+                decoration: decode_enum(EnumKind::Decoration, inst_words[2])?,
                 extra: inst_words[3..].to_vec(),
             }),
             72 => Ok(Instruction::OpMemberDecorate {
                 opcode,
                 structure_type: inst_words[1],
                 member: inst_words[2],
-                decoration: Decoration::from(inst_words[3]),
+                decoration: decode_enum(EnumKind::Decoration, inst_words[3])?,
+                extra: inst_words[4..].to_vec(),
             }),
             79 => Ok(Instruction::OpVectorShuffle {
                 opcode,
@@ -1302,16 +1820,14 @@ impl TryFrom<&[u32]> for Instruction {
                 composite: inst_words[3],
                 indexes: inst_words[4..].to_vec(),
             }),
-            87 => {
-                Ok(Instruction::OpImageSampleImplicitLod {
-                    opcode,
-                    result_type: inst_words[1],
-                    result: inst_words[2],
-                    sampled_image: inst_words[3],
-                    coordinate: inst_words[4],
-                    image_operands: None, // TODO
-                })
-            }
+            87 => Ok(Instruction::OpImageSampleImplicitLod {
+                opcode,
+                result_type: inst_words[1],
+                result: inst_words[2],
+                sampled_image: inst_words[3],
+                coordinate: inst_words[4],
+                image_operands: inst_words[5..].to_vec(),
+            }),
             112 => Ok(Instruction::OpConvertUToF {
                 opcode,
                 result_type: inst_words[1],
@@ -1338,9 +1854,6 @@ impl TryFrom<&[u32]> for Instruction {
                 operand1: inst_words[3],
                 operand2: inst_words[4],
             }),
-            132 => {
-                todo!("OpIMul");
-            }
             133 => Ok(Instruction::OpFMul {
                 opcode,
                 result_type: inst_words[1],
@@ -1348,12 +1861,6 @@ impl TryFrom<&[u32]> for Instruction {
                 operand1: inst_words[3],
                 operand2: inst_words[4],
             }),
-            134 => {
-                todo!("OpUDiv");
-            }
-            135 => {
-                todo!("OpSDiv");
-            }
             136 => Ok(Instruction::OpFDiv {
                 opcode,
                 result_type: inst_words[1],
@@ -1361,21 +1868,6 @@ impl TryFrom<&[u32]> for Instruction {
                 operand1: inst_words[3],
                 operand2: inst_words[4],
             }),
-            137 => {
-                todo!("OpUMod");
-            }
-            138 => {
-                todo!("OpSRem");
-            }
-            139 => {
-                todo!("OpSMod");
-            }
-            140 => {
-                todo!("OpFRem");
-            }
-            141 => {
-                todo!("OpFMod");
-            }
             142 => Ok(Instruction::OpVectorTimesScalar {
                 opcode,
                 result_type: inst_words[1],
@@ -1383,12 +1875,6 @@ impl TryFrom<&[u32]> for Instruction {
                 vector: inst_words[3],
                 scalar: inst_words[4],
             }),
-            143 => {
-                todo!("OpMatrixTimesScalar");
-            }
-            144 => {
-                todo!("OpVectorTimesMatrix");
-            }
             145 => Ok(Instruction::OpMatrixTimesVector {
                 opcode,
                 result_type: inst_words[1],
@@ -1396,21 +1882,6 @@ impl TryFrom<&[u32]> for Instruction {
                 matrix: inst_words[3],
                 vector: inst_words[4],
             }),
-            146 => {
-                todo!("OpMatrixTimesMatrix");
-            }
-            147 => {
-                todo!("OpOuterProduct");
-            }
-            148 => {
-                todo!("OpDot");
-            }
-            149 => {
-                todo!("OpIAddCarry");
-            }
-            150 => {
-                todo!("OpISubBorrow");
-            }
             248 => Ok(Instruction::OpLabel {
                 opcode,
                 result: inst_words[1],
@@ -1418,16 +1889,593 @@ impl TryFrom<&[u32]> for Instruction {
             253 => Ok(Instruction::OpReturn {
                 opcode,
             }),
-            400 => {
-                todo!("OpCopyLogical");
+            _ => Ok(Instruction::OpUnknown {
+                opcode,
+                operands: inst_words[1..word_count].to_vec(),
+            }),
+        }
+    }
+}
+
+impl Instruction {
+    /// Appends the words of the instruction, starting with the word count and opcode. Only the
+    /// placeholder variants without operands (which the parser never produces) can't be encoded.
+    pub fn encode(&self, words: &mut Vec<u32>) -> Result<(), String> {
+        use Instruction::*;
+
+        let start = words.len();
+        words.push(0);
+        let opcode = match self {
+            OpNop {
+                opcode,
+            }
+            | OpFunctionEnd {
+                opcode,
+            }
+            | OpReturn {
+                opcode,
+            } => *opcode,
+            OpUndef {
+                opcode,
+                result_type,
+                result,
+            }
+            | OpConstantTrue {
+                opcode,
+                result_type,
+                result,
+            }
+            | OpConstantFalse {
+                opcode,
+                result_type,
+                result,
+            }
+            | OpConstantNull {
+                opcode,
+                result_type,
+                result,
+            }
+            | OpSpecConstantTrue {
+                opcode,
+                result_type,
+                result,
+            }
+            | OpSpecConstantFalse {
+                opcode,
+                result_type,
+                result,
+            } => {
+                words.extend([*result_type, *result]);
+                *opcode
+            }
+            OpSourceContinued {
+                opcode,
+                continued_source: string,
+            }
+            | OpSourceExtension {
+                opcode,
+                extension: string,
+            }
+            | OpExtension {
+                opcode,
+                name: string,
+            } => {
+                encode_string(words, string);
+                *opcode
+            }
+            OpSource {
+                opcode,
+                source_language,
+                version,
+                file,
+                source,
+            } => {
+                words.extend([*source_language as u32, *version]);
+                words.extend(file);
+                if let Some(source) = source {
+                    encode_string(words, source);
+                }
+                *opcode
+            }
+            OpName {
+                opcode,
+                target,
+                name,
+            } => {
+                words.push(*target);
+                encode_string(words, name);
+                *opcode
+            }
+            OpMemberName {
+                opcode,
+                ttype,
+                member,
+                name,
+            } => {
+                words.extend([*ttype, *member]);
+                encode_string(words, name);
+                *opcode
+            }
+            OpString {
+                opcode,
+                result,
+                string: name,
+            }
+            | OpExtInstImport {
+                opcode,
+                result,
+                name,
+            }
+            | OpTypeOpaque {
+                opcode,
+                result,
+                name,
+            } => {
+                words.push(*result);
+                encode_string(words, name);
+                *opcode
+            }
+            OpLine {
+                opcode,
+                file,
+                line,
+                column,
+            } => {
+                words.extend([*file, *line, *column]);
+                *opcode
+            }
+            OpExtInst {
+                opcode,
+                result_type,
+                result,
+                set,
+                instruction,
+                operands,
+            } => {
+                words.extend([*result_type, *result, *set, *instruction]);
+                words.extend(operands);
+                *opcode
+            }
+            OpMemoryModel {
+                opcode,
+                addressing_model,
+                memory_model,
+            } => {
+                words.extend([*addressing_model as u32, *memory_model as u32]);
+                *opcode
+            }
+            OpEntryPoint {
+                opcode,
+                execution_model,
+                entry_point,
+                name,
+                interface,
+            } => {
+                words.extend([*execution_model as u32, *entry_point]);
+                encode_string(words, name);
+                words.extend(interface);
+                *opcode
+            }
+            OpExecutionMode {
+                opcode,
+                entry_point,
+                mode,
+                literals,
+            } => {
+                words.extend([*entry_point, *mode as u32]);
+                words.extend(literals);
+                *opcode
+            }
+            OpCapability {
+                opcode,
+                capability,
+            } => {
+                words.push(*capability as u32);
+                *opcode
+            }
+            OpTypeVoid {
+                opcode,
+                result,
+            }
+            | OpTypeBool {
+                opcode,
+                result,
+            }
+            | OpTypeSampler {
+                opcode,
+                result,
             }
-            n => {
-                panic!("{}", n);
+            | OpTypeEvent {
+                opcode,
+                result,
+            }
+            | OpTypeDeviceEvent {
+                opcode,
+                result,
+            }
+            | OpTypeReserveId {
+                opcode,
+                result,
+            }
+            | OpTypeQueue {
+                opcode,
+                result,
+            }
+            | OpLabel {
+                opcode,
+                result,
+            } => {
+                words.push(*result);
+                *opcode
+            }
+            OpTypeInt {
+                opcode,
+                result,
+                width,
+                signedness,
+            } => {
+                words.extend([*result, *width, *signedness]);
+                *opcode
+            }
+            OpTypeFloat {
+                opcode,
+                result,
+                width,
+            } => {
+                words.extend([*result, *width]);
+                *opcode
+            }
+            OpTypeVector {
+                opcode,
+                result,
+                component_type: a,
+                component_count: b,
+            }
+            | OpTypeMatrix {
+                opcode,
+                result,
+                column_type: a,
+                column_count: b,
+            }
+            | OpTypeArray {
+                opcode,
+                result,
+                element_type: a,
+                length: b,
+            } => {
+                words.extend([*result, *a, *b]);
+                *opcode
+            }
+            OpTypeImage {
+                opcode,
+                result,
+                sampled_type,
+                dim,
+                depth,
+                arrayed,
+                ms,
+                sampled,
+                image_format,
+                access_qualifier,
+            } => {
+                words.extend([
+                    *result,
+                    *sampled_type,
+                    *dim as u32,
+                    *depth,
+                    *arrayed,
+                    *ms,
+                    *sampled,
+                    *image_format as u32,
+                ]);
+                words.extend(access_qualifier.map(|q| q as u32));
+                *opcode
+            }
+            OpTypeSampledImage {
+                opcode,
+                result,
+                image_type: ttype,
+            }
+            | OpTypeRuntimeArray {
+                opcode,
+                result,
+                element_type: ttype,
+            } => {
+                words.extend([*result, *ttype]);
+                *opcode
+            }
+            OpTypeStruct {
+                opcode,
+                result,
+                member_types: ids,
+            } => {
+                words.push(*result);
+                words.extend(ids);
+                *opcode
+            }
+            OpTypePointer {
+                opcode,
+                result,
+                storage_class,
+                ttype,
+            } => {
+                words.extend([*result, *storage_class as u32, *ttype]);
+                *opcode
+            }
+            OpTypeFunction {
+                opcode,
+                result,
+                return_type,
+                parameter_types,
+            } => {
+                words.extend([*result, *return_type]);
+                words.extend(parameter_types);
+                *opcode
             }
+            OpTypePipe {
+                opcode,
+                result,
+                qualifier,
+            } => {
+                words.extend([*result, *qualifier as u32]);
+                *opcode
+            }
+            OpTypeForwardPointer {
+                opcode,
+                pointer_type,
+                storage_class,
+            } => {
+                words.extend([*pointer_type, *storage_class as u32]);
+                *opcode
+            }
+            OpConstant {
+                opcode,
+                result_type,
+                result,
+                value: operands,
+            }
+            | OpConstantComposite {
+                opcode,
+                result_type,
+                result,
+                constituents: operands,
+            }
+            | OpSpecConstant {
+                opcode,
+                result_type,
+                result,
+                value: operands,
+            }
+            | OpSpecConstantComposite {
+                opcode,
+                result_type,
+                result,
+                constituents: operands,
+            }
+            | OpCompositeConstruct {
+                opcode,
+                result_type,
+                result,
+                constituents: operands,
+            } => {
+                words.extend([*result_type, *result]);
+                words.extend(operands);
+                *opcode
+            }
+            OpConstantSampler {
+                opcode,
+                result_type,
+                result,
+                sampler_addressing_mode,
+                param,
+                sampler_filter_mode,
+            } => {
+                words.extend([
+                    *result_type,
+                    *result,
+                    *sampler_addressing_mode as u32,
+                    *param,
+                    *sampler_filter_mode as u32,
+                ]);
+                *opcode
+            }
+            OpFunction {
+                opcode,
+                result_type,
+                result,
+                function_control,
+                function_type,
+            } => {
+                words.extend([*result_type, *result, *function_control, *function_type]);
+                *opcode
+            }
+            OpVariable {
+                opcode,
+                result_type,
+                result,
+                storage_class,
+                initializer,
+            } => {
+                words.extend([*result_type, *result, *storage_class as u32]);
+                words.extend(initializer);
+                *opcode
+            }
+            OpLoad {
+                opcode,
+                result_type,
+                result,
+                pointer: id,
+                memory_operands: operands,
+            }
+            | OpAccessChain {
+                opcode,
+                result_type,
+                result,
+                base: id,
+                indexes: operands,
+            }
+            | OpCompositeExtract {
+                opcode,
+                result_type,
+                result,
+                composite: id,
+                indexes: operands,
+            } => {
+                words.extend([*result_type, *result, *id]);
+                words.extend(operands);
+                *opcode
+            }
+            OpStore {
+                opcode,
+                pointer,
+                object,
+                memory_operands,
+            } => {
+                words.extend([*pointer, *object]);
+                words.extend(memory_operands);
+                *opcode
+            }
+            OpDecorate {
+                opcode,
+                target,
+                decoration,
+                extra,
+            } => {
+                words.extend([*target, *decoration as u32]);
+                words.extend(extra);
+                *opcode
+            }
+            OpMemberDecorate {
+                opcode,
+                structure_type,
+                member,
+                decoration,
+                extra,
+            } => {
+                words.extend([*structure_type, *member, *decoration as u32]);
+                words.extend(extra);
+                *opcode
+            }
+            OpVectorShuffle {
+                opcode,
+                result_type,
+                result,
+                vector1: a,
+                vector2: b,
+                components: operands,
+            }
+            | OpImageSampleImplicitLod {
+                opcode,
+                result_type,
+                result,
+                sampled_image: a,
+                coordinate: b,
+                image_operands: operands,
+            } => {
+                words.extend([*result_type, *result, *a, *b]);
+                words.extend(operands);
+                *opcode
+            }
+            OpConvertUToF {
+                opcode,
+                result_type,
+                result,
+                unsigned_value: operand,
+            }
+            | OpFNegate {
+                opcode,
+                result_type,
+                result,
+                operand,
+            } => {
+                words.extend([*result_type, *result, *operand]);
+                *opcode
+            }
+            OpFAdd {
+                opcode,
+                result_type,
+                result,
+                operand1: a,
+                operand2: b,
+            }
+            | OpFSub {
+                opcode,
+                result_type,
+                result,
+                operand1: a,
+                operand2: b,
+            }
+            | OpFMul {
+                opcode,
+                result_type,
+                result,
+                operand1: a,
+                operand2: b,
+            }
+            | OpFDiv {
+                opcode,
+                result_type,
+                result,
+                operand1: a,
+                operand2: b,
+            }
+            | OpVectorTimesScalar {
+                opcode,
+                result_type,
+                result,
+                vector: a,
+                scalar: b,
+            }
+            | OpMatrixTimesVector {
+                opcode,
+                result_type,
+                result,
+                matrix: a,
+                vector: b,
+            } => {
+                words.extend([*result_type, *result, *a, *b]);
+                *opcode
+            }
+            OpUnknown {
+                opcode,
+                operands,
+            } => {
+                words.extend(operands);
+                *opcode
+            }
+            _ => {
+                words.truncate(start);
+                return Err(format!("{:?} has no operands and can't be encoded.", self));
+            }
+        };
+        let word_count = words.len() - start;
+        if word_count > 0xffff {
+            words.truncate(start);
+            return Err(format!("Instruction with opcode {} is too long ({} words).", opcode, word_count));
         }
+        words[start] = (word_count as u32) << 16 | opcode;
+        Ok(())
     }
 }
 
+/// Literal string from its words up to the nul terminator, which must be UTF-8.
+fn decode_string(words: &[u32]) -> Result<String, ValidationErrorKind> {
+    let bytes = words.iter().flat_map(|w| w.to_le_bytes()).take_while(|&b| b != 0).collect();
+    String::from_utf8(bytes).map_err(|_| ValidationErrorKind::InvalidInstruction("Invalid UTF-8 in a literal string"))
+}
+
+/// Operand of an enum that has a variant for `value`.
+fn decode_enum<T: TryFrom<u32, Error = u32>>(kind: EnumKind, value: u32) -> Result<T, ValidationErrorKind> {
+    T::try_from(value).map_err(|value| ValidationErrorKind::InvalidEnumValue(kind, value))
+}
+
+/// Literal strings are nul terminated and padded with zeros to a whole number of words.
+fn encode_string(words: &mut Vec<u32>, string: &str) {
+    let mut bytes = string.as_bytes().to_vec();
+    bytes.resize(string.len() / 4 * 4 + 4, 0);
+    words.extend(bytes.chunks(4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]])));
+}
+
 def_enum!(Capability {
     Matrix = 0,
     Shader = 1,
@@ -1445,60 +2493,95 @@ def_enum!(Capability {
     ImageBasic = 13,
     ImageReadWrite = 14,
     ImageMipmap = 15,
-    Pipes = 16,
-    Groups = 17,
-    DeviceEnqueue = 18,
-    LiteralSampler = 19,
-    AtomicStorage = 20,
-    Int16 = 21,
-    TessellationPointSize = 22,
-    GeometryPointSize = 23,
-    ImageGatherExtended = 24,
-    StorageImageMultisample = 25,
-    UniformBufferArrayDynamicIndexing = 26,
-    SampledImageArrayDynamicIndexing = 27,
-    StorageBufferArrayDynamicIndexing = 28,
-    StorageImageArrayDynamicIndexing = 29,
-    ClipDistance = 30,
-    CullDistance = 31,
-    ImageCubeArray = 32,
-    SampleRateShading = 33,
-    ImageRect = 34,
-    SampledRect = 35,
-    GenericPointer = 36,
-    Int8 = 37,
-    InputAttachment = 38,
-    SparseResidency = 39,
-    MinLod = 40,
-    Sampled1D = 41,
-    Image1D = 42,
-    SampledCubeArray = 43,
-    SampledBuffer = 44,
-    ImageBuffer = 45,
-    ImageMSArray = 46,
-    StorageImageExtendedFormats = 47,
-    ImageQuery = 48,
-    DerivativeControl = 49,
-    InterpolationFunction = 50,
-    TransformFeedback = 51,
-    GeometryStreams = 52,
-    StorageImageReadWithoutFormat = 53,
-    StorageImageWriteWithoutFormat = 54,
-    MultiViewport = 55,
-    SubgroupDispatch = 56,
-    NamedBarrier = 57,
-    PipeStorage = 58,
-    GroupNonUniform = 59,
-    GroupNonUniformVote = 60,
-    GroupNonUniformArithmetic = 61,
-    GroupNonUniformBallot = 62,
-    GroupNonUniformShuffle = 63,
-    GroupNonUniformShuffleRelative = 64,
-    GroupNonUniformClustered = 65,
-    GroupNonUniformQuad = 66,
-    ShaderLayer = 67,
-    ShaderViewportIndex = 68,
-    UniformDecoration = 69,
+    Pipes = 17,
+    Groups = 18,
+    DeviceEnqueue = 19,
+    LiteralSampler = 20,
+    AtomicStorage = 21,
+    Int16 = 22,
+    TessellationPointSize = 23,
+    GeometryPointSize = 24,
+    ImageGatherExtended = 25,
+    StorageImageMultisample = 27,
+    UniformBufferArrayDynamicIndexing = 28,
+    SampledImageArrayDynamicIndexing = 29,
+    StorageBufferArrayDynamicIndexing = 30,
+    StorageImageArrayDynamicIndexing = 31,
+    ClipDistance = 32,
+    CullDistance = 33,
+    ImageCubeArray = 34,
+    SampleRateShading = 35,
+    ImageRect = 36,
+    SampledRect = 37,
+    GenericPointer = 38,
+    Int8 = 39,
+    InputAttachment = 40,
+    SparseResidency = 41,
+    MinLod = 42,
+    Sampled1D = 43,
+    Image1D = 44,
+    SampledCubeArray = 45,
+    SampledBuffer = 46,
+    ImageBuffer = 47,
+    ImageMSArray = 48,
+    StorageImageExtendedFormats = 49,
+    ImageQuery = 50,
+    DerivativeControl = 51,
+    InterpolationFunction = 52,
+    TransformFeedback = 53,
+    GeometryStreams = 54,
+    StorageImageReadWithoutFormat = 55,
+    StorageImageWriteWithoutFormat = 56,
+    MultiViewport = 57,
+    SubgroupDispatch = 58,
+    NamedBarrier = 59,
+    PipeStorage = 60,
+    GroupNonUniform = 61,
+    GroupNonUniformVote = 62,
+    GroupNonUniformArithmetic = 63,
+    GroupNonUniformBallot = 64,
+    GroupNonUniformShuffle = 65,
+    GroupNonUniformShuffleRelative = 66,
+    GroupNonUniformClustered = 67,
+    GroupNonUniformQuad = 68,
+    ShaderLayer = 69,
+    ShaderViewportIndex = 70,
+    UniformDecoration = 71,
+    SubgroupBallotKHR = 4423,
+    DrawParameters = 4427,
+    SubgroupVoteKHR = 4431,
+    StorageBuffer16BitAccess = 4433,
+    UniformAndStorageBuffer16BitAccess = 4434,
+    StoragePushConstant16 = 4435,
+    StorageInputOutput16 = 4436,
+    DeviceGroup = 4437,
+    MultiView = 4439,
+    VariablePointersStorageBuffer = 4441,
+    VariablePointers = 4442,
+    SampleMaskPostDepthCoverage = 4447,
+    StorageBuffer8BitAccess = 4448,
+    UniformAndStorageBuffer8BitAccess = 4449,
+    StoragePushConstant8 = 4450,
+    DenormPreserve = 4464,
+    DenormFlushToZero = 4465,
+    SignedZeroInfNanPreserve = 4466,
+    RoundingModeRTE = 4467,
+    RoundingModeRTZ = 4468,
+    ShaderNonUniform = 5301,
+    RuntimeDescriptorArray = 5302,
+    InputAttachmentArrayDynamicIndexing = 5303,
+    UniformTexelBufferArrayDynamicIndexing = 5304,
+    StorageTexelBufferArrayDynamicIndexing = 5305,
+    UniformBufferArrayNonUniformIndexing = 5306,
+    SampledImageArrayNonUniformIndexing = 5307,
+    StorageBufferArrayNonUniformIndexing = 5308,
+    StorageImageArrayNonUniformIndexing = 5309,
+    InputAttachmentArrayNonUniformIndexing = 5310,
+    UniformTexelBufferArrayNonUniformIndexing = 5311,
+    StorageTexelBufferArrayNonUniformIndexing = 5312,
+    VulkanMemoryModel = 5345,
+    VulkanMemoryModelDeviceScope = 5346,
+    PhysicalStorageBufferAddresses = 5347,
 });
 
 def_enum!(AddressingModel {
@@ -1539,6 +2622,7 @@ def_enum!(StorageClass {
     AtomicCounter = 10,
     Image = 11,
     StorageBuffer = 12,
+    PhysicalStorageBuffer = 5349,
 });
 
 def_enum!(SourceLanguage {
@@ -1565,32 +2649,39 @@ def_enum!(ExecutionMode {
     PointMode = 10,
     Xfb = 11,
     DepthReplacing = 12,
-    DepthGreater = 13,
-    DepthLess = 14,
-    DepthUnchanged = 15,
-    LocalSize = 16,
-    LocalSizeHint = 17,
-    InputPoints = 18,
-    InputLines = 19,
-    InputLinesAdjacency = 20,
-    Triangles = 21,
-    InputTrianglesAdjacency = 22,
-    Quads = 23,
-    Isolines = 24,
-    OutputVertices = 25,
-    OutputPoints = 26,
-    OutputLineStrip = 27,
-    OutputTriangleStrip = 28,
-    VecTypeHint = 29,
-    ContractionOff = 30,
-    Initializer = 31,
-    Finalizer = 32,
-    SubgroupSize = 33,
-    SubgroupsPerWorkgroup = 34,
-    SubgroupsPerWorkgroupId = 35,
-    LocalSizeId = 36,
-    LocalSizeHintId = 37,
-    // TODO: Extensions...
+    DepthGreater = 14,
+    DepthLess = 15,
+    DepthUnchanged = 16,
+    LocalSize = 17,
+    LocalSizeHint = 18,
+    InputPoints = 19,
+    InputLines = 20,
+    InputLinesAdjacency = 21,
+    Triangles = 22,
+    InputTrianglesAdjacency = 23,
+    Quads = 24,
+    Isolines = 25,
+    OutputVertices = 26,
+    OutputPoints = 27,
+    OutputLineStrip = 28,
+    OutputTriangleStrip = 29,
+    VecTypeHint = 30,
+    ContractionOff = 31,
+    Initializer = 33,
+    Finalizer = 34,
+    SubgroupSize = 35,
+    SubgroupsPerWorkgroup = 36,
+    SubgroupsPerWorkgroupId = 37,
+    LocalSizeId = 38,
+    LocalSizeHintId = 39,
+    SubgroupUniformControlFlowKHR = 4421,
+    PostDepthCoverage = 4446,
+    DenormPreserve = 4459,
+    DenormFlushToZero = 4460,
+    SignedZeroInfNanPreserve = 4461,
+    RoundingModeRTE = 4462,
+    RoundingModeRTZ = 4463,
+    StencilRefReplacingEXT = 5027,
 });
 
 def_enum!(Dim {
@@ -1715,7 +2806,129 @@ def_enum!(Decoration {
     MaxByteOffset = 45,
     AlignmentId = 46,
     MaxByteOffsetId = 47,
-    // TODO: Extensions
+    NoSignedWrap = 4469,
+    NoUnsignedWrap = 4470,
+    NonUniform = 5300,
+    RestrictPointer = 5355,
+    AliasedPointer = 5356,
+    CounterBuffer = 5634,
+    UserSemantic = 5635,
+});
+
+def_enum!(BuiltIn {
+    Position = 0,
+    PointSize = 1,
+    ClipDistance = 3,
+    CullDistance = 4,
+    VertexId = 5,
+    InstanceId = 6,
+    PrimitiveId = 7,
+    InvocationId = 8,
+    Layer = 9,
+    ViewportIndex = 10,
+    TessLevelOuter = 11,
+    TessLevelInner = 12,
+    TessCoord = 13,
+    PatchVertices = 14,
+    FragCoord = 15,
+    PointCoord = 16,
+    FrontFacing = 17,
+    SampleId = 18,
+    SamplePosition = 19,
+    SampleMask = 20,
+    FragDepth = 22,
+    HelperInvocation = 23,
+    NumWorkgroups = 24,
+    WorkgroupSize = 25,
+    WorkgroupId = 26,
+    LocalInvocationId = 27,
+    GlobalInvocationId = 28,
+    LocalInvocationIndex = 29,
+    WorkDim = 30,
+    GlobalSize = 31,
+    EnqueuedWorkgroupSize = 32,
+    GlobalOffset = 33,
+    GlobalLinearId = 34,
+    SubgroupSize = 36,
+    SubgroupMaxSize = 37,
+    NumSubgroups = 38,
+    NumEnqueuedSubgroups = 39,
+    SubgroupId = 40,
+    SubgroupLocalInvocationId = 41,
+    VertexIndex = 42,
+    InstanceIndex = 43,
+    SubgroupEqMask = 4416,
+    SubgroupGeMask = 4417,
+    SubgroupGtMask = 4418,
+    SubgroupLeMask = 4419,
+    SubgroupLtMask = 4420,
+    BaseVertex = 4424,
+    BaseInstance = 4425,
+    DrawIndex = 4426,
+    DeviceIndex = 4438,
+    ViewIndex = 4440,
+});
+
+def_enum!(FPRoundingMode {
+    RTE = 0,
+    RTZ = 1,
+    RTP = 2,
+    RTN = 3,
+});
+
+def_enum!(LinkageType {
+    Export = 0,
+    Import = 1,
+    LinkOnceODR = 2,
+});
+
+def_enum!(FunctionParameterAttribute {
+    Zext = 0,
+    Sext = 1,
+    ByVal = 2,
+    Sret = 3,
+    NoAlias = 4,
+    NoCapture = 5,
+    NoWrite = 6,
+    NoReadWrite = 7,
+});
+
+def_enum!(GroupOperation {
+    Reduce = 0,
+    InclusiveScan = 1,
+    ExclusiveScan = 2,
+    ClusteredReduce = 3,
+});
+
+// TODO: Bit flags!!
+def_enum!(SelectionControl {
+    None = 0,
+    Flatten = 1,
+    DontFlatten = 2,
+});
+
+// TODO: Bit flags!!
+def_enum!(LoopControl {
+    None = 0,
+    Unroll = 1,
+    DontUnroll = 2,
+    DependencyInfinite = 4,
+    DependencyLength = 8,
+    MinIterations = 0x10,
+    MaxIterations = 0x20,
+    IterationMultiple = 0x40,
+    PeelCount = 0x80,
+    PartialCount = 0x100,
+});
+
+// TODO: Bit flags!!
+def_enum!(FPFastMathMode {
+    None = 0,
+    NotNaN = 1,
+    NotInf = 2,
+    NSZ = 4,
+    AllowRecip = 8,
+    Fast = 0x10,
 });
 
 // TODO: Bit flags!!
@@ -1760,11 +2973,13 @@ def_enum!(ImageOperands {
 });
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
 
     #[test]
+    #[ignore] // Needs the shaders compiled by compile_shaders.sh
     fn spirv() -> std::io::Result<()> {
+        let mut count = 0;
         for entry in std::fs::read_dir("assets/shaders/")? {
             let path = entry?.path();
             if path.is_dir() {
@@ -1777,6 +2992,8 @@ mod tests {
                         //println!("{:#?}", module);
 
                         println!("{:?}", module.input_descriptions());
+                        assert_eq!(module.to_bytes().unwrap(), bytes);
+                        count += 1;
 
                         //break;
                     }
@@ -1784,7 +3001,395 @@ mod tests {
                 }
             }
         }
+        assert!(count > 0, "No .spv under assets/shaders, run compile_shaders.sh");
 
         Ok(())
     }
+
+    #[test]
+    fn assembled_round_trip() {
+        // Capabilities, execution modes, built-ins and decorations of extensions, and a function control mask
+        let text = r#"
+               OpCapability Shader
+               OpCapability DrawParameters
+               OpCapability DenormPreserve
+               OpCapability ShaderNonUniform
+               OpExtension "SPV_KHR_shader_draw_parameters"
+               OpExtension "SPV_KHR_float_controls"
+               OpMemoryModel Logical GLSL450
+               OpEntryPoint Vertex %main "main" %draw_index %out
+               OpExecutionMode %main DenormPreserve 32
+               OpExecutionMode %main PostDepthCoverage
+               OpDecorate %draw_index BuiltIn DrawIndex
+               OpDecorate %out Location 0
+               OpDecorate %index NonUniform
+       %void = OpTypeVoid
+    %fn_void = OpTypeFunction %void
+        %int = OpTypeInt 32 1
+    %in_int = OpTypePointer Input %int
+   %out_int = OpTypePointer Output %int
+%draw_index = OpVariable %in_int Input
+        %out = OpVariable %out_int Output
+       %main = OpFunction %void Inline|Const %fn_void
+      %entry = OpLabel
+      %index = OpLoad %int %draw_index
+               OpStore %out %index
+               OpReturn
+               OpFunctionEnd
+"#;
+        let words = crate::spirv_as::assemble_words(text).unwrap();
+        let bytes: Vec<u8> = words.iter().flat_map(|w| w.to_le_bytes()).collect();
+        let module = ShaderModule::try_from(bytes.as_slice()).unwrap();
+        module.validate().unwrap();
+        assert_eq!(module.to_words().unwrap(), words);
+        assert!(module.instructions.contains(&Instruction::OpCapability {
+            opcode: 17,
+            capability: Capability::DrawParameters
+        }));
+        assert!(module.instructions.iter().any(|inst| matches!(
+            inst,
+            Instruction::OpDecorate {
+                decoration: Decoration::NonUniform,
+                ..
+            }
+        )));
+    }
+
+    /// Words of a literal string, also used by the tests of the other SPIR-V modules.
+    pub(crate) fn string_words(s: &str) -> Vec<u32> {
+        let mut bytes = s.as_bytes().to_vec();
+        bytes.resize(s.len() / 4 * 4 + 4, 0);
+        bytes.chunks(4).map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]])).collect()
+    }
+
+    /// Words of an instruction, with the word count and opcode before the operands.
+    pub(crate) fn inst(opcode: u32, operands: &[u32]) -> Vec<u32> {
+        let mut words = vec![((operands.len() as u32 + 1) << 16) | opcode];
+        words.extend_from_slice(operands);
+        words
+    }
+
+    fn module_bytes(bound: u32, instructions: &[Vec<u32>]) -> Vec<u8> {
+        let mut words = vec![0x07230203, 0x0001_0000, 0, bound, 0];
+        instructions.iter().for_each(|i| words.extend_from_slice(i));
+        words.iter().flat_map(|w| w.to_le_bytes()).collect()
+    }
+
+    fn module(bound: u32, instructions: &[Vec<u32>]) -> ShaderModule {
+        ShaderModule::try_from(module_bytes(bound, instructions).as_slice()).unwrap()
+    }
+
+    #[test]
+    fn reflection() {
+        let with_str = |words: &[u32], s: &str| [words, &string_words(s)].concat();
+        let module = module(
+            29,
+            &[
+                inst(17, &[1]),
+                inst(14, &[0, 1]),
+                inst(15, &with_str(&[0, 3], "main")),
+                inst(5, &with_str(&[12], "globals")),
+                inst(5, &with_str(&[25], "WORKGROUP")),
+                inst(6, &with_str(&[10, 1], "mvp")),
+                // Uniform buffer
+                inst(71, &[10, 2]),
+                inst(72, &[10, 0, 35, 0]),
+                inst(72, &[10, 1, 35, 16]),
+                inst(72, &[10, 1, 5]),
+                inst(72, &[10, 1, 7, 16]),
+                inst(72, &[10, 2, 35, 80]),
+                inst(71, &[9, 6, 16]),
+                inst(71, &[12, 34, 0]),
+                inst(71, &[12, 33, 0]),
+                // Storage buffer
+                inst(71, &[13, 2]),
+                inst(72, &[13, 0, 35, 0]),
+                inst(72, &[13, 1, 35, 16]),
+                inst(71, &[14, 6, 16]),
+                inst(71, &[16, 34, 0]),
+                inst(71, &[16, 33, 1]),
+                // Array of textures
+                inst(71, &[21, 34, 1]),
+                inst(71, &[21, 33, 2]),
+                // Push constants and specialization constants
+                inst(71, &[22, 2]),
+                inst(72, &[22, 0, 35, 16]),
+                inst(72, &[22, 1, 35, 20]),
+                inst(71, &[25, 1, 3]),
+                inst(71, &[26, 1, 4]),
+                inst(19, &[1]),
+                inst(33, &[2, 1]),
+                inst(22, &[4, 32]),
+                inst(23, &[5, 4, 4]),
+                inst(24, &[6, 5, 4]),
+                inst(21, &[7, 32, 0]),
+                inst(43, &[7, 8, 4]),
+                inst(28, &[9, 4, 8]),
+                inst(30, &[10, 5, 6, 9]),
+                inst(32, &[11, 2, 10]),
+                inst(59, &[11, 12, 2]),
+                inst(29, &[14, 5]),
+                inst(30, &[13, 7, 14]),
+                inst(32, &[15, 12, 13]),
+                inst(59, &[15, 16, 12]),
+                inst(25, &[17, 4, 1, 0, 0, 0, 1, 0]),
+                inst(27, &[18, 17]),
+                inst(28, &[19, 18, 8]),
+                inst(32, &[20, 0, 19]),
+                inst(59, &[20, 21, 0]),
+                inst(30, &[22, 4, 7]),
+                inst(32, &[23, 9, 22]),
+                inst(59, &[23, 24, 9]),
+                inst(20, &[27]),
+                inst(50, &[7, 25, 64]),
+                inst(48, &[27, 26]),
+                inst(54, &[1, 3, 0, 2]),
+                inst(248, &[28]),
+                inst(253, &[]),
+                inst(56, &[]),
+            ],
+        );
+        let reflection = module.reflect().unwrap();
+
+        assert_eq!(reflection.entry_points.len(), 1);
+        assert_eq!(reflection.entry_points[0].name, "main");
+        assert_eq!(reflection.entry_points[0].execution_model, ExecutionModel::Vertex);
+
+        let bindings = &reflection.descriptor_bindings;
+        assert_eq!(bindings.len(), 3);
+        let ubo = &bindings[0];
+        assert_eq!((ubo.set, ubo.binding, ubo.descriptor_type, ubo.count), (0, 0, DescriptorType::UniformBuffer, 1));
+        assert_eq!(ubo.name, "globals");
+        let block = ubo.block.as_ref().unwrap();
+        assert_eq!(block.size, 144);
+        assert_eq!(block.members[1].name, "mvp");
+        let layout = block.members.iter().map(|m| (m.offset, m.size, m.array_stride)).collect::<Vec<_>>();
+        assert_eq!(layout, vec![(0, 16, 0), (16, 64, 0), (80, 64, 16)]);
+
+        let ssbo = &bindings[1];
+        assert_eq!((ssbo.set, ssbo.binding, ssbo.descriptor_type), (0, 1, DescriptorType::StorageBuffer));
+        let block = ssbo.block.as_ref().unwrap();
+        assert_eq!(block.size, 16);
+        assert_eq!((block.members[1].size, block.members[1].array_stride), (0, 16));
+
+        let textures = &bindings[2];
+        assert_eq!(
+            (textures.set, textures.binding, textures.descriptor_type, textures.count),
+            (1, 2, DescriptorType::CombinedImageSampler, 4)
+        );
+        assert!(textures.block.is_none());
+
+        assert_eq!(reflection.push_constants.unwrap().range(), (16, 8));
+
+        let spec = &reflection.spec_constants;
+        assert_eq!(spec.len(), 2);
+        assert_eq!((spec[0].id, spec[0].name.as_str(), spec[0].default.as_slice()), (3, "WORKGROUP", &[64][..]));
+        assert_eq!((spec[1].id, spec[1].default.as_slice()), (4, &[1][..]));
+    }
+
+    #[test]
+    fn reflection_overflow() {
+        // Uniform block with a vec4[0x20000000] of stride 16, 8 GiB
+        let with_str = |words: &[u32], s: &str| [words, &string_words(s)].concat();
+        let instructions = |length: u32| {
+            vec![
+                inst(17, &[1]),
+                inst(14, &[0, 1]),
+                inst(15, &with_str(&[0, 3], "main")),
+                inst(71, &[10, 2]),
+                inst(72, &[10, 0, 35, 0]),
+                inst(71, &[9, 6, 16]),
+                inst(71, &[12, 34, 0]),
+                inst(71, &[12, 33, 0]),
+                inst(19, &[1]),
+                inst(33, &[2, 1]),
+                inst(22, &[4, 32]),
+                inst(23, &[5, 4, 4]),
+                inst(21, &[7, 32, 0]),
+                inst(43, &[7, 8, length]),
+                inst(28, &[9, 5, 8]),
+                inst(30, &[10, 9]),
+                inst(32, &[11, 2, 10]),
+                inst(59, &[11, 12, 2]),
+                inst(54, &[1, 3, 0, 2]),
+                inst(248, &[13]),
+                inst(253, &[]),
+                inst(56, &[]),
+            ]
+        };
+        let reflection = module(14, &instructions(4)).reflect().unwrap();
+        assert_eq!(reflection.descriptor_bindings[0].block.as_ref().unwrap().size, 64);
+        let module = module(14, &instructions(0x2000_0000));
+        assert!(module.validate().is_ok());
+        assert!(module.reflect().unwrap_err().contains("overflows"));
+    }
+
+    #[test]
+    fn unknown_and_long_instructions() {
+        let long_name = "a".repeat(200);
+        let module = module(
+            4,
+            &[
+                inst(5, &[1].iter().copied().chain(string_words(&long_name)).collect::<Vec<_>>()),
+                inst(132, &[1, 2, 3, 3]), // OpIMul
+            ],
+        );
+        assert!(matches!(&module.instructions[0], Instruction::OpName { name, .. } if *name == long_name));
+        assert_eq!(
+            module.instructions[1],
+            Instruction::OpUnknown {
+                opcode: 132,
+                operands: vec![1, 2, 3, 3]
+            }
+        );
+    }
+
+    #[test]
+    fn extension_enums() {
+        // glslang declares DrawParameters for gl_DrawID and gl_BaseVertex
+        let instructions = [
+            inst(17, &[1]),
+            inst(17, &[4427]),
+            inst(14, &[0, 1]),
+            inst(16, &[1, 4446]),
+            inst(16, &[1, 4459, 32]),
+            inst(71, &[2, 5300]),
+            inst(54, &[3, 1, 1 | 8, 4]), // Inline|Const
+        ];
+        let module = module(5, &instructions);
+        assert_eq!(
+            module.instructions[1],
+            Instruction::OpCapability {
+                opcode: 17,
+                capability: Capability::DrawParameters
+            }
+        );
+        assert!(matches!(
+            module.instructions[3],
+            Instruction::OpExecutionMode {
+                mode: ExecutionMode::PostDepthCoverage,
+                ..
+            }
+        ));
+        assert_eq!(module.to_bytes().unwrap(), module_bytes(5, &instructions));
+
+        // Values without a variant are errors, not panics
+        let error = ShaderModule::try_from(module_bytes(2, &[inst(17, &[4000])]).as_slice()).unwrap_err();
+        assert_eq!((error.offset, error.kind), (5, ValidationErrorKind::InvalidEnumValue(EnumKind::Capability, 4000)));
+        let error = ShaderModule::try_from(module_bytes(2, &[inst(16, &[1, 99])]).as_slice()).unwrap_err();
+        assert_eq!(error.kind, ValidationErrorKind::InvalidEnumValue(EnumKind::ExecutionMode, 99));
+    }
+
+    #[test]
+    fn encode_round_trip() {
+        let with_str = |words: &[u32], s: &str| [words, &string_words(s)].concat();
+        let instructions = [
+            inst(0, &[]),
+            inst(17, &[1]),
+            inst(11, &with_str(&[1], "GLSL.std.450")),
+            inst(14, &[0, 1]),
+            inst(15, &[with_str(&[4, 2], "main").as_slice(), &[10, 11]].concat()),
+            inst(16, &[2, 17, 8, 8, 1]),
+            inst(3, &with_str(&[2, 450, 7], "#version 450")),
+            inst(3, &[2, 450]),
+            inst(2, &string_words("void main() {}")),
+            inst(4, &string_words("GL_GOOGLE_include_directive")),
+            inst(10, &string_words("SPV_KHR_storage_buffer_storage_class")),
+            inst(7, &with_str(&[7], "shader.comp")),
+            inst(5, &with_str(&[2], "main")),
+            inst(6, &with_str(&[12, 0], "data")),
+            inst(71, &[10, 11, 28]),
+            inst(72, &[12, 0, 35, 0]),
+            inst(19, &[3]),
+            inst(20, &[13]),
+            inst(21, &[14, 32, 0]),
+            inst(22, &[15, 32]),
+            inst(23, &[16, 15, 4]),
+            inst(24, &[17, 16, 4]),
+            inst(25, &[18, 15, 1, 0, 0, 0, 1, 0]),
+            inst(25, &[19, 15, 1, 0, 0, 0, 2, 1, 2]),
+            inst(26, &[20]),
+            inst(27, &[21, 18]),
+            inst(28, &[22, 15, 23]),
+            inst(29, &[24, 15]),
+            inst(30, &[12, 24]),
+            inst(31, &with_str(&[25], "opaque")),
+            inst(32, &[26, 12, 12]),
+            inst(33, &[27, 3]),
+            inst(34, &[28]),
+            inst(35, &[29]),
+            inst(36, &[30]),
+            inst(37, &[31]),
+            inst(38, &[32, 0]),
+            inst(39, &[26, 12]),
+            inst(41, &[13, 33]),
+            inst(42, &[13, 34]),
+            inst(43, &[14, 23, 4]),
+            inst(44, &[16, 35, 36, 36, 36, 36]),
+            inst(45, &[20, 37, 1, 0, 1]),
+            inst(46, &[15, 36]),
+            inst(48, &[13, 38]),
+            inst(49, &[13, 39]),
+            inst(50, &[14, 40, 7]),
+            inst(51, &[16, 41, 36, 36, 36, 36]),
+            inst(59, &[26, 10, 12]),
+            inst(59, &[26, 11, 12, 42]),
+            inst(54, &[3, 2, 0, 27]),
+            inst(248, &[43]),
+            inst(8, &[7, 12, 4]),
+            inst(61, &[16, 44, 10]),
+            inst(61, &[16, 45, 10, 3, 16]), // Volatile|Aligned 16
+            inst(62, &[10, 44]),
+            inst(62, &[10, 44, 2, 4]),
+            inst(65, &[26, 46, 10, 47, 48]),
+            inst(79, &[16, 49, 44, 45, 0, 1, 4, 5]),
+            inst(80, &[16, 50, 36, 36, 36, 36]),
+            inst(81, &[15, 51, 44, 2]),
+            inst(87, &[16, 52, 53, 54]),
+            inst(87, &[16, 55, 53, 54, 1, 36]), // Bias
+            inst(12, &[15, 56, 1, 31, 36]),
+            inst(112, &[15, 57, 58]),
+            inst(127, &[15, 59, 36]),
+            inst(129, &[15, 60, 36, 36]),
+            inst(131, &[15, 61, 36, 36]),
+            inst(133, &[15, 62, 36, 36]),
+            inst(136, &[15, 63, 36, 36]),
+            inst(142, &[16, 64, 44, 36]),
+            inst(145, &[16, 65, 66, 44]),
+            inst(52, &[14, 67, 128, 23, 40]), // OpSpecConstantOp IAdd
+            inst(253, &[]),
+            inst(56, &[]),
+        ];
+        let bytes = module_bytes(68, &instructions);
+        let module = ShaderModule::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(module.instructions.len(), instructions.len());
+        assert_eq!(module.to_bytes().unwrap(), bytes);
+        // Only the unparsed opcode falls back to OpUnknown
+        let unknown = module.instructions.iter().filter(|i| matches!(i, Instruction::OpUnknown { .. })).count();
+        assert_eq!(unknown, 1);
+
+        // Patched modules encode their changes
+        let mut patched = module.clone();
+        for inst in &mut patched.instructions {
+            if let Instruction::OpDecorate {
+                decoration: Decoration::BuiltIn,
+                extra,
+                ..
+            } = inst
+            {
+                extra[0] = BuiltIn::LocalInvocationId as u32;
+            }
+        }
+        let patched = ShaderModule::try_from(patched.to_bytes().unwrap().as_slice()).unwrap();
+        assert!(patched.instructions.contains(&Instruction::OpDecorate {
+            opcode: 71,
+            target: 10,
+            decoration: Decoration::BuiltIn,
+            extra: vec![27],
+        }));
+
+        let mut words = vec![];
+        assert!(Instruction::OpSpecConstantOp.encode(&mut words).is_err());
+        assert!(words.is_empty());
+    }
 }
@@ -0,0 +1,772 @@
+use crate::spirv::ShaderModule;
+use crate::spirv_dis::MAGIC;
+use crate::spirv_grammar::*;
+
+use std::collections::{HashMap, HashSet};
+
+// SPIR-V assembler:
+// Turns the textual form printed by `spirv_dis` (and spirv-dis) back into a module:
+//
+//                OpCapability Shader
+//                OpMemoryModel Logical GLSL450
+//        %void = OpTypeVoid
+//     %fn_void = OpTypeFunction %void
+//
+// Tokens are separated by whitespace and ';' starts a comment. Ids are written %name; numeric ids
+// keep their number and the other names get the lowest free ids in order of first appearance.
+// Enums are written with their names (masks joined with '|') and literal numbers of OpConstant,
+// OpSpecConstant and OpSwitch take the width and format of their type. Errors are reported as
+// "line:column: message".
+
+/// SPIR-V version of assembled modules (1.0).
+const VERSION: u32 = 0x0001_0000;
+
+pub fn assemble(text: &str) -> Result<ShaderModule, String> {
+    let words = assemble_words(text)?;
+    let bytes: Vec<u8> = words.iter().flat_map(|w| w.to_le_bytes()).collect();
+    ShaderModule::try_from(bytes.as_slice()).map_err(|e| e.to_string())
+}
+
+pub fn assemble_words(text: &str) -> Result<Vec<u32>, String> {
+    let tokens = tokenize(text)?;
+    let ids = allocate_ids(&tokens)?;
+    let bound = ids.values().max().map_or(1, |max| max + 1);
+    let mut assembler = Assembler {
+        tokens,
+        pos: 0,
+        ids,
+        numbers: HashMap::new(),
+        result_types: HashMap::new(),
+        ext_imports: HashMap::new(),
+        words: vec![MAGIC, VERSION, 0, bound, 0],
+    };
+    while assembler.pos < assembler.tokens.len() {
+        assembler.instruction()?;
+    }
+    Ok(assembler.words)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    Id(String),
+    Equals,
+    Word(String),
+    String(String),
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    line: usize,
+    column: usize,
+}
+
+fn error_at(line: usize, column: usize, message: impl AsRef<str>) -> String {
+    format!("{}:{}: {}", line, column, message.as_ref())
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    for (line_index, line) in text.lines().enumerate() {
+        let line_number = line_index + 1;
+        let chars: Vec<char> = line.chars().collect();
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            let column = i + 1;
+            let start = i;
+            let kind = match c {
+                ';' => break,
+                c if c.is_whitespace() => {
+                    i += 1;
+                    continue;
+                }
+                '=' => {
+                    i += 1;
+                    TokenKind::Equals
+                }
+                '"' => {
+                    let mut string = String::new();
+                    i += 1;
+                    loop {
+                        match chars.get(i) {
+                            None => return Err(error_at(line_number, column, "Missing closing '\"' of string")),
+                            Some('"') => break,
+                            Some('\\') if i + 1 < chars.len() => {
+                                string.push(chars[i + 1]);
+                                i += 2;
+                            }
+                            Some(&c) => {
+                                string.push(c);
+                                i += 1;
+                            }
+                        }
+                    }
+                    i += 1;
+                    TokenKind::String(string)
+                }
+                '%' => {
+                    i += 1;
+                    while i < chars.len() && !chars[i].is_whitespace() && !matches!(chars[i], '=' | ';' | '"') {
+                        i += 1;
+                    }
+                    let name: String = chars[start + 1..i].iter().collect();
+                    if name.is_empty() {
+                        return Err(error_at(line_number, column, "Expected a name after '%'"));
+                    }
+                    TokenKind::Id(name)
+                }
+                _ => {
+                    while i < chars.len() && !chars[i].is_whitespace() && !matches!(chars[i], '=' | ';' | '"') {
+                        i += 1;
+                    }
+                    TokenKind::Word(chars[start..i].iter().collect())
+                }
+            };
+            tokens.push(Token {
+                kind,
+                line: line_number,
+                column,
+            });
+        }
+    }
+    Ok(tokens)
+}
+
+/// Numeric ids keep their value, names get the lowest unused ids in order of first appearance.
+fn allocate_ids(tokens: &[Token]) -> Result<HashMap<String, u32>, String> {
+    let mut ids = HashMap::new();
+    let mut reserved = HashSet::new();
+    for token in tokens {
+        if let TokenKind::Id(name) = &token.kind {
+            if let Ok(id) = name.parse::<u32>() {
+                if id == 0 {
+                    return Err(error_at(token.line, token.column, "Id 0 is not valid"));
+                }
+                reserved.insert(id);
+                ids.insert(name.clone(), id);
+            }
+        }
+    }
+    let mut next = 1;
+    for token in tokens {
+        if let TokenKind::Id(name) = &token.kind {
+            if !ids.contains_key(name) {
+                while reserved.contains(&next) {
+                    next += 1;
+                }
+                ids.insert(name.clone(), next);
+                next += 1;
+            }
+        }
+    }
+    Ok(ids)
+}
+
+struct Assembler {
+    tokens: Vec<Token>,
+    pos: usize,
+    ids: HashMap<String, u32>,
+    numbers: HashMap<u32, NumberType>,
+    result_types: HashMap<u32, u32>,
+    ext_imports: HashMap<u32, String>,
+    words: Vec<u32>,
+}
+
+struct InstructionContext {
+    info: &'static OpInfo,
+    /// Token of the opcode, for errors about missing operands
+    line: usize,
+    column: usize,
+    result: Option<u32>,
+    literal_type: Option<NumberType>,
+    /// Previous operand, used to find the instruction set of OpExtInst
+    last_id: u32,
+}
+
+impl Assembler {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    /// True at the end of the text or at the first token of the next instruction.
+    fn at_instruction_end(&self) -> bool {
+        match self.peek().map(|t| &t.kind) {
+            None => true,
+            Some(TokenKind::Id(_)) => matches!(self.tokens.get(self.pos + 1).map(|t| &t.kind), Some(TokenKind::Equals)),
+            // Opcodes are "Op" followed by a capital letter, unlike enums such as OpenCL
+            Some(TokenKind::Word(word)) => {
+                word.starts_with("Op") && word[2..].starts_with(|c: char| c.is_ascii_uppercase())
+            }
+            _ => false,
+        }
+    }
+
+    fn instruction(&mut self) -> Result<(), String> {
+        let mut result = None;
+        let first = self.next().unwrap();
+        let op_token = match &first.kind {
+            TokenKind::Id(name) => {
+                result = Some((self.ids[name], first.clone()));
+                match self.next() {
+                    Some(Token {
+                        kind: TokenKind::Equals,
+                        ..
+                    }) => {}
+                    _ => return Err(error_at(first.line, first.column, format!("Expected '=' after %{}", name))),
+                }
+                match self.next() {
+                    Some(token) => token,
+                    None => return Err(error_at(first.line, first.column, "Expected an opcode after '='")),
+                }
+            }
+            _ => first,
+        };
+        let info = match &op_token.kind {
+            TokenKind::Word(word) => op_info_by_name(word)
+                .ok_or_else(|| error_at(op_token.line, op_token.column, format!("Unknown opcode '{}'", word)))?,
+            _ => return Err(error_at(op_token.line, op_token.column, "Expected an opcode")),
+        };
+        match (&result, info.has_result()) {
+            (Some((_, token)), false) => {
+                return Err(error_at(token.line, token.column, format!("{} doesn't produce a result id", info.name)))
+            }
+            (None, true) => {
+                return Err(error_at(op_token.line, op_token.column, format!("Expected '%id =' before {}", info.name)))
+            }
+            _ => {}
+        }
+
+        let mut ctx = InstructionContext {
+            info,
+            line: op_token.line,
+            column: op_token.column,
+            result: result.map(|(id, _)| id),
+            literal_type: None,
+            last_id: 0,
+        };
+        let start = self.words.len();
+        self.words.push(0);
+        self.operands(&mut ctx, info.spec)?;
+        if !self.at_instruction_end() {
+            let token = self.peek().unwrap();
+            return Err(error_at(token.line, token.column, format!("Too many operands for {}", info.name)));
+        }
+        let word_count = self.words.len() - start;
+        if word_count > 0xffff {
+            return Err(error_at(ctx.line, ctx.column, format!("{} is too long ({} words)", info.name, word_count)));
+        }
+        self.words[start] = (word_count as u32) << 16 | info.opcode;
+        self.record_types(start);
+        Ok(())
+    }
+
+    /// Remembers types and result types needed to encode later literals.
+    fn record_types(&mut self, start: usize) {
+        let info = op_info(self.words[start] & 0xffff).unwrap();
+        let ops = &self.words[start + 1..];
+        match info.name {
+            "OpTypeInt" => {
+                self.numbers.insert(
+                    ops[0],
+                    NumberType::Int {
+                        width: ops[1],
+                        signed: ops[2] != 0,
+                    },
+                );
+            }
+            "OpTypeFloat" => {
+                self.numbers.insert(
+                    ops[0],
+                    NumberType::Float {
+                        width: ops[1],
+                    },
+                );
+            }
+            "OpExtInstImport" => {
+                let bytes: Vec<u8> = ops[1..].iter().flat_map(|w| w.to_le_bytes()).take_while(|b| *b != 0).collect();
+                self.ext_imports.insert(ops[0], String::from_utf8_lossy(&bytes).into_owned());
+            }
+            _ if info.has_result_type() => {
+                self.result_types.insert(ops[1], ops[0]);
+            }
+            _ => {}
+        }
+    }
+
+    fn operands(&mut self, ctx: &mut InstructionContext, spec: &'static str) -> Result<(), String> {
+        for operand in parse_operands(spec) {
+            match operand.quantifier {
+                Quantifier::One => self.operand(ctx, operand.kind)?,
+                Quantifier::Optional => {
+                    if !self.at_instruction_end() {
+                        self.operand(ctx, operand.kind)?;
+                    }
+                }
+                Quantifier::Variadic => {
+                    while !self.at_instruction_end() {
+                        self.operand(ctx, operand.kind)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn expect(&mut self, ctx: &InstructionContext, what: &str) -> Result<Token, String> {
+        if self.at_instruction_end() {
+            return Err(error_at(ctx.line, ctx.column, format!("Missing {} operand of {}", what, ctx.info.name)));
+        }
+        Ok(self.next().unwrap())
+    }
+
+    fn id(&mut self, ctx: &mut InstructionContext) -> Result<u32, String> {
+        let token = self.expect(ctx, "id")?;
+        match &token.kind {
+            TokenKind::Id(name) => {
+                ctx.last_id = self.ids[name];
+                Ok(ctx.last_id)
+            }
+            _ => Err(error_at(token.line, token.column, format!("Expected an id, found {}", describe(&token.kind)))),
+        }
+    }
+
+    fn operand(&mut self, ctx: &mut InstructionContext, kind: OperandKind) -> Result<(), String> {
+        match kind {
+            OperandKind::ResultType => {
+                let ty = self.id(ctx)?;
+                if matches!(ctx.info.name, "OpConstant" | "OpSpecConstant") {
+                    ctx.literal_type = self.numbers.get(&ty).copied();
+                }
+                self.words.push(ty);
+            }
+            OperandKind::Result => self.words.push(ctx.result.unwrap()),
+            OperandKind::Id => {
+                let id = self.id(ctx)?;
+                if ctx.info.name == "OpSwitch" && ctx.literal_type.is_none() {
+                    ctx.literal_type = self.result_types.get(&id).and_then(|ty| self.numbers.get(ty)).copied();
+                }
+                self.words.push(id);
+            }
+            OperandKind::Literal => {
+                let token = self.expect(ctx, "literal number")?;
+                let value = match &token.kind {
+                    TokenKind::Word(word) => parse_integer(word)
+                        .filter(|v| (i32::MIN as i128..=u32::MAX as i128).contains(v))
+                        .map(|v| v as u32),
+                    _ => None,
+                };
+                let value = value.ok_or_else(|| {
+                    error_at(
+                        token.line,
+                        token.column,
+                        format!("Expected a 32-bit literal number, found {}", describe(&token.kind)),
+                    )
+                })?;
+                self.words.push(value);
+            }
+            OperandKind::String => {
+                let token = self.expect(ctx, "string")?;
+                match &token.kind {
+                    TokenKind::String(string) => {
+                        let mut bytes = string.as_bytes().to_vec();
+                        bytes.resize(bytes.len() / 4 * 4 + 4, 0);
+                        self.words.extend(bytes.chunks(4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]])));
+                    }
+                    _ => {
+                        return Err(error_at(
+                            token.line,
+                            token.column,
+                            format!("Expected a string, found {}", describe(&token.kind)),
+                        ))
+                    }
+                }
+            }
+            OperandKind::ContextLiteral => {
+                let token = self.expect(ctx, "literal number")?;
+                let ty = ctx.literal_type.ok_or_else(|| {
+                    error_at(
+                        token.line,
+                        token.column,
+                        format!("The type of the literal of {} is not a scalar number", ctx.info.name),
+                    )
+                })?;
+                let words = match &token.kind {
+                    TokenKind::Word(word) => encode_number(word, ty),
+                    _ => Err(format!("Expected a literal number, found {}", describe(&token.kind))),
+                }
+                .map_err(|e| error_at(token.line, token.column, e))?;
+                self.words.extend(words);
+            }
+            OperandKind::ExtInst => {
+                let token = self.expect(ctx, "extended instruction")?;
+                let glsl = self.ext_imports.get(&ctx.last_id).is_some_and(|name| name == "GLSL.std.450");
+                let number = match &token.kind {
+                    TokenKind::Word(word) => parse_integer(word)
+                        .and_then(|v| u32::try_from(v).ok())
+                        .or_else(|| GLSL_STD_450.iter().position(|name| glsl && name == word).map(|i| i as u32)),
+                    _ => None,
+                };
+                let number = number.ok_or_else(|| {
+                    error_at(
+                        token.line,
+                        token.column,
+                        format!("Unknown extended instruction {}", describe(&token.kind)),
+                    )
+                })?;
+                self.words.push(number);
+            }
+            OperandKind::SpecConstantOp => {
+                let token = self.expect(ctx, "opcode")?;
+                let info = match &token.kind {
+                    TokenKind::Word(word) => op_info_by_name(&format!("Op{}", word)),
+                    _ => None,
+                }
+                .ok_or_else(|| {
+                    error_at(token.line, token.column, format!("Unknown opcode {}", describe(&token.kind)))
+                })?;
+                self.words.push(info.opcode);
+                let spec = info.spec.trim_start_matches("T R").trim_start();
+                self.operands(ctx, spec)?;
+            }
+            OperandKind::LiteralId => {
+                self.operand(ctx, OperandKind::ContextLiteral)?;
+                self.operand(ctx, OperandKind::Id)?;
+            }
+            OperandKind::IdId => {
+                self.operand(ctx, OperandKind::Id)?;
+                self.operand(ctx, OperandKind::Id)?;
+            }
+            OperandKind::IdLiteral => {
+                self.operand(ctx, OperandKind::Id)?;
+                self.operand(ctx, OperandKind::Literal)?;
+            }
+            OperandKind::Enum(kind) => {
+                let token = self.expect(ctx, "enum")?;
+                let value = match &token.kind {
+                    TokenKind::Word(word) => parse_enum(kind, word),
+                    _ => Err(format!("Expected {:?}, found {}", kind, describe(&token.kind))),
+                }
+                .map_err(|e| error_at(token.line, token.column, e))?;
+                self.words.push(value);
+                if kind.is_mask() {
+                    for bit in (0..32).map(|i| 1 << i).filter(|bit| value & bit != 0) {
+                        self.operands(ctx, kind.parameters(bit))?;
+                    }
+                } else {
+                    self.operands(ctx, kind.parameters(value))?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn describe(kind: &TokenKind) -> String {
+    match kind {
+        TokenKind::Id(name) => format!("'%{}'", name),
+        TokenKind::Equals => "'='".to_string(),
+        TokenKind::Word(word) => format!("'{}'", word),
+        TokenKind::String(string) => format!("\"{}\"", string),
+    }
+}
+
+fn parse_enum(kind: EnumKind, word: &str) -> Result<u32, String> {
+    let mut value = 0;
+    for name in word.split('|') {
+        let v = match kind.value(name) {
+            Some(v) => v,
+            None => match parse_integer(name).and_then(|v| u32::try_from(v).ok()) {
+                Some(v) => v,
+                None => return Err(format!("Invalid {:?} '{}'", kind, name)),
+            },
+        };
+        if !kind.is_mask() && word.contains('|') {
+            return Err(format!("{:?} is not a mask and can't combine '{}'", kind, word));
+        }
+        value |= v;
+    }
+    Ok(value)
+}
+
+/// Decimal or hexadecimal (0x) integer, optionally negative.
+fn parse_integer(word: &str) -> Option<i128> {
+    let (negative, digits) = match word.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, word),
+    };
+    let value = match digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        Some(hex) => i128::from_str_radix(hex, 16).ok()?,
+        None if !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()) => digits.parse().ok()?,
+        None => return None,
+    };
+    Some(if negative {
+        -value
+    } else {
+        value
+    })
+}
+
+fn encode_number(word: &str, ty: NumberType) -> Result<Vec<u32>, String> {
+    let bits = match ty {
+        NumberType::Int {
+            width,
+            signed,
+        } => {
+            let value = parse_integer(word).ok_or_else(|| format!("Invalid integer '{}'", word))?;
+            let (min, max) = if signed {
+                (-(1i128 << (width - 1)), (1i128 << (width - 1)) - 1)
+            } else {
+                (0, (1i128 << width) - 1)
+            };
+            if value < min || value > max {
+                return Err(format!("Integer {} doesn't fit in {} bits", word, width));
+            }
+            // Narrow signed values are sign extended to the whole word
+            value as i64 as u64
+        }
+        NumberType::Float {
+            width,
+        } => {
+            let (mantissa_bits, exponent_bits) = match width {
+                16 => (10, 5),
+                32 => (23, 8),
+                64 => (52, 11),
+                _ => return Err(format!("Unsupported float width {}", width)),
+            };
+            if word.trim_start_matches('-').starts_with("0x") {
+                parse_hex_float(word, mantissa_bits, exponent_bits)?
+            } else {
+                let value: f64 = word.parse().map_err(|_| format!("Invalid float '{}'", word))?;
+                match width {
+                    16 => f32_to_f16(value as f32) as u64,
+                    32 => (value as f32).to_bits() as u64,
+                    _ => value.to_bits(),
+                }
+            }
+        }
+    };
+    Ok(match ty.word_count() {
+        1 => vec![bits as u32],
+        _ => vec![bits as u32, (bits >> 32) as u32],
+    })
+}
+
+/// Parses a hex float ("-0x1.8p+3") exactly into the bits of a float format, including the
+/// Inf/NaN and subnormal encodings printed by the disassembler.
+fn parse_hex_float(word: &str, mantissa_bits: u32, exponent_bits: u32) -> Result<u64, String> {
+    let invalid = || format!("Invalid hex float '{}'", word);
+    let (negative, rest) = match word.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, word),
+    };
+    let rest = rest.strip_prefix("0x").ok_or_else(invalid)?;
+    let (digits, exponent) = match rest.split_once(['p', 'P']) {
+        Some((digits, exponent)) => (digits, exponent.parse::<i32>().map_err(|_| invalid())?),
+        None => (rest, 0),
+    };
+    let (integer, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+    if integer.is_empty() || integer.len() + fraction.len() > 15 {
+        return Err(invalid());
+    }
+    let mantissa = u64::from_str_radix(&format!("{}{}", integer, fraction), 16).map_err(|_| invalid())?;
+    let sign = (negative as u64) << (mantissa_bits + exponent_bits);
+    if mantissa == 0 {
+        return Ok(sign);
+    }
+
+    // value = mantissa * 2^(exponent - 4 * fraction digits), normalized to 1.f * 2^e
+    let top = 63 - mantissa.leading_zeros() as i32;
+    let e = top + exponent - 4 * fraction.len() as i32;
+    let bias = (1 << (exponent_bits - 1)) - 1;
+    let (biased, shift) = if e > bias {
+        // Inf and NaN
+        ((1 << exponent_bits) - 1, top - mantissa_bits as i32)
+    } else if e >= 1 - bias {
+        (e + bias, top - mantissa_bits as i32)
+    } else {
+        // Subnormal
+        (0, top - mantissa_bits as i32 + (1 - bias - e))
+    };
+    let fraction_bits = if shift >= 0 {
+        if shift >= 64 || mantissa & ((1 << shift) - 1) != 0 {
+            return Err(format!("Hex float '{}' is not exactly representable", word));
+        }
+        mantissa >> shift
+    } else {
+        mantissa << -shift
+    };
+    let fraction_bits = if biased == 0 {
+        fraction_bits
+    } else {
+        fraction_bits & ((1 << mantissa_bits) - 1)
+    };
+    Ok(sign | (biased as u64) << mantissa_bits | fraction_bits)
+}
+
+/// Converts to half precision, rounding to nearest even.
+pub fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+    if exponent == 0xff {
+        return sign
+            | 0x7c00
+            | if mantissa != 0 {
+                0x200 | (mantissa >> 13) as u16
+            } else {
+                0
+            };
+    }
+    let round = |value: u32, shift: u32| {
+        let rest = value & ((1 << shift) - 1);
+        let half = 1 << (shift - 1);
+        let truncated = value >> shift;
+        if rest > half || (rest == half && truncated & 1 != 0) {
+            truncated + 1
+        } else {
+            truncated
+        }
+    };
+    let e = exponent - 127 + 15;
+    if e >= 31 {
+        sign | 0x7c00
+    } else if e <= 0 {
+        if e < -10 {
+            return sign;
+        }
+        sign | round(mantissa | 0x80_0000, (14 - e) as u32) as u16
+    } else {
+        // A carry out of the mantissa correctly bumps the exponent
+        sign | round((e as u32) << 23 | mantissa, 13) as u16
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spirv::Instruction;
+    use crate::spirv_dis::{disassemble, DisassembleOptions};
+
+    const SHADER: &str = r#"               OpCapability Shader
+          %1 = OpExtInstImport "GLSL.std.450"
+               OpMemoryModel Logical GLSL450
+               OpEntryPoint GLCompute %main "main" %gl_GlobalInvocationID
+               OpExecutionMode %main LocalSize 64 1 1
+               OpName %main "main"
+               OpName %gl_GlobalInvocationID "gl_GlobalInvocationID"
+               OpName %data "data"
+               OpDecorate %gl_GlobalInvocationID BuiltIn GlobalInvocationId
+               OpDecorate %_runtimearr_float ArrayStride 4
+               OpDecorate %_struct_8 BufferBlock
+               OpMemberDecorate %_struct_8 0 Offset 0
+               OpDecorate %data DescriptorSet 0
+               OpDecorate %data Binding 1
+       %void = OpTypeVoid
+          %3 = OpTypeFunction %void
+       %uint = OpTypeInt 32 0
+     %v3uint = OpTypeVector %uint 3
+%_ptr_Input_v3uint = OpTypePointer Input %v3uint
+%gl_GlobalInvocationID = OpVariable %_ptr_Input_v3uint Input
+      %float = OpTypeFloat 32
+%_runtimearr_float = OpTypeRuntimeArray %float
+  %_struct_8 = OpTypeStruct %_runtimearr_float
+%_ptr_Uniform__struct_8 = OpTypePointer Uniform %_struct_8
+       %data = OpVariable %_ptr_Uniform__struct_8 Uniform
+        %int = OpTypeInt 32 1
+      %int_0 = OpConstant %int 0
+     %int_n1 = OpConstant %int -1
+  %float_0_5 = OpConstant %float 0.5
+%float_0x1p_128 = OpConstant %float 0x1p+128
+     %uint_0 = OpConstant %uint 0
+%_ptr_Input_uint = OpTypePointer Input %uint
+%_ptr_Uniform_float = OpTypePointer Uniform %float
+       %main = OpFunction %void None %3
+          %5 = OpLabel
+         %22 = OpAccessChain %_ptr_Input_uint %gl_GlobalInvocationID %uint_0
+         %23 = OpLoad %uint %22 Aligned 4
+         %24 = OpAccessChain %_ptr_Uniform_float %data %int_0 %23
+         %25 = OpLoad %float %24
+         %26 = OpExtInst %float %1 Sqrt %25
+         %27 = OpFMul %float %26 %float_0_5
+               OpStore %24 %27 Volatile|Aligned 4
+               OpSelectionMerge %28 None
+               OpSwitch %int_n1 %28 -1 %29 7 %28
+         %29 = OpLabel
+               OpBranch %28
+         %28 = OpLabel
+               OpReturn
+               OpFunctionEnd
+"#;
+
+    #[test]
+    fn round_trip_with_disassembler() {
+        let words = assemble_words(SHADER).unwrap();
+        let options = DisassembleOptions {
+            header: false,
+            ..Default::default()
+        };
+        assert_eq!(disassemble(&words, &options).unwrap(), SHADER);
+        assert_eq!(words[3], 31);
+
+        let module = assemble(SHADER).unwrap();
+        assert_eq!(module.to_words().unwrap(), words);
+        assert!(module.instructions.contains(&Instruction::OpExecutionMode {
+            opcode: 16,
+            entry_point: 2,
+            mode: crate::spirv::ExecutionMode::LocalSize,
+            literals: vec![64, 1, 1],
+        }));
+    }
+
+    #[test]
+    fn ids_and_literals() {
+        let words = assemble_words(
+            "%a = OpTypeInt 64 1 ; comment\n%7 = OpConstant %a -2\n%b = OpTypeFloat 16\n%c = OpConstant %b 1.5\n\
+             %d = OpTypeFloat 64 %e = OpConstant %d 0x1.8p-1 %f = OpString \"a \\\"quoted\\\" \\\\ string\"",
+        )
+        .unwrap();
+        let tail = &words[5..];
+        assert_eq!(&tail[..4], &[4 << 16 | 21, 1, 64, 1]);
+        assert_eq!(&tail[4..9], &[5 << 16 | 43, 1, 7, 0xffff_fffe, 0xffff_ffff]);
+        assert_eq!(&tail[12..16], &[4 << 16 | 43, 2, 3, 0x3e00]);
+        assert_eq!(&tail[19..24], &[5 << 16 | 43, 4, 5, 0, 0x3fe8_0000]);
+        let (string, _) = crate::spirv_dis::decode_string(&tail[26..]).unwrap();
+        assert_eq!(string, "a \"quoted\" \\ string");
+        assert_eq!(words[3], 8);
+
+        assert_eq!(parse_hex_float("0x1p-149", 23, 8), Ok(1));
+        assert_eq!(parse_hex_float("-0x1.8p+128", 23, 8), Ok(0xffc0_0000));
+        assert!(parse_hex_float("0x1.000001p+0", 23, 8).is_err());
+        assert_eq!(f32_to_f16(65504.0), 0x7bff);
+        assert_eq!(f32_to_f16(1e6), 0x7c00);
+        assert_eq!(f32_to_f16(5.960464e-8), 1);
+    }
+
+    #[test]
+    fn errors() {
+        let error = |text: &str| assemble_words(text).unwrap_err();
+        assert_eq!(error("OpCapability Shader\n  OpFoo"), "2:3: Unknown opcode 'OpFoo'");
+        assert_eq!(error("OpCapability Shaders"), "1:14: Invalid Capability 'Shaders'");
+        assert_eq!(error("OpTypeVoid"), "1:1: Expected '%id =' before OpTypeVoid");
+        assert_eq!(error("%1 = OpReturn"), "1:1: OpReturn doesn't produce a result id");
+        assert_eq!(error("%1 = OpTypeInt 32"), "1:6: Missing literal number operand of OpTypeInt");
+        assert_eq!(error("%1 = OpTypeInt 32 1 2"), "1:21: Too many operands for OpTypeInt");
+        assert_eq!(error("%1 = OpTypeInt 8 0\n%2 = OpConstant %1 256"), "2:20: Integer 256 doesn't fit in 8 bits");
+        assert_eq!(
+            error("%1 = OpTypeBool\n%2 = OpConstant %1 1"),
+            "2:20: The type of the literal of OpConstant is not a scalar number"
+        );
+        assert_eq!(error("OpName %x \"x"), "1:11: Missing closing '\"' of string");
+        assert_eq!(error("OpDecorate %x Location %y"), "1:24: Expected a 32-bit literal number, found '%y'");
+        assert_eq!(error("%x OpTypeVoid"), "1:1: Expected '=' after %x");
+        assert_eq!(error("OpSelectionMerge %1 Flatten|Foo"), "1:21: Invalid SelectionControl 'Foo'");
+    }
+}
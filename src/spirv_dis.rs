@@ -0,0 +1,767 @@
+use crate::spirv_grammar::*;
+use crate::spirv_val;
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+// SPIR-V disassembler:
+// Prints a module in the textual form used by spirv-dis (SPIRV-Tools), so that the output of both
+// can be diffed. Instructions are decoded from the raw words with the operand layouts from
+// `spirv_grammar`, which keeps the output lossless for opcodes `spirv::Instruction` only partially
+// decodes.
+
+pub const MAGIC: u32 = 0x07230203;
+
+/// Column at which opcodes start when indenting, like spirv-dis.
+const INDENT: usize = 15;
+
+#[derive(Debug, Clone)]
+pub struct DisassembleOptions {
+    /// Print ids with names from OpName, types and constants instead of numbers.
+    pub friendly_names: bool,
+    /// Right-align result ids so that all opcodes start in the same column.
+    pub indent: bool,
+    /// Print the module header as comments.
+    pub header: bool,
+}
+
+impl Default for DisassembleOptions {
+    fn default() -> Self {
+        Self {
+            friendly_names: true,
+            indent: true,
+            header: true,
+        }
+    }
+}
+
+/// Converts the bytes of a module to words, in either byte order.
+pub fn words_from_bytes(bytes: &[u8]) -> Result<Vec<u32>, String> {
+    spirv_val::words_from_bytes(bytes).map_err(|e| e.to_string())
+}
+
+pub fn disassemble_bytes(bytes: &[u8], options: &DisassembleOptions) -> Result<String, String> {
+    disassemble(&words_from_bytes(bytes)?, options)
+}
+
+pub fn disassemble(words: &[u32], options: &DisassembleOptions) -> Result<String, String> {
+    if words.len() < 5 {
+        return Err("Module is too short for a SPIR-V header.".to_string());
+    }
+    if words[0] != MAGIC {
+        return Err(format!("Magic number should be 0x{:08x}.", MAGIC));
+    }
+    let instructions = split_instructions(&words[5..])?;
+
+    let mut out = String::new();
+    if options.header {
+        let (version, generator, bound, schema) = (words[1], words[2], words[3], words[4]);
+        writeln!(out, "; SPIR-V").unwrap();
+        writeln!(out, "; Version: {}.{}", (version >> 16) & 0xff, (version >> 8) & 0xff).unwrap();
+        match generator_vendor(generator >> 16) {
+            Some(vendor) => writeln!(out, "; Generator: {}; {}", vendor, generator & 0xffff).unwrap(),
+            None => writeln!(out, "; Generator: Unknown({}); {}", generator >> 16, generator & 0xffff).unwrap(),
+        }
+        writeln!(out, "; Bound: {}", bound).unwrap();
+        writeln!(out, "; Schema: {}", schema).unwrap();
+    }
+
+    let mut dis = Disassembler::default();
+    dis.collect_types(&instructions)?;
+    if options.friendly_names {
+        dis.assign_names(&instructions)?;
+    }
+    for inst in &instructions {
+        let (result, text) = dis.instruction(inst)?;
+        match result {
+            Some(result) => {
+                let result = format!("{} = ", result);
+                if options.indent {
+                    write!(out, "{:>width$}", result, width = INDENT).unwrap();
+                } else {
+                    out.push_str(&result);
+                }
+            }
+            None if options.indent => out.push_str(&" ".repeat(INDENT)),
+            None => {}
+        }
+        out.push_str(&text);
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+fn generator_vendor(vendor: u32) -> Option<&'static str> {
+    Some(match vendor {
+        0 => "Khronos",
+        1 => "LunarG",
+        2 => "Valve",
+        3 => "Codeplay",
+        4 => "NVIDIA",
+        5 => "ARM",
+        6 => "Khronos LLVM/SPIR-V Translator",
+        7 => "Khronos SPIR-V Tools Assembler",
+        8 => "Khronos Glslang Reference Front End",
+        9 => "Qualcomm",
+        10 => "AMD",
+        11 => "Intel",
+        12 => "Imagination",
+        13 => "Google Shaderc over Glslang",
+        14 => "Google spiregg",
+        15 => "Google rspirv",
+        16 => "X-LEGEND Mesa-IR/SPIR-V Translator",
+        17 => "Khronos SPIR-V Tools Linker",
+        18 => "Wine VKD3D Shader Compiler",
+        19 => "Clay Clay Shader Compiler",
+        20 => "W3C WebGPU Group WHLSL Shader Translator",
+        21 => "Google Clspv",
+        22 => "Google MLIR SPIR-V Serializer",
+        23 => "Google Tint Compiler",
+        24 => "Google ANGLE Shader Compiler",
+        25 => "Netease Games Messiah Shader Compiler",
+        26 => "Xenia Xenia Emulator Microcode Translator",
+        27 => "Embark Studios Rust GPU Compiler Backend",
+        28 => "gfx-rs community Naga",
+        _ => return None,
+    })
+}
+
+pub struct RawInstruction<'a> {
+    /// Offset of the first word, counted from the start of the module
+    pub offset: usize,
+    pub info: &'static OpInfo,
+    pub operands: &'a [u32],
+}
+
+/// Splits the words following the header into instructions.
+pub fn split_instructions(words: &[u32]) -> Result<Vec<RawInstruction<'_>>, String> {
+    let mut instructions = vec![];
+    let mut i = 0;
+    while i < words.len() {
+        let offset = i + 5;
+        let word_count = (words[i] >> 16) as usize;
+        let opcode = words[i] & 0xffff;
+        if word_count == 0 {
+            return Err(format!("Instruction with a word count of 0 at word {}.", offset));
+        }
+        if i + word_count > words.len() {
+            return Err(format!("Instruction at word {} runs past the end of the module.", offset));
+        }
+        let info = op_info(opcode).ok_or_else(|| format!("Invalid opcode {} at word {}.", opcode, offset))?;
+        instructions.push(RawInstruction {
+            offset,
+            info,
+            operands: &words[i + 1..i + word_count],
+        });
+        i += word_count;
+    }
+    Ok(instructions)
+}
+
+/// Decodes a nul-terminated literal string, returning it and the number of words it occupies.
+pub fn decode_string(words: &[u32]) -> Option<(String, usize)> {
+    let mut bytes = vec![];
+    for (i, word) in words.iter().enumerate() {
+        for b in word.to_le_bytes() {
+            if b == 0 {
+                return Some((String::from_utf8_lossy(&bytes).into_owned(), i + 1));
+            }
+            bytes.push(b);
+        }
+    }
+    None
+}
+
+#[derive(Default)]
+struct Disassembler {
+    names: HashMap<u32, String>,
+    used_names: HashSet<String>,
+    /// Scalar int and float types
+    numbers: HashMap<u32, NumberType>,
+    /// Result type of every typed result
+    result_types: HashMap<u32, u32>,
+    glsl_std_450: HashSet<u32>,
+}
+
+impl Disassembler {
+    fn collect_types(&mut self, instructions: &[RawInstruction]) -> Result<(), String> {
+        for inst in instructions {
+            let ops = inst.operands;
+            let missing = || format!("Missing operands for {} at word {}.", inst.info.name, inst.offset);
+            match inst.info.name {
+                "OpTypeInt" => {
+                    let [id, width, signed] = ops[..] else {
+                        return Err(missing());
+                    };
+                    self.numbers.insert(
+                        id,
+                        NumberType::Int {
+                            width,
+                            signed: signed != 0,
+                        },
+                    );
+                }
+                "OpTypeFloat" => {
+                    let [id, width, ..] = ops[..] else {
+                        return Err(missing());
+                    };
+                    self.numbers.insert(
+                        id,
+                        NumberType::Float {
+                            width,
+                        },
+                    );
+                }
+                "OpExtInstImport" => {
+                    let (name, _) = ops.get(1..).and_then(decode_string).ok_or_else(missing)?;
+                    if name == "GLSL.std.450" {
+                        self.glsl_std_450.insert(ops[0]);
+                    }
+                }
+                _ if inst.info.has_result_type() => {
+                    let [ty, id, ..] = ops[..] else {
+                        return Err(missing());
+                    };
+                    self.result_types.insert(id, ty);
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Friendly names, following the rules of spirv-dis. Names from OpName take precedence since
+    /// they come before any type or constant.
+    fn assign_names(&mut self, instructions: &[RawInstruction]) -> Result<(), String> {
+        for inst in instructions {
+            let ops = inst.operands;
+            let name = match inst.info.name {
+                "OpName" if ops.len() >= 2 => decode_string(&ops[1..]).map(|(name, _)| name),
+                "OpTypeVoid" => Some("void".to_string()),
+                "OpTypeBool" => Some("bool".to_string()),
+                "OpTypeInt" => Some(match self.numbers[&ops[0]] {
+                    NumberType::Int {
+                        width: 32,
+                        signed,
+                    } => if signed {
+                        "int"
+                    } else {
+                        "uint"
+                    }
+                    .to_string(),
+                    NumberType::Int {
+                        width,
+                        signed,
+                    } => {
+                        let name = match width {
+                            8 => "char".to_string(),
+                            16 => "short".to_string(),
+                            64 => "long".to_string(),
+                            _ => format!("i{}", width),
+                        };
+                        if signed {
+                            name
+                        } else {
+                            format!("u{}", name)
+                        }
+                    }
+                    NumberType::Float {
+                        ..
+                    } => unreachable!(),
+                }),
+                "OpTypeFloat" => Some(match ops[1] {
+                    16 => "half".to_string(),
+                    32 => "float".to_string(),
+                    64 => "double".to_string(),
+                    width => format!("fp{}", width),
+                }),
+                "OpTypeVector" if ops.len() == 3 => Some(format!("v{}{}", ops[2], self.name(ops[1]))),
+                "OpTypeMatrix" if ops.len() == 3 => Some(format!("mat{}{}", ops[2], self.name(ops[1]))),
+                "OpTypeArray" if ops.len() == 3 => Some(format!("_arr_{}_{}", self.name(ops[1]), self.name(ops[2]))),
+                "OpTypeRuntimeArray" if ops.len() == 2 => Some(format!("_runtimearr_{}", self.name(ops[1]))),
+                "OpTypeStruct" => Some(format!("_struct_{}", ops[0])),
+                "OpTypePointer" if ops.len() == 3 => {
+                    let storage = EnumKind::StorageClass.name(ops[1]).map(str::to_string).unwrap_or(ops[1].to_string());
+                    Some(format!("_ptr_{}_{}", storage, self.name(ops[2])))
+                }
+                "OpConstantTrue" => Some("true".to_string()),
+                "OpConstantFalse" => Some("false".to_string()),
+                "OpConstant" if ops.len() >= 3 => self.numbers.get(&ops[0]).map(|ty| {
+                    let value = format_number(Some(*ty), &ops[2..]).replace('-', "n");
+                    format!("{}_{}", self.name(ops[0]), value)
+                }),
+                _ => None,
+            };
+            let target = match inst.info.name {
+                "OpName" => ops[0],
+                _ if inst.info.has_result_type() => ops[1],
+                _ => ops.first().copied().unwrap_or(0),
+            };
+            if let Some(name) = name {
+                self.save_name(target, &name);
+            }
+        }
+        Ok(())
+    }
+
+    fn save_name(&mut self, id: u32, name: &str) {
+        if self.names.contains_key(&id) {
+            return;
+        }
+        let mut name: String = name
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '_' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        if name.is_empty() {
+            name.push('_');
+        }
+        if self.used_names.contains(&name) {
+            name = (0..).map(|i| format!("{}_{}", name, i)).find(|n| !self.used_names.contains(n)).unwrap();
+        }
+        self.used_names.insert(name.clone());
+        self.names.insert(id, name);
+    }
+
+    fn name(&self, id: u32) -> String {
+        self.names.get(&id).cloned().unwrap_or_else(|| id.to_string())
+    }
+
+    fn id(&self, id: u32) -> String {
+        format!("%{}", self.name(id))
+    }
+
+    /// Returns the result id and the text of an instruction.
+    fn instruction(&self, inst: &RawInstruction) -> Result<(Option<String>, String), String> {
+        let ops = inst.operands;
+        // Width of context dependent literals
+        let literal_type = match inst.info.name {
+            "OpConstant" | "OpSpecConstant" => ops.first().and_then(|ty| self.numbers.get(ty)),
+            "OpSwitch" => ops.first().and_then(|id| self.result_types.get(id)).and_then(|ty| self.numbers.get(ty)),
+            _ => None,
+        }
+        .copied();
+
+        let mut printer = OperandPrinter {
+            dis: self,
+            inst,
+            cursor: 0,
+            literal_type,
+            result: None,
+            text: inst.info.name.to_string(),
+        };
+        printer.operands(inst.info.spec)?;
+        if printer.cursor < ops.len() {
+            return Err(format!("Too many operands for {} at word {}.", inst.info.name, inst.offset));
+        }
+        Ok((printer.result, printer.text))
+    }
+}
+
+struct OperandPrinter<'a, 'b> {
+    dis: &'a Disassembler,
+    inst: &'a RawInstruction<'b>,
+    cursor: usize,
+    literal_type: Option<NumberType>,
+    result: Option<String>,
+    text: String,
+}
+
+impl OperandPrinter<'_, '_> {
+    fn operands(&mut self, spec: &'static str) -> Result<(), String> {
+        for operand in parse_operands(spec) {
+            match operand.quantifier {
+                Quantifier::One => self.operand(operand.kind)?,
+                Quantifier::Optional => {
+                    if self.cursor < self.inst.operands.len() {
+                        self.operand(operand.kind)?;
+                    }
+                }
+                Quantifier::Variadic => {
+                    while self.cursor < self.inst.operands.len() {
+                        self.operand(operand.kind)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn word(&mut self) -> Result<u32, String> {
+        let word = self
+            .inst
+            .operands
+            .get(self.cursor)
+            .copied()
+            .ok_or_else(|| format!("Missing operands for {} at word {}.", self.inst.info.name, self.inst.offset))?;
+        self.cursor += 1;
+        Ok(word)
+    }
+
+    fn push(&mut self, text: &str) {
+        self.text.push(' ');
+        self.text.push_str(text);
+    }
+
+    fn operand(&mut self, kind: OperandKind) -> Result<(), String> {
+        match kind {
+            OperandKind::ResultType | OperandKind::Id => {
+                let id = self.dis.id(self.word()?);
+                self.push(&id);
+            }
+            OperandKind::Result => self.result = Some(self.dis.id(self.word()?)),
+            OperandKind::Literal => {
+                let value = self.word()?;
+                self.push(&value.to_string());
+            }
+            OperandKind::String => {
+                let (string, len) = decode_string(&self.inst.operands[self.cursor..]).ok_or_else(|| {
+                    format!("Unterminated string in {} at word {}.", self.inst.info.name, self.inst.offset)
+                })?;
+                self.cursor += len;
+                self.push(&quote(&string));
+            }
+            OperandKind::ContextLiteral => self.literal_number()?,
+            OperandKind::ExtInst => {
+                let set = self.inst.operands[self.cursor - 1];
+                let number = self.word()?;
+                match GLSL_STD_450.get(number as usize) {
+                    Some(name) if self.dis.glsl_std_450.contains(&set) => self.push(name),
+                    _ => self.push(&number.to_string()),
+                }
+            }
+            OperandKind::SpecConstantOp => {
+                let opcode = self.word()?;
+                let info = op_info(opcode).ok_or_else(|| {
+                    format!("Invalid opcode {} in OpSpecConstantOp at word {}.", opcode, self.inst.offset)
+                })?;
+                self.push(info.name.strip_prefix("Op").unwrap_or(info.name));
+                let spec = info.spec.trim_start_matches("T R").trim_start();
+                self.operands(spec)?;
+            }
+            OperandKind::LiteralId => {
+                self.literal_number()?;
+                self.operand(OperandKind::Id)?;
+            }
+            OperandKind::IdId => {
+                self.operand(OperandKind::Id)?;
+                self.operand(OperandKind::Id)?;
+            }
+            OperandKind::IdLiteral => {
+                self.operand(OperandKind::Id)?;
+                self.operand(OperandKind::Literal)?;
+            }
+            OperandKind::Enum(kind) => {
+                let value = self.word()?;
+                if kind.is_mask() {
+                    if value == 0 {
+                        self.push("None");
+                        return Ok(());
+                    }
+                    let bits: Vec<u32> = (0..32).map(|i| 1 << i).filter(|bit| value & bit != 0).collect();
+                    let names: Vec<String> = bits
+                        .iter()
+                        .map(|&bit| kind.name(bit).map(str::to_string).unwrap_or(format!("0x{:x}", bit)))
+                        .collect();
+                    self.push(&names.join("|"));
+                    for bit in bits {
+                        self.operands(kind.parameters(bit))?;
+                    }
+                } else {
+                    self.push(&kind.name(value).map(str::to_string).unwrap_or(value.to_string()));
+                    self.operands(kind.parameters(value))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn literal_number(&mut self) -> Result<(), String> {
+        let count = self.literal_type.map_or(1, |ty| ty.word_count());
+        let words = self
+            .inst
+            .operands
+            .get(self.cursor..self.cursor + count)
+            .ok_or_else(|| format!("Missing operands for {} at word {}.", self.inst.info.name, self.inst.offset))?;
+        self.cursor += count;
+        let text = format_number(self.literal_type, words);
+        self.push(&text);
+        Ok(())
+    }
+}
+
+fn quote(string: &str) -> String {
+    let mut quoted = String::with_capacity(string.len() + 2);
+    quoted.push('"');
+    for c in string.chars() {
+        if c == '"' || c == '\\' {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn format_number(ty: Option<NumberType>, words: &[u32]) -> String {
+    let bits = words.iter().rev().fold(0u64, |acc, &w| acc << 32 | w as u64);
+    match ty {
+        Some(NumberType::Int {
+            width,
+            signed: true,
+        }) if width < 64 => {
+            let shift = 64 - width;
+            ((bits << shift) as i64 >> shift).to_string()
+        }
+        Some(NumberType::Int {
+            signed: true,
+            ..
+        }) => (bits as i64).to_string(),
+        Some(NumberType::Float {
+            width: 16,
+        }) => hex_float(bits, 10, 5),
+        Some(NumberType::Float {
+            width: 32,
+        }) => {
+            let value = f32::from_bits(bits as u32);
+            if value.is_normal() || value == 0.0 {
+                format_g(value as f64, 9)
+            } else {
+                hex_float(bits, 23, 8)
+            }
+        }
+        Some(NumberType::Float {
+            width: 64,
+        }) => {
+            let value = f64::from_bits(bits);
+            if value.is_normal() || value == 0.0 {
+                format_g(value, 17)
+            } else {
+                hex_float(bits, 52, 11)
+            }
+        }
+        _ => bits.to_string(),
+    }
+}
+
+/// Formats like printf's "%.<precision>g".
+fn format_g(value: f64, precision: usize) -> String {
+    if value == 0.0 {
+        return if value.is_sign_negative() {
+            "-0"
+        } else {
+            "0"
+        }
+        .to_string();
+    }
+    let scientific = format!("{:.*e}", precision - 1, value);
+    let (mantissa, exponent) = scientific.split_once('e').unwrap();
+    let exponent: i32 = exponent.parse().unwrap();
+    let trim = |s: &str| {
+        if s.contains('.') {
+            s.trim_end_matches('0').trim_end_matches('.').to_string()
+        } else {
+            s.to_string()
+        }
+    };
+    if exponent < -4 || exponent >= precision as i32 {
+        let sign = if exponent < 0 {
+            '-'
+        } else {
+            '+'
+        };
+        format!("{}e{}{:02}", trim(mantissa), sign, exponent.abs())
+    } else {
+        trim(&format!("{:.*}", (precision as i32 - 1 - exponent) as usize, value))
+    }
+}
+
+/// Formats a float as a normalized hex float ("0x1.8p+1"), used for values that don't survive
+/// a trip through decimal.
+fn hex_float(bits: u64, mantissa_bits: u32, exponent_bits: u32) -> String {
+    let sign = if (bits >> (mantissa_bits + exponent_bits)) & 1 != 0 {
+        "-"
+    } else {
+        ""
+    };
+    let mantissa_mask = (1u64 << mantissa_bits) - 1;
+    let biased = ((bits >> mantissa_bits) & ((1 << exponent_bits) - 1)) as i32;
+    let mut fraction = bits & mantissa_mask;
+    let bias = (1 << (exponent_bits - 1)) - 1;
+    if biased == 0 && fraction == 0 {
+        return format!("{}0x0p+0", sign);
+    }
+    let mut exponent = biased - bias;
+    if biased == 0 {
+        // Subnormal
+        exponent += 1;
+        while fraction & (1 << mantissa_bits) == 0 {
+            fraction <<= 1;
+            exponent -= 1;
+        }
+        fraction &= mantissa_mask;
+    }
+    let nibbles = mantissa_bits.div_ceil(4) as usize;
+    let digits = format!("{:0width$x}", fraction << (nibbles as u32 * 4 - mantissa_bits), width = nibbles);
+    let digits = digits.trim_end_matches('0');
+    let dot = if digits.is_empty() {
+        ""
+    } else {
+        "."
+    };
+    let exponent_sign = if exponent >= 0 {
+        "+"
+    } else {
+        ""
+    };
+    format!("{}0x1{}{}p{}{}", sign, dot, digits, exponent_sign, exponent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spirv::tests::{inst, string_words};
+
+    fn module(instructions: &[Vec<u32>]) -> Vec<u32> {
+        let mut words = vec![MAGIC, 0x0001_0000, 13 << 16 | 11, 20, 0];
+        instructions.iter().for_each(|i| words.extend_from_slice(i));
+        words
+    }
+
+    fn fragment_shader() -> Vec<u32> {
+        module(&[
+            inst(17, &[1]),                                                        // OpCapability Shader
+            inst(11, &[[1].as_slice(), &string_words("GLSL.std.450")].concat()),   // OpExtInstImport
+            inst(14, &[0, 1]),                                                     // OpMemoryModel
+            inst(15, &[[4, 4].as_slice(), &string_words("main"), &[10]].concat()), // OpEntryPoint
+            inst(16, &[4, 7]),                                                     // OpExecutionMode
+            inst(5, &[[4].as_slice(), &string_words("main")].concat()),
+            inst(5, &[[10].as_slice(), &string_words("out.color")].concat()),
+            inst(5, &[[15].as_slice(), &string_words("main")].concat()),
+            inst(71, &[10, 30, 0]),               // OpDecorate Location 0
+            inst(19, &[2]),                       // OpTypeVoid
+            inst(33, &[3, 2]),                    // OpTypeFunction
+            inst(22, &[6, 32]),                   // OpTypeFloat
+            inst(23, &[7, 6, 4]),                 // OpTypeVector
+            inst(32, &[8, 3, 7]),                 // OpTypePointer Output
+            inst(59, &[8, 10, 3]),                // OpVariable
+            inst(43, &[6, 11, 0.5f32.to_bits()]), // OpConstant
+            inst(43, &[6, 12, 0.1f32.to_bits()]), // OpConstant
+            inst(21, &[13, 32, 1]),               // OpTypeInt
+            inst(43, &[13, 14, -1i32 as u32]),    // OpConstant
+            inst(54, &[2, 4, 0, 3]),              // OpFunction
+            inst(248, &[5]),                      // OpLabel
+            inst(12, &[6, 15, 1, 31, 11]),        // OpExtInst Sqrt
+            inst(80, &[7, 16, 15, 15, 15, 12]),   // OpCompositeConstruct
+            inst(62, &[10, 16, 2, 16]),           // OpStore Aligned 16
+            inst(251, &[14, 5, 1, 5, 2, 5]),      // OpSwitch
+            inst(253, &[]),                       // OpReturn
+            inst(56, &[]),                        // OpFunctionEnd
+        ])
+    }
+
+    #[test]
+    fn friendly_names() {
+        let text = disassemble(&fragment_shader(), &DisassembleOptions::default()).unwrap();
+        let expected = r#"; SPIR-V
+; Version: 1.0
+; Generator: Google Shaderc over Glslang; 11
+; Bound: 20
+; Schema: 0
+               OpCapability Shader
+          %1 = OpExtInstImport "GLSL.std.450"
+               OpMemoryModel Logical GLSL450
+               OpEntryPoint Fragment %main "main" %out_color
+               OpExecutionMode %main OriginUpperLeft
+               OpName %main "main"
+               OpName %out_color "out.color"
+               OpName %main_0 "main"
+               OpDecorate %out_color Location 0
+       %void = OpTypeVoid
+          %3 = OpTypeFunction %void
+      %float = OpTypeFloat 32
+    %v4float = OpTypeVector %float 4
+%_ptr_Output_v4float = OpTypePointer Output %v4float
+  %out_color = OpVariable %_ptr_Output_v4float Output
+  %float_0_5 = OpConstant %float 0.5
+%float_0_100000001 = OpConstant %float 0.100000001
+        %int = OpTypeInt 32 1
+     %int_n1 = OpConstant %int -1
+       %main = OpFunction %void None %3
+          %5 = OpLabel
+     %main_0 = OpExtInst %float %1 Sqrt %float_0_5
+         %16 = OpCompositeConstruct %v4float %main_0 %main_0 %main_0 %float_0_100000001
+               OpStore %out_color %16 Aligned 16
+               OpSwitch %int_n1 %5 1 %5 2 %5
+               OpReturn
+               OpFunctionEnd
+"#;
+        assert_eq!(text, expected);
+    }
+
+    #[test]
+    fn raw_ids() {
+        let options = DisassembleOptions {
+            friendly_names: false,
+            indent: false,
+            header: false,
+        };
+        let text = disassemble(&fragment_shader(), &options).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "OpCapability Shader");
+        assert_eq!(lines[3], "OpEntryPoint Fragment %4 \"main\" %10");
+        assert_eq!(lines[13], "%8 = OpTypePointer Output %7");
+        assert_eq!(lines[18], "%14 = OpConstant %13 -1");
+
+        let bytes: Vec<u8> = fragment_shader().iter().flat_map(|w| w.to_be_bytes()).collect();
+        assert_eq!(disassemble_bytes(&bytes, &options).unwrap(), text);
+    }
+
+    #[test]
+    fn errors() {
+        let options = DisassembleOptions::default();
+        assert!(disassemble(&module(&[inst(9, &[])]), &options).unwrap_err().contains("Invalid opcode 9 at word 5"));
+        assert!(disassemble(&module(&[inst(21, &[1, 32])]), &options).is_err());
+        assert!(disassemble(&module(&[inst(253, &[1])]), &options).unwrap_err().contains("Too many operands"));
+        assert!(disassemble(&[MAGIC, 0x0001_0000, 0, 1, 0, 0x0005_0000], &options).is_err());
+    }
+
+    #[test]
+    fn numbers() {
+        let half = Some(NumberType::Float {
+            width: 16,
+        });
+        let float = Some(NumberType::Float {
+            width: 32,
+        });
+        let double = Some(NumberType::Float {
+            width: 64,
+        });
+        let short = Some(NumberType::Int {
+            width: 16,
+            signed: true,
+        });
+        let ulong = Some(NumberType::Int {
+            width: 64,
+            signed: false,
+        });
+        assert_eq!(format_g(1.0, 9), "1");
+        assert_eq!(format_g(-2.5, 9), "-2.5");
+        assert_eq!(format_g(1e10, 9), "1e+10");
+        assert_eq!(format_g(1.5e-5f32 as f64, 9), "1.49999996e-05");
+        assert_eq!(format_g(123456789.0, 9), "123456789");
+        assert_eq!(format_number(float, &[f32::INFINITY.to_bits()]), "0x1p+128");
+        assert_eq!(format_number(float, &[0x7fc00000]), "0x1.8p+128");
+        assert_eq!(format_number(float, &[1]), "0x1p-149");
+        assert_eq!(format_number(half, &[0x3e00]), "0x1.8p+0");
+        let bits = 0.1f64.to_bits();
+        assert_eq!(format_number(double, &[bits as u32, (bits >> 32) as u32]), "0.10000000000000001");
+        assert_eq!(format_number(short, &[0xffff]), "-1");
+        assert_eq!(format_number(ulong, &[0, 1]), "4294967296");
+    }
+}
@@ -0,0 +1,2958 @@
+use crate::spirv::{
+    BuiltIn, Decoration, Dim, ExecutionMode, ExecutionModel, ImageOperands, ShaderModule, StorageClass,
+};
+use crate::spirv_dis::decode_string;
+use crate::spirv_grammar::{op_info, GLSL_STD_450};
+use crate::spirv_val::split_module;
+use std::collections::{HashMap, HashSet};
+
+// SPIR-V to GLSL cross-compiler:
+// Translates vertex and fragment shaders to GLSL 330 or GLSL ES 300 for the OpenGL backends on drivers without
+// GL_ARB_gl_spirv.
+// Structured control flow is rebuilt from the merge instructions: selections become if/switch, loops become
+// `for (;;)` with the continue construct inlined before every `continue`. OpPhi results are variables assigned on
+// the incoming edges.
+// Neither version can set bindings in the source, so the caller binds the uniform blocks and samplers with the
+// binding points and texture units returned along with the source.
+// Uniform blocks following std140 are declared as is, the others and the push constants are flattened into arrays
+// of uvec4 read with the offsets of the SPIR-V, so the host can upload the same bytes as for Vulkan.
+// Varyings are named after their location since GLSL 330 matches them between stages by name.
+
+const DECORATION_BLOCK: u32 = Decoration::Block as u32;
+const DECORATION_BUFFER_BLOCK: u32 = Decoration::BufferBlock as u32;
+const DECORATION_ROW_MAJOR: u32 = Decoration::RowMajor as u32;
+const DECORATION_ARRAY_STRIDE: u32 = Decoration::ArrayStride as u32;
+const DECORATION_MATRIX_STRIDE: u32 = Decoration::MatrixStride as u32;
+const DECORATION_BUILT_IN: u32 = Decoration::BuiltIn as u32;
+const DECORATION_NO_PERSPECTIVE: u32 = Decoration::NoPerspective as u32;
+const DECORATION_FLAT: u32 = Decoration::Flat as u32;
+const DECORATION_CENTROID: u32 = Decoration::Centroid as u32;
+const DECORATION_LOCATION: u32 = Decoration::Location as u32;
+const DECORATION_COMPONENT: u32 = Decoration::Component as u32;
+const DECORATION_BINDING: u32 = Decoration::Binding as u32;
+const DECORATION_DESCRIPTOR_SET: u32 = Decoration::DescriptorSet as u32;
+const DECORATION_OFFSET: u32 = Decoration::Offset as u32;
+
+const STORAGE_UNIFORM_CONSTANT: u32 = StorageClass::UniformConstant as u32;
+const STORAGE_INPUT: u32 = StorageClass::Input as u32;
+const STORAGE_UNIFORM: u32 = StorageClass::Uniform as u32;
+const STORAGE_OUTPUT: u32 = StorageClass::Output as u32;
+const STORAGE_PRIVATE: u32 = StorageClass::Private as u32;
+const STORAGE_FUNCTION: u32 = StorageClass::Function as u32;
+const STORAGE_PUSH_CONSTANT: u32 = StorageClass::PushConstant as u32;
+
+/// Push constants are flattened into an array of this many uvec4, the size Vulkan guarantees for them. Using the same
+/// size in every stage keeps the block identical between the stages of a program.
+const PUSH_CONSTANT_VEC4S: u32 = 8;
+const PUSH_CONSTANT_BLOCK: &str = "PushConstants";
+const PUSH_CONSTANT_DATA: &str = "_push_constants";
+
+/// Words that can't be used as identifiers: keywords, reserved words and the built-in functions of the output.
+const RESERVED: &[&str] = &[
+    "active",
+    "all",
+    "any",
+    "asm",
+    "atan",
+    "attribute",
+    "bool",
+    "break",
+    "bvec2",
+    "bvec3",
+    "bvec4",
+    "case",
+    "cast",
+    "centroid",
+    "clamp",
+    "class",
+    "common",
+    "const",
+    "continue",
+    "cross",
+    "default",
+    "determinant",
+    "dFdx",
+    "dFdy",
+    "discard",
+    "distance",
+    "do",
+    "dot",
+    "double",
+    "else",
+    "enum",
+    "equal",
+    "extern",
+    "external",
+    "false",
+    "filter",
+    "fixed",
+    "flat",
+    "float",
+    "for",
+    "fvec2",
+    "fvec3",
+    "fvec4",
+    "fwidth",
+    "goto",
+    "greaterThan",
+    "greaterThanEqual",
+    "half",
+    "highp",
+    "if",
+    "in",
+    "inline",
+    "inout",
+    "input",
+    "int",
+    "interface",
+    "invariant",
+    "inverse",
+    "isinf",
+    "isnan",
+    "ivec2",
+    "ivec3",
+    "ivec4",
+    "layout",
+    "length",
+    "lessThan",
+    "lessThanEqual",
+    "long",
+    "lowp",
+    "main",
+    "mat2",
+    "mat2x2",
+    "mat2x3",
+    "mat2x4",
+    "mat3",
+    "mat3x2",
+    "mat3x3",
+    "mat3x4",
+    "mat4",
+    "mat4x2",
+    "mat4x3",
+    "mat4x4",
+    "max",
+    "mediump",
+    "min",
+    "mix",
+    "mod",
+    "namespace",
+    "noinline",
+    "noperspective",
+    "normalize",
+    "not",
+    "notEqual",
+    "out",
+    "outerProduct",
+    "output",
+    "packed",
+    "partition",
+    "precision",
+    "public",
+    "reflect",
+    "refract",
+    "resource",
+    "return",
+    "row_major",
+    "sample",
+    "short",
+    "sizeof",
+    "smooth",
+    "smoothstep",
+    "static",
+    "step",
+    "struct",
+    "subroutine",
+    "superp",
+    "switch",
+    "template",
+    "texelFetch",
+    "texture",
+    "textureLod",
+    "textureProj",
+    "textureSize",
+    "this",
+    "transpose",
+    "true",
+    "typedef",
+    "uint",
+    "uniform",
+    "union",
+    "unsigned",
+    "using",
+    "uvec2",
+    "uvec3",
+    "uvec4",
+    "varying",
+    "vec2",
+    "vec3",
+    "vec4",
+    "void",
+    "volatile",
+    "while",
+    PUSH_CONSTANT_BLOCK,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GlslVersion {
+    /// `#version 330 core`
+    Glsl330,
+    /// `#version 300 es`
+    Essl300,
+}
+
+#[derive(Debug, Clone)]
+pub struct GlslOptions {
+    pub version: GlslVersion,
+    /// Flips Y and maps the depth from [0, 1] to [-1, 1] at the end of vertex shaders, so geometry set up for Vulkan
+    /// ends up at the same place on screen.
+    pub fix_clip_space: bool,
+    /// Binding points and texture units reserved per descriptor set: binding `b` of set `s` uses
+    /// `s * bindings_per_set + b`, the same for every stage of a program.
+    pub bindings_per_set: u32,
+    /// Binding point of the uniform block holding the push constants.
+    pub push_constant_binding: u32,
+}
+
+impl Default for GlslOptions {
+    fn default() -> Self {
+        Self {
+            version: GlslVersion::Glsl330,
+            fix_clip_space: true,
+            bindings_per_set: 8,
+            // Last binding point that both GL 3.3 and GLES 3.0 guarantee
+            push_constant_binding: 23,
+        }
+    }
+}
+
+/// Where the host has to bind a uniform block or a sampler of the generated source.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UniformBinding {
+    /// Name of the uniform block (`glGetUniformBlockIndex`) or of the sampler uniform (`glGetUniformLocation`)
+    pub name: String,
+    /// Descriptor set and binding in the SPIR-V, `None` for the push constants
+    pub descriptor: Option<(u32, u32)>,
+    /// Binding point (`glUniformBlockBinding`) or texture unit (`glUniform1i`)
+    pub binding: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct GlslShader {
+    pub source: String,
+    pub uniform_blocks: Vec<UniformBinding>,
+    pub samplers: Vec<UniformBinding>,
+}
+
+/// Translates an entry point of a vertex or fragment shader module to GLSL.
+pub fn to_glsl(module: &ShaderModule, entry_point: &str, options: &GlslOptions) -> Result<GlslShader, String> {
+    let mut compiler = Compiler::new(module, entry_point, options)?;
+    compiler.declare_globals()?;
+
+    let mut source = match options.version {
+        GlslVersion::Glsl330 => "#version 330 core\n".to_string(),
+        GlslVersion::Essl300 => "#version 300 es\nprecision highp float;\nprecision highp int;\n".to_string(),
+    };
+    if !compiler.structs.is_empty() {
+        source += "\n";
+        source += &compiler.structs;
+    }
+    if !compiler.globals.is_empty() {
+        source += "\n";
+        source += &compiler.globals;
+    }
+
+    let functions: Vec<&Function> = compiler.functions.iter().filter(|f| compiler.reachable.contains(&f.id)).collect();
+    let prototypes: Vec<String> = functions
+        .iter()
+        .filter(|f| f.id != compiler.entry)
+        .map(|f| compiler.signature(f).map(|s| s + ";\n"))
+        .collect::<Result<_, _>>()?;
+    if !prototypes.is_empty() {
+        source += "\n";
+        source += &prototypes.concat();
+    }
+    for function in functions {
+        source += "\n";
+        source += &compiler.function(function)?;
+    }
+
+    source += "\nvoid main() {\n    _main();\n";
+    if compiler.model == ExecutionModel::Vertex as u32 && options.fix_clip_space && compiler.writes_position {
+        source += "    gl_Position.y = -gl_Position.y;\n";
+        source += "    gl_Position.z = 2.0 * gl_Position.z - gl_Position.w;\n";
+    }
+    source += "}\n";
+
+    compiler.uniform_blocks.sort_by_key(|b| b.binding);
+    compiler.samplers.sort_by_key(|s| s.binding);
+    Ok(GlslShader {
+        source,
+        uniform_blocks: compiler.uniform_blocks,
+        samplers: compiler.samplers,
+    })
+}
+
+#[derive(Debug, Clone)]
+enum Type {
+    Void,
+    Bool,
+    Int(bool),
+    Float,
+    Vector(u32, u32),
+    Matrix(u32, u32),
+    Array(u32, u32),
+    RuntimeArray(u32),
+    Struct(Vec<u32>),
+    Pointer(u32, u32),
+    Image {
+        sampled: u32,
+        dim: u32,
+        depth: u32,
+        arrayed: bool,
+        multisampled: bool,
+    },
+    SampledImage(u32),
+    Other(&'static str),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Bool,
+    Int,
+    Uint,
+    Float,
+    Other,
+}
+
+struct Inst {
+    name: &'static str,
+    result_type: u32,
+    result: u32,
+    /// Operands after the result type and result
+    args: Vec<u32>,
+}
+
+struct Function {
+    id: u32,
+    result_type: u32,
+    params: Vec<u32>,
+    /// Index of the OpFunction and OpFunctionEnd instructions
+    start: usize,
+    end: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct MatrixLayout {
+    stride: u32,
+    row_major: bool,
+}
+
+impl Default for MatrixLayout {
+    fn default() -> Self {
+        Self {
+            stride: 16,
+            row_major: false,
+        }
+    }
+}
+
+/// What a pointer points to.
+#[derive(Debug, Clone)]
+enum Place {
+    /// An lvalue, `cast` when loads have to be converted to the SPIR-V type (integer built-ins)
+    Expr {
+        expr: String,
+        cast: bool,
+        deps: Vec<u32>,
+    },
+    /// Members of a struct of built-ins like gl_PerVertex
+    Builtins(u32),
+    /// Data flattened into an array of uvec4, at `data[dynamic + offset / 16]`
+    Flat {
+        data: String,
+        offset: u32,
+        dynamic: Option<String>,
+        matrix: MatrixLayout,
+        /// Bytes between the components of a vector, the matrix stride for the columns of row major matrices
+        component_stride: u32,
+        deps: Vec<u32>,
+    },
+}
+
+type Decorations = Vec<(u32, Vec<u32>)>;
+
+struct Compiler<'a> {
+    options: &'a GlslOptions,
+    insts: Vec<Inst>,
+    types: HashMap<u32, Type>,
+    /// Instruction defining each constant
+    constants: HashMap<u32, usize>,
+    result_types: HashMap<u32, u32>,
+    names: HashMap<u32, String>,
+    member_names: HashMap<(u32, u32), String>,
+    decorations: HashMap<u32, Decorations>,
+    member_decorations: HashMap<(u32, u32), Decorations>,
+    labels: HashMap<u32, usize>,
+    functions: Vec<Function>,
+    glsl_std_450: HashSet<u32>,
+    global_vars: Vec<u32>,
+    model: u32,
+    entry: u32,
+    origin_upper_left: bool,
+    reachable: HashSet<u32>,
+
+    identifiers: HashMap<u32, String>,
+    used_identifiers: HashSet<String>,
+    places: HashMap<u32, Place>,
+    structs: String,
+    declared_structs: HashSet<u32>,
+    globals: String,
+    writes_position: bool,
+    uniform_blocks: Vec<UniformBinding>,
+    samplers: Vec<UniformBinding>,
+}
+
+impl<'a> Compiler<'a> {
+    fn new(module: &ShaderModule, entry_point: &str, options: &'a GlslOptions) -> Result<Self, String> {
+        let words = module.to_words()?;
+        let mut compiler = Self {
+            options,
+            insts: vec![],
+            types: HashMap::new(),
+            constants: HashMap::new(),
+            result_types: HashMap::new(),
+            names: HashMap::new(),
+            member_names: HashMap::new(),
+            decorations: HashMap::new(),
+            member_decorations: HashMap::new(),
+            labels: HashMap::new(),
+            functions: vec![],
+            glsl_std_450: HashSet::new(),
+            global_vars: vec![],
+            model: u32::MAX,
+            entry: 0,
+            origin_upper_left: false,
+            reachable: HashSet::new(),
+            identifiers: HashMap::new(),
+            used_identifiers: HashSet::new(),
+            places: HashMap::new(),
+            structs: String::new(),
+            declared_structs: HashSet::new(),
+            globals: String::new(),
+            writes_position: false,
+            uniform_blocks: vec![],
+            samplers: vec![],
+        };
+
+        let mut entry_points = vec![];
+        let mut upper_left = HashSet::new();
+        let mut in_function = false;
+        for (offset, words) in split_module(&words).map_err(|e| e.to_string())? {
+            let info = op_info(words[0] & 0xffff).ok_or_else(|| format!("Unknown opcode at word {}", offset))?;
+            let mut args = &words[1..];
+            let mut take = |present: bool| match args.split_first().filter(|_| present) {
+                Some((&first, rest)) => {
+                    args = rest;
+                    first
+                }
+                None => 0,
+            };
+            let result_type = take(info.has_result_type());
+            let result = take(info.has_result());
+            let inst = Inst {
+                name: info.name,
+                result_type,
+                result,
+                args: args.to_vec(),
+            };
+            let arg = |i: usize| {
+                inst.args
+                    .get(i)
+                    .copied()
+                    .ok_or_else(|| format!("Missing operands for {} at word {}", inst.name, offset))
+            };
+            if result != 0 && result_type != 0 {
+                compiler.result_types.insert(result, result_type);
+            }
+
+            match inst.name {
+                "OpExtInstImport" if decode_string(&inst.args).is_some_and(|(name, _)| name == "GLSL.std.450") => {
+                    compiler.glsl_std_450.insert(result);
+                }
+                "OpEntryPoint" => {
+                    let name = decode_string(&inst.args[2..]).map(|(name, _)| name).unwrap_or_default();
+                    entry_points.push((name, arg(0)?, arg(1)?));
+                }
+                "OpExecutionMode" if arg(1)? == ExecutionMode::OriginUpperLeft as u32 => {
+                    upper_left.insert(arg(0)?);
+                }
+                "OpName" => {
+                    let name = decode_string(&inst.args[1..]).map(|(name, _)| name).unwrap_or_default();
+                    compiler.names.insert(arg(0)?, name);
+                }
+                "OpMemberName" => {
+                    let name = decode_string(&inst.args[2..]).map(|(name, _)| name).unwrap_or_default();
+                    compiler.member_names.insert((arg(0)?, arg(1)?), name);
+                }
+                "OpDecorate" => {
+                    let decorations = compiler.decorations.entry(arg(0)?).or_default();
+                    decorations.push((arg(1)?, inst.args[2..].to_vec()));
+                }
+                "OpMemberDecorate" => {
+                    let decorations = compiler.member_decorations.entry((arg(0)?, arg(1)?)).or_default();
+                    decorations.push((arg(2)?, inst.args[3..].to_vec()));
+                }
+                "OpFunction" => {
+                    in_function = true;
+                    compiler.functions.push(Function {
+                        id: result,
+                        result_type,
+                        params: vec![],
+                        start: compiler.insts.len(),
+                        end: 0,
+                    });
+                }
+                "OpFunctionParameter" => {
+                    if let Some(function) = compiler.functions.last_mut() {
+                        function.params.push(result);
+                    }
+                }
+                "OpFunctionEnd" => {
+                    in_function = false;
+                    if let Some(function) = compiler.functions.last_mut() {
+                        function.end = compiler.insts.len();
+                    }
+                }
+                "OpLabel" => {
+                    compiler.labels.insert(result, compiler.insts.len());
+                }
+                "OpVariable" if !in_function => compiler.global_vars.push(result),
+                "OpSpecConstantOp" => return Err("OpSpecConstantOp isn't supported".to_string()),
+                name if !in_function && name.starts_with("OpType") => {
+                    let ty = compiler.parse_type(&inst)?;
+                    compiler.types.insert(result, ty);
+                }
+                name if !in_function
+                    && (name.starts_with("OpConstant") || name.starts_with("OpSpecConstant") || name == "OpUndef") =>
+                {
+                    compiler.constants.insert(result, compiler.insts.len());
+                }
+                _ => {}
+            }
+            compiler.insts.push(inst);
+        }
+
+        let Some((_, model, entry)) = entry_points.into_iter().find(|(name, _, _)| name == entry_point) else {
+            return Err(format!("No entry point named '{}'", entry_point));
+        };
+        if model != ExecutionModel::Vertex as u32 && model != ExecutionModel::Fragment as u32 {
+            let model = ExecutionModel::name(model).unwrap_or("unknown");
+            return Err(format!("Only vertex and fragment shaders can be translated, not {}", model));
+        }
+        compiler.model = model;
+        compiler.entry = entry;
+        compiler.origin_upper_left = upper_left.contains(&entry);
+        compiler.find_reachable()?;
+        Ok(compiler)
+    }
+
+    fn parse_type(&self, inst: &Inst) -> Result<Type, String> {
+        let arg = |i: usize| inst.args.get(i).copied().ok_or_else(|| format!("Missing operands for {}", inst.name));
+        Ok(match inst.name {
+            "OpTypeVoid" => Type::Void,
+            "OpTypeBool" => Type::Bool,
+            "OpTypeInt" | "OpTypeFloat" if arg(0)? != 32 => {
+                return Err(format!("Only 32-bit scalars are supported, %{} has {} bits", inst.result, arg(0)?));
+            }
+            "OpTypeInt" => Type::Int(arg(1)? != 0),
+            "OpTypeFloat" => Type::Float,
+            "OpTypeVector" => Type::Vector(arg(0)?, arg(1)?),
+            "OpTypeMatrix" => Type::Matrix(arg(0)?, arg(1)?),
+            "OpTypeArray" => {
+                let length = self.constants.get(&arg(1)?).map(|&i| &self.insts[i]);
+                match length {
+                    Some(length) if length.name.ends_with("Constant") && !length.args.is_empty() => {
+                        Type::Array(arg(0)?, length.args[0])
+                    }
+                    _ => return Err(format!("Length of array type %{} isn't an integer constant", inst.result)),
+                }
+            }
+            "OpTypeRuntimeArray" => Type::RuntimeArray(arg(0)?),
+            "OpTypeStruct" => Type::Struct(inst.args.clone()),
+            "OpTypePointer" => Type::Pointer(arg(0)?, arg(1)?),
+            "OpTypeImage" => Type::Image {
+                sampled: arg(0)?,
+                dim: arg(1)?,
+                depth: arg(2)?,
+                arrayed: arg(3)? != 0,
+                multisampled: arg(4)? != 0,
+            },
+            "OpTypeSampledImage" => Type::SampledImage(arg(0)?),
+            name => Type::Other(name),
+        })
+    }
+
+    /// Finds the functions called by the entry point.
+    fn find_reachable(&mut self) -> Result<(), String> {
+        let mut stack = vec![self.entry];
+        while let Some(id) = stack.pop() {
+            if !self.reachable.insert(id) {
+                continue;
+            }
+            let function = self.function_info(id)?;
+            for inst in &self.insts[function.start..function.end] {
+                if inst.name == "OpFunctionCall" {
+                    stack.push(inst.args[0]);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn function_info(&self, id: u32) -> Result<&Function, String> {
+        self.functions.iter().find(|f| f.id == id).ok_or_else(|| format!("Undefined function %{}", id))
+    }
+
+    fn ty(&self, id: u32) -> Result<&Type, String> {
+        self.types.get(&id).ok_or_else(|| format!("Undefined type %{}", id))
+    }
+
+    fn type_of(&self, id: u32) -> Result<u32, String> {
+        self.result_types.get(&id).copied().ok_or_else(|| format!("Undefined value %{}", id))
+    }
+
+    fn pointee(&self, pointer_type: u32) -> Result<u32, String> {
+        match self.ty(pointer_type)? {
+            &Type::Pointer(_, pointee) => Ok(pointee),
+            _ => Err(format!("Type %{} isn't a pointer", pointer_type)),
+        }
+    }
+
+    fn decoration(&self, id: u32, decoration: u32) -> Option<&[u32]> {
+        let decorations = self.decorations.get(&id)?;
+        decorations.iter().find(|(d, _)| *d == decoration).map(|(_, args)| args.as_slice())
+    }
+
+    fn decoration_value(&self, id: u32, decoration: u32) -> Option<u32> {
+        self.decoration(id, decoration).and_then(|args| args.first().copied())
+    }
+
+    fn member_decoration(&self, id: u32, member: u32, decoration: u32) -> Option<&[u32]> {
+        let decorations = self.member_decorations.get(&(id, member))?;
+        decorations.iter().find(|(d, _)| *d == decoration).map(|(_, args)| args.as_slice())
+    }
+
+    fn member_offset(&self, ty: u32, member: u32) -> Result<u32, String> {
+        let offset = self.member_decoration(ty, member, DECORATION_OFFSET).and_then(|a| a.first().copied());
+        offset.ok_or_else(|| format!("Member {} of struct %{} has no Offset decoration", member, ty))
+    }
+
+    fn member_matrix_layout(&self, ty: u32, member: u32) -> MatrixLayout {
+        let stride = self.member_decoration(ty, member, DECORATION_MATRIX_STRIDE).and_then(|a| a.first().copied());
+        MatrixLayout {
+            stride: stride.unwrap_or(16),
+            row_major: self.member_decoration(ty, member, DECORATION_ROW_MAJOR).is_some(),
+        }
+    }
+
+    fn array_stride(&self, ty: u32) -> Result<u32, String> {
+        let stride = self.decoration_value(ty, DECORATION_ARRAY_STRIDE);
+        stride.ok_or_else(|| format!("Array type %{} has no ArrayStride decoration", ty))
+    }
+
+    /// Picks a unique identifier for an id, from its debug name when it's usable.
+    fn name(&mut self, id: u32) -> String {
+        if let Some(name) = self.identifiers.get(&id) {
+            return name.clone();
+        }
+        // Function names are mangled like "foo(vf2;"
+        let debug_name = self.names.get(&id).map(|n| n.split('(').next().unwrap_or_default().to_string());
+        let name = match debug_name {
+            Some(name) if is_identifier(&name) && !self.used_identifiers.contains(&name) => name,
+            _ => format!("_{}", id),
+        };
+        self.used_identifiers.insert(name.clone());
+        self.identifiers.insert(id, name.clone());
+        name
+    }
+
+    fn member_name(&self, ty: u32, member: u32) -> String {
+        let name = self.member_names.get(&(ty, member));
+        let name = name.filter(|n| is_identifier(n) || n.starts_with('_') && is_identifier(&n[1..]));
+        // Members can't clash with globals, only with each other
+        let taken = |name: &String| {
+            (0..member).any(|m| self.member_names.get(&(ty, m)) == Some(name)) || name.starts_with("_m")
+        };
+        match name {
+            Some(name) if !taken(name) => name.clone(),
+            _ => format!("_m{}", member),
+        }
+    }
+
+    fn kind(&self, ty: u32) -> Kind {
+        match self.types.get(&ty) {
+            Some(Type::Bool) => Kind::Bool,
+            Some(Type::Int(true)) => Kind::Int,
+            Some(Type::Int(false)) => Kind::Uint,
+            Some(Type::Float) => Kind::Float,
+            Some(&Type::Vector(component, _)) => self.kind(component),
+            _ => Kind::Other,
+        }
+    }
+
+    /// Number of components of a scalar or vector type.
+    fn components(&self, ty: u32) -> u32 {
+        match self.types.get(&ty) {
+            Some(&Type::Vector(_, count)) => count,
+            _ => 1,
+        }
+    }
+
+    fn type_name(&self, ty: u32) -> Result<String, String> {
+        Ok(match self.ty(ty)? {
+            Type::Void => "void".to_string(),
+            &Type::Array(element, length) => {
+                if matches!(self.ty(element)?, Type::Array(..)) {
+                    return Err("Arrays of arrays need GLSL 4.30 or GLSL ES 3.10".to_string());
+                }
+                format!("{}[{}]", self.type_name(element)?, length)
+            }
+            &Type::Matrix(column, count) => {
+                let rows = self.components(column);
+                if self.kind(column) != Kind::Float {
+                    return Err(format!("Matrix type %{} doesn't have float components", ty));
+                }
+                match rows == count {
+                    true => format!("mat{}", count),
+                    false => format!("mat{}x{}", count, rows),
+                }
+            }
+            Type::Struct(_) => self
+                .identifiers
+                .get(&ty)
+                .filter(|_| self.declared_structs.contains(&ty))
+                .cloned()
+                .ok_or_else(|| format!("Struct %{} can't be declared in GLSL", ty))?,
+            &Type::SampledImage(image) => self.sampler_type(image)?,
+            Type::RuntimeArray(_) => return Err("Runtime arrays need storage buffers".to_string()),
+            Type::Image {
+                ..
+            }
+            | Type::Other("OpTypeSampler") => {
+                return Err("Separate images and samplers aren't supported, use combined image samplers".to_string());
+            }
+            Type::Pointer(..) | Type::Other(_) => return Err(format!("Type %{} can't be used in GLSL", ty)),
+            _ => vector_type(self.kind(ty), self.components(ty)),
+        })
+    }
+
+    fn sampler_type(&self, image: u32) -> Result<String, String> {
+        let &Type::Image {
+            sampled,
+            dim,
+            depth,
+            arrayed,
+            multisampled,
+        } = self.ty(image)?
+        else {
+            return Err(format!("Type %{} isn't an image", image));
+        };
+        let prefix = match self.kind(sampled) {
+            Kind::Int => "i",
+            Kind::Uint => "u",
+            _ => "",
+        };
+        let es = self.options.version == GlslVersion::Essl300;
+        let dim = match Dim::name(dim) {
+            Some("Dim1D") if !es => "1D",
+            Some("Dim2D") => "2D",
+            Some("Dim3D") => "3D",
+            Some("Cube") => "Cube",
+            Some("Rect") if !es => "2DRect",
+            Some("Buffer") if !es => "Buffer",
+            dim => return Err(format!("Images of dimension {} aren't supported", dim.unwrap_or("?"))),
+        };
+        let ms = if multisampled && !es {
+            "MS"
+        } else {
+            ""
+        };
+        if multisampled && es {
+            return Err("Multisampled textures need GLSL ES 3.10".to_string());
+        }
+        let array = if arrayed {
+            "Array"
+        } else {
+            ""
+        };
+        let shadow = if depth == 1 {
+            "Shadow"
+        } else {
+            ""
+        };
+        Ok(format!("{}sampler{}{}{}{}", prefix, dim, ms, array, shadow))
+    }
+
+    /// Declaration of a variable, arrays put their size after the name.
+    fn declare(&self, ty: u32, name: &str) -> Result<String, String> {
+        match self.ty(ty)? {
+            &Type::Array(element, length) => {
+                if matches!(self.ty(element)?, Type::Array(..)) {
+                    return Err("Arrays of arrays need GLSL 4.30 or GLSL ES 3.10".to_string());
+                }
+                Ok(format!("{} {}[{}]", self.type_name(element)?, name, length))
+            }
+            _ => Ok(format!("{} {}", self.type_name(ty)?, name)),
+        }
+    }
+
+    fn constant(&self, id: u32) -> Result<String, String> {
+        let inst = &self.insts[self.constants[&id]];
+        let ty = inst.result_type;
+        Ok(match inst.name {
+            "OpConstantTrue" | "OpSpecConstantTrue" => "true".to_string(),
+            "OpConstantFalse" | "OpSpecConstantFalse" => "false".to_string(),
+            "OpConstant" | "OpSpecConstant" => {
+                let bits = *inst.args.first().ok_or_else(|| format!("Missing value of constant %{}", id))?;
+                literal(self.kind(ty), bits)
+            }
+            "OpConstantComposite" | "OpSpecConstantComposite" => {
+                let members = inst.args.iter().map(|&m| self.constant(m)).collect::<Result<Vec<_>, _>>()?;
+                format!("{}({})", self.type_name(ty)?, members.join(", "))
+            }
+            "OpConstantNull" | "OpUndef" => self.zero(ty)?,
+            _ => return Err(format!("{} isn't supported", inst.name)),
+        })
+    }
+
+    fn zero(&self, ty: u32) -> Result<String, String> {
+        Ok(match self.ty(ty)? {
+            Type::Bool | Type::Int(_) | Type::Float => literal(self.kind(ty), 0),
+            Type::Vector(component, _) | Type::Matrix(component, _) => {
+                format!("{}({})", self.type_name(ty)?, self.zero(*component)?)
+            }
+            &Type::Array(element, length) => {
+                let zero = self.zero(element)?;
+                format!("{}({})", self.type_name(ty)?, vec![zero; length as usize].join(", "))
+            }
+            Type::Struct(members) => {
+                let members = members.iter().map(|&m| self.zero(m)).collect::<Result<Vec<_>, _>>()?;
+                format!("{}({})", self.type_name(ty)?, members.join(", "))
+            }
+            _ => return Err(format!("Type %{} has no zero value", ty)),
+        })
+    }
+
+    /// Alignment and size of a type with the std140 rules, or None when its decorations use another layout.
+    fn std140(&self, ty: u32, matrix: MatrixLayout) -> Result<Option<(u32, u32)>, String> {
+        Ok(match self.ty(ty)? {
+            Type::Bool | Type::Int(_) | Type::Float => Some((4, 4)),
+            &Type::Vector(_, count) => Some(match count {
+                2 => (8, 8),
+                3 => (16, 12),
+                _ => (16, 16),
+            }),
+            &Type::Matrix(column, count) => {
+                let vectors = if matrix.row_major {
+                    self.components(column)
+                } else {
+                    count
+                };
+                (matrix.stride == 16).then_some((16, 16 * vectors))
+            }
+            &Type::Array(element, length) => match self.std140(element, matrix)? {
+                Some((_, size)) if self.array_stride(ty)? == size.next_multiple_of(16) => {
+                    Some((16, size.next_multiple_of(16) * length))
+                }
+                _ => None,
+            },
+            Type::Struct(members) => {
+                let mut offset = 0u32;
+                let mut align = 16;
+                for (i, &member) in members.iter().enumerate() {
+                    let layout = self.member_matrix_layout(ty, i as u32);
+                    let Some((member_align, size)) = self.std140(member, layout)? else {
+                        return Ok(None);
+                    };
+                    offset = offset.next_multiple_of(member_align);
+                    if self.member_offset(ty, i as u32)? != offset {
+                        return Ok(None);
+                    }
+                    offset += size;
+                    align = align.max(member_align);
+                }
+                Some((align, offset.next_multiple_of(align)))
+            }
+            _ => None,
+        })
+    }
+
+    /// Size in bytes of a type in a buffer.
+    fn byte_size(&self, ty: u32, matrix: MatrixLayout) -> Result<u32, String> {
+        Ok(match self.ty(ty)? {
+            &Type::Vector(_, count) => 4 * count,
+            &Type::Matrix(column, count) => {
+                matrix.stride
+                    * if matrix.row_major {
+                        self.components(column)
+                    } else {
+                        count
+                    }
+            }
+            &Type::Array(element, length) => {
+                let last = self.byte_size(element, matrix)?;
+                self.array_stride(ty)? * length.saturating_sub(1) + last
+            }
+            Type::Struct(members) => {
+                let mut size = 0;
+                for (i, &member) in members.iter().enumerate() {
+                    let layout = self.member_matrix_layout(ty, i as u32);
+                    size = size.max(self.member_offset(ty, i as u32)? + self.byte_size(member, layout)?);
+                }
+                size
+            }
+            Type::RuntimeArray(_) => return Err("Runtime arrays need storage buffers".to_string()),
+            _ => 4,
+        })
+    }
+
+    /// Declares the structs, then the globals used by the entry point.
+    fn declare_globals(&mut self) -> Result<(), String> {
+        let block_types: HashSet<u32> = self
+            .types
+            .keys()
+            .copied()
+            .filter(|&ty| {
+                self.decoration(ty, DECORATION_BLOCK).is_some()
+                    || self.decoration(ty, DECORATION_BUFFER_BLOCK).is_some()
+                    || self.member_decoration(ty, 0, DECORATION_BUILT_IN).is_some()
+            })
+            .collect();
+        let mut structs: Vec<u32> = self.insts.iter().filter(|i| i.name == "OpTypeStruct").map(|i| i.result).collect();
+        structs.retain(|ty| !block_types.contains(ty));
+        for ty in structs {
+            // Structs GLSL can't express are left out, using them fails later on
+            let Type::Struct(members) = self.ty(ty)?.clone() else {
+                unreachable!()
+            };
+            let declarations: Result<Vec<String>, String> = members
+                .iter()
+                .enumerate()
+                .map(|(i, &member)| self.declare(member, &self.member_name(ty, i as u32)))
+                .collect();
+            if let Ok(declarations) = declarations {
+                let name = self.name(ty);
+                self.declared_structs.insert(ty);
+                self.structs += &format!("struct {} {{\n", name);
+                for declaration in declarations {
+                    self.structs += &format!("    {};\n", declaration);
+                }
+                self.structs += "};\n";
+            }
+        }
+
+        let mut used = HashSet::new();
+        for function in self.functions.iter().filter(|f| self.reachable.contains(&f.id)) {
+            for inst in &self.insts[function.start..function.end] {
+                used.extend(inst.args.iter().copied());
+            }
+        }
+        for var in self.global_vars.clone() {
+            if used.contains(&var) {
+                self.declare_global(var)?;
+            }
+        }
+        for function in &self.functions {
+            if function.id == self.entry {
+                self.identifiers.insert(function.id, "_main".to_string());
+            }
+        }
+        for function in self.functions.iter().map(|f| f.id).collect::<Vec<_>>() {
+            self.name(function);
+        }
+        Ok(())
+    }
+
+    fn declare_global(&mut self, var: u32) -> Result<(), String> {
+        let inst = &self.insts.iter().find(|i| i.result == var).expect("global variable");
+        let (storage, initializer) = (inst.args[0], inst.args.get(1).copied());
+        let ty = self.pointee(inst.result_type)?;
+        let vertex = self.model == ExecutionModel::Vertex as u32;
+        let es = self.options.version == GlslVersion::Essl300;
+        let binding = |c: &Self| {
+            let set = c.decoration_value(var, DECORATION_DESCRIPTOR_SET).unwrap_or(0);
+            let binding = c.decoration_value(var, DECORATION_BINDING).unwrap_or(0);
+            ((set, binding), set * c.options.bindings_per_set + binding)
+        };
+        let place = |expr: String, cast: bool| Place::Expr {
+            expr,
+            cast,
+            deps: vec![],
+        };
+
+        if let Some(builtin) = self.decoration_value(var, DECORATION_BUILT_IN) {
+            let name = self.builtin(builtin)?;
+            if name == "gl_FragCoord" && self.origin_upper_left && !es {
+                self.globals += "layout(origin_upper_left) in vec4 gl_FragCoord;\n";
+            }
+            // gl_VertexID and gl_InstanceID are signed
+            let cast = storage == STORAGE_INPUT && self.kind(ty) == Kind::Uint;
+            self.places.insert(var, place(name.to_string(), cast));
+            return Ok(());
+        }
+        if self.member_decoration(ty, 0, DECORATION_BUILT_IN).is_some() {
+            let Type::Struct(members) = self.ty(ty)? else {
+                return Err(format!("Built-in block %{} isn't a struct", ty));
+            };
+            let position = (0..members.len() as u32).any(|i| {
+                let builtin = self.member_decoration(ty, i, DECORATION_BUILT_IN).and_then(|a| a.first().copied());
+                builtin == Some(BuiltIn::Position as u32)
+            });
+            self.writes_position |= position && storage == STORAGE_OUTPUT;
+            self.places.insert(var, Place::Builtins(ty));
+            return Ok(());
+        }
+
+        match storage {
+            STORAGE_INPUT | STORAGE_OUTPUT => {
+                if self.decoration(ty, DECORATION_BLOCK).is_some() {
+                    return Err("Interface blocks aren't supported".to_string());
+                }
+                if self.decoration(var, DECORATION_COMPONENT).is_some() {
+                    return Err("The Component decoration isn't supported".to_string());
+                }
+                let location = self.decoration_value(var, DECORATION_LOCATION);
+                let location = location.ok_or_else(|| format!("Interface variable %{} has no location", var))?;
+                let direction = if storage == STORAGE_INPUT {
+                    "in"
+                } else {
+                    "out"
+                };
+                // Vertex inputs and fragment outputs have explicit locations, varyings are matched by name
+                let name;
+                if vertex == (storage == STORAGE_INPUT) {
+                    name = self.name(var);
+                    let declaration = self.declare(ty, &name)?;
+                    self.globals += &format!("layout(location = {}) {} {};\n", location, direction, declaration);
+                } else {
+                    name = format!("_location{}", location);
+                    self.identifiers.insert(var, name.clone());
+                    let mut qualifiers = String::new();
+                    let integer = matches!(self.kind(ty), Kind::Int | Kind::Uint);
+                    if self.decoration(var, DECORATION_FLAT).is_some() || integer {
+                        qualifiers += "flat ";
+                    } else if self.decoration(var, DECORATION_NO_PERSPECTIVE).is_some() {
+                        if es {
+                            return Err("noperspective isn't available in GLSL ES".to_string());
+                        }
+                        qualifiers += "noperspective ";
+                    }
+                    if self.decoration(var, DECORATION_CENTROID).is_some() {
+                        qualifiers += "centroid ";
+                    }
+                    let declaration = self.declare(ty, &name)?;
+                    self.globals += &format!("{}{} {};\n", qualifiers, direction, declaration);
+                }
+                self.places.insert(var, place(name, false));
+            }
+            STORAGE_UNIFORM if self.decoration(ty, DECORATION_BUFFER_BLOCK).is_some() => {
+                return Err("Storage buffers need GLSL 4.30 or GLSL ES 3.10".to_string());
+            }
+            STORAGE_UNIFORM => {
+                let block = self.name(ty);
+                let instance = self.name(var);
+                let (descriptor, binding) = binding(self);
+                if self.std140(ty, MatrixLayout::default())?.is_some() {
+                    let Type::Struct(members) = self.ty(ty)?.clone() else {
+                        return Err(format!("Uniform block %{} isn't a struct", var));
+                    };
+                    self.globals += &format!("layout(std140) uniform {} {{\n", block);
+                    for (i, &member) in members.iter().enumerate() {
+                        let declaration = self.declare(member, &self.member_name(ty, i as u32))?;
+                        let row_major = self.member_matrix_layout(ty, i as u32).row_major;
+                        let layout = if row_major {
+                            "layout(row_major) "
+                        } else {
+                            ""
+                        };
+                        self.globals += &format!("    {}{};\n", layout, declaration);
+                    }
+                    self.globals += &format!("}} {};\n", instance);
+                    self.places.insert(var, place(instance, false));
+                } else {
+                    let vec4s = self.byte_size(ty, MatrixLayout::default())?.div_ceil(16).max(1);
+                    self.globals +=
+                        &format!("layout(std140) uniform {} {{\n    uvec4 {}[{}];\n}};\n", block, instance, vec4s);
+                    self.places.insert(var, flat(instance));
+                }
+                self.uniform_blocks.push(UniformBinding {
+                    name: block,
+                    descriptor: Some(descriptor),
+                    binding,
+                });
+            }
+            STORAGE_PUSH_CONSTANT => {
+                if self.byte_size(ty, MatrixLayout::default())? > 16 * PUSH_CONSTANT_VEC4S {
+                    return Err(format!("Push constants can't be larger than {} bytes", 16 * PUSH_CONSTANT_VEC4S));
+                }
+                self.globals += &format!(
+                    "layout(std140) uniform {} {{\n    uvec4 {}[{}];\n}};\n",
+                    PUSH_CONSTANT_BLOCK, PUSH_CONSTANT_DATA, PUSH_CONSTANT_VEC4S
+                );
+                self.places.insert(var, flat(PUSH_CONSTANT_DATA.to_string()));
+                self.uniform_blocks.push(UniformBinding {
+                    name: PUSH_CONSTANT_BLOCK.to_string(),
+                    descriptor: None,
+                    binding: self.options.push_constant_binding,
+                });
+            }
+            STORAGE_UNIFORM_CONSTANT => {
+                if !matches!(self.ty(ty)?, Type::SampledImage(_)) {
+                    let _ = self.type_name(ty)?;
+                    return Err(format!("Uniform %{} isn't a combined image sampler", var));
+                }
+                let name = self.name(var);
+                let precision = if es {
+                    "highp "
+                } else {
+                    ""
+                };
+                self.globals += &format!("uniform {}{} {};\n", precision, self.type_name(ty)?, name);
+                let (descriptor, binding) = binding(self);
+                self.samplers.push(UniformBinding {
+                    name: name.clone(),
+                    descriptor: Some(descriptor),
+                    binding,
+                });
+                self.places.insert(var, place(name, false));
+            }
+            STORAGE_PRIVATE => {
+                let name = self.name(var);
+                let declaration = self.declare(ty, &name)?;
+                match initializer {
+                    Some(init) => self.globals += &format!("{} = {};\n", declaration, self.constant(init)?),
+                    None => self.globals += &format!("{};\n", declaration),
+                }
+                self.places.insert(var, place(name, false));
+            }
+            storage => {
+                let storage = StorageClass::name(storage).unwrap_or("unknown");
+                return Err(format!("Variables in the {} storage class aren't supported", storage));
+            }
+        }
+        Ok(())
+    }
+
+    fn builtin(&mut self, builtin: u32) -> Result<&'static str, String> {
+        let es = self.options.version == GlslVersion::Essl300;
+        Ok(match BuiltIn::name(builtin) {
+            Some("Position") => {
+                self.writes_position = true;
+                "gl_Position"
+            }
+            Some("PointSize") => "gl_PointSize",
+            Some("ClipDistance") if !es => "gl_ClipDistance",
+            Some("VertexIndex") => "gl_VertexID",
+            Some("InstanceIndex") => "gl_InstanceID",
+            Some("FragCoord") => "gl_FragCoord",
+            Some("PointCoord") => "gl_PointCoord",
+            Some("FrontFacing") => "gl_FrontFacing",
+            Some("FragDepth") => "gl_FragDepth",
+            name => return Err(format!("Built-in {} isn't supported", name.unwrap_or("?"))),
+        })
+    }
+
+    fn signature(&self, function: &Function) -> Result<String, String> {
+        let params = function
+            .params
+            .iter()
+            .map(|&param| {
+                let ty = self.type_of(param)?;
+                let name = &self.identifiers[&param];
+                match self.ty(ty)? {
+                    &Type::Pointer(STORAGE_FUNCTION | STORAGE_PRIVATE, pointee) => {
+                        Ok(format!("inout {}", self.declare(pointee, name)?))
+                    }
+                    Type::Pointer(..) => Err(format!("Parameter %{} points to a global", param)),
+                    _ => self.declare(ty, name),
+                }
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        let name = &self.identifiers[&function.id];
+        Ok(format!("{} {}({})", self.type_name(function.result_type)?, name, params.join(", ")))
+    }
+
+    /// Translates a function, twice when values have to be declared at the top because they're used outside of the
+    /// scope they're defined in.
+    fn function(&self, function: &Function) -> Result<String, String> {
+        let insts = &self.insts[function.start..function.end];
+        let mut hoisted: HashSet<u32> = insts.iter().filter(|i| i.name == "OpPhi").map(|i| i.result).collect();
+        loop {
+            let mut writer = Writer::new(self, function, hoisted.clone())?;
+            let first_label = insts.iter().find(|i| i.name == "OpLabel").ok_or("Function has no body")?;
+            writer.block(first_label.result)?;
+            if writer.escaping.is_subset(&hoisted) {
+                return writer.finish();
+            }
+            hoisted.extend(writer.escaping);
+        }
+    }
+}
+
+fn flat(data: String) -> Place {
+    Place::Flat {
+        data,
+        offset: 0,
+        dynamic: None,
+        matrix: MatrixLayout::default(),
+        component_stride: 4,
+        deps: vec![],
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !name.starts_with("gl_")
+        && !name.contains("__")
+        && !RESERVED.contains(&name)
+        && !["sampler", "isampler", "usampler", "image", "dmat", "dvec", "hvec", "ivec", "bvec", "uvec"]
+            .iter()
+            .any(|prefix| name.starts_with(prefix) && name[prefix.len()..].starts_with(|c: char| c.is_ascii_digit()))
+}
+
+fn vector_type(kind: Kind, count: u32) -> String {
+    let (scalar, prefix) = match kind {
+        Kind::Bool => ("bool", "b"),
+        Kind::Int => ("int", "i"),
+        Kind::Uint => ("uint", "u"),
+        _ => ("float", ""),
+    };
+    match count {
+        1 => scalar.to_string(),
+        n => format!("{}vec{}", prefix, n),
+    }
+}
+
+fn literal(kind: Kind, bits: u32) -> String {
+    match kind {
+        Kind::Bool => (bits != 0).to_string(),
+        Kind::Uint => format!("{}u", bits),
+        Kind::Int => match bits as i32 {
+            i32::MIN => "(-2147483647 - 1)".to_string(),
+            v if v < 0 => format!("({})", v),
+            v => v.to_string(),
+        },
+        _ => match f32::from_bits(bits) {
+            v if !v.is_finite() => format!("uintBitsToFloat(0x{:08x}u)", bits),
+            v if v.is_sign_negative() => format!("({:?})", v),
+            v => format!("{:?}", v),
+        },
+    }
+}
+
+const SWIZZLE: [&str; 4] = ["x", "y", "z", "w"];
+
+#[derive(Debug)]
+enum Construct {
+    Selection {
+        merge: u32,
+    },
+    Loop {
+        header: u32,
+        merge: u32,
+        continue_target: u32,
+    },
+    Switch {
+        merge: u32,
+        cases: Vec<u32>,
+        current: usize,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Jump {
+    /// To the merge block of the enclosing selection, which follows it
+    Fallthrough,
+    Break,
+    /// To the continue target, which is written before the `continue`
+    Continue,
+    /// Back edge to the loop header
+    BackEdge,
+    /// To a block of the current construct, written in place
+    Inline,
+}
+
+/// Writes the body of a function.
+struct Writer<'a> {
+    c: &'a Compiler<'a>,
+    function: &'a Function,
+    out: String,
+    scope: Vec<u32>,
+    next_scope: u32,
+    constructs: Vec<Construct>,
+    block: u32,
+    blocks_written: usize,
+    /// Expression of each value, its name unless it's inlined (samplers)
+    values: HashMap<u32, String>,
+    places: HashMap<u32, Place>,
+    locals: Vec<u32>,
+    /// Values declared at the top of the function
+    hoisted: HashSet<u32>,
+    /// Scope of the last definition of each value
+    defined_in: HashMap<u32, Vec<u32>>,
+    /// Values used outside of the scope they're defined in
+    escaping: HashSet<u32>,
+}
+
+impl<'a> Writer<'a> {
+    fn new(c: &'a Compiler<'a>, function: &'a Function, hoisted: HashSet<u32>) -> Result<Self, String> {
+        let mut writer = Self {
+            c,
+            function,
+            out: String::new(),
+            scope: vec![],
+            next_scope: 0,
+            constructs: vec![],
+            block: 0,
+            blocks_written: 0,
+            values: HashMap::new(),
+            places: c.places.clone(),
+            locals: vec![],
+            hoisted,
+            defined_in: HashMap::new(),
+            escaping: HashSet::new(),
+        };
+        for &param in &function.params {
+            let name = c.identifiers[&param].clone();
+            match c.ty(c.type_of(param)?)? {
+                Type::Pointer(..) => {
+                    writer.places.insert(
+                        param,
+                        Place::Expr {
+                            expr: name,
+                            cast: false,
+                            deps: vec![],
+                        },
+                    );
+                }
+                _ => {
+                    writer.values.insert(param, name);
+                    writer.defined_in.insert(param, vec![]);
+                }
+            }
+        }
+        for inst in &c.insts[function.start..function.end] {
+            if inst.name == "OpVariable" {
+                let name = format!("_{}", inst.result);
+                writer.places.insert(
+                    inst.result,
+                    Place::Expr {
+                        expr: name,
+                        cast: false,
+                        deps: vec![],
+                    },
+                );
+                writer.locals.push(inst.result);
+            }
+        }
+        for &id in &writer.hoisted {
+            writer.values.insert(id, format!("_{}", id));
+            writer.defined_in.insert(id, vec![]);
+        }
+        Ok(writer)
+    }
+
+    fn finish(self) -> Result<String, String> {
+        let c = self.c;
+        let mut out = c.signature(self.function)? + " {\n";
+        for &local in &self.locals {
+            let inst = c.insts[c.function_info(self.function.id)?.start..].iter().find(|i| i.result == local).unwrap();
+            let declaration = c.declare(c.pointee(inst.result_type)?, &format!("_{}", local))?;
+            match inst.args.get(1) {
+                Some(&init) => out += &format!("    {} = {};\n", declaration, c.constant(init)?),
+                None => out += &format!("    {};\n", declaration),
+            }
+        }
+        let mut hoisted: Vec<u32> = self.hoisted.iter().copied().collect();
+        hoisted.sort();
+        for id in hoisted {
+            out += &format!("    {};\n", c.declare(c.type_of(id)?, &format!("_{}", id))?);
+        }
+        out += &self.out;
+        out += "}\n";
+        Ok(out)
+    }
+
+    fn line(&mut self, line: &str) {
+        for _ in 0..=self.scope.len() {
+            self.out += "    ";
+        }
+        self.out += line;
+        self.out += "\n";
+    }
+
+    /// Writes a line ending with `{` and enters a new scope.
+    fn open(&mut self, line: &str) {
+        self.line(&format!("{} {{", line));
+        self.scope.push(self.next_scope);
+        self.next_scope += 1;
+    }
+
+    fn close(&mut self) {
+        self.scope.pop();
+        self.line("}");
+    }
+
+    fn track(&mut self, id: u32) {
+        if let Some(scope) = self.defined_in.get(&id) {
+            if !self.scope.starts_with(scope) {
+                self.escaping.insert(id);
+            }
+        }
+    }
+
+    fn value(&mut self, id: u32) -> Result<String, String> {
+        if self.c.constants.contains_key(&id) {
+            return self.c.constant(id);
+        }
+        match self.values.get(&id) {
+            Some(value) => {
+                let value = value.clone();
+                self.track(id);
+                Ok(value)
+            }
+            None if self.places.contains_key(&id) => Err(format!("Pointer %{} used as a value", id)),
+            None => Err(format!("Value %{} is used before it's defined", id)),
+        }
+    }
+
+    fn type_of(&self, id: u32) -> Result<u32, String> {
+        self.c.type_of(id)
+    }
+
+    /// A value converted to another kind of scalar, keeping its number of components.
+    fn value_as(&mut self, id: u32, kind: Kind) -> Result<String, String> {
+        let value = self.value(id)?;
+        let ty = self.type_of(id)?;
+        Ok(self.cast(value, ty, kind))
+    }
+
+    fn cast(&self, expr: String, ty: u32, kind: Kind) -> String {
+        match self.c.kind(ty) {
+            k if k == kind || k == Kind::Other || kind == Kind::Other => expr,
+            _ => format!("{}({})", vector_type(kind, self.c.components(ty)), expr),
+        }
+    }
+
+    /// Defines a value, declaring its variable unless it's hoisted or still in scope.
+    fn define(&mut self, inst: &Inst, expr: String) -> Result<(), String> {
+        let name = format!("_{}", inst.result);
+        let visible = self.defined_in.get(&inst.result).is_some_and(|scope| self.scope.starts_with(scope));
+        if self.hoisted.contains(&inst.result) || visible {
+            self.line(&format!("{} = {};", name, expr));
+        } else {
+            let declaration = self.c.declare(inst.result_type, &name)?;
+            self.line(&format!("{} = {};", declaration, expr));
+            self.defined_in.insert(inst.result, self.scope.clone());
+        }
+        self.values.insert(inst.result, name);
+        Ok(())
+    }
+
+    fn block(&mut self, label: u32) -> Result<(), String> {
+        self.blocks_written += 1;
+        if self.blocks_written > 100 * self.c.labels.len() + 1000 {
+            return Err("Control flow isn't structured".to_string());
+        }
+        let start = *self.c.labels.get(&label).ok_or_else(|| format!("Undefined label %{}", label))?;
+        let insts = &self.c.insts;
+        let mut end = start + 1;
+        while !is_terminator(insts[end].name) {
+            if matches!(insts[end].name, "OpLabel" | "OpFunctionEnd") {
+                return Err(format!("Block %{} has no terminator", label));
+            }
+            end += 1;
+        }
+        self.block = label;
+
+        let merge = &insts[end - 1];
+        match merge.name {
+            "OpLoopMerge" => {
+                self.open("for (;;)");
+                self.constructs.push(Construct::Loop {
+                    header: label,
+                    merge: merge.args[0],
+                    continue_target: merge.args[1],
+                });
+                self.instructions(start + 1..end - 1)?;
+                self.terminator(end)?;
+                self.constructs.pop();
+                self.close();
+                self.follow(merge.args[0])
+            }
+            "OpSelectionMerge" => {
+                self.instructions(start + 1..end - 1)?;
+                let merge = merge.args[0];
+                if insts[end].name == "OpSwitch" {
+                    self.switch(end, merge)?;
+                } else {
+                    self.constructs.push(Construct::Selection {
+                        merge,
+                    });
+                    self.terminator(end)?;
+                    self.constructs.pop();
+                }
+                self.follow(merge)
+            }
+            _ => {
+                self.instructions(start + 1..end)?;
+                self.terminator(end)
+            }
+        }
+    }
+
+    /// Continues with the merge block of a construct, whose phis were assigned by the branches to it.
+    fn follow(&mut self, merge: u32) -> Result<(), String> {
+        match self.classify(merge)? {
+            Jump::Fallthrough => Ok(()),
+            Jump::Break => {
+                self.line("break;");
+                Ok(())
+            }
+            Jump::BackEdge => {
+                self.line("continue;");
+                Ok(())
+            }
+            Jump::Continue | Jump::Inline => self.block(merge),
+        }
+    }
+
+    fn classify(&self, target: u32) -> Result<Jump, String> {
+        let mut in_switch = false;
+        for construct in self.constructs.iter().rev() {
+            match construct {
+                Construct::Selection {
+                    merge,
+                } => {
+                    if *merge == target {
+                        return Ok(Jump::Fallthrough);
+                    }
+                }
+                Construct::Switch {
+                    merge,
+                    cases,
+                    current,
+                } => {
+                    if *merge == target {
+                        return Ok(Jump::Break);
+                    }
+                    if cases.get(current + 1) == Some(&target) {
+                        return Ok(Jump::Fallthrough);
+                    }
+                    if cases.contains(&target) {
+                        return Err("Switch cases can only fall through to the next case".to_string());
+                    }
+                    in_switch = true;
+                }
+                Construct::Loop {
+                    header,
+                    merge,
+                    continue_target,
+                } => {
+                    if *merge == target && in_switch {
+                        return Err("Breaking out of a loop from a switch isn't supported".to_string());
+                    }
+                    if *merge == target {
+                        return Ok(Jump::Break);
+                    }
+                    if *header == target {
+                        return Ok(Jump::BackEdge);
+                    }
+                    if *continue_target == target {
+                        return Ok(Jump::Continue);
+                    }
+                    break;
+                }
+            }
+        }
+        Ok(Jump::Inline)
+    }
+
+    fn jump(&mut self, target: u32) -> Result<(), String> {
+        self.assign_phis(target)?;
+        self.follow(target)
+    }
+
+    /// Assigns the values coming from the current block to the phis of a block.
+    fn assign_phis(&mut self, target: u32) -> Result<(), String> {
+        let insts = &self.c.insts;
+        let start = *self.c.labels.get(&target).ok_or_else(|| format!("Undefined label %{}", target))?;
+        let mut phis = vec![];
+        for phi in insts[start + 1..].iter().take_while(|i| i.name == "OpPhi") {
+            let pair = phi.args.chunks(2).find(|pair| pair.get(1) == Some(&self.block));
+            let pair = pair.ok_or_else(|| format!("OpPhi %{} has no value for block %{}", phi.result, self.block))?;
+            if pair[0] != phi.result {
+                phis.push((phi, pair[0]));
+            }
+        }
+        // Phis are assigned all at once, so a phi reading another one reads its previous value
+        let parallel = phis.len() > 1 && phis.iter().any(|(_, value)| phis.iter().any(|(phi, _)| phi.result == *value));
+        if parallel {
+            self.open("");
+            let mut assignments = vec![];
+            for &(phi, value) in &phis {
+                let value = self.value(value)?;
+                let declaration = self.c.declare(phi.result_type, &format!("_{}_next", phi.result))?;
+                self.line(&format!("{} = {};", declaration, value));
+                assignments.push(format!("_{} = _{}_next;", phi.result, phi.result));
+            }
+            for assignment in assignments {
+                self.line(&assignment);
+            }
+            self.close();
+        } else {
+            for (phi, value) in phis {
+                let value = self.value(value)?;
+                self.line(&format!("_{} = {};", phi.result, value));
+            }
+        }
+        Ok(())
+    }
+
+    fn terminator(&mut self, index: usize) -> Result<(), String> {
+        let inst = &self.c.insts[index];
+        match inst.name {
+            "OpBranch" => self.jump(inst.args[0]),
+            "OpBranchConditional" => {
+                let condition = self.value(inst.args[0])?;
+                self.branch(&condition, inst.args[1], inst.args[2])
+            }
+            "OpSwitch" => Err("OpSwitch without OpSelectionMerge".to_string()),
+            "OpReturn" => {
+                self.line("return;");
+                Ok(())
+            }
+            "OpReturnValue" => {
+                let value = self.value(inst.args[0])?;
+                self.line(&format!("return {};", value));
+                Ok(())
+            }
+            "OpKill" | "OpTerminateInvocation" => {
+                self.line("discard;");
+                Ok(())
+            }
+            // Nothing to write, the driver knows the end of the block is never reached
+            "OpUnreachable" => Ok(()),
+            name => Err(format!("{} isn't supported", name)),
+        }
+    }
+
+    fn branch(&mut self, condition: &str, t: u32, f: u32) -> Result<(), String> {
+        if t == f {
+            return self.jump(t);
+        }
+        let leaves = |jump| matches!(jump, Jump::Break | Jump::BackEdge);
+        let (jump_t, jump_f) = (self.classify(t)?, self.classify(f)?);
+        // `if (!c) { break; }` followed by the rest of the loop, instead of nesting it
+        if leaves(jump_f) && !leaves(jump_t) && jump_t != Jump::Fallthrough {
+            self.open(&format!("if (!{})", condition));
+            self.jump(f)?;
+            self.close();
+            return self.jump(t);
+        }
+        if leaves(jump_t) && !leaves(jump_f) && jump_f != Jump::Fallthrough {
+            self.open(&format!("if ({})", condition));
+            self.jump(t)?;
+            self.close();
+            return self.jump(f);
+        }
+
+        self.open(&format!("if ({})", condition));
+        self.jump(t)?;
+        self.scope.pop();
+        let length = self.out.len();
+        self.open("} else");
+        let else_start = self.out.len();
+        self.jump(f)?;
+        if self.out.len() == else_start {
+            // Empty else
+            self.out.truncate(length);
+            self.scope.pop();
+            self.line("}");
+        } else {
+            self.close();
+        }
+        Ok(())
+    }
+
+    fn switch(&mut self, index: usize, merge: u32) -> Result<(), String> {
+        let inst = &self.c.insts[index];
+        let selector_type = self.type_of(inst.args[0])?;
+        let kind = self.c.kind(selector_type);
+        let selector = self.value(inst.args[0])?;
+        let default = inst.args[1];
+
+        let mut cases: Vec<u32> = vec![];
+        let mut labels: HashMap<u32, Vec<String>> = HashMap::new();
+        for pair in inst.args[2..].chunks(2) {
+            if !cases.contains(&pair[1]) {
+                cases.push(pair[1]);
+            }
+            labels.entry(pair[1]).or_default().push(format!("case {}:", literal(kind, pair[0])));
+        }
+        // The default goes last, the phis of the merge block need their values from it too
+        if !cases.contains(&default) {
+            cases.push(default);
+        }
+        labels.entry(default).or_default().push("default:".to_string());
+
+        self.open(&format!("switch ({})", selector));
+        self.constructs.push(Construct::Switch {
+            merge,
+            cases: cases.clone(),
+            current: 0,
+        });
+        let block = self.block;
+        for (i, &case) in cases.iter().enumerate() {
+            if let Some(Construct::Switch {
+                current,
+                ..
+            }) = self.constructs.last_mut()
+            {
+                *current = i;
+            }
+            let (last, labels) = labels[&case].split_last().expect("case labels");
+            for label in labels {
+                self.line(label);
+            }
+            self.open(last);
+            self.block = block;
+            self.assign_phis(case)?;
+            match case == merge {
+                true => self.line("break;"),
+                false => self.block(case)?,
+            }
+            self.close();
+        }
+        self.constructs.pop();
+        self.close();
+        self.block = block;
+        Ok(())
+    }
+
+    fn instructions(&mut self, range: std::ops::Range<usize>) -> Result<(), String> {
+        for inst in &self.c.insts[range] {
+            match inst.name {
+                "OpPhi" | "OpVariable" | "OpLine" | "OpNoLine" | "OpNop" | "OpSelectionMerge" | "OpLoopMerge" => {}
+                _ => self.instruction(inst).map_err(|e| match inst.result {
+                    0 => format!("{}: {}", inst.name, e),
+                    result => format!("{} %{}: {}", inst.name, result, e),
+                })?,
+            }
+        }
+        Ok(())
+    }
+
+    fn place(&mut self, id: u32) -> Result<Place, String> {
+        let place = self.places.get(&id).cloned().ok_or_else(|| format!("%{} isn't a pointer", id))?;
+        let deps = match &place {
+            Place::Expr {
+                deps,
+                ..
+            }
+            | Place::Flat {
+                deps,
+                ..
+            } => deps.clone(),
+            Place::Builtins(_) => vec![],
+        };
+        for dep in deps {
+            self.track(dep);
+        }
+        Ok(place)
+    }
+
+    fn lvalue(&mut self, id: u32) -> Result<String, String> {
+        match self.place(id)? {
+            Place::Expr {
+                expr,
+                ..
+            } => Ok(expr),
+            Place::Builtins(_) => Err("The built-in block can only be accessed through its members".to_string()),
+            Place::Flat {
+                ..
+            } => Err("Uniform data is read only".to_string()),
+        }
+    }
+
+    fn load(&mut self, pointer: u32, ty: u32) -> Result<String, String> {
+        match self.place(pointer)? {
+            Place::Expr {
+                expr,
+                cast: true,
+                ..
+            } => Ok(format!("{}({})", self.c.type_name(ty)?, expr)),
+            Place::Expr {
+                expr,
+                ..
+            } => Ok(expr),
+            Place::Builtins(_) => Err("The built-in block can only be accessed through its members".to_string()),
+            Place::Flat {
+                data,
+                offset,
+                dynamic,
+                matrix,
+                component_stride,
+                ..
+            } => self.flat_load(&data, offset, dynamic.as_deref(), matrix, component_stride, ty),
+        }
+    }
+
+    /// Reads a value of flattened uniform data.
+    fn flat_load(
+        &self,
+        data: &str,
+        offset: u32,
+        dynamic: Option<&str>,
+        matrix: MatrixLayout,
+        component_stride: u32,
+        ty: u32,
+    ) -> Result<String, String> {
+        let c = self.c;
+        let vec4 = |offset: u32| match dynamic {
+            Some(dynamic) => format!("{}[{} + {}]", data, dynamic, offset / 16),
+            None => format!("{}[{}]", data, offset / 16),
+        };
+        let word = |offset: u32| format!("{}.{}", vec4(offset), SWIZZLE[(offset % 16 / 4) as usize]);
+        let convert = |expr: String, kind: Kind, count: u32| match kind {
+            Kind::Float => format!("uintBitsToFloat({})", expr),
+            Kind::Int => format!("{}({})", vector_type(Kind::Int, count), expr),
+            Kind::Bool if count == 1 => format!("({} != 0u)", expr),
+            Kind::Bool => format!("notEqual({}, uvec{}(0u))", expr, count),
+            _ => expr,
+        };
+        let construct = |ty: u32, members: Vec<String>| Ok(format!("{}({})", c.type_name(ty)?, members.join(", ")));
+        if !offset.is_multiple_of(4) {
+            return Err(format!("Offset {} isn't aligned to 4 bytes", offset));
+        }
+
+        match c.ty(ty)? {
+            Type::Bool | Type::Int(_) | Type::Float => Ok(convert(word(offset), c.kind(ty), 1)),
+            &Type::Vector(component, count) => {
+                if component_stride == 4 && offset % 16 + 4 * count <= 16 {
+                    let first = (offset % 16 / 4) as usize;
+                    let vector = match count {
+                        4 => vec4(offset),
+                        _ => format!("{}.{}", vec4(offset), SWIZZLE[first..first + count as usize].concat()),
+                    };
+                    Ok(convert(vector, c.kind(component), count))
+                } else {
+                    let components = (0..count)
+                        .map(|i| self.flat_load(data, offset + i * component_stride, dynamic, matrix, 4, component))
+                        .collect::<Result<_, _>>()?;
+                    construct(ty, components)
+                }
+            }
+            &Type::Matrix(column, count) => {
+                let columns = (0..count)
+                    .map(|i| match matrix.row_major {
+                        true => self.flat_load(data, offset + 4 * i, dynamic, matrix, matrix.stride, column),
+                        false => self.flat_load(data, offset + matrix.stride * i, dynamic, matrix, 4, column),
+                    })
+                    .collect::<Result<_, _>>()?;
+                construct(ty, columns)
+            }
+            &Type::Array(element, length) => {
+                let stride = c.array_stride(ty)?;
+                let elements = (0..length)
+                    .map(|i| self.flat_load(data, offset + stride * i, dynamic, matrix, 4, element))
+                    .collect::<Result<_, _>>()?;
+                construct(ty, elements)
+            }
+            Type::Struct(members) => {
+                let members = (0..members.len() as u32)
+                    .map(|i| {
+                        let offset = offset + c.member_offset(ty, i)?;
+                        let layout = c.member_matrix_layout(ty, i);
+                        self.flat_load(data, offset, dynamic, layout, 4, members[i as usize])
+                    })
+                    .collect::<Result<_, String>>()?;
+                construct(ty, members)
+            }
+            _ => Err(format!("Type %{} can't be read from uniform data", ty)),
+        }
+    }
+
+    fn access_chain(&mut self, inst: &Inst) -> Result<Place, String> {
+        let c = self.c;
+        let mut ty = c.pointee(self.type_of(inst.args[0])?)?;
+        let mut place = self.place(inst.args[0])?;
+        let mut indices = inst.args[1..].iter().copied();
+
+        if let Place::Builtins(block) = place {
+            let member = indices.next().ok_or("Access chain into the built-in block has no index")?;
+            let member = c.constants.get(&member).map(|&i| c.insts[i].args[0]).ok_or("Dynamic built-in index")?;
+            let builtin = c.member_decoration(block, member, DECORATION_BUILT_IN).and_then(|a| a.first().copied());
+            let builtin = builtin.ok_or_else(|| format!("Member {} of the built-in block isn't a built-in", member))?;
+            let name = match BuiltIn::name(builtin) {
+                Some("Position") => "gl_Position",
+                Some("PointSize") => "gl_PointSize",
+                Some("ClipDistance") if c.options.version == GlslVersion::Glsl330 => "gl_ClipDistance",
+                name => return Err(format!("Built-in {} isn't supported", name.unwrap_or("?"))),
+            };
+            let Type::Struct(members) = c.ty(block)? else {
+                return Err("The built-in block isn't a struct".to_string());
+            };
+            ty = members[member as usize];
+            place = Place::Expr {
+                expr: name.to_string(),
+                cast: false,
+                deps: vec![],
+            };
+        }
+
+        for index in indices {
+            let constant = c.constants.get(&index).map(|&i| c.insts[i].args.first().copied().unwrap_or(0));
+            let (next, member) = match c.ty(ty)? {
+                Type::Struct(members) => {
+                    let member = constant.ok_or("Struct member index isn't a constant")?;
+                    let next = *members.get(member as usize).ok_or("Struct member index is out of bounds")?;
+                    (next, Some(member))
+                }
+                &Type::Vector(component, _) => (component, None),
+                &Type::Matrix(column, _) => (column, None),
+                &Type::Array(element, _) | &Type::RuntimeArray(element) => (element, None),
+                _ => return Err("Indexing into a scalar".to_string()),
+            };
+
+            match &mut place {
+                Place::Expr {
+                    expr,
+                    deps,
+                    ..
+                } => match (c.ty(ty)?, member, constant) {
+                    (Type::Struct(_), Some(member), _) => *expr += &format!(".{}", c.member_name(ty, member)),
+                    (Type::Vector(..), _, Some(i)) if i < 4 => *expr += &format!(".{}", SWIZZLE[i as usize]),
+                    (_, _, Some(i)) => *expr += &format!("[{}]", i),
+                    _ => {
+                        *expr += &format!("[{}]", self.value(index)?);
+                        deps.push(index);
+                    }
+                },
+                Place::Flat {
+                    offset,
+                    dynamic,
+                    matrix,
+                    component_stride,
+                    deps,
+                    ..
+                } => match (c.ty(ty)?, constant) {
+                    (Type::Struct(_), _) => {
+                        let member = member.unwrap_or(0);
+                        *offset += c.member_offset(ty, member)?;
+                        *matrix = c.member_matrix_layout(ty, member);
+                    }
+                    (Type::Vector(..), Some(i)) => *offset += i * *component_stride,
+                    (Type::Matrix(..), Some(i)) if matrix.row_major => {
+                        *offset += 4 * i;
+                        *component_stride = matrix.stride;
+                    }
+                    (Type::Matrix(..), Some(i)) => *offset += i * matrix.stride,
+                    (Type::Array(..) | Type::RuntimeArray(_), Some(i)) => *offset += i * c.array_stride(ty)?,
+                    (Type::Array(..) | Type::RuntimeArray(_), None) if c.array_stride(ty)?.is_multiple_of(16) => {
+                        let index_value = self.value_as(index, Kind::Int)?;
+                        let scaled = match c.array_stride(ty)? / 16 {
+                            1 => index_value,
+                            n => format!("{} * {}", index_value, n),
+                        };
+                        *dynamic = Some(match dynamic.take() {
+                            Some(d) => format!("{} + {}", d, scaled),
+                            None => scaled,
+                        });
+                        deps.push(index);
+                    }
+                    (Type::Matrix(..), None) if !matrix.row_major && matrix.stride == 16 => {
+                        let index_value = self.value_as(index, Kind::Int)?;
+                        *dynamic = Some(match dynamic.take() {
+                            Some(d) => format!("{} + {}", d, index_value),
+                            None => index_value,
+                        });
+                        deps.push(index);
+                    }
+                    _ => return Err("Dynamic index into uniform data that isn't 16-byte aligned".to_string()),
+                },
+                Place::Builtins(_) => unreachable!(),
+            }
+            ty = next;
+        }
+        Ok(place)
+    }
+
+    fn instruction(&mut self, inst: &Inst) -> Result<(), String> {
+        let c = self.c;
+        let result_kind = c.kind(inst.result_type);
+        let expr = match inst.name {
+            "OpUndef" | "OpConstantNull" => c.zero(inst.result_type)?,
+            "OpLoad" => {
+                let load = self.load(inst.args[0], inst.result_type)?;
+                if matches!(c.ty(inst.result_type)?, Type::SampledImage(_)) {
+                    // Samplers can't be stored in variables
+                    self.values.insert(inst.result, load);
+                    return Ok(());
+                }
+                load
+            }
+            "OpStore" => {
+                let (target, value) = (self.lvalue(inst.args[0])?, self.value(inst.args[1])?);
+                self.line(&format!("{} = {};", target, value));
+                return Ok(());
+            }
+            "OpCopyMemory" => {
+                let target = self.lvalue(inst.args[0])?;
+                let ty = c.pointee(self.type_of(inst.args[1])?)?;
+                let value = self.load(inst.args[1], ty)?;
+                self.line(&format!("{} = {};", target, value));
+                return Ok(());
+            }
+            "OpAccessChain" | "OpInBoundsAccessChain" => {
+                let place = self.access_chain(inst)?;
+                self.places.insert(inst.result, place);
+                return Ok(());
+            }
+            "OpFunctionCall" => {
+                let name = &c.identifiers[&inst.args[0]];
+                let args = inst.args[1..]
+                    .iter()
+                    .map(|&arg| match self.places.contains_key(&arg) {
+                        true => self.lvalue(arg),
+                        false => self.value(arg),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                let call = format!("{}({})", name, args.join(", "));
+                if matches!(c.ty(inst.result_type)?, Type::Void) {
+                    self.line(&format!("{};", call));
+                    return Ok(());
+                }
+                call
+            }
+            "OpCompositeConstruct" => {
+                let args = inst.args.iter().map(|&arg| self.value(arg)).collect::<Result<Vec<_>, _>>()?;
+                format!("{}({})", c.type_name(inst.result_type)?, args.join(", "))
+            }
+            "OpCompositeExtract" => {
+                let mut expr = self.value(inst.args[0])?;
+                let mut ty = self.type_of(inst.args[0])?;
+                for &i in &inst.args[1..] {
+                    let (access, next) = self.member_access(ty, i)?;
+                    expr += &access;
+                    ty = next;
+                }
+                expr
+            }
+            "OpCompositeInsert" => {
+                let object = self.value(inst.args[0])?;
+                let composite = self.value(inst.args[1])?;
+                self.define(inst, composite)?;
+                let mut path = format!("_{}", inst.result);
+                let mut ty = inst.result_type;
+                for &i in &inst.args[2..] {
+                    let (access, next) = self.member_access(ty, i)?;
+                    path += &access;
+                    ty = next;
+                }
+                self.line(&format!("{} = {};", path, object));
+                return Ok(());
+            }
+            "OpCopyObject" => self.value(inst.args[0])?,
+            "OpVectorExtractDynamic" => format!("{}[{}]", self.value(inst.args[0])?, self.value(inst.args[1])?),
+            "OpVectorInsertDynamic" => {
+                let (component, index) = (self.value(inst.args[1])?, self.value(inst.args[2])?);
+                let vector = self.value(inst.args[0])?;
+                self.define(inst, vector)?;
+                self.line(&format!("_{}[{}] = {};", inst.result, index, component));
+                return Ok(());
+            }
+            "OpVectorShuffle" => self.shuffle(inst)?,
+            "OpTranspose" => format!("transpose({})", self.value(inst.args[0])?),
+            "OpSelect" => {
+                let condition = self.value(inst.args[0])?;
+                let (a, b) = (self.value(inst.args[1])?, self.value(inst.args[2])?);
+                match c.components(self.type_of(inst.args[0])?) {
+                    1 => format!("{} ? {} : {}", condition, a, b),
+                    n => {
+                        let components = (0..n as usize).map(|i| {
+                            let s = SWIZZLE[i];
+                            format!("{}.{} ? {}.{} : {}.{}", condition, s, a, s, b, s)
+                        });
+                        format!("{}({})", c.type_name(inst.result_type)?, components.collect::<Vec<_>>().join(", "))
+                    }
+                }
+            }
+
+            "OpConvertFToU" | "OpConvertFToS" | "OpUConvert" | "OpSConvert" | "OpFConvert" => {
+                format!("{}({})", c.type_name(inst.result_type)?, self.value(inst.args[0])?)
+            }
+            "OpConvertSToF" => {
+                format!("{}({})", c.type_name(inst.result_type)?, self.value_as(inst.args[0], Kind::Int)?)
+            }
+            "OpConvertUToF" => {
+                format!("{}({})", c.type_name(inst.result_type)?, self.value_as(inst.args[0], Kind::Uint)?)
+            }
+            "OpBitcast" => {
+                let from = c.kind(self.type_of(inst.args[0])?);
+                let value = self.value(inst.args[0])?;
+                match (from, result_kind) {
+                    (Kind::Float, Kind::Int) => format!("floatBitsToInt({})", value),
+                    (Kind::Float, Kind::Uint) => format!("floatBitsToUint({})", value),
+                    (Kind::Int, Kind::Float) => format!("intBitsToFloat({})", value),
+                    (Kind::Uint, Kind::Float) => format!("uintBitsToFloat({})", value),
+                    (Kind::Int | Kind::Uint, Kind::Int | Kind::Uint) => {
+                        self.cast(value, self.type_of(inst.args[0])?, result_kind)
+                    }
+                    _ => return Err("Only bitcasts between 32-bit scalars and vectors are supported".to_string()),
+                }
+            }
+
+            "OpSNegate" => format!("-{}", self.value_as(inst.args[0], result_kind)?),
+            "OpFNegate" => format!("-{}", self.value(inst.args[0])?),
+            "OpNot" => format!("~{}", self.value_as(inst.args[0], result_kind)?),
+            "OpIAdd" => self.binary(inst, "+", result_kind)?,
+            "OpISub" => self.binary(inst, "-", result_kind)?,
+            "OpIMul" => self.binary(inst, "*", result_kind)?,
+            "OpUDiv" => self.binary(inst, "/", Kind::Uint)?,
+            "OpSDiv" => self.binary(inst, "/", Kind::Int)?,
+            "OpUMod" => self.binary(inst, "%", Kind::Uint)?,
+            // GLSL leaves the sign of % with negative operands undefined, / truncates like OpSDiv
+            "OpSRem" | "OpSMod" => {
+                let (a, b) = (self.value_as(inst.args[0], Kind::Int)?, self.value_as(inst.args[1], Kind::Int)?);
+                let rem = format!("{} - {} * ({} / {})", a, b, a, b);
+                // OpSMod takes the sign of b: b is added when the remainder and b have opposite signs
+                let expr = match inst.name {
+                    "OpSRem" => rem,
+                    _ => format!("({rem}) - {b} * min(sign({rem}) * sign({b}), 0)", rem = rem, b = b),
+                };
+                match result_kind {
+                    Kind::Int => expr,
+                    _ => format!("{}({})", c.type_name(inst.result_type)?, expr),
+                }
+            }
+            "OpFAdd" => self.binary(inst, "+", Kind::Other)?,
+            "OpFSub" => self.binary(inst, "-", Kind::Other)?,
+            "OpFMul"
+            | "OpVectorTimesScalar"
+            | "OpMatrixTimesScalar"
+            | "OpVectorTimesMatrix"
+            | "OpMatrixTimesVector"
+            | "OpMatrixTimesMatrix" => self.binary(inst, "*", Kind::Other)?,
+            "OpFDiv" => self.binary(inst, "/", Kind::Other)?,
+            "OpFMod" => format!("mod({}, {})", self.value(inst.args[0])?, self.value(inst.args[1])?),
+            "OpFRem" => {
+                let (a, b) = (self.value(inst.args[0])?, self.value(inst.args[1])?);
+                format!("{} - {} * trunc({} / {})", a, b, a, b)
+            }
+            "OpDot" => format!("dot({}, {})", self.value(inst.args[0])?, self.value(inst.args[1])?),
+            "OpOuterProduct" => format!("outerProduct({}, {})", self.value(inst.args[0])?, self.value(inst.args[1])?),
+            "OpShiftLeftLogical" => {
+                format!("{} << {}", self.value_as(inst.args[0], result_kind)?, self.value(inst.args[1])?)
+            }
+            "OpShiftRightLogical" | "OpShiftRightArithmetic" => {
+                let kind = if inst.name == "OpShiftRightLogical" {
+                    Kind::Uint
+                } else {
+                    Kind::Int
+                };
+                let shift = format!("{} >> {}", self.value_as(inst.args[0], kind)?, self.value(inst.args[1])?);
+                match kind == result_kind {
+                    true => shift,
+                    false => format!("{}({})", c.type_name(inst.result_type)?, shift),
+                }
+            }
+            "OpBitwiseOr" => self.binary(inst, "|", result_kind)?,
+            "OpBitwiseXor" => self.binary(inst, "^", result_kind)?,
+            "OpBitwiseAnd" => self.binary(inst, "&", result_kind)?,
+
+            "OpAny" => format!("any({})", self.value(inst.args[0])?),
+            "OpAll" => format!("all({})", self.value(inst.args[0])?),
+            "OpIsNan" => format!("isnan({})", self.value(inst.args[0])?),
+            "OpIsInf" => format!("isinf({})", self.value(inst.args[0])?),
+            "OpLogicalEqual" => self.compare(inst, "==", "equal", Kind::Other)?,
+            "OpLogicalNotEqual" => self.compare(inst, "!=", "notEqual", Kind::Other)?,
+            "OpLogicalOr" | "OpLogicalAnd" => {
+                let op = if inst.name == "OpLogicalOr" {
+                    "||"
+                } else {
+                    "&&"
+                };
+                let (a, b) = (self.value(inst.args[0])?, self.value(inst.args[1])?);
+                match c.components(inst.result_type) {
+                    1 => format!("{} {} {}", a, op, b),
+                    n => {
+                        let components =
+                            (0..n as usize).map(|i| format!("{}.{} {} {}.{}", a, SWIZZLE[i], op, b, SWIZZLE[i]));
+                        format!("bvec{}({})", n, components.collect::<Vec<_>>().join(", "))
+                    }
+                }
+            }
+            "OpLogicalNot" => match c.components(inst.result_type) {
+                1 => format!("!{}", self.value(inst.args[0])?),
+                _ => format!("not({})", self.value(inst.args[0])?),
+            },
+            "OpIEqual" => self.compare(inst, "==", "equal", Kind::Other)?,
+            "OpINotEqual" => self.compare(inst, "!=", "notEqual", Kind::Other)?,
+            "OpUGreaterThan" => self.compare(inst, ">", "greaterThan", Kind::Uint)?,
+            "OpSGreaterThan" => self.compare(inst, ">", "greaterThan", Kind::Int)?,
+            "OpUGreaterThanEqual" => self.compare(inst, ">=", "greaterThanEqual", Kind::Uint)?,
+            "OpSGreaterThanEqual" => self.compare(inst, ">=", "greaterThanEqual", Kind::Int)?,
+            "OpULessThan" => self.compare(inst, "<", "lessThan", Kind::Uint)?,
+            "OpSLessThan" => self.compare(inst, "<", "lessThan", Kind::Int)?,
+            "OpULessThanEqual" => self.compare(inst, "<=", "lessThanEqual", Kind::Uint)?,
+            "OpSLessThanEqual" => self.compare(inst, "<=", "lessThanEqual", Kind::Int)?,
+            "OpFOrdEqual" | "OpFUnordEqual" => self.compare(inst, "==", "equal", Kind::Other)?,
+            "OpFOrdNotEqual" | "OpFUnordNotEqual" => self.compare(inst, "!=", "notEqual", Kind::Other)?,
+            "OpFOrdLessThan" => self.compare(inst, "<", "lessThan", Kind::Other)?,
+            "OpFOrdGreaterThan" => self.compare(inst, ">", "greaterThan", Kind::Other)?,
+            "OpFOrdLessThanEqual" => self.compare(inst, "<=", "lessThanEqual", Kind::Other)?,
+            "OpFOrdGreaterThanEqual" => self.compare(inst, ">=", "greaterThanEqual", Kind::Other)?,
+            // Unordered comparisons are the negation of the opposite ordered one, true when an operand is NaN
+            "OpFUnordLessThan" => self.negated_compare(inst, ">=", "greaterThanEqual")?,
+            "OpFUnordGreaterThan" => self.negated_compare(inst, "<=", "lessThanEqual")?,
+            "OpFUnordLessThanEqual" => self.negated_compare(inst, ">", "greaterThan")?,
+            "OpFUnordGreaterThanEqual" => self.negated_compare(inst, "<", "lessThan")?,
+
+            // Only the plain derivatives exist before GLSL 4.50, the fine and coarse ones are hints
+            "OpDPdx" | "OpDPdxFine" | "OpDPdxCoarse" => format!("dFdx({})", self.value(inst.args[0])?),
+            "OpDPdy" | "OpDPdyFine" | "OpDPdyCoarse" => format!("dFdy({})", self.value(inst.args[0])?),
+            "OpFwidth" | "OpFwidthFine" | "OpFwidthCoarse" => format!("fwidth({})", self.value(inst.args[0])?),
+
+            "OpImage" => {
+                let image = self.value(inst.args[0])?;
+                self.values.insert(inst.result, image);
+                return Ok(());
+            }
+            "OpSampledImage" => {
+                return Err("Separate images and samplers aren't supported, use combined image samplers".to_string());
+            }
+            "OpImageSampleImplicitLod"
+            | "OpImageSampleExplicitLod"
+            | "OpImageSampleDrefImplicitLod"
+            | "OpImageSampleDrefExplicitLod"
+            | "OpImageSampleProjImplicitLod"
+            | "OpImageSampleProjExplicitLod" => self.sample(inst)?,
+            "OpImageFetch" => self.fetch(inst)?,
+            "OpImageQuerySizeLod" | "OpImageQuerySize" => {
+                let image = self.value(inst.args[0])?;
+                let multisampled = match c.ty(self.type_of(inst.args[0])?)? {
+                    &Type::SampledImage(image) => matches!(
+                        c.ty(image)?,
+                        Type::Image {
+                            multisampled: true,
+                            ..
+                        }
+                    ),
+                    _ => false,
+                };
+                let size = match inst.args.get(1) {
+                    Some(&lod) => format!("textureSize({}, {})", image, self.value_as(lod, Kind::Int)?),
+                    None if multisampled => format!("textureSize({})", image),
+                    None => format!("textureSize({}, 0)", image),
+                };
+                match result_kind {
+                    Kind::Int => size,
+                    _ => format!("{}({})", c.type_name(inst.result_type)?, size),
+                }
+            }
+
+            "OpExtInst" if c.glsl_std_450.contains(&inst.args[0]) => self.glsl_std_450(inst)?,
+            "OpExtInst" => return Err("Unsupported extended instruction set".to_string()),
+            _ => return Err("Unsupported instruction".to_string()),
+        };
+        self.define(inst, expr)
+    }
+
+    /// Accessor of a member of a composite, and the type of the member.
+    fn member_access(&self, ty: u32, i: u32) -> Result<(String, u32), String> {
+        let c = self.c;
+        Ok(match c.ty(ty)? {
+            Type::Struct(members) => {
+                let member = *members.get(i as usize).ok_or("Struct member index is out of bounds")?;
+                (format!(".{}", c.member_name(ty, i)), member)
+            }
+            &Type::Vector(component, _) if i < 4 => (format!(".{}", SWIZZLE[i as usize]), component),
+            &Type::Matrix(member, _) | &Type::Array(member, _) => (format!("[{}]", i), member),
+            _ => return Err(format!("Index {} is out of bounds", i)),
+        })
+    }
+
+    fn shuffle(&mut self, inst: &Inst) -> Result<String, String> {
+        let c = self.c;
+        let (a, b) = (self.value(inst.args[0])?, self.value(inst.args[1])?);
+        let a_count = c.components(self.type_of(inst.args[0])?);
+        // Undefined components (0xffffffff) take the first component
+        let indices: Vec<u32> = inst.args[2..]
+            .iter()
+            .map(|&i| {
+                if i == u32::MAX {
+                    0
+                } else {
+                    i
+                }
+            })
+            .collect();
+        if indices.iter().all(|&i| i < a_count) {
+            return Ok(format!("{}.{}", a, indices.iter().map(|&i| SWIZZLE[i as usize]).collect::<String>()));
+        }
+        if indices.iter().all(|&i| i >= a_count && i - a_count < 4) {
+            let swizzle: String = indices.iter().map(|&i| SWIZZLE[(i - a_count) as usize]).collect();
+            return Ok(format!("{}.{}", b, swizzle));
+        }
+        let components = indices
+            .iter()
+            .map(|&i| match i < a_count {
+                true => format!("{}.{}", a, SWIZZLE[i as usize]),
+                false => format!("{}.{}", b, SWIZZLE[((i - a_count) % 4) as usize]),
+            })
+            .collect::<Vec<_>>();
+        Ok(format!("{}({})", c.type_name(inst.result_type)?, components.join(", ")))
+    }
+
+    /// Binary operator, with the operands converted to a kind of integer and the result converted back.
+    fn binary(&mut self, inst: &Inst, op: &str, kind: Kind) -> Result<String, String> {
+        let a = self.value_as(inst.args[0], kind)?;
+        let b = self.value_as(inst.args[1], kind)?;
+        let expr = format!("{} {} {}", a, op, b);
+        let result_kind = self.c.kind(inst.result_type);
+        match kind == Kind::Other || kind == result_kind {
+            true => Ok(expr),
+            false => Ok(format!("{}({})", self.c.type_name(inst.result_type)?, expr)),
+        }
+    }
+
+    /// Comparison, with an operator for scalars and a function for vectors.
+    fn compare(&mut self, inst: &Inst, op: &str, function: &str, kind: Kind) -> Result<String, String> {
+        let a = self.value_as(inst.args[0], kind)?;
+        // Both operands need the same signedness
+        let kind = if kind == Kind::Other {
+            self.c.kind(self.type_of(inst.args[0])?)
+        } else {
+            kind
+        };
+        let b = self.value_as(inst.args[1], kind)?;
+        Ok(match self.c.components(inst.result_type) {
+            1 => format!("{} {} {}", a, op, b),
+            _ => format!("{}({}, {})", function, a, b),
+        })
+    }
+
+    fn negated_compare(&mut self, inst: &Inst, op: &str, function: &str) -> Result<String, String> {
+        let compare = self.compare(inst, op, function, Kind::Other)?;
+        Ok(match self.c.components(inst.result_type) {
+            1 => format!("!({})", compare),
+            _ => format!("not({})", compare),
+        })
+    }
+
+    fn sample(&mut self, inst: &Inst) -> Result<String, String> {
+        let c = self.c;
+        let proj = inst.name.contains("Proj");
+        let dref = inst.name.contains("Dref");
+        let sampler = self.value(inst.args[0])?;
+        let mut coordinate = self.value(inst.args[1])?;
+        let mut next = 2;
+        if dref {
+            // The reference goes in the last component of the coordinate
+            let count = c.components(self.type_of(inst.args[1])?) + 1;
+            if count > 4 {
+                return Err("Depth comparisons with 4 component coordinates aren't supported".to_string());
+            }
+            coordinate = format!("vec{}({}, {})", count, coordinate, self.value(inst.args[2])?);
+            next = 3;
+        }
+        let operands = self.image_operands(inst, next)?;
+        if operands.sample.is_some() || operands.offset_is_dynamic {
+            return Err("Unsupported image operands".to_string());
+        }
+        let mut function = "texture".to_string();
+        let mut args = vec![sampler, coordinate];
+        if proj {
+            function += "Proj";
+        }
+        if let Some(lod) = operands.lod {
+            function += "Lod";
+            args.push(lod);
+        }
+        if let Some((dx, dy)) = operands.grad {
+            function += "Grad";
+            args.extend([dx, dy]);
+        }
+        if let Some(offset) = operands.offset {
+            function += "Offset";
+            args.push(offset);
+        }
+        args.extend(operands.bias);
+        Ok(format!("{}({})", function, args.join(", ")))
+    }
+
+    fn fetch(&mut self, inst: &Inst) -> Result<String, String> {
+        let image = self.value(inst.args[0])?;
+        let coordinate = self.value_as(inst.args[1], Kind::Int)?;
+        let operands = self.image_operands(inst, 2)?;
+        if operands.offset_is_dynamic || operands.bias.is_some() || operands.grad.is_some() {
+            return Err("Unsupported image operands".to_string());
+        }
+        let lod = operands.sample.or(operands.lod).unwrap_or_else(|| "0".to_string());
+        Ok(match operands.offset {
+            Some(offset) => format!("texelFetchOffset({}, {}, {}, {})", image, coordinate, lod, offset),
+            None => format!("texelFetch({}, {}, {})", image, coordinate, lod),
+        })
+    }
+
+    fn image_operands(&mut self, inst: &Inst, index: usize) -> Result<ImageOperandValues, String> {
+        let mut values = ImageOperandValues::default();
+        let Some(&mask) = inst.args.get(index) else {
+            return Ok(values);
+        };
+        let mut ids = inst.args[index + 1..].iter().copied();
+        let mut next = |writer: &mut Self, kind: Kind| {
+            let id = ids.next().ok_or("Missing image operands")?;
+            writer.value_as(id, kind)
+        };
+        let supported = ImageOperands::Bias as u32
+            | ImageOperands::Lod as u32
+            | ImageOperands::Grad as u32
+            | ImageOperands::ConstOffset as u32
+            | ImageOperands::Offset as u32
+            | ImageOperands::Sample as u32;
+        if mask & !supported != 0 {
+            return Err(format!("Unsupported image operands 0x{:x}", mask & !supported));
+        }
+        // Operands follow in the order of their bits
+        if mask & ImageOperands::Bias as u32 != 0 {
+            values.bias = Some(next(self, Kind::Other)?);
+        }
+        if mask & ImageOperands::Lod as u32 != 0 {
+            let kind = if inst.name == "OpImageFetch" {
+                Kind::Int
+            } else {
+                Kind::Other
+            };
+            values.lod = Some(next(self, kind)?);
+        }
+        if mask & ImageOperands::Grad as u32 != 0 {
+            values.grad = Some((next(self, Kind::Other)?, next(self, Kind::Other)?));
+        }
+        if mask & ImageOperands::ConstOffset as u32 != 0 {
+            values.offset = Some(next(self, Kind::Int)?);
+        }
+        if mask & ImageOperands::Offset as u32 != 0 {
+            values.offset_is_dynamic = true;
+        }
+        if mask & ImageOperands::Sample as u32 != 0 {
+            values.sample = Some(next(self, Kind::Int)?);
+        }
+        Ok(values)
+    }
+
+    fn glsl_std_450(&mut self, inst: &Inst) -> Result<String, String> {
+        let c = self.c;
+        let name = GLSL_STD_450.get(inst.args[1] as usize).copied().unwrap_or("?");
+        let operands = &inst.args[2..];
+        let result_kind = c.kind(inst.result_type);
+        let call = |writer: &mut Self, function: &str, kind: Kind| -> Result<String, String> {
+            let args = operands.iter().map(|&id| writer.value_as(id, kind)).collect::<Result<Vec<_>, _>>()?;
+            let call = format!("{}({})", function, args.join(", "));
+            match kind == Kind::Other || kind == result_kind {
+                true => Ok(call),
+                false => Ok(format!("{}({})", c.type_name(inst.result_type)?, call)),
+            }
+        };
+        let function = match name {
+            "Round" => "round",
+            "RoundEven" => "roundEven",
+            "Trunc" => "trunc",
+            "FAbs" => "abs",
+            "FSign" => "sign",
+            "Floor" => "floor",
+            "Ceil" => "ceil",
+            "Fract" => "fract",
+            "Radians" => "radians",
+            "Degrees" => "degrees",
+            "Sin" => "sin",
+            "Cos" => "cos",
+            "Tan" => "tan",
+            "Asin" => "asin",
+            "Acos" => "acos",
+            "Atan" | "Atan2" => "atan",
+            "Sinh" => "sinh",
+            "Cosh" => "cosh",
+            "Tanh" => "tanh",
+            "Asinh" => "asinh",
+            "Acosh" => "acosh",
+            "Atanh" => "atanh",
+            "Pow" => "pow",
+            "Exp" => "exp",
+            "Log" => "log",
+            "Exp2" => "exp2",
+            "Log2" => "log2",
+            "Sqrt" => "sqrt",
+            "InverseSqrt" => "inversesqrt",
+            "Determinant" => "determinant",
+            "MatrixInverse" => "inverse",
+            "FMin" | "NMin" => "min",
+            "FMax" | "NMax" => "max",
+            "FClamp" | "NClamp" => "clamp",
+            "FMix" => "mix",
+            "Step" => "step",
+            "SmoothStep" => "smoothstep",
+            "Length" => "length",
+            "Distance" => "distance",
+            "Cross" => "cross",
+            "Normalize" => "normalize",
+            "FaceForward" => "faceforward",
+            "Reflect" => "reflect",
+            "Refract" => "refract",
+            "SAbs" => return call(self, "abs", Kind::Int),
+            "SSign" => return call(self, "sign", Kind::Int),
+            "SMin" => return call(self, "min", Kind::Int),
+            "SMax" => return call(self, "max", Kind::Int),
+            "SClamp" => return call(self, "clamp", Kind::Int),
+            "UMin" => return call(self, "min", Kind::Uint),
+            "UMax" => return call(self, "max", Kind::Uint),
+            "UClamp" => return call(self, "clamp", Kind::Uint),
+            "Fma" => {
+                let (a, b, x) = (self.value(operands[0])?, self.value(operands[1])?, self.value(operands[2])?);
+                return Ok(format!("{} * {} + {}", a, b, x));
+            }
+            "Ldexp" => {
+                let ty = c.type_name(self.type_of(operands[0])?)?;
+                return Ok(format!("{} * exp2({}({}))", self.value(operands[0])?, ty, self.value(operands[1])?));
+            }
+            "PackSnorm2x16" | "UnpackSnorm2x16" | "PackUnorm2x16" | "UnpackUnorm2x16" | "PackHalf2x16"
+            | "UnpackHalf2x16"
+                if c.options.version == GlslVersion::Essl300 =>
+            {
+                let mut function = name.to_string();
+                function[..1].make_ascii_lowercase();
+                return call(self, &function, Kind::Other);
+            }
+            _ => {
+                let version = match c.options.version {
+                    GlslVersion::Glsl330 => "GLSL 330",
+                    GlslVersion::Essl300 => "GLSL ES 300",
+                };
+                return Err(format!("GLSL.std.450 {} isn't available in {}", name, version));
+            }
+        };
+        call(self, function, Kind::Other)
+    }
+}
+
+#[derive(Default)]
+struct ImageOperandValues {
+    bias: Option<String>,
+    lod: Option<String>,
+    grad: Option<(String, String)>,
+    offset: Option<String>,
+    offset_is_dynamic: bool,
+    sample: Option<String>,
+}
+
+fn is_terminator(name: &str) -> bool {
+    matches!(
+        name,
+        "OpBranch"
+            | "OpBranchConditional"
+            | "OpSwitch"
+            | "OpReturn"
+            | "OpReturnValue"
+            | "OpKill"
+            | "OpTerminateInvocation"
+            | "OpUnreachable"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spirv_as::assemble;
+
+    /// Vertex stage like atlas.vert: a quad moved by the push constants, transformed by a matrix of a uniform block
+    /// and shrunk by a loop.
+    const QUAD_VERT: &str = r#"
+               OpCapability Shader
+               OpMemoryModel Logical GLSL450
+               OpEntryPoint Vertex %main "main" %per_vertex %vertex_index %uv
+               OpName %main "main"
+               OpName %camera "Camera"
+               OpMemberName %camera 0 "view"
+               OpName %camera_var "camera"
+               OpName %push "Push"
+               OpName %vertex_index "vertex_index"
+               OpMemberDecorate %gl_PerVertex 0 BuiltIn Position
+               OpMemberDecorate %gl_PerVertex 1 BuiltIn PointSize
+               OpDecorate %gl_PerVertex Block
+               OpDecorate %vertex_index BuiltIn VertexIndex
+               OpDecorate %uv Location 1
+               OpMemberDecorate %camera 0 ColMajor
+               OpMemberDecorate %camera 0 Offset 0
+               OpMemberDecorate %camera 0 MatrixStride 16
+               OpDecorate %camera Block
+               OpDecorate %camera_var DescriptorSet 1
+               OpDecorate %camera_var Binding 2
+               OpMemberDecorate %push 0 Offset 0
+               OpMemberDecorate %push 1 Offset 8
+               OpDecorate %push Block
+       %void = OpTypeVoid
+         %fn = OpTypeFunction %void
+      %float = OpTypeFloat 32
+        %int = OpTypeInt 32 1
+       %uint = OpTypeInt 32 0
+       %bool = OpTypeBool
+    %v2float = OpTypeVector %float 2
+    %v4float = OpTypeVector %float 4
+%mat4v4float = OpTypeMatrix %v4float 4
+%gl_PerVertex = OpTypeStruct %v4float %float
+%ptr_per_vertex = OpTypePointer Output %gl_PerVertex
+ %per_vertex = OpVariable %ptr_per_vertex Output
+ %ptr_out_v4 = OpTypePointer Output %v4float
+ %ptr_out_v2 = OpTypePointer Output %v2float
+ %ptr_in_int = OpTypePointer Input %int
+%vertex_index = OpVariable %ptr_in_int Input
+         %uv = OpVariable %ptr_out_v2 Output
+     %camera = OpTypeStruct %mat4v4float
+ %ptr_camera = OpTypePointer Uniform %camera
+ %camera_var = OpVariable %ptr_camera Uniform
+   %ptr_mat4 = OpTypePointer Uniform %mat4v4float
+       %push = OpTypeStruct %v2float %uint
+   %ptr_push = OpTypePointer PushConstant %push
+%ptr_push_v2 = OpTypePointer PushConstant %v2float
+%ptr_push_uint = OpTypePointer PushConstant %uint
+         %pc = OpVariable %ptr_push PushConstant
+      %int_0 = OpConstant %int 0
+      %int_1 = OpConstant %int 1
+     %uint_0 = OpConstant %uint 0
+     %uint_1 = OpConstant %uint 1
+    %float_0 = OpConstant %float 0
+    %float_1 = OpConstant %float 1
+   %float_05 = OpConstant %float 0.5
+       %main = OpFunction %void None %fn
+      %entry = OpLabel
+      %index = OpLoad %int %vertex_index
+          %x = OpBitwiseAnd %int %index %int_1
+         %xf = OpConvertSToF %float %x
+     %corner = OpCompositeConstruct %v2float %xf %float_0
+ %offset_ptr = OpAccessChain %ptr_push_v2 %pc %int_0
+     %offset = OpLoad %v2float %offset_ptr
+      %moved = OpFAdd %v2float %corner %offset
+  %count_ptr = OpAccessChain %ptr_push_uint %pc %int_1
+      %count = OpLoad %uint %count_ptr
+               OpBranch %header
+     %header = OpLabel
+          %i = OpPhi %uint %uint_0 %entry %next_i %continue
+          %p = OpPhi %v2float %moved %entry %half %continue
+               OpLoopMerge %merge %continue None
+               OpBranch %check
+      %check = OpLabel
+       %more = OpULessThan %bool %i %count
+               OpBranchConditional %more %body %merge
+       %body = OpLabel
+       %half = OpVectorTimesScalar %v2float %p %float_05
+               OpBranch %continue
+   %continue = OpLabel
+     %next_i = OpIAdd %uint %i %uint_1
+               OpBranch %header
+      %merge = OpLabel
+   %view_ptr = OpAccessChain %ptr_mat4 %camera_var %int_0
+       %view = OpLoad %mat4v4float %view_ptr
+   %position = OpCompositeConstruct %v4float %p %float_0 %float_1
+%transformed = OpMatrixTimesVector %v4float %view %position
+%position_ptr = OpAccessChain %ptr_out_v4 %per_vertex %int_0
+               OpStore %position_ptr %transformed
+               OpStore %uv %corner
+               OpReturn
+               OpFunctionEnd
+"#;
+
+    /// Fragment stage like atlas.frag: samples a texture, discards transparent texels and picks a tint with a
+    /// switch.
+    const TINT_FRAG: &str = r#"
+               OpCapability Shader
+               OpMemoryModel Logical GLSL450
+               OpEntryPoint Fragment %main "main" %uv %mode %color
+               OpExecutionMode %main OriginUpperLeft
+               OpName %atlas "atlas"
+               OpName %color "out_color"
+               OpDecorate %uv Location 1
+               OpDecorate %mode Location 2
+               OpDecorate %mode Flat
+               OpDecorate %color Location 0
+               OpDecorate %atlas DescriptorSet 0
+               OpDecorate %atlas Binding 3
+               OpMemberDecorate %push 0 Offset 0
+               OpMemberDecorate %push 1 Offset 8
+               OpDecorate %push Block
+       %void = OpTypeVoid
+         %fn = OpTypeFunction %void
+      %float = OpTypeFloat 32
+       %uint = OpTypeInt 32 0
+        %int = OpTypeInt 32 1
+       %bool = OpTypeBool
+    %v2float = OpTypeVector %float 2
+    %v4float = OpTypeVector %float 4
+      %image = OpTypeImage %float 2D 0 0 0 1 Unknown
+    %sampled = OpTypeSampledImage %image
+%ptr_sampled = OpTypePointer UniformConstant %sampled
+      %atlas = OpVariable %ptr_sampled UniformConstant
+  %ptr_in_v2 = OpTypePointer Input %v2float
+%ptr_in_uint = OpTypePointer Input %uint
+ %ptr_out_v4 = OpTypePointer Output %v4float
+         %uv = OpVariable %ptr_in_v2 Input
+       %mode = OpVariable %ptr_in_uint Input
+      %color = OpVariable %ptr_out_v4 Output
+       %push = OpTypeStruct %v2float %float
+   %ptr_push = OpTypePointer PushConstant %push
+%ptr_push_float = OpTypePointer PushConstant %float
+         %pc = OpVariable %ptr_push PushConstant
+      %int_1 = OpConstant %int 1
+    %float_0 = OpConstant %float 0
+    %float_1 = OpConstant %float 1
+   %float_05 = OpConstant %float 0.5
+       %red = OpConstantComposite %v4float %float_1 %float_0 %float_0 %float_1
+      %white = OpConstantComposite %v4float %float_1 %float_1 %float_1 %float_1
+       %main = OpFunction %void None %fn
+      %entry = OpLabel
+    %sampler = OpLoad %sampled %atlas
+   %uv_value = OpLoad %v2float %uv
+      %texel = OpImageSampleImplicitLod %v4float %sampler %uv_value
+      %alpha = OpCompositeExtract %float %texel 3
+%transparent = OpFOrdLessThan %bool %alpha %float_05
+               OpSelectionMerge %visible None
+               OpBranchConditional %transparent %discard %visible
+    %discard = OpLabel
+               OpKill
+    %visible = OpLabel
+ %mode_value = OpLoad %uint %mode
+               OpSelectionMerge %tinted None
+               OpSwitch %mode_value %tinted 0 %case_red 1 %case_faded
+   %case_red = OpLabel
+               OpBranch %tinted
+ %case_faded = OpLabel
+  %fade_ptr = OpAccessChain %ptr_push_float %pc %int_1
+      %fade = OpLoad %float %fade_ptr
+     %faded = OpVectorTimesScalar %v4float %white %fade
+               OpBranch %tinted
+     %tinted = OpLabel
+       %tint = OpPhi %v4float %white %visible %red %case_red %faded %case_faded
+     %result = OpFMul %v4float %texel %tint
+               OpStore %color %result
+               OpReturn
+               OpFunctionEnd
+"#;
+
+    /// Uniform block with a scalar layout, that has to be flattened, and an array indexed by a varying.
+    const SCALAR_BLOCK_FRAG: &str = r#"
+               OpCapability Shader
+               OpMemoryModel Logical GLSL450
+               OpEntryPoint Fragment %main "main" %index %color %coord
+               OpExecutionMode %main OriginUpperLeft
+               OpName %params "Params"
+               OpName %params_var "params"
+               OpName %color "color"
+               OpDecorate %index Location 0
+               OpDecorate %index Flat
+               OpDecorate %color Location 0
+               OpDecorate %coord BuiltIn FragCoord
+               OpDecorate %colors ArrayStride 16
+               OpMemberDecorate %params 0 Offset 0
+               OpMemberDecorate %params 1 Offset 4
+               OpMemberDecorate %params 2 Offset 16
+               OpDecorate %params Block
+               OpDecorate %params_var DescriptorSet 0
+               OpDecorate %params_var Binding 0
+       %void = OpTypeVoid
+         %fn = OpTypeFunction %void
+      %float = OpTypeFloat 32
+        %int = OpTypeInt 32 1
+    %v2float = OpTypeVector %float 2
+    %v4float = OpTypeVector %float 4
+      %int_0 = OpConstant %int 0
+      %int_1 = OpConstant %int 1
+      %int_2 = OpConstant %int 2
+     %colors = OpTypeArray %v4float %int_2
+     %params = OpTypeStruct %float %v2float %colors
+ %ptr_params = OpTypePointer Uniform %params
+ %params_var = OpVariable %ptr_params Uniform
+%ptr_uniform_v2 = OpTypePointer Uniform %v2float
+%ptr_uniform_v4 = OpTypePointer Uniform %v4float
+ %ptr_in_int = OpTypePointer Input %int
+  %ptr_in_v4 = OpTypePointer Input %v4float
+ %ptr_out_v4 = OpTypePointer Output %v4float
+      %index = OpVariable %ptr_in_int Input
+      %coord = OpVariable %ptr_in_v4 Input
+      %color = OpVariable %ptr_out_v4 Output
+       %main = OpFunction %void None %fn
+      %entry = OpLabel
+          %i = OpLoad %int %index
+  %color_ptr = OpAccessChain %ptr_uniform_v4 %params_var %int_2 %i
+          %c = OpLoad %v4float %color_ptr
+  %scale_ptr = OpAccessChain %ptr_uniform_v2 %params_var %int_1
+      %scale = OpLoad %v2float %scale_ptr
+          %p = OpLoad %v4float %coord
+         %xy = OpVectorShuffle %v2float %p %p 0 1
+     %scaled = OpFMul %v2float %xy %scale
+         %rg = OpVectorShuffle %v4float %c %scaled 4 5 2 3
+               OpStore %color %rg
+               OpReturn
+               OpFunctionEnd
+"#;
+
+    #[test]
+    fn vertex() {
+        let module = assemble(QUAD_VERT).unwrap();
+        let shader = to_glsl(&module, "main", &GlslOptions::default()).unwrap();
+        let source = &shader.source;
+        assert!(source.starts_with("#version 330 core\n"));
+        assert!(source.contains("layout(std140) uniform Camera {\n    mat4 view;\n} camera;\n"));
+        assert!(source.contains("out vec2 _location1;\n"));
+        // Push constants are read from the flattened block
+        assert!(source.contains("uintBitsToFloat(_push_constants[0].xy)"));
+        assert!(source.contains("= _push_constants[0].z;"));
+        // The loop exits at the top and its continue block is written before going back
+        assert!(source.contains("for (;;) {"));
+        assert!(source.contains("        if (!_53) {\n            break;\n        }\n"));
+        assert!(source.contains("        _46 = _47;\n        _49 = _50;\n        continue;\n"));
+        assert!(source.contains("gl_Position = _58;"));
+        assert!(source.contains("    _main();\n    gl_Position.y = -gl_Position.y;\n"));
+        assert_eq!(
+            shader.uniform_blocks,
+            [
+                UniformBinding {
+                    name: "Camera".to_string(),
+                    descriptor: Some((1, 2)),
+                    binding: 10,
+                },
+                UniformBinding {
+                    name: "PushConstants".to_string(),
+                    descriptor: None,
+                    binding: 23,
+                },
+            ]
+        );
+        assert!(shader.samplers.is_empty());
+
+        let options = GlslOptions {
+            fix_clip_space: false,
+            ..Default::default()
+        };
+        let shader = to_glsl(&module, "main", &options).unwrap();
+        assert!(!shader.source.contains("gl_Position.y"));
+        assert_eq!(to_glsl(&module, "other", &options).unwrap_err(), "No entry point named 'other'");
+    }
+
+    #[test]
+    fn fragment() {
+        let module = assemble(TINT_FRAG).unwrap();
+        let options = GlslOptions {
+            version: GlslVersion::Essl300,
+            ..Default::default()
+        };
+        let shader = to_glsl(&module, "main", &options).unwrap();
+        let source = &shader.source;
+        assert!(source.starts_with("#version 300 es\nprecision highp float;\nprecision highp int;\n"));
+        assert!(source.contains("uniform highp sampler2D atlas;\nin vec2 _location1;\nflat in uint _location2;\n"));
+        assert!(source.contains("layout(location = 0) out vec4 out_color;\n"));
+        assert!(source.contains("texture(atlas, _32)"));
+        assert!(source.contains("if (_35) {\n        discard;\n    }\n"));
+        assert!(
+            source.contains("        case 0u: {\n            _45 = vec4(1.0, 0.0, 0.0, 1.0);\n            break;\n")
+        );
+        assert!(source.contains("uintBitsToFloat(_push_constants[0].z)"));
+        assert!(
+            source.contains("        default: {\n            _45 = vec4(1.0, 1.0, 1.0, 1.0);\n            break;\n")
+        );
+        assert!(!source.contains("gl_Position"));
+        assert_eq!(
+            shader.samplers,
+            [UniformBinding {
+                name: "atlas".to_string(),
+                descriptor: Some((0, 3)),
+                binding: 3,
+            }]
+        );
+        assert_eq!(shader.uniform_blocks.len(), 1);
+    }
+
+    #[test]
+    fn flattened_block() {
+        let module = assemble(SCALAR_BLOCK_FRAG).unwrap();
+        let shader = to_glsl(&module, "main", &GlslOptions::default()).unwrap();
+        let expected = r#"#version 330 core
+
+layout(std140) uniform Params {
+    uvec4 params[3];
+};
+flat in int _location0;
+layout(origin_upper_left) in vec4 gl_FragCoord;
+layout(location = 0) out vec4 color;
+
+void _main() {
+    int _24 = _location0;
+    vec4 _26 = uintBitsToFloat(params[_24 + 1]);
+    vec2 _28 = uintBitsToFloat(params[0].yz);
+    vec4 _29 = gl_FragCoord;
+    vec2 _30 = _29.xy;
+    vec2 _31 = _30 * _28;
+    vec4 _32 = vec4(_31.x, _31.y, _26.z, _26.w);
+    color = _32;
+    return;
+}
+
+void main() {
+    _main();
+}
+"#;
+        assert_eq!(shader.source, expected);
+
+        let es = GlslOptions {
+            version: GlslVersion::Essl300,
+            ..Default::default()
+        };
+        assert!(!to_glsl(&module, "main", &es).unwrap().source.contains("origin_upper_left"));
+    }
+
+    #[test]
+    fn signed_remainder() {
+        // OpSRem takes the sign of the dividend and OpSMod the sign of the divisor: -7 and 3 give -1 and 2
+        let text = SCALAR_BLOCK_FRAG.replace(
+            "%i = OpLoad %int %index\n",
+            "%i = OpLoad %int %index\n%rem = OpSRem %int %i %int_2\n%mod = OpSMod %int %rem %int_2\n",
+        );
+        let module = assemble(&text.replace("%params_var %int_2 %i", "%params_var %int_2 %mod")).unwrap();
+        let source = to_glsl(&module, "main", &GlslOptions::default()).unwrap().source;
+        assert!(source.contains("int _25 = _24 - 2 * (_24 / 2);"));
+        assert!(source.contains("int _26 = (_25 - 2 * (_25 / 2)) - 2 * min(sign(_25 - 2 * (_25 / 2)) * sign(2), 0);"));
+    }
+
+    #[test]
+    fn errors() {
+        let compute = SCALAR_BLOCK_FRAG
+            .replace("Fragment %main", "GLCompute %main")
+            .replace("OriginUpperLeft", "LocalSize 1 1 1");
+        let module = assemble(&compute).unwrap();
+        let error = to_glsl(&module, "main", &GlslOptions::default()).unwrap_err();
+        assert_eq!(error, "Only vertex and fragment shaders can be translated, not GLCompute");
+
+        let storage = SCALAR_BLOCK_FRAG.replace("OpDecorate %params Block", "OpDecorate %params BufferBlock");
+        let module = assemble(&storage).unwrap();
+        let error = to_glsl(&module, "main", &GlslOptions::default()).unwrap_err();
+        assert_eq!(error, "Storage buffers need GLSL 4.30 or GLSL ES 3.10");
+
+        // A dynamic index needs elements aligned to the uvec4 of the flattened data
+        let unaligned = SCALAR_BLOCK_FRAG.replace("ArrayStride 16", "ArrayStride 20");
+        let module = assemble(&unaligned).unwrap();
+        let error = to_glsl(&module, "main", &GlslOptions::default()).unwrap_err();
+        assert_eq!(error, "OpAccessChain %25: Dynamic index into uniform data that isn't 16-byte aligned");
+    }
+}
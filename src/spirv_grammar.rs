@@ -0,0 +1,755 @@
+use crate::spirv::*;
+
+// SPIR-V grammar:
+// Operand layout of every core instruction, shared by the disassembler, the assembler and the
+// validator. Operands are described with a compact spec string, one token per operand:
+//   T  result type id              R  result id
+//   I  id                          L  literal integer (one word)
+//   S  literal string              C  literal number whose width depends on the result type
+//   X  extended instruction        O  opcode of OpSpecConstantOp, followed by that op's operands
+//   LI pair of literal and id      II pair of ids         IL pair of id and literal
+//   <Name> an operand of the enum or bit mask `Name` (see `EnumKind`)
+// A '?' suffix marks an optional operand and a '*' suffix an operand repeated until the end.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperandKind {
+    ResultType,
+    Result,
+    Id,
+    Literal,
+    String,
+    ContextLiteral,
+    ExtInst,
+    SpecConstantOp,
+    LiteralId,
+    IdId,
+    IdLiteral,
+    Enum(EnumKind),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quantifier {
+    One,
+    Optional,
+    Variadic,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Operand {
+    pub kind: OperandKind,
+    pub quantifier: Quantifier,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnumKind {
+    SourceLanguage,
+    ExecutionModel,
+    AddressingModel,
+    MemoryModel,
+    ExecutionMode,
+    StorageClass,
+    Dim,
+    SamplerAddressingMode,
+    SamplerFilterMode,
+    ImageFormat,
+    AccessQualifier,
+    Decoration,
+    BuiltIn,
+    Capability,
+    FPRoundingMode,
+    LinkageType,
+    FunctionParameterAttribute,
+    GroupOperation,
+    // Bit masks
+    FunctionControl,
+    MemoryAccess,
+    ImageOperands,
+    SelectionControl,
+    LoopControl,
+    FPFastMathMode,
+}
+
+impl EnumKind {
+    fn from_spec(name: &str) -> Option<Self> {
+        use EnumKind::*;
+        Some(match name {
+            "SourceLanguage" => SourceLanguage,
+            "ExecutionModel" => ExecutionModel,
+            "AddressingModel" => AddressingModel,
+            "MemoryModel" => MemoryModel,
+            "ExecutionMode" => ExecutionMode,
+            "StorageClass" => StorageClass,
+            "Dim" => Dim,
+            "SamplerAddressingMode" => SamplerAddressingMode,
+            "SamplerFilterMode" => SamplerFilterMode,
+            "ImageFormat" => ImageFormat,
+            "AccessQualifier" => AccessQualifier,
+            "Decoration" => Decoration,
+            "BuiltIn" => BuiltIn,
+            "Capability" => Capability,
+            "FPRoundingMode" => FPRoundingMode,
+            "LinkageType" => LinkageType,
+            "FunctionParameterAttribute" => FunctionParameterAttribute,
+            "GroupOperation" => GroupOperation,
+            "FunctionControl" => FunctionControl,
+            "MemoryAccess" => MemoryAccess,
+            "ImageOperands" => ImageOperands,
+            "SelectionControl" => SelectionControl,
+            "LoopControl" => LoopControl,
+            "FPFastMathMode" => FPFastMathMode,
+            _ => return None,
+        })
+    }
+
+    pub fn is_mask(&self) -> bool {
+        matches!(
+            self,
+            EnumKind::FunctionControl
+                | EnumKind::MemoryAccess
+                | EnumKind::ImageOperands
+                | EnumKind::SelectionControl
+                | EnumKind::LoopControl
+                | EnumKind::FPFastMathMode
+        )
+    }
+
+    /// Name of a single value (or a single bit of a mask).
+    pub fn name(&self, value: u32) -> Option<&'static str> {
+        match self {
+            EnumKind::SourceLanguage => SourceLanguage::name(value),
+            EnumKind::ExecutionModel => ExecutionModel::name(value),
+            EnumKind::AddressingModel => AddressingModel::name(value),
+            EnumKind::MemoryModel => MemoryModel::name(value),
+            EnumKind::ExecutionMode => ExecutionMode::name(value),
+            EnumKind::StorageClass => StorageClass::name(value),
+            // The variants can't start with a digit, so they are spelled Dim1D, Dim2D, ...
+            EnumKind::Dim => Dim::name(value).map(|n| n.strip_prefix("Dim").unwrap_or(n)),
+            EnumKind::SamplerAddressingMode => SamplerAddressingMode::name(value),
+            EnumKind::SamplerFilterMode => SamplerFilterMode::name(value),
+            EnumKind::ImageFormat => ImageFormat::name(value),
+            EnumKind::AccessQualifier => AccessQualifier::name(value),
+            EnumKind::Decoration => Decoration::name(value),
+            EnumKind::BuiltIn => BuiltIn::name(value),
+            EnumKind::Capability => Capability::name(value),
+            EnumKind::FPRoundingMode => FPRoundingMode::name(value),
+            EnumKind::LinkageType => LinkageType::name(value),
+            EnumKind::FunctionParameterAttribute => FunctionParameterAttribute::name(value),
+            EnumKind::GroupOperation => GroupOperation::name(value),
+            EnumKind::FunctionControl => FunctionControl::name(value),
+            EnumKind::MemoryAccess => MemoryOperands::name(value),
+            EnumKind::ImageOperands => ImageOperands::name(value),
+            EnumKind::SelectionControl => SelectionControl::name(value),
+            EnumKind::LoopControl => LoopControl::name(value),
+            EnumKind::FPFastMathMode => FPFastMathMode::name(value),
+        }
+    }
+
+    /// Value of a single name (or a single bit of a mask).
+    pub fn value(&self, name: &str) -> Option<u32> {
+        match self {
+            EnumKind::SourceLanguage => SourceLanguage::from_name(name),
+            EnumKind::ExecutionModel => ExecutionModel::from_name(name),
+            EnumKind::AddressingModel => AddressingModel::from_name(name),
+            EnumKind::MemoryModel => MemoryModel::from_name(name),
+            EnumKind::ExecutionMode => ExecutionMode::from_name(name),
+            EnumKind::StorageClass => StorageClass::from_name(name),
+            EnumKind::Dim => Dim::from_name(&format!("Dim{}", name)).or_else(|| Dim::from_name(name)),
+            EnumKind::SamplerAddressingMode => SamplerAddressingMode::from_name(name),
+            EnumKind::SamplerFilterMode => SamplerFilterMode::from_name(name),
+            EnumKind::ImageFormat => ImageFormat::from_name(name),
+            EnumKind::AccessQualifier => AccessQualifier::from_name(name),
+            EnumKind::Decoration => Decoration::from_name(name),
+            EnumKind::BuiltIn => BuiltIn::from_name(name),
+            EnumKind::Capability => Capability::from_name(name),
+            EnumKind::FPRoundingMode => FPRoundingMode::from_name(name),
+            EnumKind::LinkageType => LinkageType::from_name(name),
+            EnumKind::FunctionParameterAttribute => FunctionParameterAttribute::from_name(name),
+            EnumKind::GroupOperation => GroupOperation::from_name(name),
+            EnumKind::FunctionControl => FunctionControl::from_name(name),
+            EnumKind::MemoryAccess => MemoryOperands::from_name(name),
+            EnumKind::ImageOperands => ImageOperands::from_name(name),
+            EnumKind::SelectionControl => SelectionControl::from_name(name),
+            EnumKind::LoopControl => LoopControl::from_name(name),
+            EnumKind::FPFastMathMode => FPFastMathMode::from_name(name),
+        }
+    }
+
+    /// Operands that follow a value (or each set bit of a mask, in increasing bit order).
+    pub fn parameters(&self, value: u32) -> &'static str {
+        match (self, value) {
+            (EnumKind::Decoration, 1 | 6 | 7 | 29..=37 | 43 | 44 | 45) => "L",
+            (EnumKind::Decoration, 11) => "BuiltIn",
+            (EnumKind::Decoration, 38) => "FunctionParameterAttribute",
+            (EnumKind::Decoration, 39) => "FPRoundingMode",
+            (EnumKind::Decoration, 40) => "FPFastMathMode",
+            (EnumKind::Decoration, 41) => "S LinkageType",
+            (EnumKind::Decoration, 27 | 46 | 47 | 5634) => "I",
+            (EnumKind::Decoration, 5635) => "S",
+            (EnumKind::ExecutionMode, 0 | 26 | 30 | 35 | 36 | 4459..=4463) => "L",
+            (EnumKind::ExecutionMode, 17 | 18) => "L L L",
+            (EnumKind::ExecutionMode, 37) => "I",
+            (EnumKind::ExecutionMode, 38 | 39) => "I I I",
+            (EnumKind::ImageOperands, 0x4) => "I I",
+            (EnumKind::ImageOperands, 0x1 | 0x2 | 0x8 | 0x10 | 0x20 | 0x40 | 0x80 | 0x100 | 0x200) => "I",
+            (EnumKind::MemoryAccess, 0x2) => "L",
+            (EnumKind::MemoryAccess, 0x8 | 0x10) => "I",
+            (EnumKind::LoopControl, 0x8 | 0x10 | 0x20 | 0x40 | 0x80 | 0x100) => "L",
+            _ => "",
+        }
+    }
+}
+
+/// Parses an operand spec string (see the top of the file).
+pub fn parse_operands(spec: &'static str) -> impl Iterator<Item = Operand> {
+    spec.split_whitespace().map(|token| {
+        let (token, quantifier) = match token.as_bytes()[token.len() - 1] {
+            b'?' => (&token[..token.len() - 1], Quantifier::Optional),
+            b'*' => (&token[..token.len() - 1], Quantifier::Variadic),
+            _ => (token, Quantifier::One),
+        };
+        let kind = match token {
+            "T" => OperandKind::ResultType,
+            "R" => OperandKind::Result,
+            "I" => OperandKind::Id,
+            "L" => OperandKind::Literal,
+            "S" => OperandKind::String,
+            "C" => OperandKind::ContextLiteral,
+            "X" => OperandKind::ExtInst,
+            "O" => OperandKind::SpecConstantOp,
+            "LI" => OperandKind::LiteralId,
+            "II" => OperandKind::IdId,
+            "IL" => OperandKind::IdLiteral,
+            name => {
+                OperandKind::Enum(EnumKind::from_spec(name).unwrap_or_else(|| panic!("Unknown operand '{}'", name)))
+            }
+        };
+        Operand {
+            kind,
+            quantifier,
+        }
+    })
+}
+
+#[derive(Debug)]
+pub struct OpInfo {
+    pub opcode: u32,
+    pub name: &'static str,
+    pub spec: &'static str,
+}
+
+impl OpInfo {
+    pub fn operands(&self) -> impl Iterator<Item = Operand> {
+        parse_operands(self.spec)
+    }
+
+    pub fn has_result(&self) -> bool {
+        self.operands().any(|o| o.kind == OperandKind::Result)
+    }
+
+    pub fn has_result_type(&self) -> bool {
+        self.operands().any(|o| o.kind == OperandKind::ResultType)
+    }
+}
+
+pub fn op_info(opcode: u32) -> Option<&'static OpInfo> {
+    OPCODES.binary_search_by_key(&opcode, |op| op.opcode).ok().map(|i| &OPCODES[i])
+}
+
+pub fn op_info_by_name(name: &str) -> Option<&'static OpInfo> {
+    OPCODES.iter().find(|op| op.name == name)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Scalar type of context dependent literals (OpConstant, OpSpecConstant and OpSwitch).
+pub enum NumberType {
+    Int {
+        width: u32,
+        signed: bool,
+    },
+    Float {
+        width: u32,
+    },
+}
+
+impl NumberType {
+    pub fn word_count(&self) -> usize {
+        match self {
+            NumberType::Int {
+                width,
+                ..
+            }
+            | NumberType::Float {
+                width,
+            } => (*width as usize).div_ceil(32).max(1),
+        }
+    }
+}
+
+macro_rules! ops {
+    ($($opcode:expr => $name:ident $spec:expr,)*) => {
+        &[$(OpInfo { opcode: $opcode, name: stringify!($name), spec: $spec },)*]
+    };
+}
+
+// Sorted by opcode
+#[rustfmt::skip]
+pub static OPCODES: &[OpInfo] = ops! {
+    0 => OpNop "",
+    1 => OpUndef "T R",
+    2 => OpSourceContinued "S",
+    3 => OpSource "SourceLanguage L I? S?",
+    4 => OpSourceExtension "S",
+    5 => OpName "I S",
+    6 => OpMemberName "I L S",
+    7 => OpString "R S",
+    8 => OpLine "I L L",
+    10 => OpExtension "S",
+    11 => OpExtInstImport "R S",
+    12 => OpExtInst "T R I X I*",
+    14 => OpMemoryModel "AddressingModel MemoryModel",
+    15 => OpEntryPoint "ExecutionModel I S I*",
+    16 => OpExecutionMode "I ExecutionMode",
+    17 => OpCapability "Capability",
+    19 => OpTypeVoid "R",
+    20 => OpTypeBool "R",
+    21 => OpTypeInt "R L L",
+    22 => OpTypeFloat "R L",
+    23 => OpTypeVector "R I L",
+    24 => OpTypeMatrix "R I L",
+    25 => OpTypeImage "R I Dim L L L L ImageFormat AccessQualifier?",
+    26 => OpTypeSampler "R",
+    27 => OpTypeSampledImage "R I",
+    28 => OpTypeArray "R I I",
+    29 => OpTypeRuntimeArray "R I",
+    30 => OpTypeStruct "R I*",
+    31 => OpTypeOpaque "R S",
+    32 => OpTypePointer "R StorageClass I",
+    33 => OpTypeFunction "R I I*",
+    34 => OpTypeEvent "R",
+    35 => OpTypeDeviceEvent "R",
+    36 => OpTypeReserveId "R",
+    37 => OpTypeQueue "R",
+    38 => OpTypePipe "R AccessQualifier",
+    39 => OpTypeForwardPointer "I StorageClass",
+    41 => OpConstantTrue "T R",
+    42 => OpConstantFalse "T R",
+    43 => OpConstant "T R C",
+    44 => OpConstantComposite "T R I*",
+    45 => OpConstantSampler "T R SamplerAddressingMode L SamplerFilterMode",
+    46 => OpConstantNull "T R",
+    48 => OpSpecConstantTrue "T R",
+    49 => OpSpecConstantFalse "T R",
+    50 => OpSpecConstant "T R C",
+    51 => OpSpecConstantComposite "T R I*",
+    52 => OpSpecConstantOp "T R O",
+    54 => OpFunction "T R FunctionControl I",
+    55 => OpFunctionParameter "T R",
+    56 => OpFunctionEnd "",
+    57 => OpFunctionCall "T R I I*",
+    59 => OpVariable "T R StorageClass I?",
+    60 => OpImageTexelPointer "T R I I I",
+    61 => OpLoad "T R I MemoryAccess?",
+    62 => OpStore "I I MemoryAccess?",
+    63 => OpCopyMemory "I I MemoryAccess? MemoryAccess?",
+    64 => OpCopyMemorySized "I I I MemoryAccess? MemoryAccess?",
+    65 => OpAccessChain "T R I I*",
+    66 => OpInBoundsAccessChain "T R I I*",
+    67 => OpPtrAccessChain "T R I I I*",
+    68 => OpArrayLength "T R I L",
+    69 => OpGenericPtrMemSemantics "T R I",
+    70 => OpInBoundsPtrAccessChain "T R I I I*",
+    71 => OpDecorate "I Decoration",
+    72 => OpMemberDecorate "I L Decoration",
+    73 => OpDecorationGroup "R",
+    74 => OpGroupDecorate "I I*",
+    75 => OpGroupMemberDecorate "I IL*",
+    77 => OpVectorExtractDynamic "T R I I",
+    78 => OpVectorInsertDynamic "T R I I I",
+    79 => OpVectorShuffle "T R I I L*",
+    80 => OpCompositeConstruct "T R I*",
+    81 => OpCompositeExtract "T R I L*",
+    82 => OpCompositeInsert "T R I I L*",
+    83 => OpCopyObject "T R I",
+    84 => OpTranspose "T R I",
+    86 => OpSampledImage "T R I I",
+    87 => OpImageSampleImplicitLod "T R I I ImageOperands?",
+    88 => OpImageSampleExplicitLod "T R I I ImageOperands",
+    89 => OpImageSampleDrefImplicitLod "T R I I I ImageOperands?",
+    90 => OpImageSampleDrefExplicitLod "T R I I I ImageOperands",
+    91 => OpImageSampleProjImplicitLod "T R I I ImageOperands?",
+    92 => OpImageSampleProjExplicitLod "T R I I ImageOperands",
+    93 => OpImageSampleProjDrefImplicitLod "T R I I I ImageOperands?",
+    94 => OpImageSampleProjDrefExplicitLod "T R I I I ImageOperands",
+    95 => OpImageFetch "T R I I ImageOperands?",
+    96 => OpImageGather "T R I I I ImageOperands?",
+    97 => OpImageDrefGather "T R I I I ImageOperands?",
+    98 => OpImageRead "T R I I ImageOperands?",
+    99 => OpImageWrite "I I I ImageOperands?",
+    100 => OpImage "T R I",
+    101 => OpImageQueryFormat "T R I",
+    102 => OpImageQueryOrder "T R I",
+    103 => OpImageQuerySizeLod "T R I I",
+    104 => OpImageQuerySize "T R I",
+    105 => OpImageQueryLod "T R I I",
+    106 => OpImageQueryLevels "T R I",
+    107 => OpImageQuerySamples "T R I",
+    109 => OpConvertFToU "T R I",
+    110 => OpConvertFToS "T R I",
+    111 => OpConvertSToF "T R I",
+    112 => OpConvertUToF "T R I",
+    113 => OpUConvert "T R I",
+    114 => OpSConvert "T R I",
+    115 => OpFConvert "T R I",
+    116 => OpQuantizeToF16 "T R I",
+    117 => OpConvertPtrToU "T R I",
+    118 => OpSatConvertSToU "T R I",
+    119 => OpSatConvertUToS "T R I",
+    120 => OpConvertUToPtr "T R I",
+    121 => OpPtrCastToGeneric "T R I",
+    122 => OpGenericCastToPtr "T R I",
+    123 => OpGenericCastToPtrExplicit "T R I StorageClass",
+    124 => OpBitcast "T R I",
+    126 => OpSNegate "T R I",
+    127 => OpFNegate "T R I",
+    128 => OpIAdd "T R I I",
+    129 => OpFAdd "T R I I",
+    130 => OpISub "T R I I",
+    131 => OpFSub "T R I I",
+    132 => OpIMul "T R I I",
+    133 => OpFMul "T R I I",
+    134 => OpUDiv "T R I I",
+    135 => OpSDiv "T R I I",
+    136 => OpFDiv "T R I I",
+    137 => OpUMod "T R I I",
+    138 => OpSRem "T R I I",
+    139 => OpSMod "T R I I",
+    140 => OpFRem "T R I I",
+    141 => OpFMod "T R I I",
+    142 => OpVectorTimesScalar "T R I I",
+    143 => OpMatrixTimesScalar "T R I I",
+    144 => OpVectorTimesMatrix "T R I I",
+    145 => OpMatrixTimesVector "T R I I",
+    146 => OpMatrixTimesMatrix "T R I I",
+    147 => OpOuterProduct "T R I I",
+    148 => OpDot "T R I I",
+    149 => OpIAddCarry "T R I I",
+    150 => OpISubBorrow "T R I I",
+    151 => OpUMulExtended "T R I I",
+    152 => OpSMulExtended "T R I I",
+    154 => OpAny "T R I",
+    155 => OpAll "T R I",
+    156 => OpIsNan "T R I",
+    157 => OpIsInf "T R I",
+    158 => OpIsFinite "T R I",
+    159 => OpIsNormal "T R I",
+    160 => OpSignBitSet "T R I",
+    161 => OpLessOrGreater "T R I I",
+    162 => OpOrdered "T R I I",
+    163 => OpUnordered "T R I I",
+    164 => OpLogicalEqual "T R I I",
+    165 => OpLogicalNotEqual "T R I I",
+    166 => OpLogicalOr "T R I I",
+    167 => OpLogicalAnd "T R I I",
+    168 => OpLogicalNot "T R I",
+    169 => OpSelect "T R I I I",
+    170 => OpIEqual "T R I I",
+    171 => OpINotEqual "T R I I",
+    172 => OpUGreaterThan "T R I I",
+    173 => OpSGreaterThan "T R I I",
+    174 => OpUGreaterThanEqual "T R I I",
+    175 => OpSGreaterThanEqual "T R I I",
+    176 => OpULessThan "T R I I",
+    177 => OpSLessThan "T R I I",
+    178 => OpULessThanEqual "T R I I",
+    179 => OpSLessThanEqual "T R I I",
+    180 => OpFOrdEqual "T R I I",
+    181 => OpFUnordEqual "T R I I",
+    182 => OpFOrdNotEqual "T R I I",
+    183 => OpFUnordNotEqual "T R I I",
+    184 => OpFOrdLessThan "T R I I",
+    185 => OpFUnordLessThan "T R I I",
+    186 => OpFOrdGreaterThan "T R I I",
+    187 => OpFUnordGreaterThan "T R I I",
+    188 => OpFOrdLessThanEqual "T R I I",
+    189 => OpFUnordLessThanEqual "T R I I",
+    190 => OpFOrdGreaterThanEqual "T R I I",
+    191 => OpFUnordGreaterThanEqual "T R I I",
+    194 => OpShiftRightLogical "T R I I",
+    195 => OpShiftRightArithmetic "T R I I",
+    196 => OpShiftLeftLogical "T R I I",
+    197 => OpBitwiseOr "T R I I",
+    198 => OpBitwiseXor "T R I I",
+    199 => OpBitwiseAnd "T R I I",
+    200 => OpNot "T R I",
+    201 => OpBitFieldInsert "T R I I I I",
+    202 => OpBitFieldSExtract "T R I I I",
+    203 => OpBitFieldUExtract "T R I I I",
+    204 => OpBitReverse "T R I",
+    205 => OpBitCount "T R I",
+    207 => OpDPdx "T R I",
+    208 => OpDPdy "T R I",
+    209 => OpFwidth "T R I",
+    210 => OpDPdxFine "T R I",
+    211 => OpDPdyFine "T R I",
+    212 => OpFwidthFine "T R I",
+    213 => OpDPdxCoarse "T R I",
+    214 => OpDPdyCoarse "T R I",
+    215 => OpFwidthCoarse "T R I",
+    218 => OpEmitVertex "",
+    219 => OpEndPrimitive "",
+    220 => OpEmitStreamVertex "I",
+    221 => OpEndStreamPrimitive "I",
+    224 => OpControlBarrier "I I I",
+    225 => OpMemoryBarrier "I I",
+    227 => OpAtomicLoad "T R I I I",
+    228 => OpAtomicStore "I I I I",
+    229 => OpAtomicExchange "T R I I I I",
+    230 => OpAtomicCompareExchange "T R I I I I I I",
+    231 => OpAtomicCompareExchangeWeak "T R I I I I I I",
+    232 => OpAtomicIIncrement "T R I I I",
+    233 => OpAtomicIDecrement "T R I I I",
+    234 => OpAtomicIAdd "T R I I I I",
+    235 => OpAtomicISub "T R I I I I",
+    236 => OpAtomicSMin "T R I I I I",
+    237 => OpAtomicUMin "T R I I I I",
+    238 => OpAtomicSMax "T R I I I I",
+    239 => OpAtomicUMax "T R I I I I",
+    240 => OpAtomicAnd "T R I I I I",
+    241 => OpAtomicOr "T R I I I I",
+    242 => OpAtomicXor "T R I I I I",
+    245 => OpPhi "T R II*",
+    246 => OpLoopMerge "I I LoopControl",
+    247 => OpSelectionMerge "I SelectionControl",
+    248 => OpLabel "R",
+    249 => OpBranch "I",
+    250 => OpBranchConditional "I I I L*",
+    251 => OpSwitch "I I LI*",
+    252 => OpKill "",
+    253 => OpReturn "",
+    254 => OpReturnValue "I",
+    255 => OpUnreachable "",
+    256 => OpLifetimeStart "I L",
+    257 => OpLifetimeStop "I L",
+    259 => OpGroupAsyncCopy "T R I I I I I I",
+    260 => OpGroupWaitEvents "I I I",
+    261 => OpGroupAll "T R I I",
+    262 => OpGroupAny "T R I I",
+    263 => OpGroupBroadcast "T R I I I",
+    264 => OpGroupIAdd "T R I GroupOperation I",
+    265 => OpGroupFAdd "T R I GroupOperation I",
+    266 => OpGroupFMin "T R I GroupOperation I",
+    267 => OpGroupUMin "T R I GroupOperation I",
+    268 => OpGroupSMin "T R I GroupOperation I",
+    269 => OpGroupFMax "T R I GroupOperation I",
+    270 => OpGroupUMax "T R I GroupOperation I",
+    271 => OpGroupSMax "T R I GroupOperation I",
+    274 => OpReadPipe "T R I I I I",
+    275 => OpWritePipe "T R I I I I",
+    276 => OpReservedReadPipe "T R I I I I I I",
+    277 => OpReservedWritePipe "T R I I I I I I",
+    278 => OpReserveReadPipePackets "T R I I I I",
+    279 => OpReserveWritePipePackets "T R I I I I",
+    280 => OpCommitReadPipe "I I I I",
+    281 => OpCommitWritePipe "I I I I",
+    282 => OpIsValidReserveId "T R I",
+    283 => OpGetNumPipePackets "T R I I I",
+    284 => OpGetMaxPipePackets "T R I I I",
+    285 => OpGroupReserveReadPipePackets "T R I I I I I",
+    286 => OpGroupReserveWritePipePackets "T R I I I I I",
+    287 => OpGroupCommitReadPipe "I I I I I",
+    288 => OpGroupCommitWritePipe "I I I I I",
+    291 => OpEnqueueMarker "T R I I I I",
+    292 => OpEnqueueKernel "T R I I I I I I I I I I I*",
+    293 => OpGetKernelNDrangeSubGroupCount "T R I I I I I",
+    294 => OpGetKernelNDrangeMaxSubGroupSize "T R I I I I I",
+    295 => OpGetKernelWorkGroupSize "T R I I I I",
+    296 => OpGetKernelPreferredWorkGroupSizeMultiple "T R I I I I",
+    297 => OpRetainEvent "I",
+    298 => OpReleaseEvent "I",
+    299 => OpCreateUserEvent "T R",
+    300 => OpIsValidEvent "T R I",
+    301 => OpSetUserEventStatus "I I",
+    302 => OpCaptureEventProfilingInfo "I I I",
+    303 => OpGetDefaultQueue "T R",
+    304 => OpBuildNDRange "T R I I I",
+    305 => OpImageSparseSampleImplicitLod "T R I I ImageOperands?",
+    306 => OpImageSparseSampleExplicitLod "T R I I ImageOperands",
+    307 => OpImageSparseSampleDrefImplicitLod "T R I I I ImageOperands?",
+    308 => OpImageSparseSampleDrefExplicitLod "T R I I I ImageOperands",
+    309 => OpImageSparseSampleProjImplicitLod "T R I I ImageOperands?",
+    310 => OpImageSparseSampleProjExplicitLod "T R I I ImageOperands",
+    311 => OpImageSparseSampleProjDrefImplicitLod "T R I I I ImageOperands?",
+    312 => OpImageSparseSampleProjDrefExplicitLod "T R I I I ImageOperands",
+    313 => OpImageSparseFetch "T R I I ImageOperands?",
+    314 => OpImageSparseGather "T R I I I ImageOperands?",
+    315 => OpImageSparseDrefGather "T R I I I ImageOperands?",
+    316 => OpImageSparseTexelsResident "T R I",
+    317 => OpNoLine "",
+    318 => OpAtomicFlagTestAndSet "T R I I I",
+    319 => OpAtomicFlagClear "I I I",
+    320 => OpImageSparseRead "T R I I ImageOperands?",
+    321 => OpSizeOf "T R I",
+    322 => OpTypePipeStorage "R",
+    323 => OpConstantPipeStorage "T R L L L",
+    324 => OpCreatePipeFromPipeStorage "T R I",
+    325 => OpGetKernelLocalSizeForSubgroupCount "T R I I I I I",
+    326 => OpGetKernelMaxNumSubgroups "T R I I I I",
+    327 => OpTypeNamedBarrier "R",
+    328 => OpNamedBarrierInitialize "T R I",
+    329 => OpMemoryNamedBarrier "I I I",
+    330 => OpModuleProcessed "S",
+    331 => OpExecutionModeId "I ExecutionMode",
+    332 => OpDecorateId "I Decoration",
+    333 => OpGroupNonUniformElect "T R I",
+    334 => OpGroupNonUniformAll "T R I I",
+    335 => OpGroupNonUniformAny "T R I I",
+    336 => OpGroupNonUniformAllEqual "T R I I",
+    337 => OpGroupNonUniformBroadcast "T R I I I",
+    338 => OpGroupNonUniformBroadcastFirst "T R I I",
+    339 => OpGroupNonUniformBallot "T R I I",
+    340 => OpGroupNonUniformInverseBallot "T R I I",
+    341 => OpGroupNonUniformBallotBitExtract "T R I I I",
+    342 => OpGroupNonUniformBallotBitCount "T R I GroupOperation I",
+    343 => OpGroupNonUniformBallotFindLSB "T R I I",
+    344 => OpGroupNonUniformBallotFindMSB "T R I I",
+    345 => OpGroupNonUniformShuffle "T R I I I",
+    346 => OpGroupNonUniformShuffleXor "T R I I I",
+    347 => OpGroupNonUniformShuffleUp "T R I I I",
+    348 => OpGroupNonUniformShuffleDown "T R I I I",
+    349 => OpGroupNonUniformIAdd "T R I GroupOperation I I?",
+    350 => OpGroupNonUniformFAdd "T R I GroupOperation I I?",
+    351 => OpGroupNonUniformIMul "T R I GroupOperation I I?",
+    352 => OpGroupNonUniformFMul "T R I GroupOperation I I?",
+    353 => OpGroupNonUniformSMin "T R I GroupOperation I I?",
+    354 => OpGroupNonUniformUMin "T R I GroupOperation I I?",
+    355 => OpGroupNonUniformFMin "T R I GroupOperation I I?",
+    356 => OpGroupNonUniformSMax "T R I GroupOperation I I?",
+    357 => OpGroupNonUniformUMax "T R I GroupOperation I I?",
+    358 => OpGroupNonUniformFMax "T R I GroupOperation I I?",
+    359 => OpGroupNonUniformBitwiseAnd "T R I GroupOperation I I?",
+    360 => OpGroupNonUniformBitwiseOr "T R I GroupOperation I I?",
+    361 => OpGroupNonUniformBitwiseXor "T R I GroupOperation I I?",
+    362 => OpGroupNonUniformLogicalAnd "T R I GroupOperation I I?",
+    363 => OpGroupNonUniformLogicalOr "T R I GroupOperation I I?",
+    364 => OpGroupNonUniformLogicalXor "T R I GroupOperation I I?",
+    365 => OpGroupNonUniformQuadBroadcast "T R I I I",
+    366 => OpGroupNonUniformQuadSwap "T R I I I",
+    400 => OpCopyLogical "T R I",
+    401 => OpPtrEqual "T R I I",
+    402 => OpPtrNotEqual "T R I I",
+    403 => OpPtrDiff "T R I I",
+    4416 => OpTerminateInvocation "",
+    5380 => OpDemoteToHelperInvocation "",
+    5381 => OpIsHelperInvocationEXT "T R",
+};
+
+/// Instruction names of the "GLSL.std.450" extended instruction set, indexed by number.
+pub const GLSL_STD_450: [&str; 82] = [
+    "Bad",
+    "Round",
+    "RoundEven",
+    "Trunc",
+    "FAbs",
+    "SAbs",
+    "FSign",
+    "SSign",
+    "Floor",
+    "Ceil",
+    "Fract",
+    "Radians",
+    "Degrees",
+    "Sin",
+    "Cos",
+    "Tan",
+    "Asin",
+    "Acos",
+    "Atan",
+    "Sinh",
+    "Cosh",
+    "Tanh",
+    "Asinh",
+    "Acosh",
+    "Atanh",
+    "Atan2",
+    "Pow",
+    "Exp",
+    "Log",
+    "Exp2",
+    "Log2",
+    "Sqrt",
+    "InverseSqrt",
+    "Determinant",
+    "MatrixInverse",
+    "Modf",
+    "ModfStruct",
+    "FMin",
+    "UMin",
+    "SMin",
+    "FMax",
+    "UMax",
+    "SMax",
+    "FClamp",
+    "UClamp",
+    "SClamp",
+    "FMix",
+    "IMix",
+    "Step",
+    "SmoothStep",
+    "Fma",
+    "Frexp",
+    "FrexpStruct",
+    "Ldexp",
+    "PackSnorm4x8",
+    "PackUnorm4x8",
+    "PackSnorm2x16",
+    "PackUnorm2x16",
+    "PackHalf2x16",
+    "PackDouble2x32",
+    "UnpackSnorm2x16",
+    "UnpackUnorm2x16",
+    "UnpackHalf2x16",
+    "UnpackSnorm4x8",
+    "UnpackUnorm4x8",
+    "UnpackDouble2x32",
+    "Length",
+    "Distance",
+    "Cross",
+    "Normalize",
+    "FaceForward",
+    "Reflect",
+    "Refract",
+    "FindILsb",
+    "FindSMsb",
+    "FindUMsb",
+    "InterpolateAtCentroid",
+    "InterpolateAtSample",
+    "InterpolateAtOffset",
+    "NMin",
+    "NMax",
+    "NClamp",
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opcode_table() {
+        assert!(OPCODES.windows(2).all(|w| w[0].opcode < w[1].opcode));
+        for op in OPCODES {
+            // Parses every spec string
+            let operands = op.operands().collect::<Vec<_>>();
+            let variadic = operands.iter().position(|o| o.quantifier == Quantifier::Variadic);
+            assert!(variadic.is_none() || variadic == Some(operands.len() - 1), "{}", op.name);
+        }
+        assert_eq!(op_info(43).unwrap().name, "OpConstant");
+        assert!(op_info(9).is_none());
+        assert!(op_info_by_name("OpTypeImage").unwrap().has_result());
+        assert!(!op_info_by_name("OpStore").unwrap().has_result_type());
+        assert_eq!(EnumKind::Dim.name(1), Some("2D"));
+        assert_eq!(EnumKind::Dim.value("2D"), Some(1));
+        assert_eq!(EnumKind::ExecutionMode.name(17), Some("LocalSize"));
+        assert_eq!(EnumKind::ExecutionMode.parameters(17), "L L L");
+        assert_eq!(EnumKind::Capability.value("StorageImageMultisample"), Some(27));
+    }
+}
@@ -0,0 +1,1929 @@
+use crate::spirv::{BuiltIn, ShaderModule};
+use crate::spirv_as::f32_to_f16;
+use crate::spirv_dis::decode_string;
+use crate::spirv_grammar::{op_info, GLSL_STD_450};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+// SPIR-V interpreter:
+// Runs fragment and compute shaders on the CPU so they can be unit tested without a GPU.
+// Invocations run one after the other: barriers are no-ops, atomics are plain read-modify-writes
+// and derivatives are always 0. Only 32-bit scalars are supported.
+// Buffers are decoded with the layout given by their Offset, ArrayStride and MatrixStride
+// decorations and written back when the shader is done. Textures are sampled from the nearest
+// texel with repeat addressing, like the sampler of `VkContext`.
+
+const STORAGE_UNIFORM_CONSTANT: u32 = 0;
+const STORAGE_INPUT: u32 = 1;
+const STORAGE_UNIFORM: u32 = 2;
+const STORAGE_OUTPUT: u32 = 3;
+const STORAGE_WORKGROUP: u32 = 4;
+const STORAGE_PRIVATE: u32 = 6;
+const STORAGE_PUSH_CONSTANT: u32 = 9;
+const STORAGE_STORAGE_BUFFER: u32 = 12;
+
+/// Scalars in a value of any one type, so that composite types of absurd sizes are rejected before they're allocated
+const MAX_TYPE_VALUES: usize = 1 << 20;
+
+const DECORATION_ROW_MAJOR: u32 = 4;
+const DECORATION_ARRAY_STRIDE: u32 = 6;
+const DECORATION_MATRIX_STRIDE: u32 = 7;
+const DECORATION_BUILT_IN: u32 = 11;
+const DECORATION_LOCATION: u32 = 30;
+const DECORATION_BINDING: u32 = 33;
+const DECORATION_DESCRIPTOR_SET: u32 = 34;
+const DECORATION_OFFSET: u32 = 35;
+
+const EXECUTION_MODE_LOCAL_SIZE: u32 = 17;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    /// Integers hold their bits, the instruction using them decides the signedness
+    Int(u32),
+    Float(f32),
+    /// Vectors, matrices (one member per column), arrays and structs
+    Composite(Vec<Value>),
+    Pointer {
+        variable: usize,
+        path: Vec<u32>,
+    },
+    /// Descriptor set and binding of a texture
+    Texture(u32, u32),
+    Sampler,
+}
+
+impl Value {
+    pub fn floats(values: &[f32]) -> Self {
+        Value::Composite(values.iter().map(|&v| Value::Float(v)).collect())
+    }
+
+    pub fn uints(values: &[u32]) -> Self {
+        Value::Composite(values.iter().map(|&v| Value::Int(v)).collect())
+    }
+
+    pub fn as_f32(&self) -> Option<f32> {
+        match self {
+            Value::Float(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_u32(&self) -> Option<u32> {
+        match self {
+            Value::Int(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_i32(&self) -> Option<i32> {
+        self.as_u32().map(|v| v as i32)
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// Components of a float scalar or vector.
+    pub fn as_floats(&self) -> Option<Vec<f32>> {
+        match self {
+            Value::Composite(members) => members.iter().map(Value::as_f32).collect(),
+            v => v.as_f32().map(|v| vec![v]),
+        }
+    }
+}
+
+/// RGBA texels, row by row.
+#[derive(Debug, Clone)]
+pub struct Texture {
+    pub width: u32,
+    pub height: u32,
+    pub texels: Vec<[f32; 4]>,
+}
+
+#[derive(Debug, Clone)]
+enum Type {
+    Void,
+    Bool,
+    Int,
+    Float,
+    Vector(u32, u32),
+    Matrix(u32, u32),
+    Array(u32, u32),
+    RuntimeArray(u32),
+    Struct(Vec<u32>),
+    Pointer(u32),
+    Image,
+    Sampler,
+    Other,
+}
+
+/// Decoration and its operands
+type Decorations = Vec<(u32, Vec<u32>)>;
+
+struct Inst {
+    opcode: u32,
+    name: &'static str,
+    result_type: u32,
+    result: u32,
+    /// Operands after the result type and result
+    args: Vec<u32>,
+}
+
+struct Global {
+    id: u32,
+    storage: u32,
+    ty: u32,
+    initializer: Option<u32>,
+}
+
+#[derive(Default)]
+struct Function {
+    params: Vec<u32>,
+    /// Index of the first OpLabel
+    start: Option<usize>,
+}
+
+struct EntryPoint {
+    name: String,
+    function: u32,
+}
+
+enum Flow {
+    Return(Option<Value>),
+    Kill,
+}
+
+#[derive(Clone, Copy)]
+struct MatrixLayout {
+    stride: usize,
+    row_major: bool,
+}
+
+impl Default for MatrixLayout {
+    fn default() -> Self {
+        Self {
+            stride: 16,
+            row_major: false,
+        }
+    }
+}
+
+pub struct Interpreter {
+    insts: Rc<Vec<Inst>>,
+    types: HashMap<u32, Type>,
+    constants: HashMap<u32, Value>,
+    decorations: HashMap<u32, Decorations>,
+    member_decorations: HashMap<(u32, u32), Decorations>,
+    globals: Vec<Global>,
+    global_vars: HashMap<u32, usize>,
+    functions: HashMap<u32, Function>,
+    labels: HashMap<u32, usize>,
+    entry_points: Vec<EntryPoint>,
+    local_sizes: HashMap<u32, [u32; 3]>,
+    glsl_std_450: Vec<u32>,
+
+    inputs: HashMap<u32, Value>,
+    builtins: HashMap<u32, Value>,
+    buffers: HashMap<(u32, u32), Vec<u8>>,
+    push_constants: Vec<u8>,
+    textures: HashMap<(u32, u32), Texture>,
+
+    /// Variables: the globals followed by the locals of the functions being run
+    memory: Vec<Value>,
+    killed: bool,
+    steps: usize,
+    /// Maximum number of instructions run per invocation, so endless loops fail instead of hanging.
+    pub max_steps: usize,
+}
+
+impl Interpreter {
+    pub fn new(module: &ShaderModule) -> Result<Self, String> {
+        let words = module.to_words()?;
+        let mut interp = Self {
+            insts: Rc::new(vec![]),
+            types: HashMap::new(),
+            constants: HashMap::new(),
+            decorations: HashMap::new(),
+            member_decorations: HashMap::new(),
+            globals: vec![],
+            global_vars: HashMap::new(),
+            functions: HashMap::new(),
+            labels: HashMap::new(),
+            entry_points: vec![],
+            local_sizes: HashMap::new(),
+            glsl_std_450: vec![],
+            inputs: HashMap::new(),
+            builtins: HashMap::new(),
+            buffers: HashMap::new(),
+            push_constants: vec![],
+            textures: HashMap::new(),
+            memory: vec![],
+            killed: false,
+            steps: 0,
+            max_steps: 10_000_000,
+        };
+
+        let mut insts = vec![];
+        let mut function = None;
+        let mut offset = 5;
+        while offset < words.len() {
+            let word_count = (words[offset] >> 16) as usize;
+            let Some(inst_words) = words.get(offset..offset + word_count).filter(|_| word_count > 0) else {
+                return Err(format!("Invalid word count at word {}", offset));
+            };
+            offset += word_count;
+
+            let opcode = inst_words[0] & 0xffff;
+            let info = op_info(opcode);
+            let mut args = &inst_words[1..];
+            let mut take = |present: bool| match args.split_first().filter(|_| present) {
+                Some((&first, rest)) => {
+                    args = rest;
+                    first
+                }
+                None => 0,
+            };
+            let result_type = take(info.is_some_and(|info| info.has_result_type()));
+            let result = take(info.is_some_and(|info| info.has_result()));
+            let inst = Inst {
+                opcode,
+                name: info.map_or("", |info| info.name),
+                result_type,
+                result,
+                args: args.to_vec(),
+            };
+            let arg = |i: usize| {
+                inst.args
+                    .get(i)
+                    .copied()
+                    .ok_or_else(|| format!("Missing operands for {} at word {}", inst.name, offset))
+            };
+
+            match inst.name {
+                "OpExtInstImport" if decode_string(&inst.args).is_some_and(|(name, _)| name == "GLSL.std.450") => {
+                    interp.glsl_std_450.push(result);
+                }
+                "OpEntryPoint" => interp.entry_points.push(EntryPoint {
+                    name: decode_string(&inst.args[2..]).map(|(name, _)| name).unwrap_or_default(),
+                    function: arg(1)?,
+                }),
+                "OpExecutionMode" if arg(1)? == EXECUTION_MODE_LOCAL_SIZE => {
+                    interp.local_sizes.insert(arg(0)?, [arg(2)?, arg(3)?, arg(4)?]);
+                }
+                "OpDecorate" => {
+                    let decorations = interp.decorations.entry(arg(0)?).or_default();
+                    decorations.push((arg(1)?, inst.args[2..].to_vec()));
+                }
+                "OpMemberDecorate" => {
+                    let decorations = interp.member_decorations.entry((arg(0)?, arg(1)?)).or_default();
+                    decorations.push((arg(2)?, inst.args[3..].to_vec()));
+                }
+                "OpFunction" => {
+                    function = Some(result);
+                    interp.functions.insert(result, Function::default());
+                }
+                "OpFunctionParameter" => {
+                    if let Some(f) = function.and_then(|f| interp.functions.get_mut(&f)) {
+                        f.params.push(result);
+                    }
+                }
+                "OpFunctionEnd" => function = None,
+                "OpLabel" => {
+                    interp.labels.insert(result, insts.len());
+                    if let Some(f) = function.and_then(|f| interp.functions.get_mut(&f)) {
+                        f.start.get_or_insert(insts.len());
+                    }
+                }
+                "OpVariable" if function.is_none() => {
+                    let Some(Type::Pointer(ty)) = interp.types.get(&result_type) else {
+                        return Err(format!("Variable %{} doesn't have a pointer type", result));
+                    };
+                    interp.global_vars.insert(result, interp.globals.len());
+                    interp.globals.push(Global {
+                        id: result,
+                        storage: arg(0)?,
+                        ty: *ty,
+                        initializer: inst.args.get(1).copied(),
+                    });
+                }
+                "OpTypeForwardPointer" => {}
+                name if function.is_none() && name.starts_with("OpType") => {
+                    let ty = interp.parse_type(&inst)?;
+                    interp.types.insert(result, ty);
+                }
+                name if function.is_none()
+                    && (name.starts_with("OpConstant") || name.starts_with("OpSpecConstant") || name == "OpUndef") =>
+                {
+                    // OpSpecConstantOp isn't folded, using its result fails as an undefined value
+                    if let Some(value) = interp.constant(&inst)? {
+                        interp.constants.insert(result, value);
+                    }
+                }
+                _ => {}
+            }
+            insts.push(inst);
+        }
+        interp.insts = Rc::new(insts);
+        Ok(interp)
+    }
+
+    fn parse_type(&self, inst: &Inst) -> Result<Type, String> {
+        let arg = |i: usize| inst.args.get(i).copied().ok_or_else(|| format!("Missing operands for {}", inst.name));
+        let ty = match inst.name {
+            "OpTypeVoid" => Type::Void,
+            "OpTypeBool" => Type::Bool,
+            "OpTypeInt" | "OpTypeFloat" if arg(0)? != 32 => {
+                return Err(format!("Only 32-bit scalars are supported, %{} has {} bits", inst.result, arg(0)?));
+            }
+            "OpTypeInt" => Type::Int,
+            "OpTypeFloat" => Type::Float,
+            "OpTypeVector" | "OpTypeMatrix" if !(2..=4).contains(&arg(1)?) => {
+                return Err(format!("{} %{} of {} components is not supported", inst.name, inst.result, arg(1)?));
+            }
+            "OpTypeVector" => Type::Vector(arg(0)?, arg(1)?),
+            "OpTypeMatrix" => Type::Matrix(arg(0)?, arg(1)?),
+            "OpTypeArray" => {
+                let Some(Value::Int(len)) = self.constants.get(&arg(1)?) else {
+                    return Err(format!("Length of array type %{} isn't an integer constant", inst.result));
+                };
+                Type::Array(arg(0)?, *len)
+            }
+            "OpTypeRuntimeArray" => Type::RuntimeArray(arg(0)?),
+            "OpTypeStruct" => Type::Struct(inst.args.clone()),
+            "OpTypePointer" => Type::Pointer(arg(1)?),
+            "OpTypeImage" | "OpTypeSampledImage" => Type::Image,
+            "OpTypeSampler" => Type::Sampler,
+            _ => Type::Other,
+        };
+        let count = self.value_count(&ty);
+        if count > MAX_TYPE_VALUES {
+            return Err(format!("Type %{} of {} values is too large", inst.result, count));
+        }
+        Ok(ty)
+    }
+
+    /// Number of scalars in a value of a type, undefined member types count as 1.
+    fn value_count(&self, ty: &Type) -> usize {
+        let count = |member: u32| self.types.get(&member).map_or(1, |ty| self.value_count(ty));
+        match *ty {
+            Type::Vector(member, n) | Type::Matrix(member, n) | Type::Array(member, n) => {
+                count(member).saturating_mul(n as usize)
+            }
+            Type::Struct(ref members) => {
+                members.iter().fold(0, |sum: usize, &member| sum.saturating_add(count(member)))
+            }
+            _ => 1,
+        }
+    }
+
+    fn constant(&self, inst: &Inst) -> Result<Option<Value>, String> {
+        Ok(Some(match inst.name {
+            "OpConstantTrue" | "OpSpecConstantTrue" => Value::Bool(true),
+            "OpConstantFalse" | "OpSpecConstantFalse" => Value::Bool(false),
+            "OpConstant" | "OpSpecConstant" => {
+                let bits = *inst.args.first().ok_or_else(|| format!("Missing value of constant %{}", inst.result))?;
+                match self.types.get(&inst.result_type) {
+                    Some(Type::Float) => Value::Float(f32::from_bits(bits)),
+                    _ => Value::Int(bits),
+                }
+            }
+            "OpConstantComposite" | "OpSpecConstantComposite" => Value::Composite(
+                inst.args
+                    .iter()
+                    .map(|id| self.constants.get(id).cloned().ok_or_else(|| format!("Undefined constant %{}", id)))
+                    .collect::<Result<_, _>>()?,
+            ),
+            "OpConstantNull" | "OpUndef" => self.zero(inst.result_type)?,
+            "OpConstantSampler" => Value::Sampler,
+            _ => return Ok(None),
+        }))
+    }
+
+    fn ty(&self, id: u32) -> Result<&Type, String> {
+        self.types.get(&id).ok_or_else(|| format!("Undefined type %{}", id))
+    }
+
+    fn zero(&self, ty: u32) -> Result<Value, String> {
+        Ok(match self.ty(ty)? {
+            Type::Bool => Value::Bool(false),
+            Type::Int => Value::Int(0),
+            Type::Float => Value::Float(0.0),
+            &Type::Vector(member, count) | &Type::Matrix(member, count) | &Type::Array(member, count) => {
+                Value::Composite(vec![self.zero(member)?; count as usize])
+            }
+            Type::Struct(members) => Value::Composite(members.iter().map(|&m| self.zero(m)).collect::<Result<_, _>>()?),
+            Type::Sampler => Value::Sampler,
+            _ => Value::Composite(vec![]),
+        })
+    }
+
+    fn decoration(&self, id: u32, decoration: u32) -> Option<&[u32]> {
+        let decorations = self.decorations.get(&id)?;
+        decorations.iter().find(|(d, _)| *d == decoration).map(|(_, args)| args.as_slice())
+    }
+
+    fn member_decoration(&self, id: u32, member: u32, decoration: u32) -> Option<&[u32]> {
+        let decorations = self.member_decorations.get(&(id, member))?;
+        decorations.iter().find(|(d, _)| *d == decoration).map(|(_, args)| args.as_slice())
+    }
+
+    fn binding(&self, id: u32) -> (u32, u32) {
+        let set = self.decoration(id, DECORATION_DESCRIPTOR_SET).and_then(|a| a.first().copied());
+        let binding = self.decoration(id, DECORATION_BINDING).and_then(|a| a.first().copied());
+        (set.unwrap_or(0), binding.unwrap_or(0))
+    }
+
+    /// Sets the value of the Input variable at a location.
+    pub fn set_input(&mut self, location: u32, value: Value) {
+        self.inputs.insert(location, value);
+    }
+
+    /// Sets the value of a built-in Input variable, like FragCoord. `dispatch` sets the compute ones.
+    pub fn set_builtin(&mut self, builtin: BuiltIn, value: Value) {
+        self.builtins.insert(builtin as u32, value);
+    }
+
+    /// Binds the contents of a uniform or storage buffer.
+    pub fn set_buffer(&mut self, set: u32, binding: u32, bytes: Vec<u8>) {
+        self.buffers.insert((set, binding), bytes);
+    }
+
+    /// Contents of a buffer, including what the shader wrote to it.
+    pub fn buffer(&self, set: u32, binding: u32) -> Option<&[u8]> {
+        self.buffers.get(&(set, binding)).map(Vec::as_slice)
+    }
+
+    pub fn set_push_constants(&mut self, bytes: Vec<u8>) {
+        self.push_constants = bytes;
+    }
+
+    pub fn set_texture(&mut self, set: u32, binding: u32, texture: Texture) {
+        self.textures.insert((set, binding), texture);
+    }
+
+    /// Value of the Output variable at a location after a run.
+    pub fn output(&self, location: u32) -> Option<&Value> {
+        self.globals
+            .iter()
+            .position(|g| {
+                g.storage == STORAGE_OUTPUT
+                    && self.decoration(g.id, DECORATION_LOCATION).and_then(|a| a.first()) == Some(&location)
+            })
+            .and_then(|i| self.memory.get(i))
+    }
+
+    /// Value of a built-in Output variable after a run, like FragDepth.
+    pub fn builtin_output(&self, builtin: BuiltIn) -> Option<&Value> {
+        self.globals
+            .iter()
+            .position(|g| {
+                g.storage == STORAGE_OUTPUT
+                    && self.decoration(g.id, DECORATION_BUILT_IN).and_then(|a| a.first()) == Some(&(builtin as u32))
+            })
+            .and_then(|i| self.memory.get(i))
+    }
+
+    /// Whether the last invocation was discarded (OpKill).
+    pub fn killed(&self) -> bool {
+        self.killed
+    }
+
+    fn entry_point(&self, name: &str) -> Result<u32, String> {
+        let entry_point = self.entry_points.iter().find(|e| e.name == name);
+        entry_point.map(|e| e.function).ok_or_else(|| format!("No entry point named '{}'", name))
+    }
+
+    /// Runs a single invocation of an entry point, a fragment for fragment shaders.
+    pub fn run(&mut self, entry_point: &str) -> Result<(), String> {
+        let function = self.entry_point(entry_point)?;
+        self.begin()?;
+        self.invoke(function)?;
+        self.finish()
+    }
+
+    /// Runs every invocation of a compute shader for a number of workgroups.
+    pub fn dispatch(&mut self, entry_point: &str, workgroups: [u32; 3]) -> Result<(), String> {
+        let function = self.entry_point(entry_point)?;
+        let [sx, sy, sz] = self.local_sizes.get(&function).copied().unwrap_or([1, 1, 1]);
+        self.begin()?;
+        self.set_builtin(BuiltIn::NumWorkgroups, Value::uints(&workgroups));
+        for gz in 0..workgroups[2] {
+            for gy in 0..workgroups[1] {
+                for gx in 0..workgroups[0] {
+                    self.reset(&[STORAGE_WORKGROUP])?;
+                    self.set_builtin(BuiltIn::WorkgroupId, Value::uints(&[gx, gy, gz]));
+                    for lz in 0..sz {
+                        for ly in 0..sy {
+                            for lx in 0..sx {
+                                let global = [gx * sx + lx, gy * sy + ly, gz * sz + lz];
+                                self.set_builtin(BuiltIn::LocalInvocationId, Value::uints(&[lx, ly, lz]));
+                                self.set_builtin(BuiltIn::GlobalInvocationId, Value::uints(&global));
+                                let index = (lz * sy + ly) * sx + lx;
+                                self.set_builtin(BuiltIn::LocalInvocationIndex, Value::Int(index));
+                                self.reset(&[STORAGE_INPUT, STORAGE_OUTPUT, STORAGE_PRIVATE])?;
+                                self.invoke(function)?;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        self.finish()
+    }
+
+    fn invoke(&mut self, function: u32) -> Result<(), String> {
+        self.killed = false;
+        self.steps = 0;
+        self.call(function, vec![])?;
+        Ok(())
+    }
+
+    /// Initializes every global variable.
+    fn begin(&mut self) -> Result<(), String> {
+        self.memory.clear();
+        for i in 0..self.globals.len() {
+            let value = self.initial_value(&self.globals[i])?;
+            self.memory.push(value);
+        }
+        Ok(())
+    }
+
+    fn reset(&mut self, storages: &[u32]) -> Result<(), String> {
+        for i in 0..self.globals.len() {
+            if storages.contains(&self.globals[i].storage) {
+                self.memory[i] = self.initial_value(&self.globals[i])?;
+            }
+        }
+        Ok(())
+    }
+
+    fn initial_value(&self, global: &Global) -> Result<Value, String> {
+        match global.storage {
+            STORAGE_INPUT => {
+                let builtin = self.decoration(global.id, DECORATION_BUILT_IN).and_then(|a| a.first());
+                let location = self.decoration(global.id, DECORATION_LOCATION).and_then(|a| a.first());
+                let value = match (builtin, location) {
+                    (Some(builtin), _) => self.builtins.get(builtin),
+                    (None, Some(location)) => self.inputs.get(location),
+                    _ => None,
+                };
+                value.cloned().map_or_else(|| self.zero(global.ty), Ok)
+            }
+            STORAGE_PUSH_CONSTANT => self.read(&self.push_constants, 0, global.ty, MatrixLayout::default()),
+            STORAGE_UNIFORM | STORAGE_STORAGE_BUFFER => {
+                let (set, binding) = self.binding(global.id);
+                let bytes = self
+                    .buffers
+                    .get(&(set, binding))
+                    .ok_or_else(|| format!("No buffer bound to set {} binding {}", set, binding))?;
+                self.read(bytes, 0, global.ty, MatrixLayout::default())
+            }
+            STORAGE_UNIFORM_CONSTANT => match self.ty(global.ty)? {
+                Type::Image => {
+                    let (set, binding) = self.binding(global.id);
+                    Ok(Value::Texture(set, binding))
+                }
+                _ => self.zero(global.ty),
+            },
+            _ => match global.initializer {
+                Some(id) => self.constants.get(&id).cloned().ok_or_else(|| format!("Undefined constant %{}", id)),
+                None => self.zero(global.ty),
+            },
+        }
+    }
+
+    /// Writes the buffers back.
+    fn finish(&mut self) -> Result<(), String> {
+        for (i, global) in self.globals.iter().enumerate() {
+            if matches!(global.storage, STORAGE_UNIFORM | STORAGE_STORAGE_BUFFER) {
+                let key = self.binding(global.id);
+                let mut bytes = self.buffers.get(&key).cloned().unwrap_or_default();
+                self.write(&mut bytes, 0, global.ty, MatrixLayout::default(), &self.memory[i])?;
+                self.buffers.insert(key, bytes);
+            }
+        }
+        Ok(())
+    }
+
+    fn array_stride(&self, ty: u32) -> Result<usize, String> {
+        let stride = self.decoration(ty, DECORATION_ARRAY_STRIDE).and_then(|a| a.first());
+        stride.map(|&s| s as usize).ok_or_else(|| format!("Array type %{} has no ArrayStride decoration", ty))
+    }
+
+    fn member_layout(&self, ty: u32, member: u32) -> Result<(usize, MatrixLayout), String> {
+        let offset = self.member_decoration(ty, member, DECORATION_OFFSET).and_then(|a| a.first());
+        let offset = offset.ok_or_else(|| format!("Member {} of struct %{} has no Offset decoration", member, ty))?;
+        let stride = self.member_decoration(ty, member, DECORATION_MATRIX_STRIDE).and_then(|a| a.first());
+        let layout = MatrixLayout {
+            stride: stride.map_or(16, |&s| s as usize),
+            row_major: self.member_decoration(ty, member, DECORATION_ROW_MAJOR).is_some(),
+        };
+        Ok((*offset as usize, layout))
+    }
+
+    /// Offsets of the members of a buffer value of the given type.
+    fn layout(
+        &self,
+        bytes: &[u8],
+        offset: usize,
+        ty: u32,
+        layout: MatrixLayout,
+    ) -> Result<Vec<(usize, u32, MatrixLayout)>, String> {
+        Ok(match self.ty(ty)? {
+            &Type::Vector(member, count) => (0..count as usize).map(|i| (offset + 4 * i, member, layout)).collect(),
+            &Type::Matrix(column, count) => {
+                // The components of a column are `stride` apart when row major, see `component_step`
+                (0..count as usize)
+                    .map(|c| {
+                        let offset = if layout.row_major {
+                            offset + 4 * c
+                        } else {
+                            offset + layout.stride * c
+                        };
+                        (offset, column, layout)
+                    })
+                    .collect()
+            }
+            &Type::Array(element, count) => {
+                let stride = self.array_stride(ty)?;
+                (0..count as usize).map(|i| (offset + stride * i, element, layout)).collect()
+            }
+            &Type::RuntimeArray(element) => {
+                let stride = self.array_stride(ty)?;
+                let count = bytes.len().saturating_sub(offset) / stride.max(1);
+                (0..count).map(|i| (offset + stride * i, element, layout)).collect()
+            }
+            Type::Struct(members) => members
+                .iter()
+                .enumerate()
+                .map(|(i, &member)| {
+                    let (member_offset, layout) = self.member_layout(ty, i as u32)?;
+                    Ok((offset + member_offset, member, layout))
+                })
+                .collect::<Result<_, String>>()?,
+            _ => return Err(format!("Type %{} can't be stored in a buffer", ty)),
+        })
+    }
+
+    /// Step between the components of a vector, which is the matrix stride for the columns of row major matrices.
+    fn component_step(layout: MatrixLayout, in_matrix: bool) -> usize {
+        if in_matrix && layout.row_major {
+            layout.stride
+        } else {
+            4
+        }
+    }
+
+    fn read(&self, bytes: &[u8], offset: usize, ty: u32, layout: MatrixLayout) -> Result<Value, String> {
+        self.read_value(bytes, offset, ty, layout, false)
+    }
+
+    fn read_value(
+        &self,
+        bytes: &[u8],
+        offset: usize,
+        ty: u32,
+        layout: MatrixLayout,
+        in_matrix: bool,
+    ) -> Result<Value, String> {
+        let word = || {
+            let word =
+                bytes.get(offset..offset + 4).ok_or_else(|| format!("Buffer of {} bytes is too small", bytes.len()))?;
+            Ok::<_, String>(u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+        };
+        Ok(match self.ty(ty)? {
+            Type::Bool => Value::Bool(word()? != 0),
+            Type::Int => Value::Int(word()?),
+            Type::Float => Value::Float(f32::from_bits(word()?)),
+            &Type::Vector(scalar, count) => {
+                let step = Self::component_step(layout, in_matrix);
+                let components =
+                    (0..count as usize).map(|i| self.read_value(bytes, offset + step * i, scalar, layout, false));
+                Value::Composite(components.collect::<Result<_, _>>()?)
+            }
+            Type::Matrix(..) => {
+                let columns = self.layout(bytes, offset, ty, layout)?;
+                let columns = columns.into_iter().map(|(o, column, l)| self.read_value(bytes, o, column, l, true));
+                Value::Composite(columns.collect::<Result<_, _>>()?)
+            }
+            _ => {
+                let members = self.layout(bytes, offset, ty, layout)?;
+                let members = members.into_iter().map(|(o, member, l)| self.read_value(bytes, o, member, l, false));
+                Value::Composite(members.collect::<Result<_, _>>()?)
+            }
+        })
+    }
+
+    fn write(
+        &self,
+        bytes: &mut Vec<u8>,
+        offset: usize,
+        ty: u32,
+        layout: MatrixLayout,
+        value: &Value,
+    ) -> Result<(), String> {
+        self.write_value(bytes, offset, ty, layout, value, false)
+    }
+
+    fn write_value(
+        &self,
+        bytes: &mut Vec<u8>,
+        offset: usize,
+        ty: u32,
+        layout: MatrixLayout,
+        value: &Value,
+        in_matrix: bool,
+    ) -> Result<(), String> {
+        let word = match (self.ty(ty)?, value) {
+            (Type::Bool, Value::Bool(v)) => Some(*v as u32),
+            (Type::Int, Value::Int(v)) => Some(*v),
+            (Type::Float, Value::Float(v)) => Some(v.to_bits()),
+            (_, Value::Composite(_)) => None,
+            _ => return Err(format!("Value {:?} doesn't match type %{}", value, ty)),
+        };
+        if let Some(word) = word {
+            if bytes.len() < offset + 4 {
+                bytes.resize(offset + 4, 0);
+            }
+            bytes[offset..offset + 4].copy_from_slice(&word.to_le_bytes());
+            return Ok(());
+        }
+
+        let Value::Composite(members) = value else {
+            unreachable!()
+        };
+        let offsets = match *self.ty(ty)? {
+            Type::Vector(scalar, count) => {
+                let step = Self::component_step(layout, in_matrix);
+                (0..count as usize).map(|i| (offset + step * i, scalar, layout)).collect()
+            }
+            Type::RuntimeArray(element) => {
+                let stride = self.array_stride(ty)?;
+                (0..members.len()).map(|i| (offset + stride * i, element, layout)).collect()
+            }
+            _ => self.layout(bytes, offset, ty, layout)?,
+        };
+        if offsets.len() != members.len() {
+            return Err(format!("Value {:?} doesn't match type %{}", value, ty));
+        }
+        let is_matrix = matches!(self.ty(ty)?, Type::Matrix(..));
+        for ((offset, member_ty, layout), member) in offsets.into_iter().zip(members) {
+            self.write_value(bytes, offset, member_ty, layout, member, is_matrix)?;
+        }
+        Ok(())
+    }
+
+    fn value(&self, values: &HashMap<u32, Value>, id: u32) -> Result<Value, String> {
+        if let Some(value) = values.get(&id).or_else(|| self.constants.get(&id)) {
+            return Ok(value.clone());
+        }
+        match self.global_vars.get(&id) {
+            Some(&variable) => Ok(Value::Pointer {
+                variable,
+                path: vec![],
+            }),
+            None => Err(format!("Undefined value %{}", id)),
+        }
+    }
+
+    fn label(&self, id: u32) -> Result<usize, String> {
+        self.labels.get(&id).copied().ok_or_else(|| format!("Undefined label %{}", id))
+    }
+
+    fn call(&mut self, function: u32, args: Vec<Value>) -> Result<Flow, String> {
+        let f = self.functions.get(&function).ok_or_else(|| format!("Undefined function %{}", function))?;
+        let start = f.start.ok_or_else(|| format!("Function %{} has no body", function))?;
+        if f.params.len() != args.len() {
+            return Err(format!("Function %{} takes {} arguments, not {}", function, f.params.len(), args.len()));
+        }
+        let mut values: HashMap<u32, Value> = f.params.iter().copied().zip(args).collect();
+        let insts = Rc::clone(&self.insts);
+        let stack = self.memory.len();
+        let (mut pc, mut block, mut previous) = (start, 0, 0);
+        let flow = loop {
+            self.steps += 1;
+            if self.steps > self.max_steps {
+                return Err(format!("Exceeded the limit of {} steps", self.max_steps));
+            }
+            let inst = &insts[pc];
+            pc += 1;
+            match inst.name {
+                "OpLabel" => {
+                    previous = block;
+                    block = inst.result;
+                }
+                "OpPhi" => {
+                    // The phis of a block all read the values from before any of them is assigned
+                    let mut phis = vec![];
+                    let mut i = pc - 1;
+                    while insts[i].name == "OpPhi" {
+                        let phi = &insts[i];
+                        let pair = phi.args.chunks(2).find(|pair| pair.get(1) == Some(&previous));
+                        let pair =
+                            pair.ok_or_else(|| format!("OpPhi %{} has no value for block %{}", phi.result, previous))?;
+                        phis.push((phi.result, self.value(&values, pair[0])?));
+                        i += 1;
+                    }
+                    values.extend(phis);
+                    pc = i;
+                }
+                "OpBranch" => pc = self.label(inst.args[0])?,
+                "OpBranchConditional" => {
+                    let condition = self.value(&values, inst.args[0])?;
+                    let condition = condition.as_bool().ok_or("Branch condition isn't a boolean")?;
+                    pc = self.label(
+                        inst.args[if condition {
+                            1
+                        } else {
+                            2
+                        }],
+                    )?;
+                }
+                "OpSwitch" => {
+                    let selector = self.value(&values, inst.args[0])?;
+                    let selector = selector.as_u32().ok_or("Switch selector isn't an integer")?;
+                    let case = inst.args[2..].chunks(2).find(|pair| pair[0] == selector);
+                    pc = self.label(case.map_or(inst.args[1], |pair| pair[1]))?;
+                }
+                "OpReturn" => break Flow::Return(None),
+                "OpReturnValue" => break Flow::Return(Some(self.value(&values, inst.args[0])?)),
+                "OpKill" | "OpTerminateInvocation" => {
+                    self.killed = true;
+                    break Flow::Kill;
+                }
+                "OpUnreachable" => return Err(format!("Reached OpUnreachable in block %{}", block)),
+                "OpFunctionEnd" => return Err(format!("Function %{} ends without returning", function)),
+                "OpFunctionCall" => {
+                    let args = inst.args[1..].iter().map(|&id| self.value(&values, id)).collect::<Result<_, _>>()?;
+                    match self.call(inst.args[0], args)? {
+                        Flow::Return(Some(value)) => {
+                            values.insert(inst.result, value);
+                        }
+                        Flow::Return(None) => {}
+                        Flow::Kill => break Flow::Kill,
+                    }
+                }
+                _ => {
+                    let value = self.execute(inst, &values).map_err(|e| match inst.result {
+                        0 => format!("{}: {}", inst.name, e),
+                        result => format!("{} %{}: {}", inst.name, result, e),
+                    })?;
+                    if let Some(value) = value {
+                        values.insert(inst.result, value);
+                    }
+                }
+            }
+        };
+        self.memory.truncate(stack);
+        Ok(flow)
+    }
+
+    fn load(&self, pointer: &Value) -> Result<Value, String> {
+        let Value::Pointer {
+            variable,
+            path,
+        } = pointer
+        else {
+            return Err("Expected a pointer".to_string());
+        };
+        let mut value = self.memory.get(*variable).ok_or("Dangling pointer")?;
+        for &i in path {
+            let Value::Composite(members) = value else {
+                return Err("Indexing into a scalar".to_string());
+            };
+            value = members.get(i as usize).ok_or_else(|| format!("Index {} is out of bounds", i))?;
+        }
+        Ok(value.clone())
+    }
+
+    fn store(&mut self, pointer: &Value, new: Value) -> Result<(), String> {
+        let Value::Pointer {
+            variable,
+            path,
+        } = pointer
+        else {
+            return Err("Expected a pointer".to_string());
+        };
+        let mut value = self.memory.get_mut(*variable).ok_or("Dangling pointer")?;
+        for &i in path {
+            let Value::Composite(members) = value else {
+                return Err("Indexing into a scalar".to_string());
+            };
+            value = members.get_mut(i as usize).ok_or_else(|| format!("Index {} is out of bounds", i))?;
+        }
+        *value = new;
+        Ok(())
+    }
+
+    fn execute(&mut self, inst: &Inst, values: &HashMap<u32, Value>) -> Result<Option<Value>, String> {
+        let arg = |i: usize| -> Result<Value, String> {
+            let id = *inst.args.get(i).ok_or("Missing operands")?;
+            self.value(values, id)
+        };
+        let value = match inst.name {
+            "OpNop" | "OpLine" | "OpNoLine" | "OpSelectionMerge" | "OpLoopMerge" | "OpControlBarrier"
+            | "OpMemoryBarrier" => return Ok(None),
+            "OpUndef" => self.zero(inst.result_type)?,
+            "OpVariable" => {
+                let Some(&Type::Pointer(ty)) = self.types.get(&inst.result_type) else {
+                    return Err("Variable doesn't have a pointer type".to_string());
+                };
+                let initial = match inst.args.get(1) {
+                    Some(_) => arg(1)?,
+                    None => self.zero(ty)?,
+                };
+                self.memory.push(initial);
+                Value::Pointer {
+                    variable: self.memory.len() - 1,
+                    path: vec![],
+                }
+            }
+            "OpLoad" | "OpAtomicLoad" => self.load(&arg(0)?)?,
+            "OpStore" => {
+                self.store(&arg(0)?, arg(1)?)?;
+                return Ok(None);
+            }
+            "OpAtomicStore" => {
+                self.store(&arg(0)?, arg(3)?)?;
+                return Ok(None);
+            }
+            "OpCopyMemory" => {
+                let value = self.load(&arg(1)?)?;
+                self.store(&arg(0)?, value)?;
+                return Ok(None);
+            }
+            "OpAccessChain" | "OpInBoundsAccessChain" => {
+                let Value::Pointer {
+                    variable,
+                    mut path,
+                } = arg(0)?
+                else {
+                    return Err("Base isn't a pointer".to_string());
+                };
+                for i in 1..inst.args.len() {
+                    path.push(arg(i)?.as_u32().ok_or("Index isn't an integer")?);
+                }
+                Value::Pointer {
+                    variable,
+                    path,
+                }
+            }
+            "OpArrayLength" => {
+                let Value::Composite(members) = self.load(&arg(0)?)? else {
+                    return Err("Expected a pointer to a struct".to_string());
+                };
+                match members.get(inst.args[1] as usize) {
+                    Some(Value::Composite(elements)) => Value::Int(elements.len() as u32),
+                    _ => return Err("Member isn't a runtime array".to_string()),
+                }
+            }
+            "OpCompositeConstruct" => {
+                let constituents = (0..inst.args.len()).map(arg).collect::<Result<Vec<_>, _>>()?;
+                match self.ty(inst.result_type)? {
+                    // Vectors can be built from smaller vectors
+                    Type::Vector(..) => Value::Composite(
+                        constituents
+                            .into_iter()
+                            .flat_map(|c| match c {
+                                Value::Composite(members) => members,
+                                scalar => vec![scalar],
+                            })
+                            .collect(),
+                    ),
+                    _ => Value::Composite(constituents),
+                }
+            }
+            "OpCompositeExtract" => {
+                let mut value = arg(0)?;
+                for &i in &inst.args[1..] {
+                    value = member(&value, i)?.clone();
+                }
+                value
+            }
+            "OpCompositeInsert" => {
+                let mut composite = arg(1)?;
+                let mut target = &mut composite;
+                for &i in &inst.args[2..] {
+                    let Value::Composite(members) = target else {
+                        return Err("Indexing into a scalar".to_string());
+                    };
+                    target = members.get_mut(i as usize).ok_or_else(|| format!("Index {} is out of bounds", i))?;
+                }
+                *target = arg(0)?;
+                composite
+            }
+            "OpVectorExtractDynamic" => member(&arg(0)?, arg(1)?.as_u32().ok_or("Index isn't an integer")?)?.clone(),
+            "OpVectorInsertDynamic" => {
+                let mut vector = arg(0)?;
+                let i = arg(2)?.as_u32().ok_or("Index isn't an integer")?;
+                if let Value::Composite(members) = &mut vector {
+                    *members.get_mut(i as usize).ok_or_else(|| format!("Index {} is out of bounds", i))? = arg(1)?;
+                }
+                vector
+            }
+            "OpVectorShuffle" => {
+                let (Value::Composite(a), Value::Composite(b)) = (arg(0)?, arg(1)?) else {
+                    return Err("Operands aren't vectors".to_string());
+                };
+                let components = [a, b].concat();
+                let undefined = self.zero(match self.ty(inst.result_type)? {
+                    &Type::Vector(scalar, _) => scalar,
+                    _ => return Err("Result isn't a vector".to_string()),
+                })?;
+                Value::Composite(
+                    inst.args[2..]
+                        .iter()
+                        .map(|&i| match i {
+                            0xffffffff => Ok(undefined.clone()),
+                            i => components
+                                .get(i as usize)
+                                .cloned()
+                                .ok_or_else(|| format!("Index {} is out of bounds", i)),
+                        })
+                        .collect::<Result<_, String>>()?,
+                )
+            }
+            "OpCopyObject" | "OpSampledImage" | "OpImage" | "OpUConvert" | "OpSConvert" | "OpFConvert" => arg(0)?,
+            "OpTranspose" => from_matrix(transpose(&matrix(&arg(0)?)?)),
+            "OpSelect" => {
+                let condition = arg(0)?;
+                let (a, b) = (arg(1)?, arg(2)?);
+                match condition {
+                    Value::Bool(c) => {
+                        if c {
+                            a
+                        } else {
+                            b
+                        }
+                    }
+                    condition => zip3(&condition, &a, &b, &|c, a, b| {
+                        Ok(if boolean(c)? {
+                            a.clone()
+                        } else {
+                            b.clone()
+                        })
+                    })?,
+                }
+            }
+
+            // Conversions
+            "OpConvertFToU" => map(&arg(0)?, &|v| Ok(Value::Int(float(v)? as u32)))?,
+            "OpConvertFToS" => map(&arg(0)?, &|v| Ok(Value::Int(float(v)? as i32 as u32)))?,
+            "OpConvertSToF" => map(&arg(0)?, &|v| Ok(Value::Float(int(v)? as i32 as f32)))?,
+            "OpConvertUToF" => map(&arg(0)?, &|v| Ok(Value::Float(int(v)? as f32)))?,
+            "OpQuantizeToF16" => float1(&arg(0)?, |x| f16_to_f32(f32_to_f16(x)))?,
+            "OpBitcast" => {
+                let float_result = match self.ty(inst.result_type)? {
+                    &Type::Vector(scalar, _) => matches!(self.ty(scalar)?, Type::Float),
+                    ty => matches!(ty, Type::Float),
+                };
+                map(&arg(0)?, &|v| {
+                    let bits = match v {
+                        Value::Float(f) => f.to_bits(),
+                        v => int(v)?,
+                    };
+                    Ok(if float_result {
+                        Value::Float(f32::from_bits(bits))
+                    } else {
+                        Value::Int(bits)
+                    })
+                })?
+            }
+
+            // Arithmetic
+            "OpSNegate" => int1(&arg(0)?, |x| (x as i32).wrapping_neg() as u32)?,
+            "OpFNegate" => float1(&arg(0)?, |x| -x)?,
+            "OpIAdd" => int2(&arg(0)?, &arg(1)?, u32::wrapping_add)?,
+            "OpFAdd" => float2(&arg(0)?, &arg(1)?, |x, y| x + y)?,
+            "OpISub" => int2(&arg(0)?, &arg(1)?, u32::wrapping_sub)?,
+            "OpFSub" => float2(&arg(0)?, &arg(1)?, |x, y| x - y)?,
+            "OpIMul" => int2(&arg(0)?, &arg(1)?, u32::wrapping_mul)?,
+            "OpFMul" => float2(&arg(0)?, &arg(1)?, |x, y| x * y)?,
+            // Division by zero is undefined, it gives 0 here
+            "OpUDiv" => int2(&arg(0)?, &arg(1)?, |x, y| x.checked_div(y).unwrap_or(0))?,
+            "OpSDiv" => int2(&arg(0)?, &arg(1)?, |x, y| (x as i32).checked_div(y as i32).unwrap_or(0) as u32)?,
+            "OpFDiv" => float2(&arg(0)?, &arg(1)?, |x, y| x / y)?,
+            "OpUMod" => int2(&arg(0)?, &arg(1)?, |x, y| x.checked_rem(y).unwrap_or(0))?,
+            "OpSRem" => int2(&arg(0)?, &arg(1)?, |x, y| (x as i32).checked_rem(y as i32).unwrap_or(0) as u32)?,
+            "OpSMod" => int2(&arg(0)?, &arg(1)?, |x, y| {
+                let (x, y) = (x as i32, y as i32);
+                let r = x.checked_rem(y).unwrap_or(0);
+                // The result takes the sign of the divisor
+                (if r != 0 && (r < 0) != (y < 0) {
+                    r + y
+                } else {
+                    r
+                }) as u32
+            })?,
+            "OpFRem" => float2(&arg(0)?, &arg(1)?, |x, y| x % y)?,
+            "OpFMod" => float2(&arg(0)?, &arg(1)?, |x, y| x - y * (x / y).floor())?,
+            "OpVectorTimesScalar" | "OpMatrixTimesScalar" => {
+                let scalar = float(&arg(1)?)?;
+                float1(&arg(0)?, |x| x * scalar)?
+            }
+            "OpDot" => Value::Float(dot(&floats(&arg(0)?)?, &floats(&arg(1)?)?)),
+            "OpMatrixTimesVector" => Value::floats(&matrix_times_vector(&matrix(&arg(0)?)?, &floats(&arg(1)?)?)),
+            "OpVectorTimesMatrix" => {
+                let v = floats(&arg(0)?)?;
+                Value::floats(&matrix(&arg(1)?)?.iter().map(|column| dot(&v, column)).collect::<Vec<_>>())
+            }
+            "OpMatrixTimesMatrix" => {
+                let a = matrix(&arg(0)?)?;
+                from_matrix(matrix(&arg(1)?)?.iter().map(|column| matrix_times_vector(&a, column)).collect())
+            }
+            "OpOuterProduct" => {
+                let (c, r) = (floats(&arg(0)?)?, floats(&arg(1)?)?);
+                from_matrix(r.iter().map(|&s| c.iter().map(|&x| x * s).collect()).collect())
+            }
+
+            // Bits
+            "OpShiftRightLogical" => int2(&arg(0)?, &arg(1)?, u32::wrapping_shr)?,
+            "OpShiftRightArithmetic" => int2(&arg(0)?, &arg(1)?, |x, y| (x as i32).wrapping_shr(y) as u32)?,
+            "OpShiftLeftLogical" => int2(&arg(0)?, &arg(1)?, u32::wrapping_shl)?,
+            "OpBitwiseOr" => int2(&arg(0)?, &arg(1)?, |x, y| x | y)?,
+            "OpBitwiseXor" => int2(&arg(0)?, &arg(1)?, |x, y| x ^ y)?,
+            "OpBitwiseAnd" => int2(&arg(0)?, &arg(1)?, |x, y| x & y)?,
+            "OpNot" => int1(&arg(0)?, |x| !x)?,
+            "OpBitReverse" => int1(&arg(0)?, u32::reverse_bits)?,
+            "OpBitCount" => int1(&arg(0)?, u32::count_ones)?,
+            "OpBitFieldInsert" | "OpBitFieldSExtract" | "OpBitFieldUExtract" => {
+                let (offset, count) = match inst.name {
+                    "OpBitFieldInsert" => (int(&arg(2)?)?, int(&arg(3)?)?),
+                    _ => (int(&arg(1)?)?, int(&arg(2)?)?),
+                };
+                let mask = if count >= 32 {
+                    !0
+                } else {
+                    (1u32 << count) - 1
+                };
+                match inst.name {
+                    "OpBitFieldInsert" => int2(&arg(0)?, &arg(1)?, |base, insert| {
+                        base & !mask.wrapping_shl(offset) | (insert & mask).wrapping_shl(offset)
+                    })?,
+                    "OpBitFieldUExtract" => int1(&arg(0)?, |x| x.wrapping_shr(offset) & mask)?,
+                    _ => int1(&arg(0)?, |x| {
+                        let shift = 32 - count.clamp(1, 32);
+                        ((((x.wrapping_shr(offset) & mask) << shift) as i32) >> shift) as u32
+                    })?,
+                }
+            }
+
+            // Relational and logical
+            "OpAny" => Value::Bool(bools(&arg(0)?)?.into_iter().any(|b| b)),
+            "OpAll" => Value::Bool(bools(&arg(0)?)?.into_iter().all(|b| b)),
+            "OpIsNan" => map(&arg(0)?, &|v| Ok(Value::Bool(float(v)?.is_nan())))?,
+            "OpIsInf" => map(&arg(0)?, &|v| Ok(Value::Bool(float(v)?.is_infinite())))?,
+            "OpLogicalEqual" => bool2(&arg(0)?, &arg(1)?, |x, y| x == y)?,
+            "OpLogicalNotEqual" => bool2(&arg(0)?, &arg(1)?, |x, y| x != y)?,
+            "OpLogicalOr" => bool2(&arg(0)?, &arg(1)?, |x, y| x || y)?,
+            "OpLogicalAnd" => bool2(&arg(0)?, &arg(1)?, |x, y| x && y)?,
+            "OpLogicalNot" => map(&arg(0)?, &|v| Ok(Value::Bool(!boolean(v)?)))?,
+            "OpIEqual" => compare_int(&arg(0)?, &arg(1)?, |x, y| x == y)?,
+            "OpINotEqual" => compare_int(&arg(0)?, &arg(1)?, |x, y| x != y)?,
+            "OpUGreaterThan" => compare_int(&arg(0)?, &arg(1)?, |x, y| x > y)?,
+            "OpSGreaterThan" => compare_int(&arg(0)?, &arg(1)?, |x, y| x as i32 > y as i32)?,
+            "OpUGreaterThanEqual" => compare_int(&arg(0)?, &arg(1)?, |x, y| x >= y)?,
+            "OpSGreaterThanEqual" => compare_int(&arg(0)?, &arg(1)?, |x, y| x as i32 >= y as i32)?,
+            "OpULessThan" => compare_int(&arg(0)?, &arg(1)?, |x, y| x < y)?,
+            "OpSLessThan" => compare_int(&arg(0)?, &arg(1)?, |x, y| (x as i32) < y as i32)?,
+            "OpULessThanEqual" => compare_int(&arg(0)?, &arg(1)?, |x, y| x <= y)?,
+            "OpSLessThanEqual" => compare_int(&arg(0)?, &arg(1)?, |x, y| x as i32 <= y as i32)?,
+            // Ordered comparisons are false when an operand is NaN, unordered ones are true
+            "OpFOrdEqual" => compare_float(&arg(0)?, &arg(1)?, |x, y| x == y)?,
+            "OpFUnordEqual" => compare_float(&arg(0)?, &arg(1)?, |x, y| x.is_nan() || y.is_nan() || x == y)?,
+            "OpFOrdNotEqual" => compare_float(&arg(0)?, &arg(1)?, |x, y| !x.is_nan() && !y.is_nan() && x != y)?,
+            "OpFUnordNotEqual" => compare_float(&arg(0)?, &arg(1)?, |x, y| x != y)?,
+            "OpFOrdLessThan" => compare_float(&arg(0)?, &arg(1)?, |x, y| x < y)?,
+            "OpFUnordLessThan" => compare_float(&arg(0)?, &arg(1)?, |x, y| x.is_nan() || y.is_nan() || x < y)?,
+            "OpFOrdGreaterThan" => compare_float(&arg(0)?, &arg(1)?, |x, y| x > y)?,
+            "OpFUnordGreaterThan" => compare_float(&arg(0)?, &arg(1)?, |x, y| x.is_nan() || y.is_nan() || x > y)?,
+            "OpFOrdLessThanEqual" => compare_float(&arg(0)?, &arg(1)?, |x, y| x <= y)?,
+            "OpFUnordLessThanEqual" => compare_float(&arg(0)?, &arg(1)?, |x, y| x.is_nan() || y.is_nan() || x <= y)?,
+            "OpFOrdGreaterThanEqual" => compare_float(&arg(0)?, &arg(1)?, |x, y| x >= y)?,
+            "OpFUnordGreaterThanEqual" => compare_float(&arg(0)?, &arg(1)?, |x, y| x.is_nan() || y.is_nan() || x >= y)?,
+
+            // A single invocation has no neighbours to take derivatives with
+            "OpDPdx" | "OpDPdy" | "OpFwidth" | "OpDPdxFine" | "OpDPdyFine" | "OpFwidthFine" | "OpDPdxCoarse"
+            | "OpDPdyCoarse" | "OpFwidthCoarse" => float1(&arg(0)?, |_| 0.0)?,
+
+            // Images
+            "OpImageSampleImplicitLod" | "OpImageSampleExplicitLod" => {
+                let texture = self.texture(&arg(0)?)?;
+                let uv = floats(&arg(1)?)?;
+                let (w, h) = (texture.width as i64, texture.height as i64);
+                let x = ((uv[0] * w as f32).floor() as i64).rem_euclid(w.max(1));
+                let y = ((uv.get(1).copied().unwrap_or(0.0) * h as f32).floor() as i64).rem_euclid(h.max(1));
+                Value::floats(texture.texels.get((y * w + x) as usize).unwrap_or(&[0.0; 4]))
+            }
+            "OpImageFetch" => {
+                let texture = self.texture(&arg(0)?)?;
+                let Value::Composite(xy) = arg(1)? else {
+                    return Err("Coordinate isn't a vector".to_string());
+                };
+                let (x, y) = (int(&xy[0])?, xy.get(1).map_or(Ok(0), int)?);
+                let in_bounds = x < texture.width && y < texture.height;
+                let texel = in_bounds.then(|| texture.texels.get((y * texture.width + x) as usize)).flatten();
+                Value::floats(texel.unwrap_or(&[0.0; 4]))
+            }
+            "OpImageQuerySize" | "OpImageQuerySizeLod" => {
+                let texture = self.texture(&arg(0)?)?;
+                Value::uints(&[texture.width, texture.height])
+            }
+
+            // Invocations run one at a time, so atomics are plain read-modify-writes
+            "OpAtomicExchange"
+            | "OpAtomicCompareExchange"
+            | "OpAtomicIIncrement"
+            | "OpAtomicIDecrement"
+            | "OpAtomicIAdd"
+            | "OpAtomicISub"
+            | "OpAtomicSMin"
+            | "OpAtomicUMin"
+            | "OpAtomicSMax"
+            | "OpAtomicUMax"
+            | "OpAtomicAnd"
+            | "OpAtomicOr"
+            | "OpAtomicXor" => {
+                let pointer = arg(0)?;
+                let old = self.load(&pointer)?;
+                let x = int(&old)?;
+                let new = match inst.name {
+                    "OpAtomicIIncrement" => x.wrapping_add(1),
+                    "OpAtomicIDecrement" => x.wrapping_sub(1),
+                    "OpAtomicCompareExchange" => {
+                        if x == int(&arg(5)?)? {
+                            int(&arg(4)?)?
+                        } else {
+                            x
+                        }
+                    }
+                    name => {
+                        let y = int(&arg(3)?)?;
+                        match name {
+                            "OpAtomicExchange" => y,
+                            "OpAtomicIAdd" => x.wrapping_add(y),
+                            "OpAtomicISub" => x.wrapping_sub(y),
+                            "OpAtomicSMin" => (x as i32).min(y as i32) as u32,
+                            "OpAtomicUMin" => x.min(y),
+                            "OpAtomicSMax" => (x as i32).max(y as i32) as u32,
+                            "OpAtomicUMax" => x.max(y),
+                            "OpAtomicAnd" => x & y,
+                            "OpAtomicOr" => x | y,
+                            _ => x ^ y,
+                        }
+                    }
+                };
+                self.store(&pointer, Value::Int(new))?;
+                old
+            }
+
+            "OpExtInst" if self.glsl_std_450.contains(&inst.args[0]) => {
+                let operands = (2..inst.args.len()).map(arg).collect::<Result<Vec<_>, _>>()?;
+                glsl_std_450(inst.args[1], &operands)?
+            }
+            "OpExtInst" => return Err("Unsupported extended instruction set".to_string()),
+            "" => return Err(format!("Unknown opcode {}", inst.opcode)),
+            _ => return Err("Unsupported instruction".to_string()),
+        };
+        Ok(Some(value))
+    }
+
+    fn texture(&self, value: &Value) -> Result<&Texture, String> {
+        let &Value::Texture(set, binding) = value else {
+            return Err("Expected a texture".to_string());
+        };
+        self.textures.get(&(set, binding)).ok_or_else(|| format!("No texture bound to set {} binding {}", set, binding))
+    }
+}
+
+fn member(value: &Value, i: u32) -> Result<&Value, String> {
+    match value {
+        Value::Composite(members) => members.get(i as usize).ok_or_else(|| format!("Index {} is out of bounds", i)),
+        _ => Err("Indexing into a scalar".to_string()),
+    }
+}
+
+fn float(value: &Value) -> Result<f32, String> {
+    value.as_f32().ok_or_else(|| format!("Expected a float, not {:?}", value))
+}
+
+fn int(value: &Value) -> Result<u32, String> {
+    value.as_u32().ok_or_else(|| format!("Expected an integer, not {:?}", value))
+}
+
+fn boolean(value: &Value) -> Result<bool, String> {
+    value.as_bool().ok_or_else(|| format!("Expected a boolean, not {:?}", value))
+}
+
+fn floats(value: &Value) -> Result<Vec<f32>, String> {
+    value.as_floats().ok_or_else(|| format!("Expected a float vector, not {:?}", value))
+}
+
+fn bools(value: &Value) -> Result<Vec<bool>, String> {
+    match value {
+        Value::Composite(members) => members.iter().map(boolean).collect(),
+        v => Ok(vec![boolean(v)?]),
+    }
+}
+
+/// Applies `f` to every scalar of a value.
+fn map(a: &Value, f: &dyn Fn(&Value) -> Result<Value, String>) -> Result<Value, String> {
+    match a {
+        Value::Composite(members) => Ok(Value::Composite(members.iter().map(|m| map(m, f)).collect::<Result<_, _>>()?)),
+        scalar => f(scalar),
+    }
+}
+
+/// Applies `f` to the scalars of two values of the same shape, a scalar is used for every component.
+fn zip(a: &Value, b: &Value, f: &dyn Fn(&Value, &Value) -> Result<Value, String>) -> Result<Value, String> {
+    match (a, b) {
+        (Value::Composite(x), Value::Composite(y)) => {
+            if x.len() != y.len() {
+                return Err("Operands have different sizes".to_string());
+            }
+            Ok(Value::Composite(x.iter().zip(y).map(|(x, y)| zip(x, y, f)).collect::<Result<_, _>>()?))
+        }
+        (Value::Composite(x), y) => Ok(Value::Composite(x.iter().map(|x| zip(x, y, f)).collect::<Result<_, _>>()?)),
+        (x, Value::Composite(y)) => Ok(Value::Composite(y.iter().map(|y| zip(x, y, f)).collect::<Result<_, _>>()?)),
+        (x, y) => f(x, y),
+    }
+}
+
+fn zip3(
+    a: &Value,
+    b: &Value,
+    c: &Value,
+    f: &dyn Fn(&Value, &Value, &Value) -> Result<Value, String>,
+) -> Result<Value, String> {
+    let len = [a, b, c].iter().find_map(|v| match v {
+        Value::Composite(members) => Some(members.len()),
+        _ => None,
+    });
+    let Some(len) = len else {
+        return f(a, b, c);
+    };
+    let component = |v: &Value, i: usize| match v {
+        Value::Composite(members) => members.get(i).cloned().ok_or("Operands have different sizes".to_string()),
+        scalar => Ok(scalar.clone()),
+    };
+    let members = (0..len).map(|i| zip3(&component(a, i)?, &component(b, i)?, &component(c, i)?, f));
+    Ok(Value::Composite(members.collect::<Result<_, _>>()?))
+}
+
+fn float1(a: &Value, f: impl Fn(f32) -> f32) -> Result<Value, String> {
+    map(a, &|x| Ok(Value::Float(f(float(x)?))))
+}
+
+fn float2(a: &Value, b: &Value, f: impl Fn(f32, f32) -> f32) -> Result<Value, String> {
+    zip(a, b, &|x, y| Ok(Value::Float(f(float(x)?, float(y)?))))
+}
+
+fn float3(a: &Value, b: &Value, c: &Value, f: impl Fn(f32, f32, f32) -> f32) -> Result<Value, String> {
+    zip3(a, b, c, &|x, y, z| Ok(Value::Float(f(float(x)?, float(y)?, float(z)?))))
+}
+
+fn int1(a: &Value, f: impl Fn(u32) -> u32) -> Result<Value, String> {
+    map(a, &|x| Ok(Value::Int(f(int(x)?))))
+}
+
+fn int2(a: &Value, b: &Value, f: impl Fn(u32, u32) -> u32) -> Result<Value, String> {
+    zip(a, b, &|x, y| Ok(Value::Int(f(int(x)?, int(y)?))))
+}
+
+fn int3(a: &Value, b: &Value, c: &Value, f: impl Fn(u32, u32, u32) -> u32) -> Result<Value, String> {
+    zip3(a, b, c, &|x, y, z| Ok(Value::Int(f(int(x)?, int(y)?, int(z)?))))
+}
+
+fn bool2(a: &Value, b: &Value, f: impl Fn(bool, bool) -> bool) -> Result<Value, String> {
+    zip(a, b, &|x, y| Ok(Value::Bool(f(boolean(x)?, boolean(y)?))))
+}
+
+fn compare_int(a: &Value, b: &Value, f: impl Fn(u32, u32) -> bool) -> Result<Value, String> {
+    zip(a, b, &|x, y| Ok(Value::Bool(f(int(x)?, int(y)?))))
+}
+
+fn compare_float(a: &Value, b: &Value, f: impl Fn(f32, f32) -> bool) -> Result<Value, String> {
+    zip(a, b, &|x, y| Ok(Value::Bool(f(float(x)?, float(y)?))))
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+/// Columns of a matrix.
+fn matrix(value: &Value) -> Result<Vec<Vec<f32>>, String> {
+    match value {
+        Value::Composite(columns) => columns.iter().map(floats).collect(),
+        _ => Err(format!("Expected a matrix, not {:?}", value)),
+    }
+}
+
+fn from_matrix(columns: Vec<Vec<f32>>) -> Value {
+    Value::Composite(columns.iter().map(|column| Value::floats(column)).collect())
+}
+
+fn transpose(m: &[Vec<f32>]) -> Vec<Vec<f32>> {
+    let rows = m.first().map_or(0, Vec::len);
+    (0..rows).map(|r| m.iter().map(|column| column[r]).collect()).collect()
+}
+
+fn matrix_times_vector(m: &[Vec<f32>], v: &[f32]) -> Vec<f32> {
+    let rows = m.first().map_or(0, Vec::len);
+    (0..rows).map(|r| m.iter().zip(v).map(|(column, x)| column[r] * x).sum()).collect()
+}
+
+fn determinant(m: &[Vec<f32>]) -> f32 {
+    match m.len() {
+        0 => 1.0,
+        1 => m[0][0],
+        2 => m[0][0] * m[1][1] - m[1][0] * m[0][1],
+        n => (0..n)
+            .map(|c| {
+                // Cofactor expansion along the first row
+                let minor: Vec<Vec<f32>> =
+                    m.iter().enumerate().filter(|&(i, _)| i != c).map(|(_, column)| column[1..].to_vec()).collect();
+                let sign = if c % 2 == 0 {
+                    1.0
+                } else {
+                    -1.0
+                };
+                sign * m[c][0] * determinant(&minor)
+            })
+            .sum(),
+    }
+}
+
+/// Inverse by Gauss-Jordan elimination, a singular matrix gives NaNs like on most GPUs.
+fn inverse(m: &[Vec<f32>]) -> Vec<Vec<f32>> {
+    let n = m.len();
+    // Rows of [A | I]
+    let mut rows: Vec<Vec<f32>> = (0..n)
+        .map(|r| {
+            (0..2 * n)
+                .map(|c| {
+                    if c < n {
+                        m[c][r]
+                    } else {
+                        (c - n == r) as u32 as f32
+                    }
+                })
+                .collect()
+        })
+        .collect();
+    for c in 0..n {
+        let pivot = (c..n).max_by(|&a, &b| rows[a][c].abs().total_cmp(&rows[b][c].abs())).unwrap();
+        rows.swap(c, pivot);
+        let p = rows[c][c];
+        rows[c].iter_mut().for_each(|x| *x /= p);
+        for r in 0..n {
+            if r != c {
+                let factor = rows[r][c];
+                let pivot_row = rows[c].clone();
+                rows[r].iter_mut().zip(pivot_row).for_each(|(x, p)| *x -= factor * p);
+            }
+        }
+    }
+    (0..n).map(|c| (0..n).map(|r| rows[r][n + c]).collect()).collect()
+}
+
+fn f16_to_f32(half: u16) -> f32 {
+    let sign = if half & 0x8000 != 0 {
+        -1.0
+    } else {
+        1.0
+    };
+    let exponent = (half >> 10 & 0x1f) as i32;
+    let mantissa = (half & 0x3ff) as f32;
+    sign * match exponent {
+        0 => mantissa * 2f32.powi(-24),
+        31 if mantissa == 0.0 => f32::INFINITY,
+        31 => f32::NAN,
+        e => (1.0 + mantissa / 1024.0) * 2f32.powi(e - 15),
+    }
+}
+
+fn pack(values: &[f32], bits: u32, f: impl Fn(f32) -> u32) -> u32 {
+    values.iter().enumerate().fold(0, |packed, (i, &v)| packed | (f(v) << (bits * i as u32)))
+}
+
+fn unpack(packed: u32, count: u32, f: impl Fn(u32) -> f32) -> Value {
+    let bits = 32 / count;
+    let mask = (1u64 << bits) as u32 - 1;
+    Value::Composite((0..count).map(|i| Value::Float(f(packed >> (bits * i) & mask))).collect())
+}
+
+/// Runs a GLSL.std.450 extended instruction.
+fn glsl_std_450(number: u32, operands: &[Value]) -> Result<Value, String> {
+    let name = GLSL_STD_450.get(number as usize).copied().unwrap_or("?");
+    let arg = |i: usize| operands.get(i).ok_or_else(|| format!("Missing operands for {}", name));
+    let (x, y, z) = (arg(0), arg(1), arg(2));
+    Ok(match name {
+        "Round" => float1(x?, f32::round)?,
+        "RoundEven" => float1(x?, f32::round_ties_even)?,
+        "Trunc" => float1(x?, f32::trunc)?,
+        "FAbs" => float1(x?, f32::abs)?,
+        "SAbs" => int1(x?, |x| (x as i32).wrapping_abs() as u32)?,
+        "FSign" => float1(x?, |x| {
+            if x > 0.0 {
+                1.0
+            } else if x < 0.0 {
+                -1.0
+            } else {
+                0.0
+            }
+        })?,
+        "SSign" => int1(x?, |x| (x as i32).signum() as u32)?,
+        "Floor" => float1(x?, f32::floor)?,
+        "Ceil" => float1(x?, f32::ceil)?,
+        "Fract" => float1(x?, |x| x - x.floor())?,
+        "Radians" => float1(x?, f32::to_radians)?,
+        "Degrees" => float1(x?, f32::to_degrees)?,
+        "Sin" => float1(x?, f32::sin)?,
+        "Cos" => float1(x?, f32::cos)?,
+        "Tan" => float1(x?, f32::tan)?,
+        "Asin" => float1(x?, f32::asin)?,
+        "Acos" => float1(x?, f32::acos)?,
+        "Atan" => float1(x?, f32::atan)?,
+        "Sinh" => float1(x?, f32::sinh)?,
+        "Cosh" => float1(x?, f32::cosh)?,
+        "Tanh" => float1(x?, f32::tanh)?,
+        "Asinh" => float1(x?, f32::asinh)?,
+        "Acosh" => float1(x?, f32::acosh)?,
+        "Atanh" => float1(x?, f32::atanh)?,
+        "Atan2" => float2(x?, y?, f32::atan2)?,
+        "Pow" => float2(x?, y?, f32::powf)?,
+        "Exp" => float1(x?, f32::exp)?,
+        "Log" => float1(x?, f32::ln)?,
+        "Exp2" => float1(x?, f32::exp2)?,
+        "Log2" => float1(x?, f32::log2)?,
+        "Sqrt" => float1(x?, f32::sqrt)?,
+        "InverseSqrt" => float1(x?, |x| 1.0 / x.sqrt())?,
+        "Determinant" => Value::Float(determinant(&matrix(x?)?)),
+        "MatrixInverse" => from_matrix(inverse(&matrix(x?)?)),
+        "FMin" | "NMin" => float2(x?, y?, f32::min)?,
+        "UMin" => int2(x?, y?, u32::min)?,
+        "SMin" => int2(x?, y?, |x, y| (x as i32).min(y as i32) as u32)?,
+        "FMax" | "NMax" => float2(x?, y?, f32::max)?,
+        "UMax" => int2(x?, y?, u32::max)?,
+        "SMax" => int2(x?, y?, |x, y| (x as i32).max(y as i32) as u32)?,
+        "FClamp" | "NClamp" => float3(x?, y?, z?, |x, lo, hi| x.max(lo).min(hi))?,
+        "UClamp" => int3(x?, y?, z?, |x, lo, hi| x.max(lo).min(hi))?,
+        "SClamp" => int3(x?, y?, z?, |x, lo, hi| (x as i32).max(lo as i32).min(hi as i32) as u32)?,
+        "FMix" => float3(x?, y?, z?, |x, y, a| x * (1.0 - a) + y * a)?,
+        "Step" => float2(x?, y?, |edge, x| {
+            if x < edge {
+                0.0
+            } else {
+                1.0
+            }
+        })?,
+        "SmoothStep" => float3(x?, y?, z?, |e0, e1, x| {
+            let t = ((x - e0) / (e1 - e0)).clamp(0.0, 1.0);
+            t * t * (3.0 - 2.0 * t)
+        })?,
+        "Fma" => float3(x?, y?, z?, f32::mul_add)?,
+        "Ldexp" => zip(x?, y?, &|x, e| Ok(Value::Float(float(x)? * 2f32.powi(int(e)? as i32))))?,
+        "PackSnorm4x8" => {
+            Value::Int(pack(&floats(x?)?, 8, |v| ((v.clamp(-1.0, 1.0) * 127.0).round() as i8) as u8 as u32))
+        }
+        "PackUnorm4x8" => Value::Int(pack(&floats(x?)?, 8, |v| (v.clamp(0.0, 1.0) * 255.0).round() as u32)),
+        "PackSnorm2x16" => {
+            Value::Int(pack(&floats(x?)?, 16, |v| ((v.clamp(-1.0, 1.0) * 32767.0).round() as i16) as u16 as u32))
+        }
+        "PackUnorm2x16" => Value::Int(pack(&floats(x?)?, 16, |v| (v.clamp(0.0, 1.0) * 65535.0).round() as u32)),
+        "PackHalf2x16" => Value::Int(pack(&floats(x?)?, 16, |v| f32_to_f16(v) as u32)),
+        "UnpackSnorm2x16" => unpack(int(x?)?, 2, |v| (v as u16 as i16 as f32 / 32767.0).max(-1.0)),
+        "UnpackUnorm2x16" => unpack(int(x?)?, 2, |v| v as f32 / 65535.0),
+        "UnpackHalf2x16" => unpack(int(x?)?, 2, |v| f16_to_f32(v as u16)),
+        "UnpackSnorm4x8" => unpack(int(x?)?, 4, |v| (v as u8 as i8 as f32 / 127.0).max(-1.0)),
+        "UnpackUnorm4x8" => unpack(int(x?)?, 4, |v| v as f32 / 255.0),
+        "Length" => {
+            let v = floats(x?)?;
+            Value::Float(dot(&v, &v).sqrt())
+        }
+        "Distance" => {
+            let d: Vec<f32> = floats(x?)?.iter().zip(floats(y?)?).map(|(a, b)| a - b).collect();
+            Value::Float(dot(&d, &d).sqrt())
+        }
+        "Cross" => {
+            let (a, b) = (floats(x?)?, floats(y?)?);
+            if a.len() != 3 || b.len() != 3 {
+                return Err("Cross needs 3 component vectors".to_string());
+            }
+            Value::floats(&[a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]])
+        }
+        "Normalize" => {
+            let v = floats(x?)?;
+            let length = dot(&v, &v).sqrt();
+            Value::floats(&v.iter().map(|x| x / length).collect::<Vec<_>>())
+        }
+        "FaceForward" => {
+            if dot(&floats(z?)?, &floats(y?)?) < 0.0 {
+                x?.clone()
+            } else {
+                float1(x?, |x| -x)?
+            }
+        }
+        "Reflect" => {
+            let (i, n) = (floats(x?)?, floats(y?)?);
+            let d = dot(&n, &i);
+            Value::floats(&i.iter().zip(&n).map(|(i, n)| i - 2.0 * d * n).collect::<Vec<_>>())
+        }
+        "Refract" => {
+            let (i, n, eta) = (floats(x?)?, floats(y?)?, float(z?)?);
+            let d = dot(&n, &i);
+            let k = 1.0 - eta * eta * (1.0 - d * d);
+            if k < 0.0 {
+                Value::floats(&vec![0.0; i.len()])
+            } else {
+                Value::floats(&i.iter().zip(&n).map(|(i, n)| eta * i - (eta * d + k.sqrt()) * n).collect::<Vec<_>>())
+            }
+        }
+        "FindILsb" => int1(x?, |x| {
+            if x == 0 {
+                u32::MAX
+            } else {
+                x.trailing_zeros()
+            }
+        })?,
+        "FindSMsb" => int1(x?, |x| {
+            // Most significant bit that differs from the sign bit
+            let x = if (x as i32) < 0 {
+                !x
+            } else {
+                x
+            };
+            if x == 0 {
+                u32::MAX
+            } else {
+                31 - x.leading_zeros()
+            }
+        })?,
+        "FindUMsb" => int1(x?, |x| {
+            if x == 0 {
+                u32::MAX
+            } else {
+                31 - x.leading_zeros()
+            }
+        })?,
+        _ => return Err(format!("Unsupported GLSL.std.450 instruction {} ({})", name, number)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spirv_as::assemble;
+    use crate::vk_util::RenderCommand;
+
+    /// Synthetic fragment shader reading a buffer laid out like `RenderCommand`: colors the fragment with the command
+    /// of the instance, darkened on the right half of the rect, and discards it when transparent. It exercises
+    /// buffers, push constants, branches and OpKill, the renderer's shaders don't darken anything.
+    const SIMPLE_FRAG: &str = r#"
+               OpCapability Shader
+               OpMemoryModel Logical GLSL450
+               OpEntryPoint Fragment %main "main" %uv %color
+               OpExecutionMode %main OriginUpperLeft
+               OpDecorate %uv Location 0
+               OpDecorate %color Location 0
+               OpMemberDecorate %command 0 Offset 0
+               OpMemberDecorate %command 1 Offset 8
+               OpMemberDecorate %command 2 Offset 16
+               OpMemberDecorate %command 3 Offset 20
+               OpDecorate %command_array ArrayStride 36
+               OpMemberDecorate %buffer 0 Offset 0
+               OpDecorate %buffer BufferBlock
+               OpDecorate %commands DescriptorSet 0
+               OpDecorate %commands Binding 1
+               OpMemberDecorate %push 0 Offset 0
+               OpDecorate %push Block
+       %void = OpTypeVoid
+         %fn = OpTypeFunction %void
+      %float = OpTypeFloat 32
+       %uint = OpTypeInt 32 0
+       %bool = OpTypeBool
+    %v2float = OpTypeVector %float 2
+    %v3float = OpTypeVector %float 3
+    %v4float = OpTypeVector %float 4
+    %command = OpTypeStruct %v2float %v2float %float %v4float
+%command_array = OpTypeRuntimeArray %command
+     %buffer = OpTypeStruct %command_array
+ %ptr_buffer = OpTypePointer Uniform %buffer
+  %ptr_color = OpTypePointer Uniform %v4float
+       %push = OpTypeStruct %uint
+   %ptr_push = OpTypePointer PushConstant %push
+%ptr_push_uint = OpTypePointer PushConstant %uint
+  %ptr_input = OpTypePointer Input %v2float
+ %ptr_output = OpTypePointer Output %v4float
+   %commands = OpVariable %ptr_buffer Uniform
+         %pc = OpVariable %ptr_push PushConstant
+         %uv = OpVariable %ptr_input Input
+      %color = OpVariable %ptr_output Output
+     %uint_0 = OpConstant %uint 0
+     %uint_3 = OpConstant %uint 3
+    %float_0 = OpConstant %float 0
+   %float_05 = OpConstant %float 0.5
+       %main = OpFunction %void None %fn
+      %entry = OpLabel
+  %index_ptr = OpAccessChain %ptr_push_uint %pc %uint_0
+      %index = OpLoad %uint %index_ptr
+  %color_ptr = OpAccessChain %ptr_color %commands %uint_0 %index %uint_3
+          %c = OpLoad %v4float %color_ptr
+      %alpha = OpCompositeExtract %float %c 3
+%transparent = OpFOrdEqual %bool %alpha %float_0
+               OpSelectionMerge %visible None
+               OpBranchConditional %transparent %discard %visible
+    %discard = OpLabel
+               OpKill
+    %visible = OpLabel
+   %uv_value = OpLoad %v2float %uv
+          %u = OpCompositeExtract %float %uv_value 0
+      %right = OpFOrdGreaterThan %bool %u %float_05
+               OpSelectionMerge %done None
+               OpBranchConditional %right %darken %done
+     %darken = OpLabel
+        %rgb = OpVectorShuffle %v3float %c %c 0 1 2
+       %dark = OpVectorTimesScalar %v3float %rgb %float_05
+  %dark_rgba = OpCompositeConstruct %v4float %dark %alpha
+               OpBranch %done
+       %done = OpLabel
+     %result = OpPhi %v4float %c %visible %dark_rgba %darken
+               OpStore %color %result
+               OpReturn
+               OpFunctionEnd
+"#;
+
+    /// Scales the floats of a storage buffer, clamped by a function with a loop, and adds their index.
+    const SCALE_COMP: &str = r#"
+               OpCapability Shader
+       %glsl = OpExtInstImport "GLSL.std.450"
+               OpMemoryModel Logical GLSL450
+               OpEntryPoint GLCompute %main "main" %gid
+               OpExecutionMode %main LocalSize 4 1 1
+               OpDecorate %gid BuiltIn GlobalInvocationId
+               OpDecorate %floats ArrayStride 4
+               OpMemberDecorate %buffer 0 Offset 0
+               OpDecorate %buffer Block
+               OpDecorate %data DescriptorSet 0
+               OpDecorate %data Binding 0
+               OpMemberDecorate %push 0 Offset 0
+               OpMemberDecorate %push 1 Offset 4
+               OpDecorate %push Block
+       %void = OpTypeVoid
+         %fn = OpTypeFunction %void
+      %float = OpTypeFloat 32
+       %uint = OpTypeInt 32 0
+       %bool = OpTypeBool
+     %v3uint = OpTypeVector %uint 3
+     %floats = OpTypeRuntimeArray %float
+     %buffer = OpTypeStruct %floats
+ %ptr_buffer = OpTypePointer StorageBuffer %buffer
+  %ptr_float = OpTypePointer StorageBuffer %float
+       %push = OpTypeStruct %float %uint
+   %ptr_push = OpTypePointer PushConstant %push
+%ptr_push_float = OpTypePointer PushConstant %float
+%ptr_push_uint = OpTypePointer PushConstant %uint
+    %ptr_gid = OpTypePointer Input %v3uint
+   %fn_shade = OpTypeFunction %float %float %uint
+       %data = OpVariable %ptr_buffer StorageBuffer
+         %pc = OpVariable %ptr_push PushConstant
+        %gid = OpVariable %ptr_gid Input
+     %uint_0 = OpConstant %uint 0
+     %uint_1 = OpConstant %uint 1
+    %float_0 = OpConstant %float 0
+   %float_10 = OpConstant %float 10
+      %shade = OpFunction %float None %fn_shade
+          %x = OpFunctionParameter %float
+          %n = OpFunctionParameter %uint
+    %s_entry = OpLabel
+               OpBranch %header
+     %header = OpLabel
+          %i = OpPhi %uint %uint_0 %s_entry %next_i %body
+        %sum = OpPhi %float %float_0 %s_entry %next_sum %body
+       %more = OpULessThan %bool %i %n
+               OpLoopMerge %exit %body None
+               OpBranchConditional %more %body %exit
+       %body = OpLabel
+   %next_sum = OpFAdd %float %sum %x
+     %next_i = OpIAdd %uint %i %uint_1
+               OpBranch %header
+       %exit = OpLabel
+    %clamped = OpExtInst %float %glsl FClamp %sum %float_0 %float_10
+               OpReturnValue %clamped
+               OpFunctionEnd
+       %main = OpFunction %void None %fn
+    %m_entry = OpLabel
+         %id = OpLoad %v3uint %gid
+      %index = OpCompositeExtract %uint %id 0
+  %count_ptr = OpAccessChain %ptr_push_uint %pc %uint_1
+      %count = OpLoad %uint %count_ptr
+     %inside = OpULessThan %bool %index %count
+               OpSelectionMerge %end None
+               OpBranchConditional %inside %work %end
+       %work = OpLabel
+  %scale_ptr = OpAccessChain %ptr_push_float %pc %uint_0
+      %scale = OpLoad %float %scale_ptr
+  %value_ptr = OpAccessChain %ptr_float %data %uint_0 %index
+      %value = OpLoad %float %value_ptr
+     %scaled = OpFMul %float %value %scale
+     %shaded = OpFunctionCall %float %shade %scaled %index
+   %index_f = OpConvertUToF %float %index
+     %result = OpFAdd %float %shaded %index_f
+               OpStore %value_ptr %result
+               OpBranch %end
+        %end = OpLabel
+               OpReturn
+               OpFunctionEnd
+"#;
+
+    fn command_bytes(commands: &[RenderCommand]) -> Vec<u8> {
+        let mut bytes = vec![];
+        for command in commands {
+            let RenderCommand::Rect(x, y, w, h, z, color) = command;
+            for v in [*x, *y, *w, *h, *z].iter().chain(color) {
+                bytes.extend(v.to_le_bytes());
+            }
+        }
+        bytes
+    }
+
+    #[test]
+    fn fragment() {
+        let module = assemble(SIMPLE_FRAG).unwrap();
+        let mut interp = Interpreter::new(&module).unwrap();
+        let commands = [
+            RenderCommand::Rect(0.0, 0.0, 10.0, 10.0, 0.5, [1.0, 0.0, 0.0, 1.0]),
+            RenderCommand::Rect(5.0, 5.0, 20.0, 10.0, 0.0, [0.2, 0.4, 0.8, 0.5]),
+            RenderCommand::Rect(5.0, 5.0, 20.0, 10.0, 0.0, [1.0, 1.0, 1.0, 0.0]),
+        ];
+        interp.set_buffer(0, 1, command_bytes(&commands));
+
+        interp.set_input(0, Value::floats(&[0.25, 0.5]));
+        interp.set_push_constants(1u32.to_le_bytes().to_vec());
+        interp.run("main").unwrap();
+        assert!(!interp.killed());
+        assert_eq!(interp.output(0), Some(&Value::floats(&[0.2, 0.4, 0.8, 0.5])));
+
+        interp.set_input(0, Value::floats(&[0.75, 0.5]));
+        interp.set_push_constants(0u32.to_le_bytes().to_vec());
+        interp.run("main").unwrap();
+        assert_eq!(interp.output(0), Some(&Value::floats(&[0.5, 0.0, 0.0, 1.0])));
+
+        interp.set_push_constants(2u32.to_le_bytes().to_vec());
+        interp.run("main").unwrap();
+        assert!(interp.killed());
+
+        // Reading past the end of the buffer is an error, not a panic
+        interp.set_push_constants(3u32.to_le_bytes().to_vec());
+        let error = interp.run("main").unwrap_err();
+        assert!(error.starts_with("OpLoad %"), "{}", error);
+        assert!(interp.run("missing").is_err());
+
+        // Composite types too large to allocate
+        let module = assemble(&SIMPLE_FRAG.replace("OpTypeVector %float 3", "OpTypeVector %float 0x20000002")).unwrap();
+        assert!(Interpreter::new(&module).err().unwrap().contains("components is not supported"));
+        let array =
+            "%uint_3 = OpConstant %uint 3\n%big = OpConstant %uint 0x10000000\n%array = OpTypeArray %v4float %big";
+        let module = assemble(&SIMPLE_FRAG.replace("%uint_3 = OpConstant %uint 3", array)).unwrap();
+        assert!(Interpreter::new(&module).err().unwrap().contains("too large"));
+    }
+
+    #[test]
+    fn compute() {
+        let module = assemble(SCALE_COMP).unwrap();
+        let mut interp = Interpreter::new(&module).unwrap();
+        let values = [1.0f32, 2.0, 3.0, 4.0, 5.0, 6.0];
+        interp.set_buffer(0, 0, values.iter().flat_map(|v| v.to_le_bytes()).collect());
+        interp.set_push_constants([2.0f32.to_le_bytes(), 5u32.to_le_bytes()].concat());
+        interp.dispatch("main", [2, 1, 1]).unwrap();
+
+        let bytes = interp.buffer(0, 0).unwrap();
+        let result: Vec<f32> = bytes.chunks(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect();
+        assert_eq!(result, [0.0, 5.0, 12.0, 13.0, 14.0, 6.0]);
+
+        interp.max_steps = 10;
+        assert!(interp.dispatch("main", [1, 1, 1]).unwrap_err().contains("steps"));
+    }
+
+    #[test]
+    fn math() {
+        let m = vec![vec![2.0, 0.0, 0.0], vec![1.0, 1.0, 0.0], vec![0.0, 3.0, 4.0]];
+        assert_eq!(determinant(&m), 8.0);
+        let identity = inverse(&m).iter().map(|column| matrix_times_vector(&m, column)).collect::<Vec<_>>();
+        assert_eq!(identity, [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]]);
+        assert_eq!(transpose(&[vec![1.0, 2.0], vec![3.0, 4.0]]), [[1.0, 3.0], [2.0, 4.0]]);
+
+        let v = Value::floats(&[0.5, -1.0]);
+        let packed = glsl_std_450(58, &[v.clone()]).unwrap(); // PackHalf2x16
+        assert_eq!(glsl_std_450(62, &[packed]).unwrap(), v); // UnpackHalf2x16
+        let packed = glsl_std_450(55, &[Value::floats(&[0.0, 1.0, 0.5, 1.0])]).unwrap(); // PackUnorm4x8
+        assert_eq!(packed, Value::Int(0xff80ff00));
+        assert_eq!(glsl_std_450(75, &[Value::Int(0)]).unwrap(), Value::Int(u32::MAX)); // FindUMsb
+        assert_eq!(glsl_std_450(74, &[Value::Int(-2i32 as u32)]).unwrap(), Value::Int(0)); // FindSMsb
+        assert!(glsl_std_450(78, &[]).unwrap_err().contains("InterpolateAtOffset"));
+    }
+}
@@ -0,0 +1,680 @@
+use crate::spirv::ShaderModule;
+use crate::spirv_grammar::{op_info, op_info_by_name, OpInfo};
+use crate::spirv_val::{id_positions, split_module, validate};
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+
+// SPIR-V optimizer:
+// A few passes over the instruction words of a module to shrink it, in the spirit of spirv-opt but much smaller.
+// Instructions are kept as words with the positions of their ids, so the passes don't have to know about every
+// opcode: ids are found with the operand specs of the grammar, like the validator does.
+// The module is validated after every pass, so a pass producing an invalid module fails with an error instead
+// of handing it to the driver.
+
+const DECORATION_LINKAGE_ATTRIBUTES: u32 = 41;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pass {
+    /// Removes OpName, OpMemberName, OpLine, OpSource and the other debug instructions.
+    StripDebugInfo,
+    /// Removes the functions, types, constants and global variables the entry points don't use.
+    EliminateDeadCode,
+    /// Replaces arithmetic, comparisons and conversions of 32-bit constants by their result.
+    FoldConstants,
+    /// Renumbers the ids from 1 so that the bound is as small as possible.
+    CompactIds,
+}
+
+impl Pass {
+    /// Every pass, in an order where each one leaves the most for the next: folding leaves constants unused,
+    /// removing them and the debug info leaves holes in the ids.
+    pub const ALL: [Pass; 4] = [Pass::FoldConstants, Pass::EliminateDeadCode, Pass::StripDebugInfo, Pass::CompactIds];
+}
+
+/// Runs passes over a module, in order.
+pub fn optimize(module: &ShaderModule, passes: &[Pass]) -> Result<ShaderModule, String> {
+    let words = module.to_words()?;
+    validate(&words).map_err(|e| format!("Invalid module: {}", e))?;
+    let mut module = Module::parse(&words)?;
+    for &pass in passes {
+        match pass {
+            Pass::StripDebugInfo => module.strip_debug_info(),
+            Pass::EliminateDeadCode => module.eliminate_dead_code(),
+            Pass::FoldConstants => module.fold_constants()?,
+            Pass::CompactIds => module.compact_ids(),
+        }
+        validate(&module.words()).map_err(|e| format!("{:?} produced an invalid module: {}", pass, e))?;
+    }
+
+    let bytes: Vec<u8> = module.words().iter().flat_map(|w| w.to_le_bytes()).collect();
+    ShaderModule::try_from(bytes.as_slice()).map_err(|e| e.to_string())
+}
+
+struct Inst {
+    info: &'static OpInfo,
+    /// Operand words, without the word count and opcode
+    operands: Vec<u32>,
+    /// Index in `operands` of the result type, result and ids
+    ids: Vec<usize>,
+}
+
+impl Inst {
+    fn new(name: &str, operands: Vec<u32>) -> Self {
+        let info = op_info_by_name(name).expect("known opcode");
+        let ids = id_positions(info, &operands, None).expect("valid operands");
+        Self {
+            info,
+            operands,
+            ids,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        self.info.name
+    }
+
+    fn result_type(&self) -> Option<u32> {
+        self.info.has_result_type().then(|| self.operands[0])
+    }
+
+    fn result(&self) -> Option<u32> {
+        match (self.info.has_result_type(), self.info.has_result()) {
+            (true, true) => Some(self.operands[1]),
+            (false, true) => Some(self.operands[0]),
+            _ => None,
+        }
+    }
+
+    /// Ids used by the instruction, without its result.
+    fn uses(&self) -> impl Iterator<Item = u32> + '_ {
+        let result = self.info.has_result().then(|| usize::from(self.info.has_result_type()));
+        self.ids
+            .iter()
+            .enumerate()
+            .filter(move |&(i, _)| Some(i) != result)
+            .map(|(_, &position)| self.operands[position])
+    }
+}
+
+/// Debug and annotation instructions, kept only as long as the id they apply to.
+fn is_annotation(name: &str) -> bool {
+    matches!(
+        name,
+        "OpName"
+            | "OpMemberName"
+            | "OpDecorate"
+            | "OpDecorateId"
+            | "OpDecorateString"
+            | "OpMemberDecorate"
+            | "OpMemberDecorateString"
+            | "OpTypeForwardPointer"
+    )
+}
+
+fn is_debug_info(name: &str) -> bool {
+    matches!(
+        name,
+        "OpName"
+            | "OpMemberName"
+            | "OpLine"
+            | "OpNoLine"
+            | "OpSource"
+            | "OpSourceContinued"
+            | "OpSourceExtension"
+            | "OpModuleProcessed"
+    )
+}
+
+struct Module {
+    header: [u32; 5],
+    insts: Vec<Inst>,
+}
+
+impl Module {
+    fn parse(words: &[u32]) -> Result<Self, String> {
+        let mut insts = vec![];
+        // Widths of the scalar types and the types of the values, to tell how many words literals take
+        let mut widths: HashMap<u32, u32> = HashMap::new();
+        let mut types: HashMap<u32, u32> = HashMap::new();
+        for (offset, words) in split_module(words).map_err(|e| e.to_string())? {
+            let info = op_info(words[0] & 0xffff).ok_or_else(|| format!("Unknown opcode at word {}", offset))?;
+            let operands = &words[1..];
+            let literal_type = match info.name {
+                "OpConstant" | "OpSpecConstant" => operands.first(),
+                "OpSwitch" => operands.first().and_then(|selector| types.get(selector)),
+                _ => None,
+            };
+            let literal_words = literal_type.map(|ty| widths.get(ty).map_or(1, |&w| w.div_ceil(32).max(1) as usize));
+            let ids = id_positions(info, operands, literal_words).map_err(|e| format!("{} at word {}", e, offset))?;
+            let inst = Inst {
+                info,
+                operands: operands.to_vec(),
+                ids,
+            };
+            if matches!(info.name, "OpTypeInt" | "OpTypeFloat") {
+                widths.insert(operands[0], operands[1]);
+            }
+            if let (Some(ty), Some(result)) = (inst.result_type(), inst.result()) {
+                types.insert(result, ty);
+            }
+            insts.push(inst);
+        }
+        Ok(Self {
+            header: [words[0], words[1], words[2], words[3], words[4]],
+            insts,
+        })
+    }
+
+    fn words(&self) -> Vec<u32> {
+        let mut words = self.header.to_vec();
+        for inst in &self.insts {
+            words.push(((inst.operands.len() as u32 + 1) << 16) | inst.info.opcode);
+            words.extend(&inst.operands);
+        }
+        words
+    }
+
+    /// Range of the instructions of each function, by function id.
+    fn functions(&self) -> HashMap<u32, Range<usize>> {
+        let mut functions = HashMap::new();
+        let mut start = 0;
+        for (i, inst) in self.insts.iter().enumerate() {
+            match inst.name() {
+                "OpFunction" => start = i,
+                "OpFunctionEnd" => {
+                    functions.insert(self.insts[start].operands[1], start..i + 1);
+                }
+                _ => {}
+            }
+        }
+        functions
+    }
+
+    fn first_function(&self) -> usize {
+        self.insts.iter().position(|i| i.name() == "OpFunction").unwrap_or(self.insts.len())
+    }
+
+    fn strip_debug_info(&mut self) {
+        self.insts.retain(|inst| !is_debug_info(inst.name()));
+        // Strings can still be used by non-semantic instructions
+        let used: HashSet<u32> = self.insts.iter().flat_map(|inst| inst.uses()).collect();
+        self.insts.retain(|inst| inst.name() != "OpString" || used.contains(&inst.operands[0]));
+    }
+
+    fn eliminate_dead_code(&mut self) {
+        let functions = self.functions();
+        let globals = self.first_function();
+        let definitions: HashMap<u32, usize> =
+            self.insts[..globals].iter().enumerate().filter_map(|(i, inst)| Some((inst.result()?, i))).collect();
+
+        // Everything that isn't an annotation or a definition is used, starting with the entry points
+        let mut live = HashSet::new();
+        let mut stack: Vec<u32> = vec![];
+        for inst in &self.insts[..globals] {
+            let linkage = inst.name() == "OpDecorate" && inst.operands.get(1) == Some(&DECORATION_LINKAGE_ATTRIBUTES);
+            if inst.result().is_none() && (!is_annotation(inst.name()) || linkage) {
+                stack.extend(inst.uses());
+            }
+        }
+        loop {
+            while let Some(id) = stack.pop() {
+                if !live.insert(id) {
+                    continue;
+                }
+                if let Some(range) = functions.get(&id) {
+                    for inst in &self.insts[range.clone()] {
+                        live.extend(inst.result());
+                        stack.extend(inst.uses());
+                    }
+                } else if let Some(&i) = definitions.get(&id) {
+                    stack.extend(self.insts[i].uses());
+                }
+            }
+            // Annotations of used ids can use other ids, like OpDecorateId
+            for inst in self.insts[..globals].iter().filter(|inst| is_annotation(inst.name())) {
+                if live.contains(&inst.operands[0]) {
+                    stack.extend(inst.uses().filter(|id| !live.contains(id)));
+                }
+            }
+            if stack.is_empty() {
+                break;
+            }
+        }
+
+        let dead_functions: Vec<Range<usize>> =
+            functions.iter().filter(|(id, _)| !live.contains(id)).map(|(_, range)| range.clone()).collect();
+        let mut index = 0;
+        self.insts.retain(|inst| {
+            let in_dead_function = dead_functions.iter().any(|range| range.contains(&index));
+            index += 1;
+            if index > globals {
+                return !in_dead_function;
+            }
+            match inst.result() {
+                Some(result) => live.contains(&result),
+                None if is_annotation(inst.name()) => live.contains(&inst.operands[0]),
+                None => true,
+            }
+        });
+    }
+
+    fn fold_constants(&mut self) -> Result<(), String> {
+        let mut folder = Folder::default();
+        let globals = self.first_function();
+        for inst in &self.insts[..globals] {
+            folder.declare(inst);
+        }
+
+        let mut bound = self.header[3];
+        let mut new_constants = vec![];
+        let mut folded = HashSet::new();
+        for (i, inst) in self.insts.iter().enumerate().skip(globals) {
+            let (Some(ty), Some(result)) = (inst.result_type(), inst.result()) else {
+                continue;
+            };
+            let Some(components) = folder.fold(inst) else {
+                continue;
+            };
+            folded.insert(i);
+            let id = folder.constant(ty, &components, Some(result), &mut bound, &mut new_constants);
+            if id != result {
+                folder.replacements.insert(result, id);
+            }
+        }
+        if folded.is_empty() {
+            return Ok(());
+        }
+
+        let mut index = 0;
+        self.insts.retain(|inst| {
+            let replaced = is_annotation(inst.name()) && folder.replacements.contains_key(&inst.operands[0]);
+            let keep = !folded.contains(&index) && !replaced;
+            index += 1;
+            keep
+        });
+        for inst in &mut self.insts {
+            for &position in &inst.ids {
+                if let Some(&id) = folder.replacements.get(&inst.operands[position]) {
+                    inst.operands[position] = id;
+                }
+            }
+        }
+        let globals = self.first_function();
+        self.insts.splice(globals..globals, new_constants);
+        self.header[3] = bound;
+        Ok(())
+    }
+
+    fn compact_ids(&mut self) {
+        let mut ids: HashMap<u32, u32> = HashMap::new();
+        for inst in &mut self.insts {
+            for &position in &inst.ids {
+                let next = ids.len() as u32 + 1;
+                inst.operands[position] = *ids.entry(inst.operands[position]).or_insert(next);
+            }
+        }
+        self.header[3] = ids.len() as u32 + 1;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Scalar {
+    Bool,
+    Int,
+    Float,
+}
+
+/// Constants known by the folder, as the bits of their components.
+#[derive(Default)]
+struct Folder {
+    /// Scalar kind and component count of 32-bit scalar and vector types
+    types: HashMap<u32, (Scalar, u32)>,
+    /// Vector type with a component type and count
+    vector_types: HashMap<(u32, u32), u32>,
+    /// Type and components of the constants
+    values: HashMap<u32, (u32, Vec<u32>)>,
+    /// Constant of a type with given components
+    constants: HashMap<(u32, Vec<u32>), u32>,
+    /// Folded results that are equal to another constant
+    replacements: HashMap<u32, u32>,
+}
+
+impl Folder {
+    fn declare(&mut self, inst: &Inst) {
+        let operands = &inst.operands;
+        match inst.name() {
+            "OpTypeBool" => {
+                self.types.insert(operands[0], (Scalar::Bool, 1));
+            }
+            "OpTypeInt" if operands[1] == 32 => {
+                self.types.insert(operands[0], (Scalar::Int, 1));
+            }
+            "OpTypeFloat" if operands[1] == 32 => {
+                self.types.insert(operands[0], (Scalar::Float, 1));
+            }
+            "OpTypeVector" => {
+                if let Some(&(scalar, 1)) = self.types.get(&operands[1]) {
+                    self.types.insert(operands[0], (scalar, operands[2]));
+                    self.vector_types.entry((operands[1], operands[2])).or_insert(operands[0]);
+                }
+            }
+            "OpConstantTrue" | "OpConstantFalse" | "OpConstant" => {
+                let value = match inst.name() {
+                    "OpConstantTrue" => 1,
+                    "OpConstantFalse" => 0,
+                    _ => operands[2],
+                };
+                if self.types.contains_key(&operands[0]) {
+                    self.add(operands[0], vec![value], operands[1]);
+                }
+            }
+            "OpConstantComposite" if self.types.get(&operands[0]).is_some_and(|&(_, count)| count > 1) => {
+                let components: Option<Vec<u32>> =
+                    operands[2..].iter().map(|id| self.scalar(*id)).collect::<Option<_>>();
+                if let Some(components) = components {
+                    self.add(operands[0], components, operands[1]);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn add(&mut self, ty: u32, components: Vec<u32>, id: u32) {
+        self.constants.entry((ty, components.clone())).or_insert(id);
+        self.values.insert(id, (ty, components));
+    }
+
+    fn scalar(&self, id: u32) -> Option<u32> {
+        match self.values.get(&id) {
+            Some((_, components)) if components.len() == 1 => Some(components[0]),
+            _ => None,
+        }
+    }
+
+    fn components(&self, id: u32) -> Option<&[u32]> {
+        let id = self.replacements.get(&id).unwrap_or(&id);
+        self.values.get(id).map(|(_, components)| components.as_slice())
+    }
+
+    /// Finds or declares the constant of a type with given components, using `id` for it when it's new.
+    fn constant(
+        &mut self,
+        ty: u32,
+        components: &[u32],
+        id: Option<u32>,
+        bound: &mut u32,
+        declarations: &mut Vec<Inst>,
+    ) -> u32 {
+        if let Some(&existing) = self.constants.get(&(ty, components.to_vec())) {
+            return existing;
+        }
+        let id = id.unwrap_or_else(|| {
+            *bound += 1;
+            *bound - 1
+        });
+        let (scalar, count) = self.types[&ty];
+        let inst = if count > 1 {
+            let component_type = self.vector_types.iter().find(|&(_, &v)| v == ty).map(|(&(c, _), _)| c);
+            let component_type = component_type.expect("vector type of a constant");
+            let mut operands = vec![ty, id];
+            for &component in components {
+                operands.push(self.constant(component_type, &[component], None, bound, declarations));
+            }
+            Inst::new("OpConstantComposite", operands)
+        } else if scalar == Scalar::Bool {
+            Inst::new(
+                if components[0] != 0 {
+                    "OpConstantTrue"
+                } else {
+                    "OpConstantFalse"
+                },
+                vec![ty, id],
+            )
+        } else {
+            Inst::new("OpConstant", vec![ty, id, components[0]])
+        };
+        declarations.push(inst);
+        self.add(ty, components.to_vec(), id);
+        id
+    }
+
+    /// Components of the result of an instruction whose operands are all constant.
+    fn fold(&self, inst: &Inst) -> Option<Vec<u32>> {
+        let ty = inst.result_type()?;
+        let &(_, count) = self.types.get(&ty)?;
+        let name = inst.name();
+        let args: Vec<&[u32]> = inst.uses().skip(1).map(|id| self.components(id)).collect::<Option<_>>()?;
+
+        if name == "OpCompositeExtract" && args.len() == 1 && inst.operands.len() == 4 {
+            return args[0].get(inst.operands[3] as usize).map(|&c| vec![c]);
+        }
+        let (a, b) = match args.as_slice() {
+            [a] => (*a, None),
+            [a, b] => (*a, Some(*b)),
+            _ => return None,
+        };
+        // Scalar operands of vector operations apply to every component
+        let component = |values: &[u32], i: usize| values.get(i).or(values.first()).copied();
+        if args.iter().any(|values| values.len() != count as usize && values.len() != 1) {
+            return None;
+        }
+        (0..count as usize)
+            .map(|i| fold_scalar(name, component(a, i)?, b.map(|b| component(b, i)).unwrap_or(Some(0))?))
+            .collect()
+    }
+}
+
+/// Result of an operation on 32-bit scalars, None when it's undefined or not supported.
+fn fold_scalar(name: &str, a: u32, b: u32) -> Option<u32> {
+    let (fa, fb) = (f32::from_bits(a), f32::from_bits(b));
+    let (sa, sb) = (a as i32, b as i32);
+    let float = |v: f32| Some(v.to_bits());
+    let bool = |v: bool| Some(u32::from(v));
+    match name {
+        "OpIAdd" => Some(a.wrapping_add(b)),
+        "OpISub" => Some(a.wrapping_sub(b)),
+        "OpIMul" => Some(a.wrapping_mul(b)),
+        "OpUDiv" => a.checked_div(b),
+        "OpUMod" => a.checked_rem(b),
+        "OpSDiv" => sa.checked_div(sb).map(|v| v as u32),
+        "OpSRem" => sa.checked_rem(sb).map(|v| v as u32),
+        "OpSMod" => sa.checked_rem(sb).map(|r| if r != 0 && (r < 0) != (sb < 0) { r + sb } else { r } as u32),
+        "OpSNegate" => Some(sa.wrapping_neg() as u32),
+        "OpNot" => Some(!a),
+        "OpShiftLeftLogical" => a.checked_shl(b),
+        "OpShiftRightLogical" => a.checked_shr(b),
+        "OpShiftRightArithmetic" => sa.checked_shr(b).map(|v| v as u32),
+        "OpBitwiseOr" => Some(a | b),
+        "OpBitwiseXor" => Some(a ^ b),
+        "OpBitwiseAnd" => Some(a & b),
+        "OpFAdd" => float(fa + fb),
+        "OpFSub" => float(fa - fb),
+        "OpFMul" | "OpVectorTimesScalar" => float(fa * fb),
+        "OpFDiv" => float(fa / fb),
+        "OpFRem" => float(fa % fb),
+        "OpFMod" => float(fa - fb * (fa / fb).floor()),
+        "OpFNegate" => float(-fa),
+        "OpIEqual" | "OpLogicalEqual" => bool(a == b),
+        "OpINotEqual" | "OpLogicalNotEqual" => bool(a != b),
+        "OpUGreaterThan" => bool(a > b),
+        "OpUGreaterThanEqual" => bool(a >= b),
+        "OpULessThan" => bool(a < b),
+        "OpULessThanEqual" => bool(a <= b),
+        "OpSGreaterThan" => bool(sa > sb),
+        "OpSGreaterThanEqual" => bool(sa >= sb),
+        "OpSLessThan" => bool(sa < sb),
+        "OpSLessThanEqual" => bool(sa <= sb),
+        "OpFOrdEqual" => bool(fa == fb),
+        "OpFOrdNotEqual" => bool(!fa.is_nan() && !fb.is_nan() && fa != fb),
+        "OpFOrdLessThan" => bool(fa < fb),
+        "OpFOrdGreaterThan" => bool(fa > fb),
+        "OpFOrdLessThanEqual" => bool(fa <= fb),
+        "OpFOrdGreaterThanEqual" => bool(fa >= fb),
+        "OpFUnordEqual" => bool(fa.is_nan() || fb.is_nan() || fa == fb),
+        "OpFUnordNotEqual" => bool(fa != fb),
+        "OpFUnordLessThan" => bool(fa.is_nan() || fb.is_nan() || fa < fb),
+        "OpFUnordGreaterThan" => bool(fa.is_nan() || fb.is_nan() || fa > fb),
+        "OpFUnordLessThanEqual" => bool(fa.is_nan() || fb.is_nan() || fa <= fb),
+        "OpFUnordGreaterThanEqual" => bool(fa.is_nan() || fb.is_nan() || fa >= fb),
+        "OpLogicalOr" => bool(a != 0 || b != 0),
+        "OpLogicalAnd" => bool(a != 0 && b != 0),
+        "OpLogicalNot" => bool(a == 0),
+        "OpConvertSToF" => float(sa as f32),
+        "OpConvertUToF" => float(a as f32),
+        // Out of range conversions are undefined
+        "OpConvertFToS" if (-2147483648.0..2147483648.0).contains(&fa) => Some(fa as i32 as u32),
+        "OpConvertFToU" if fa > -1.0 && fa < 4294967296.0 => Some(fa as u32),
+        "OpBitcast" | "OpCopyObject" => Some(a),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spirv_as::assemble;
+    use crate::spirv_dis::{disassemble, DisassembleOptions};
+    use crate::spirv_interp::Interpreter;
+
+    /// Writes arithmetic on constants to a storage buffer, with debug info, an unused function and unused
+    /// globals.
+    const CONSTANTS_COMP: &str = r#"
+               OpCapability Shader
+       %glsl = OpExtInstImport "GLSL.std.450"
+               OpMemoryModel Logical GLSL450
+               OpEntryPoint GLCompute %main "main"
+               OpExecutionMode %main LocalSize 1 1 1
+       %file = OpString "constants.comp"
+               OpSource GLSL 450 %file
+               OpName %main "main"
+               OpName %unused "unused"
+               OpName %buffer "Buffer"
+               OpMemberName %buffer 0 "values"
+               OpDecorate %values ArrayStride 4
+               OpMemberDecorate %buffer 0 Offset 0
+               OpDecorate %buffer Block
+               OpDecorate %data DescriptorSet 0
+               OpDecorate %data Binding 0
+               OpDecorate %private RelaxedPrecision
+       %void = OpTypeVoid
+         %fn = OpTypeFunction %void
+      %float = OpTypeFloat 32
+       %uint = OpTypeInt 32 0
+        %int = OpTypeInt 32 1
+       %bool = OpTypeBool
+     %v2uint = OpTypeVector %uint 2
+    %v3float = OpTypeVector %float 3
+     %values = OpTypeRuntimeArray %uint
+     %buffer = OpTypeStruct %values
+ %ptr_buffer = OpTypePointer StorageBuffer %buffer
+   %ptr_uint = OpTypePointer StorageBuffer %uint
+%ptr_private = OpTypePointer Private %float
+       %data = OpVariable %ptr_buffer StorageBuffer
+    %private = OpVariable %ptr_private Private
+     %uint_0 = OpConstant %uint 0
+     %uint_1 = OpConstant %uint 1
+     %uint_2 = OpConstant %uint 2
+     %uint_3 = OpConstant %uint 3
+     %uint_4 = OpConstant %uint 4
+     %uint_7 = OpConstant %uint 7
+   %uint_100 = OpConstant %uint 100
+     %int_m7 = OpConstant %int -7
+      %int_2 = OpConstant %int 2
+   %float_15 = OpConstant %float 1.5
+    %float_4 = OpConstant %float 4
+    %v3_null = OpConstantNull %v3float
+    %v2_1_2 = OpConstantComposite %v2uint %uint_1 %uint_2
+    %v2_7_3 = OpConstantComposite %v2uint %uint_7 %uint_3
+       %main = OpFunction %void None %fn
+      %entry = OpLabel
+               OpLine %file 10 1
+        %sum = OpIAdd %uint %uint_2 %uint_3
+    %product = OpIMul %uint %sum %uint_7
+   %quotient = OpSDiv %int %int_m7 %int_2
+    %product_f = OpFMul %float %float_15 %float_4
+  %truncated = OpConvertFToU %uint %product_f
+     %vector = OpIAdd %v2uint %v2_1_2 %v2_7_3
+      %first = OpCompositeExtract %uint %vector 0
+       %less = OpULessThan %bool %product %uint_100
+     %choice = OpSelect %uint %less %uint_4 %uint_0
+ %by_zero = OpUDiv %uint %uint_7 %uint_0
+    %ptr_0 = OpAccessChain %ptr_uint %data %uint_0 %uint_0
+               OpStore %ptr_0 %product
+    %ptr_1 = OpAccessChain %ptr_uint %data %uint_0 %uint_1
+ %quotient_u = OpBitcast %uint %quotient
+               OpStore %ptr_1 %quotient_u
+    %ptr_2 = OpAccessChain %ptr_uint %data %uint_0 %uint_2
+               OpStore %ptr_2 %truncated
+    %ptr_3 = OpAccessChain %ptr_uint %data %uint_0 %uint_3
+               OpStore %ptr_3 %first
+    %ptr_4 = OpAccessChain %ptr_uint %data %uint_0 %choice
+               OpStore %ptr_4 %sum
+               OpReturn
+               OpFunctionEnd
+     %unused = OpFunction %void None %fn
+   %u_entry = OpLabel
+      %scale = OpVectorTimesScalar %v3float %v3_null %float_4
+      %value = OpExtInst %float %glsl Sqrt %float_4
+               OpStore %private %value
+               OpReturn
+               OpFunctionEnd
+"#;
+
+    fn run(module: &ShaderModule) -> Vec<u8> {
+        let mut interp = Interpreter::new(module).unwrap();
+        interp.set_buffer(0, 0, vec![0; 20]);
+        interp.dispatch("main", [1, 1, 1]).unwrap();
+        interp.buffer(0, 0).unwrap().to_vec()
+    }
+
+    fn text(module: &ShaderModule) -> String {
+        let options = DisassembleOptions {
+            friendly_names: false,
+            ..Default::default()
+        };
+        disassemble(&module.to_words().unwrap(), &options).unwrap()
+    }
+
+    #[test]
+    fn passes() {
+        let module = assemble(CONSTANTS_COMP).unwrap();
+        let expected = run(&module);
+        assert_eq!(expected, [35, 0, 0, 0, 253, 255, 255, 255, 6, 0, 0, 0, 8, 0, 0, 0, 5, 0, 0, 0]);
+
+        let folded = optimize(&module, &[Pass::FoldConstants]).unwrap();
+        let disassembly = text(&folded);
+        for op in ["OpIAdd", "OpIMul", "OpSDiv", "OpFMul", "OpConvertFToU", "OpCompositeExtract", "OpULessThan"] {
+            assert!(!disassembly.contains(op), "{} wasn't folded", op);
+        }
+        // Undefined results and instructions that aren't arithmetic stay
+        assert!(disassembly.contains("OpUDiv") && disassembly.contains("OpSelect"));
+        assert!(disassembly.contains("OpConstant %12 35"));
+        assert_eq!(run(&folded), expected);
+
+        let live = optimize(&folded, &[Pass::EliminateDeadCode]).unwrap();
+        let disassembly = text(&live);
+        for unused in ["OpExtInstImport", "OpTypeVector %11 3", "OpConstantNull", "Private", "%unused"] {
+            assert!(!disassembly.contains(unused), "{} wasn't removed", unused);
+        }
+        assert!(!disassembly.contains("OpConstant %13 -7"));
+        assert!(disassembly.contains("OpName %2 \"main\""));
+        assert_eq!(run(&live), expected);
+
+        let stripped = optimize(&live, &[Pass::StripDebugInfo]).unwrap();
+        let disassembly = text(&stripped);
+        for debug in ["OpName", "OpMemberName", "OpSource", "OpString", "OpLine"] {
+            assert!(!disassembly.contains(debug), "{} wasn't stripped", debug);
+        }
+        assert_eq!(run(&stripped), expected);
+
+        let compact = optimize(&stripped, &[Pass::CompactIds]).unwrap();
+        let results = text(&compact).lines().filter(|line| line.contains(" = Op")).count() as u32;
+        assert_eq!(compact.bound, results + 1);
+        assert!(compact.bound < module.bound);
+        assert_eq!(run(&compact), expected);
+
+        let all = optimize(&module, &Pass::ALL).unwrap();
+        assert_eq!(all.to_words().unwrap(), compact.to_words().unwrap());
+        assert!(all.to_words().unwrap().len() < module.to_words().unwrap().len() * 3 / 4);
+    }
+}
@@ -0,0 +1,1282 @@
+use crate::spirv::ShaderModule;
+use crate::spirv_dis::{decode_string, MAGIC};
+use crate::spirv_grammar::*;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+// SPIR-V validation:
+// Structural checks of a module before it is handed to the driver. This is not a full validator
+// like spirv-val, it catches the malformed modules that would otherwise crash a driver or our own
+// decoder: strings that aren't UTF-8, ids out of bounds or defined twice, operands of the wrong type
+// for common opcodes, vectors and matrices of invalid sizes, sections in the wrong order and entry
+// points whose interface doesn't match the variables they use.
+// Errors carry the word offset of the offending instruction (the header is words 0 to 4).
+
+const STORAGE_INPUT: u32 = 1;
+const STORAGE_OUTPUT: u32 = 3;
+const STORAGE_FUNCTION: u32 = 7;
+const CAPABILITY_VECTOR16: u32 = 7;
+const CAPABILITY_LINKAGE: u32 = 5;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationErrorKind {
+    // Header and encoding
+    InvalidLength,
+    InvalidMagic(u32),
+    UnsupportedVersion(u32),
+    InvalidSchema(u32),
+    ZeroWordCount,
+    Truncated,
+    UnknownOpcode(u32),
+    MissingOperands(&'static str),
+    ExtraOperands(&'static str),
+    UnterminatedString(&'static str),
+    InvalidUtf8(&'static str),
+    InvalidEnumValue(EnumKind, u32),
+    InvalidInstruction(&'static str),
+    // Ids
+    IdOutOfBounds {
+        id: u32,
+        bound: u32,
+    },
+    DuplicateDefinition(u32),
+    UndefinedId(u32),
+    // Types
+    NotAType(u32),
+    WrongType {
+        id: u32,
+        expected: &'static str,
+    },
+    InvalidOperand(&'static str),
+    // Layout
+    OutOfOrder(&'static str),
+    OutsideBlock(&'static str),
+    MissingTerminator,
+    UnterminatedFunction,
+    MissingMemoryModel,
+    DuplicateMemoryModel,
+    // Entry points
+    MissingEntryPoint,
+    EntryPointNotFunction(u32),
+    DuplicateEntryPoint(String),
+    InvalidInterface(u32),
+    MissingInterface {
+        entry_point: u32,
+        variable: u32,
+    },
+    ExecutionModeTarget(u32),
+}
+
+impl fmt::Display for ValidationErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use ValidationErrorKind::*;
+        match self {
+            InvalidLength => write!(f, "Length should be an even multiple of 4 and hold a header"),
+            InvalidMagic(magic) => write!(f, "Magic number should be 0x{:08x}, not 0x{:08x}", MAGIC, magic),
+            UnsupportedVersion(version) => write!(f, "Unsupported version number 0x{:08x}", version),
+            InvalidSchema(schema) => write!(f, "Reserved word should be 0, not {}", schema),
+            ZeroWordCount => write!(f, "Instruction with a word count of 0"),
+            Truncated => write!(f, "Instruction runs past the end of the module"),
+            UnknownOpcode(opcode) => write!(f, "Unknown opcode {}", opcode),
+            MissingOperands(name) => write!(f, "Missing operands for {}", name),
+            ExtraOperands(name) => write!(f, "Too many operands for {}", name),
+            UnterminatedString(name) => write!(f, "Unterminated string in {}", name),
+            InvalidUtf8(name) => write!(f, "String in {} is not valid UTF-8", name),
+            InvalidEnumValue(kind, value) => write!(f, "Invalid {:?} {}", kind, value),
+            InvalidInstruction(e) => write!(f, "Failed to decode instruction: {}", e),
+            IdOutOfBounds {
+                id,
+                bound,
+            } => write!(f, "Id %{} is out of bounds (bound is {})", id, bound),
+            DuplicateDefinition(id) => write!(f, "Id %{} is defined more than once", id),
+            UndefinedId(id) => write!(f, "Id %{} is used but never defined", id),
+            NotAType(id) => write!(f, "Id %{} is not a type", id),
+            WrongType {
+                id,
+                expected,
+            } => write!(f, "Id %{} should be {}", id, expected),
+            InvalidOperand(message) => write!(f, "{}", message),
+            OutOfOrder(name) => write!(f, "{} is not allowed here by the logical layout of a module", name),
+            OutsideBlock(name) => write!(f, "{} is outside of a block", name),
+            MissingTerminator => write!(f, "Block doesn't end with a branch or return instruction"),
+            UnterminatedFunction => write!(f, "Function doesn't end with OpFunctionEnd"),
+            MissingMemoryModel => write!(f, "Module has no OpMemoryModel"),
+            DuplicateMemoryModel => write!(f, "Module has more than one OpMemoryModel"),
+            MissingEntryPoint => write!(f, "Module has no OpEntryPoint and no Linkage capability"),
+            EntryPointNotFunction(id) => write!(f, "Entry point %{} is not a function", id),
+            DuplicateEntryPoint(name) => write!(f, "Entry point '{}' is declared more than once", name),
+            InvalidInterface(id) => write!(f, "Interface id %{} is not a global Input or Output variable", id),
+            MissingInterface {
+                entry_point,
+                variable,
+            } => write!(f, "Entry point %{} uses %{} which is missing from its interface", entry_point, variable),
+            ExecutionModeTarget(id) => write!(f, "Execution mode target %{} is not an entry point", id),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationError {
+    /// Word offset of the offending instruction, from the start of the module.
+    pub offset: usize,
+    pub kind: ValidationErrorKind,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at word {}", self.kind, self.offset)
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+fn error<T>(offset: usize, kind: ValidationErrorKind) -> Result<T, ValidationError> {
+    Err(ValidationError {
+        offset,
+        kind,
+    })
+}
+
+/// Converts bytes to words, swapping big endian modules.
+pub fn words_from_bytes(bytes: &[u8]) -> Result<Vec<u32>, ValidationError> {
+    if !bytes.len().is_multiple_of(4) {
+        return error(bytes.len() / 4, ValidationErrorKind::InvalidLength);
+    }
+    let mut words: Vec<u32> = bytes.chunks_exact(4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect();
+    if words.first() == Some(&MAGIC.swap_bytes()) {
+        words.iter_mut().for_each(|w| *w = w.swap_bytes());
+    }
+    Ok(words)
+}
+
+/// Checks the header and the word counts and splits the module into (offset, instruction words).
+/// The operand layout of known opcodes is checked too, unknown opcodes are let through.
+pub fn split_module(words: &[u32]) -> Result<Vec<(usize, &[u32])>, ValidationError> {
+    if words.len() < 5 {
+        return error(words.len(), ValidationErrorKind::InvalidLength);
+    }
+    if words[0] != MAGIC {
+        return error(0, ValidationErrorKind::InvalidMagic(words[0]));
+    }
+    if !(0x0001_0000..=0x0001_0600).contains(&words[1]) {
+        // 1.0 <= version <= 1.6
+        return error(1, ValidationErrorKind::UnsupportedVersion(words[1]));
+    }
+    if words[4] != 0 {
+        return error(4, ValidationErrorKind::InvalidSchema(words[4]));
+    }
+
+    let mut instructions = vec![];
+    let mut offset = 5;
+    while offset < words.len() {
+        let word_count = (words[offset] >> 16) as usize;
+        if word_count == 0 {
+            return error(offset, ValidationErrorKind::ZeroWordCount);
+        }
+        let Some(inst) = words.get(offset..offset + word_count) else {
+            return error(offset, ValidationErrorKind::Truncated);
+        };
+        if let Some(info) = op_info(inst[0] & 0xffff) {
+            // The width of OpSwitch literals depends on the selector type, `validate` checks them
+            if info.name != "OpSwitch" {
+                walk(info, &inst[1..], None).map_err(|kind| ValidationError {
+                    offset,
+                    kind,
+                })?;
+            }
+        }
+        instructions.push((offset, inst));
+        offset += word_count;
+    }
+    Ok(instructions)
+}
+
+/// Ids and enum values of an instruction, in operand order.
+#[derive(Default)]
+struct Operands {
+    result_type: Option<u32>,
+    result: Option<u32>,
+    ids: Vec<u32>,
+    /// Index of each of the ids in the operand words
+    id_positions: Vec<usize>,
+    enums: Vec<(EnumKind, u32)>,
+}
+
+struct Walker<'a> {
+    info: &'static OpInfo,
+    words: &'a [u32],
+    cursor: usize,
+    /// Words per context dependent literal, or None to let them take the rest of the instruction
+    literal_words: Option<usize>,
+    operands: Operands,
+}
+
+fn walk(info: &'static OpInfo, words: &[u32], literal_words: Option<usize>) -> Result<Operands, ValidationErrorKind> {
+    let mut walker = Walker {
+        info,
+        words,
+        cursor: 0,
+        literal_words,
+        operands: Operands::default(),
+    };
+    walker.operands(info.spec)?;
+    if walker.cursor < words.len() {
+        return Err(ValidationErrorKind::ExtraOperands(info.name));
+    }
+    Ok(walker.operands)
+}
+
+/// Index in the operand words (without the opcode) of the result type, result and id operands of an instruction.
+/// `literal_words` is the number of words of its context dependent literals, see `NumberType::word_count`.
+pub fn id_positions(
+    info: &'static OpInfo,
+    words: &[u32],
+    literal_words: Option<usize>,
+) -> Result<Vec<usize>, ValidationErrorKind> {
+    let operands = walk(info, words, literal_words)?;
+    let results = operands.result_type.iter().chain(&operands.result).count();
+    Ok((0..results).chain(operands.id_positions).collect())
+}
+
+impl Walker<'_> {
+    fn operands(&mut self, spec: &'static str) -> Result<(), ValidationErrorKind> {
+        for operand in parse_operands(spec) {
+            match operand.quantifier {
+                Quantifier::One => self.operand(operand.kind)?,
+                Quantifier::Optional => {
+                    if self.cursor < self.words.len() {
+                        self.operand(operand.kind)?;
+                    }
+                }
+                Quantifier::Variadic => {
+                    while self.cursor < self.words.len() {
+                        self.operand(operand.kind)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn word(&mut self) -> Result<u32, ValidationErrorKind> {
+        let word = *self.words.get(self.cursor).ok_or(ValidationErrorKind::MissingOperands(self.info.name))?;
+        self.cursor += 1;
+        Ok(word)
+    }
+
+    fn operand(&mut self, kind: OperandKind) -> Result<(), ValidationErrorKind> {
+        match kind {
+            OperandKind::ResultType => self.operands.result_type = Some(self.word()?),
+            OperandKind::Result => self.operands.result = Some(self.word()?),
+            OperandKind::Id => {
+                self.operands.id_positions.push(self.cursor);
+                let id = self.word()?;
+                self.operands.ids.push(id);
+            }
+            OperandKind::Literal | OperandKind::ExtInst => {
+                self.word()?;
+            }
+            OperandKind::String => {
+                let (_, len) = decode_string(&self.words[self.cursor..])
+                    .ok_or(ValidationErrorKind::UnterminatedString(self.info.name))?;
+                let bytes: Vec<u8> =
+                    self.words[self.cursor..self.cursor + len].iter().flat_map(|w| w.to_le_bytes()).collect();
+                let nul = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+                if std::str::from_utf8(&bytes[..nul]).is_err() {
+                    return Err(ValidationErrorKind::InvalidUtf8(self.info.name));
+                }
+                self.cursor += len;
+            }
+            OperandKind::ContextLiteral => {
+                let count = self.literal_words.unwrap_or(self.words.len().saturating_sub(self.cursor).max(1));
+                if self.cursor + count > self.words.len() {
+                    return Err(ValidationErrorKind::MissingOperands(self.info.name));
+                }
+                self.cursor += count;
+            }
+            OperandKind::SpecConstantOp => {
+                let opcode = self.word()?;
+                let info =
+                    op_info(opcode).ok_or(ValidationErrorKind::InvalidOperand("Invalid opcode in OpSpecConstantOp"))?;
+                self.operands(info.spec.trim_start_matches("T R").trim_start())?;
+            }
+            OperandKind::LiteralId => {
+                self.operand(OperandKind::ContextLiteral)?;
+                self.operand(OperandKind::Id)?;
+            }
+            OperandKind::IdId => {
+                self.operand(OperandKind::Id)?;
+                self.operand(OperandKind::Id)?;
+            }
+            OperandKind::IdLiteral => {
+                self.operand(OperandKind::Id)?;
+                self.operand(OperandKind::Literal)?;
+            }
+            OperandKind::Enum(kind) => {
+                let value = self.word()?;
+                self.operands.enums.push((kind, value));
+                if kind.is_mask() {
+                    for bit in (0..32).map(|i| 1 << i).filter(|bit| value & bit != 0) {
+                        self.operands(kind.parameters(bit))?;
+                    }
+                } else {
+                    self.operands(kind.parameters(value))?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Validates a module given as little or big endian bytes.
+pub fn validate_bytes(bytes: &[u8]) -> Result<(), ValidationError> {
+    validate(&words_from_bytes(bytes)?)
+}
+
+/// Validates a module, returning the first error found.
+pub fn validate(words: &[u32]) -> Result<(), ValidationError> {
+    let mut validator = Validator {
+        bound: words.get(3).copied().unwrap_or(0),
+        ..Validator::default()
+    };
+    let instructions = split_module(words)?;
+    validator.definitions(&instructions)?;
+    validator.layout(words.len())?;
+    validator.types()?;
+    validator.entry_points()
+}
+
+impl ShaderModule {
+    /// Validates the module, see `spirv_val::validate`.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let words = self.to_words().map_err(|_| ValidationError {
+            offset: 0,
+            kind: ValidationErrorKind::InvalidInstruction("Module can't be encoded"),
+        })?;
+        validate(&words)
+    }
+}
+
+struct Inst<'a> {
+    offset: usize,
+    info: &'static OpInfo,
+    words: &'a [u32],
+    operands: Operands,
+}
+
+struct Def<'a> {
+    opcode: &'static str,
+    result_type: Option<u32>,
+    /// Operand words, without the opcode
+    words: &'a [u32],
+}
+
+struct EntryPoint {
+    offset: usize,
+    function: u32,
+    interface: Vec<u32>,
+}
+
+#[derive(Default)]
+struct Validator<'a> {
+    bound: u32,
+    instructions: Vec<Inst<'a>>,
+    defs: HashMap<u32, Def<'a>>,
+    /// Ids referenced by each function
+    function_uses: HashMap<u32, HashSet<u32>>,
+    /// Whether the module declares the Vector16 capability, for vectors of 8 and 16 components
+    vector16: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionState {
+    Outside,
+    /// After OpFunction, before the first block
+    Parameters,
+    InBlock {
+        first: bool,
+        variables: bool,
+    },
+    BetweenBlocks,
+}
+
+fn is_terminator(name: &str) -> bool {
+    matches!(
+        name,
+        "OpBranch" | "OpBranchConditional" | "OpSwitch" | "OpReturn" | "OpReturnValue" | "OpKill" | "OpUnreachable"
+    )
+}
+
+/// Section of the logical layout of a module, None for instructions in functions.
+fn section(name: &str) -> Option<u8> {
+    Some(match name {
+        "OpCapability" => 0,
+        "OpExtension" => 1,
+        "OpExtInstImport" => 2,
+        "OpMemoryModel" => 3,
+        "OpEntryPoint" => 4,
+        "OpExecutionMode" | "OpExecutionModeId" => 5,
+        "OpString" | "OpSourceExtension" | "OpSource" | "OpSourceContinued" => 6,
+        "OpName" | "OpMemberName" => 7,
+        "OpModuleProcessed" => 8,
+        "OpDecorate"
+        | "OpMemberDecorate"
+        | "OpDecorationGroup"
+        | "OpGroupDecorate"
+        | "OpGroupMemberDecorate"
+        | "OpDecorateId" => 9,
+        "OpTypeForwardPointer" => 10,
+        _ if name.starts_with("OpType") || name.starts_with("OpConstant") || name.starts_with("OpSpecConstant") => 10,
+        _ => return None,
+    })
+}
+
+impl<'a> Validator<'a> {
+    /// Decodes the operands of every instruction and checks ids against the bound and for single definitions.
+    fn definitions(&mut self, instructions: &[(usize, &'a [u32])]) -> Result<(), ValidationError> {
+        for &(offset, words) in instructions {
+            let opcode = words[0] & 0xffff;
+            let Some(info) = op_info(opcode) else {
+                return error(offset, ValidationErrorKind::UnknownOpcode(opcode));
+            };
+            let literal_words = match info.name {
+                "OpConstant" | "OpSpecConstant" => Some(self.literal_words(words[1])),
+                "OpSwitch" => {
+                    Some(words.get(1).and_then(|&id| self.type_of(id)).map_or(1, |ty| self.literal_words(ty)))
+                }
+                _ => None,
+            };
+            let operands = walk(info, &words[1..], literal_words).map_err(|kind| ValidationError {
+                offset,
+                kind,
+            })?;
+            for &(kind, value) in &operands.enums {
+                let valid = if kind.is_mask() {
+                    (0..32).map(|i| 1 << i).filter(|bit| value & bit != 0).all(|bit| kind.name(bit).is_some())
+                } else {
+                    kind.name(value).is_some()
+                };
+                if !valid {
+                    return error(offset, ValidationErrorKind::InvalidEnumValue(kind, value));
+                }
+            }
+            let ids = operands.result_type.iter().chain(&operands.result).chain(&operands.ids);
+            if let Some(&id) = ids.clone().find(|&&id| id == 0 || id >= self.bound) {
+                return error(
+                    offset,
+                    ValidationErrorKind::IdOutOfBounds {
+                        id,
+                        bound: self.bound,
+                    },
+                );
+            }
+            if info.name == "OpCapability" {
+                self.vector16 |= words[1] == CAPABILITY_VECTOR16;
+            }
+            if let Some(result) = operands.result {
+                let def = Def {
+                    opcode: info.name,
+                    result_type: operands.result_type,
+                    words: &words[1..],
+                };
+                if self.defs.insert(result, def).is_some() {
+                    return error(offset, ValidationErrorKind::DuplicateDefinition(result));
+                }
+            }
+            self.instructions.push(Inst {
+                offset,
+                info,
+                words: &words[1..],
+                operands,
+            });
+        }
+
+        for inst in &self.instructions {
+            let uses = inst.operands.result_type.iter().chain(&inst.operands.ids);
+            if let Some(&id) = uses.clone().find(|id| !self.defs.contains_key(id)) {
+                return error(inst.offset, ValidationErrorKind::UndefinedId(id));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the logical layout: the order of the module sections and the structure of functions.
+    fn layout(&mut self, len: usize) -> Result<(), ValidationError> {
+        let mut section_index = 0;
+        let mut memory_models = 0;
+        let mut has_entry_point = false;
+        let mut has_linkage = false;
+        let mut state = FunctionState::Outside;
+        let mut function = 0;
+        for inst in &self.instructions {
+            let (offset, name) = (inst.offset, inst.info.name);
+            match name {
+                "OpNop" | "OpLine" | "OpNoLine" => continue,
+                "OpCapability" => has_linkage |= inst.words[0] == CAPABILITY_LINKAGE,
+                "OpMemoryModel" => {
+                    memory_models += 1;
+                    if memory_models > 1 {
+                        return error(offset, ValidationErrorKind::DuplicateMemoryModel);
+                    }
+                }
+                "OpEntryPoint" => has_entry_point = true,
+                _ => {}
+            }
+
+            // Module level instructions
+            let global = match name {
+                "OpVariable" => {
+                    let function_storage = inst.words[2] == STORAGE_FUNCTION;
+                    if state == FunctionState::Outside && function_storage {
+                        return error(
+                            offset,
+                            ValidationErrorKind::InvalidOperand(
+                                "Global variables can't use the Function storage class",
+                            ),
+                        );
+                    }
+                    if state != FunctionState::Outside && !function_storage {
+                        return error(
+                            offset,
+                            ValidationErrorKind::InvalidOperand(
+                                "Variables in functions must use the Function storage class",
+                            ),
+                        );
+                    }
+                    (state == FunctionState::Outside).then_some(10)
+                }
+                "OpUndef" | "OpExtInst" if state == FunctionState::Outside => Some(10),
+                "OpFunction" => Some(11),
+                _ => section(name),
+            };
+            if let Some(index) = global {
+                if index < section_index || (state != FunctionState::Outside && name != "OpFunction") {
+                    return error(offset, ValidationErrorKind::OutOfOrder(name));
+                }
+                section_index = index;
+                if name != "OpFunction" {
+                    continue;
+                }
+            }
+
+            // Function structure
+            state = match (name, state) {
+                ("OpFunction", FunctionState::Outside) => {
+                    function = inst.operands.result.unwrap();
+                    FunctionState::Parameters
+                }
+                ("OpFunction", _) => return error(offset, ValidationErrorKind::OutOfOrder(name)),
+                ("OpFunctionParameter", FunctionState::Parameters) => state,
+                ("OpLabel", FunctionState::Parameters | FunctionState::BetweenBlocks) => FunctionState::InBlock {
+                    first: state == FunctionState::Parameters,
+                    variables: state == FunctionState::Parameters,
+                },
+                (
+                    "OpLabel" | "OpFunctionEnd",
+                    FunctionState::InBlock {
+                        ..
+                    },
+                ) => {
+                    return error(offset, ValidationErrorKind::MissingTerminator);
+                }
+                ("OpFunctionEnd", FunctionState::Parameters | FunctionState::BetweenBlocks) => FunctionState::Outside,
+                (
+                    "OpVariable",
+                    FunctionState::InBlock {
+                        first: true,
+                        variables: true,
+                    },
+                ) => state,
+                (
+                    _,
+                    FunctionState::InBlock {
+                        first,
+                        ..
+                    },
+                ) if name != "OpVariable" => {
+                    if is_terminator(name) {
+                        FunctionState::BetweenBlocks
+                    } else {
+                        FunctionState::InBlock {
+                            first,
+                            variables: false,
+                        }
+                    }
+                }
+                (_, FunctionState::BetweenBlocks) => return error(offset, ValidationErrorKind::OutsideBlock(name)),
+                _ => return error(offset, ValidationErrorKind::OutOfOrder(name)),
+            };
+            let uses = self.function_uses.entry(function).or_default();
+            uses.extend(inst.operands.ids.iter().copied());
+        }
+
+        if state != FunctionState::Outside {
+            return error(len, ValidationErrorKind::UnterminatedFunction);
+        }
+        if memory_models == 0 {
+            return error(len, ValidationErrorKind::MissingMemoryModel);
+        }
+        if !has_entry_point && !has_linkage {
+            return error(len, ValidationErrorKind::MissingEntryPoint);
+        }
+        Ok(())
+    }
+
+    fn opcode(&self, id: u32) -> &'static str {
+        self.defs.get(&id).map_or("", |def| def.opcode)
+    }
+
+    fn def_words(&self, id: u32) -> &'a [u32] {
+        self.defs.get(&id).map_or(&[], |def| def.words)
+    }
+
+    fn type_of(&self, id: u32) -> Option<u32> {
+        self.defs.get(&id).and_then(|def| def.result_type)
+    }
+
+    fn is_type(&self, id: u32) -> bool {
+        self.opcode(id).starts_with("OpType")
+    }
+
+    /// Words of a literal of the given numeric type.
+    fn literal_words(&self, ty: u32) -> usize {
+        match (self.opcode(ty), self.def_words(ty).get(1)) {
+            ("OpTypeInt" | "OpTypeFloat", Some(&width)) => (width as usize).div_ceil(32).max(1),
+            _ => 1,
+        }
+    }
+
+    /// Scalar type opcode and component count of a scalar or vector type.
+    fn components(&self, ty: u32) -> Option<(&'static str, u32)> {
+        match self.opcode(ty) {
+            "OpTypeVector" => {
+                let words = self.def_words(ty);
+                Some((self.opcode(words[1]), words[2]))
+            }
+            opcode @ ("OpTypeInt" | "OpTypeFloat" | "OpTypeBool") => Some((opcode, 1)),
+            _ => None,
+        }
+    }
+
+    /// Storage class and pointee of a pointer type.
+    fn pointer(&self, ty: u32) -> Option<(u32, u32)> {
+        let words = self.def_words(ty);
+        (self.opcode(ty) == "OpTypePointer").then(|| (words[1], words[2]))
+    }
+
+    fn expect(&self, id: u32, opcodes: &[&str], expected: &'static str) -> Result<(), ValidationErrorKind> {
+        if opcodes.contains(&self.opcode(id)) {
+            Ok(())
+        } else {
+            Err(ValidationErrorKind::WrongType {
+                id,
+                expected,
+            })
+        }
+    }
+
+    fn expect_type(&self, id: u32) -> Result<(), ValidationErrorKind> {
+        if self.is_type(id) {
+            Ok(())
+        } else {
+            Err(ValidationErrorKind::NotAType(id))
+        }
+    }
+
+    fn expect_value_of(&self, id: u32, ty: u32, expected: &'static str) -> Result<(), ValidationErrorKind> {
+        if self.type_of(id) == Some(ty) {
+            Ok(())
+        } else {
+            Err(ValidationErrorKind::WrongType {
+                id,
+                expected,
+            })
+        }
+    }
+
+    /// Checks the types of the operands of common instructions.
+    fn types(&self) -> Result<(), ValidationError> {
+        let mut return_type = 0;
+        for inst in &self.instructions {
+            if inst.info.name == "OpFunction" {
+                return_type = inst.operands.result_type.unwrap();
+            }
+            self.check_types(inst, return_type).map_err(|kind| ValidationError {
+                offset: inst.offset,
+                kind,
+            })?;
+        }
+        Ok(())
+    }
+
+    fn check_types(&self, inst: &Inst, return_type: u32) -> Result<(), ValidationErrorKind> {
+        use ValidationErrorKind::*;
+        if let Some(ty) = inst.operands.result_type {
+            self.expect_type(ty)?;
+        }
+        let ty = inst.operands.result_type.unwrap_or(0);
+        let ids = inst.operands.ids.as_slice();
+        let w = inst.words;
+        match inst.info.name {
+            "OpTypeVector" => {
+                self.expect(w[1], &["OpTypeInt", "OpTypeFloat", "OpTypeBool"], "a scalar type")?;
+                let valid = match w[2] {
+                    2..=4 => true,
+                    8 | 16 => self.vector16,
+                    _ => false,
+                };
+                if !valid {
+                    return Err(InvalidOperand("Vectors need 2 to 4 components, or 8 or 16 with Vector16"));
+                }
+            }
+            "OpTypeMatrix" => {
+                if self.components(w[1]).is_none_or(|(scalar, count)| scalar != "OpTypeFloat" || count < 2) {
+                    return Err(WrongType {
+                        id: w[1],
+                        expected: "a float vector type",
+                    });
+                }
+                if !(2..=4).contains(&w[2]) {
+                    return Err(InvalidOperand("Matrices need 2 to 4 columns"));
+                }
+            }
+            "OpTypeArray" => {
+                self.expect_type(w[1])?;
+                let length_type = self.type_of(w[2]).unwrap_or(0);
+                if !self.opcode(w[2]).contains("Constant") || self.opcode(length_type) != "OpTypeInt" {
+                    return Err(WrongType {
+                        id: w[2],
+                        expected: "an integer constant",
+                    });
+                }
+            }
+            "OpTypeRuntimeArray" | "OpTypeStruct" | "OpTypeFunction" | "OpTypePointer" => {
+                ids.iter().try_for_each(|&id| self.expect_type(id))?;
+            }
+            "OpConstant" | "OpSpecConstant" => {
+                self.expect(ty, &["OpTypeInt", "OpTypeFloat"], "a numeric scalar type")?
+            }
+            "OpConstantTrue" | "OpConstantFalse" | "OpSpecConstantTrue" | "OpSpecConstantFalse" => {
+                self.expect(ty, &["OpTypeBool"], "a boolean type")?
+            }
+            "OpConstantComposite" | "OpSpecConstantComposite" => {
+                self.expect(ty, &["OpTypeVector", "OpTypeMatrix", "OpTypeArray", "OpTypeStruct"], "a composite type")?
+            }
+            "OpVariable" => {
+                let (storage, _) = self.pointer(ty).ok_or(WrongType {
+                    id: ty,
+                    expected: "a pointer type",
+                })?;
+                if storage != w[2] {
+                    return Err(InvalidOperand("Storage class of OpVariable doesn't match its pointer type"));
+                }
+            }
+            "OpLoad" => {
+                let (_, pointee) = self.pointer(self.type_of(w[2]).unwrap_or(0)).ok_or(WrongType {
+                    id: w[2],
+                    expected: "a pointer",
+                })?;
+                if pointee != ty {
+                    return Err(WrongType {
+                        id: ty,
+                        expected: "the type pointed to by the loaded pointer",
+                    });
+                }
+            }
+            "OpStore" => {
+                let (_, pointee) = self.pointer(self.type_of(w[0]).unwrap_or(0)).ok_or(WrongType {
+                    id: w[0],
+                    expected: "a pointer",
+                })?;
+                self.expect_value_of(w[1], pointee, "a value of the type pointed to")?;
+            }
+            "OpAccessChain" | "OpInBoundsAccessChain" => {
+                self.expect(ty, &["OpTypePointer"], "a pointer type")?;
+                if self.pointer(self.type_of(w[2]).unwrap_or(0)).is_none() {
+                    return Err(WrongType {
+                        id: w[2],
+                        expected: "a pointer",
+                    });
+                }
+            }
+            "OpFunction" => {
+                self.expect(w[3], &["OpTypeFunction"], "a function type")?;
+                if self.def_words(w[3])[1] != ty {
+                    return Err(WrongType {
+                        id: ty,
+                        expected: "the return type of the function type",
+                    });
+                }
+            }
+            "OpFunctionCall" => {
+                self.expect(w[2], &["OpFunction"], "a function")?;
+                if self.type_of(w[2]) != Some(ty) {
+                    return Err(WrongType {
+                        id: ty,
+                        expected: "the return type of the called function",
+                    });
+                }
+            }
+            "OpReturn" if self.opcode(return_type) != "OpTypeVoid" => {
+                return Err(InvalidOperand("OpReturn in a function returning a value"))
+            }
+            "OpReturnValue" => self.expect_value_of(w[0], return_type, "a value of the return type of the function")?,
+            "OpFAdd" | "OpFSub" | "OpFMul" | "OpFDiv" | "OpFRem" | "OpFMod" | "OpFNegate" => {
+                if self.components(ty).is_none_or(|(scalar, _)| scalar != "OpTypeFloat") {
+                    return Err(WrongType {
+                        id: ty,
+                        expected: "a float scalar or vector type",
+                    });
+                }
+                ids.iter().try_for_each(|&id| self.expect_value_of(id, ty, "a value of the result type"))?;
+            }
+            "OpIAdd" | "OpISub" | "OpIMul" | "OpUDiv" | "OpSDiv" | "OpUMod" | "OpSRem" | "OpSMod" | "OpSNegate" => {
+                let Some(("OpTypeInt", count)) = self.components(ty) else {
+                    return Err(WrongType {
+                        id: ty,
+                        expected: "an integer scalar or vector type",
+                    });
+                };
+                for &id in ids {
+                    if self.components(self.type_of(id).unwrap_or(0)) != Some(("OpTypeInt", count)) {
+                        return Err(WrongType {
+                            id,
+                            expected: "an integer value with as many components as the result",
+                        });
+                    }
+                }
+            }
+            "OpVectorTimesScalar" => {
+                let Some(("OpTypeFloat", 2..)) = self.components(ty) else {
+                    return Err(WrongType {
+                        id: ty,
+                        expected: "a float vector type",
+                    });
+                };
+                self.expect_value_of(w[2], ty, "a value of the result type")?;
+                self.expect_value_of(w[3], self.def_words(ty)[1], "a value of the component type of the result")?;
+            }
+            "OpBranch" | "OpSelectionMerge" => self.expect(w[0], &["OpLabel"], "a label")?,
+            "OpLoopMerge" => {
+                self.expect(w[0], &["OpLabel"], "a label")?;
+                self.expect(w[1], &["OpLabel"], "a label")?;
+            }
+            "OpBranchConditional" => {
+                if self.components(self.type_of(w[0]).unwrap_or(0)) != Some(("OpTypeBool", 1)) {
+                    return Err(WrongType {
+                        id: w[0],
+                        expected: "a boolean value",
+                    });
+                }
+                self.expect(w[1], &["OpLabel"], "a label")?;
+                self.expect(w[2], &["OpLabel"], "a label")?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Checks entry points, their interfaces and execution modes.
+    fn entry_points(&self) -> Result<(), ValidationError> {
+        let mut entry_points = vec![];
+        let mut names = HashSet::new();
+        for inst in self.instructions.iter().filter(|inst| inst.info.name == "OpEntryPoint") {
+            let (model, function) = (inst.words[0], inst.words[1]);
+            let (name, _) = decode_string(&inst.words[2..]).unwrap();
+            if self.opcode(function) != "OpFunction" {
+                return error(inst.offset, ValidationErrorKind::EntryPointNotFunction(function));
+            }
+            let function_type = self.def_words(self.def_words(function)[3]);
+            if self.opcode(function_type[1]) != "OpTypeVoid" || function_type.len() > 2 {
+                return error(
+                    inst.offset,
+                    ValidationErrorKind::WrongType {
+                        id: function,
+                        expected: "a function returning void without parameters",
+                    },
+                );
+            }
+            if !names.insert((model, name.clone())) {
+                return error(inst.offset, ValidationErrorKind::DuplicateEntryPoint(name));
+            }
+            let interface = inst.operands.ids[1..].to_vec();
+            for &id in &interface {
+                let words = self.def_words(id);
+                if self.opcode(id) != "OpVariable" || !matches!(words[2], STORAGE_INPUT | STORAGE_OUTPUT) {
+                    return error(inst.offset, ValidationErrorKind::InvalidInterface(id));
+                }
+            }
+            entry_points.push(EntryPoint {
+                offset: inst.offset,
+                function,
+                interface,
+            });
+        }
+
+        for inst in self.instructions.iter().filter(|inst| inst.info.name.starts_with("OpExecutionMode")) {
+            let target = inst.words[0];
+            if !entry_points.iter().any(|e| e.function == target) {
+                return error(inst.offset, ValidationErrorKind::ExecutionModeTarget(target));
+            }
+        }
+
+        // Every Input or Output variable used by an entry point or the functions it calls is in its interface
+        for entry_point in &entry_points {
+            let mut visited = HashSet::from([entry_point.function]);
+            let mut stack = vec![entry_point.function];
+            let mut used = vec![];
+            while let Some(function) = stack.pop() {
+                let Some(uses) = self.function_uses.get(&function) else {
+                    continue;
+                };
+                let mut uses: Vec<u32> = uses.iter().copied().collect();
+                uses.sort_unstable();
+                for id in uses {
+                    match self.opcode(id) {
+                        "OpFunction" if visited.insert(id) => stack.push(id),
+                        "OpVariable" if matches!(self.def_words(id)[2], STORAGE_INPUT | STORAGE_OUTPUT) => {
+                            used.push(id)
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            used.sort_unstable();
+            if let Some(&variable) = used.iter().find(|id| !entry_point.interface.contains(id)) {
+                return error(
+                    entry_point.offset,
+                    ValidationErrorKind::MissingInterface {
+                        entry_point: entry_point.function,
+                        variable,
+                    },
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spirv_as::assemble_words;
+    use ValidationErrorKind::*;
+
+    const SHADER: &str = r#"
+               OpCapability Shader
+          %1 = OpExtInstImport "GLSL.std.450"
+               OpMemoryModel Logical GLSL450
+               OpEntryPoint Fragment %main "main" %color %uv
+               OpExecutionMode %main OriginUpperLeft
+               OpName %main "main"
+               OpDecorate %color Location 0
+               OpDecorate %uv Location 0
+       %void = OpTypeVoid
+         %fn = OpTypeFunction %void
+      %float = OpTypeFloat 32
+    %v2float = OpTypeVector %float 2
+    %v4float = OpTypeVector %float 4
+  %ptr_input = OpTypePointer Input %v2float
+ %ptr_output = OpTypePointer Output %v4float
+%ptr_function = OpTypePointer Function %float
+       %bool = OpTypeBool
+         %uv = OpVariable %ptr_input Input
+      %color = OpVariable %ptr_output Output
+    %float_1 = OpConstant %float 1
+      %fn_v2 = OpTypeFunction %v2float
+    %load_uv = OpFunction %v2float None %fn_v2
+         %10 = OpLabel
+         %11 = OpLoad %v2float %uv
+               OpReturnValue %11
+               OpFunctionEnd
+       %main = OpFunction %void None %fn
+         %12 = OpLabel
+          %x = OpVariable %ptr_function Function
+         %13 = OpFunctionCall %v2float %load_uv
+         %14 = OpVectorTimesScalar %v2float %13 %float_1
+         %15 = OpCompositeExtract %float %14 0
+         %16 = OpFAdd %float %15 %float_1
+               OpStore %x %16
+       %cond = OpFOrdLessThan %bool %16 %float_1
+               OpSelectionMerge %18 None
+               OpBranchConditional %cond %17 %18
+         %17 = OpLabel
+               OpBranch %18
+         %18 = OpLabel
+         %19 = OpCompositeConstruct %v4float %15 %16 %15 %16
+               OpStore %color %19
+               OpReturn
+               OpFunctionEnd
+"#;
+
+    fn edit(from: &str, to: &str) -> Vec<u32> {
+        assert!(SHADER.contains(from), "{}", from);
+        assemble_words(&SHADER.replacen(from, to, 1)).unwrap()
+    }
+
+    fn kind(words: &[u32]) -> ValidationErrorKind {
+        validate(words).unwrap_err().kind
+    }
+
+    /// Offset of the first instruction with the given opcode.
+    fn offset_of(words: &[u32], opcode: u32) -> usize {
+        split_module(words).unwrap().iter().find(|(_, inst)| inst[0] & 0xffff == opcode).unwrap().0
+    }
+
+    #[test]
+    fn valid_module() {
+        let words = assemble_words(SHADER).unwrap();
+        assert_eq!(validate(&words), Ok(()));
+        let bytes: Vec<u8> = words.iter().flat_map(|w| w.to_be_bytes()).collect();
+        assert_eq!(validate_bytes(&bytes), Ok(()));
+        let module = ShaderModule::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(module.validate(), Ok(()));
+
+        for entry in std::fs::read_dir("assets/shaders").unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_some_and(|ext| ext == "spv") {
+                let bytes = std::fs::read(&path).unwrap();
+                validate_bytes(&bytes).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+            }
+        }
+    }
+
+    #[test]
+    fn structure() {
+        let words = assemble_words(SHADER).unwrap();
+        let error_at = |words: &[u32]| {
+            let bytes: Vec<u8> = words.iter().flat_map(|w| w.to_le_bytes()).collect();
+            let e = ShaderModule::try_from(bytes.as_slice()).unwrap_err();
+            assert_eq!(validate(words), Err(e.clone()));
+            e
+        };
+
+        let mut bad = words.clone();
+        bad[0] = 0x12345678;
+        assert_eq!(error_at(&bad).kind, InvalidMagic(0x12345678));
+        let mut bad = words.clone();
+        bad[1] = 0x0002_0000;
+        assert_eq!(error_at(&bad).kind, UnsupportedVersion(0x0002_0000));
+        assert_eq!(
+            ShaderModule::try_from(&[3u8, 2, 0x23, 7, 0][..]).unwrap_err(),
+            ValidationError {
+                offset: 1,
+                kind: InvalidLength,
+            }
+        );
+
+        // OpFunctionEnd claiming 2 words
+        let mut bad = words.clone();
+        *bad.last_mut().unwrap() = 2 << 16 | 56;
+        assert_eq!(
+            error_at(&bad),
+            ValidationError {
+                offset: words.len() - 1,
+                kind: Truncated,
+            }
+        );
+
+        // OpTypeFloat without its width
+        let offset = offset_of(&words, 22);
+        let mut bad = words.clone();
+        bad[offset] = 2 << 16 | 22;
+        bad.remove(offset + 2);
+        assert_eq!(
+            error_at(&bad),
+            ValidationError {
+                offset,
+                kind: MissingOperands("OpTypeFloat"),
+            }
+        );
+
+        // %color (3) in OpEntryPoint is the first id beyond the bound
+        let mut bad = words.clone();
+        bad[3] = 3;
+        let error = validate(&bad).unwrap_err();
+        let offset = offset_of(&words, 15);
+        assert_eq!(
+            error,
+            ValidationError {
+                offset,
+                kind: IdOutOfBounds {
+                    id: 3,
+                    bound: 3,
+                },
+            }
+        );
+        assert_eq!(error.to_string(), format!("Id %3 is out of bounds (bound is 3) at word {}", offset));
+
+        // OpTypeFloat with an extra word
+        let offset = offset_of(&words, 22);
+        let mut bad = words.clone();
+        bad[offset] = 4 << 16 | 22;
+        bad.insert(offset + 3, 0);
+        assert_eq!(
+            error_at(&bad),
+            ValidationError {
+                offset,
+                kind: ExtraOperands("OpTypeFloat"),
+            }
+        );
+        assert_eq!(kind(&edit("Fragment %main", "99 %main")), InvalidEnumValue(EnumKind::ExecutionModel, 99));
+
+        // OpName "\xffain", rejected before decoding the instruction
+        let offset = offset_of(&words, 5);
+        let mut bad = words.clone();
+        bad[offset + 2] = u32::from_le_bytes([0xFF, b'a', b'i', b'n']);
+        assert_eq!(
+            error_at(&bad),
+            ValidationError {
+                offset,
+                kind: InvalidUtf8("OpName"),
+            }
+        );
+    }
+
+    #[test]
+    fn ids_and_types() {
+        assert!(matches!(
+            kind(&edit("%19 = OpCompositeConstruct", "%15 = OpCompositeConstruct")),
+            DuplicateDefinition(_)
+        ));
+        assert_eq!(kind(&edit("OpBranch %18", "OpBranch %99")), UndefinedId(99));
+
+        let words = edit("%16 = OpFAdd %float %15 %float_1", "%16 = OpFAdd %float %15 %14");
+        let error = validate(&words).unwrap_err();
+        assert!(
+            matches!(
+                error.kind,
+                WrongType {
+                    expected: "a value of the result type",
+                    ..
+                }
+            ),
+            "{}",
+            error
+        );
+        assert_eq!(error.offset, offset_of(&words, 129));
+
+        assert!(matches!(kind(&edit("OpStore %x %16", "OpStore %x %13")), WrongType { .. }));
+        assert!(matches!(kind(&edit("OpLoad %v2float %uv", "OpLoad %float %uv")), WrongType { .. }));
+        assert!(matches!(kind(&edit("OpLoad %v2float %uv", "OpLoad %v2float %float_1")), WrongType { .. }));
+        assert!(matches!(kind(&edit("OpTypePointer Input %v2float", "OpTypePointer Input %float_1")), NotAType(_)));
+        for count in ["1", "8", "0x20000002"] {
+            assert_eq!(
+                kind(&edit("OpTypeVector %float 2", &format!("OpTypeVector %float {}", count))),
+                InvalidOperand("Vectors need 2 to 4 components, or 8 or 16 with Vector16")
+            );
+        }
+        let vector16 = SHADER
+            .replacen("OpCapability Shader", "OpCapability Shader\nOpCapability Vector16", 1)
+            .replacen("%bool = OpTypeBool", "%bool = OpTypeBool\n%v8float = OpTypeVector %float 8", 1);
+        assert_eq!(validate(&assemble_words(&vector16).unwrap()), Ok(()));
+        let words = edit("%bool = OpTypeBool", "%bool = OpTypeBool\n%m5 = OpTypeMatrix %v2float 5");
+        assert_eq!(kind(&words), InvalidOperand("Matrices need 2 to 4 columns"));
+        assert!(matches!(
+            kind(&edit("%uv = OpVariable %ptr_input Input", "%uv = OpVariable %ptr_input Output")),
+            InvalidOperand(_)
+        ));
+        assert!(matches!(kind(&edit("OpBranchConditional %cond", "OpBranchConditional %16")), WrongType { .. }));
+        assert!(matches!(
+            kind(&edit("OpBranch %18", "OpBranch %float_1")),
+            WrongType {
+                expected: "a label",
+                ..
+            }
+        ));
+        assert!(matches!(
+            kind(&edit("OpFunctionCall %v2float %load_uv", "OpFunctionCall %v2float %main")),
+            WrongType { .. }
+        ));
+        assert_eq!(
+            kind(&edit("OpReturnValue %11", "OpReturn")),
+            InvalidOperand("OpReturn in a function returning a value")
+        );
+    }
+
+    #[test]
+    fn layout() {
+        let words = edit("               OpMemoryModel Logical GLSL450\n", "");
+        assert_eq!(
+            validate(&words),
+            Err(ValidationError {
+                offset: words.len(),
+                kind: MissingMemoryModel,
+            })
+        );
+        assert_eq!(kind(&edit("OpName %main \"main\"", "OpMemoryModel Logical GLSL450")), DuplicateMemoryModel);
+        assert_eq!(kind(&edit("OpName %main \"main\"", "OpCapability Shader")), OutOfOrder("OpCapability"));
+        assert_eq!(
+            kind(&edit("%bool = OpTypeBool", "OpDecorate %uv Flat\n%bool = OpTypeBool")),
+            OutOfOrder("OpDecorate")
+        );
+        assert_eq!(
+            kind(&edit(
+                "%12 = OpLabel\n          %x = OpVariable %ptr_function Function",
+                "%x = OpVariable %ptr_function Function\n%12 = OpLabel"
+            )),
+            OutOfOrder("OpVariable")
+        );
+        assert_eq!(
+            kind(&edit("%12 = OpLabel\n          %x = OpVariable %ptr_function Function\n         %13 = OpFunctionCall %v2float %load_uv", "%12 = OpLabel\n%13 = OpFunctionCall %v2float %load_uv\n%x = OpVariable %ptr_function Function")),
+            OutOfOrder("OpVariable")
+        );
+        assert_eq!(
+            kind(&edit("%x = OpVariable %ptr_function Function", "%x = OpVariable %ptr_function Private")),
+            InvalidOperand("Variables in functions must use the Function storage class")
+        );
+        assert_eq!(
+            kind(&edit("%bool = OpTypeBool", "%bool = OpTypeBool\n%c = OpConstant %float 2\n%d = OpFAdd %float %c %c")),
+            OutOfOrder("OpFAdd")
+        );
+        assert_eq!(kind(&edit("               OpBranch %18\n", "")), MissingTerminator);
+        assert_eq!(
+            kind(&edit("%17 = OpLabel", "%20 = OpLabel\nOpBranch %17\nOpReturn\n%17 = OpLabel")),
+            OutsideBlock("OpReturn")
+        );
+        assert_eq!(
+            kind(&edit("OpReturnValue %11\n               OpFunctionEnd", "OpReturnValue %11")),
+            OutOfOrder("OpFunction")
+        );
+        let words = assemble_words(SHADER.trim_end().strip_suffix("OpFunctionEnd").unwrap()).unwrap();
+        assert_eq!(kind(&words), UnterminatedFunction);
+    }
+
+    #[test]
+    fn entry_points() {
+        let words = edit("%main \"main\" %color %uv", "%main \"main\" %color");
+        let error = validate(&words).unwrap_err();
+        assert!(matches!(error.kind, MissingInterface { .. }), "{}", error);
+        assert_eq!(error.offset, offset_of(&words, 15));
+        // Ids are allocated in order of first appearance: %1, %main, %color, %uv
+        assert_eq!(
+            error.kind,
+            MissingInterface {
+                entry_point: 2,
+                variable: 4,
+            }
+        );
+
+        assert!(matches!(
+            kind(&edit("%main \"main\" %color %uv", "%main \"main\" %color %uv %float_1")),
+            InvalidInterface(_)
+        ));
+        assert!(matches!(kind(&edit("%main \"main\"", "%float_1 \"main\"")), EntryPointNotFunction(_)));
+        assert!(matches!(kind(&edit("OpExecutionMode %main", "OpExecutionMode %load_uv")), ExecutionModeTarget(_)));
+        assert!(matches!(kind(&edit("%main \"main\"", "%load_uv \"main\"")), WrongType { .. }));
+        assert_eq!(
+            kind(&edit("OpExecutionMode", "OpEntryPoint Fragment %main \"main\" %color %uv\nOpExecutionMode")),
+            DuplicateEntryPoint("main".to_string())
+        );
+        let without_entry_point = SHADER.replacen(
+            "OpEntryPoint Fragment %main \"main\" %color %uv\n               OpExecutionMode %main OriginUpperLeft",
+            "",
+            1,
+        );
+        assert_eq!(kind(&assemble_words(&without_entry_point).unwrap()), MissingEntryPoint);
+        // Libraries don't need an entry point
+        let linkage =
+            assemble_words(&without_entry_point.replacen("OpCapability Shader", "OpCapability Linkage", 1)).unwrap();
+        assert_eq!(validate(&linkage), Ok(()));
+    }
+}
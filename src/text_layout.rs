@@ -0,0 +1,418 @@
+use crate::glyph::{Font, FontSet};
+use crate::glyph_sdf::SdfAtlas;
+use crate::math::Rect;
+use crate::unicode::graphemes;
+
+use std::ops::Range;
+
+// Text layout:
+// Places the characters of a string with the advances and kerning pairs of a font, breaks the text into lines (at
+// '\n' and, given a maximum width, between words) and aligns every line. Positions are in pixels for a text size in
+// pixels per em, relative to the top-left corner of the text box with y down. The first baseline is `ascent` below
+// the top and the following ones are `line_height` apart.
+// Text is processed by grapheme clusters: a line is never broken inside a cluster and the combining marks of a cluster
+// are placed after its base character, where fonts expect them. Words longer than the maximum width are broken between
+// clusters. Whitespace at the end of a line doesn't count in its width.
+
+/// Metrics of a font needed for layout, in ems.
+pub trait LayoutFont {
+    /// Advance width of a character, 0 for characters that can't be rendered
+    fn advance(&self, c: char) -> f32;
+    /// Adjustment of the advance of `left` when followed by `right`
+    fn kerning(&self, left: char, right: char) -> f32;
+    /// Ascent, descent (negative below the baseline) and line gap
+    fn line_metrics(&self) -> (f32, f32, f32);
+}
+
+impl LayoutFont for Font {
+    // Missing characters are rendered with the missing glyph
+    fn advance(&self, c: char) -> f32 {
+        self.h_metrics(self.glyph_index(c).unwrap_or(0)).advance_width as f32 / self.units_per_em as f32
+    }
+
+    fn kerning(&self, left: char, right: char) -> f32 {
+        match (self.glyph_index(left), self.glyph_index(right)) {
+            (Some(left), Some(right)) => Font::kerning(self, left, right) as f32 / self.units_per_em as f32,
+            _ => 0.0,
+        }
+    }
+
+    fn line_metrics(&self) -> (f32, f32, f32) {
+        let units_per_em = self.units_per_em as f32;
+        (self.ascent as f32 / units_per_em, self.descent as f32 / units_per_em, self.line_gap as f32 / units_per_em)
+    }
+}
+
+impl LayoutFont for FontSet<'_> {
+    fn advance(&self, c: char) -> f32 {
+        let (font, glyph) = self.lookup(c);
+        let font = self.fonts[font];
+        font.h_metrics(glyph).advance_width as f32 / font.units_per_em as f32
+    }
+
+    // Only glyphs of the same font are kerned
+    fn kerning(&self, left: char, right: char) -> f32 {
+        match (self.find(left), self.find(right)) {
+            (Some((left_font, left)), Some((right_font, right))) if left_font == right_font => {
+                let font = self.fonts[left_font];
+                font.kerning(left, right) as f32 / font.units_per_em as f32
+            }
+            _ => 0.0,
+        }
+    }
+
+    fn line_metrics(&self) -> (f32, f32, f32) {
+        self.fonts[0].line_metrics()
+    }
+}
+
+impl LayoutFont for SdfAtlas {
+    fn advance(&self, c: char) -> f32 {
+        self.glyph(c).map_or(0.0, |glyph| glyph.advance)
+    }
+
+    fn kerning(&self, left: char, right: char) -> f32 {
+        self.kerning.get(&(left, right)).copied().unwrap_or(0.0)
+    }
+
+    fn line_metrics(&self) -> (f32, f32, f32) {
+        (self.ascent, self.descent, self.line_gap)
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    #[default]
+    Left,
+    Center,
+    Right,
+    /// Stretches the spaces between words so that lines fill the width, except the last line of a paragraph
+    Justify,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LayoutOptions {
+    /// Text size in pixels per em
+    pub size: f32,
+    /// Width to break the lines at and to align them in, by default the width of the longest line
+    pub max_width: Option<f32>,
+    pub align: Align,
+    /// Multiplier of the line height of the font
+    pub line_spacing: f32,
+}
+
+impl Default for LayoutOptions {
+    fn default() -> Self {
+        Self {
+            size: 16.0,
+            max_width: None,
+            align: Align::Left,
+            line_spacing: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PositionedGlyph {
+    pub c: char,
+    /// Byte offset of the character in the text
+    pub index: usize,
+    pub line: usize,
+    /// Pen position on the baseline
+    pub x: f32,
+    pub y: f32,
+    /// Distance to the next character, kerning and justification included
+    pub advance: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayoutLine {
+    /// Byte range of the line in the text, without the line break
+    pub text: Range<usize>,
+    /// Range of the line in `TextLayout::glyphs`
+    pub glyphs: Range<usize>,
+    pub x: f32,
+    pub baseline: f32,
+    /// Width without the trailing whitespace
+    pub width: f32,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct TextLayout {
+    pub glyphs: Vec<PositionedGlyph>,
+    pub lines: Vec<LayoutLine>,
+    /// Text size in pixels per em
+    pub size: f32,
+    /// Line metrics in pixels
+    pub ascent: f32,
+    pub descent: f32,
+    pub line_height: f32,
+}
+
+/// Byte offset, grapheme cluster and advance in pixels
+type Item<'a> = (usize, &'a str, f32);
+
+fn is_space(item: &Item) -> bool {
+    item.1.starts_with(char::is_whitespace)
+}
+
+fn base(cluster: &str) -> char {
+    cluster.chars().next().unwrap()
+}
+
+impl TextLayout {
+    pub fn new<F: LayoutFont + ?Sized>(font: &F, text: &str, options: &LayoutOptions) -> Self {
+        let size = options.size;
+        let (ascent, descent, line_gap) = font.line_metrics();
+        let mut layout = Self {
+            size,
+            ascent: ascent * size,
+            descent: descent * size,
+            line_height: (ascent - descent + line_gap) * size * options.line_spacing,
+            ..Default::default()
+        };
+
+        // Break the paragraphs into lines: (characters with their advances, byte offset, ends a paragraph)
+        let mut lines: Vec<(Vec<Item>, usize, bool)> = vec![];
+        let mut paragraph_start = 0;
+        for paragraph in text.split('\n') {
+            let mut clusters: Vec<(usize, &str)> = vec![];
+            for cluster in graphemes(paragraph) {
+                let index = clusters.last().map_or(paragraph_start, |&(index, previous)| index + previous.len());
+                clusters.push((index, cluster));
+            }
+            let items: Vec<Item> = (0..clusters.len())
+                .map(|i| {
+                    let (index, cluster) = clusters[i];
+                    let advance: f32 = cluster.chars().map(|c| font.advance(c)).sum();
+                    let kerning = clusters.get(i + 1).map_or(0.0, |&(_, next)| font.kerning(base(cluster), base(next)));
+                    (index, cluster, (advance + kerning) * size)
+                })
+                .collect();
+
+            let (mut start, mut width, mut break_at) = (0, 0.0, None);
+            for i in 0..items.len() {
+                let advance = items[i].2;
+                if !is_space(&items[i]) {
+                    if i > start && is_space(&items[i - 1]) {
+                        break_at = Some(i);
+                    }
+                    if options.max_width.is_some_and(|max_width| width + advance > max_width) && i > start {
+                        let end = break_at.unwrap_or(i);
+                        lines.push((items[start..end].to_vec(), items[start].0, false));
+                        start = end;
+                        width = items[start..i].iter().map(|item| item.2).sum();
+                        break_at = None;
+                    }
+                }
+                width += advance;
+            }
+            let text_start = items.get(start).map_or(paragraph_start, |item| item.0);
+            lines.push((items[start..].to_vec(), text_start, true));
+            paragraph_start += paragraph.len() + 1;
+        }
+
+        let visible_len = |items: &[Item]| items.len() - items.iter().rev().take_while(|item| is_space(item)).count();
+        let widths: Vec<f32> =
+            lines.iter().map(|(items, ..)| items[..visible_len(items)].iter().map(|item| item.2).sum()).collect();
+        let available = options.max_width.unwrap_or_else(|| widths.iter().copied().fold(0.0, f32::max));
+
+        for (line_index, ((items, text_start, paragraph_end), width)) in lines.into_iter().zip(widths).enumerate() {
+            let visible = visible_len(&items);
+            let spaces = items[..visible].iter().filter(|item| is_space(item)).count();
+            let (x, stretch) = match options.align {
+                Align::Left => (0.0, 0.0),
+                Align::Center => ((available - width) / 2.0, 0.0),
+                Align::Right => (available - width, 0.0),
+                Align::Justify if paragraph_end || spaces == 0 => (0.0, 0.0),
+                Align::Justify => (0.0, ((available - width) / spaces as f32).max(0.0)),
+            };
+            let width = width + stretch * spaces as f32;
+            let baseline = layout.ascent + line_index as f32 * layout.line_height;
+
+            let first_glyph = layout.glyphs.len();
+            let mut pen = x;
+            for (i, item) in items.iter().enumerate() {
+                let (index, cluster, mut advance) = *item;
+                if i < visible && is_space(item) {
+                    advance += stretch;
+                }
+                // The last character of the cluster takes the kerning and the justification
+                let mut chars = cluster.char_indices().peekable();
+                while let Some((offset, c)) = chars.next() {
+                    let own = if chars.peek().is_some() {
+                        font.advance(c) * size
+                    } else {
+                        advance
+                    };
+                    layout.glyphs.push(PositionedGlyph {
+                        c,
+                        index: index + offset,
+                        line: line_index,
+                        x: pen,
+                        y: baseline,
+                        advance: own,
+                    });
+                    pen += own;
+                    advance -= own;
+                }
+            }
+            let text_end = items.last().map_or(text_start, |&(index, cluster, _)| index + cluster.len());
+            layout.lines.push(LayoutLine {
+                text: text_start..text_end,
+                glyphs: first_glyph..layout.glyphs.len(),
+                x,
+                baseline,
+                width,
+            });
+        }
+        layout
+    }
+
+    /// Box around the lines, from the ascent of the first line to the descent of the last one.
+    pub fn bounds(&self) -> Rect {
+        if self.lines.is_empty() {
+            return Rect::default();
+        }
+        let left = self.lines.iter().map(|line| line.x).fold(f32::INFINITY, f32::min);
+        let right = self.lines.iter().map(|line| line.x + line.width).fold(f32::NEG_INFINITY, f32::max);
+        let bottom = self.lines.last().map_or(0.0, |line| line.baseline - self.descent);
+        Rect::offset_extent((left, 0.0), (right - left, bottom))
+    }
+}
+
+/// Bounding box of a text, see `TextLayout::bounds`.
+pub fn measure<F: LayoutFont + ?Sized>(font: &F, text: &str, options: &LayoutOptions) -> Rect {
+    TextLayout::new(font, text, options).bounds()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Letters are half an em wide, spaces a quarter, marks have no width and "AV" is kerned by a tenth.
+    struct TestFont;
+
+    impl LayoutFont for TestFont {
+        fn advance(&self, c: char) -> f32 {
+            if c == ' ' {
+                0.25
+            } else if crate::unicode::is_combining_mark(c) {
+                0.0
+            } else {
+                0.5
+            }
+        }
+
+        fn kerning(&self, left: char, right: char) -> f32 {
+            if (left, right) == ('A', 'V') {
+                -0.1
+            } else {
+                0.0
+            }
+        }
+
+        fn line_metrics(&self) -> (f32, f32, f32) {
+            (0.75, -0.25, 0.5)
+        }
+    }
+
+    fn options(max_width: Option<f32>, align: Align) -> LayoutOptions {
+        LayoutOptions {
+            size: 10.0,
+            max_width,
+            align,
+            ..Default::default()
+        }
+    }
+
+    fn line_texts<'a>(layout: &TextLayout, text: &'a str) -> Vec<&'a str> {
+        layout.lines.iter().map(|line| &text[line.text.clone()]).collect()
+    }
+
+    #[test]
+    fn single_line() {
+        let layout = TextLayout::new(&TestFont, "AVA", &options(None, Align::Left));
+        let xs: Vec<f32> = layout.glyphs.iter().map(|g| g.x).collect();
+        assert_eq!(xs, [0.0, 4.0, 9.0]);
+        assert!(layout.glyphs.iter().all(|g| g.y == 7.5 && g.line == 0));
+        assert_eq!((layout.ascent, layout.descent, layout.line_height), (7.5, -2.5, 15.0));
+        assert_eq!(
+            measure(&TestFont, "AVA", &options(None, Align::Left)),
+            Rect::offset_extent((0.0, 0.0), (14.0, 10.0))
+        );
+        // Trailing whitespace is not measured
+        assert_eq!(measure(&TestFont, "AB  ", &options(None, Align::Left)).extent.x, 10.0);
+        assert_eq!(measure(&TestFont, "", &options(None, Align::Left)), Rect::offset_extent((0.0, 0.0), (0.0, 10.0)));
+    }
+
+    #[test]
+    fn wrapping() {
+        let text = "ab cd efg\nh\n\nijklmnop";
+        let layout = TextLayout::new(&TestFont, text, &options(Some(26.0), Align::Left));
+        assert_eq!(line_texts(&layout, text), ["ab cd ", "efg", "h", "", "ijklm", "nop"]);
+        let widths: Vec<f32> = layout.lines.iter().map(|line| line.width).collect();
+        assert_eq!(widths, [22.5, 15.0, 5.0, 0.0, 25.0, 15.0]);
+        let baselines: Vec<f32> = layout.lines.iter().map(|line| line.baseline).collect();
+        assert_eq!(baselines, [7.5, 22.5, 37.5, 52.5, 67.5, 82.5]);
+        assert_eq!(layout.glyphs[layout.lines[1].glyphs.start].c, 'e');
+        assert_eq!(layout.glyphs.iter().filter(|g| g.line == 4).count(), 5);
+        assert_eq!(layout.bounds(), Rect::offset_extent((0.0, 0.0), (25.0, 85.0)));
+    }
+
+    #[test]
+    fn alignment() {
+        let text = "ab cd efg";
+        let x = |align| {
+            let layout = TextLayout::new(&TestFont, text, &options(Some(30.0), align));
+            layout.lines.iter().map(|line| line.x).collect::<Vec<f32>>()
+        };
+        assert_eq!(x(Align::Left), [0.0, 0.0]);
+        assert_eq!(x(Align::Center), [3.75, 7.5]);
+        assert_eq!(x(Align::Right), [7.5, 15.0]);
+
+        // The space of the first line takes the remaining width, the last line is not stretched
+        let layout = TextLayout::new(&TestFont, text, &options(Some(30.0), Align::Justify));
+        assert_eq!(layout.lines[0].width, 30.0);
+        assert_eq!(layout.glyphs[3].x, 20.0);
+        assert_eq!(layout.lines[1].width, 15.0);
+
+        // Without a maximum width lines are aligned with the longest one
+        let layout = TextLayout::new(&TestFont, "abcd\nab", &options(None, Align::Right));
+        assert_eq!(layout.lines[1].x, 10.0);
+        assert_eq!(layout.bounds().extent.x, 20.0);
+    }
+
+    #[test]
+    fn clusters() {
+        // The accent is placed after its letter and the kerning goes after the accent
+        let text = "A\u{30A}V";
+        let layout = TextLayout::new(&TestFont, text, &options(None, Align::Left));
+        let glyphs: Vec<(char, usize, f32, f32)> =
+            layout.glyphs.iter().map(|g| (g.c, g.index, g.x, g.advance)).collect();
+        assert_eq!(glyphs, [('A', 0, 0.0, 5.0), ('\u{30A}', 1, 5.0, -1.0), ('V', 3, 4.0, 5.0)]);
+
+        // Lines are not broken between a letter and its marks
+        let text = "ab\u{301}\u{302}c";
+        let layout = TextLayout::new(&TestFont, text, &options(Some(10.0), Align::Left));
+        assert_eq!(line_texts(&layout, text), ["ab\u{301}\u{302}", "c"]);
+        assert_eq!(layout.lines[0].glyphs, 0..4);
+    }
+
+    #[test]
+    #[ignore]
+    fn ttf() {
+        let font = Font::load("/usr/share/fonts/TTF/DejaVuSans.ttf")
+            .or_else(|_| Font::load("/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf"))
+            .unwrap();
+        let options = LayoutOptions::default();
+        let width = |text| measure(&font, text, &options).extent.x;
+        assert!(width("AV") < width("A") + width("V"));
+        let bounds = measure(&font, "Hello\nWorld", &options);
+        assert!(bounds.extent.y > 2.0 * options.size);
+        // Combining marks don't advance, missing characters take the width of the replacement character
+        assert_eq!(width("e\u{301}"), width("e"));
+        let fonts = FontSet::new(vec![&font]);
+        assert_eq!(measure(&fonts, "中", &options), measure(&fonts, "\u{FFFD}", &options));
+    }
+}
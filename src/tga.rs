@@ -0,0 +1,265 @@
+use crate::parsing::{get_bytes, get_u16_le};
+
+// TGA images:
+// Decodes color-mapped, true color and grayscale Truevision TGA files, uncompressed or run-length encoded, with 8,
+// 15/16, 24 or 32 bits per pixel and any origin, to RGBA8. Encodes RGBA8 pixels as run-length encoded 24-bit or
+// 32-bit true color with a TGA 2.0 footer.
+// The format has no magic number, not even TGA 2.0 files with their footer: `is_tga` validates the header.
+
+const HEADER_SIZE: usize = 18;
+const FOOTER_SIGNATURE: &[u8; 18] = b"TRUEVISION-XFILE.\0";
+const TYPE_COLOR_MAPPED: u8 = 1;
+const TYPE_TRUE_COLOR: u8 = 2;
+const TYPE_GRAY: u8 = 3;
+/// Added to the types above for the run-length encoded variants
+const TYPE_RLE: u8 = 8;
+/// Image descriptor bits
+const RIGHT_TO_LEFT: u8 = 0x10;
+const TOP_TO_BOTTOM: u8 = 0x20;
+
+/// Whether `bytes` starts with a plausible TGA header.
+pub fn is_tga(bytes: &[u8]) -> bool {
+    if bytes.len() < HEADER_SIZE {
+        return false;
+    }
+    let (color_map_type, image_type, depth) = (bytes[1], bytes[2], bytes[16]);
+    let color_map_depth = bytes[7];
+    let valid_color_map = match color_map_type {
+        0 => true,
+        1 => matches!(color_map_depth, 15 | 16 | 24 | 32),
+        _ => false,
+    };
+    let valid_type = match image_type & !TYPE_RLE {
+        TYPE_COLOR_MAPPED => color_map_type == 1 && depth == 8,
+        TYPE_TRUE_COLOR => matches!(depth, 15 | 16 | 24 | 32),
+        TYPE_GRAY => depth == 8,
+        _ => false,
+    };
+    valid_color_map
+        && valid_type
+        && get_u16_le(bytes, 12).is_ok_and(|w| w > 0)
+        && get_u16_le(bytes, 14).is_ok_and(|h| h > 0)
+}
+
+/// RGBA of a 15/16, 24 or 32-bit little endian BGR(A) value.
+fn color(p: &[u8], alpha: bool) -> [u8; 4] {
+    match p.len() {
+        2 => {
+            let v = u16::from_le_bytes([p[0], p[1]]) as usize;
+            let expand = |c: usize| ((c & 31) * 255 / 31) as u8;
+            let a = if alpha && v & 0x8000 == 0 {
+                0
+            } else {
+                255
+            };
+            [expand(v >> 10), expand(v >> 5), expand(v), a]
+        }
+        3 => [p[2], p[1], p[0], 255],
+        _ => [
+            p[2],
+            p[1],
+            p[0],
+            if alpha {
+                p[3]
+            } else {
+                255
+            },
+        ],
+    }
+}
+
+/// Decodes a TGA file, returns (RGBA8 pixels, width, height).
+pub fn decode_tga(bytes: &[u8]) -> Result<(Vec<u8>, usize, usize), String> {
+    if !is_tga(bytes) {
+        return Err(String::from("Not a TGA file"));
+    }
+    let (id_length, image_type, depth, descriptor) = (bytes[0] as usize, bytes[2], bytes[16], bytes[17]);
+    let (width, height) = (get_u16_le(bytes, 12)? as usize, get_u16_le(bytes, 14)? as usize);
+    let alpha = descriptor & 0x0F > 0;
+    let mut offset = HEADER_SIZE + id_length;
+
+    let mut palette = vec![];
+    if bytes[1] == 1 {
+        let (first, length) = (get_u16_le(bytes, 3)? as usize, get_u16_le(bytes, 5)? as usize);
+        let entry_size = (bytes[7] as usize).div_ceil(8);
+        let table = get_bytes(bytes, offset, length * entry_size).map_err(|_| "Truncated TGA color map")?;
+        palette = vec![[0; 4]; first];
+        palette.extend(table.chunks_exact(entry_size).map(|p| color(p, alpha)));
+        offset += length * entry_size;
+    }
+
+    let bytes_per_pixel = (depth as usize).div_ceil(8);
+    let pixel = |p: &[u8]| -> Result<[u8; 4], String> {
+        match image_type & !TYPE_RLE {
+            TYPE_COLOR_MAPPED => {
+                palette.get(p[0] as usize).copied().ok_or_else(|| format!("Invalid color index {}", p[0]))
+            }
+            TYPE_GRAY => Ok([p[0], p[0], p[0], 255]),
+            _ => Ok(color(p, alpha)),
+        }
+    };
+    // The header size isn't trusted for allocations before the data is known to hold the pixels
+    let count = width * height;
+    let mut decoded = vec![];
+    let truncated = || String::from("Truncated TGA pixel data");
+    if image_type & TYPE_RLE == 0 {
+        let data = get_bytes(bytes, offset, count * bytes_per_pixel).map_err(|_| truncated())?;
+        decoded = data.chunks_exact(bytes_per_pixel).map(pixel).collect::<Result<_, _>>()?;
+    } else {
+        // Packets: a header byte with the count - 1 in the low bits, then one pixel repeated (high bit set) or the
+        // count raw pixels. Packets may cross rows, the output grows with them.
+        while decoded.len() < count {
+            let header = *bytes.get(offset).ok_or_else(truncated)?;
+            let n = (header & 0x7F) as usize + 1;
+            offset += 1;
+            if header & 0x80 != 0 {
+                let p = pixel(get_bytes(bytes, offset, bytes_per_pixel).map_err(|_| truncated())?)?;
+                decoded.extend(std::iter::repeat_n(p, n.min(count - decoded.len())));
+                offset += bytes_per_pixel;
+            } else {
+                let data = get_bytes(bytes, offset, n * bytes_per_pixel).map_err(|_| truncated())?;
+                for p in data.chunks_exact(bytes_per_pixel).take(count - decoded.len()) {
+                    decoded.push(pixel(p)?);
+                }
+                offset += n * bytes_per_pixel;
+            }
+        }
+    }
+
+    // Stored bottom to top and left to right unless the descriptor says otherwise
+    let mut pixels = vec![0; count * 4];
+    for (i, p) in decoded.iter().enumerate() {
+        let (mut x, mut y) = (i % width, i / width);
+        if descriptor & RIGHT_TO_LEFT != 0 {
+            x = width - 1 - x;
+        }
+        if descriptor & TOP_TO_BOTTOM == 0 {
+            y = height - 1 - y;
+        }
+        pixels[(y * width + x) * 4..][..4].copy_from_slice(p);
+    }
+    Ok((pixels, width, height))
+}
+
+/// Encodes RGBA8 pixels as a top to bottom, run-length encoded TGA file, 24-bit if every pixel is opaque, 32-bit
+/// otherwise.
+pub fn encode_tga(pixels: &[u8], width: usize, height: usize) -> Vec<u8> {
+    assert_eq!(pixels.len(), width * height * 4);
+    assert!(width <= u16::MAX as usize && height <= u16::MAX as usize, "TGA images are at most 65535 pixels wide");
+    let opaque = pixels.chunks_exact(4).all(|p| p[3] == 255);
+    let (depth, alpha_bits) = if opaque {
+        (24, 0)
+    } else {
+        (32, 8)
+    };
+
+    let mut out = vec![0, 0, TYPE_TRUE_COLOR | TYPE_RLE, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    out.extend((width as u16).to_le_bytes());
+    out.extend((height as u16).to_le_bytes());
+    out.extend([depth, alpha_bits | TOP_TO_BOTTOM]);
+
+    let push = |out: &mut Vec<u8>, p: &[u8]| {
+        out.extend([p[2], p[1], p[0]]);
+        if !opaque {
+            out.push(p[3]);
+        }
+    };
+    // Packets don't cross rows, as the TGA 2.0 specification recommends
+    for row in pixels.chunks_exact(width.max(1) * 4) {
+        let row: Vec<&[u8]> = row.chunks_exact(4).collect();
+        let mut x = 0;
+        while x < row.len() {
+            let run = row[x..].iter().take(128).take_while(|&&p| p == row[x]).count();
+            if run > 1 {
+                out.push(0x80 | (run - 1) as u8);
+                push(&mut out, row[x]);
+                x += run;
+                continue;
+            }
+            // Raw pixels up to the next run of 2
+            let mut n = 1;
+            while x + n < row.len() && n < 128 && row[x + n] != row[x + n - 1] {
+                n += 1;
+            }
+            if x + n < row.len() && n > 1 && n < 128 {
+                n -= 1;
+            }
+            out.push((n - 1) as u8);
+            row[x..x + n].iter().for_each(|p| push(&mut out, p));
+            x += n;
+        }
+    }
+
+    // No extension nor developer area
+    out.extend([0; 8]);
+    out.extend(FOOTER_SIGNATURE);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let mut pixels: Vec<u8> = (0..200u8).flat_map(|i| [i / 30 * 40, i / 4, 9, 255]).collect();
+        let file = encode_tga(&pixels, 20, 10);
+        assert!(is_tga(&file));
+        assert_eq!((file[16], file.len() < 200 * 3), (24, true));
+        assert_eq!(decode_tga(&file).unwrap(), (pixels.clone(), 20, 10));
+
+        pixels[7] = 0;
+        let file = encode_tga(&pixels, 40, 5);
+        assert_eq!(file[16], 32);
+        assert_eq!(decode_tga(&file).unwrap(), (pixels, 40, 5));
+
+        let noise: Vec<u8> = (0..300u32).flat_map(|i| i.wrapping_mul(2654435761).to_le_bytes()).collect();
+        assert_eq!(decode_tga(&encode_tga(&noise, 150, 2)).unwrap(), (noise, 150, 2));
+    }
+
+    fn header(color_map: &[u8], image_type: u8, width: u16, height: u16, depth: u8, descriptor: u8) -> Vec<u8> {
+        let mut out = vec![0, !color_map.is_empty() as u8, image_type];
+        out.extend(color_map);
+        out.resize(12, 0);
+        out.extend(width.to_le_bytes());
+        out.extend(height.to_le_bytes());
+        out.extend([depth, descriptor]);
+        out
+    }
+
+    #[test]
+    fn formats() {
+        // Bottom-up grayscale
+        let file = [header(&[], TYPE_GRAY, 2, 2, 8, 0), vec![1, 2, 3, 4]].concat();
+        assert!(is_tga(&file));
+        let (pixels, ..) = decode_tga(&file).unwrap();
+        assert_eq!(pixels.chunks(4).map(|p| p[0]).collect::<Vec<_>>(), [3, 4, 1, 2]);
+
+        // Run-length encoded color map of 16-bit entries starting at index 1, right to left: a run of 2 then 1 raw
+        let color_map = [1, 0, 2, 0, 16];
+        let entries = [0x00, 0x7C, 0xE0, 0x83];
+        let data = [0x81, 1, 0x00, 2];
+        let file = [header(&color_map, TYPE_COLOR_MAPPED | TYPE_RLE, 3, 1, 8, 0x31), entries.to_vec(), data.to_vec()];
+        let (pixels, ..) = decode_tga(&file.concat()).unwrap();
+        assert_eq!(pixels, [0, 255, 0, 255, 255, 0, 0, 0, 255, 0, 0, 0]);
+
+        // 32-bit without alpha bits is opaque
+        let file = [header(&[], TYPE_TRUE_COLOR, 1, 1, 32, TOP_TO_BOTTOM), vec![1, 2, 3, 4]].concat();
+        assert_eq!(decode_tga(&file).unwrap().0, [3, 2, 1, 255]);
+        assert!(decode_tga(&file[..file.len() - 1]).unwrap_err().contains("Truncated"));
+
+        let index = [header(&color_map, TYPE_COLOR_MAPPED, 1, 1, 8, 0), entries.to_vec(), vec![3]].concat();
+        assert!(decode_tga(&index).unwrap_err().contains("color index"));
+        assert!(!is_tga(&header(&[], 4, 1, 1, 8, 0)));
+        assert!(!is_tga(&header(&[], TYPE_TRUE_COLOR, 0, 1, 24, 0)));
+        assert!(!is_tga(b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR"));
+
+        // Huge sizes with little data, and a footer doesn't make up for an invalid header
+        let huge = [header(&[], TYPE_TRUE_COLOR | TYPE_RLE, 0xFF05, 0x8002, 24, 0), vec![0xFF, 1, 2, 3, 0x00, 4]];
+        assert!(decode_tga(&huge.concat()).unwrap_err().contains("Truncated"));
+        let huge = [header(&[], TYPE_TRUE_COLOR, 0xFF05, 0x8002, 24, 0), vec![1, 2, 3]];
+        assert!(decode_tga(&huge.concat()).unwrap_err().contains("Truncated"));
+        let footer = [header(&[], TYPE_TRUE_COLOR, 1, 1, 0, 0), vec![0; 8], FOOTER_SIGNATURE.to_vec()].concat();
+        assert!(!is_tga(&footer) && decode_tga(&footer).is_err());
+    }
+}
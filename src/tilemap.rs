@@ -0,0 +1,546 @@
+use crate::atlas::SpriteSheet;
+use crate::color::{Color, WHITE};
+use crate::math::{Rect, Vec2};
+use crate::vk_util::{self, RenderCommand, FULL_UV};
+
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+
+// Tilemaps:
+// A `Grid` describes the geometry (size in tiles, tile size and world position of the top-left
+// corner) and does the grid <-> world conversions and the neighbor queries. A `Tilemap` adds layers
+// of tile ids on top of it, the ids index a `Tileset` that references sprites of an atlas.
+// Tile id 0 is always the empty tile, so id N is the (N-1)th entry of the tileset, this is the same
+// convention Tiled uses for its global tile ids (with a single tileset starting at 1).
+//
+// Layers are split in square chunks of CHUNK_SIZE tiles that keep a count of non-empty tiles, so
+// rendering only walks the chunks that overlap the view and skips the empty ones.
+
+pub type TileId = u32;
+pub const EMPTY_TILE: TileId = 0;
+pub const CHUNK_SIZE: usize = 16;
+/// Largest number of tiles of a map loaded from a .tmx file
+const MAX_TMX_TILES: usize = 1 << 24;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Grid {
+    pub width: usize,
+    pub height: usize,
+    pub tile_size: Vec2,
+    pub origin: Vec2, // top-left corner of the tile (0, 0)
+}
+
+impl Grid {
+    pub fn new<T1: Into<Vec2>, T2: Into<Vec2>>(width: usize, height: usize, tile_size: T1, origin: T2) -> Self {
+        Self {
+            width,
+            height,
+            tile_size: tile_size.into(),
+            origin: origin.into(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.width * self.height
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn idx(&self, col: usize, row: usize) -> usize {
+        debug_assert!(col < self.width && row < self.height);
+        row * self.width + col
+    }
+
+    /// (col, row) of a tile index.
+    pub fn pos(&self, idx: usize) -> (usize, usize) {
+        (idx % self.width, idx / self.width)
+    }
+
+    pub fn in_bounds(&self, col: isize, row: isize) -> bool {
+        col >= 0 && row >= 0 && (col as usize) < self.width && (row as usize) < self.height
+    }
+
+    /// World position of the top-left corner of a tile.
+    pub fn to_world(&self, col: usize, row: usize) -> Vec2 {
+        self.origin + Vec2::new(col as f32 * self.tile_size.x, row as f32 * self.tile_size.y)
+    }
+
+    pub fn tile_center(&self, col: usize, row: usize) -> Vec2 {
+        self.to_world(col, row) + self.tile_size * 0.5
+    }
+
+    pub fn tile_rect(&self, col: usize, row: usize) -> Rect {
+        Rect::offset_extent(self.to_world(col, row), self.tile_size)
+    }
+
+    /// (col, row) of the tile under a world position, None when outside the grid.
+    pub fn to_grid<T: Into<Vec2>>(&self, p: T) -> Option<(usize, usize)> {
+        let p = p.into() - self.origin;
+        let col = (p.x / self.tile_size.x).floor();
+        let row = (p.y / self.tile_size.y).floor();
+        if col < 0.0 || row < 0.0 || col >= self.width as f32 || row >= self.height as f32 {
+            return None;
+        }
+        Some((col as usize, row as usize))
+    }
+
+    fn neighbors(&self, col: usize, row: usize, offsets: &[(isize, isize)]) -> Vec<(usize, usize)> {
+        offsets
+            .iter()
+            .map(|(dx, dy)| (col as isize + dx, row as isize + dy))
+            .filter(|&(c, r)| self.in_bounds(c, r))
+            .map(|(c, r)| (c as usize, r as usize))
+            .collect()
+    }
+
+    /// Tiles sharing an edge with (col, row).
+    pub fn neighbors4(&self, col: usize, row: usize) -> Vec<(usize, usize)> {
+        self.neighbors(col, row, &[(0, -1), (-1, 0), (1, 0), (0, 1)])
+    }
+
+    /// Tiles sharing an edge or a corner with (col, row).
+    pub fn neighbors8(&self, col: usize, row: usize) -> Vec<(usize, usize)> {
+        self.neighbors(col, row, &[(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)])
+    }
+
+    /// Columns and rows of the tiles overlapping `view`, the ranges are empty when nothing is visible.
+    pub fn visible_range(&self, view: &Rect) -> (Range<usize>, Range<usize>) {
+        let axis = |offset: f32, extent: f32, origin: f32, size: f32, count: usize| {
+            let start = ((offset - origin) / size).floor().max(0.0) as usize;
+            let end = (((offset + extent - origin) / size).ceil().max(0.0) as usize).min(count);
+            start.min(end)..end
+        };
+        (
+            axis(view.offset.x, view.extent.x, self.origin.x, self.tile_size.x, self.width),
+            axis(view.offset.y, view.extent.y, self.origin.y, self.tile_size.y, self.height),
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TileDef {
+    pub material: u32,
+    pub uv: [f32; 4],
+    pub color: Color,
+}
+
+impl Default for TileDef {
+    fn default() -> Self {
+        Self {
+            material: 0,
+            uv: FULL_UV,
+            color: WHITE,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Tileset {
+    pub tiles: Vec<TileDef>,
+}
+
+impl Tileset {
+    /// Tileset with one tile per sprite, in order, so `names[i]` gets the id i + 1.
+    pub fn from_sheet(sheet: &SpriteSheet, names: &[&str]) -> Self {
+        let mut tileset = Self::default();
+        for name in names {
+            tileset.add_sprite(sheet, name);
+        }
+        tileset
+    }
+
+    pub fn add(&mut self, tile: TileDef) -> TileId {
+        self.tiles.push(tile);
+        self.tiles.len() as TileId
+    }
+
+    pub fn add_sprite(&mut self, sheet: &SpriteSheet, name: &str) -> TileId {
+        self.add(TileDef {
+            material: sheet.material(name),
+            uv: sheet.uv(name),
+            color: WHITE,
+        })
+    }
+
+    pub fn get(&self, id: TileId) -> Option<&TileDef> {
+        if id == EMPTY_TILE {
+            return None;
+        }
+        self.tiles.get(id as usize - 1)
+    }
+
+    pub fn len(&self) -> usize {
+        self.tiles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tiles.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TileLayer {
+    pub name: String,
+    pub visible: bool,
+    pub z: f32,
+    tiles: Vec<TileId>,
+    chunk_counts: Vec<u32>, // Non-empty tiles in each chunk
+}
+
+impl TileLayer {
+    pub fn tiles(&self) -> &[TileId] {
+        &self.tiles
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tilemap {
+    pub grid: Grid,
+    pub tileset: Tileset,
+    pub layers: Vec<TileLayer>,
+    chunks_x: usize,
+    chunks_y: usize,
+}
+
+impl Tilemap {
+    pub fn new(grid: Grid, tileset: Tileset) -> Self {
+        Self {
+            grid,
+            tileset,
+            layers: vec![],
+            chunks_x: grid.width.div_ceil(CHUNK_SIZE),
+            chunks_y: grid.height.div_ceil(CHUNK_SIZE),
+        }
+    }
+
+    /// Adds an empty layer, layers are rendered in order so the depth `z` decides what ends on top.
+    pub fn add_layer<S: Into<String>>(&mut self, name: S, z: f32) -> usize {
+        self.layers.push(TileLayer {
+            name: name.into(),
+            visible: true,
+            z,
+            tiles: vec![EMPTY_TILE; self.grid.len()],
+            chunk_counts: vec![0; self.chunks_x * self.chunks_y],
+        });
+        self.layers.len() - 1
+    }
+
+    pub fn layer_by_name(&self, name: &str) -> Option<usize> {
+        self.layers.iter().position(|l| l.name == name)
+    }
+
+    fn chunk_idx(&self, col: usize, row: usize) -> usize {
+        (row / CHUNK_SIZE) * self.chunks_x + col / CHUNK_SIZE
+    }
+
+    pub fn get(&self, layer: usize, col: usize, row: usize) -> TileId {
+        self.layers[layer].tiles[self.grid.idx(col, row)]
+    }
+
+    pub fn set(&mut self, layer: usize, col: usize, row: usize, id: TileId) {
+        let idx = self.grid.idx(col, row);
+        let chunk = self.chunk_idx(col, row);
+        let layer = &mut self.layers[layer];
+        let old = std::mem::replace(&mut layer.tiles[idx], id);
+        match (old == EMPTY_TILE, id == EMPTY_TILE) {
+            (true, false) => layer.chunk_counts[chunk] += 1,
+            (false, true) => layer.chunk_counts[chunk] -= 1,
+            _ => {}
+        }
+    }
+
+    pub fn fill(&mut self, layer: usize, id: TileId) {
+        for row in 0..self.grid.height {
+            for col in 0..self.grid.width {
+                self.set(layer, col, row, id);
+            }
+        }
+    }
+
+    /// Topmost (last) visible layer with a tile at the given position.
+    pub fn tile_at<T: Into<Vec2>>(&self, p: T) -> Option<(usize, TileId)> {
+        let (col, row) = self.grid.to_grid(p)?;
+        (0..self.layers.len())
+            .rev()
+            .filter(|&l| self.layers[l].visible)
+            .map(|l| (l, self.get(l, col, row)))
+            .find(|&(_, id)| id != EMPTY_TILE)
+    }
+
+    /// Pushes the tiles overlapping `view` with their material and uv, chunks without tiles are
+    /// skipped entirely. Returns the number of tiles pushed.
+    pub fn render(
+        &self,
+        view: &Rect,
+        cmd: &mut Vec<RenderCommand>,
+        materials: &mut Vec<u32>,
+        uvs: &mut Vec<[f32; 4]>,
+    ) -> usize {
+        let (cols, rows) = self.grid.visible_range(view);
+        if cols.is_empty() || rows.is_empty() {
+            return 0;
+        }
+        let chunk_cols = cols.start / CHUNK_SIZE..(cols.end - 1) / CHUNK_SIZE + 1;
+        let chunk_rows = rows.start / CHUNK_SIZE..(rows.end - 1) / CHUNK_SIZE + 1;
+
+        let mut count = 0;
+        for layer in self.layers.iter().filter(|l| l.visible) {
+            for chunk_row in chunk_rows.clone() {
+                for chunk_col in chunk_cols.clone() {
+                    if layer.chunk_counts[chunk_row * self.chunks_x + chunk_col] == 0 {
+                        continue;
+                    }
+                    let row_start = rows.start.max(chunk_row * CHUNK_SIZE);
+                    let row_end = rows.end.min((chunk_row + 1) * CHUNK_SIZE);
+                    let col_start = cols.start.max(chunk_col * CHUNK_SIZE);
+                    let col_end = cols.end.min((chunk_col + 1) * CHUNK_SIZE);
+                    for row in row_start..row_end {
+                        for col in col_start..col_end {
+                            let Some(tile) = self.tileset.get(layer.tiles[self.grid.idx(col, row)]) else {
+                                continue;
+                            };
+                            vk_util::push_rect_color(cmd, self.grid.tile_rect(col, row), layer.z, tile.color);
+                            materials.push(tile.material);
+                            uvs.push(tile.uv);
+                            count += 1;
+                        }
+                    }
+                }
+            }
+        }
+        count
+    }
+
+    /// Parses a Tiled map (.tmx) with CSV encoded layers. Global tile ids are remapped so the first
+    /// tile of the map's tileset is id 1 of `tileset`, flip flags are dropped.
+    pub fn parse_tmx(text: &str, tileset: Tileset) -> Result<Self, String> {
+        let (map, _) = find_tag(text, "map").ok_or("Missing <map> element")?;
+        let width = attr_num(map, "width")?;
+        let height = attr_num(map, "height")?;
+        let tile_w = attr_num(map, "tilewidth")?;
+        let tile_h = attr_num(map, "tileheight")?;
+        if width == 0 || height == 0 || tile_w == 0 || tile_h == 0 {
+            return Err(format!("Invalid map of {}x{} tiles of {}x{}", width, height, tile_w, tile_h));
+        }
+        let tile_count = width
+            .checked_mul(height)
+            .filter(|&count| count <= MAX_TMX_TILES)
+            .ok_or_else(|| format!("Map of {}x{} tiles is too large", width, height))?;
+        let first_gid = match find_tag(text, "tileset") {
+            Some((ts, _)) => match attr_num(ts, "firstgid")? {
+                first_gid @ 1..=0x1FFF_FFFF => first_gid as u32,
+                first_gid => return Err(format!("Invalid firstgid {}", first_gid)),
+            },
+            None => 1,
+        };
+
+        let grid = Grid::new(width, height, (tile_w as f32, tile_h as f32), (0.0, 0.0));
+        let mut tilemap = Self::new(grid, tileset);
+        let mut rest = text;
+        while let Some((layer_attrs, after)) = find_tag(rest, "layer") {
+            let name = attr(layer_attrs, "name").unwrap_or("");
+            let (data_attrs, after) = find_tag(after, "data").ok_or_else(|| format!("Layer '{}' has no data", name))?;
+            if attr(data_attrs, "encoding") != Some("csv") {
+                return Err(format!("Layer '{}': only CSV encoding is supported", name));
+            }
+            let end = after.find("</data>").ok_or_else(|| format!("Layer '{}': unterminated data", name))?;
+
+            // The tiles are checked before the layer is allocated
+            let mut ids = vec![];
+            for token in after[..end].split(',').map(str::trim).filter(|t| !t.is_empty()) {
+                if ids.len() >= tile_count {
+                    return Err(format!("Layer '{}' has more than {} tiles", name, tile_count));
+                }
+                let gid = token.parse::<u32>().map_err(|_| format!("Layer '{}': invalid tile '{}'", name, token))?;
+                let gid = gid & 0x1FFF_FFFF;
+                let id = if gid == 0 {
+                    EMPTY_TILE
+                } else {
+                    // Gids below the tileset's are invalid rather than empty
+                    gid.checked_sub(first_gid)
+                        .map(|id| id + 1)
+                        .ok_or_else(|| format!("Layer '{}': invalid tile {}", name, gid))?
+                };
+                if id as usize > tilemap.tileset.len() {
+                    return Err(format!("Layer '{}': tile {} is not in the tileset", name, id));
+                }
+                ids.push(id);
+            }
+            if ids.len() != tile_count {
+                return Err(format!("Layer '{}' has {} tiles, expected {}", name, ids.len(), tile_count));
+            }
+
+            let z = 0.9 - 0.1 * tilemap.layers.len() as f32;
+            let layer = tilemap.add_layer(name, z);
+            if attr(layer_attrs, "visible") == Some("0") {
+                tilemap.layers[layer].visible = false;
+            }
+            for (idx, id) in ids.into_iter().enumerate() {
+                let (col, row) = grid.pos(idx);
+                tilemap.set(layer, col, row, id);
+            }
+            rest = &after[end..];
+        }
+        Ok(tilemap)
+    }
+
+    pub fn load_tmx<P: AsRef<Path>>(path: P, tileset: Tileset) -> Result<Self, String> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Self::parse_tmx(&text, tileset).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+// Just enough XML for Tiled maps: returns the attributes of the first <name ...> tag and the text after it.
+fn find_tag<'a>(text: &'a str, name: &str) -> Option<(&'a str, &'a str)> {
+    let open = format!("<{}", name);
+    let mut from = 0;
+    while let Some(i) = text[from..].find(&open) {
+        let start = from + i + open.len();
+        let rest = &text[start..];
+        if rest.starts_with(|c: char| c.is_whitespace() || c == '>' || c == '/') {
+            let end = rest.find('>')?;
+            return Some((rest[..end].trim_end_matches('/'), &rest[end + 1..]));
+        }
+        from = start;
+    }
+    None
+}
+
+fn attr<'a>(attrs: &'a str, key: &str) -> Option<&'a str> {
+    let mut rest = attrs;
+    while let Some(eq) = rest.find('=') {
+        let name = rest[..eq].trim();
+        let value = rest[eq + 1..].trim_start();
+        let quote = value.chars().next()?;
+        let value = &value[1..];
+        let end = value.find(quote)?;
+        if name == key {
+            return Some(&value[..end]);
+        }
+        rest = &value[end + 1..];
+    }
+    None
+}
+
+fn attr_num(attrs: &str, key: &str) -> Result<usize, String> {
+    let value = attr(attrs, key).ok_or_else(|| format!("Missing attribute '{}'", key))?;
+    value.parse().map_err(|_| format!("Invalid value '{}' for '{}'", value, key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tileset(count: usize) -> Tileset {
+        let mut tileset = Tileset::default();
+        for i in 0..count {
+            tileset.add(TileDef {
+                material: i as u32,
+                ..Default::default()
+            });
+        }
+        tileset
+    }
+
+    #[test]
+    fn grid_conversions() {
+        let grid = Grid::new(4, 3, (10.0, 20.0), (100.0, 50.0));
+        assert_eq!(grid.to_grid((100.0, 50.0)), Some((0, 0)));
+        assert_eq!(grid.to_grid((139.9, 109.9)), Some((3, 2)));
+        assert_eq!(grid.to_grid((99.9, 60.0)), None);
+        assert_eq!(grid.to_grid((140.0, 60.0)), None);
+        assert_eq!(grid.to_world(2, 1), Vec2::new(120.0, 70.0));
+        assert_eq!(grid.tile_center(2, 1), Vec2::new(125.0, 80.0));
+        assert_eq!(grid.pos(grid.idx(3, 2)), (3, 2));
+    }
+
+    #[test]
+    fn grid_neighbors() {
+        let grid = Grid::new(3, 3, (1.0, 1.0), (0.0, 0.0));
+        assert_eq!(grid.neighbors4(1, 1), vec![(1, 0), (0, 1), (2, 1), (1, 2)]);
+        assert_eq!(grid.neighbors4(0, 0), vec![(1, 0), (0, 1)]);
+        assert_eq!(grid.neighbors8(1, 1).len(), 8);
+        assert_eq!(grid.neighbors8(2, 2), vec![(1, 1), (2, 1), (1, 2)]);
+    }
+
+    #[test]
+    fn render_visible_chunks() {
+        let grid = Grid::new(40, 40, (8.0, 8.0), (0.0, 0.0));
+        let mut map = Tilemap::new(grid, tileset(2));
+        let ground = map.add_layer("ground", 0.9);
+        let top = map.add_layer("top", 0.5);
+        map.set(ground, 0, 0, 1);
+        map.set(ground, 20, 20, 2);
+        map.set(top, 1, 0, 2);
+        map.set(top, 1, 0, EMPTY_TILE);
+        assert_eq!(map.layers[top].chunk_counts.iter().sum::<u32>(), 0);
+
+        let (mut cmd, mut materials, mut uvs) = (vec![], vec![], vec![]);
+        let view = Rect::offset_extent((0.0, 0.0), (64.0, 64.0));
+        assert_eq!(map.render(&view, &mut cmd, &mut materials, &mut uvs), 1);
+        assert_eq!(materials, vec![0]);
+
+        map.fill(top, 1);
+        let view = Rect::offset_extent((156.0, 156.0), (16.0, 16.0));
+        let count = map.render(&view, &mut cmd, &mut materials, &mut uvs);
+        // 3x3 tiles of the top layer plus the tile at (20, 20)
+        assert_eq!(count, 10);
+        assert_eq!(cmd.len(), 11);
+        assert_eq!(map.tile_at((163.0, 163.0)), Some((top, 1)));
+        map.layers[top].visible = false;
+        assert_eq!(map.tile_at((163.0, 163.0)), Some((ground, 2)));
+    }
+
+    #[test]
+    fn parse_tmx_csv() {
+        let tmx = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" width="3" height="2" tilewidth="16" tileheight="16">
+ <tileset firstgid="1" source="tiles.tsx"/>
+ <layer id="1" name="ground" width="3" height="2">
+  <data encoding="csv">
+1,2,0,
+2147483650,0,1
+</data>
+ </layer>
+ <layer id="2" name="deco" width="3" height="2" visible="0">
+  <data encoding="csv">0,0,0,0,0,2</data>
+ </layer>
+</map>"#;
+        let map = Tilemap::parse_tmx(tmx, tileset(2)).unwrap();
+        assert_eq!(map.grid.tile_size, Vec2::new(16.0, 16.0));
+        assert_eq!(map.layers.len(), 2);
+        assert_eq!(map.layers[0].tiles(), &[1, 2, 0, 2, 0, 1]);
+        assert_eq!(map.layer_by_name("deco"), Some(1));
+        assert!(!map.layers[1].visible);
+        assert!(Tilemap::parse_tmx(tmx, tileset(1)).is_err());
+        assert!(Tilemap::parse_tmx(&tmx.replace("csv", "base64"), tileset(2)).is_err());
+
+        // Gids are offset by firstgid, those below it are invalid, and 0 isn't a valid firstgid
+        let tmx = |first_gid: u32, data: &str| {
+            format!(
+                r#"<map width="2" height="1" tilewidth="8" tileheight="8"><tileset firstgid="{}"/>
+<layer name="ground"><data encoding="csv">{}</data></layer></map>"#,
+                first_gid, data
+            )
+        };
+        assert_eq!(Tilemap::parse_tmx(&tmx(3, "4,0"), tileset(2)).unwrap().layers[0].tiles(), &[2, 0]);
+        assert!(Tilemap::parse_tmx(&tmx(3, "2,3"), tileset(2)).unwrap_err().contains("invalid tile 2"));
+        assert!(Tilemap::parse_tmx(&tmx(0, "1,0"), tileset(2)).unwrap_err().contains("firstgid 0"));
+
+        // Sizes are checked before anything is allocated
+        let sized = |width: &str, height: &str, tile_size: u32| {
+            let size = format!(r#"width="{}" height="{}" tilewidth="{}""#, width, height, tile_size);
+            tmx(1, "1").replace(r#"width="2" height="1" tilewidth="8""#, &size)
+        };
+        let error = |tmx: String| Tilemap::parse_tmx(&tmx, tileset(2)).unwrap_err();
+        assert!(error(sized("18446744073709551615", "2", 8)).contains("too large"));
+        assert!(error(sized("4000000000", "4000", 8)).contains("too large"));
+        assert!(error(sized("0", "1", 8)).contains("Invalid map"));
+        assert!(error(sized("1", "1", 0)).contains("Invalid map"));
+        assert!(error(sized("4096", "4096", 8)).contains("has 1 tiles, expected 16777216"));
+    }
+}
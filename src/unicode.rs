@@ -0,0 +1,218 @@
+// Unicode text segmentation:
+// Splits text into grapheme clusters, the user-perceived characters: a base character with its combining marks,
+// a CR LF pair, a Hangul syllable made of jamos, an emoji ZWJ sequence or a regional indicator pair (a flag).
+// These are the rules of UAX #29 (extended grapheme clusters) without Prepend characters, with the property ranges
+// reduced to the blocks most used in practice.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Break {
+    Cr,
+    Lf,
+    Control,
+    Extend,
+    Zwj,
+    RegionalIndicator,
+    /// Hangul jamos and syllables
+    L,
+    V,
+    T,
+    Lv,
+    Lvt,
+    Pictographic,
+    Other,
+}
+
+/// Nonspacing, enclosing and spacing combining marks, variation selectors and emoji modifiers.
+const EXTEND: &[(u32, u32)] = &[
+    (0x0300, 0x036F), // Combining Diacritical Marks
+    (0x0483, 0x0489), // Cyrillic
+    (0x0591, 0x05BD), // Hebrew
+    (0x05BF, 0x05BF),
+    (0x05C1, 0x05C2),
+    (0x05C4, 0x05C5),
+    (0x05C7, 0x05C7),
+    (0x0610, 0x061A), // Arabic
+    (0x064B, 0x065F),
+    (0x0670, 0x0670),
+    (0x06D6, 0x06DC),
+    (0x06DF, 0x06E4),
+    (0x06E7, 0x06E8),
+    (0x06EA, 0x06ED),
+    (0x0900, 0x0903), // Devanagari
+    (0x093A, 0x093C),
+    (0x093E, 0x094F),
+    (0x0951, 0x0957),
+    (0x0962, 0x0963),
+    (0x0E31, 0x0E31), // Thai
+    (0x0E34, 0x0E3A),
+    (0x0E47, 0x0E4E),
+    (0x1AB0, 0x1AFF),   // Combining Diacritical Marks Extended
+    (0x1DC0, 0x1DFF),   // Combining Diacritical Marks Supplement
+    (0x200C, 0x200C),   // Zero width non-joiner
+    (0x20D0, 0x20FF),   // Combining Diacritical Marks for Symbols
+    (0x302A, 0x302F),   // CJK tone marks
+    (0x3099, 0x309A),   // Kana voicing marks
+    (0xFE00, 0xFE0F),   // Variation Selectors
+    (0xFE20, 0xFE2F),   // Combining Half Marks
+    (0x1F3FB, 0x1F3FF), // Emoji skin tone modifiers
+    (0xE0020, 0xE007F), // Tags
+    (0xE0100, 0xE01EF), // Variation Selectors Supplement
+];
+
+const PICTOGRAPHIC: &[(u32, u32)] = &[
+    (0x00A9, 0x00A9),
+    (0x00AE, 0x00AE),
+    (0x203C, 0x203C),
+    (0x2049, 0x2049),
+    (0x2122, 0x2122),
+    (0x2139, 0x2139),
+    (0x2194, 0x21AA),
+    (0x231A, 0x23FF),
+    (0x25AA, 0x25FE),
+    (0x2600, 0x27BF),
+    (0x2934, 0x2935),
+    (0x2B05, 0x2B55),
+    (0x3030, 0x3030),
+    (0x303D, 0x303D),
+    (0x3297, 0x3299),
+    (0x1F000, 0x1F0FF),
+    (0x1F10D, 0x1F1AD),
+    (0x1F201, 0x1F3FA),
+    (0x1F400, 0x1FAFF),
+];
+
+fn in_ranges(ranges: &[(u32, u32)], c: char) -> bool {
+    let c = c as u32;
+    let i = ranges.partition_point(|&(_, end)| end < c);
+    ranges.get(i).is_some_and(|&(start, _)| start <= c)
+}
+
+fn break_property(c: char) -> Break {
+    match c as u32 {
+        0x0D => Break::Cr,
+        0x0A => Break::Lf,
+        0x00..=0x1F | 0x7F..=0x9F | 0x2028 | 0x2029 => Break::Control,
+        0x200D => Break::Zwj,
+        0x1F1E6..=0x1F1FF => Break::RegionalIndicator,
+        0x1100..=0x115F | 0xA960..=0xA97C => Break::L,
+        0x1160..=0x11A7 | 0xD7B0..=0xD7C6 => Break::V,
+        0x11A8..=0x11FF | 0xD7CB..=0xD7FB => Break::T,
+        syllable @ 0xAC00..=0xD7A3 => {
+            if (syllable - 0xAC00) % 28 == 0 {
+                Break::Lv
+            } else {
+                Break::Lvt
+            }
+        }
+        _ if in_ranges(EXTEND, c) => Break::Extend,
+        _ if in_ranges(PICTOGRAPHIC, c) => Break::Pictographic,
+        _ => Break::Other,
+    }
+}
+
+/// Characters drawn over or next to the previous one instead of on their own.
+pub fn is_combining_mark(c: char) -> bool {
+    matches!(break_property(c), Break::Extend | Break::Zwj)
+}
+
+/// Iterator over the grapheme clusters of a string, see `graphemes`.
+#[derive(Debug, Clone)]
+pub struct Graphemes<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Iterator for Graphemes<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        let mut chars = self.rest.char_indices();
+        let (_, first) = chars.next()?;
+        let mut previous = break_property(first);
+        // Emoji followed only by extending characters so far, and regional indicators in the cluster
+        let mut pictographic = previous == Break::Pictographic;
+        let mut regional_indicators = (previous == Break::RegionalIndicator) as usize;
+        let mut end = self.rest.len();
+        for (i, c) in chars {
+            let next = break_property(c);
+            let join = match (previous, next) {
+                (Break::Cr, Break::Lf) => true,
+                (Break::Cr | Break::Lf | Break::Control, _) | (_, Break::Cr | Break::Lf | Break::Control) => false,
+                (Break::L, Break::L | Break::V | Break::Lv | Break::Lvt) => true,
+                (Break::Lv | Break::V, Break::V | Break::T) => true,
+                (Break::Lvt | Break::T, Break::T) => true,
+                (_, Break::Extend | Break::Zwj) => true,
+                (Break::Zwj, Break::Pictographic) => pictographic,
+                (Break::RegionalIndicator, Break::RegionalIndicator) => regional_indicators % 2 == 1,
+                _ => false,
+            };
+            if !join {
+                end = i;
+                break;
+            }
+            pictographic = match next {
+                Break::Pictographic => true,
+                Break::Extend | Break::Zwj => pictographic,
+                _ => false,
+            };
+            regional_indicators += (next == Break::RegionalIndicator) as usize;
+            previous = next;
+        }
+        let (cluster, rest) = self.rest.split_at(end);
+        self.rest = rest;
+        Some(cluster)
+    }
+}
+
+/// Splits `s` into grapheme clusters.
+pub fn graphemes(s: &str) -> Graphemes<'_> {
+    Graphemes {
+        rest: s,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(s: &str) -> Vec<&str> {
+        graphemes(s).collect()
+    }
+
+    #[test]
+    fn clusters() {
+        assert_eq!(split(""), Vec::<&str>::new());
+        assert_eq!(split("abc"), ["a", "b", "c"]);
+        // Combining acute accent and diaeresis stay with their base letter
+        assert_eq!(split("e\u{301}x\u{308}\u{301}!"), ["e\u{301}", "x\u{308}\u{301}", "!"]);
+        // A leading mark is a cluster of its own
+        assert_eq!(split("\u{301}a"), ["\u{301}", "a"]);
+        assert_eq!(split("a\r\nb\n\n"), ["a", "\r\n", "b", "\n", "\n"]);
+        assert_eq!(split("\n\u{301}"), ["\n", "\u{301}"]);
+        // Hangul jamos form syllables
+        assert_eq!(
+            split("\u{1100}\u{1161}\u{11A8}\u{AC00}\u{11A8}\u{1100}"),
+            ["\u{1100}\u{1161}\u{11A8}", "\u{AC00}\u{11A8}", "\u{1100}"]
+        );
+        // Flags are pairs of regional indicators
+        assert_eq!(
+            split("\u{1F1EB}\u{1F1F7}\u{1F1E9}\u{1F1EA}\u{1F1EE}"),
+            ["\u{1F1EB}\u{1F1F7}", "\u{1F1E9}\u{1F1EA}", "\u{1F1EE}"]
+        );
+        // Emoji ZWJ sequence with a skin tone modifier, a ZWJ after a letter doesn't join the emoji
+        assert_eq!(
+            split("\u{1F469}\u{1F3FD}\u{200D}\u{1F4BB}a\u{200D}\u{1F4BB}"),
+            ["\u{1F469}\u{1F3FD}\u{200D}\u{1F4BB}", "a\u{200D}", "\u{1F4BB}"]
+        );
+        assert_eq!(split("\u{2764}\u{FE0F}"), ["\u{2764}\u{FE0F}"]);
+    }
+
+    #[test]
+    fn combining_marks() {
+        assert!(is_combining_mark('\u{301}'));
+        assert!(is_combining_mark('\u{FE0F}'));
+        assert!(is_combining_mark('\u{093F}'));
+        assert!(!is_combining_mark('e'));
+        assert!(!is_combining_mark('\u{2014}'));
+        assert!(!is_combining_mark('\u{1F600}'));
+    }
+}
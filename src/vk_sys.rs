@@ -817,6 +817,7 @@ pub const VK_UUID_SIZE: usize = 16;
 pub const VK_QUEUE_FAMILY_IGNORED: u32 = !0;
 pub const VK_SUBPASS_EXTERNAL: u32 = !0;
 pub const VK_WHOLE_SIZE: VkDeviceSize = !0;
+pub const VK_LOD_CLAMP_NONE: f32 = 1000.0;
 pub const VK_MAX_MEMORY_TYPES: usize = 32;
 pub const VK_MAX_MEMORY_HEAPS: usize = 16;
 pub const VK_MAX_PHYSICAL_DEVICE_NAME_SIZE: usize = 256;
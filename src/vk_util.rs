@@ -1,11 +1,19 @@
+use crate::atlas::{AtlasPage, SpriteSheet};
+use crate::bcn::{decode_bc, BcFormat};
+use crate::bitmap_font::BitmapFont;
 use crate::color::*;
 use crate::cstr;
-use crate::glyph::{Glyph, GLYPHS, GLYPH_HEIGHT, GLYPH_WIDTH};
+use crate::glyph::{bitmap_glyph, Glyph, GLYPH_WIDTH};
+use crate::glyph_sdf::SdfAtlas;
+use crate::image::{ColorSpace, RgbaImage};
+use crate::ktx2::{texel_block, Ktx2};
 use crate::math::{Rect, Vec2};
 use crate::platform::Platform;
-use crate::spirv::ShaderModule;
-use crate::stb_image::*;
+use crate::spirv::{DescriptorType, Reflection, ShaderModule};
+use crate::spirv_val;
 use crate::string_util::*;
+use crate::text_layout::{LayoutOptions, TextLayout};
+use crate::unicode::{graphemes, is_combining_mark};
 use crate::vk_sys::*;
 use crate::x11_sys::XCloseDisplay;
 use crate::xcb_sys::{xcb_connection_t, xcb_window_t};
@@ -91,6 +99,7 @@ pub fn vk_create_device(
     queue_family_index: u32,
     enabled_extensions: &[*const i8],
 ) -> (VkDevice, VkQueue) {
+    let supported_features = vk_get_physical_device_features(physical_device);
     unsafe {
         let mut device = VkDevice::default();
         check!(vkCreateDevice(
@@ -109,6 +118,11 @@ pub fn vk_create_device(
                 pEnabledFeatures: &VkPhysicalDeviceFeatures {
                     samplerAnisotropy: VK_TRUE, // TODO: Check if features are actually supported
                     fillModeNonSolid: VK_TRUE,
+                    // Compressed formats can only be sampled with their feature enabled
+                    textureCompressionBC: supported_features.textureCompressionBC,
+                    textureCompressionETC2: supported_features.textureCompressionETC2,
+                    textureCompressionASTC_LDR: supported_features.textureCompressionASTC_LDR,
+                    imageCubeArray: supported_features.imageCubeArray,
                     ..VkPhysicalDeviceFeatures::default()
                 },
                 ..VkDeviceCreateInfo::default()
@@ -237,18 +251,39 @@ pub fn vk_create_image(device: VkDevice, format: VkFormat, usage: u32, width: u3
     }
 }
 pub fn vk_create_image_view(device: VkDevice, image: VkImage, format: VkFormat, aspect: u32) -> VkImageView {
+    vk_create_image_view_levels(device, image, format, aspect, 1)
+}
+pub fn vk_create_image_view_levels(
+    device: VkDevice,
+    image: VkImage,
+    format: VkFormat,
+    aspect: u32,
+    levels: u32,
+) -> VkImageView {
+    vk_create_image_view_layers(device, image, VK_IMAGE_VIEW_TYPE_2D, format, aspect, levels, 1)
+}
+/// View of every mip level and array layer, where the 6 faces of a cubemap count as layers.
+pub fn vk_create_image_view_layers(
+    device: VkDevice,
+    image: VkImage,
+    view_type: VkImageViewType,
+    format: VkFormat,
+    aspect: u32,
+    levels: u32,
+    layers: u32,
+) -> VkImageView {
     unsafe {
         let mut image_view = VkImageView::default();
         check!(vkCreateImageView(
             device,
             &VkImageViewCreateInfo {
                 image,
-                viewType: VK_IMAGE_VIEW_TYPE_2D,
+                viewType: view_type,
                 format,
                 subresourceRange: VkImageSubresourceRange {
                     aspectMask: aspect.into(),
-                    levelCount: 1,
-                    layerCount: 1,
+                    levelCount: levels,
+                    layerCount: layers,
                     ..VkImageSubresourceRange::default()
                 },
                 ..VkImageViewCreateInfo::default()
@@ -582,7 +617,8 @@ pub fn vk_create_framebuffer(
     }
 }
 pub fn vk_create_shader_module<P: AsRef<Path>>(device: VkDevice, path: P) -> VkShaderModule {
-    let data = fs::read(path).unwrap();
+    let data = fs::read(&path).unwrap();
+    spirv_val::validate_bytes(&data).unwrap_or_else(|e| panic!("{}: {}", path.as_ref().display(), e));
     let mut module = VkShaderModule::default();
     unsafe {
         vkCreateShaderModule(
@@ -879,6 +915,87 @@ pub fn push_rect_color<R: Into<Rect>, C: Into<Color>>(cmd: &mut Vec<RenderComman
     let r = r.into();
     cmd.push(RenderCommand::Rect(r.offset.x, r.offset.y, r.extent.x, r.extent.y, z, c.into().as_f32()));
 }
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SliceMode {
+    Stretch,
+    Tile,
+}
+
+/// Texture region split by border insets: corners keep their size while the edges and the center
+/// stretch (or tile) to fill the destination rect.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NineSlice {
+    pub uv: [f32; 4],     // u0, v0, u1, v1 of the whole panel in the texture
+    pub size: (f32, f32), // Size of the region in pixels
+    pub insets: [f32; 4], // left, top, right, bottom borders in pixels
+    pub mode: SliceMode,  // How edges and center fill the space between the corners
+}
+
+// Splits [dst0, dst1] into segments mapped to [src0, src1], repeating the source when tiling.
+fn slice_segments(dst0: f32, dst1: f32, src0: f32, src1: f32, tile: bool) -> Vec<(f32, f32, f32, f32)> {
+    let src_len = src1 - src0;
+    if dst1 <= dst0 {
+        return vec![];
+    }
+    if !tile || src_len <= 0.0 {
+        return vec![(dst0, dst1, src0, src1)];
+    }
+    let mut segments = vec![];
+    let mut d = dst0;
+    while d < dst1 {
+        let len = src_len.min(dst1 - d);
+        segments.push((d, d + len, src0, src0 + len));
+        d += len;
+    }
+    segments
+}
+
+/// Pushes the rects of a nine-slice panel together with their texture coordinates (to be used
+/// with `VkContext::render_sprite_uv`). Returns the number of rects pushed.
+pub fn push_nine_slice<R: Into<Rect>, C: Into<Color>>(
+    cmd: &mut Vec<RenderCommand>,
+    uvs: &mut Vec<[f32; 4]>,
+    r: R,
+    z: f32,
+    c: C,
+    slice: &NineSlice,
+) -> usize {
+    let r = r.into();
+    let color = c.into();
+    let (w, h) = slice.size;
+    let [left, top, right, bottom] = slice.insets;
+
+    // Shrink the borders when the rect is smaller than the corners
+    let sx = (r.extent.x / (left + right)).min(1.0);
+    let sy = (r.extent.y / (top + bottom)).min(1.0);
+    let (x0, y0) = (r.offset.x, r.offset.y);
+    let (x1, y1) = (x0 + r.extent.x, y0 + r.extent.y);
+    let dst_x = [x0, x0 + left * sx, x1 - right * sx, x1];
+    let dst_y = [y0, y0 + top * sy, y1 - bottom * sy, y1];
+    let src_x = [0.0, left, w - right, w];
+    let src_y = [0.0, top, h - bottom, h];
+
+    let [u0, v0, u1, v1] = slice.uv;
+    let to_u = |x: f32| u0 + (u1 - u0) * x / w;
+    let to_v = |y: f32| v0 + (v1 - v0) * y / h;
+
+    let count = cmd.len();
+    for row in 0..3 {
+        for col in 0..3 {
+            let tile_x = slice.mode == SliceMode::Tile && col == 1;
+            let tile_y = slice.mode == SliceMode::Tile && row == 1;
+            let xs = slice_segments(dst_x[col], dst_x[col + 1], src_x[col], src_x[col + 1], tile_x);
+            let ys = slice_segments(dst_y[row], dst_y[row + 1], src_y[row], src_y[row + 1], tile_y);
+            for &(dy0, dy1, sy0, sy1) in &ys {
+                for &(dx0, dx1, sx0, sx1) in &xs {
+                    push_rect_color(cmd, Rect::offset_extent((dx0, dy0), (dx1 - dx0, dy1 - dy0)), z, color);
+                    uvs.push([to_u(sx0), to_v(sy0), to_u(sx1), to_v(sy1)]);
+                }
+            }
+        }
+    }
+    cmd.len() - count
+}
 pub const GLYPH_OUTLINE_SIZE: f32 = 4.0;
 pub fn push_glyph(cmd: &mut Vec<RenderCommand>, glyph: &Glyph, x: f32, y: f32, z: f32, pixel_size: f32) {
     push_glyph_color(cmd, glyph, (x, y), z, pixel_size, WHITE, false);
@@ -892,33 +1009,43 @@ pub fn push_glyph_color<C: Into<Color>>(
     color: C,
     outline: bool,
 ) {
-    let color = color.into();
-    for row in 0..GLYPH_HEIGHT {
-        for col in 0..GLYPH_WIDTH {
-            if glyph[row * GLYPH_WIDTH + col] != 0 {
-                push_rect_color(
-                    cmd,
-                    Rect::offset_extent(
-                        (offset.0 + pixel_size * (col as f32), offset.1 + pixel_size * (row as f32)),
-                        (pixel_size, pixel_size),
-                    ),
-                    z,
-                    //TEXT_Z,
-                    color,
-                );
-                if outline {
-                    push_rect_color(
-                        cmd,
-                        Rect::offset_extent(
-                            (offset.0 + pixel_size * (col as f32), offset.1 + pixel_size * (row as f32)),
-                            (pixel_size + GLYPH_OUTLINE_SIZE, pixel_size + GLYPH_OUTLINE_SIZE),
-                        ),
-                        //OUTLINE_Z,
-                        z + 0.1,
-                        color.invert(), //(1.0 - color.0, 1.0 - color.1, 1.0 - color.2),
-                    );
-                }
-            }
+    push_pixels_color(cmd, glyph, GLYPH_WIDTH, offset, z, pixel_size, color.into(), outline);
+}
+/// Pushes one square per set pixel of a 1-bit image with rows of `width` pixels.
+#[allow(clippy::too_many_arguments)]
+fn push_pixels_color(
+    cmd: &mut Vec<RenderCommand>,
+    pixels: &[u8],
+    width: usize,
+    offset: (f32, f32),
+    z: f32,
+    pixel_size: f32,
+    color: Color,
+    outline: bool,
+) {
+    for (idx, _) in pixels.iter().enumerate().filter(|(_, &p)| p != 0) {
+        let (col, row) = (idx % width, idx / width);
+        push_rect_color(
+            cmd,
+            Rect::offset_extent(
+                (offset.0 + pixel_size * (col as f32), offset.1 + pixel_size * (row as f32)),
+                (pixel_size, pixel_size),
+            ),
+            z,
+            //TEXT_Z,
+            color,
+        );
+        if outline {
+            push_rect_color(
+                cmd,
+                Rect::offset_extent(
+                    (offset.0 + pixel_size * (col as f32), offset.1 + pixel_size * (row as f32)),
+                    (pixel_size + GLYPH_OUTLINE_SIZE, pixel_size + GLYPH_OUTLINE_SIZE),
+                ),
+                //OUTLINE_Z,
+                z + 0.1,
+                color.invert(), //(1.0 - color.0, 1.0 - color.1, 1.0 - color.2),
+            );
         }
     }
 }
@@ -934,9 +1061,7 @@ pub fn push_char_color(
     color: Color,
     outline: bool,
 ) {
-    assert!((' '..='~').contains(&c));
-    let glyph_idx = c as usize - ' ' as usize;
-    push_glyph_color(cmd, &GLYPHS[glyph_idx], offset, z, pixel_size, color, outline);
+    push_glyph_color(cmd, bitmap_glyph(c), offset, z, pixel_size, color, outline);
 }
 pub fn push_str(cmd: &mut Vec<RenderCommand>, s: &str, x: f32, y: f32, z: f32, pixel_size: f32) {
     push_str_color(cmd, s, (x, y), z, pixel_size, WHITE, false);
@@ -956,7 +1081,7 @@ pub fn push_str_centered_color<R: Into<Rect>>(
     r: R,
 ) {
     let r = r.into();
-    let text_extent = (s.len() as f32) * 6.0 * pixel_size;
+    let text_extent = (graphemes(s).count() as f32) * 6.0 * pixel_size;
     //let x = WINDOW_WIDTH / 2.0 - text_extent / 2.0;
     let x = r.center().x - text_extent / 2.0;
     push_str_color(cmd, s, (x, y), z, pixel_size, color, outline);
@@ -970,9 +1095,102 @@ pub fn push_str_color(
     color: Color,
     outline: bool,
 ) {
-    for (idx, c) in s.chars().enumerate() {
+    // The pixel font has no combining marks, clusters are drawn with their base character
+    for (idx, cluster) in graphemes(s).enumerate() {
         let offset = (offset.0 + (idx as f32) * pixel_size * (GLYPH_WIDTH as f32 + 1.0), offset.1);
-        push_char_color(cmd, c, offset, z, pixel_size, color, outline);
+        push_char_color(cmd, cluster.chars().next().unwrap(), offset, z, pixel_size, color, outline);
+    }
+}
+
+/// Draws `s` with a pixel font, `offset` is the top-left corner of the line and `pixel_size` the
+/// size of a font pixel. Grapheme clusters are drawn with their first character unless the font has
+/// the combining marks. Returns the width of the text in pixels.
+#[allow(clippy::too_many_arguments)]
+pub fn push_str_bitmap(
+    cmd: &mut Vec<RenderCommand>,
+    font: &BitmapFont,
+    s: &str,
+    offset: (f32, f32),
+    z: f32,
+    pixel_size: f32,
+    color: Color,
+    outline: bool,
+) -> f32 {
+    let baseline = offset.1 + font.ascent as f32 * pixel_size;
+    let mut x = offset.0;
+    for cluster in graphemes(s) {
+        let mut chars = cluster.chars();
+        let base = font.glyph(chars.next().unwrap());
+        for glyph in std::iter::once(base).chain(chars.filter_map(|c| font.glyphs.get(&c))) {
+            let glyph_offset = (x + glyph.left as f32 * pixel_size, baseline + glyph.top as f32 * pixel_size);
+            push_pixels_color(cmd, &glyph.pixels, glyph.width as usize, glyph_offset, z, pixel_size, color, outline);
+        }
+        x += base.advance as f32 * pixel_size;
+    }
+    x - offset.0
+}
+
+/// Pushes one quad per character of `s` out of a distance field atlas (to be used with
+/// `VkContext::render_sprite_sdf`), `offset` is the top-left corner of the line and `size` the
+/// text size in pixels per em. Characters missing in the atlas are drawn with its replacement
+/// glyph. Returns the width of the text in pixels.
+#[allow(clippy::too_many_arguments)]
+pub fn push_str_sdf<C: Into<Color>>(
+    cmd: &mut Vec<RenderCommand>,
+    materials: &mut Vec<u32>,
+    uvs: &mut Vec<[f32; 4]>,
+    distance_ranges: &mut Vec<f32>,
+    atlas: &SdfAtlas,
+    s: &str,
+    offset: (f32, f32),
+    z: f32,
+    size: f32,
+    color: C,
+) -> f32 {
+    let options = LayoutOptions {
+        size,
+        ..Default::default()
+    };
+    let layout = TextLayout::new(atlas, s, &options);
+    push_text_sdf(cmd, materials, uvs, distance_ranges, atlas, &layout, offset, z, color);
+    layout.bounds().extent.x
+}
+
+/// Like `push_str_sdf` for text laid out with `atlas` (see `text_layout::TextLayout`), `offset` is
+/// the top-left corner of the layout.
+#[allow(clippy::too_many_arguments)]
+pub fn push_text_sdf<C: Into<Color>>(
+    cmd: &mut Vec<RenderCommand>,
+    materials: &mut Vec<u32>,
+    uvs: &mut Vec<[f32; 4]>,
+    distance_ranges: &mut Vec<f32>,
+    atlas: &SdfAtlas,
+    layout: &TextLayout,
+    offset: (f32, f32),
+    z: f32,
+    color: C,
+) {
+    let color = color.into();
+    let size = layout.size;
+    let distance_range = atlas.range * size / atlas.pixel_size;
+    for positioned in &layout.glyphs {
+        // Marks missing in the atlas are left out rather than drawing a replacement over their base character
+        let glyph = if is_combining_mark(positioned.c) {
+            atlas.glyphs.get(&positioned.c)
+        } else {
+            atlas.glyph(positioned.c)
+        };
+        let Some(glyph) = glyph.filter(|glyph| glyph.size.0 > 0.0) else {
+            continue;
+        };
+        let r = Rect::offset_extent(
+            (offset.0 + positioned.x + glyph.bearing.0 * size, offset.1 + positioned.y + glyph.bearing.1 * size),
+            (glyph.size.0 * size, glyph.size.1 * size),
+        );
+        push_rect_color(cmd, r, z, color);
+        materials.push(atlas.material_base + glyph.page as u32);
+        uvs.push(glyph.uv);
+        distance_ranges.push(distance_range);
     }
 }
 
@@ -993,6 +1211,106 @@ pub struct VkPhysicalDeviceMeta {
 
 const MAX_FRAMES_IN_FLIGHT: usize = 2;
 const MAX_TEXTURES: usize = 20;
+
+pub const FULL_UV: [f32; 4] = [0.0, 0.0, 1.0, 1.0];
+
+// Push constants of the "sprite" and "atlas" shaders
+#[repr(C)]
+#[derive(Default)]
+struct SpritePushConstants {
+    rect: [f32; 9], // vec2 offset + vec2 size + z + color
+    rotation_id: u32,
+    uv: [f32; 4],        // u0, v0, u1, v1
+    distance_range: f32, // Screen pixels spanned by the distance field, 0 for plain textures
+}
+
+/// A descriptor of set 0 as seen by all the stages of a pipeline.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShaderBinding {
+    pub binding: u32,
+    pub descriptor_type: DescriptorType,
+    pub count: u32,
+    pub stages: VkFlags,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShaderPushConstants {
+    pub stages: VkFlags,
+    pub offset: u32,
+    pub size: u32,
+}
+
+/// Descriptors and push constants of a pipeline, merged from the reflection of its shader stages.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ShaderInterface {
+    pub bindings: Vec<ShaderBinding>, // Sorted by binding
+    pub push_constants: Option<ShaderPushConstants>,
+}
+
+impl ShaderInterface {
+    /// `stages` pairs the reflection of every shader with its VK_SHADER_STAGE_*_BIT.
+    pub fn from_stages(stages: &[(VkFlags, &Reflection)]) -> Result<Self, String> {
+        let mut interface = Self::default();
+        for (stage, reflection) in stages {
+            for b in &reflection.descriptor_bindings {
+                if b.set != 0 {
+                    return Err(format!("'{}' uses descriptor set {}, only set 0 is supported", b.name, b.set));
+                }
+                if b.count == 0 {
+                    return Err(format!("'{}' is a runtime sized array of descriptors", b.name));
+                }
+                match interface.bindings.iter_mut().find(|x| x.binding == b.binding) {
+                    Some(x) if x.descriptor_type != b.descriptor_type || x.count != b.count => {
+                        return Err(format!("Binding {} is declared differently across stages", b.binding));
+                    }
+                    Some(x) => x.stages |= stage,
+                    None => interface.bindings.push(ShaderBinding {
+                        binding: b.binding,
+                        descriptor_type: b.descriptor_type,
+                        count: b.count,
+                        stages: *stage,
+                    }),
+                }
+            }
+            if let Some(block) = &reflection.push_constants {
+                let (offset, size) = block.range();
+                interface.push_constants = Some(match interface.push_constants {
+                    Some(pc) => {
+                        let start = pc.offset.min(offset);
+                        let end = (pc.offset + pc.size).max(offset + size);
+                        ShaderPushConstants {
+                            stages: pc.stages | stage,
+                            offset: start,
+                            size: end - start,
+                        }
+                    }
+                    None => ShaderPushConstants {
+                        stages: *stage,
+                        offset,
+                        size,
+                    },
+                });
+            }
+        }
+        interface.bindings.sort_by_key(|b| b.binding);
+        Ok(interface)
+    }
+}
+
+pub fn vk_descriptor_type(descriptor_type: DescriptorType) -> VkDescriptorType {
+    match descriptor_type {
+        DescriptorType::Sampler => VK_DESCRIPTOR_TYPE_SAMPLER,
+        DescriptorType::CombinedImageSampler => VK_DESCRIPTOR_TYPE_COMBINED_IMAGE_SAMPLER,
+        DescriptorType::SampledImage => VK_DESCRIPTOR_TYPE_SAMPLED_IMAGE,
+        DescriptorType::StorageImage => VK_DESCRIPTOR_TYPE_STORAGE_IMAGE,
+        DescriptorType::UniformTexelBuffer => VK_DESCRIPTOR_TYPE_UNIFORM_TEXEL_BUFFER,
+        DescriptorType::StorageTexelBuffer => VK_DESCRIPTOR_TYPE_STORAGE_TEXEL_BUFFER,
+        DescriptorType::UniformBuffer => VK_DESCRIPTOR_TYPE_UNIFORM_BUFFER,
+        DescriptorType::StorageBuffer => VK_DESCRIPTOR_TYPE_STORAGE_BUFFER,
+        DescriptorType::InputAttachment => VK_DESCRIPTOR_TYPE_INPUT_ATTACHMENT,
+    }
+}
+
 //#[derive(Default)]
 pub struct VkContext {
     pub generation: usize,
@@ -1038,6 +1356,8 @@ pub struct VkContext {
     pub ubo: Buffer,  // Uniform Buffer Object
     pub ssbo: Buffer, // Shader Storage Buffer Object
 
+    pub shader_interface: ShaderInterface, // Reflected from the current shaders
+    pub ubo_range: VkDeviceSize,
     pub descriptor_set_layout: VkDescriptorSetLayout,
     pub descriptor_pool: VkDescriptorPool,
     pub descriptor_sets: [VkDescriptorSet; MAX_TEXTURES * MAX_FRAMES_IN_FLIGHT],
@@ -1094,6 +1414,8 @@ impl Default for VkContext {
             texture_sampler: VkSampler::default(),
             ubo: Buffer::default(),
             ssbo: Buffer::default(),
+            shader_interface: ShaderInterface::default(),
+            ubo_range: 0,
             descriptor_set_layout: VkDescriptorSetLayout::default(),
             descriptor_pool: VkDescriptorPool::default(),
             descriptor_sets: [VkDescriptorSet::default(); MAX_TEXTURES * MAX_FRAMES_IN_FLIGHT],
@@ -1159,7 +1481,7 @@ impl VkContext {
         vk_ctx.create_ubo(ubo_size);
         vk_map_memory_copy(vk_ctx.device, vk_ctx.ubo.memory, &global_state, ubo_size);
 
-        // TODO: Sync this with the shaders
+        vk_ctx.load_shader_interface();
         vk_ctx.create_descriptor_set_layout();
         vk_ctx.create_pipeline_layout();
         vk_ctx.create_graphics_pipeline();
@@ -1320,27 +1642,58 @@ impl VkContext {
         clear_color: Option<Color>,
         material_ids: &[u32],
         rotations: &[u32],
+    ) {
+        let uvs = vec![FULL_UV; render_commands.len()];
+        self.render_sprite_uv(render_commands, clear_color, material_ids, rotations, &uvs);
+    }
+
+    /// Like `render_sprite` but every sprite samples only the `uvs` region ([u0, v0, u1, v1]) of its
+    /// material, used to draw sprites out of a shared atlas texture (see `atlas::SpriteSheet`).
+    pub fn render_sprite_uv<RenderCommand>(
+        &mut self,
+        render_commands: &[RenderCommand],
+        clear_color: Option<Color>,
+        material_ids: &[u32],
+        rotations: &[u32],
+        uvs: &[[f32; 4]],
+    ) {
+        let distance_ranges = vec![0.0; render_commands.len()];
+        self.render_sprite_sdf(render_commands, clear_color, material_ids, rotations, uvs, &distance_ranges);
+    }
+
+    /// Like `render_sprite_uv` but the materials of the sprites with a `distance_ranges` above 0 are
+    /// signed distance fields, rendered with that distance range in pixels (see `glyph_sdf::SdfAtlas`).
+    pub fn render_sprite_sdf<RenderCommand>(
+        &mut self,
+        render_commands: &[RenderCommand],
+        clear_color: Option<Color>,
+        material_ids: &[u32],
+        rotations: &[u32],
+        uvs: &[[f32; 4]],
+        distance_ranges: &[f32],
     ) {
         if let Some(image_index) = self.render_begin(clear_color) {
             unsafe {
                 let cmd = self.command_buffers[self.current_frame];
 
                 let layout = self.pipeline_layout;
+                // The shaders lay out the push constants as SpritePushConstants, push the reflected range of it.
+                // Older sprite shaders only declare a prefix of the push constants.
+                let push_constants = self.shader_interface.push_constants.expect("The shader has no push constants");
+                let offset = push_constants.offset;
+                let end = (offset + push_constants.size).min(mem::size_of::<SpritePushConstants>() as u32);
+                assert!(offset < end, "The push constants of the shader start after SpritePushConstants");
 
                 for i in 0..render_commands.len() {
-                    let rotation_id = rotations[i];
-                    // TODO: Simplify this
-                    #[rustfmt::skip]
-                    let mut v = (
-                        0.0_f32, 0.0_f32, // offset
-                        0.0_f32, 0.0_f32, // size
-                        0.0_f32, // z
-                        0.0_f32, 0.0_f32, 0.0_f32, 0.0_f32, // color
-                        rotation_id
-                    );
-                    ptr::copy(&render_commands[i] as *const _ as *const f32, &mut v as *mut _ as *mut f32, 9);
-                    let v = &v as *const _ as *const c_void;
-                    vkCmdPushConstants(cmd, self.pipeline_layout, VK_SHADER_STAGE_VERTEX_BIT.into(), 0, 10 * 4, v);
+                    let mut v = SpritePushConstants {
+                        rotation_id: rotations[i],
+                        uv: uvs[i],
+                        distance_range: distance_ranges[i],
+                        ..SpritePushConstants::default()
+                    };
+                    ptr::copy(&render_commands[i] as *const _ as *const f32, v.rect.as_mut_ptr(), 9);
+                    let v = (&v as *const SpritePushConstants as *const u8).add(offset as usize) as *const c_void;
+                    vkCmdPushConstants(cmd, layout, push_constants.stages.into(), offset, end - offset, v);
 
                     let img_idx = material_ids[i] as usize;
                     let dsc_set = self.descriptor_sets[img_idx * MAX_FRAMES_IN_FLIGHT + self.current_frame];
@@ -1372,7 +1725,7 @@ impl VkContext {
     ) {
         match self.shader_id.as_str() {
             "simple" => self.render_simple(render_commands, clear_color),
-            "sprite" => self.render_sprite(render_commands, clear_color, material_ids, rotations),
+            "sprite" | "atlas" => self.render_sprite(render_commands, clear_color, material_ids, rotations),
             _ => panic!("Unsupported shader"),
         }
     }
@@ -1640,6 +1993,15 @@ impl VkContext {
         println!("Recreating Swapchain");
         unsafe { vkDeviceWaitIdle(self.device) };
         self.cleanup_swapchain();
+        if self.load_shader_interface() {
+            // The descriptor sets are allocated with the layout, so they are rebuilt too
+            self.destroy_descriptor_pool();
+            self.destroy_descriptor_set_layout();
+            self.create_descriptor_set_layout();
+            self.create_descriptor_pool();
+            self.allocate_descriptor_sets();
+            self.write_descriptor_sets();
+        }
         self.create_swapchain();
         self.create_render_pass();
         self.create_pipeline_layout();
@@ -1666,13 +2028,45 @@ impl VkContext {
         vk_get_swapchain_images_khr(self.device, self.swapchain)
     }
 
+    /// Compiles and reflects the current shaders. Returns true when their descriptor bindings changed.
+    fn load_shader_interface(&mut self) -> bool {
+        std::process::Command::new("/bin/sh").arg("compile_shaders.sh").status().unwrap();
+        let mut reflections = vec![];
+        for (stage, ext) in [(VK_SHADER_STAGE_VERTEX_BIT, "vert"), (VK_SHADER_STAGE_FRAGMENT_BIT, "frag")] {
+            let path = format!("assets/shaders/{}.{}.spv", self.shader_id, ext);
+            let code = fs::read(&path).unwrap_or_else(|e| panic!("{}: {}", path, e));
+            let module = ShaderModule::try_from(code.as_slice()).unwrap_or_else(|e| panic!("{}: {}", path, e));
+            // Reject invalid modules here rather than handing them to the driver
+            module.validate().unwrap_or_else(|e| panic!("{}: {}", path, e));
+            reflections.push((stage, module.reflect().unwrap_or_else(|e| panic!("{}: {}", path, e))));
+        }
+        let stages = reflections.iter().map(|(stage, r)| (*stage, r)).collect::<Vec<_>>();
+        let interface = ShaderInterface::from_stages(&stages)
+            .unwrap_or_else(|e| panic!("Shader '{}' is not supported: {}", self.shader_id, e));
+        for b in &interface.bindings {
+            // Only the resources the context owns can be bound
+            let supported = matches!(
+                b.descriptor_type,
+                DescriptorType::UniformBuffer | DescriptorType::StorageBuffer | DescriptorType::CombinedImageSampler
+            );
+            if !supported || b.count != 1 {
+                panic!("Shader '{}': unsupported descriptor at binding {}: {:?}", self.shader_id, b.binding, b);
+            }
+        }
+
+        let bindings_changed = interface.bindings != self.shader_interface.bindings;
+        self.shader_interface = interface;
+        bindings_changed
+    }
+
     fn create_descriptor_set_layout(&mut self) {
         unsafe {
-            let layout_bindings = [
-                layout_binding(0, VK_DESCRIPTOR_TYPE_UNIFORM_BUFFER, VK_SHADER_STAGE_VERTEX_BIT),
-                layout_binding(1, VK_DESCRIPTOR_TYPE_STORAGE_BUFFER, VK_SHADER_STAGE_VERTEX_BIT),
-                layout_binding(2, VK_DESCRIPTOR_TYPE_COMBINED_IMAGE_SAMPLER, VK_SHADER_STAGE_FRAGMENT_BIT),
-            ];
+            let layout_bindings = self
+                .shader_interface
+                .bindings
+                .iter()
+                .map(|b| layout_binding(b.binding as usize, vk_descriptor_type(b.descriptor_type), b.stages))
+                .collect::<Vec<_>>();
             check!(vkCreateDescriptorSetLayout(
                 self.device,
                 &VkDescriptorSetLayoutCreateInfo {
@@ -1765,17 +2159,23 @@ impl VkContext {
 
     fn create_pipeline_layout(&mut self) {
         unsafe {
+            let push_constants = self
+                .shader_interface
+                .push_constants
+                .iter()
+                .map(|pc| VkPushConstantRange {
+                    stageFlags: pc.stages.into(),
+                    offset: pc.offset,
+                    size: pc.size,
+                })
+                .collect::<Vec<_>>();
             check!(vkCreatePipelineLayout(
                 self.device,
                 &VkPipelineLayoutCreateInfo {
                     setLayoutCount: 1,
                     pSetLayouts: &self.descriptor_set_layout,
-                    pushConstantRangeCount: 1,
-                    pPushConstantRanges: &VkPushConstantRange {
-                        stageFlags: VK_SHADER_STAGE_VERTEX_BIT.into(),
-                        offset: 0,
-                        size: 10 * 4, // vec2 offset + vec2 size + z + color + materialId + rotationId
-                    },
+                    pushConstantRangeCount: push_constants.len() as u32,
+                    pPushConstantRanges: push_constants.as_ptr(),
                     ..VkPipelineLayoutCreateInfo::default()
                 },
                 self.allocator,
@@ -1790,7 +2190,6 @@ impl VkContext {
 
     fn create_graphics_pipeline(&mut self) {
         unsafe {
-            std::process::Command::new("/bin/sh").arg("compile_shaders.sh").status().unwrap();
             let vs_path = format!("assets/shaders/{}.vert.spv", self.shader_id);
             let fs_path = format!("assets/shaders/{}.frag.spv", self.shader_id);
             let vs_code = fs::read(vs_path).expect("Failed to load vertex shader");
@@ -1992,14 +2391,21 @@ impl VkContext {
 
     fn create_descriptor_pool(&mut self) {
         unsafe {
-            let pool_sizes = [
-                VkDescriptorPoolSize::new(VK_DESCRIPTOR_TYPE_UNIFORM_BUFFER, MAX_TEXTURES * MAX_FRAMES_IN_FLIGHT),
-                VkDescriptorPoolSize::new(VK_DESCRIPTOR_TYPE_STORAGE_BUFFER, MAX_TEXTURES * MAX_FRAMES_IN_FLIGHT),
-                VkDescriptorPoolSize::new(
-                    VK_DESCRIPTOR_TYPE_COMBINED_IMAGE_SAMPLER,
-                    MAX_TEXTURES * MAX_FRAMES_IN_FLIGHT,
-                ),
-            ];
+            let mut pool_sizes = self
+                .shader_interface
+                .bindings
+                .iter()
+                .map(|b| {
+                    VkDescriptorPoolSize::new(
+                        vk_descriptor_type(b.descriptor_type),
+                        b.count as usize * MAX_TEXTURES * MAX_FRAMES_IN_FLIGHT,
+                    )
+                })
+                .collect::<Vec<_>>();
+            if pool_sizes.is_empty() {
+                // A pool needs at least one size, even if the sets are empty
+                pool_sizes.push(VkDescriptorPoolSize::new(VK_DESCRIPTOR_TYPE_UNIFORM_BUFFER, 1));
+            }
             check!(vkCreateDescriptorPool(
                 self.device,
                 &VkDescriptorPoolCreateInfo {
@@ -2099,6 +2505,9 @@ impl VkContext {
             VK_IMAGE_USAGE_DEPTH_STENCIL_ATTACHMENT_BIT.into(),
             VK_MEMORY_PROPERTY_DEVICE_LOCAL_BIT.into(),
             VK_IMAGE_ASPECT_DEPTH_BIT.into(),
+            1,
+            1,
+            VK_IMAGE_VIEW_TYPE_2D,
         );
     }
 
@@ -2143,6 +2552,7 @@ impl VkContext {
                     maxAnisotropy: { self.physical_device_meta.props.limits.maxSamplerAnisotropy },
                     compareOp: VK_COMPARE_OP_ALWAYS,
                     borderColor: VK_BORDER_COLOR_INT_OPAQUE_BLACK,
+                    maxLod: VK_LOD_CLAMP_NONE,
                     ..VkSamplerCreateInfo::default()
                 },
                 self.allocator,
@@ -2159,7 +2569,7 @@ impl VkContext {
         vk_create_image_view(self.device, image, format, aspect.value)
     }
 
-    fn create_buffer(&self, size: usize, usage: VkBufferUsageFlags, properties: VkMemoryPropertyFlags) -> Buffer {
+    pub fn create_buffer(&self, size: usize, usage: VkBufferUsageFlags, properties: VkMemoryPropertyFlags) -> Buffer {
         unsafe {
             let mut buffer = VkBuffer::default();
             check!(vkCreateBuffer(
@@ -2262,52 +2672,54 @@ impl VkContext {
     }
 
     pub fn update_descriptor_sets<G>(&mut self, global_state: G) {
+        self.ubo_range = mem::size_of_val(&global_state) as VkDeviceSize;
+        self.write_descriptor_sets();
+    }
+
+    // Every binding of the shader interface gets the resource of its type: the UBO, the SSBO or
+    // the texture of the set.
+    fn write_descriptor_sets(&mut self) {
         unsafe {
+            let ubo_info = VkDescriptorBufferInfo {
+                buffer: self.ubo.buffer,
+                offset: 0,
+                range: self.ubo_range,
+            };
+            let ssbo_info = VkDescriptorBufferInfo {
+                buffer: self.ssbo.buffer,
+                offset: 0,
+                range: VK_WHOLE_SIZE,
+            };
             for img_idx in 0..self.texture_images.len() {
-                let texture_image = &self.texture_images[img_idx];
+                let image_info = VkDescriptorImageInfo {
+                    sampler: self.texture_sampler,
+                    imageView: self.texture_images[img_idx].view,
+                    imageLayout: VK_IMAGE_LAYOUT_SHADER_READ_ONLY_OPTIMAL,
+                };
 
                 for i in 0..MAX_FRAMES_IN_FLIGHT {
-                    let writes = [
-                        VkWriteDescriptorSet {
-                            dstSet: self.descriptor_sets[img_idx * MAX_FRAMES_IN_FLIGHT + i],
-                            dstBinding: 0,
-                            dstArrayElement: 0,
-                            descriptorCount: 1,
-                            descriptorType: VK_DESCRIPTOR_TYPE_UNIFORM_BUFFER,
-                            pBufferInfo: &VkDescriptorBufferInfo {
-                                buffer: self.ubo.buffer,
-                                offset: 0,
-                                range: mem::size_of_val(&global_state) as VkDeviceSize,
-                            },
-                            ..VkWriteDescriptorSet::default()
-                        },
-                        VkWriteDescriptorSet {
-                            dstSet: self.descriptor_sets[img_idx * MAX_FRAMES_IN_FLIGHT + i],
-                            dstBinding: 1,
-                            dstArrayElement: 0,
-                            descriptorCount: 1,
-                            descriptorType: VK_DESCRIPTOR_TYPE_STORAGE_BUFFER,
-                            pBufferInfo: &VkDescriptorBufferInfo {
-                                buffer: self.ssbo.buffer,
-                                offset: 0,
-                                range: VK_WHOLE_SIZE,
-                            },
-                            ..VkWriteDescriptorSet::default()
-                        },
-                        VkWriteDescriptorSet {
-                            dstSet: self.descriptor_sets[img_idx * MAX_FRAMES_IN_FLIGHT + i],
-                            dstBinding: 2,
-                            dstArrayElement: 0,
-                            descriptorCount: 1,
-                            descriptorType: VK_DESCRIPTOR_TYPE_COMBINED_IMAGE_SAMPLER,
-                            pImageInfo: &VkDescriptorImageInfo {
-                                sampler: self.texture_sampler,
-                                imageView: texture_image.view,
-                                imageLayout: VK_IMAGE_LAYOUT_SHADER_READ_ONLY_OPTIMAL,
-                            },
-                            ..VkWriteDescriptorSet::default()
-                        },
-                    ];
+                    let writes = self
+                        .shader_interface
+                        .bindings
+                        .iter()
+                        .map(|b| {
+                            let mut write = VkWriteDescriptorSet {
+                                dstSet: self.descriptor_sets[img_idx * MAX_FRAMES_IN_FLIGHT + i],
+                                dstBinding: b.binding,
+                                dstArrayElement: 0,
+                                descriptorCount: 1,
+                                descriptorType: vk_descriptor_type(b.descriptor_type),
+                                ..VkWriteDescriptorSet::default()
+                            };
+                            match b.descriptor_type {
+                                DescriptorType::UniformBuffer => write.pBufferInfo = &ubo_info,
+                                DescriptorType::StorageBuffer => write.pBufferInfo = &ssbo_info,
+                                DescriptorType::CombinedImageSampler => write.pImageInfo = &image_info,
+                                t => unreachable!("{:?}", t), // Rejected by load_shader_interface
+                            }
+                            write
+                        })
+                        .collect::<Vec<_>>();
 
                     vkUpdateDescriptorSets(self.device, writes.len() as u32, writes.as_ptr(), 0, ptr::null());
                 }
@@ -2315,62 +2727,201 @@ impl VkContext {
         }
     }
 
-    pub fn load_texture_image<P: AsRef<str>>(&mut self, path: P) {
-        self.texture_images.push(self.load_texture_image_internal(path));
+    pub fn load_texture_image<P: AsRef<str>>(&mut self, path: P) -> Result<(), String> {
+        let image = self.load_texture_image_internal(path)?;
+        self.texture_images.push(image);
+        Ok(())
     }
 
-    fn load_texture_image_internal<P: AsRef<str>>(&self, path: P) -> Image {
-        let mut width = 0;
-        let mut height = 0;
-        let mut channels = 0;
-        let mut path = path.as_ref().to_string();
-        path.push(0 as char);
-        let pixels = unsafe {
-            let raw = stbi_load(path.as_ptr() as *const i8, &mut width, &mut height, &mut channels, 4);
-            assert!(!raw.is_null(), "{}", path);
-            let image_size = width * height * 4;
+    /// Loads a texture with its full mip chain, for textures drawn smaller than their size.
+    pub fn load_texture_image_mipmapped<P: AsRef<str>>(&mut self, path: P) -> Result<(), String> {
+        let image = RgbaImage::load(path.as_ref())?;
+        let texture = self.create_texture_image_mipmaps(&image.mipmaps());
+        self.texture_images.push(texture);
+        Ok(())
+    }
 
-            let mut pixels: Vec<u8> = vec![0; image_size as usize];
-            ptr::copy(raw, pixels.as_mut_ptr(), image_size as usize);
+    /// Loads a KTX2 texture, see `create_texture_image_ktx2`.
+    pub fn load_texture_ktx2<P: AsRef<str>>(&mut self, path: P) -> Result<(), String> {
+        let ktx = Ktx2::load(path.as_ref())?;
+        let image = self.create_texture_image_ktx2(&ktx)?;
+        self.texture_images.push(image);
+        Ok(())
+    }
 
-            stbi_image_free(raw as *mut c_void);
-            pixels
-        };
-        self.create_texture_image(&pixels, width as usize, height as usize)
+    fn load_texture_image_internal<P: AsRef<str>>(&self, path: P) -> Result<Image, String> {
+        let (pixels, width, height) = load_image_rgba(path)?;
+        Ok(self.create_texture_image(&pixels, width, height))
+    }
+
+    /// Uploads the pages of a sprite sheet as textures, the sprites of the sheet then refer to them
+    /// through `SpriteSheet::material`. Remember to call `update_descriptor_sets` afterwards.
+    pub fn load_sprite_sheet(&mut self, sheet: &mut SpriteSheet, pages: &[AtlasPage]) {
+        assert_eq!(sheet.pages.len(), pages.len());
+        sheet.material_base = self.texture_images.len() as u32;
+        for page in pages {
+            self.texture_images.push(self.create_texture_image(
+                &page.pixels,
+                page.width as usize,
+                page.height as usize,
+            ));
+        }
+    }
+
+    /// Uploads the pages of a distance field atlas as textures, the glyphs of the atlas then use
+    /// `material_base + page` as material. Remember to call `update_descriptor_sets` afterwards.
+    pub fn load_sdf_atlas(&mut self, atlas: &mut SdfAtlas) {
+        atlas.material_base = self.texture_images.len() as u32;
+        for page in &atlas.pages {
+            // Distances are linear, not sRGB encoded
+            self.texture_images.push(self.create_texture_image_format(
+                &page.pixels,
+                page.width as usize,
+                page.height as usize,
+                VK_FORMAT_R8G8B8A8_UNORM,
+            ));
+        }
     }
 
     pub fn create_texture_image(&self, pixels: &[u8], width: usize, height: usize) -> Image {
-        let image_size = width * height * 4;
+        self.create_texture_image_format(pixels, width, height, VK_FORMAT_R8G8B8A8_SRGB)
+    }
+
+    pub fn create_texture_image_format(&self, pixels: &[u8], width: usize, height: usize, format: VkFormat) -> Image {
+        self.create_texture_image_levels(&[(pixels, width as u32, height as u32)], format)
+    }
+
+    /// Uploads a mip chain (see `RgbaImage::mipmaps`), as sRGB or linear depending on the color space of the
+    /// images.
+    pub fn create_texture_image_mipmaps(&self, levels: &[RgbaImage]) -> Image {
+        let format = match levels[0].color_space {
+            ColorSpace::Srgb => VK_FORMAT_R8G8B8A8_SRGB,
+            ColorSpace::Linear => VK_FORMAT_R8G8B8A8_UNORM,
+        };
+        let levels: Vec<(&[u8], u32, u32)> =
+            levels.iter().map(|level| (&level.pixels[..], level.width, level.height)).collect();
+        self.create_texture_image_levels(&levels, format)
+    }
+
+    /// Whether the device can sample optimally tiled images of `format`.
+    pub fn supports_texture_format(&self, format: VkFormat) -> bool {
+        let format_props = vk_get_physical_device_format_properties(self.physical_device, format);
+        format_props.optimalTilingFeatures.value & VK_FORMAT_FEATURE_SAMPLED_IMAGE_BIT != 0
+    }
+
+    /// Uploads a KTX2 texture with its mip levels, layers and faces as a 2D, 2D array, cube or cube array image.
+    /// Formats the device can't sample are decoded on the CPU for BC1 to BC5 and rejected otherwise.
+    pub fn create_texture_image_ktx2(&self, ktx: &Ktx2) -> Result<Image, String> {
+        let view_type = match (ktx.is_cubemap(), ktx.is_array) {
+            (false, false) => VK_IMAGE_VIEW_TYPE_2D,
+            (false, true) => VK_IMAGE_VIEW_TYPE_2D_ARRAY,
+            (true, false) => VK_IMAGE_VIEW_TYPE_CUBE,
+            (true, true) => VK_IMAGE_VIEW_TYPE_CUBE_ARRAY,
+        };
+        let layers = ktx.layers * ktx.faces;
+        if self.supports_texture_format(ktx.format) {
+            let levels: Vec<(&[u8], u32, u32)> = (0..ktx.levels.len())
+                .map(|level| {
+                    let (width, height) = ktx.level_size(level);
+                    (&ktx.levels[level][..], width, height)
+                })
+                .collect();
+            return Ok(self.create_texture_image_layers(&levels, ktx.format, layers, view_type));
+        }
+
+        let (bc_format, format) = bc_fallback(ktx.format)
+            .ok_or_else(|| format!("{:?} textures are not supported by the device", ktx.format))?;
+        let mut decoded = vec![];
+        for level in 0..ktx.levels.len() {
+            let (width, height) = ktx.level_size(level);
+            let mut pixels = vec![];
+            for layer in 0..ktx.layers {
+                for face in 0..ktx.faces {
+                    let image = ktx.image(level, layer, face);
+                    pixels.extend(decode_bc(bc_format, image, width as usize, height as usize)?);
+                }
+            }
+            decoded.push((pixels, width, height));
+        }
+        let levels: Vec<(&[u8], u32, u32)> =
+            decoded.iter().map(|(pixels, width, height)| (&pixels[..], *width, *height)).collect();
+        Ok(self.create_texture_image_layers(&levels, format, layers, view_type))
+    }
+
+    /// Uploads the pixels of every mip level, (data, width, height), from the full size image down.
+    pub fn create_texture_image_levels(&self, levels: &[(&[u8], u32, u32)], format: VkFormat) -> Image {
+        self.create_texture_image_layers(levels, format, 1, VK_IMAGE_VIEW_TYPE_2D)
+    }
+
+    /// Uploads the mip levels of an array texture or cubemap, each holding the images of `layers` layers one after
+    /// the other, where the 6 faces of a cubemap count as layers.
+    pub fn create_texture_image_layers(
+        &self,
+        levels: &[(&[u8], u32, u32)],
+        format: VkFormat,
+        layers: u32,
+        view_type: VkImageViewType,
+    ) -> Image {
+        // Buffer offsets must be multiples of 4 and of the texel or block size, like 12 for R8G8B8 or 48 for R32G32B32
+        let size = texel_block(format).map_or(4, |(_, _, size)| size);
+        let alignment = match size % 4 {
+            0 => size,
+            2 => size * 2,
+            _ => size * 4,
+        };
+        let mut offset = 0;
+        let regions: Vec<(usize, u32, u32)> = levels
+            .iter()
+            .map(|(data, width, height)| {
+                let start = offset;
+                offset = (offset + data.len()).next_multiple_of(alignment);
+                (start, *width, *height)
+            })
+            .collect();
+        let image_size = offset;
         let mut staging_buffer = self.create_buffer(
             image_size,
             VK_BUFFER_USAGE_TRANSFER_SRC_BIT.into(),
             (VK_MEMORY_PROPERTY_HOST_VISIBLE_BIT | VK_MEMORY_PROPERTY_HOST_COHERENT_BIT).into(),
         );
-        vk_map_memory_copy(self.device, staging_buffer.memory, pixels.as_ptr(), image_size as usize);
+        let mut data = vec![0u8; image_size];
+        for ((level, _, _), (offset, _, _)) in levels.iter().zip(&regions) {
+            data[*offset..offset + level.len()].copy_from_slice(level);
+        }
+        vk_map_memory_copy(self.device, staging_buffer.memory, data.as_ptr(), image_size);
 
+        let (_, width, height) = levels[0];
+        let mip_levels = levels.len() as u32;
         let texture_image = self.create_image(
-            (width as u32, height as u32),
-            VK_FORMAT_R8G8B8A8_SRGB,
+            (width, height),
+            format,
             VK_IMAGE_TILING_OPTIMAL,
             (VK_IMAGE_USAGE_TRANSFER_DST_BIT | VK_IMAGE_USAGE_SAMPLED_BIT).into(),
             VK_MEMORY_PROPERTY_DEVICE_LOCAL_BIT.into(),
             VK_IMAGE_ASPECT_COLOR_BIT.into(),
+            mip_levels,
+            layers,
+            view_type,
         );
 
         self.transition_image_layout(
             texture_image.image,
-            VK_FORMAT_R8G8B8A8_SRGB,
+            format,
             VK_IMAGE_LAYOUT_UNDEFINED,
             VK_IMAGE_LAYOUT_TRANSFER_DST_OPTIMAL,
+            mip_levels,
+            layers,
         );
 
-        self.copy_buffer_to_image(staging_buffer.buffer, texture_image.image, width as u32, height as u32);
+        self.copy_buffer_to_image(staging_buffer.buffer, texture_image.image, &regions, layers);
 
         self.transition_image_layout(
             texture_image.image,
-            VK_FORMAT_R8G8B8A8_SRGB,
+            format,
             VK_IMAGE_LAYOUT_TRANSFER_DST_OPTIMAL,
             VK_IMAGE_LAYOUT_SHADER_READ_ONLY_OPTIMAL,
+            mip_levels,
+            layers,
         );
 
         staging_buffer.destroy();
@@ -2378,6 +2929,7 @@ impl VkContext {
         texture_image
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn create_image(
         &self,
         dimensions: (u32, u32),
@@ -2386,12 +2938,21 @@ impl VkContext {
         usage: VkImageUsageFlags,
         mem_props: VkMemoryPropertyFlags,
         aspect: VkImageAspectFlags,
+        mip_levels: u32,
+        layers: u32,
+        view_type: VkImageViewType,
     ) -> Image {
+        let cube = matches!(view_type, VK_IMAGE_VIEW_TYPE_CUBE | VK_IMAGE_VIEW_TYPE_CUBE_ARRAY);
         unsafe {
             let mut image = VkImage::default();
             check!(vkCreateImage(
                 self.device,
                 &VkImageCreateInfo {
+                    flags: if cube {
+                        VK_IMAGE_CREATE_CUBE_COMPATIBLE_BIT.into()
+                    } else {
+                        0.into()
+                    },
                     imageType: VK_IMAGE_TYPE_2D,
                     format,
                     extent: VkExtent3D {
@@ -2399,8 +2960,8 @@ impl VkContext {
                         height: dimensions.1,
                         depth: 1,
                     },
-                    mipLevels: 1,
-                    arrayLayers: 1,
+                    mipLevels: mip_levels,
+                    arrayLayers: layers,
                     samples: VK_SAMPLE_COUNT_1_BIT.into(), // TODO: VkSampleCountFlagBits
                     tiling,
                     usage,
@@ -2429,7 +2990,8 @@ impl VkContext {
 
             check!(vkBindImageMemory(self.device, image, memory, 0));
 
-            let view = self.create_image_view(image, format, aspect);
+            let view =
+                vk_create_image_view_layers(self.device, image, view_type, format, aspect.value, mip_levels, layers);
 
             Image {
                 device: self.device,
@@ -2446,7 +3008,29 @@ impl VkContext {
         self.end_single_time_commands(command_buffer);
     }
 
-    fn copy_buffer_to_image(&self, buffer: VkBuffer, image: VkImage, width: u32, height: u32) {
+    /// Copies one region per mip level, (buffer offset, width, height), each holding the images of `layers` layers.
+    fn copy_buffer_to_image(&self, buffer: VkBuffer, image: VkImage, levels: &[(usize, u32, u32)], layers: u32) {
+        let regions: Vec<VkBufferImageCopy> = levels
+            .iter()
+            .enumerate()
+            .map(|(level, &(offset, width, height))| VkBufferImageCopy {
+                bufferOffset: offset as VkDeviceSize,
+                bufferRowLength: 0,
+                bufferImageHeight: 0,
+                imageSubresource: VkImageSubresourceLayers {
+                    aspectMask: VK_IMAGE_ASPECT_COLOR_BIT.into(),
+                    mipLevel: level as u32,
+                    baseArrayLayer: 0,
+                    layerCount: layers,
+                },
+                imageOffset: VkOffset3D::default(),
+                imageExtent: VkExtent3D {
+                    width,
+                    height,
+                    depth: 1,
+                },
+            })
+            .collect();
         unsafe {
             let command_buffer = self.begin_single_time_commands();
             vkCmdCopyBufferToImage(
@@ -2454,24 +3038,8 @@ impl VkContext {
                 buffer,
                 image,
                 VK_IMAGE_LAYOUT_TRANSFER_DST_OPTIMAL,
-                1,
-                &VkBufferImageCopy {
-                    bufferOffset: 0,
-                    bufferRowLength: 0,
-                    bufferImageHeight: 0,
-                    imageSubresource: VkImageSubresourceLayers {
-                        aspectMask: VK_IMAGE_ASPECT_COLOR_BIT.into(),
-                        mipLevel: 0,
-                        baseArrayLayer: 0,
-                        layerCount: 1,
-                    },
-                    imageOffset: VkOffset3D::default(),
-                    imageExtent: VkExtent3D {
-                        width,
-                        height,
-                        depth: 1,
-                    },
-                },
+                regions.len() as u32,
+                regions.as_ptr(),
             );
             self.end_single_time_commands(command_buffer);
         }
@@ -2518,6 +3086,8 @@ impl VkContext {
         _format: VkFormat,
         old_layout: VkImageLayout,
         new_layout: VkImageLayout,
+        mip_levels: u32,
+        layers: u32,
     ) {
         unsafe {
             let command_buffer = self.begin_single_time_commands();
@@ -2562,8 +3132,8 @@ impl VkContext {
                     image,
                     subresourceRange: VkImageSubresourceRange {
                         aspectMask: VK_IMAGE_ASPECT_COLOR_BIT.into(),
-                        levelCount: 1,
-                        layerCount: 1,
+                        levelCount: mip_levels,
+                        layerCount: layers,
                         ..VkImageSubresourceRange::default()
                     },
                     ..VkImageMemoryBarrier::default()
@@ -2575,6 +3145,25 @@ impl VkContext {
 }
 
 // Utility Functions
+/// BCn format of `format` for `decode_bc`, with the uncompressed format of the decoded pixels
+fn bc_fallback(format: VkFormat) -> Option<(BcFormat, VkFormat)> {
+    Some(match format {
+        VK_FORMAT_BC1_RGB_UNORM_BLOCK => (BcFormat::Bc1Rgb, VK_FORMAT_R8G8B8A8_UNORM),
+        VK_FORMAT_BC1_RGB_SRGB_BLOCK => (BcFormat::Bc1Rgb, VK_FORMAT_R8G8B8A8_SRGB),
+        VK_FORMAT_BC1_RGBA_UNORM_BLOCK => (BcFormat::Bc1Rgba, VK_FORMAT_R8G8B8A8_UNORM),
+        VK_FORMAT_BC1_RGBA_SRGB_BLOCK => (BcFormat::Bc1Rgba, VK_FORMAT_R8G8B8A8_SRGB),
+        VK_FORMAT_BC2_UNORM_BLOCK => (BcFormat::Bc2, VK_FORMAT_R8G8B8A8_UNORM),
+        VK_FORMAT_BC2_SRGB_BLOCK => (BcFormat::Bc2, VK_FORMAT_R8G8B8A8_SRGB),
+        VK_FORMAT_BC3_UNORM_BLOCK => (BcFormat::Bc3, VK_FORMAT_R8G8B8A8_UNORM),
+        VK_FORMAT_BC3_SRGB_BLOCK => (BcFormat::Bc3, VK_FORMAT_R8G8B8A8_SRGB),
+        VK_FORMAT_BC4_UNORM_BLOCK => (BcFormat::Bc4, VK_FORMAT_R8G8B8A8_UNORM),
+        VK_FORMAT_BC4_SNORM_BLOCK => (BcFormat::Bc4Signed, VK_FORMAT_R8G8B8A8_SNORM),
+        VK_FORMAT_BC5_UNORM_BLOCK => (BcFormat::Bc5, VK_FORMAT_R8G8B8A8_UNORM),
+        VK_FORMAT_BC5_SNORM_BLOCK => (BcFormat::Bc5Signed, VK_FORMAT_R8G8B8A8_SNORM),
+        _ => return None,
+    })
+}
+
 fn get_memory_type(physical_device: VkPhysicalDevice, type_filter: u32, properties: VkMemoryPropertyFlags) -> u32 {
     let mem_properties = vk_get_physical_device_memory_properties(physical_device);
     for i in 0..mem_properties.memoryTypeCount {
@@ -2758,6 +3347,12 @@ pub fn vk_get_physical_device_surface_present_modes_khr(
     }
 }
 
+/// Loads a PNG, BMP, TGA, QOI, PPM or PGM file as tightly packed RGBA8 pixels, returns (pixels, width, height).
+pub fn load_image_rgba<P: AsRef<str>>(path: P) -> Result<(Vec<u8>, usize, usize), String> {
+    let image = RgbaImage::load(path.as_ref())?;
+    Ok((image.pixels, image.width as usize, image.height as usize))
+}
+
 pub fn vk_map_memory_copy<T>(device: VkDevice, memory: VkDeviceMemory, data: *const T, size: usize) {
     unsafe {
         if size > 0 {
@@ -3261,6 +3856,20 @@ impl Default for VkGraphicsPipelineCreateInfo {
     }
 }
 
+impl Default for VkComputePipelineCreateInfo {
+    fn default() -> Self {
+        Self {
+            sType: VK_STRUCTURE_TYPE_COMPUTE_PIPELINE_CREATE_INFO,
+            pNext: ptr::null(),
+            flags: 0.into(),
+            stage: VkPipelineShaderStageCreateInfo::default(),
+            layout: VkPipelineLayout::default(),
+            basePipelineHandle: VkPipeline::default(),
+            basePipelineIndex: 0,
+        }
+    }
+}
+
 impl Default for VkPipelineShaderStageCreateInfo {
     fn default() -> Self {
         Self {
@@ -3721,3 +4330,119 @@ extern "C" fn debug_callback(
         VK_FALSE
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(cmd: &RenderCommand) -> (f32, f32, f32, f32) {
+        let RenderCommand::Rect(x, y, w, h, ..) = *cmd;
+        (x, y, w, h)
+    }
+
+    #[test]
+    fn nine_slice_stretch() {
+        let slice = NineSlice {
+            uv: [0.0, 0.0, 1.0, 1.0],
+            size: (16.0, 16.0),
+            insets: [4.0, 4.0, 4.0, 4.0],
+            mode: SliceMode::Stretch,
+        };
+        let mut cmd = vec![];
+        let mut uvs = vec![];
+        let count =
+            push_nine_slice(&mut cmd, &mut uvs, Rect::offset_extent((10.0, 20.0), (100.0, 50.0)), 0.5, WHITE, &slice);
+        assert_eq!(count, 9);
+        assert_eq!(uvs.len(), 9);
+        // Corners keep their size
+        assert_eq!(rect(&cmd[0]), (10.0, 20.0, 4.0, 4.0));
+        assert_eq!(rect(&cmd[8]), (106.0, 66.0, 4.0, 4.0));
+        // Center stretches
+        assert_eq!(rect(&cmd[4]), (14.0, 24.0, 92.0, 42.0));
+        assert_eq!(uvs[4], [0.25, 0.25, 0.75, 0.75]);
+    }
+
+    #[test]
+    fn nine_slice_tile_and_small_rects() {
+        let slice = NineSlice {
+            uv: [0.5, 0.0, 1.0, 0.5],
+            size: (16.0, 16.0),
+            insets: [4.0, 4.0, 4.0, 4.0],
+            mode: SliceMode::Tile,
+        };
+        let mut cmd = vec![];
+        let mut uvs = vec![];
+        // 20 pixels of horizontal edge/center: two full 8px tiles and a 4px remainder
+        let count =
+            push_nine_slice(&mut cmd, &mut uvs, Rect::offset_extent((0.0, 0.0), (28.0, 16.0)), 0.5, WHITE, &slice);
+        assert_eq!(count, 3 * (2 + 3));
+        let last_top = cmd.iter().zip(&uvs).filter(|(c, _)| rect(c).1 == 0.0).nth(3).unwrap();
+        assert_eq!(rect(last_top.0), (20.0, 0.0, 4.0, 4.0));
+        assert_eq!(*last_top.1, [0.625, 0.0, 0.75, 0.125]);
+
+        // Smaller than the borders: the corners shrink and the middle disappears
+        cmd.clear();
+        uvs.clear();
+        let count =
+            push_nine_slice(&mut cmd, &mut uvs, Rect::offset_extent((0.0, 0.0), (4.0, 4.0)), 0.5, WHITE, &slice);
+        assert_eq!(count, 4);
+        assert_eq!(rect(&cmd[3]), (2.0, 2.0, 2.0, 2.0));
+    }
+
+    #[test]
+    fn shader_interface_merges_stages() {
+        use crate::spirv::{BlockLayout, BlockMember, DescriptorBinding};
+
+        let binding = |binding, descriptor_type| DescriptorBinding {
+            set: 0,
+            binding,
+            descriptor_type,
+            count: 1,
+            name: String::new(),
+            block: None,
+        };
+        let push_constants = |offset, size| BlockLayout {
+            name: String::new(),
+            size: offset + size,
+            members: vec![BlockMember {
+                offset,
+                size,
+                ..Default::default()
+            }],
+        };
+        let vs = Reflection {
+            descriptor_bindings: vec![
+                binding(1, DescriptorType::StorageBuffer),
+                binding(0, DescriptorType::UniformBuffer),
+            ],
+            push_constants: Some(push_constants(0, 40)),
+            ..Default::default()
+        };
+        let mut fs = Reflection {
+            descriptor_bindings: vec![
+                binding(0, DescriptorType::UniformBuffer),
+                binding(2, DescriptorType::CombinedImageSampler),
+            ],
+            push_constants: Some(push_constants(40, 16)),
+            ..Default::default()
+        };
+        let stages = [(VK_SHADER_STAGE_VERTEX_BIT, &vs), (VK_SHADER_STAGE_FRAGMENT_BIT, &fs)];
+        let interface = ShaderInterface::from_stages(&stages).unwrap();
+
+        let bindings = interface.bindings.iter().map(|b| (b.binding, b.stages)).collect::<Vec<_>>();
+        let all = VK_SHADER_STAGE_VERTEX_BIT | VK_SHADER_STAGE_FRAGMENT_BIT;
+        assert_eq!(bindings, vec![(0, all), (1, VK_SHADER_STAGE_VERTEX_BIT), (2, VK_SHADER_STAGE_FRAGMENT_BIT)]);
+        assert_eq!(
+            interface.push_constants,
+            Some(ShaderPushConstants {
+                stages: all,
+                offset: 0,
+                size: 56,
+            })
+        );
+
+        fs.descriptor_bindings[0].descriptor_type = DescriptorType::StorageBuffer;
+        let stages = [(VK_SHADER_STAGE_VERTEX_BIT, &vs), (VK_SHADER_STAGE_FRAGMENT_BIT, &fs)];
+        assert!(ShaderInterface::from_stages(&stages).is_err());
+    }
+}